@@ -0,0 +1,209 @@
+use fluxcd_meta::{Duration, LastFailure, LocalObjectReference, ReconcileRequestStatus};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use kube::CustomResource;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Serialize};
+
+/// ImageRepository scans the repository of an image in an OCI registry for its tags, which the
+/// ImagePolicies referencing it select the latest of.
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[kube(
+  group = "image.fluxcd.yolodev.io",
+  version = "v1beta1",
+  kind = "ImageRepository",
+  status = "ImageRepositoryStatus",
+  namespaced
+)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ImageRepositorySpec {
+  /// Image is the name of the image, without a tag, like `ghcr.io/org/app`, or `nginx` for an
+  /// image of Docker Hub.
+  pub image: String,
+
+  /// The interval at which to scan the registry for tags.
+  pub interval: Duration,
+
+  /// The timeout for scanning the registry, defaults to 60s and never exceeds the interval.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub timeout: Option<Duration>,
+
+  /// SecretRef specifies an image pull Secret, of type `kubernetes.io/dockerconfigjson`, with the
+  /// credentials of the registry. Without it, the registry is scanned anonymously.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub secret_ref: Option<LocalObjectReference>,
+
+  /// ExclusionList holds regular expressions of tags which are not recorded, like `^.*\.sig$`.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  pub exclusion_list: Vec<String>,
+
+  /// Insecure allows scanning the registry over plain HTTP.
+  #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+  pub insecure: bool,
+
+  /// Suspend tells the controller to suspend scanning this repository.
+  #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+  pub suspend: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageRepositoryStatus {
+  #[serde(flatten)]
+  pub reconcile_request_status: ReconcileRequestStatus,
+
+  /// Conditions holds the conditions for the repository.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  #[schemars(schema_with = "conditions_schema")]
+  pub conditions: Vec<Condition>,
+
+  /// CanonicalImageName is the name of the image with its registry, like
+  /// `docker.io/library/nginx`.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub canonical_image_name: Option<String>,
+
+  /// LastScanResult describes the last successful scan of the registry.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_scan_result: Option<ScanResult>,
+
+  /// LastFailure describes the most recent failed reconciliation.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_failure: Option<LastFailure>,
+
+  /// NextReconcileAt is the time the next scheduled reconciliation is expected to run.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub next_reconcile_at: Option<Time>,
+}
+
+/// ScanResult describes a scan of the registry of an ImageRepository.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanResult {
+  /// TagCount is the number of tags recorded.
+  pub tag_count: u32,
+
+  /// ScanTime is the time the registry was scanned.
+  pub scan_time: Time,
+
+  /// LatestTags are the last tags in alphabetical order, at most 10.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  pub latest_tags: Vec<String>,
+}
+
+/// ImagePolicy selects the latest of the tags of an ImageRepository.
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[kube(
+  group = "image.fluxcd.yolodev.io",
+  version = "v1beta1",
+  kind = "ImagePolicy",
+  status = "ImagePolicyStatus",
+  namespaced
+)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ImagePolicySpec {
+  /// ImageRepositoryRef references the ImageRepository whose tags are selected from, in the
+  /// namespace of the ImagePolicy.
+  pub image_repository_ref: LocalObjectReference,
+
+  /// Policy selects the latest tag, with exactly one of `semver`, `alphabetical` and `numerical`.
+  pub policy: ImagePolicyChoice,
+
+  /// FilterTags only keeps the tags matching a pattern, and compares them on a value extracted
+  /// from them.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub filter_tags: Option<TagFilter>,
+
+  /// Suspend tells the controller to suspend selecting the latest tag.
+  #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+  pub suspend: bool,
+}
+
+/// ImagePolicyChoice is the policy selecting the latest tag, of which exactly one is set.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ImagePolicyChoice {
+  /// SemVer selects the greatest version in a range, ignoring tags which are not versions.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub semver: Option<SemVerPolicy>,
+
+  /// Alphabetical selects the last tag in alphabetical order.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub alphabetical: Option<OrderPolicy>,
+
+  /// Numerical selects the last tag in numerical order, ignoring tags which are not numbers.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub numerical: Option<OrderPolicy>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SemVerPolicy {
+  /// Range of the versions selected from, like `>=1.0.0, <2.0.0`.
+  pub range: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct OrderPolicy {
+  /// Order of the tags, defaults to `asc`, which selects the greatest tag.
+  #[serde(default)]
+  pub order: SortOrder,
+}
+
+/// SortOrder is the order of the tags of an ImagePolicy, the latest tag being the last one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+  /// The greatest tag is the latest.
+  #[default]
+  Asc,
+
+  /// The least tag is the latest.
+  Desc,
+}
+
+/// TagFilter only keeps the tags of an ImagePolicy matching a pattern.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TagFilter {
+  /// Pattern is a regular expression the tags have to match, like
+  /// `^main-[a-f0-9]+-(?P<ts>[0-9]+)$`.
+  pub pattern: String,
+
+  /// Extract is the value the tags are compared on, with the groups of the pattern, like `$ts`.
+  /// Defaults to the whole tag.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub extract: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ImagePolicyStatus {
+  #[serde(flatten)]
+  pub reconcile_request_status: ReconcileRequestStatus,
+
+  /// Conditions holds the conditions for the policy.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  #[schemars(schema_with = "conditions_schema")]
+  pub conditions: Vec<Condition>,
+
+  /// LatestImage is the image with the latest tag, like `ghcr.io/org/app:1.2.3`.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub latest_image: Option<String>,
+
+  /// ObservedPreviousImage is the latest image before the current one, if it changed.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub observed_previous_image: Option<String>,
+
+  /// LastFailure describes the most recent failed reconciliation.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_failure: Option<LastFailure>,
+
+  /// NextReconcileAt is the time the next scheduled reconciliation is expected to run.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub next_reconcile_at: Option<Time>,
+}
+
+/// The conditions only have the generic reasons of [fluxcd_meta::Reason].
+fn conditions_schema(gen: &mut SchemaGenerator) -> Schema {
+  fluxcd_meta::conditions_schema(gen, &[])
+}
@@ -0,0 +1,178 @@
+use fluxcd_meta::{Duration, LastFailure, LocalObjectReference, ReconcileRequestStatus};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use kube::CustomResource;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Serialize};
+
+/// Provider is a chat system, or any webhook, which the events of the Alerts referencing it are
+/// posted to.
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[kube(
+  group = "notification.fluxcd.yolodev.io",
+  version = "v1beta1",
+  kind = "Provider",
+  status = "ProviderStatus",
+  namespaced
+)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ProviderSpec {
+  /// Type of the provider, which determines the format of the notifications.
+  #[serde(rename = "type")]
+  pub type_: ProviderType,
+
+  /// Address of the webhook the notifications are posted to. Webhook URLs usually embed a
+  /// secret, so they are better given in the `address` of the Secret referenced by `secretRef`.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub address: Option<String>,
+
+  /// Channel the notifications are posted to, rather than the default channel of the webhook,
+  /// for the `slack` type.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub channel: Option<String>,
+
+  /// Username the notifications are posted as, rather than the default name of the webhook, for
+  /// the `slack` and `discord` types.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub username: Option<String>,
+
+  /// SecretRef specifies the Secret containing the `address` of the webhook, which takes
+  /// precedence over the `address` of the spec.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub secret_ref: Option<LocalObjectReference>,
+
+  /// The timeout for posting a notification, defaults to 15s.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub timeout: Option<Duration>,
+
+  /// Suspend tells the controller to stop posting notifications to this provider.
+  #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+  pub suspend: bool,
+}
+
+/// ProviderType is the kind of service the notifications of a Provider are posted to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderType {
+  /// A Slack incoming webhook.
+  Slack,
+
+  /// A Discord webhook.
+  Discord,
+
+  /// A Microsoft Teams incoming webhook.
+  #[serde(rename = "msteams")]
+  MsTeams,
+
+  /// Any webhook, which the events are posted to as json.
+  Generic,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderStatus {
+  #[serde(flatten)]
+  pub reconcile_request_status: ReconcileRequestStatus,
+
+  /// Conditions holds the conditions for the provider.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  #[schemars(schema_with = "conditions_schema")]
+  pub conditions: Vec<Condition>,
+
+  /// LastFailure describes the most recent failed reconciliation.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_failure: Option<LastFailure>,
+
+  /// NextReconcileAt is the time the next scheduled reconciliation is expected to run.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub next_reconcile_at: Option<Time>,
+}
+
+/// Alert routes the events of objects to a Provider, filtered by their severity and the objects
+/// they are about.
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[kube(
+  group = "notification.fluxcd.yolodev.io",
+  version = "v1beta1",
+  kind = "Alert",
+  status = "AlertStatus",
+  namespaced
+)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AlertSpec {
+  /// ProviderRef references the Provider the events are posted to, in the namespace of the
+  /// Alert.
+  pub provider_ref: LocalObjectReference,
+
+  /// EventSeverity is the lowest severity of the events posted, defaults to `info`, which posts
+  /// every event.
+  #[serde(default)]
+  pub event_severity: EventSeverity,
+
+  /// EventSources are the objects whose events are posted. Only events of objects in the
+  /// namespace of the Alert are posted, unless a source names another namespace.
+  pub event_sources: Vec<EventSource>,
+
+  /// Summary is added to every notification, like the name of the cluster.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub summary: Option<String>,
+
+  /// Suspend tells the controller to stop posting the events of this alert.
+  #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+  pub suspend: bool,
+}
+
+/// EventSeverity is the lowest severity of the events an Alert posts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EventSeverity {
+  /// Every event.
+  #[default]
+  Info,
+
+  /// Only the events of failures.
+  Error,
+}
+
+/// EventSource selects the objects whose events an Alert posts.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct EventSource {
+  /// Kind of the objects, like `Bucket`.
+  pub kind: String,
+
+  /// Name of the object, or `*` for every object of the kind.
+  pub name: String,
+
+  /// Namespace of the objects, defaults to the namespace of the Alert.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub namespace: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertStatus {
+  #[serde(flatten)]
+  pub reconcile_request_status: ReconcileRequestStatus,
+
+  /// Conditions holds the conditions for the alert.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  #[schemars(schema_with = "conditions_schema")]
+  pub conditions: Vec<Condition>,
+
+  /// LastPostTime is the time an event of the alert was last posted.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_post_time: Option<Time>,
+
+  /// LastFailure describes the most recent failed reconciliation.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_failure: Option<LastFailure>,
+
+  /// NextReconcileAt is the time the next scheduled reconciliation is expected to run.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub next_reconcile_at: Option<Time>,
+}
+
+/// The conditions only have the generic reasons of [fluxcd_meta::Reason].
+fn conditions_schema(gen: &mut SchemaGenerator) -> Schema {
+  fluxcd_meta::conditions_schema(gen, &[])
+}
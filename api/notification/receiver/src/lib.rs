@@ -0,0 +1,110 @@
+use fluxcd_meta::{LastFailure, LocalObjectReference, ReconcileRequestStatus};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use kube::CustomResource;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Serialize};
+
+/// Receiver requests the reconciliation of resources when a webhook is received, like a push
+/// to a GitHub repository, by setting their `reconcile.fluxcd.io/requestedAt` annotation.
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[kube(
+  group = "notification.fluxcd.yolodev.io",
+  version = "v1beta1",
+  kind = "Receiver",
+  status = "ReceiverStatus",
+  namespaced
+)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ReceiverSpec {
+  /// Type of the service sending the webhooks, which determines how they are authenticated.
+  #[serde(rename = "type")]
+  pub type_: ReceiverType,
+
+  /// Events limits the webhooks acted upon to those of these events, like `push` for GitHub or
+  /// `Push Hook` for GitLab. Webhooks of every event are acted upon when it is empty. Only used
+  /// by the `github` and `gitlab` types, whose webhooks have an event header.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  pub events: Vec<String>,
+
+  /// Resources whose reconciliation is requested, in the namespace of the Receiver.
+  pub resources: Vec<ReceiverResource>,
+
+  /// SecretRef specifies the Secret containing the `token` the webhooks are authenticated with.
+  /// The path of the webhook is derived from it, so it changes along with the token.
+  pub secret_ref: LocalObjectReference,
+
+  /// Suspend tells the controller to ignore the webhooks of this receiver.
+  #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+  pub suspend: bool,
+}
+
+/// ReceiverType is the kind of service the webhooks of a Receiver are sent by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReceiverType {
+  /// Any service. Webhooks are only authenticated by their path.
+  Generic,
+
+  /// Any service signing the payload with the token, in the `X-Signature` header as
+  /// `<sha1|sha256|sha512>=<hex digest>`.
+  GenericHmac,
+
+  /// GitHub, with the token as the secret of the webhook.
+  #[serde(rename = "github")]
+  GitHub,
+
+  /// GitLab, with the token as the secret token of the webhook.
+  #[serde(rename = "gitlab")]
+  GitLab,
+
+  /// Harbor, with the token as the auth header of the webhook.
+  Harbor,
+}
+
+/// ReceiverResource references a resource whose reconciliation is requested by a Receiver.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ReceiverResource {
+  /// API version of the resource, like `source.fluxcd.yolodev.io/v1beta1`.
+  pub api_version: String,
+
+  /// Kind of the resource, like `HttpEndpoint`.
+  pub kind: String,
+
+  /// Name of the resource.
+  pub name: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiverStatus {
+  #[serde(flatten)]
+  pub reconcile_request_status: ReconcileRequestStatus,
+
+  /// Conditions holds the conditions for the receiver.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  #[schemars(schema_with = "conditions_schema")]
+  pub conditions: Vec<Condition>,
+
+  /// WebhookPath is the path the webhooks of the receiver are to be sent to, on the receiver
+  /// server of the controller.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub webhook_path: Option<String>,
+
+  /// LastWebhookTime is the time a webhook was last acted upon.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_webhook_time: Option<Time>,
+
+  /// LastFailure describes the most recent failed reconciliation.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_failure: Option<LastFailure>,
+
+  /// NextReconcileAt is the time the next scheduled reconciliation is expected to run.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub next_reconcile_at: Option<Time>,
+}
+
+/// The conditions only have the generic reasons of [fluxcd_meta::Reason].
+fn conditions_schema(gen: &mut SchemaGenerator) -> Schema {
+  fluxcd_meta::conditions_schema(gen, &[])
+}
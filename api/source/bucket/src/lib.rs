@@ -0,0 +1,123 @@
+use fluxcd_meta::{Artifact, Duration, LastFailure, LocalObjectReference, ReconcileRequestStatus};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use kube::CustomResource;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Serialize};
+
+/// Bucket publishes the objects of a bucket of an object storage, like AWS S3, Google Cloud
+/// Storage, Azure Blob Storage or MinIO, as a tarball artifact.
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[kube(
+  group = "source.fluxcd.yolodev.io",
+  version = "v1beta1",
+  kind = "Bucket",
+  status = "BucketStatus",
+  namespaced
+)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BucketSpec {
+  /// Provider of the object storage, defaults to `generic`, which is any S3 compatible storage.
+  #[serde(default)]
+  pub provider: BucketProvider,
+
+  /// Name of the bucket, or of the container with the `azure` provider.
+  pub bucket_name: String,
+
+  /// Endpoint of the object storage, either a host name like `s3.amazonaws.com` or a URL. With
+  /// the `azure` provider, the endpoint of the storage account, like
+  /// `https://<account>.blob.core.windows.net`.
+  pub endpoint: String,
+
+  /// Insecure allows connecting to the endpoint over plain HTTP.
+  #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+  pub insecure: bool,
+
+  /// Region of the bucket, for the `generic` and `aws` providers. Defaults to `us-east-1`.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub region: Option<String>,
+
+  /// Prefix limits the objects published to those whose key starts with it.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub prefix: Option<String>,
+
+  /// SecretRef specifies the Secret containing the credentials of the object storage: an
+  /// `accesskey` and `secretkey` for S3 compatible storages, a `serviceaccount` JSON key for
+  /// Google Cloud Storage, and either an `accountKey` or a `sasKey` for Azure Blob Storage.
+  /// Without it, the bucket is read anonymously, or with the `gcp` provider as the service
+  /// account of the controller.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub secret_ref: Option<LocalObjectReference>,
+
+  /// The interval at which to check the bucket for updates.
+  pub interval: Duration,
+
+  /// The timeout for fetching the objects, defaults to 60s and never exceeds the interval.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub timeout: Option<Duration>,
+
+  /// Ignore overrides the set of excluded patterns in the `.sourceignore` format (which is the
+  /// same as `.gitignore`). The patterns apply in addition to those of a `.sourceignore` file at
+  /// the root of the bucket.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub ignore: Option<String>,
+
+  /// Suspend tells the controller to suspend the reconciliation of this source.
+  #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+  pub suspend: bool,
+}
+
+/// BucketProvider is the kind of object storage a Bucket is read from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BucketProvider {
+  /// Any S3 compatible storage, like MinIO.
+  #[default]
+  Generic,
+
+  /// AWS S3.
+  Aws,
+
+  /// Google Cloud Storage.
+  Gcp,
+
+  /// Azure Blob Storage.
+  Azure,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketStatus {
+  #[serde(flatten)]
+  pub reconcile_request_status: ReconcileRequestStatus,
+
+  /// Conditions holds the conditions for the source.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  #[schemars(schema_with = "conditions_schema")]
+  pub conditions: Vec<Condition>,
+
+  /// Artifact is the tarball of the objects of the bucket, from the last successful
+  /// reconciliation. Its revision is the checksum of the keys and entity tags of the objects.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub artifact: Option<Artifact>,
+
+  /// ObjectCount is the number of objects in the artifact.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub object_count: Option<u32>,
+
+  /// LastFetchTime is the time the bucket was last fetched successfully.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_fetch_time: Option<Time>,
+
+  /// LastFailure describes the most recent failed reconciliation.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_failure: Option<LastFailure>,
+
+  /// NextReconcileAt is the time the next scheduled reconciliation is expected to run.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub next_reconcile_at: Option<Time>,
+}
+
+/// The conditions only have the generic reasons of [fluxcd_meta::Reason].
+fn conditions_schema(gen: &mut SchemaGenerator) -> Schema {
+  fluxcd_meta::conditions_schema(gen, &[])
+}
@@ -0,0 +1,128 @@
+use fluxcd_meta::{Duration, LastFailure, ReconcileRequestStatus, StalePolicy, Verification};
+use fluxcd_utils_macros::str_enum;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use kube::CustomResource;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Serialize};
+
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[kube(
+  group = "source.fluxcd.yolodev.io",
+  version = "v1beta1",
+  kind = "DnsRecords",
+  status = "DnsRecordsStatus",
+  namespaced
+)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DnsRecordsSpec {
+  /// The fully qualified domain name to resolve.
+  pub name: String,
+
+  /// The type of records to resolve.
+  pub record_type: DnsRecordType,
+
+  /// The interval at which to resolve the records.
+  pub interval: Duration,
+
+  /// The timeout for resolving records, defaults to 60s and never exceeds the interval.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub timeout: Option<Duration>,
+
+  /// Suspend tells the controller to suspend the reconciliation of this source.
+  #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+  pub suspend: bool,
+
+  /// Nameservers to query, as `ip` or `ip:port`. Defaults to the nameservers configured for the
+  /// controller's pod.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  pub nameservers: Vec<String>,
+
+  /// Require the records to be validated using DNSSEC.
+  #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+  pub dnssec: bool,
+
+  /// MaxAge is how long the content may go without being refreshed, e.g. because the upstream is unavailable, before
+  /// it is considered stale.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub max_age: Option<Duration>,
+
+  /// StalePolicy defines what happens to the content once it is stale, defaults to `Keep`.
+  #[serde(default)]
+  pub stale_policy: StalePolicy,
+
+  /// Target is the ConfigMap the resolved records are written to, one record per line.
+  pub target: DnsRecordsTarget,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum DnsRecordType {
+  /// TXT records, written as the concatenation of their character strings.
+  Txt,
+
+  /// SSHFP records, written as `<algorithm> <fingerprint type> <hex fingerprint>`.
+  Sshfp,
+}
+
+/// DnsRecordsTarget is the ConfigMap and key the resolved records are written to.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DnsRecordsTarget {
+  /// Name of the ConfigMap, created in the namespace of the DnsRecords.
+  pub name: String,
+
+  /// The key the records are written to.
+  pub key: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsRecordsStatus {
+  #[serde(flatten)]
+  pub reconcile_request_status: ReconcileRequestStatus,
+
+  /// Conditions holds the conditions for the source.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  #[schemars(schema_with = "conditions_schema")]
+  pub conditions: Vec<Condition>,
+
+  /// Checksum of the last resolved records, in the form `sha256:<hex>`.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub checksum: Option<String>,
+
+  /// Verification describes the content last resolved, and how it was verified.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub verification: Option<Verification>,
+
+  /// LastFetchTime is the time the records were last resolved successfully.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_fetch_time: Option<Time>,
+
+  /// LastFailure describes the most recent failed reconciliation.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_failure: Option<LastFailure>,
+
+  /// OutputHash is the hash of the output last applied to the target, used to skip applying
+  /// unchanged output.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub output_hash: Option<String>,
+
+  /// NextReconcileAt is the time the next scheduled reconciliation is expected to run.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub next_reconcile_at: Option<Time>,
+}
+
+str_enum! {
+  /// The reasons specific to DnsRecords of its conditions, in addition to the generic ones of [fluxcd_meta::Reason].
+  #[non_exhaustive]
+  #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+  pub enum DnsRecordsReason {
+    /// MaxAgeExceededReason is the reason of the ContentStale condition, when the records could not be refreshed for
+    /// longer than the max age.
+    MaxAgeExceeded = "MaxAgeExceeded",
+  }
+}
+
+fn conditions_schema(gen: &mut SchemaGenerator) -> Schema {
+  fluxcd_meta::conditions_schema(gen, DnsRecordsReason::VALUES)
+}
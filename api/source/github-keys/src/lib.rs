@@ -1,9 +1,14 @@
 use fluxcd_acl::AccessFrom;
-use fluxcd_meta::{Duration, ReconcileRequestStatus};
+use fluxcd_meta::{
+  Artifact, Duration, FailedItems, LastFailure, LocalObjectReference, NamespacedObjectReference,
+  OciPushTarget, OciPushedArtifact, ReconcileRequestStatus, Verification,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, LabelSelector, Time};
 use kube::CustomResource;
-use schemars::JsonSchema;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
 use serde::{Deserialize, Serialize};
 
+/// GitHubUserSshKeys writes the public SSH keys of a GitHub user to a Secret of the same name.
 #[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
 #[kube(
   group = "source.fluxcd.yolodev.io",
@@ -14,31 +19,282 @@ use serde::{Deserialize, Serialize};
 )]
 pub struct GitHubUserSshKeysSpec {
   /// GitHub user name.
-  user: String,
+  pub user: String,
 
   /// The interval at which to check for repository updates.
-  interval: Duration,
+  pub interval: Duration,
 
-  /// The timeout for fetching values, defaults to 60s.
+  /// The timeout for fetching values, defaults to 60s and never exceeds the interval.
   #[serde(skip_serializing_if = "Option::is_none", default)]
-  timeout: Option<Duration>,
+  pub timeout: Option<Duration>,
 
   /// Suspend tells the controller to suspend the reconciliation of this source.
   /// This flag tells the controller to suspend the reconciliation of this source.
   #[serde(skip_serializing_if = "std::ops::Not::not", default = "const_false")]
-  suspend: bool,
+  pub suspend: bool,
 
   #[serde(skip_serializing_if = "Option::is_none", default)]
-  access_from: Option<AccessFrom>,
+  pub access_from: Option<AccessFrom>,
+
+  /// SecretRef specifies the Secret containing the credentials used to authenticate to GitHub.
+  /// Either a personal access `token`, or the `githubAppID`, `githubAppInstallationID` and
+  /// `githubAppPrivateKey` of a GitHub App installation, which gets much higher rate limits.
+  #[serde(rename = "secretRef", skip_serializing_if = "Option::is_none", default)]
+  pub secret_ref: Option<LocalObjectReference>,
+
+  /// Projection defines how the keys are written to the Secret, defaults to `Combined`.
+  #[serde(default)]
+  pub projection: SecretProjection,
+
+  /// Filter selects which of the keys are written, e.g. to leave out legacy algorithms or short
+  /// RSA keys. Every key is written by default.
+  #[serde(rename = "filter", skip_serializing_if = "Option::is_none", default)]
+  pub filter: Option<KeyFilter>,
+
+  /// PushTo publishes the `authorized_keys` file of the keys to an OCI repository, for other
+  /// clusters to consume. Experimental, requires a controller built with the `oci-push` feature.
+  #[serde(rename = "pushTo", skip_serializing_if = "Option::is_none", default)]
+  pub push_to: Option<OciPushTarget>,
+}
+
+/// ClusterGitHubUserSshKeys is the cluster-scoped counterpart of GitHubUserSshKeys, which writes the keys to a Secret
+/// in every namespace matched by its target.
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[kube(
+  group = "source.fluxcd.yolodev.io",
+  version = "v1beta1",
+  kind = "ClusterGitHubUserSshKeys",
+  status = "GitHubUserSshKeysStatus"
+)]
+pub struct ClusterGitHubUserSshKeysSpec {
+  /// GitHub user name.
+  pub user: String,
+
+  /// The interval at which to check for repository updates.
+  pub interval: Duration,
+
+  /// The timeout for fetching values, defaults to 60s and never exceeds the interval.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub timeout: Option<Duration>,
+
+  /// This flag tells the controller to suspend the reconciliation of this source.
+  #[serde(skip_serializing_if = "std::ops::Not::not", default = "const_false")]
+  pub suspend: bool,
+
+  /// SecretRef specifies the Secret containing the credentials used to authenticate to GitHub, see
+  /// GitHubUserSshKeys. As the resource is cluster-scoped, the namespace of the Secret is required.
+  #[serde(rename = "secretRef", skip_serializing_if = "Option::is_none", default)]
+  pub secret_ref: Option<NamespacedObjectReference>,
+
+  /// Projection defines how the keys are written to the Secrets, defaults to `Combined`.
+  #[serde(default)]
+  pub projection: SecretProjection,
+
+  /// Filter selects which of the keys are written, e.g. to leave out legacy algorithms or short
+  /// RSA keys. Every key is written by default.
+  #[serde(rename = "filter", skip_serializing_if = "Option::is_none", default)]
+  pub filter: Option<KeyFilter>,
+
+  /// Target defines the Secrets the keys are written to.
+  pub target: ClusterSecretTarget,
+
+  /// PushTo publishes the `authorized_keys` file of the keys to an OCI repository, see
+  /// GitHubUserSshKeys. As the resource is cluster-scoped, the namespace of its Secret is required.
+  #[serde(rename = "pushTo", skip_serializing_if = "Option::is_none", default)]
+  pub push_to: Option<OciPushTarget>,
 }
 
+/// GitHubTeamSshKeys writes the public SSH keys of all members of a GitHub team to a Secret of
+/// the same name, like the `authorized_keys` of a bastion host shared by the team.
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[kube(
+  group = "source.fluxcd.yolodev.io",
+  version = "v1beta1",
+  kind = "GitHubTeamSshKeys",
+  status = "GitHubTeamSshKeysStatus",
+  namespaced
+)]
+pub struct GitHubTeamSshKeysSpec {
+  /// GitHub organization the team belongs to.
+  pub org: String,
+
+  /// Slug of the team, as in its URL. Members of child teams are members of the team as well.
+  pub team: String,
+
+  /// The interval at which to check for membership and key updates.
+  pub interval: Duration,
+
+  /// The timeout for fetching values, defaults to 60s and never exceeds the interval.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub timeout: Option<Duration>,
+
+  /// This flag tells the controller to suspend the reconciliation of this source.
+  #[serde(skip_serializing_if = "std::ops::Not::not", default = "const_false")]
+  pub suspend: bool,
+
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub access_from: Option<AccessFrom>,
+
+  /// SecretRef specifies the Secret containing the credentials used to authenticate to GitHub, see
+  /// GitHubUserSshKeys. Listing the members of a team requires credentials which can read the
+  /// organization.
+  #[serde(rename = "secretRef")]
+  pub secret_ref: LocalObjectReference,
+
+  /// Projection defines how the keys are written to the Secret, defaults to `Combined`.
+  #[serde(default)]
+  pub projection: SecretProjection,
+
+  /// Filter selects which of the keys are written, e.g. to leave out legacy algorithms or short
+  /// RSA keys. Every key is written by default.
+  #[serde(rename = "filter", skip_serializing_if = "Option::is_none", default)]
+  pub filter: Option<KeyFilter>,
+}
+
+/// ClusterSecretTarget defines a Secret written to multiple namespaces.
 #[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+pub struct ClusterSecretTarget {
+  /// Name of the Secret in every matched namespace.
+  pub name: String,
+
+  /// NamespaceSelector selects the namespaces the Secret is written to. An empty selector matches
+  /// every namespace.
+  #[serde(rename = "namespaceSelector", default)]
+  pub namespace_selector: LabelSelector,
+}
+
+/// KeyFilter selects which SSH keys are written.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+pub struct KeyFilter {
+  /// Algorithms lists the algorithms of the keys which are written, by their name in
+  /// `authorized_keys` files (e.g. `ssh-ed25519`). Keys of every algorithm are written if empty.
+  #[serde(rename = "algorithms", skip_serializing_if = "Vec::is_empty", default)]
+  pub algorithms: Vec<String>,
+
+  /// MinRsaBits is the minimum size of the RSA keys which are written, in bits.
+  #[serde(
+    rename = "minRsaBits",
+    skip_serializing_if = "Option::is_none",
+    default
+  )]
+  pub min_rsa_bits: Option<u32>,
+}
+
+/// SecretProjection defines how SSH keys are written to a Secret.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub enum SecretProjection {
+  /// All keys in a single `authorized_keys` entry.
+  #[default]
+  Combined,
+
+  /// Every key in its own entry, keyed by its SHA256 fingerprint in the URL-safe base64 alphabet
+  /// (e.g. `SHA256-<fingerprint>`).
+  PerKey,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct GitHubUserSshKeysStatus {
   #[serde(flatten)]
-  reconcile_request_status: ReconcileRequestStatus,
+  pub reconcile_request_status: ReconcileRequestStatus,
+
+  /// Conditions holds the conditions for the source.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  #[schemars(schema_with = "conditions_schema")]
+  pub conditions: Vec<Condition>,
+
+  /// LastFetchTime is the time the keys were last fetched successfully.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_fetch_time: Option<Time>,
+
+  /// KeyCount is the number of keys written by the last successful reconciliation.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub key_count: Option<u32>,
+
+  /// Verification describes the content last resolved, and how it was verified.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub verification: Option<Verification>,
+
+  /// LastFailure describes the most recent failed reconciliation.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_failure: Option<LastFailure>,
+
+  /// Artifact is the `authorized_keys` file of the keys, when the controller serves artifacts.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub artifact: Option<Artifact>,
+
+  /// PushedArtifact describes the `authorized_keys` artifact last pushed to the OCI repository of
+  /// `pushTo`.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub pushed_artifact: Option<OciPushedArtifact>,
+
+  /// OutputHash is the hash of the output last applied to the Secrets, used to skip applying
+  /// unchanged output.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub output_hash: Option<String>,
+
+  /// NextReconcileAt is the time the next scheduled reconciliation is expected to run.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub next_reconcile_at: Option<Time>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GitHubTeamSshKeysStatus {
+  #[serde(flatten)]
+  pub reconcile_request_status: ReconcileRequestStatus,
+
+  /// Conditions holds the conditions for the source.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  #[schemars(schema_with = "conditions_schema")]
+  pub conditions: Vec<Condition>,
+
+  /// LastFetchTime is the time the keys were last fetched successfully.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_fetch_time: Option<Time>,
+
+  /// MemberCount is the number of members of the team in the last successful reconciliation.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub member_count: Option<u32>,
+
+  /// KeyCount is the number of keys written by the last successful reconciliation, once keys
+  /// shared by members are deduplicated.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub key_count: Option<u32>,
+
+  /// FailedMembers lists the members whose keys could not be fetched in the last reconciliation,
+  /// and which are left out of the Secret.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub failed_members: Option<FailedItems>,
+
+  /// Verification describes the content last resolved, and how it was verified.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub verification: Option<Verification>,
+
+  /// LastFailure describes the most recent failed reconciliation.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_failure: Option<LastFailure>,
+
+  /// Artifact is the `authorized_keys` file of the keys, when the controller serves artifacts.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub artifact: Option<Artifact>,
+
+  /// OutputHash is the hash of the output last applied to the Secret, used to skip applying
+  /// unchanged output.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub output_hash: Option<String>,
+
+  /// NextReconcileAt is the time the next scheduled reconciliation is expected to run.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub next_reconcile_at: Option<Time>,
 }
 
 #[inline]
 const fn const_false() -> bool {
   false
 }
+
+/// The conditions only have the generic reasons of [fluxcd_meta::Reason].
+fn conditions_schema(gen: &mut SchemaGenerator) -> Schema {
+  fluxcd_meta::conditions_schema(gen, &[])
+}
@@ -0,0 +1,155 @@
+use fluxcd_meta::{
+  Duration, LastFailure, LocalObjectReference, ReconcileRequestStatus, StalePolicy, Verification,
+};
+use fluxcd_utils_macros::str_enum;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use kube::CustomResource;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Serialize};
+
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[kube(
+  group = "source.fluxcd.yolodev.io",
+  version = "v1beta1",
+  kind = "HttpEndpoint",
+  status = "HttpEndpointStatus",
+  namespaced
+)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct HttpEndpointSpec {
+  /// The URL to fetch, only `https` URLs are allowed.
+  pub url: String,
+
+  /// The interval at which to fetch the URL.
+  pub interval: Duration,
+
+  /// The timeout for fetching values, defaults to 60s and never exceeds the interval.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub timeout: Option<Duration>,
+
+  /// Suspend tells the controller to suspend the reconciliation of this source.
+  #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+  pub suspend: bool,
+
+  /// SecretRef specifies the Secret containing authentication credentials for the endpoint.
+  /// Either a `username` and `password` for basic access authentication, or a `token` for bearer
+  /// token authentication. Endpoints requiring mutual TLS are authenticated with the client
+  /// certificate in `tls.crt` and `tls.key`, in addition to or instead of these, trusting the
+  /// certificate authorities in `ca.crt` if present. Changes to the Secret are picked up on the
+  /// next fetch.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub secret_ref: Option<LocalObjectReference>,
+
+  /// Verify the fetched content before writing it to the target.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub verify: Option<HttpEndpointVerification>,
+
+  /// MaxAge is how long the content may go without being refreshed, e.g. because the upstream is unavailable, before
+  /// it is considered stale.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub max_age: Option<Duration>,
+
+  /// StalePolicy defines what happens to the content once it is stale, defaults to `Keep`.
+  #[serde(default)]
+  pub stale_policy: StalePolicy,
+
+  /// Target is the Secret or ConfigMap the fetched content is written to.
+  pub target: HttpEndpointTarget,
+}
+
+/// HttpEndpointVerification describes how fetched content is verified. When both a checksum and
+/// a signature are given, both have to match.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct HttpEndpointVerification {
+  /// The expected checksum of the content, in the form `sha256:<hex>`.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub checksum: Option<String>,
+
+  /// A detached ed25519 signature of the content.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub signature: Option<HttpEndpointSignature>,
+}
+
+/// HttpEndpointSignature describes a detached ed25519 signature of the fetched content.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct HttpEndpointSignature {
+  /// The URL of the base64 encoded signature.
+  pub url: String,
+
+  /// The base64 encoded ed25519 public key the signature is verified against.
+  pub public_key: String,
+}
+
+/// HttpEndpointTarget is the object and key the fetched content is written to.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct HttpEndpointTarget {
+  /// Kind of the target object.
+  pub kind: HttpEndpointTargetKind,
+
+  /// Name of the target object, created in the namespace of the HttpEndpoint.
+  pub name: String,
+
+  /// The key the content is written to.
+  pub key: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub enum HttpEndpointTargetKind {
+  Secret,
+  ConfigMap,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpEndpointStatus {
+  #[serde(flatten)]
+  pub reconcile_request_status: ReconcileRequestStatus,
+
+  /// Conditions holds the conditions for the source.
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  #[schemars(schema_with = "conditions_schema")]
+  pub conditions: Vec<Condition>,
+
+  /// Checksum of the last fetched content, in the form `sha256:<hex>`.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub checksum: Option<String>,
+
+  /// Verification describes the content last fetched, and how it was verified.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub verification: Option<Verification>,
+
+  /// LastFetchTime is the time the content was last fetched successfully.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_fetch_time: Option<Time>,
+
+  /// LastFailure describes the most recent failed reconciliation.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_failure: Option<LastFailure>,
+
+  /// OutputHash is the hash of the output last applied to the target, used to skip applying
+  /// unchanged output.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub output_hash: Option<String>,
+
+  /// NextReconcileAt is the time the next scheduled reconciliation is expected to run.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub next_reconcile_at: Option<Time>,
+}
+
+str_enum! {
+  /// The reasons specific to HttpEndpoint of its conditions, in addition to the generic ones of [fluxcd_meta::Reason].
+  #[non_exhaustive]
+  #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+  pub enum HttpEndpointReason {
+    /// MaxAgeExceededReason is the reason of the ContentStale condition, when the content could not be refreshed for
+    /// longer than the max age.
+    MaxAgeExceeded = "MaxAgeExceeded",
+  }
+}
+
+fn conditions_schema(gen: &mut SchemaGenerator) -> Schema {
+  fluxcd_meta::conditions_schema(gen, HttpEndpointReason::VALUES)
+}
@@ -0,0 +1,434 @@
+use async_trait::async_trait;
+use eyre::{eyre, Result, WrapErr};
+use fluxcd_api_image_reflector::{
+  ImagePolicy, ImagePolicyStatus, ImageRepository, ImageRepositoryStatus, SortOrder,
+};
+use fluxcd_meta::{
+  mark_ready, mark_reconciling, mark_stalled, resolve_timeout, Reason, DEFAULT_TIMEOUT,
+  RECONCILE_REQUEST_ANNOTATION,
+};
+use fluxcd_registry::{
+  Credentials, Error as RegistryError, ImageName, Order, Registry, TagFilter, TagPolicy,
+};
+use fluxcd_utils_cap::{
+  context::ReconcileCtx,
+  flux_controller,
+  http::HttpConfig,
+  metrics,
+  predicate::Predicates,
+  rbac::{Permissions, READ},
+  secrets::{secret_reason, SecretCheck},
+  Controller, ControllerApp,
+};
+use k8s_openapi::{
+  apimachinery::pkg::apis::meta::v1::{Condition, Time},
+  chrono::Utc,
+};
+use kube::{
+  api::{Patch, PatchParams},
+  runtime::controller::ReconcilerAction,
+  Api, ResourceExt,
+};
+use regex::Regex;
+use serde_json::{json, Value};
+use std::{
+  collections::HashMap,
+  sync::{Arc, RwLock},
+  time::{Duration, SystemTime},
+};
+use thiserror::Error;
+
+const CRATE_NAME: &str = env!("CARGO_PKG_NAME");
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Key of the Secret entry holding the docker config of an image pull Secret.
+const DOCKER_CONFIG_KEY: &str = ".dockerconfigjson";
+
+/// Number of tags recorded in the status of a repository.
+const LATEST_TAGS: usize = 10;
+
+/// How often the latest tag of a policy is selected again, as scans of its repository do not
+/// trigger a reconcile.
+const POLICY_RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Error)]
+#[error("image repository '{0}' has not been scanned yet")]
+struct NotScanned(String);
+
+#[derive(Debug, Error)]
+#[error("invalid policy, expected exactly one of 'semver', 'alphabetical' and 'numerical'")]
+struct InvalidPolicy;
+
+#[derive(Debug, Error)]
+#[error("invalid exclusion: {0}")]
+struct InvalidExclusion(regex::Error);
+
+/// The tags of an image, as last scanned.
+struct Scan {
+  image: ImageName,
+  tags: Vec<String>,
+}
+
+/// The last scans of the repositories, by namespace and name, which the policies select from.
+#[derive(Clone, Default)]
+struct TagStore(Arc<RwLock<HashMap<(String, String), Arc<Scan>>>>);
+
+impl TagStore {
+  fn get(&self, namespace: &str, name: &str) -> Option<Arc<Scan>> {
+    let scans = self.0.read().expect("tag store poisoned");
+    scans
+      .get(&(namespace.to_string(), name.to_string()))
+      .cloned()
+  }
+
+  fn insert(&self, namespace: String, name: String, scan: Arc<Scan>) {
+    let mut scans = self.0.write().expect("tag store poisoned");
+    scans.insert((namespace, name), scan);
+  }
+}
+
+/// Whether `error` will keep failing until the resource changes, rather than being resolved by
+/// retrying.
+fn is_stalled(error: &eyre::Report) -> bool {
+  error.chain().any(|cause| {
+    cause.is::<InvalidPolicy>()
+      || cause.is::<InvalidExclusion>()
+      || matches!(
+        cause.downcast_ref::<RegistryError>(),
+        Some(
+          RegistryError::InvalidImage(_)
+            | RegistryError::TaggedImage(_)
+            | RegistryError::InvalidDockerConfig(_)
+            | RegistryError::UnsupportedChallenge(_)
+            | RegistryError::InvalidRange(_)
+            | RegistryError::InvalidFilter(_)
+        )
+      )
+  })
+}
+
+/// Marks the conditions of a failed reconcile: `Stalled` if retrying will not help, and
+/// otherwise `Reconciling` while it is retried.
+fn mark_failed(conditions: &mut Vec<Condition>, generation: Option<i64>, error: &eyre::Report) {
+  let message = format!("{error:#}");
+  if is_stalled(error) {
+    mark_stalled(conditions, generation, Reason::Failed, message);
+  } else {
+    // a missing secret is retried with backoff until it is created
+    let reason = secret_reason(error).unwrap_or(Reason::Progressing);
+    let message = format!("retrying after: {message}");
+    mark_reconciling(conditions, generation, reason, message);
+  }
+}
+
+/// The status recording the outcome of a scan: `Ready` with the latest tags once the registry is
+/// scanned.
+fn repository_status_patch(
+  status: Option<&ImageRepositoryStatus>,
+  generation: Option<i64>,
+  result: &Result<Arc<Scan>>,
+) -> Value {
+  let mut conditions = status.map(|s| s.conditions.clone()).unwrap_or_default();
+  let scan = match result {
+    Ok(scan) => scan,
+    Err(error) => {
+      mark_failed(&mut conditions, generation, error);
+      return json!({
+        "status": {
+          "conditions": conditions,
+        }
+      });
+    }
+  };
+
+  let message = format!("scanned {} tag(s) of '{}'", scan.tags.len(), scan.image);
+  mark_ready(&mut conditions, generation, Reason::Succeeded, message);
+  let latest_tags: Vec<_> = scan.tags.iter().rev().take(LATEST_TAGS).collect();
+
+  json!({
+    "status": {
+      "conditions": conditions,
+      "canonicalImageName": scan.image.to_string(),
+      "lastScanResult": {
+        "tagCount": scan.tags.len(),
+        "scanTime": Time(Utc::now()),
+        "latestTags": latest_tags,
+      },
+    }
+  })
+}
+
+struct ImageRepositoryController {
+  metrics: metrics::Recorder,
+  http: reqwest::Client,
+  tags: TagStore,
+}
+
+impl ImageRepositoryController {
+  fn new(tags: TagStore) -> Result<Self> {
+    let metrics = metrics::Recorder::new()?;
+    // not https only, as registries may be served over plain http with `insecure`
+    let builder = reqwest::Client::builder().user_agent(USER_AGENT);
+    let http = HttpConfig::from_env()?.apply(builder).build()?;
+
+    Ok(Self {
+      metrics,
+      http,
+      tags,
+    })
+  }
+
+  /// The credentials of the registry of `image`, from the image pull Secret of `resource`.
+  async fn credentials(
+    &self,
+    ctx: &ReconcileCtx,
+    resource: &ImageRepository,
+    image: &ImageName,
+  ) -> Result<Option<Credentials>> {
+    let name = match resource.spec.secret_ref.as_ref().and_then(|r| r.name()) {
+      Some(name) => name,
+      None => return Ok(None),
+    };
+
+    let namespace = resource.namespace().unwrap_or_default();
+    let config = SecretCheck::new(&namespace, name)
+      .require(DOCKER_CONFIG_KEY)
+      .fetch(ctx.client())
+      .await?
+      .remove(DOCKER_CONFIG_KEY)
+      .expect("the key is required");
+
+    Ok(Credentials::from_docker_config(
+      &config.0,
+      image.registry(),
+    )?)
+  }
+
+  /// Scans the registry for the tags of the image of `resource`, which are sorted and stored for
+  /// the policies.
+  async fn scan(&self, ctx: &ReconcileCtx, resource: &ImageRepository) -> Result<Arc<Scan>> {
+    let spec = &resource.spec;
+    let image = ImageName::parse(&spec.image)?;
+    let exclusions = spec
+      .exclusion_list
+      .iter()
+      .map(|pattern| Regex::new(pattern).map_err(InvalidExclusion))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let credentials = self.credentials(ctx, resource, &image).await?;
+    let registry = Registry::new(self.http.clone(), spec.insecure);
+    let timeout = resolve_timeout(spec.timeout, DEFAULT_TIMEOUT, Some(spec.interval));
+    let mut tags = tokio::time::timeout(timeout, registry.tags(&image, credentials.as_ref()))
+      .await
+      .map_err(|_| eyre!("timed out scanning image '{image}'"))?
+      .wrap_err_with(|| format!("failed to scan image '{image}'"))?;
+    tags.retain(|tag| !exclusions.iter().any(|exclusion| exclusion.is_match(tag)));
+    tags.sort();
+    tags.dedup();
+
+    let scan = Arc::new(Scan { image, tags });
+    let namespace = resource.namespace().unwrap_or_default();
+    self.tags.insert(namespace, resource.name(), scan.clone());
+
+    Ok(scan)
+  }
+}
+
+#[flux_controller(suspend = spec.suspend, interval = spec.interval)]
+#[async_trait]
+impl Controller<ImageRepository> for ImageRepositoryController {
+  async fn reconcile(
+    self: Arc<Self>,
+    resource: Arc<ImageRepository>,
+    ctx: ReconcileCtx,
+  ) -> Result<ReconcilerAction> {
+    let scanned = self.scan(&ctx, &resource).await;
+    let status = repository_status_patch(
+      resource.status.as_ref(),
+      resource.metadata.generation,
+      &scanned,
+    );
+
+    let namespace = resource.namespace().unwrap_or_default();
+    let patched = Api::<ImageRepository>::namespaced(ctx.client().clone(), &namespace)
+      .patch_status(
+        &resource.name(),
+        &PatchParams::default(),
+        &Patch::Merge(&status),
+      )
+      .await;
+    // the error of scanning the registry takes precedence over that of recording it
+    scanned?;
+    patched?;
+
+    Ok(ReconcilerAction {
+      requeue_after: Self::reconcile_interval(&resource),
+    })
+  }
+
+  fn permissions() -> Permissions {
+    Permissions::default().namespaced("", "secrets", READ)
+  }
+
+  fn predicates() -> Predicates {
+    // status patches do not bump the generation, so they do not trigger another scan
+    Predicates::generation().annotation(RECONCILE_REQUEST_ANNOTATION)
+  }
+
+  fn last_reconciled(resource: &ImageRepository) -> Option<SystemTime> {
+    let time = &resource
+      .status
+      .as_ref()?
+      .last_scan_result
+      .as_ref()?
+      .scan_time;
+    Some(time.0.into())
+  }
+}
+
+/// The tag policy of `resource`, and the filter of its tags if any.
+fn tag_policy(resource: &ImagePolicy) -> Result<(TagPolicy, Option<TagFilter>)> {
+  let order = |order: SortOrder| match order {
+    SortOrder::Asc => Order::Ascending,
+    SortOrder::Desc => Order::Descending,
+  };
+
+  let choice = &resource.spec.policy;
+  let policy = match (&choice.semver, &choice.alphabetical, &choice.numerical) {
+    (Some(semver), None, None) => TagPolicy::semver(&semver.range)?,
+    (None, Some(alphabetical), None) => TagPolicy::Alphabetical(order(alphabetical.order)),
+    (None, None, Some(numerical)) => TagPolicy::Numerical(order(numerical.order)),
+    _ => return Err(InvalidPolicy.into()),
+  };
+  let filter = resource
+    .spec
+    .filter_tags
+    .as_ref()
+    .map(|filter| TagFilter::new(&filter.pattern, filter.extract.clone()))
+    .transpose()?;
+
+  Ok((policy, filter))
+}
+
+/// The status recording the outcome of selecting the latest image: `Ready` with the image, which
+/// the previous one is recorded along with when it changed.
+fn policy_status_patch(
+  status: Option<&ImagePolicyStatus>,
+  generation: Option<i64>,
+  result: &Result<String>,
+) -> Value {
+  let mut conditions = status.map(|s| s.conditions.clone()).unwrap_or_default();
+  let latest = match result {
+    Ok(latest) => latest,
+    Err(error) => {
+      mark_failed(&mut conditions, generation, error);
+      return json!({
+        "status": {
+          "conditions": conditions,
+        }
+      });
+    }
+  };
+
+  let message = format!("latest image is '{latest}'");
+  mark_ready(&mut conditions, generation, Reason::Succeeded, message);
+
+  let mut patch = json!({
+    "status": {
+      "conditions": conditions,
+      "latestImage": latest,
+    }
+  });
+  match status.and_then(|s| s.latest_image.as_ref()) {
+    Some(previous) if previous != latest => {
+      patch["status"]["observedPreviousImage"] = previous.as_str().into();
+    }
+    _ => {}
+  }
+
+  patch
+}
+
+struct ImagePolicyController {
+  metrics: metrics::Recorder,
+  tags: TagStore,
+}
+
+impl ImagePolicyController {
+  fn new(tags: TagStore) -> Result<Self> {
+    let metrics = metrics::Recorder::new()?;
+    Ok(Self { metrics, tags })
+  }
+
+  /// The image with the latest of the tags of the repository of `resource`.
+  fn select(&self, resource: &ImagePolicy) -> Result<String> {
+    let (policy, filter) = tag_policy(resource)?;
+    let namespace = resource.namespace().unwrap_or_default();
+    let name = resource
+      .spec
+      .image_repository_ref
+      .name()
+      .unwrap_or_default();
+    // the repository may yet be created or scanned, so this is retried
+    let scan = self
+      .tags
+      .get(&namespace, name)
+      .ok_or_else(|| NotScanned(format!("{namespace}/{name}")))?;
+
+    let tag = policy
+      .select(&scan.tags, filter.as_ref())
+      .ok_or_else(|| eyre!("no tag of image '{}' is selected by the policy", scan.image))?;
+
+    Ok(format!("{}:{tag}", scan.image))
+  }
+}
+
+#[flux_controller(suspend = spec.suspend)]
+#[async_trait]
+impl Controller<ImagePolicy> for ImagePolicyController {
+  async fn reconcile(
+    self: Arc<Self>,
+    resource: Arc<ImagePolicy>,
+    ctx: ReconcileCtx,
+  ) -> Result<ReconcilerAction> {
+    let selected = self.select(&resource);
+    let status = policy_status_patch(
+      resource.status.as_ref(),
+      resource.metadata.generation,
+      &selected,
+    );
+
+    let namespace = resource.namespace().unwrap_or_default();
+    let patched = Api::<ImagePolicy>::namespaced(ctx.client().clone(), &namespace)
+      .patch_status(
+        &resource.name(),
+        &PatchParams::default(),
+        &Patch::Merge(&status),
+      )
+      .await;
+    selected?;
+    patched?;
+
+    Ok(ReconcilerAction {
+      requeue_after: Some(POLICY_RECHECK_INTERVAL),
+    })
+  }
+
+  fn predicates() -> Predicates {
+    Predicates::generation().annotation(RECONCILE_REQUEST_ANNOTATION)
+  }
+}
+
+fn main() -> Result<()> {
+  ControllerApp::main(CRATE_NAME, CRATE_VERSION, |app| {
+    // the policies select from the tags the repositories were scanned for in this process
+    let tags = TagStore::default();
+    Ok(
+      app
+        .controller(ImageRepositoryController::new(tags.clone())?)
+        .controller(ImagePolicyController::new(tags)?),
+    )
+  })
+}
@@ -0,0 +1,247 @@
+mod server;
+
+use async_trait::async_trait;
+use eyre::{Result, WrapErr};
+use fluxcd_api_notification_alert::{Alert, Provider, ProviderType};
+use fluxcd_meta::{
+  mark_ready, mark_reconciling, mark_stalled, Reason, RECONCILE_REQUEST_ANNOTATION,
+};
+use fluxcd_notifier::{Error as NotifierError, Notifier, Service};
+use fluxcd_utils_cap::{
+  context::ReconcileCtx,
+  flux_controller,
+  http::HttpConfig,
+  metrics,
+  predicate::Predicates,
+  rbac::{Permissions, READ},
+  Controller, ControllerApp,
+};
+use k8s_openapi::{api::core::v1::Secret, apimachinery::pkg::apis::meta::v1::Condition};
+use kube::{
+  api::{Patch, PatchParams},
+  runtime::controller::ReconcilerAction,
+  Api, Client, ResourceExt,
+};
+use serde_json::json;
+use server::EventServer;
+use std::{sync::Arc, time::Duration};
+use thiserror::Error;
+
+const CRATE_NAME: &str = env!("CARGO_PKG_NAME");
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Key of the Secret entry holding the address of a provider.
+const ADDRESS_KEY: &str = "address";
+
+/// Timeout for posting a notification, when the provider does not specify one.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Error)]
+#[error("the provider has no address, neither in its spec nor in its secret")]
+struct MissingAddress;
+
+/// The notifier posting to `provider`, at the address of its Secret, or else of its spec.
+async fn notifier(client: &Client, provider: &Provider) -> Result<Notifier> {
+  let spec = &provider.spec;
+  let namespace = provider.namespace().unwrap_or_default();
+  let mut address = spec.address.clone();
+  if let Some(name) = spec.secret_ref.as_ref().and_then(|r| r.name()) {
+    let secret = Api::<Secret>::namespaced(client.clone(), &namespace)
+      .get(name)
+      .await
+      .wrap_err_with(|| format!("failed to get secret '{namespace}/{name}'"))?;
+    if let Some(secret_address) = secret.data.unwrap_or_default().remove(ADDRESS_KEY) {
+      let secret_address = String::from_utf8(secret_address.0)
+        .wrap_err_with(|| format!("invalid address in secret '{namespace}/{name}'"))?;
+      address = Some(secret_address);
+    }
+  }
+
+  let service = match spec.type_ {
+    ProviderType::Slack => Service::Slack,
+    ProviderType::Discord => Service::Discord,
+    ProviderType::MsTeams => Service::MsTeams,
+    ProviderType::Generic => Service::Generic,
+  };
+  let notifier = Notifier::new(service, &address.ok_or(MissingAddress)?)?
+    .with_channel(spec.channel.clone())
+    .with_username(spec.username.clone());
+
+  Ok(notifier)
+}
+
+/// Records the outcome of a reconcile in the conditions: `Ready` with `ready` as the message, and
+/// otherwise either `Stalled` if `stalled` tells retrying will not help, or `Reconciling` while
+/// it is retried.
+fn record(
+  conditions: &mut Vec<Condition>,
+  generation: Option<i64>,
+  result: &Result<String>,
+  stalled: impl Fn(&eyre::Report) -> bool,
+) {
+  match result {
+    Ok(message) => mark_ready(conditions, generation, Reason::Succeeded, message.clone()),
+    Err(error) if stalled(error) => {
+      mark_stalled(conditions, generation, Reason::Failed, format!("{error:#}"))
+    }
+    Err(error) => {
+      let message = format!("retrying after: {error:#}");
+      mark_reconciling(conditions, generation, Reason::Progressing, message);
+    }
+  }
+}
+
+struct ProviderController {
+  metrics: metrics::Recorder,
+}
+
+impl ProviderController {
+  fn new() -> Result<Self> {
+    let metrics = metrics::Recorder::new()?;
+    Ok(Self { metrics })
+  }
+}
+
+#[flux_controller(suspend = spec.suspend)]
+#[async_trait]
+impl Controller<Provider> for ProviderController {
+  async fn reconcile(
+    self: Arc<Self>,
+    resource: Arc<Provider>,
+    ctx: ReconcileCtx,
+  ) -> Result<ReconcilerAction> {
+    // the address is read again for every notification, so this only reports whether it is valid
+    let checked = notifier(ctx.client(), &resource)
+      .await
+      .map(|_| "the address of the provider is valid".to_string());
+
+    let mut conditions = resource
+      .status
+      .as_ref()
+      .map(|s| s.conditions.clone())
+      .unwrap_or_default();
+    record(
+      &mut conditions,
+      resource.metadata.generation,
+      &checked,
+      |error| {
+        error.chain().any(|cause| {
+          cause.is::<MissingAddress>()
+            || matches!(
+              cause.downcast_ref::<NotifierError>(),
+              Some(NotifierError::InvalidAddress(_))
+            )
+        })
+      },
+    );
+
+    let namespace = resource.namespace().unwrap_or_default();
+    let status = json!({ "status": { "conditions": conditions } });
+    let patched = Api::<Provider>::namespaced(ctx.client().clone(), &namespace)
+      .patch_status(
+        &resource.name(),
+        &PatchParams::default(),
+        &Patch::Merge(&status),
+      )
+      .await;
+    // the error of checking the address takes precedence over that of recording it
+    checked?;
+    patched?;
+
+    Ok(ReconcilerAction {
+      requeue_after: None,
+    })
+  }
+
+  fn permissions() -> Permissions {
+    Permissions::default().namespaced("", "secrets", READ)
+  }
+
+  fn predicates() -> Predicates {
+    Predicates::generation().annotation(RECONCILE_REQUEST_ANNOTATION)
+  }
+}
+
+struct AlertController {
+  metrics: metrics::Recorder,
+}
+
+impl AlertController {
+  fn new() -> Result<Self> {
+    let metrics = metrics::Recorder::new()?;
+    Ok(Self { metrics })
+  }
+}
+
+#[flux_controller(suspend = spec.suspend)]
+#[async_trait]
+impl Controller<Alert> for AlertController {
+  async fn reconcile(
+    self: Arc<Self>,
+    resource: Arc<Alert>,
+    ctx: ReconcileCtx,
+  ) -> Result<ReconcilerAction> {
+    let namespace = resource.namespace().unwrap_or_default();
+    let provider = resource.spec.provider_ref.name().unwrap_or_default();
+    let checked = Api::<Provider>::namespaced(ctx.client().clone(), &namespace)
+      .get(provider)
+      .await
+      .wrap_err_with(|| format!("failed to get provider '{namespace}/{provider}'"))
+      .map(|_| {
+        format!(
+          "posting the events of {} source(s) to provider '{provider}'",
+          resource.spec.event_sources.len()
+        )
+      });
+
+    let mut conditions = resource
+      .status
+      .as_ref()
+      .map(|s| s.conditions.clone())
+      .unwrap_or_default();
+    // the provider may yet be created, so a missing one is retried
+    record(
+      &mut conditions,
+      resource.metadata.generation,
+      &checked,
+      |_| false,
+    );
+
+    let status = json!({ "status": { "conditions": conditions } });
+    let patched = Api::<Alert>::namespaced(ctx.client().clone(), &namespace)
+      .patch_status(
+        &resource.name(),
+        &PatchParams::default(),
+        &Patch::Merge(&status),
+      )
+      .await;
+    checked?;
+    patched?;
+
+    Ok(ReconcilerAction {
+      requeue_after: None,
+    })
+  }
+
+  fn predicates() -> Predicates {
+    Predicates::generation().annotation(RECONCILE_REQUEST_ANNOTATION)
+  }
+}
+
+fn main() -> Result<()> {
+  ControllerApp::main(CRATE_NAME, CRATE_VERSION, |app| {
+    let builder = reqwest::Client::builder().user_agent(USER_AGENT);
+    let http = HttpConfig::from_env()?.apply(builder).build()?;
+    let server = EventServer::from_env(http)?;
+    Ok(
+      app
+        .controller(ProviderController::new()?)
+        .controller(AlertController::new()?)
+        .service("event-server", move |client, signal| {
+          server.serve(client, signal)
+        }),
+    )
+  })
+}
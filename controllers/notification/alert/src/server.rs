@@ -0,0 +1,202 @@
+use crate::{notifier, DEFAULT_TIMEOUT};
+use eyre::{Result, WrapErr};
+use fluxcd_api_notification_alert::{Alert, EventSeverity, Provider};
+use fluxcd_meta::resolve_timeout;
+use fluxcd_notifier::{Event, Severity};
+use hyper::{
+  body::HttpBody,
+  service::{make_service_fn, service_fn},
+  Body, Method, Request, Response, Server, StatusCode,
+};
+use k8s_openapi::{apimachinery::pkg::apis::meta::v1::Time, chrono::Utc};
+use kube::{
+  api::{ListParams, Patch, PatchParams},
+  Api, Client, ResourceExt,
+};
+use serde_json::json;
+use std::{convert::Infallible, future::Future, net::SocketAddr};
+use tracing::{debug, info, warn};
+
+/// Environment variable of the address events are received at.
+const EVENTS_RECEIVER_ADDR_ENV: &str = "EVENTS_RECEIVER_ADDR";
+
+/// Address events are received at, unless configured otherwise.
+const DEFAULT_EVENTS_RECEIVER_ADDR: &str = "0.0.0.0:9393";
+
+/// Largest event accepted.
+const MAX_EVENT_SIZE: usize = 1 << 20;
+
+/// Server receiving the events the controllers forward with `--events-addr`, and posting them to
+/// the providers of the alerts they match.
+pub(crate) struct EventServer {
+  addr: SocketAddr,
+  http: reqwest::Client,
+}
+
+impl EventServer {
+  /// The server at the address of the `EVENTS_RECEIVER_ADDR` environment variable, posting
+  /// notifications with `http`.
+  pub(crate) fn from_env(http: reqwest::Client) -> Result<Self> {
+    let addr = match std::env::var(EVENTS_RECEIVER_ADDR_ENV) {
+      Ok(addr) if !addr.is_empty() => addr,
+      _ => DEFAULT_EVENTS_RECEIVER_ADDR.to_string(),
+    };
+    let addr = addr
+      .parse()
+      .wrap_err_with(|| format!("invalid {EVENTS_RECEIVER_ADDR_ENV} '{addr}'"))?;
+
+    Ok(Self { addr, http })
+  }
+
+  /// Receives events, until `signal` completes.
+  pub(crate) async fn serve(self, client: Client, signal: impl Future<Output = ()>) -> Result<()> {
+    let http = self.http;
+    let make_service = make_service_fn(move |_| {
+      let (client, http) = (client.clone(), http.clone());
+      let service = service_fn(move |request| respond(client.clone(), http.clone(), request));
+      async move { Ok::<_, Infallible>(service) }
+    });
+
+    let server = Server::try_bind(&self.addr)?.serve(make_service);
+    info!(addr = %self.addr, "receiving events");
+    server.with_graceful_shutdown(signal).await?;
+
+    Ok(())
+  }
+}
+
+/// Accepts the event of `request`, which is dispatched to the alerts it matches in the
+/// background, so that the controller forwarding it is not held up by the providers.
+async fn respond(
+  client: Client,
+  http: reqwest::Client,
+  request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+  let (status, message) = match read_event(request).await {
+    Ok(event) => {
+      tokio::spawn(dispatch(client, http, event));
+      (StatusCode::ACCEPTED, "event accepted".to_string())
+    }
+    Err(rejection) => rejection,
+  };
+
+  let mut response = Response::new(Body::from(message));
+  *response.status_mut() = status;
+  Ok(response)
+}
+
+async fn read_event(request: Request<Body>) -> Result<Event, (StatusCode, String)> {
+  if *request.method() != Method::POST {
+    return Err((
+      StatusCode::METHOD_NOT_ALLOWED,
+      "events are sent with POST".into(),
+    ));
+  }
+
+  let mut body = request.into_body();
+  let mut payload = Vec::new();
+  while let Some(chunk) = body.data().await {
+    let chunk = chunk.map_err(|error| (StatusCode::BAD_REQUEST, error.to_string()))?;
+    if payload.len() + chunk.len() > MAX_EVENT_SIZE {
+      return Err((
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("events are limited to {MAX_EVENT_SIZE} bytes"),
+      ));
+    }
+    payload.extend_from_slice(&chunk);
+  }
+
+  serde_json::from_slice(&payload)
+    .map_err(|error| (StatusCode::BAD_REQUEST, format!("invalid event: {error}")))
+}
+
+/// Whether `alert` posts `event`: it is severe enough, and about one of the sources of the alert.
+fn matches(alert: &Alert, event: &Event) -> bool {
+  let spec = &alert.spec;
+  if spec.suspend
+    || (spec.event_severity == EventSeverity::Error && event.severity < Severity::Error)
+  {
+    return false;
+  }
+
+  let object = &event.involved_object;
+  let alert_namespace = alert.namespace();
+  spec.event_sources.iter().any(|source| {
+    let namespace = source.namespace.as_ref().or(alert_namespace.as_ref());
+    object.kind.as_deref() == Some(&*source.kind)
+      && (source.name == "*" || object.name.as_deref() == Some(&*source.name))
+      && object.namespace.as_ref() == namespace
+  })
+}
+
+/// Posts `event` to the providers of the alerts it matches.
+async fn dispatch(client: Client, http: reqwest::Client, event: Event) {
+  let alerts = match Api::<Alert>::all(client.clone())
+    .list(&ListParams::default())
+    .await
+  {
+    Ok(alerts) => alerts,
+    Err(error) => {
+      warn!(%error, object = %event.object(), "failed to list alerts, dropping event");
+      return;
+    }
+  };
+
+  for alert in alerts.into_iter().filter(|alert| matches(alert, &event)) {
+    let name = alert.name();
+    let namespace = alert.namespace().unwrap_or_default();
+    match post(&client, &http, &alert, &event).await {
+      Ok(true) => {}
+      Ok(false) => {
+        debug!(alert = %name, %namespace, "provider is suspended, event not posted");
+        continue;
+      }
+      Err(error) => {
+        let (object, error) = (event.object(), format!("{error:#}"));
+        warn!(alert = %name, %namespace, %object, %error, "failed to post event");
+        continue;
+      }
+    }
+
+    let status = json!({
+      "status": {
+        "lastPostTime": Time(Utc::now()),
+      }
+    });
+    let recorded = Api::<Alert>::namespaced(client.clone(), &namespace)
+      .patch_status(&name, &PatchParams::default(), &Patch::Merge(&status))
+      .await;
+    if let Err(error) = recorded {
+      warn!(alert = %name, %namespace, %error, "failed to record posted event");
+    }
+  }
+}
+
+/// Posts `event` to the provider of `alert`, unless it is suspended. Whether it was posted.
+async fn post(
+  client: &Client,
+  http: &reqwest::Client,
+  alert: &Alert,
+  event: &Event,
+) -> Result<bool> {
+  let namespace = alert.namespace().unwrap_or_default();
+  let name = alert.spec.provider_ref.name().unwrap_or_default();
+  let provider = Api::<Provider>::namespaced(client.clone(), &namespace)
+    .get(name)
+    .await
+    .wrap_err_with(|| format!("failed to get provider '{namespace}/{name}'"))?;
+  if provider.spec.suspend {
+    return Ok(false);
+  }
+
+  let timeout = resolve_timeout(provider.spec.timeout, DEFAULT_TIMEOUT, None);
+  let notifier = notifier(client, &provider).await?;
+  tokio::time::timeout(
+    timeout,
+    notifier.post(http, event, alert.spec.summary.as_deref()),
+  )
+  .await
+  .map_err(|_| eyre::eyre!("timed out posting to provider '{namespace}/{name}'"))??;
+
+  Ok(true)
+}
@@ -0,0 +1,172 @@
+mod server;
+
+use async_trait::async_trait;
+use eyre::{Result, WrapErr};
+use fluxcd_api_notification_receiver::{Receiver, ReceiverStatus};
+use fluxcd_meta::{
+  mark_ready, mark_reconciling, mark_stalled, Reason, RECONCILE_REQUEST_ANNOTATION,
+};
+use fluxcd_receiver::webhook_path;
+use fluxcd_utils_cap::{
+  context::ReconcileCtx,
+  flux_controller, metrics,
+  predicate::Predicates,
+  rbac::{Permissions, READ},
+  Controller, ControllerApp,
+};
+use k8s_openapi::api::core::v1::Secret;
+use kube::{
+  api::{Patch, PatchParams},
+  runtime::controller::ReconcilerAction,
+  Api, Client, ResourceExt,
+};
+use serde_json::{json, Value};
+use server::ReceiverServer;
+use std::{sync::Arc, time::Duration};
+use thiserror::Error;
+
+const CRATE_NAME: &str = env!("CARGO_PKG_NAME");
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Key of the Secret entry holding the token of a receiver.
+const TOKEN_KEY: &str = "token";
+
+/// How often the token of a receiver is read again, so that the webhook path follows a rotated
+/// token even though changing the Secret does not trigger a reconcile.
+const TOKEN_RECHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Error)]
+#[error("secret '{namespace}/{name}' has no 'token'")]
+struct MissingToken {
+  namespace: String,
+  name: String,
+}
+
+/// The token of the receiver `resource`, from its Secret.
+async fn token(client: &Client, resource: &Receiver) -> Result<String> {
+  let namespace = resource.namespace().unwrap_or_default();
+  let name = resource.spec.secret_ref.name().unwrap_or_default();
+  let secret = Api::<Secret>::namespaced(client.clone(), &namespace)
+    .get(name)
+    .await
+    .wrap_err_with(|| format!("failed to get secret '{namespace}/{name}'"))?;
+
+  let missing = || MissingToken {
+    namespace: namespace.clone(),
+    name: name.to_string(),
+  };
+  let token = secret
+    .data
+    .unwrap_or_default()
+    .remove(TOKEN_KEY)
+    .ok_or_else(missing)?;
+  let token = String::from_utf8(token.0).map_err(|_| missing())?;
+
+  Ok(token.trim().to_string())
+}
+
+/// The status recording the outcome of a reconcile: `Ready` with the webhook path once the token
+/// is read, and otherwise either `Stalled` if the Secret has no token, or `Reconciling` while it
+/// is retried.
+fn status_patch(
+  status: Option<&ReceiverStatus>,
+  generation: Option<i64>,
+  result: &Result<String>,
+) -> Value {
+  let mut conditions = status.map(|s| s.conditions.clone()).unwrap_or_default();
+  let path = match result {
+    Ok(path) => path,
+    Err(error) => {
+      let message = format!("{error:#}");
+      if error.chain().any(|cause| cause.is::<MissingToken>()) {
+        mark_stalled(&mut conditions, generation, Reason::Failed, message);
+      } else {
+        let message = format!("retrying after: {message}");
+        mark_reconciling(&mut conditions, generation, Reason::Progressing, message);
+      }
+
+      return json!({
+        "status": {
+          "conditions": conditions,
+        }
+      });
+    }
+  };
+
+  let message = format!("receiving webhooks at '{path}'");
+  mark_ready(&mut conditions, generation, Reason::Succeeded, message);
+
+  json!({
+    "status": {
+      "conditions": conditions,
+      "webhookPath": path,
+    }
+  })
+}
+
+struct ReceiverController {
+  metrics: metrics::Recorder,
+}
+
+impl ReceiverController {
+  fn new() -> Result<Self> {
+    let metrics = metrics::Recorder::new()?;
+    Ok(Self { metrics })
+  }
+}
+
+#[flux_controller(suspend = spec.suspend)]
+#[async_trait]
+impl Controller<Receiver> for ReceiverController {
+  async fn reconcile(
+    self: Arc<Self>,
+    resource: Arc<Receiver>,
+    ctx: ReconcileCtx,
+  ) -> Result<ReconcilerAction> {
+    let namespace = resource.namespace().unwrap_or_default();
+    let path = token(ctx.client(), &resource)
+      .await
+      .map(|token| webhook_path(&token, &resource.name(), &namespace));
+    let status = status_patch(
+      resource.status.as_ref(),
+      resource.metadata.generation,
+      &path,
+    );
+
+    let patched = Api::<Receiver>::namespaced(ctx.client().clone(), &namespace)
+      .patch_status(
+        &resource.name(),
+        &PatchParams::default(),
+        &Patch::Merge(&status),
+      )
+      .await;
+    // the error of reading the token takes precedence over that of recording it
+    path?;
+    patched?;
+
+    Ok(ReconcilerAction {
+      requeue_after: Some(TOKEN_RECHECK_INTERVAL),
+    })
+  }
+
+  fn permissions() -> Permissions {
+    Permissions::default().namespaced("", "secrets", READ)
+  }
+
+  fn predicates() -> Predicates {
+    Predicates::generation().annotation(RECONCILE_REQUEST_ANNOTATION)
+  }
+}
+
+fn main() -> Result<()> {
+  ControllerApp::main(CRATE_NAME, CRATE_VERSION, |app| {
+    let server = ReceiverServer::from_env()?;
+    Ok(
+      app
+        .controller(ReceiverController::new()?)
+        .service("receiver-server", move |client, signal| {
+          server.serve(client, signal)
+        }),
+    )
+  })
+}
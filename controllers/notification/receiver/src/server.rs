@@ -0,0 +1,273 @@
+use eyre::{bail, eyre, Result, WrapErr};
+use fluxcd_api_notification_receiver::{Receiver, ReceiverResource, ReceiverType};
+use fluxcd_meta::RECONCILE_REQUEST_ANNOTATION;
+use fluxcd_receiver::{verify, webhook_path, Error as VerifyError, Provider, Verdict};
+use hyper::{
+  body::HttpBody,
+  service::{make_service_fn, service_fn},
+  Body, Method, Request, Response, Server, StatusCode,
+};
+use k8s_openapi::{
+  apimachinery::pkg::apis::meta::v1::Time,
+  chrono::{SecondsFormat, Utc},
+};
+use kube::{
+  api::{ListParams, Patch, PatchParams},
+  core::{ApiResource, DynamicObject, GroupVersionKind},
+  Api, Client, ResourceExt,
+};
+use serde_json::json;
+use std::{convert::Infallible, future::Future, net::SocketAddr};
+use tracing::{info, warn};
+
+/// Environment variable of the address webhooks are received at.
+const RECEIVER_ADDR_ENV: &str = "RECEIVER_ADDR";
+
+/// Address webhooks are received at, unless configured otherwise.
+const DEFAULT_RECEIVER_ADDR: &str = "0.0.0.0:9292";
+
+/// Largest payload accepted, as payloads are read in full to check their signature.
+const MAX_PAYLOAD_SIZE: usize = 1 << 20;
+
+/// Server receiving the webhooks of every Receiver, at their `status.webhookPath`.
+pub(crate) struct ReceiverServer {
+  addr: SocketAddr,
+}
+
+/// Why a webhook was not acted upon, answered with the status and message.
+struct Rejection(StatusCode, String);
+
+impl Rejection {
+  fn new(status: StatusCode, message: impl Into<String>) -> Self {
+    Self(status, message.into())
+  }
+}
+
+impl ReceiverServer {
+  /// The server at the address of the `RECEIVER_ADDR` environment variable.
+  pub(crate) fn from_env() -> Result<Self> {
+    let addr = match std::env::var(RECEIVER_ADDR_ENV) {
+      Ok(addr) if !addr.is_empty() => addr,
+      _ => DEFAULT_RECEIVER_ADDR.to_string(),
+    };
+    let addr = addr
+      .parse()
+      .wrap_err_with(|| format!("invalid {RECEIVER_ADDR_ENV} '{addr}'"))?;
+
+    Ok(Self { addr })
+  }
+
+  /// Receives webhooks, until `signal` completes.
+  pub(crate) async fn serve(self, client: Client, signal: impl Future<Output = ()>) -> Result<()> {
+    let make_service = make_service_fn(move |_| {
+      let client = client.clone();
+      let service = service_fn(move |request| respond(client.clone(), request));
+      async move { Ok::<_, Infallible>(service) }
+    });
+
+    let server = Server::try_bind(&self.addr)?.serve(make_service);
+    info!(addr = %self.addr, "receiving webhooks");
+    server.with_graceful_shutdown(signal).await?;
+
+    Ok(())
+  }
+}
+
+async fn respond(client: Client, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+  let path = request.uri().path().to_string();
+  let (status, message) = match receive(client, request).await {
+    Ok(message) => (StatusCode::OK, message),
+    Err(Rejection(status, message)) => {
+      if status.is_server_error() {
+        warn!(%path, %status, %message, "failed to handle webhook");
+      }
+      (status, message)
+    }
+  };
+
+  let mut response = Response::new(Body::from(message));
+  *response.status_mut() = status;
+  Ok(response)
+}
+
+/// Authenticates the webhook of `request`, and requests the reconciliation of the resources of
+/// its receiver.
+async fn receive(client: Client, request: Request<Body>) -> Result<String, Rejection> {
+  if *request.method() != Method::POST {
+    return Err(Rejection::new(
+      StatusCode::METHOD_NOT_ALLOWED,
+      "webhooks are sent with POST",
+    ));
+  }
+
+  let path = request.uri().path().to_string();
+  let not_found = || Rejection::new(StatusCode::NOT_FOUND, "there is no receiver at this path");
+  let receivers = Api::<Receiver>::all(client.clone())
+    .list(&ListParams::default())
+    .await
+    .map_err(|error| unavailable("failed to list receivers", error))?;
+  let receiver = receivers
+    .into_iter()
+    .find(|r| r.status.as_ref().and_then(|s| s.webhook_path.as_deref()) == Some(path.as_str()))
+    .ok_or_else(not_found)?;
+  let name = receiver.name();
+  let namespace = receiver.namespace().unwrap_or_default();
+
+  if receiver.spec.suspend {
+    return Err(Rejection::new(
+      StatusCode::SERVICE_UNAVAILABLE,
+      "the receiver is suspended",
+    ));
+  }
+
+  let token = crate::token(&client, &receiver)
+    .await
+    .map_err(|error| unavailable("failed to read the token of the receiver", error))?;
+  // the path of a rotated token is only recorded by the next reconcile, until which the webhooks
+  // at the path of the previous token are rejected
+  if webhook_path(&token, &name, &namespace) != path {
+    return Err(not_found());
+  }
+
+  let (parts, body) = request.into_parts();
+  let payload = read_payload(body).await?;
+  let provider = provider(receiver.spec.type_);
+  match verify(
+    provider,
+    &parts.headers,
+    &payload,
+    &token,
+    &receiver.spec.events,
+  ) {
+    Ok(Verdict::Accept) => {}
+    Ok(Verdict::Ignore(reason)) => return Ok(format!("ignored: {reason}")),
+    Err(error @ (VerifyError::MissingHeader(_) | VerifyError::InvalidHeader(_))) => {
+      return Err(Rejection::new(StatusCode::BAD_REQUEST, error.to_string()))
+    }
+    Err(error) => return Err(Rejection::new(StatusCode::UNAUTHORIZED, error.to_string())),
+  }
+
+  let requested_at = Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true);
+  let mut failed = 0;
+  for resource in &receiver.spec.resources {
+    let target = format!("{}/{}", resource.kind, resource.name);
+    match request_reconcile(&client, &namespace, resource, &requested_at).await {
+      Ok(()) => info!(receiver = %name, %namespace, %target, "reconcile requested"),
+      Err(error) => {
+        let error = format!("{error:#}");
+        warn!(receiver = %name, %namespace, %target, %error, "failed to request reconcile");
+        failed += 1;
+      }
+    }
+  }
+
+  let status = json!({
+    "status": {
+      "lastWebhookTime": Time(Utc::now()),
+    }
+  });
+  let recorded = Api::<Receiver>::namespaced(client, &namespace)
+    .patch_status(&name, &PatchParams::default(), &Patch::Merge(&status))
+    .await;
+  if let Err(error) = recorded {
+    warn!(receiver = %name, %namespace, %error, "failed to record webhook");
+  }
+
+  let requested = receiver.spec.resources.len() - failed;
+  if failed > 0 {
+    return Err(Rejection::new(
+      StatusCode::INTERNAL_SERVER_ERROR,
+      format!("requested the reconciliation of {requested} resource(s), {failed} failed"),
+    ));
+  }
+
+  Ok(format!(
+    "requested the reconciliation of {requested} resource(s)"
+  ))
+}
+
+/// A rejection for an error of the API server, whose detail is only logged.
+fn unavailable(message: &str, error: impl std::fmt::Display) -> Rejection {
+  warn!(error = %format!("{error:#}"), "{message}");
+  Rejection::new(StatusCode::SERVICE_UNAVAILABLE, message)
+}
+
+fn provider(type_: ReceiverType) -> Provider {
+  match type_ {
+    ReceiverType::Generic => Provider::Generic,
+    ReceiverType::GenericHmac => Provider::GenericHmac,
+    ReceiverType::GitHub => Provider::GitHub,
+    ReceiverType::GitLab => Provider::GitLab,
+    ReceiverType::Harbor => Provider::Harbor,
+  }
+}
+
+/// Reads the payload of a webhook, up to [MAX_PAYLOAD_SIZE].
+async fn read_payload(mut body: Body) -> Result<Vec<u8>, Rejection> {
+  let mut payload = Vec::new();
+  while let Some(chunk) = body.data().await {
+    let chunk =
+      chunk.map_err(|error| Rejection::new(StatusCode::BAD_REQUEST, error.to_string()))?;
+    if payload.len() + chunk.len() > MAX_PAYLOAD_SIZE {
+      return Err(Rejection::new(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("payloads are limited to {MAX_PAYLOAD_SIZE} bytes"),
+      ));
+    }
+    payload.extend_from_slice(&chunk);
+  }
+
+  Ok(payload)
+}
+
+/// Sets the reconcile request annotation of `resource`, in `namespace`, to `requested_at`.
+async fn request_reconcile(
+  client: &Client,
+  namespace: &str,
+  resource: &ReceiverResource,
+  requested_at: &str,
+) -> Result<()> {
+  let api_resource = api_resource(client, resource).await?;
+  let patch = json!({
+    "metadata": {
+      "annotations": {
+        RECONCILE_REQUEST_ANNOTATION: requested_at,
+      }
+    }
+  });
+
+  Api::<DynamicObject>::namespaced_with(client.clone(), namespace, &api_resource)
+    .patch(
+      &resource.name,
+      &PatchParams::default(),
+      &Patch::Merge(&patch),
+    )
+    .await?;
+
+  Ok(())
+}
+
+/// The API resource of the kind of `resource`, as discovered from the API server, which has to
+/// be namespaced.
+async fn api_resource(client: &Client, resource: &ReceiverResource) -> Result<ApiResource> {
+  let api_version = &resource.api_version;
+  let list = match api_version.split_once('/') {
+    Some(_) => client.list_api_group_resources(api_version).await,
+    None => client.list_core_api_resources(api_version).await,
+  }
+  .wrap_err_with(|| format!("failed to discover {api_version}"))?;
+
+  // subresources, like `status`, are listed as `<plural>/<subresource>`
+  let discovered = list
+    .resources
+    .into_iter()
+    .find(|r| r.kind == resource.kind && !r.name.contains('/'))
+    .ok_or_else(|| eyre!("{api_version} has no kind {}", resource.kind))?;
+  if !discovered.namespaced {
+    bail!("{} is cluster-scoped", resource.kind);
+  }
+
+  let (group, version) = api_version.split_once('/').unwrap_or(("", api_version));
+  let gvk = GroupVersionKind::gvk(group, version, &resource.kind);
+  Ok(ApiResource::from_gvk_with_plural(&gvk, &discovered.name))
+}
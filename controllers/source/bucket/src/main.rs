@@ -0,0 +1,362 @@
+use async_trait::async_trait;
+use eyre::{eyre, Result, WrapErr};
+use fluxcd_api_source_bucket::{Bucket, BucketProvider, BucketStatus};
+use fluxcd_bucket::{
+  endpoint_url, AzureCredentials, Bucket as BucketClient, CredentialsError, Error as BucketError,
+  Object, S3Credentials, ServiceAccountKey, ACCESS_KEY_KEY, ACCOUNT_KEY_KEY, SAS_KEY_KEY,
+  SECRET_KEY_KEY, SERVICE_ACCOUNT_KEY,
+};
+use fluxcd_meta::{
+  mark_ready, mark_reconciling, mark_stalled, resolve_timeout, Artifact, Reason, DEFAULT_TIMEOUT,
+  RECONCILE_REQUEST_ANNOTATION,
+};
+use fluxcd_sourceignore::{SourceIgnore, SourceIgnoreBuilder, IGNORE_FILE};
+use fluxcd_utils_cap::{
+  archive,
+  artifact::{checksum, ArtifactStorage},
+  context::ReconcileCtx,
+  fanout::try_join_limited,
+  flux_controller,
+  http::HttpConfig,
+  metrics,
+  predicate::Predicates,
+  rbac::{Permissions, READ},
+  secrets::{secret_reason, SecretCheck},
+  Controller, ControllerApp,
+};
+use k8s_openapi::{apimachinery::pkg::apis::meta::v1::Time, chrono::Utc};
+use kube::{
+  api::{Patch, PatchParams},
+  runtime::controller::ReconcilerAction,
+  Api, Resource, ResourceExt,
+};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::{fmt::Write, sync::Arc, time::SystemTime};
+use tokio::fs;
+
+const CRATE_NAME: &str = env!("CARGO_PKG_NAME");
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Region of S3 compatible buckets, when the spec does not specify one.
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// File name of the artifact of every Bucket.
+const ARTIFACT_FILE: &str = "bucket.tar.gz";
+
+/// How many objects are downloaded at once.
+const DOWNLOAD_CONCURRENCY: usize = 8;
+
+#[derive(Debug, thiserror::Error)]
+#[error("artifacts are not stored, the controller has to run with --storage-path")]
+struct NoArtifactStorage;
+
+/// Whether `error` will keep failing until the resource (or the bucket) changes, rather than being
+/// resolved by retrying.
+fn is_stalled(error: &eyre::Report) -> bool {
+  let permanent = |error: &reqwest::Error| {
+    matches!(
+      error.status(),
+      Some(StatusCode::NOT_FOUND | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+    )
+  };
+
+  error.chain().any(|cause| {
+    if cause.is::<NoArtifactStorage>() || cause.is::<CredentialsError>() {
+      return true;
+    }
+
+    match cause.downcast_ref::<BucketError>() {
+      Some(
+        BucketError::InvalidEndpoint(_)
+        | BucketError::InsecureEndpoint(_)
+        | BucketError::InvalidPrivateKey,
+      ) => true,
+      Some(BucketError::Http(error)) => permanent(error),
+      _ => false,
+    }
+  })
+}
+
+/// The patterns of the `.sourceignore` file at the root of the bucket and the `ignore` of the
+/// spec, in that order, so the spec can re-include what the file excludes.
+fn ignore_rules(source_ignore: Option<&str>, ignore: Option<&str>) -> Result<SourceIgnore> {
+  let mut builder = SourceIgnoreBuilder::new();
+  if let Some(source_ignore) = source_ignore {
+    builder.add_ignore_file(IGNORE_FILE, source_ignore)?;
+  }
+  if let Some(ignore) = ignore {
+    builder.add_patterns(ignore)?;
+  }
+
+  Ok(builder.build())
+}
+
+/// The revision of the content of `objects`: the checksum of their keys and entity tags, so that
+/// it is known before any object is downloaded.
+fn revision(objects: &[Object]) -> String {
+  let mut index = String::new();
+  for object in objects {
+    let _ = writeln!(index, "{} {}", object.key, object.etag);
+  }
+
+  checksum(index.as_bytes())
+}
+
+/// The outcome of fetching a bucket.
+struct Fetched {
+  objects: usize,
+  artifact: Artifact,
+}
+
+/// The status recording the outcome of a reconcile: `Ready` once the artifact is stored, and
+/// otherwise either `Stalled` if retrying will not help, or `Reconciling` while it is retried.
+fn status_patch(
+  status: Option<&BucketStatus>,
+  generation: Option<i64>,
+  bucket_name: &str,
+  result: &Result<Fetched>,
+) -> Value {
+  let mut conditions = status.map(|s| s.conditions.clone()).unwrap_or_default();
+  let fetched = match result {
+    Ok(fetched) => fetched,
+    Err(error) => {
+      let message = format!("{error:#}");
+      if is_stalled(error) {
+        mark_stalled(&mut conditions, generation, Reason::Failed, message);
+      } else {
+        // a missing secret is retried with backoff until it is created
+        let reason = secret_reason(error).unwrap_or(Reason::Progressing);
+        let message = format!("retrying after: {message}");
+        mark_reconciling(&mut conditions, generation, reason, message);
+      }
+
+      return json!({
+        "status": {
+          "conditions": conditions,
+        }
+      });
+    }
+  };
+
+  let message = format!(
+    "stored artifact of {} object(s) of '{bucket_name}' for revision '{}'",
+    fetched.objects,
+    fetched.artifact.revision().unwrap_or_default()
+  );
+  mark_ready(&mut conditions, generation, Reason::Succeeded, message);
+
+  json!({
+    "status": {
+      "conditions": conditions,
+      "artifact": fetched.artifact,
+      "objectCount": fetched.objects,
+      "lastFetchTime": Time(Utc::now()),
+    }
+  })
+}
+
+struct BucketController {
+  metrics: metrics::Recorder,
+  http: reqwest::Client,
+}
+
+impl BucketController {
+  fn new() -> Result<Self> {
+    let metrics = metrics::Recorder::new()?;
+    // not https only, as buckets may be served over plain http with `insecure`, and the metadata
+    // server of Google Cloud only is
+    let builder = reqwest::Client::builder().user_agent(USER_AGENT);
+    let http = HttpConfig::from_env()?.apply(builder).build()?;
+
+    Ok(Self { metrics, http })
+  }
+
+  /// The client of the bucket of `resource`, with the credentials of its secret.
+  async fn client(&self, ctx: &ReconcileCtx, resource: &Bucket) -> Result<BucketClient> {
+    let spec = &resource.spec;
+    let namespace = resource.namespace().unwrap_or_default();
+    let data = match spec.secret_ref.as_ref().and_then(|r| r.name()) {
+      Some(name) => {
+        // checked before anything is fetched, so that a missing key is reported as such
+        let check = SecretCheck::new(&namespace, name);
+        let check = match spec.provider {
+          BucketProvider::Generic | BucketProvider::Aws => {
+            check.require(ACCESS_KEY_KEY).require(SECRET_KEY_KEY)
+          }
+          BucketProvider::Gcp => check.require(SERVICE_ACCOUNT_KEY),
+          BucketProvider::Azure => check.require_any([ACCOUNT_KEY_KEY, SAS_KEY_KEY]),
+        };
+        Some(check.fetch(ctx.client()).await?)
+      }
+      None => None,
+    };
+
+    let endpoint = endpoint_url(&spec.endpoint, spec.insecure)?;
+    let http = self.http.clone();
+    let bucket = &spec.bucket_name;
+    let client = match spec.provider {
+      BucketProvider::Generic | BucketProvider::Aws => {
+        let credentials = data
+          .as_ref()
+          .map(S3Credentials::from_secret_data)
+          .transpose()?;
+        let region = spec.region.as_deref().unwrap_or(DEFAULT_REGION);
+        BucketClient::s3(http, endpoint, bucket, region, credentials)
+      }
+      BucketProvider::Gcp => {
+        let key = data
+          .as_ref()
+          .map(ServiceAccountKey::from_secret_data)
+          .transpose()?;
+        BucketClient::gcs(http, endpoint, bucket, key)
+      }
+      BucketProvider::Azure => {
+        let credentials = data
+          .as_ref()
+          .map(AzureCredentials::from_secret_data)
+          .transpose()?;
+        BucketClient::azure(http, endpoint, bucket, credentials)?
+      }
+    };
+
+    Ok(client)
+  }
+
+  /// Lists the objects of the bucket which are not ignored, sorted by key.
+  async fn list(&self, client: &BucketClient, resource: &Bucket) -> Result<Vec<Object>> {
+    let spec = &resource.spec;
+    let mut objects = client.list(spec.prefix.as_deref()).await?;
+    // directory placeholders, as created by the consoles of the providers
+    objects.retain(|object| !object.key.ends_with('/'));
+
+    let mut source_ignore = None;
+    if objects.iter().any(|o| o.key == IGNORE_FILE) {
+      let content = client.get(IGNORE_FILE).await?;
+      source_ignore = Some(String::from_utf8(content).wrap_err("invalid .sourceignore")?);
+    }
+
+    let rules = ignore_rules(source_ignore.as_deref(), spec.ignore.as_deref())?;
+    objects.retain(|object| !rules.matches(&object.key, false));
+    objects.sort_by(|a, b| a.key.cmp(&b.key));
+
+    Ok(objects)
+  }
+
+  /// Publishes the objects of the bucket as an artifact, unless the artifact of the last
+  /// reconcile already has the revision of the objects.
+  async fn fetch(&self, ctx: &ReconcileCtx, resource: &Bucket) -> Result<Fetched> {
+    let storage = ctx.artifacts().ok_or(NoArtifactStorage)?;
+    let spec = &resource.spec;
+    let timeout = resolve_timeout(spec.timeout, DEFAULT_TIMEOUT, Some(spec.interval));
+    let timeout = timeout.min(ctx.remaining());
+
+    let client = self.client(ctx, resource).await?;
+    let fetched = async {
+      let objects = self.list(&client, resource).await?;
+      let revision = revision(&objects);
+      let path = ArtifactStorage::artifact_path(
+        &Bucket::kind(&()),
+        resource.namespace().as_deref(),
+        &resource.name(),
+        ARTIFACT_FILE,
+      );
+
+      // nothing is downloaded while the objects are the same as those of the stored artifact
+      let previous = resource.status.as_ref().and_then(|s| s.artifact.as_ref());
+      let unchanged =
+        previous.filter(|p| p.path() == Some(&*path) && p.revision() == Some(&*revision));
+      if let Some((previous, file)) = unchanged.zip(storage.resolve(&path)) {
+        if fs::metadata(&file).await.is_ok() {
+          return Ok(Fetched {
+            objects: objects.len(),
+            artifact: previous.clone(),
+          });
+        }
+      }
+
+      let downloads = objects.iter().map(|object| {
+        let download = async move { client.get(&object.key).await.map(|c| (object.clone(), c)) };
+        (object.key.as_str(), download)
+      });
+      let downloaded = try_join_limited(DOWNLOAD_CONCURRENCY, downloads)
+        .await
+        .into_complete()?
+        .into_iter()
+        .map(|(_, downloaded)| downloaded)
+        .collect::<Vec<_>>();
+
+      let content = archive::pack(downloaded.iter().map(|(o, c)| (&o.key, c)))
+        .wrap_err("failed to archive the bucket objects")?;
+      let artifact = storage
+        .update(previous, &path, &revision, &content)
+        .await
+        .wrap_err("failed to store the bucket artifact")?;
+
+      Ok(Fetched {
+        objects: objects.len(),
+        artifact,
+      })
+    };
+
+    tokio::time::timeout(timeout, fetched)
+      .await
+      .map_err(|_| eyre!("timed out fetching bucket '{}'", resource.spec.bucket_name))?
+  }
+}
+
+#[flux_controller(suspend = spec.suspend, interval = spec.interval)]
+#[async_trait]
+impl Controller<Bucket> for BucketController {
+  async fn reconcile(
+    self: Arc<Self>,
+    resource: Arc<Bucket>,
+    ctx: ReconcileCtx,
+  ) -> Result<ReconcilerAction> {
+    let fetched = self.fetch(&ctx, &resource).await;
+    let status = status_patch(
+      resource.status.as_ref(),
+      resource.metadata.generation,
+      &resource.spec.bucket_name,
+      &fetched,
+    );
+
+    let namespace = resource.namespace().unwrap_or_default();
+    let patched = Api::<Bucket>::namespaced(ctx.client().clone(), &namespace)
+      .patch_status(
+        &resource.name(),
+        &PatchParams::default(),
+        &Patch::Merge(&status),
+      )
+      .await;
+    // the error of fetching the bucket takes precedence over that of recording it
+    fetched?;
+    patched?;
+
+    Ok(ReconcilerAction {
+      requeue_after: Self::reconcile_interval(&resource),
+    })
+  }
+
+  fn permissions() -> Permissions {
+    Permissions::default().namespaced("", "secrets", READ)
+  }
+
+  fn predicates() -> Predicates {
+    // status patches do not bump the generation, so they do not trigger another fetch
+    Predicates::generation().annotation(RECONCILE_REQUEST_ANNOTATION)
+  }
+
+  fn last_reconciled(resource: &Bucket) -> Option<SystemTime> {
+    let time = resource.status.as_ref()?.last_fetch_time.as_ref()?;
+    Some(time.0.into())
+  }
+}
+
+fn main() -> Result<()> {
+  ControllerApp::main(CRATE_NAME, CRATE_VERSION, |app| {
+    Ok(app.controller(BucketController::new()?))
+  })
+}
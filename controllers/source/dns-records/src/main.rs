@@ -0,0 +1,368 @@
+use async_trait::async_trait;
+use eyre::{bail, Result, WrapErr};
+use fluxcd_api_source_dns_records::{DnsRecordType, DnsRecords, DnsRecordsReason};
+use fluxcd_meta::{
+  mark_ready, mark_reconciling, mark_stalled, new_condition, remove_condition, resolve_timeout,
+  set_condition, Condition as MetaCondition, Reason, StalePolicy, Verification, VerificationMethod,
+  DEFAULT_TIMEOUT, RECONCILE_REQUEST_ANNOTATION,
+};
+use fluxcd_utils_cap::{
+  artifact::checksum,
+  context::ReconcileCtx,
+  flux_controller, metrics,
+  output::{output_hash, OutputCache},
+  owned::controller_owner_ref,
+  predicate::Predicates,
+  rbac::{Permissions, READ, WRITE},
+  Controller, ControllerApp,
+};
+use k8s_openapi::{
+  api::core::v1::ConfigMap,
+  apimachinery::pkg::apis::meta::v1::{Condition, Time},
+  chrono::Utc,
+};
+use kube::{
+  api::{ObjectMeta, Patch, PatchParams},
+  runtime::controller::ReconcilerAction,
+  Api, Client, Resource, ResourceExt,
+};
+use serde_json::{json, Value};
+use std::{
+  collections::BTreeMap,
+  fmt::Write,
+  net::{IpAddr, SocketAddr},
+  sync::Arc,
+  time::{Duration, SystemTime},
+};
+use thiserror::Error;
+use tracing::warn;
+use trust_dns_resolver::{
+  config::{NameServerConfigGroup, ResolverConfig},
+  proto::{
+    rr::{RData, RecordType},
+    xfer::DnsRequestOptions,
+  },
+  system_conf::read_system_conf,
+  TokioAsyncResolver,
+};
+
+const CRATE_NAME: &str = env!("CARGO_PKG_NAME");
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Error)]
+#[error("invalid nameserver '{0}'")]
+struct InvalidNameserver(String);
+
+/// The records resolved, once written to the target.
+struct Resolved {
+  records: usize,
+  checksum: String,
+  output_hash: Option<String>,
+}
+
+struct DnsRecordsController {
+  metrics: metrics::Recorder,
+  outputs: OutputCache,
+}
+
+impl DnsRecordsController {
+  pub fn new() -> Result<Self> {
+    let metrics = metrics::Recorder::new()?;
+
+    Ok(Self {
+      metrics,
+      outputs: OutputCache::new(),
+    })
+  }
+
+  /// Resolves the records of `resource`, and writes them to the target.
+  async fn refresh(&self, ctx: &ReconcileCtx, resource: &DnsRecords) -> Result<Resolved> {
+    let spec = &resource.spec;
+    let timeout = resolve_timeout(spec.timeout, DEFAULT_TIMEOUT, Some(spec.interval));
+    let timeout = timeout.min(ctx.remaining());
+
+    let records = resolve(resource, timeout).await?;
+    let count = records.len();
+    let mut content = records.join("\n");
+    content.push('\n');
+    let checksum = checksum(content.as_bytes());
+
+    let namespace = resource.namespace().unwrap_or_default();
+    let target = &resource.spec.target;
+    let config_map = ConfigMap {
+      metadata: ObjectMeta {
+        name: Some(target.name.clone()),
+        namespace: Some(namespace.clone()),
+        owner_references: controller_owner_ref(resource).map(|r| vec![r]),
+        ..Default::default()
+      },
+      data: Some(BTreeMap::from([(target.key.clone(), content)])),
+      ..Default::default()
+    };
+
+    ctx.admit(resource, &config_map).await?;
+
+    // skip applying output identical to what was applied last time
+    let output_hash = output_hash(&config_map)?;
+    let uid = resource.uid().unwrap_or_default();
+    let persisted = resource
+      .status
+      .as_ref()
+      .and_then(|s| s.output_hash.as_deref());
+    let applied = if self.outputs.is_unchanged(&uid, &output_hash, persisted) {
+      self.metrics.record_noop(&resource.object_ref(&()));
+      Some(output_hash)
+    } else {
+      let api = Api::<ConfigMap>::namespaced(ctx.client().clone(), &namespace);
+      ctx.apply(resource, &api, CRATE_NAME, &config_map).await?;
+      if ctx.is_read_only() {
+        // nothing was applied, so the output is applied once writes are allowed
+        persisted.map(String::from)
+      } else {
+        self.outputs.record(uid, output_hash.clone());
+        Some(output_hash)
+      }
+    };
+
+    Ok(Resolved {
+      records: count,
+      checksum,
+      output_hash: applied,
+    })
+  }
+}
+
+fn resolver(resource: &DnsRecords, timeout: Duration) -> Result<TokioAsyncResolver> {
+  let (config, mut opts) = read_system_conf()?;
+  let config = if resource.spec.nameservers.is_empty() {
+    config
+  } else {
+    let mut group = NameServerConfigGroup::new();
+    for nameserver in &resource.spec.nameservers {
+      let addr = match nameserver.parse::<SocketAddr>() {
+        Ok(addr) => addr,
+        Err(_) => SocketAddr::new(
+          nameserver
+            .parse::<IpAddr>()
+            .map_err(|_| InvalidNameserver(nameserver.clone()))?,
+          53,
+        ),
+      };
+
+      group.merge(NameServerConfigGroup::from_ips_clear(
+        &[addr.ip()],
+        addr.port(),
+        true,
+      ));
+    }
+
+    ResolverConfig::from_parts(None, vec![], group)
+  };
+
+  opts.timeout = timeout;
+  opts.validate = resource.spec.dnssec;
+  Ok(TokioAsyncResolver::tokio(config, opts)?)
+}
+
+async fn resolve(resource: &DnsRecords, timeout: Duration) -> Result<Vec<String>> {
+  let resolver = resolver(resource, timeout)?;
+  let name = &resource.spec.name;
+  let record_type = match resource.spec.record_type {
+    DnsRecordType::Txt => RecordType::TXT,
+    DnsRecordType::Sshfp => RecordType::SSHFP,
+  };
+
+  let lookup = resolver
+    .lookup(name.as_str(), record_type, DnsRequestOptions::default())
+    .await
+    .wrap_err_with(|| format!("failed to resolve {record_type} records for '{name}'"))?;
+
+  let mut records = Vec::new();
+  for rdata in lookup.iter() {
+    match rdata {
+      RData::TXT(txt) => records.push(
+        txt
+          .iter()
+          .map(|s| String::from_utf8_lossy(s))
+          .collect::<String>(),
+      ),
+
+      RData::SSHFP(sshfp) => {
+        let mut record = format!(
+          "{} {} ",
+          u8::from(sshfp.algorithm()),
+          u8::from(sshfp.fingerprint_type())
+        );
+
+        for byte in sshfp.fingerprint() {
+          let _ = write!(record, "{byte:02x}");
+        }
+
+        records.push(record);
+      }
+
+      _ => (),
+    }
+  }
+
+  if records.is_empty() {
+    bail!("no {record_type} records found for '{name}'");
+  }
+
+  // resolvers may return records in any order, sort them to get stable output
+  records.sort();
+  records.dedup();
+  Ok(records)
+}
+
+/// Whether `error` will keep failing until the resource changes, rather than being resolved by
+/// retrying.
+fn is_stalled(error: &eyre::Report) -> bool {
+  error.chain().any(|cause| cause.is::<InvalidNameserver>())
+}
+
+/// The ContentStale condition of `resource` once its records have not been refreshed for longer
+/// than `maxAge`.
+fn stale_condition(resource: &DnsRecords) -> Option<Condition> {
+  let max_age = resource.spec.max_age?;
+  let last_fetch = resource.status.as_ref()?.last_fetch_time.as_ref()?.0;
+  let age = (Utc::now() - last_fetch).to_std().unwrap_or_default();
+  if !matches!(max_age.to_std(), Some(max_age) if age > max_age) {
+    return None;
+  }
+
+  let message = format!(
+    "records were last resolved at {}, longer ago than the max age of {max_age}",
+    last_fetch.to_rfc3339()
+  );
+  Some(new_condition(
+    MetaCondition::ContentStale,
+    true,
+    DnsRecordsReason::MaxAgeExceeded,
+    message,
+    resource.metadata.generation,
+  ))
+}
+
+/// The status patch recording the outcome of a reconcile of `resource`.
+fn status_patch(resource: &DnsRecords, result: &Result<Resolved>) -> Value {
+  let generation = resource.metadata.generation;
+  let mut conditions = resource
+    .status
+    .as_ref()
+    .map(|s| s.conditions.clone())
+    .unwrap_or_default();
+
+  let resolved = match result {
+    Ok(resolved) => resolved,
+    Err(error) => {
+      if let Some(stale) = stale_condition(resource) {
+        set_condition(&mut conditions, stale);
+      }
+
+      let message = format!("{error:#}");
+      if is_stalled(error) {
+        mark_stalled(&mut conditions, generation, Reason::Failed, message);
+      } else {
+        let message = format!("retrying after: {message}");
+        mark_reconciling(&mut conditions, generation, Reason::Progressing, message);
+      }
+
+      return json!({
+        "status": {
+          "conditions": conditions,
+        }
+      });
+    }
+  };
+
+  remove_condition(&mut conditions, MetaCondition::ContentStale);
+  let message = format!(
+    "wrote {} record(s) of '{}'",
+    resolved.records, resource.spec.name
+  );
+  mark_ready(&mut conditions, generation, Reason::Succeeded, message);
+
+  // plain DNS answers carry nothing to verify them by
+  let verification = Verification::new(&resolved.checksum, VerificationMethod::None);
+  json!({
+    "status": {
+      "checksum": resolved.checksum,
+      "verification": verification,
+      "lastFetchTime": Time(Utc::now()),
+      "conditions": conditions,
+      "outputHash": resolved.output_hash,
+    }
+  })
+}
+
+async fn patch_status(
+  client: &Client,
+  resource: &DnsRecords,
+  status: &serde_json::Value,
+) -> Result<()> {
+  let namespace = resource.namespace().unwrap_or_default();
+  Api::<DnsRecords>::namespaced(client.clone(), &namespace)
+    .patch_status(
+      &resource.name(),
+      &PatchParams::default(),
+      &Patch::Merge(status),
+    )
+    .await?;
+
+  Ok(())
+}
+
+#[flux_controller(suspend = spec.suspend, interval = spec.interval)]
+#[async_trait]
+impl Controller<DnsRecords> for DnsRecordsController {
+  async fn reconcile(
+    self: Arc<Self>,
+    resource: Arc<DnsRecords>,
+    ctx: ReconcileCtx,
+  ) -> Result<ReconcilerAction> {
+    let resolved = self.refresh(&ctx, &resource).await;
+    if resolved.is_err()
+      && resource.spec.stale_policy == StalePolicy::Remove
+      && stale_condition(&resource).is_some()
+    {
+      // the resolve error is what the resource reports, whether or not the removal succeeds
+      let namespace = resource.namespace().unwrap_or_default();
+      let api = Api::<ConfigMap>::namespaced(ctx.client().clone(), &namespace);
+      if let Err(remove_error) = ctx.delete(&api, &resource.spec.target.name).await {
+        warn!(error = %remove_error, "failed to remove the stale records");
+      }
+    }
+
+    let status = status_patch(&resource, &resolved);
+    let patched = patch_status(ctx.client(), &resource, &status).await;
+    resolved?;
+    patched?;
+
+    Ok(ReconcilerAction {
+      requeue_after: Self::reconcile_interval(&resource),
+    })
+  }
+
+  fn permissions() -> Permissions {
+    Permissions::default()
+      .namespaced("", "configmaps", READ)
+      .namespaced("", "configmaps", WRITE)
+  }
+
+  fn predicates() -> Predicates {
+    // status patches do not bump the generation, so they do not trigger another fetch
+    Predicates::generation().annotation(RECONCILE_REQUEST_ANNOTATION)
+  }
+
+  fn last_reconciled(resource: &DnsRecords) -> Option<SystemTime> {
+    let time = resource.status.as_ref()?.last_fetch_time.as_ref()?;
+    Some(time.0.into())
+  }
+}
+
+fn main() -> Result<()> {
+  ControllerApp::main(CRATE_NAME, CRATE_VERSION, |app| {
+    Ok(app.controller(DnsRecordsController::new()?))
+  })
+}
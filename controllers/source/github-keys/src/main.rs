@@ -1,45 +1,1107 @@
+mod push;
+
 use async_trait::async_trait;
-use eyre::Result;
-use fluxcd_api_source_github_keys::GitHubUserSshKeys;
-use fluxcd_utils_cap::{metrics, Controller, ControllerApp};
-use kube::runtime::controller::ReconcilerAction;
+use eyre::{bail, eyre, Result, WrapErr};
+use fluxcd_api_source_github_keys::{
+  ClusterGitHubUserSshKeys, GitHubTeamSshKeys, GitHubTeamSshKeysStatus, GitHubUserSshKeys,
+  GitHubUserSshKeysStatus, KeyFilter, SecretProjection,
+};
+use fluxcd_github::{
+  Credentials, CredentialsError, Error as GitHubError, GitHub, APP_ID_KEY, APP_INSTALLATION_ID_KEY,
+  APP_PRIVATE_KEY_KEY, TOKEN_KEY,
+};
+use fluxcd_meta::{
+  mark_ready, mark_reconciling, mark_stalled, resolve_timeout, Artifact, FailedItems, ItemFailure,
+  OciPushTarget, OciPushedArtifact, Reason, Verification, VerificationMethod, DEFAULT_TIMEOUT,
+  RECONCILE_REQUEST_ANNOTATION,
+};
+use fluxcd_ssh_keys::{
+  aggregate, combined, parse_authorized_keys, per_key, Algorithm, Filter, PublicKey,
+  UnsupportedAlgorithm,
+};
+use fluxcd_utils_cap::{
+  artifact::{checksum, ArtifactStorage},
+  context::ReconcileCtx,
+  fanout::try_join_limited,
+  flux_controller,
+  http::{HttpClient, HttpConfig},
+  metrics,
+  output::{output_hash, OutputCache},
+  owned::{gc_owned, inventory_id, own},
+  predicate::Predicates,
+  rbac::{Permissions, READ, WRITE},
+  references::ReferenceError,
+  requirements::{KubeVersion, Requirements},
+  secrets::{secret_reason, SecretCheck},
+  Controller, ControllerApp,
+};
+use k8s_openapi::{
+  api::core::v1::{Namespace, Secret},
+  apimachinery::pkg::apis::meta::v1::{Condition, LabelSelector, Time},
+  chrono::Utc,
+  ByteString,
+};
+use kube::{
+  api::{ListParams, ObjectMeta, Patch, PatchParams},
+  runtime::controller::ReconcilerAction,
+  Api, Resource, ResourceExt,
+};
+use push::{Content, Pusher};
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::{
+  collections::BTreeMap,
+  fmt::Debug,
+  sync::Arc,
+  time::{Duration, Instant, SystemTime},
+};
+use thiserror::Error;
 
 const CRATE_NAME: &str = env!("CARGO_PKG_NAME");
 // const CRATE_DESC: &str = env!("CARGO_PKG_DESCRIPTION");
 const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Key of the Secret entry holding all keys, with the `Combined` projection.
+const AUTHORIZED_KEYS_KEY: &str = "authorized_keys";
+
+/// How many members of a team have their keys fetched at once.
+const MEMBER_CONCURRENCY: usize = 8;
+
+#[derive(Debug, Error)]
+#[error("'{0}' is not a valid GitHub user name")]
+struct InvalidUser(String);
+
+#[derive(Debug, Error)]
+#[error("'{0}/{1}' is not a valid GitHub team")]
+struct InvalidTeam(String, String);
+
+#[derive(Debug, Error)]
+#[error("secretRef must name the secret holding the GitHub credentials")]
+struct MissingSecretName;
+
+/// The keys of the members of a team.
+struct TeamKeys {
+  members: usize,
+  keys: Vec<PublicKey>,
+
+  /// The members whose keys could not be fetched, and why.
+  failures: Vec<ItemFailure>,
+}
+
+/// Fetches the public SSH keys of GitHub users. Shared by all controllers, so GitHub App
+/// installation tokens are cached across them.
+struct KeyFetcher {
+  http: HttpClient,
+  github: GitHub,
+}
+
+impl KeyFetcher {
+  fn new() -> Result<Self> {
+    let builder = || {
+      reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .https_only(true)
+    };
+    let http = HttpClient::new("github-keys", builder, &HttpConfig::from_env()?)?;
+
+    Ok(Self {
+      http,
+      github: GitHub::new(USER_AGENT)?,
+    })
+  }
+
+  /// Fetches the keys of `user` through the API if `credentials` are given, and otherwise from
+  /// the public `https://github.com/<user>.keys` listing.
+  async fn fetch(
+    &self,
+    user: &str,
+    credentials: Option<&Credentials>,
+    timeout: Duration,
+  ) -> Result<Vec<PublicKey>> {
+    validate_user(user)?;
+
+    let content = match credentials {
+      Some(credentials) => {
+        let keys = tokio::time::timeout(timeout, self.github.user_ssh_keys(credentials, user))
+          .await
+          .map_err(|_| eyre!("timed out fetching the keys of '{user}'"))??;
+        keys
+          .into_iter()
+          .map(|key| key.key)
+          .collect::<Vec<_>>()
+          .join("\n")
+      }
+      None => {
+        let url = format!("https://github.com/{user}.keys");
+        let content = self.http.fetch(self.http.get(url).timeout(timeout)).await?;
+        String::from_utf8(content).wrap_err("keys are not valid utf-8")?
+      }
+    };
+
+    // keys of algorithms which are not supported are left out, rather than failing on all keys
+    Ok(
+      parse_authorized_keys(&content)
+        .filter_map(Result::ok)
+        .collect(),
+    )
+  }
+
+  /// Fetches the keys of every member of the team `team` (by slug) of `org`, sorted and
+  /// deduplicated. Members whose keys fail to fetch are returned with the error instead, so that
+  /// the keys of the others are still written, unless they all failed.
+  async fn fetch_team(
+    &self,
+    org: &str,
+    team: &str,
+    credentials: &Credentials,
+    timeout: Duration,
+  ) -> Result<TeamKeys> {
+    validate_team(org, team)?;
+
+    // the timeout applies to the team as a whole, rather than to every member
+    let deadline = Instant::now() + timeout;
+    let remaining = || deadline.saturating_duration_since(Instant::now());
+    let members = tokio::time::timeout(
+      remaining(),
+      self.github.team_members(credentials, org, team),
+    )
+    .await
+    .map_err(|_| eyre!("timed out listing the members of '{org}/{team}'"))?
+    .wrap_err_with(|| format!("failed to list the members of '{org}/{team}'"))?;
+
+    let fetches = members.iter().map(|member| {
+      let login = member.login.as_str();
+      (login, self.fetch(login, Some(credentials), remaining()))
+    });
+    let mut fetched = try_join_limited(MEMBER_CONCURRENCY, fetches).await;
+    let failures = fetched.failures();
+
+    // once rate limited, the keys of the other members are left out as well
+    let rate_limited = fetched
+      .failed
+      .iter()
+      .position(|(_, error)| retry_after(error).is_some());
+    if let Some(index) = rate_limited {
+      let (login, error) = fetched.failed.swap_remove(index);
+      return Err(error.wrap_err(format!("failed to fetch the keys of '{login}'")));
+    }
+
+    if fetched.succeeded.is_empty() {
+      if let Some((login, error)) = fetched.failed.into_iter().next() {
+        let count = members.len();
+        return Err(error.wrap_err(format!(
+          "failed to fetch the keys of all {count} members of '{org}/{team}', like '{login}'"
+        )));
+      }
+    }
+
+    let keys = fetched
+      .succeeded
+      .into_iter()
+      .flat_map(|(_, keys)| keys)
+      .collect();
+
+    Ok(TeamKeys {
+      members: members.len(),
+      keys: aggregate(keys),
+      failures,
+    })
+  }
+}
+
+/// Rejects names which GitHub does not allow, as the name is part of the URL the keys are
+/// fetched from.
+fn validate_user(user: &str) -> Result<(), InvalidUser> {
+  if !is_valid_login(user) {
+    return Err(InvalidUser(user.to_string()));
+  }
+
+  Ok(())
+}
+
+/// Rejects teams which GitHub does not allow, as the organization and the slug of the team are
+/// part of the URL its members are listed from. Organizations share the names of users.
+fn validate_team(org: &str, team: &str) -> Result<(), InvalidTeam> {
+  let valid_slug = !team.is_empty()
+    && team
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+  if !is_valid_login(org) || !valid_slug {
+    return Err(InvalidTeam(org.to_string(), team.to_string()));
+  }
+
+  Ok(())
+}
+
+/// The filter of the keys which are written, from the `filter` of the spec. Fails on algorithms
+/// which are not supported, rather than never writing any key.
+fn key_filter(filter: Option<&KeyFilter>) -> Result<Filter, UnsupportedAlgorithm> {
+  let filter = match filter {
+    Some(filter) => filter,
+    None => return Ok(Filter::new()),
+  };
+
+  let mut key_filter = Filter::new();
+  if !filter.algorithms.is_empty() {
+    let algorithms = filter
+      .algorithms
+      .iter()
+      .map(|name| name.parse())
+      .collect::<Result<Vec<Algorithm>, _>>()?;
+    key_filter = key_filter.algorithms(algorithms);
+  }
+
+  if let Some(bits) = filter.min_rsa_bits {
+    key_filter = key_filter.min_bits(Algorithm::Rsa, bits);
+  }
+
+  Ok(key_filter)
+}
+
+fn is_valid_login(login: &str) -> bool {
+  !login.is_empty()
+    && login.len() <= 39
+    && !login.starts_with('-')
+    && !login.ends_with('-')
+    && login.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+async fn credentials(ctx: &ReconcileCtx, namespace: &str, name: &str) -> Result<Credentials> {
+  // checked before anything is fetched, so that a missing key is reported as such
+  let check = SecretCheck::new(namespace, name).require_any([TOKEN_KEY, APP_ID_KEY]);
+  let secret = ctx.secrets().get(namespace, name).await?;
+  let data = secret.data();
+  check.validate(data)?;
+  if data.contains_key(APP_ID_KEY) {
+    check
+      .require(APP_INSTALLATION_ID_KEY)
+      .require(APP_PRIVATE_KEY_KEY)
+      .validate(data)?;
+  }
+
+  Credentials::from_secret_data(data)
+    .wrap_err_with(|| format!("invalid credentials in secret '{namespace}/{name}'"))
+}
+
+/// Whether `error` will keep failing until the resource (or the GitHub account) changes, rather
+/// than being resolved by retrying.
+fn is_stalled(error: &eyre::Report) -> bool {
+  let permanent = |error: &reqwest::Error| {
+    matches!(
+      error.status(),
+      Some(StatusCode::NOT_FOUND | StatusCode::UNAUTHORIZED)
+    )
+  };
+
+  error.chain().any(|cause| {
+    if cause.is::<InvalidUser>()
+      || cause.is::<InvalidTeam>()
+      || cause.is::<MissingSecretName>()
+      || cause.is::<UnsupportedAlgorithm>()
+      || cause.is::<CredentialsError>()
+      || cause.is::<ReferenceError>()
+      || push::is_stalled(cause)
+    {
+      return true;
+    }
+
+    match cause.downcast_ref::<GitHubError>() {
+      Some(
+        GitHubError::InvalidPrivateKey | GitHubError::NotFound(_) | GitHubError::Unauthorized,
+      ) => true,
+      Some(_) => false,
+      // the public keys listing is not fetched through the API
+      None => cause
+        .downcast_ref::<reqwest::Error>()
+        .is_some_and(permanent),
+    }
+  })
+}
+
+/// How long to wait before retrying after `error`: at least until the GitHub rate limit resets,
+/// as requests fail without reaching GitHub until then.
+fn retry_delay(error: &eyre::Report, retry: Duration) -> Duration {
+  retry_after(error).map_or(retry, |retry_after| retry_after.max(retry))
+}
+
+/// How long until the GitHub rate limit resets, if `error` is caused by it.
+fn retry_after(error: &eyre::Report) -> Option<Duration> {
+  error
+    .chain()
+    .filter_map(|cause| cause.downcast_ref::<GitHubError>()?.retry_after())
+    .max()
+}
+
+fn secret_data(keys: &[PublicKey], projection: SecretProjection) -> BTreeMap<String, ByteString> {
+  let entries = match projection {
+    SecretProjection::Combined => {
+      BTreeMap::from([(AUTHORIZED_KEYS_KEY.to_string(), combined(keys))])
+    }
+    SecretProjection::PerKey => per_key(keys),
+  };
+
+  entries
+    .into_iter()
+    .map(|(key, value)| (key, ByteString(value.into_bytes())))
+    .collect()
+}
+
+fn secret<K>(owner: &K, name: &str, namespace: &str, data: BTreeMap<String, ByteString>) -> Secret
+where
+  K: Resource<DynamicType = ()>,
+{
+  let mut metadata = ObjectMeta {
+    name: Some(name.to_string()),
+    namespace: Some(namespace.to_string()),
+    ..Default::default()
+  };
+  own(owner, &mut metadata);
+
+  Secret {
+    metadata,
+    data: Some(data),
+    ..Default::default()
+  }
+}
+
+/// Applies `secrets` on behalf of `owner`, unless they are identical to the `persisted` output
+/// applied last time or a policy vetoes them. Returns the hash of the output applied, which is the
+/// one applied before if the context is read-only.
+async fn apply_secrets<K>(
+  ctx: &ReconcileCtx,
+  outputs: &OutputCache,
+  metrics: &metrics::Recorder,
+  owner: &K,
+  persisted: Option<&str>,
+  secrets: &[Secret],
+) -> Result<Option<String>>
+where
+  K: Resource<DynamicType = ()> + Serialize,
+{
+  for secret in secrets {
+    ctx.admit(owner, secret).await?;
+  }
+
+  let hash = output_hash(&secrets)?;
+  let uid = owner.uid().unwrap_or_default();
+  if outputs.is_unchanged(&uid, &hash, persisted) {
+    metrics.record_noop(&owner.object_ref(&()));
+    return Ok(Some(hash));
+  }
+
+  for secret in secrets {
+    let namespace = secret.namespace().unwrap_or_default();
+    let api = Api::<Secret>::namespaced(ctx.client().clone(), &namespace);
+    ctx.apply(owner, &api, CRATE_NAME, secret).await?;
+  }
+
+  if ctx.is_read_only() {
+    return Ok(persisted.map(Into::into));
+  }
+
+  outputs.record(uid, hash.clone());
+  Ok(Some(hash))
+}
+
+/// Stores the keys as an `authorized_keys` artifact, if the app serves artifacts.
+async fn store_artifact<K>(
+  ctx: &ReconcileCtx,
+  resource: &K,
+  previous: Option<&Artifact>,
+  keys: &[PublicKey],
+) -> Result<Option<Artifact>>
+where
+  K: Resource<DynamicType = ()>,
+{
+  let storage = match ctx.artifacts() {
+    Some(storage) => storage,
+    None => return Ok(None),
+  };
+
+  let content = combined(keys);
+  let path = ArtifactStorage::artifact_path(
+    &K::kind(&()),
+    resource.namespace().as_deref(),
+    &resource.name(),
+    AUTHORIZED_KEYS_KEY,
+  );
+  let revision = checksum(content.as_bytes());
+  let artifact = storage
+    .update(previous, &path, &revision, content.as_bytes())
+    .await
+    .wrap_err("failed to store the keys artifact")?;
+
+  Ok(Some(artifact))
+}
+
+/// Pushes the keys to the OCI repository of `target`, if any.
+async fn push_keys<K>(
+  ctx: &ReconcileCtx,
+  pusher: &Pusher,
+  resource: &K,
+  target: Option<&OciPushTarget>,
+  status: Option<&GitHubUserSshKeysStatus>,
+  user: &str,
+  keys: &[PublicKey],
+) -> Result<Option<OciPushedArtifact>>
+where
+  K: Resource<DynamicType = ()>,
+{
+  let authorized_keys = combined(keys);
+  let revision = checksum(authorized_keys.as_bytes());
+  let content = Content {
+    user,
+    revision: &revision,
+    authorized_keys: &authorized_keys,
+  };
+  let previous = status.and_then(|s| s.pushed_artifact.as_ref());
+
+  pusher
+    .push(
+      ctx,
+      target,
+      resource.namespace().as_deref(),
+      previous,
+      content,
+    )
+    .await
+}
+
+/// The outcome of writing the keys.
+struct Written {
+  keys: usize,
+  digest: String,
+  output_hash: Option<String>,
+  artifact: Option<Artifact>,
+  pushed: Option<OciPushedArtifact>,
+}
+
+/// The outcome of writing the keys of a team.
+struct TeamWritten {
+  written: Written,
+  members: usize,
+
+  /// The members whose keys were left out.
+  failed: FailedItems,
+}
+
+/// The status recording the outcome of a reconcile: `Ready` once the keys are written, and
+/// otherwise either `Stalled` if retrying will not help, or `Reconciling` while it is retried.
+fn status_patch(
+  status: Option<&GitHubUserSshKeysStatus>,
+  generation: Option<i64>,
+  user: &str,
+  result: &Result<Written>,
+) -> Value {
+  let mut conditions = status.map(|s| s.conditions.clone()).unwrap_or_default();
+  let written = match result {
+    Ok(written) => written,
+    Err(error) => {
+      mark_failed(&mut conditions, generation, error);
+      return json!({
+        "status": {
+          "conditions": conditions,
+        }
+      });
+    }
+  };
+
+  let message = format!("wrote {} key(s) of '{user}'", written.keys);
+  mark_ready(&mut conditions, generation, Reason::Succeeded, message);
+
+  json!({
+    "status": {
+      "conditions": conditions,
+      "lastFetchTime": Time(Utc::now()),
+      "keyCount": written.keys,
+      // the keys are published by GitHub unsigned
+      "verification": Verification::new(&written.digest, VerificationMethod::None),
+      "outputHash": written.output_hash,
+      "artifact": written.artifact,
+      "pushedArtifact": written.pushed,
+    }
+  })
+}
+
+/// The status recording the outcome of a reconcile of a team, like [status_patch]. While the keys
+/// of some members fail to fetch, it is `Ready` with the `PartialFailure` reason, and the members
+/// are listed in `failedMembers`.
+fn team_status_patch(
+  status: Option<&GitHubTeamSshKeysStatus>,
+  generation: Option<i64>,
+  team: &str,
+  result: &Result<TeamWritten>,
+) -> Value {
+  let mut conditions = status.map(|s| s.conditions.clone()).unwrap_or_default();
+  let team_written = match result {
+    Ok(written) => written,
+    Err(error) => {
+      mark_failed(&mut conditions, generation, error);
+      return json!({
+        "status": {
+          "conditions": conditions,
+        }
+      });
+    }
+  };
+
+  let written = &team_written.written;
+  match team_written.failed.summary(team_written.members) {
+    Some(summary) => mark_ready(&mut conditions, generation, Reason::PartialFailure, summary),
+    None => {
+      let members = team_written.members;
+      let message = format!(
+        "wrote {} key(s) of {members} member(s) of '{team}'",
+        written.keys
+      );
+      mark_ready(&mut conditions, generation, Reason::Succeeded, message);
+    }
+  }
+
+  // the failures of the previous reconcile are cleared by the merge patch once none are left
+  let failed = Some(&team_written.failed).filter(|failed| !failed.is_empty());
+  json!({
+    "status": {
+      "conditions": conditions,
+      "lastFetchTime": Time(Utc::now()),
+      "memberCount": team_written.members,
+      "keyCount": written.keys,
+      "failedMembers": failed,
+      // the keys are published by GitHub unsigned
+      "verification": Verification::new(&written.digest, VerificationMethod::None),
+      "outputHash": written.output_hash,
+      "artifact": written.artifact,
+    }
+  })
+}
+
+/// Records a failed reconcile in `conditions`: `Stalled` if retrying will not help, and otherwise
+/// `Reconciling` while it is retried.
+fn mark_failed(conditions: &mut Vec<Condition>, generation: Option<i64>, error: &eyre::Report) {
+  let message = format!("{error:#}");
+  if is_stalled(error) {
+    mark_stalled(conditions, generation, Reason::Failed, message);
+  } else {
+    // a missing secret is retried with backoff until it is created
+    let reason = secret_reason(error).unwrap_or(Reason::Progressing);
+    let message = format!("retrying after: {message}");
+    mark_reconciling(conditions, generation, reason, message);
+  }
+}
+
+async fn patch_status<K>(api: Api<K>, name: &str, status: &Value) -> Result<()>
+where
+  K: Clone + DeserializeOwned + Debug,
+{
+  api
+    .patch_status(name, &PatchParams::default(), &Patch::Merge(status))
+    .await?;
+
+  Ok(())
+}
+
+/// Formats `selector` in the syntax of label selector queries, like `app=web,tier in (a,b)`.
+fn label_selector(selector: &LabelSelector) -> Result<String> {
+  let mut requirements = selector
+    .match_labels
+    .iter()
+    .flatten()
+    .map(|(key, value)| format!("{key}={value}"))
+    .collect::<Vec<_>>();
+
+  for expression in selector.match_expressions.iter().flatten() {
+    let key = &expression.key;
+    let values = expression.values.as_deref().unwrap_or_default().join(",");
+    requirements.push(match &*expression.operator {
+      "In" => format!("{key} in ({values})"),
+      "NotIn" => format!("{key} notin ({values})"),
+      "Exists" => key.clone(),
+      "DoesNotExist" => format!("!{key}"),
+      operator => bail!("unsupported label selector operator '{operator}'"),
+    });
+  }
+
+  Ok(requirements.join(","))
+}
+
+fn fetch_timeout(
+  timeout: Option<fluxcd_meta::Duration>,
+  interval: fluxcd_meta::Duration,
+  ctx: &ReconcileCtx,
+) -> Duration {
+  resolve_timeout(timeout, DEFAULT_TIMEOUT, Some(interval)).min(ctx.remaining())
+}
+
+fn last_fetch_time(time: Option<&Time>) -> Option<SystemTime> {
+  Some(time?.0.into())
+}
+
 struct GitHubUserSshKeysController {
   metrics: metrics::Recorder,
+  outputs: OutputCache,
+  keys: Arc<KeyFetcher>,
+  pusher: Arc<Pusher>,
 }
 
 impl GitHubUserSshKeysController {
-  pub fn new() -> Result<Self> {
+  fn new(keys: Arc<KeyFetcher>, pusher: Arc<Pusher>) -> Result<Self> {
     let metrics = metrics::Recorder::new()?;
 
-    Ok(Self { metrics })
+    Ok(Self {
+      metrics,
+      outputs: OutputCache::new(),
+      keys,
+      pusher,
+    })
+  }
+
+  /// Writes the keys to the Secret named after `resource`, in its namespace.
+  async fn write_keys(&self, ctx: &ReconcileCtx, resource: &GitHubUserSshKeys) -> Result<Written> {
+    let spec = &resource.spec;
+    let namespace = resource.namespace().unwrap_or_default();
+    let filter = key_filter(spec.filter.as_ref()).wrap_err("invalid filter")?;
+    let credentials = match spec.secret_ref.as_ref().and_then(|r| r.name()) {
+      Some(name) => Some(credentials(ctx, &namespace, name).await?),
+      None => None,
+    };
+
+    let timeout = fetch_timeout(spec.timeout, spec.interval, ctx);
+    let keys = self
+      .keys
+      .fetch(&spec.user, credentials.as_ref(), timeout)
+      .await?;
+    let keys = filter.apply(keys).collect::<Vec<_>>();
+
+    let data = secret_data(&keys, spec.projection);
+    let secrets = [secret(resource, &resource.name(), &namespace, data)];
+    let output_hash = apply_secrets(
+      ctx,
+      &self.outputs,
+      &self.metrics,
+      resource,
+      resource
+        .status
+        .as_ref()
+        .and_then(|s| s.output_hash.as_deref()),
+      &secrets,
+    )
+    .await?;
+
+    let previous = resource.status.as_ref().and_then(|s| s.artifact.as_ref());
+    let artifact = store_artifact(ctx, resource, previous, &keys).await?;
+    let pushed = push_keys(
+      ctx,
+      &self.pusher,
+      resource,
+      spec.push_to.as_ref(),
+      resource.status.as_ref(),
+      &spec.user,
+      &keys,
+    )
+    .await?;
+
+    Ok(Written {
+      keys: keys.len(),
+      digest: checksum(combined(&keys).as_bytes()),
+      output_hash,
+      artifact,
+      pushed,
+    })
   }
 }
 
+#[flux_controller(suspend = spec.suspend, interval = spec.interval)]
 #[async_trait]
 impl Controller<GitHubUserSshKeys> for GitHubUserSshKeysController {
   async fn reconcile(
-    self: std::sync::Arc<Self>,
-    _resource: std::sync::Arc<GitHubUserSshKeys>,
-  ) -> eyre::Result<ReconcilerAction> {
-    todo!()
+    self: Arc<Self>,
+    resource: Arc<GitHubUserSshKeys>,
+    ctx: ReconcileCtx,
+  ) -> Result<ReconcilerAction> {
+    let written = self.write_keys(&ctx, &resource).await;
+    let status = status_patch(
+      resource.status.as_ref(),
+      resource.metadata.generation,
+      &resource.spec.user,
+      &written,
+    );
+
+    let namespace = resource.namespace().unwrap_or_default();
+    let api = Api::<GitHubUserSshKeys>::namespaced(ctx.client().clone(), &namespace);
+    let patched = patch_status(api, &resource.name(), &status).await;
+    // the error of writing the keys takes precedence over that of recording it
+    written?;
+    patched?;
+
+    Ok(ReconcilerAction {
+      requeue_after: Self::reconcile_interval(&resource),
+    })
+  }
+
+  fn requirements() -> Requirements {
+    Requirements::default().min_kube_version(KubeVersion::new(1, 21))
+  }
+
+  fn permissions() -> Permissions {
+    Permissions::default()
+      .namespaced("", "secrets", READ)
+      .namespaced("", "secrets", WRITE)
+  }
+
+  fn predicates() -> Predicates {
+    // status patches do not bump the generation, so they do not trigger another fetch
+    Predicates::generation().annotation(RECONCILE_REQUEST_ANNOTATION)
   }
 
-  fn error_policy(self: std::sync::Arc<Self>, _error: &eyre::Report) -> ReconcilerAction {
-    todo!()
+  fn last_reconciled(resource: &GitHubUserSshKeys) -> Option<SystemTime> {
+    last_fetch_time(resource.status.as_ref()?.last_fetch_time.as_ref())
   }
 
-  fn metrics(&self) -> &metrics::Recorder {
-    &self.metrics
+  fn error_policy(self: Arc<Self>, error: &eyre::Report, retry: Duration) -> ReconcilerAction {
+    ReconcilerAction {
+      requeue_after: Some(retry_delay(error, retry)),
+    }
+  }
+}
+
+struct ClusterGitHubUserSshKeysController {
+  metrics: metrics::Recorder,
+  outputs: OutputCache,
+  keys: Arc<KeyFetcher>,
+  pusher: Arc<Pusher>,
+}
+
+impl ClusterGitHubUserSshKeysController {
+  fn new(keys: Arc<KeyFetcher>, pusher: Arc<Pusher>) -> Result<Self> {
+    let metrics = metrics::Recorder::new()?;
+
+    Ok(Self {
+      metrics,
+      outputs: OutputCache::new(),
+      keys,
+      pusher,
+    })
+  }
+
+  /// Writes the keys to the target Secret in every namespace matched by the target's selector.
+  /// Namespaces created (or labelled) later on get the Secret on the next reconcile.
+  async fn write_keys(
+    &self,
+    ctx: &ReconcileCtx,
+    resource: &ClusterGitHubUserSshKeys,
+  ) -> Result<Written> {
+    let spec = &resource.spec;
+    let client = ctx.client();
+    let filter = key_filter(spec.filter.as_ref()).wrap_err("invalid filter")?;
+    let credentials = match &spec.secret_ref {
+      Some(secret_ref) => {
+        let secret = ctx.resolve_reference("secretRef", secret_ref, None)?;
+        Some(credentials(ctx, secret.namespace, secret.name).await?)
+      }
+      None => None,
+    };
+
+    let timeout = fetch_timeout(spec.timeout, spec.interval, ctx);
+    let keys = self
+      .keys
+      .fetch(&spec.user, credentials.as_ref(), timeout)
+      .await?;
+    let keys = filter.apply(keys).collect::<Vec<_>>();
+
+    let selector = label_selector(&spec.target.namespace_selector)?;
+    let mut params = ListParams::default();
+    if !selector.is_empty() {
+      params = params.labels(&selector);
+    }
+
+    let data = secret_data(&keys, spec.projection);
+    let secrets = Api::<Namespace>::all(client.clone())
+      .list(&params)
+      .await?
+      .iter()
+      .map(|namespace| secret(resource, &spec.target.name, &namespace.name(), data.clone()))
+      .collect::<Vec<_>>();
+    let output_hash = apply_secrets(
+      ctx,
+      &self.outputs,
+      &self.metrics,
+      resource,
+      resource
+        .status
+        .as_ref()
+        .and_then(|s| s.output_hash.as_deref()),
+      &secrets,
+    )
+    .await?;
+    // namespaces which are no longer selected lose their Secret
+    if let Some(inventory) = inventory_id(resource) {
+      gc_owned(ctx, &inventory, &secrets).await?;
+    }
+
+    let previous = resource.status.as_ref().and_then(|s| s.artifact.as_ref());
+    let artifact = store_artifact(ctx, resource, previous, &keys).await?;
+    let pushed = push_keys(
+      ctx,
+      &self.pusher,
+      resource,
+      spec.push_to.as_ref(),
+      resource.status.as_ref(),
+      &spec.user,
+      &keys,
+    )
+    .await?;
+
+    Ok(Written {
+      keys: keys.len(),
+      digest: checksum(combined(&keys).as_bytes()),
+      output_hash,
+      artifact,
+      pushed,
+    })
+  }
+}
+
+#[flux_controller(suspend = spec.suspend, interval = spec.interval)]
+#[async_trait]
+impl Controller<ClusterGitHubUserSshKeys> for ClusterGitHubUserSshKeysController {
+  async fn reconcile(
+    self: Arc<Self>,
+    resource: Arc<ClusterGitHubUserSshKeys>,
+    ctx: ReconcileCtx,
+  ) -> Result<ReconcilerAction> {
+    let written = self.write_keys(&ctx, &resource).await;
+    let status = status_patch(
+      resource.status.as_ref(),
+      resource.metadata.generation,
+      &resource.spec.user,
+      &written,
+    );
+
+    let api = Api::<ClusterGitHubUserSshKeys>::all(ctx.client().clone());
+    let patched = patch_status(api, &resource.name(), &status).await;
+    // the error of writing the keys takes precedence over that of recording it
+    written?;
+    patched?;
+
+    Ok(ReconcilerAction {
+      requeue_after: Self::reconcile_interval(&resource),
+    })
+  }
+
+  fn requirements() -> Requirements {
+    Requirements::default().min_kube_version(KubeVersion::new(1, 21))
+  }
+
+  fn permissions() -> Permissions {
+    // the keys are written to the namespaces selected by the resource, and pruned from those it
+    // no longer selects
+    Permissions::default()
+      .cluster("", "secrets", READ)
+      .cluster("", "secrets", WRITE)
+      .cluster("", "namespaces", READ)
+  }
+
+  fn predicates() -> Predicates {
+    Predicates::generation().annotation(RECONCILE_REQUEST_ANNOTATION)
+  }
+
+  fn last_reconciled(resource: &ClusterGitHubUserSshKeys) -> Option<SystemTime> {
+    last_fetch_time(resource.status.as_ref()?.last_fetch_time.as_ref())
+  }
+
+  fn error_policy(self: Arc<Self>, error: &eyre::Report, retry: Duration) -> ReconcilerAction {
+    ReconcilerAction {
+      requeue_after: Some(retry_delay(error, retry)),
+    }
+  }
+}
+
+struct GitHubTeamSshKeysController {
+  metrics: metrics::Recorder,
+  outputs: OutputCache,
+  keys: Arc<KeyFetcher>,
+}
+
+impl GitHubTeamSshKeysController {
+  fn new(keys: Arc<KeyFetcher>) -> Result<Self> {
+    let metrics = metrics::Recorder::new()?;
+
+    Ok(Self {
+      metrics,
+      outputs: OutputCache::new(),
+      keys,
+    })
+  }
+
+  /// Writes the keys of the members of the team to the Secret named after `resource`, in its
+  /// namespace.
+  async fn write_keys(
+    &self,
+    ctx: &ReconcileCtx,
+    resource: &GitHubTeamSshKeys,
+  ) -> Result<TeamWritten> {
+    let spec = &resource.spec;
+    let namespace = resource.namespace().unwrap_or_default();
+    let secret_name = spec.secret_ref.name().ok_or(MissingSecretName)?;
+    let filter = key_filter(spec.filter.as_ref()).wrap_err("invalid filter")?;
+    let credentials = credentials(ctx, &namespace, secret_name).await?;
+
+    let timeout = fetch_timeout(spec.timeout, spec.interval, ctx);
+    let team = self
+      .keys
+      .fetch_team(&spec.org, &spec.team, &credentials, timeout)
+      .await?;
+    let keys = filter.apply(team.keys).collect::<Vec<_>>();
+
+    let data = secret_data(&keys, spec.projection);
+    let secrets = [secret(resource, &resource.name(), &namespace, data)];
+    let output_hash = apply_secrets(
+      ctx,
+      &self.outputs,
+      &self.metrics,
+      resource,
+      resource
+        .status
+        .as_ref()
+        .and_then(|s| s.output_hash.as_deref()),
+      &secrets,
+    )
+    .await?;
+
+    let previous = resource.status.as_ref().and_then(|s| s.artifact.as_ref());
+    let artifact = store_artifact(ctx, resource, previous, &keys).await?;
+
+    Ok(TeamWritten {
+      written: Written {
+        keys: keys.len(),
+        digest: checksum(combined(&keys).as_bytes()),
+        output_hash,
+        artifact,
+        pushed: None,
+      },
+      members: team.members,
+      failed: FailedItems::new(team.failures),
+    })
+  }
+}
+
+#[flux_controller(suspend = spec.suspend, interval = spec.interval)]
+#[async_trait]
+impl Controller<GitHubTeamSshKeys> for GitHubTeamSshKeysController {
+  async fn reconcile(
+    self: Arc<Self>,
+    resource: Arc<GitHubTeamSshKeys>,
+    ctx: ReconcileCtx,
+  ) -> Result<ReconcilerAction> {
+    let written = self.write_keys(&ctx, &resource).await;
+    let team = format!("{}/{}", resource.spec.org, resource.spec.team);
+    let status = team_status_patch(
+      resource.status.as_ref(),
+      resource.metadata.generation,
+      &team,
+      &written,
+    );
+
+    let namespace = resource.namespace().unwrap_or_default();
+    let api = Api::<GitHubTeamSshKeys>::namespaced(ctx.client().clone(), &namespace);
+    let patched = patch_status(api, &resource.name(), &status).await;
+    // the error of writing the keys takes precedence over that of recording it
+    written?;
+    patched?;
+
+    Ok(ReconcilerAction {
+      requeue_after: Self::reconcile_interval(&resource),
+    })
+  }
+
+  fn requirements() -> Requirements {
+    Requirements::default().min_kube_version(KubeVersion::new(1, 21))
+  }
+
+  fn permissions() -> Permissions {
+    Permissions::default()
+      .namespaced("", "secrets", READ)
+      .namespaced("", "secrets", WRITE)
+  }
+
+  fn predicates() -> Predicates {
+    Predicates::generation().annotation(RECONCILE_REQUEST_ANNOTATION)
+  }
+
+  fn last_reconciled(resource: &GitHubTeamSshKeys) -> Option<SystemTime> {
+    last_fetch_time(resource.status.as_ref()?.last_fetch_time.as_ref())
+  }
+
+  fn error_policy(self: Arc<Self>, error: &eyre::Report, retry: Duration) -> ReconcilerAction {
+    ReconcilerAction {
+      requeue_after: Some(retry_delay(error, retry)),
+    }
   }
 }
 
 fn main() -> eyre::Result<()> {
   ControllerApp::main(CRATE_NAME, CRATE_VERSION, |app| {
-    Ok(app.controller(GitHubUserSshKeysController::new()?))
+    let keys = Arc::new(KeyFetcher::new()?);
+    let pusher = Arc::new(Pusher::new()?);
+    Ok(
+      app
+        .controller(GitHubUserSshKeysController::new(
+          keys.clone(),
+          pusher.clone(),
+        )?)
+        .controller(ClusterGitHubUserSshKeysController::new(
+          keys.clone(),
+          pusher,
+        )?)
+        .controller(GitHubTeamSshKeysController::new(keys)?),
+    )
   })
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ED25519: &str =
+    "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl alice@example";
+
+  const RSA: &str = "ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAAAgQDFWDRjxnQsjy3pLM/+SUtixKdTIMGt8FB5KsT3DtIvD9IxXDtmgW4kEJE3psUZRiwS7bK6YrPNitq43+IHhAEK9XsRoV7Is76jDW7ewkC4zHP5XKIQcofrMuDGvQ2TAgL4ezbQov3zuOiO8hEX5sSuusNITm+S23Sh60xqjML0pQ== rsa@example";
+
+  #[test]
+  fn filters_the_keys_written() {
+    let keys = parse_authorized_keys(&format!("{ED25519}\n{RSA}\n"))
+      .collect::<Result<Vec<_>, _>>()
+      .unwrap();
+    let written = |filter: Option<KeyFilter>| {
+      let filter = key_filter(filter.as_ref()).unwrap();
+      filter.apply(keys.clone()).count()
+    };
+
+    assert_eq!(written(None), 2);
+    assert_eq!(
+      written(Some(KeyFilter {
+        algorithms: vec!["ssh-ed25519".into()],
+        min_rsa_bits: None,
+      })),
+      1
+    );
+    // the RSA key is only 1024 bits long
+    assert_eq!(
+      written(Some(KeyFilter {
+        algorithms: Vec::new(),
+        min_rsa_bits: Some(2048),
+      })),
+      1
+    );
+
+    let unsupported = KeyFilter {
+      algorithms: vec!["ssh-foo".into()],
+      min_rsa_bits: None,
+    };
+    let error = eyre::Report::new(key_filter(Some(&unsupported)).unwrap_err());
+    assert!(is_stalled(&error));
+  }
+}
@@ -0,0 +1,235 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub(crate) enum PushError {
+  #[cfg(not(feature = "oci-push"))]
+  #[error("pushTo is experimental, the controller has to be built with the 'oci-push' feature")]
+  Disabled,
+
+  #[cfg(feature = "oci-push")]
+  #[error("invalid pushTo: {0}")]
+  InvalidTarget(String),
+}
+
+/// What is pushed: the `authorized_keys` file of the keys of a GitHub user.
+#[cfg_attr(not(feature = "oci-push"), allow(dead_code))]
+pub(crate) struct Content<'a> {
+  pub user: &'a str,
+  pub revision: &'a str,
+  pub authorized_keys: &'a str,
+}
+
+/// Publishes the keys to OCI repositories, with an HTTP client of its own as registries may be
+/// served over plain http. Pushing is experimental and only built in with the `oci-push`
+/// feature, without it sources with `pushTo` stall.
+pub(crate) struct Pusher {
+  #[cfg(feature = "oci-push")]
+  http: reqwest::Client,
+}
+
+#[cfg(not(feature = "oci-push"))]
+mod disabled {
+  use super::{Content, PushError, Pusher};
+  use eyre::Result;
+  use fluxcd_meta::{OciPushTarget, OciPushedArtifact};
+  use fluxcd_utils_cap::context::ReconcileCtx;
+
+  impl Pusher {
+    pub(crate) fn new() -> Result<Self> {
+      Ok(Self {})
+    }
+
+    /// Fails if there is a `target`, as pushing is not built in.
+    pub(crate) async fn push(
+      &self,
+      _ctx: &ReconcileCtx,
+      target: Option<&OciPushTarget>,
+      _namespace: Option<&str>,
+      _previous: Option<&OciPushedArtifact>,
+      _content: Content<'_>,
+    ) -> Result<Option<OciPushedArtifact>> {
+      match target {
+        Some(_) => Err(PushError::Disabled.into()),
+        None => Ok(None),
+      }
+    }
+  }
+
+  /// Whether `cause` will keep failing the push until the resource changes.
+  pub(crate) fn is_stalled(cause: &(dyn std::error::Error + 'static)) -> bool {
+    cause.is::<PushError>()
+  }
+}
+
+#[cfg(not(feature = "oci-push"))]
+pub(crate) use disabled::is_stalled;
+
+#[cfg(feature = "oci-push")]
+mod enabled {
+  use super::{Content, PushError, Pusher};
+  use crate::USER_AGENT;
+  use eyre::{Result, WrapErr};
+  use flate2::{write::GzEncoder, Compression};
+  use fluxcd_meta::{OciPushTarget, OciPushedArtifact};
+  use fluxcd_registry::{Credentials, Error as RegistryError, OciReference, Registry};
+  use fluxcd_utils_cap::{context::ReconcileCtx, http::HttpConfig};
+  use k8s_openapi::{api::core::v1::Secret, apimachinery::pkg::apis::meta::v1::Time, chrono::Utc};
+  use kube::Api;
+  use std::collections::BTreeMap;
+
+  /// Key of the Secret entry holding the docker config of an image pull Secret.
+  const DOCKER_CONFIG_KEY: &str = ".dockerconfigjson";
+
+  /// Name of the file of the keys in the artifact.
+  const AUTHORIZED_KEYS_FILE: &str = "authorized_keys";
+
+  impl Pusher {
+    pub(crate) fn new() -> Result<Self> {
+      let builder = reqwest::Client::builder().user_agent(USER_AGENT);
+      let http = HttpConfig::from_env()?.apply(builder).build()?;
+
+      Ok(Self { http })
+    }
+
+    /// Pushes `content` to the OCI repository of `target`, unless the `previous` push holds
+    /// its revision already. `namespace` is the namespace of the source, if it is namespaced,
+    /// which the Secret of the target has to be in.
+    pub(crate) async fn push(
+      &self,
+      ctx: &ReconcileCtx,
+      target: Option<&OciPushTarget>,
+      namespace: Option<&str>,
+      previous: Option<&OciPushedArtifact>,
+      content: Content<'_>,
+    ) -> Result<Option<OciPushedArtifact>> {
+      let target = match target {
+        Some(target) => target,
+        None => return Ok(None),
+      };
+
+      let url = target
+        .url()
+        .ok_or_else(|| PushError::InvalidTarget("url is required".into()))?;
+      let reference = OciReference::parse(url)?;
+      if let Some(previous) = previous.filter(|p| p.is_pushed(url, content.revision)) {
+        return Ok(Some(previous.clone()));
+      }
+
+      let credentials = self.credentials(ctx, target, namespace, &reference).await?;
+      let annotations = BTreeMap::from([
+        (
+          "org.opencontainers.image.created".to_string(),
+          Utc::now().to_rfc3339(),
+        ),
+        (
+          "org.opencontainers.image.source".to_string(),
+          format!("https://github.com/{}.keys", content.user),
+        ),
+        (
+          "org.opencontainers.image.revision".to_string(),
+          content.revision.to_string(),
+        ),
+      ]);
+
+      let layer = archive(content.authorized_keys)?;
+      let registry = Registry::new(self.http.clone(), target.insecure());
+      let digest = tokio::time::timeout(
+        ctx.remaining(),
+        registry.push(&reference, credentials.as_ref(), layer, &annotations),
+      )
+      .await
+      .map_err(|_| eyre::eyre!("timed out pushing to '{reference}'"))?
+      .wrap_err_with(|| format!("failed to push to '{reference}'"))?;
+
+      Ok(Some(OciPushedArtifact::new(
+        url,
+        digest,
+        content.revision,
+        Time(Utc::now()),
+      )))
+    }
+
+    /// The credentials of the registry of `reference`, from the image pull Secret of `target`.
+    async fn credentials(
+      &self,
+      ctx: &ReconcileCtx,
+      target: &OciPushTarget,
+      namespace: Option<&str>,
+      reference: &OciReference,
+    ) -> Result<Option<Credentials>> {
+      let secret_ref = match target.secret_ref() {
+        Some(secret_ref) => secret_ref,
+        None => return Ok(None),
+      };
+      let invalid = |message: &str| PushError::InvalidTarget(message.into());
+      let name = secret_ref
+        .name()
+        .ok_or_else(|| invalid("secretRef requires a name"))?;
+      let namespace = match (namespace, secret_ref.namespace()) {
+        (Some(own), None) => own,
+        (Some(own), Some(namespace)) if namespace == own => own,
+        (Some(_), Some(_)) => {
+          return Err(invalid("secretRef has to be in the namespace of the source").into())
+        }
+        (None, Some(namespace)) => namespace,
+        (None, None) => {
+          return Err(invalid("secretRef of a cluster-scoped resource requires a namespace").into())
+        }
+      };
+
+      let secret = Api::<Secret>::namespaced(ctx.client().clone(), namespace)
+        .get(name)
+        .await
+        .wrap_err_with(|| format!("failed to get secret '{namespace}/{name}'"))?;
+      let config = secret
+        .data
+        .unwrap_or_default()
+        .remove(DOCKER_CONFIG_KEY)
+        .ok_or_else(|| {
+          RegistryError::InvalidDockerConfig(format!(
+            "secret '{namespace}/{name}' has no '{DOCKER_CONFIG_KEY}'"
+          ))
+        })?;
+
+      Ok(Credentials::from_docker_config(
+        &config.0,
+        reference.image().registry(),
+      )?)
+    }
+  }
+
+  /// Packs the `authorized_keys` file into a gzipped tarball. The entry has fixed metadata, so
+  /// that the same keys always result in the same layer.
+  fn archive(authorized_keys: &str) -> Result<Vec<u8>> {
+    let mut archive = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    let mut header = tar::Header::new_gnu();
+    header.set_size(authorized_keys.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(0);
+    archive
+      .append_data(
+        &mut header,
+        AUTHORIZED_KEYS_FILE,
+        authorized_keys.as_bytes(),
+      )
+      .wrap_err("failed to archive the keys")?;
+
+    Ok(archive.into_inner()?.finish()?)
+  }
+
+  /// Whether `cause` will keep failing the push until the resource changes.
+  pub(crate) fn is_stalled(cause: &(dyn std::error::Error + 'static)) -> bool {
+    cause.is::<PushError>()
+      || matches!(
+        cause.downcast_ref::<RegistryError>(),
+        Some(
+          RegistryError::InvalidReference(_)
+            | RegistryError::InvalidDockerConfig(_)
+            | RegistryError::UnsupportedChallenge(_)
+        )
+      )
+  }
+}
+
+#[cfg(feature = "oci-push")]
+pub(crate) use enabled::is_stalled;
@@ -0,0 +1,474 @@
+use async_trait::async_trait;
+use eyre::{bail, eyre, Result, WrapErr};
+use fluxcd_api_source_http_endpoint::{
+  HttpEndpoint, HttpEndpointReason, HttpEndpointSignature, HttpEndpointTargetKind,
+  HttpEndpointVerification,
+};
+use fluxcd_meta::{
+  mark_ready, mark_reconciling, mark_stalled, new_condition, remove_condition, resolve_timeout,
+  set_condition, Condition as MetaCondition, Reason, StalePolicy, Verification, VerificationMethod,
+  DEFAULT_TIMEOUT, RECONCILE_REQUEST_ANNOTATION,
+};
+use fluxcd_utils_cap::{
+  artifact::checksum,
+  context::ReconcileCtx,
+  flux_controller,
+  http::{ClientTls, ClientTlsError, HttpClient, HttpConfig, TLS_CERT_KEY, TLS_KEY_KEY},
+  metrics,
+  output::{output_hash, OutputCache},
+  owned::controller_owner_ref,
+  predicate::Predicates,
+  rbac::{Permissions, READ, WRITE},
+  secrets::{secret_reason, SecretCheck},
+  Controller, ControllerApp,
+};
+use k8s_openapi::{
+  api::core::v1::{ConfigMap, ObjectReference, Secret},
+  apimachinery::pkg::apis::meta::v1::{Condition, Time},
+  chrono::Utc,
+  ByteString,
+};
+use kube::{
+  api::{ObjectMeta, Patch, PatchParams},
+  runtime::controller::ReconcilerAction,
+  Api, Client, Resource, ResourceExt,
+};
+use reqwest::StatusCode;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::{
+  collections::BTreeMap,
+  fmt::Debug,
+  sync::Arc,
+  time::{Duration, SystemTime},
+};
+use thiserror::Error;
+use tracing::warn;
+
+const CRATE_NAME: &str = env!("CARGO_PKG_NAME");
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Error)]
+#[error("url '{0}' must use the https scheme")]
+struct InsecureUrl(String);
+
+/// The content fetched from the endpoint, once written to the target.
+struct Fetched {
+  checksum: String,
+  method: VerificationMethod,
+  output_hash: Option<String>,
+}
+
+/// Whether `error` will keep failing until the resource (or the endpoint) changes, rather than
+/// being resolved by retrying.
+fn is_stalled(error: &eyre::Report) -> bool {
+  let permanent = |error: &reqwest::Error| {
+    matches!(
+      error.status(),
+      Some(StatusCode::NOT_FOUND | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+    )
+  };
+
+  error.chain().any(|cause| {
+    if cause.is::<InsecureUrl>() || cause.is::<ClientTlsError>() {
+      return true;
+    }
+
+    matches!(cause.downcast_ref::<reqwest::Error>(), Some(error) if permanent(error))
+  })
+}
+
+/// The ContentStale condition of `resource` once its content has not been refreshed for longer
+/// than `maxAge`.
+fn stale_condition(resource: &HttpEndpoint) -> Option<Condition> {
+  let max_age = resource.spec.max_age?;
+  let last_fetch = resource.status.as_ref()?.last_fetch_time.as_ref()?.0;
+  let age = (Utc::now() - last_fetch).to_std().unwrap_or_default();
+  if !matches!(max_age.to_std(), Some(max_age) if age > max_age) {
+    return None;
+  }
+
+  let message = format!(
+    "content was last refreshed at {}, longer ago than the max age of {max_age}",
+    last_fetch.to_rfc3339()
+  );
+  Some(new_condition(
+    MetaCondition::ContentStale,
+    true,
+    HttpEndpointReason::MaxAgeExceeded,
+    message,
+    resource.metadata.generation,
+  ))
+}
+
+/// The status patch recording the outcome of a reconcile of `resource`.
+fn status_patch(resource: &HttpEndpoint, result: &Result<Fetched>) -> Value {
+  let generation = resource.metadata.generation;
+  let mut conditions = resource
+    .status
+    .as_ref()
+    .map(|s| s.conditions.clone())
+    .unwrap_or_default();
+
+  let fetched = match result {
+    Ok(fetched) => fetched,
+    Err(error) => {
+      if let Some(stale) = stale_condition(resource) {
+        set_condition(&mut conditions, stale);
+      }
+
+      let message = format!("{error:#}");
+      if is_stalled(error) {
+        mark_stalled(&mut conditions, generation, Reason::Failed, message);
+      } else {
+        // a missing secret is retried with backoff until it is created
+        let reason = secret_reason(error).unwrap_or(Reason::Progressing);
+        let message = format!("retrying after: {message}");
+        mark_reconciling(&mut conditions, generation, reason, message);
+      }
+
+      return json!({
+        "status": {
+          "conditions": conditions,
+        }
+      });
+    }
+  };
+
+  remove_condition(&mut conditions, MetaCondition::ContentStale);
+  let message = format!(
+    "wrote the content of '{}' with checksum '{}'",
+    resource.spec.url, fetched.checksum
+  );
+  mark_ready(&mut conditions, generation, Reason::Succeeded, message);
+
+  let verification = Verification::new(&fetched.checksum, fetched.method);
+  json!({
+    "status": {
+      "checksum": fetched.checksum,
+      "verification": verification,
+      "lastFetchTime": Time(Utc::now()),
+      "conditions": conditions,
+      "outputHash": fetched.output_hash,
+    }
+  })
+}
+
+struct HttpEndpointController {
+  metrics: metrics::Recorder,
+  outputs: OutputCache,
+  http: HttpClient,
+}
+
+impl HttpEndpointController {
+  pub fn new() -> Result<Self> {
+    let metrics = metrics::Recorder::new()?;
+    let builder = || {
+      reqwest::Client::builder()
+        .user_agent(concat!(
+          env!("CARGO_PKG_NAME"),
+          "/",
+          env!("CARGO_PKG_VERSION")
+        ))
+        .https_only(true)
+    };
+    let http = HttpClient::new("http-endpoint", builder, &HttpConfig::from_env()?)?;
+
+    Ok(Self {
+      metrics,
+      outputs: OutputCache::new(),
+      http,
+    })
+  }
+
+  async fn fetch(
+    &self,
+    ctx: &ReconcileCtx,
+    resource: &HttpEndpoint,
+    url: &str,
+    timeout: Duration,
+  ) -> Result<Vec<u8>> {
+    let name = match resource.spec.secret_ref.as_ref().and_then(|r| r.name()) {
+      Some(name) => name,
+      None => return Ok(self.http.fetch(self.http.get(url).timeout(timeout)).await?),
+    };
+
+    // the secret is read on every fetch, so a rotated client certificate is picked up right away
+    let namespace = resource.namespace().unwrap_or_default();
+    let check = SecretCheck::new(&namespace, name).require_any(["username", "token", TLS_CERT_KEY]);
+    let secret = ctx.secrets().get(&namespace, name).await?;
+    let data = secret.data();
+    check.validate(data)?;
+    if data.contains_key(TLS_CERT_KEY) || data.contains_key(TLS_KEY_KEY) {
+      // a client certificate is only usable along with its private key
+      check
+        .require(TLS_CERT_KEY)
+        .require(TLS_KEY_KEY)
+        .validate(data)?;
+    }
+
+    let object = format!("{namespace}/{}", resource.name());
+    let http = match ClientTls::from_secret_data(data)
+      .wrap_err_with(|| format!("invalid secret '{namespace}/{name}'"))?
+    {
+      Some(tls) => self.http.with_tls(&object, &tls)?,
+      None => {
+        self.http.forget_tls(&object);
+        self.http.clone()
+      }
+    };
+
+    let mut request = http.get(url).timeout(timeout);
+    if secret.contains("username") {
+      let auth = secret.basic_auth()?;
+      request = request.basic_auth(auth.username, auth.password);
+    } else if secret.contains("token") {
+      request = request.bearer_auth(secret.token()?);
+    }
+
+    Ok(http.fetch(request).await?)
+  }
+
+  /// Verifies `content` as asked for by `verification`, returning the strongest method it was
+  /// verified by.
+  async fn verify(
+    &self,
+    verification: &HttpEndpointVerification,
+    checksum: &str,
+    content: &[u8],
+    timeout: Duration,
+  ) -> Result<VerificationMethod> {
+    let mut method = VerificationMethod::None;
+    if let Some(expected) = &verification.checksum {
+      if !expected.eq_ignore_ascii_case(checksum) {
+        bail!("checksum mismatch, expected '{expected}' but got '{checksum}'");
+      }
+
+      method = VerificationMethod::Checksum;
+    }
+
+    if let Some(HttpEndpointSignature { url, public_key }) = &verification.signature {
+      let signature = self.http.fetch(self.http.get(url).timeout(timeout)).await?;
+      let signature = String::from_utf8_lossy(&signature);
+      let signature = base64::decode(signature.trim()).wrap_err("invalid signature encoding")?;
+      let public_key = base64::decode(public_key.trim()).wrap_err("invalid public key encoding")?;
+      UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(content, &signature)
+        .map_err(|_| eyre!("signature verification failed"))?;
+      method = VerificationMethod::Signature;
+    }
+
+    Ok(method)
+  }
+
+  /// Writes `content` to the target of `resource`, returning the hash of the output applied.
+  async fn write_target(
+    &self,
+    ctx: &ReconcileCtx,
+    resource: &HttpEndpoint,
+    content: Vec<u8>,
+  ) -> Result<Option<String>> {
+    let client = ctx.client();
+    let target = &resource.spec.target;
+    let namespace = resource.namespace().unwrap_or_default();
+    let metadata = ObjectMeta {
+      name: Some(target.name.clone()),
+      namespace: Some(namespace.clone()),
+      owner_references: controller_owner_ref(resource).map(|r| vec![r]),
+      ..Default::default()
+    };
+
+    match target.kind {
+      HttpEndpointTargetKind::Secret => {
+        let secret = Secret {
+          metadata,
+          data: Some(BTreeMap::from([(target.key.clone(), ByteString(content))])),
+          ..Default::default()
+        };
+
+        let api = Api::<Secret>::namespaced(client.clone(), &namespace);
+        self.apply_target(ctx, api, resource, &secret).await
+      }
+
+      HttpEndpointTargetKind::ConfigMap => {
+        let mut config_map = ConfigMap {
+          metadata,
+          ..Default::default()
+        };
+
+        match String::from_utf8(content) {
+          Ok(text) => config_map.data = Some(BTreeMap::from([(target.key.clone(), text)])),
+          Err(e) => {
+            config_map.binary_data = Some(BTreeMap::from([(
+              target.key.clone(),
+              ByteString(e.into_bytes()),
+            )]))
+          }
+        }
+
+        let api = Api::<ConfigMap>::namespaced(client.clone(), &namespace);
+        self.apply_target(ctx, api, resource, &config_map).await
+      }
+    }
+  }
+
+  /// Applies `object` as the target of `resource`, unless it is identical to the output applied
+  /// last time or a policy vetoes it. Returns the hash of the output applied, which is the one
+  /// applied before if the context is read-only.
+  async fn apply_target<K>(
+    &self,
+    ctx: &ReconcileCtx,
+    api: Api<K>,
+    resource: &HttpEndpoint,
+    object: &K,
+  ) -> Result<Option<String>>
+  where
+    K: Resource + Serialize + DeserializeOwned + Clone + Debug,
+    K::DynamicType: Default,
+  {
+    ctx.admit(resource, object).await?;
+    let hash = output_hash(object)?;
+    let uid = resource.uid().unwrap_or_default();
+    let persisted = resource
+      .status
+      .as_ref()
+      .and_then(|s| s.output_hash.as_deref());
+    if self.outputs.is_unchanged(&uid, &hash, persisted) {
+      self.metrics.record_noop(&resource.object_ref(&()));
+      return Ok(Some(hash));
+    }
+
+    ctx.apply(resource, &api, CRATE_NAME, object).await?;
+    if ctx.is_read_only() {
+      return Ok(persisted.map(Into::into));
+    }
+
+    self.outputs.record(uid, hash.clone());
+    Ok(Some(hash))
+  }
+
+  async fn remove_target(&self, ctx: &ReconcileCtx, resource: &HttpEndpoint) -> Result<()> {
+    let target = &resource.spec.target;
+    let namespace = resource.namespace().unwrap_or_default();
+    let client = ctx.client().clone();
+    match target.kind {
+      HttpEndpointTargetKind::Secret => {
+        let api = Api::<Secret>::namespaced(client, &namespace);
+        ctx.delete(&api, &target.name).await
+      }
+      HttpEndpointTargetKind::ConfigMap => {
+        let api = Api::<ConfigMap>::namespaced(client, &namespace);
+        ctx.delete(&api, &target.name).await
+      }
+    }
+  }
+
+  /// Fetches the content of `resource`, verifies it and writes it to the target.
+  async fn refresh(&self, ctx: &ReconcileCtx, resource: &HttpEndpoint) -> Result<Fetched> {
+    let url = &resource.spec.url;
+    if !url.starts_with("https://") {
+      return Err(InsecureUrl(url.clone()).into());
+    }
+
+    let spec = &resource.spec;
+    let timeout = resolve_timeout(spec.timeout, DEFAULT_TIMEOUT, Some(spec.interval));
+    let timeout = timeout.min(ctx.remaining());
+
+    let content = self.fetch(ctx, resource, url, timeout).await?;
+    let checksum = checksum(&content);
+    let method = match &resource.spec.verify {
+      Some(verification) => {
+        self
+          .verify(verification, &checksum, &content, timeout)
+          .await?
+      }
+      None => VerificationMethod::None,
+    };
+
+    let output_hash = self.write_target(ctx, resource, content).await?;
+    Ok(Fetched {
+      checksum,
+      method,
+      output_hash,
+    })
+  }
+}
+
+async fn patch_status(
+  client: &Client,
+  resource: &HttpEndpoint,
+  status: &serde_json::Value,
+) -> Result<()> {
+  let namespace = resource.namespace().unwrap_or_default();
+  Api::<HttpEndpoint>::namespaced(client.clone(), &namespace)
+    .patch_status(
+      &resource.name(),
+      &PatchParams::default(),
+      &Patch::Merge(status),
+    )
+    .await?;
+
+  Ok(())
+}
+
+#[flux_controller(suspend = spec.suspend, interval = spec.interval)]
+#[async_trait]
+impl Controller<HttpEndpoint> for HttpEndpointController {
+  async fn reconcile(
+    self: Arc<Self>,
+    resource: Arc<HttpEndpoint>,
+    ctx: ReconcileCtx,
+  ) -> Result<ReconcilerAction> {
+    let fetched = self.refresh(&ctx, &resource).await;
+    if fetched.is_err()
+      && resource.spec.stale_policy == StalePolicy::Remove
+      && stale_condition(&resource).is_some()
+    {
+      // the fetch error is what the resource reports, whether or not the removal succeeds
+      if let Err(remove_error) = self.remove_target(&ctx, &resource).await {
+        warn!(error = %remove_error, "failed to remove the stale content");
+      }
+    }
+
+    let status = status_patch(&resource, &fetched);
+    let patched = patch_status(ctx.client(), &resource, &status).await;
+    fetched?;
+    patched?;
+
+    Ok(ReconcilerAction {
+      requeue_after: Self::reconcile_interval(&resource),
+    })
+  }
+
+  fn permissions() -> Permissions {
+    Permissions::default()
+      .namespaced("", "secrets", READ)
+      .namespaced("", "secrets", WRITE)
+      .namespaced("", "configmaps", READ)
+      .namespaced("", "configmaps", WRITE)
+  }
+
+  fn predicates() -> Predicates {
+    // status patches do not bump the generation, so they do not trigger another fetch
+    Predicates::generation().annotation(RECONCILE_REQUEST_ANNOTATION)
+  }
+
+  fn last_reconciled(resource: &HttpEndpoint) -> Option<SystemTime> {
+    let time = resource.status.as_ref()?.last_fetch_time.as_ref()?;
+    Some(time.0.into())
+  }
+
+  fn deleted(&self, resource: &ObjectReference) {
+    // the client presenting the certificate of the endpoint is kept until then
+    let namespace = resource.namespace.as_deref().unwrap_or_default();
+    let name = resource.name.as_deref().unwrap_or_default();
+    self.http.forget_tls(&format!("{namespace}/{name}"));
+  }
+}
+
+fn main() -> Result<()> {
+  ControllerApp::main(CRATE_NAME, CRATE_VERSION, |app| {
+    Ok(app.controller(HttpEndpointController::new()?))
+  })
+}
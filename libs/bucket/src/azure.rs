@@ -0,0 +1,172 @@
+use crate::{encode, encode_path, fetch, query_string, url, xml, AzureCredentials, Error, Object};
+use reqwest::Url;
+use ring::hmac;
+use time::OffsetDateTime;
+
+/// Version of the Blob Storage API requests are made against.
+const API_VERSION: &str = "2020-10-02";
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+const MONTHS: [&str; 12] = [
+  "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A container of Azure Blob Storage.
+pub(crate) struct Azure {
+  endpoint: Url,
+  /// Name of the storage account, the first label of the host of the endpoint.
+  account: String,
+  container: String,
+  credentials: Option<AzureCredentials>,
+}
+
+impl Azure {
+  pub(crate) fn new(
+    endpoint: Url,
+    container: &str,
+    credentials: Option<AzureCredentials>,
+  ) -> Result<Self, Error> {
+    let account = endpoint
+      .host_str()
+      .and_then(|host| host.split('.').next())
+      .filter(|account| !account.is_empty())
+      .ok_or_else(|| Error::InvalidEndpoint(endpoint.to_string()))?
+      .to_string();
+
+    Ok(Self {
+      endpoint,
+      account,
+      container: container.into(),
+      credentials,
+    })
+  }
+
+  pub(crate) async fn list(
+    &self,
+    http: &reqwest::Client,
+    prefix: Option<&str>,
+  ) -> Result<Vec<Object>, Error> {
+    let path = format!("/{}", encode(&self.container));
+    let mut objects = Vec::new();
+    let mut marker = None;
+    loop {
+      let mut params = vec![
+        ("restype", "container".to_string()),
+        ("comp", "list".to_string()),
+      ];
+      if let Some(prefix) = prefix {
+        params.push(("prefix", prefix.to_string()));
+      }
+      if let Some(marker) = marker.take() {
+        params.push(("marker", marker));
+      }
+
+      let page = xml::azure_page(&self.get_path(http, &path, &params).await?)?;
+      objects.extend(page.objects);
+      match page.next {
+        Some(next) => marker = Some(next),
+        None => return Ok(objects),
+      }
+    }
+  }
+
+  pub(crate) async fn get(&self, http: &reqwest::Client, key: &str) -> Result<Vec<u8>, Error> {
+    let path = format!("/{}/{}", encode(&self.container), encode_path(key));
+    self.get_path(http, &path, &[]).await
+  }
+
+  async fn get_path(
+    &self,
+    http: &reqwest::Client,
+    path: &str,
+    params: &[(&str, String)],
+  ) -> Result<Vec<u8>, Error> {
+    let mut query = query_string(params);
+    if let Some(AzureCredentials::Sas(sas)) = &self.credentials {
+      query = [query.as_str(), sas.as_str()]
+        .into_iter()
+        .filter(|q| !q.is_empty())
+        .collect::<Vec<_>>()
+        .join("&");
+    }
+
+    let url = url(&self.endpoint, path, &query);
+    let date = http_date(OffsetDateTime::now_utc());
+    let mut request = http
+      .get(url.clone())
+      .header("x-ms-date", &date)
+      .header("x-ms-version", API_VERSION);
+
+    if let Some(AzureCredentials::SharedKey(key)) = &self.credentials {
+      let signature = shared_key_signature(key, &self.account, url.path(), params, &date);
+      request = request.header(
+        "authorization",
+        format!("SharedKey {}:{signature}", self.account),
+      );
+    }
+
+    fetch(request).await
+  }
+}
+
+/// Signs a `GET` request of the encoded `path` with `params` with the key of the storage account,
+/// see "Authorize with Shared Key" of the Azure Storage documentation.
+fn shared_key_signature(
+  key: &[u8],
+  account: &str,
+  path: &str,
+  params: &[(&str, String)],
+  date: &str,
+) -> String {
+  let mut params = params
+    .iter()
+    .map(|(name, value)| format!("\n{}:{value}", name.to_ascii_lowercase()))
+    .collect::<Vec<_>>();
+  params.sort();
+
+  // the verb, followed by the standard headers, none of which are sent
+  let string_to_sign = format!(
+    "GET{}\nx-ms-date:{date}\nx-ms-version:{API_VERSION}\n/{account}{path}{}",
+    "\n".repeat(12),
+    params.concat()
+  );
+
+  let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+  base64::encode(hmac::sign(&key, string_to_sign.as_bytes()))
+}
+
+/// Formats `time` like `Sun, 06 Nov 1994 08:49:37 GMT`, as HTTP dates are.
+fn http_date(time: OffsetDateTime) -> String {
+  format!(
+    "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+    WEEKDAYS[usize::from(time.weekday().number_days_from_monday())],
+    time.day(),
+    MONTHS[usize::from(u8::from(time.month()) - 1)],
+    time.year(),
+    time.hour(),
+    time.minute(),
+    time.second()
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use time::macros::datetime;
+
+  #[test]
+  fn formats_http_dates() {
+    assert_eq!(
+      http_date(datetime!(1994-11-06 08:49:37 UTC)),
+      "Sun, 06 Nov 1994 08:49:37 GMT"
+    );
+  }
+
+  #[test]
+  fn account_is_the_first_label_of_the_endpoint() {
+    let endpoint = Url::parse("https://fluxmanifests.blob.core.windows.net").unwrap();
+    let azure = Azure::new(endpoint, "manifests", None).unwrap();
+    assert_eq!(azure.account, "fluxmanifests");
+  }
+}
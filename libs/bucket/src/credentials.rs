@@ -0,0 +1,187 @@
+use k8s_openapi::ByteString;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt};
+use thiserror::Error;
+
+/// Secret key holding the access key ID of S3 compatible storages.
+pub const ACCESS_KEY_KEY: &str = "accesskey";
+
+/// Secret key holding the secret access key of S3 compatible storages.
+pub const SECRET_KEY_KEY: &str = "secretkey";
+
+/// Secret key holding the JSON key of a Google Cloud service account.
+pub const SERVICE_ACCOUNT_KEY: &str = "serviceaccount";
+
+/// Secret key holding the base64 encoded key of an Azure storage account.
+pub const ACCOUNT_KEY_KEY: &str = "accountKey";
+
+/// Secret key holding an Azure shared access signature, as a query string.
+pub const SAS_KEY_KEY: &str = "sasKey";
+
+/// Credentials of S3 compatible storages.
+#[derive(Clone)]
+pub struct S3Credentials {
+  pub access_key: String,
+  pub secret_key: String,
+}
+
+/// The JSON key of a Google Cloud service account, as downloaded from the console.
+#[derive(Clone, Deserialize)]
+pub struct ServiceAccountKey {
+  pub client_email: String,
+
+  /// PEM encoded PKCS#8 RSA private key.
+  pub private_key: String,
+
+  /// Endpoint the signed assertions are exchanged for access tokens at.
+  pub token_uri: String,
+}
+
+/// Credentials of Azure Blob Storage.
+#[derive(Clone)]
+pub enum AzureCredentials {
+  /// The key of the storage account, which requests are signed with.
+  SharedKey(Vec<u8>),
+
+  /// A shared access signature, appended to the query of every request.
+  Sas(String),
+}
+
+#[derive(Debug, Error)]
+pub enum CredentialsError {
+  #[error("secret is missing '{0}'")]
+  MissingKey(&'static str),
+
+  #[error("secret must contain either '{ACCOUNT_KEY_KEY}' or '{SAS_KEY_KEY}'")]
+  MissingAzureKey,
+
+  #[error("'{0}' is not valid utf-8")]
+  InvalidUtf8(&'static str),
+
+  #[error("'{0}' is not valid: {1}")]
+  Invalid(&'static str, String),
+}
+
+/// The trimmed value of `key` in `data`, if it is there.
+fn value(
+  data: &BTreeMap<String, ByteString>,
+  key: &'static str,
+) -> Result<Option<String>, CredentialsError> {
+  match data.get(key) {
+    None => Ok(None),
+    Some(value) => String::from_utf8(value.0.clone())
+      .map(|v| Some(v.trim().to_owned()))
+      .map_err(|_| CredentialsError::InvalidUtf8(key)),
+  }
+}
+
+fn required(
+  data: &BTreeMap<String, ByteString>,
+  key: &'static str,
+) -> Result<String, CredentialsError> {
+  value(data, key)?.ok_or(CredentialsError::MissingKey(key))
+}
+
+impl S3Credentials {
+  /// Reads the credentials from the `accesskey` and `secretkey` of a Secret.
+  pub fn from_secret_data(data: &BTreeMap<String, ByteString>) -> Result<Self, CredentialsError> {
+    Ok(Self {
+      access_key: required(data, ACCESS_KEY_KEY)?,
+      secret_key: required(data, SECRET_KEY_KEY)?,
+    })
+  }
+}
+
+impl ServiceAccountKey {
+  /// Reads the key from the `serviceaccount` of a Secret.
+  pub fn from_secret_data(data: &BTreeMap<String, ByteString>) -> Result<Self, CredentialsError> {
+    let key = required(data, SERVICE_ACCOUNT_KEY)?;
+    serde_json::from_str(&key)
+      .map_err(|e| CredentialsError::Invalid(SERVICE_ACCOUNT_KEY, e.to_string()))
+  }
+}
+
+impl AzureCredentials {
+  /// Reads the credentials from a Secret, preferring the `accountKey` over a `sasKey` when both
+  /// are present.
+  pub fn from_secret_data(data: &BTreeMap<String, ByteString>) -> Result<Self, CredentialsError> {
+    if let Some(key) = value(data, ACCOUNT_KEY_KEY)? {
+      let key = base64::decode(key)
+        .map_err(|e| CredentialsError::Invalid(ACCOUNT_KEY_KEY, e.to_string()))?;
+      return Ok(Self::SharedKey(key));
+    }
+
+    match value(data, SAS_KEY_KEY)? {
+      Some(sas) => Ok(Self::Sas(sas.trim_start_matches('?').to_string())),
+      None => Err(CredentialsError::MissingAzureKey),
+    }
+  }
+}
+
+// credentials end up in logs through error reports, so never print the secrets themselves
+impl fmt::Debug for S3Credentials {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("S3Credentials")
+      .field("access_key", &self.access_key)
+      .finish_non_exhaustive()
+  }
+}
+
+impl fmt::Debug for ServiceAccountKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ServiceAccountKey")
+      .field("client_email", &self.client_email)
+      .finish_non_exhaustive()
+  }
+}
+
+impl fmt::Debug for AzureCredentials {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      AzureCredentials::SharedKey(_) => f.write_str("SharedKey(..)"),
+      AzureCredentials::Sas(_) => f.write_str("Sas(..)"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn data(entries: &[(&str, &str)]) -> BTreeMap<String, ByteString> {
+    entries
+      .iter()
+      .map(|(k, v)| (k.to_string(), ByteString(v.as_bytes().to_vec())))
+      .collect()
+  }
+
+  #[test]
+  fn reads_s3_credentials() {
+    let credentials =
+      S3Credentials::from_secret_data(&data(&[("accesskey", "minio\n"), ("secretkey", "secret")]))
+        .unwrap();
+    assert_eq!(credentials.access_key, "minio");
+    assert_eq!(credentials.secret_key, "secret");
+
+    assert!(matches!(
+      S3Credentials::from_secret_data(&data(&[("accesskey", "minio")])),
+      Err(CredentialsError::MissingKey(SECRET_KEY_KEY))
+    ));
+  }
+
+  #[test]
+  fn prefers_azure_account_key() {
+    let credentials =
+      AzureCredentials::from_secret_data(&data(&[("accountKey", "a2V5"), ("sasKey", "?sv=1")]))
+        .unwrap();
+    assert!(matches!(credentials, AzureCredentials::SharedKey(key) if key == b"key"));
+
+    let credentials = AzureCredentials::from_secret_data(&data(&[("sasKey", "?sv=1")])).unwrap();
+    assert!(matches!(credentials, AzureCredentials::Sas(sas) if sas == "sv=1"));
+
+    assert!(matches!(
+      AzureCredentials::from_secret_data(&data(&[])),
+      Err(CredentialsError::MissingAzureKey)
+    ));
+  }
+}
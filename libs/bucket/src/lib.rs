@@ -0,0 +1,233 @@
+mod azure;
+mod credentials;
+mod gcs;
+mod s3;
+mod xml;
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::{RequestBuilder, Url};
+use thiserror::Error;
+
+pub use credentials::*;
+
+/// Characters left as they are when encoding URLs: the unreserved characters of RFC 3986, which
+/// is what the signatures of S3 and Azure expect.
+const URL_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+  .remove(b'-')
+  .remove(b'_')
+  .remove(b'.')
+  .remove(b'~');
+
+#[derive(Debug, Error)]
+pub enum Error {
+  #[error(transparent)]
+  Http(#[from] reqwest::Error),
+
+  #[error("invalid endpoint '{0}'")]
+  InvalidEndpoint(String),
+
+  #[error("endpoint '{0}' uses plain http, which requires insecure to be set")]
+  InsecureEndpoint(String),
+
+  #[error("invalid response from the bucket: {0}")]
+  InvalidResponse(String),
+
+  #[error("invalid google service account private key")]
+  InvalidPrivateKey,
+
+  #[error("failed to sign request")]
+  Signing,
+}
+
+/// An object of a bucket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Object {
+  /// Key of the object, which is its path in the bucket.
+  pub key: String,
+
+  /// Entity tag of the object, which changes along with its content.
+  pub etag: String,
+}
+
+/// Client for a single bucket of an object storage. The bucket is only ever read from.
+pub struct Bucket {
+  http: reqwest::Client,
+  store: Store,
+}
+
+enum Store {
+  S3(s3::S3),
+  Gcs(gcs::Gcs),
+  Azure(azure::Azure),
+}
+
+impl Bucket {
+  /// A bucket of an S3 compatible storage, like AWS S3 or MinIO, at `endpoint`. Requests are
+  /// anonymous without `credentials`.
+  pub fn s3(
+    http: reqwest::Client,
+    endpoint: Url,
+    bucket: &str,
+    region: &str,
+    credentials: Option<S3Credentials>,
+  ) -> Self {
+    let store = Store::S3(s3::S3::new(endpoint, bucket, region, credentials));
+    Self { http, store }
+  }
+
+  /// A bucket of Google Cloud Storage, through its JSON API at `endpoint`. Without a service
+  /// account key, requests are authenticated as the service account of the workload, through the
+  /// metadata server.
+  pub fn gcs(
+    http: reqwest::Client,
+    endpoint: Url,
+    bucket: &str,
+    key: Option<ServiceAccountKey>,
+  ) -> Self {
+    let store = Store::Gcs(gcs::Gcs::new(endpoint, bucket, key));
+    Self { http, store }
+  }
+
+  /// A container of Azure Blob Storage, of the storage account at `endpoint`, like
+  /// `https://<account>.blob.core.windows.net`. Requests are anonymous without `credentials`.
+  pub fn azure(
+    http: reqwest::Client,
+    endpoint: Url,
+    container: &str,
+    credentials: Option<AzureCredentials>,
+  ) -> Result<Self, Error> {
+    let store = Store::Azure(azure::Azure::new(endpoint, container, credentials)?);
+    Ok(Self { http, store })
+  }
+
+  /// Lists every object of the bucket whose key starts with `prefix`.
+  pub async fn list(&self, prefix: Option<&str>) -> Result<Vec<Object>, Error> {
+    match &self.store {
+      Store::S3(s3) => s3.list(&self.http, prefix).await,
+      Store::Gcs(gcs) => gcs.list(&self.http, prefix).await,
+      Store::Azure(azure) => azure.list(&self.http, prefix).await,
+    }
+  }
+
+  /// Downloads the content of the object `key`.
+  pub async fn get(&self, key: &str) -> Result<Vec<u8>, Error> {
+    match &self.store {
+      Store::S3(s3) => s3.get(&self.http, key).await,
+      Store::Gcs(gcs) => gcs.get(&self.http, key).await,
+      Store::Azure(azure) => azure.get(&self.http, key).await,
+    }
+  }
+}
+
+/// The URL of `endpoint`, which is either a URL or a host name (with an optional port), in which
+/// case `https` is used unless `insecure` is set. Plain `http` URLs are only accepted if
+/// `insecure` is set.
+pub fn endpoint_url(endpoint: &str, insecure: bool) -> Result<Url, Error> {
+  let url = if endpoint.contains("://") {
+    Url::parse(endpoint)
+  } else if insecure {
+    Url::parse(&format!("http://{endpoint}"))
+  } else {
+    Url::parse(&format!("https://{endpoint}"))
+  };
+
+  let url = url.map_err(|_| Error::InvalidEndpoint(endpoint.into()))?;
+  match url.scheme() {
+    "https" if url.has_host() => Ok(url),
+    "http" if url.has_host() && insecure => Ok(url),
+    "http" if url.has_host() => Err(Error::InsecureEndpoint(endpoint.into())),
+    _ => Err(Error::InvalidEndpoint(endpoint.into())),
+  }
+}
+
+/// Encodes `value` for use in a URL, see [URL_ENCODE_SET].
+fn encode(value: &str) -> String {
+  utf8_percent_encode(value, URL_ENCODE_SET).to_string()
+}
+
+/// Encodes the segments of the path `key`, keeping the slashes between them.
+fn encode_path(key: &str) -> String {
+  key.split('/').map(encode).collect::<Vec<_>>().join("/")
+}
+
+/// The query string of `params`, sorted by name as the signatures require.
+fn query_string(params: &[(&str, String)]) -> String {
+  let mut params = params
+    .iter()
+    .map(|(name, value)| format!("{}={}", encode(name), encode(value)))
+    .collect::<Vec<_>>();
+  params.sort();
+  params.join("&")
+}
+
+/// The URL of `path` (already encoded) at `endpoint`, with the encoded `query`.
+fn url(endpoint: &Url, path: &str, query: &str) -> Url {
+  let mut url = endpoint.clone();
+  let base = url.path().trim_end_matches('/').to_string();
+  url.set_path(&format!("{base}{path}"));
+  url.set_query(Some(query).filter(|q| !q.is_empty()));
+  url
+}
+
+/// The host of `url` as sent in the `Host` header, with the port unless it is the default one.
+fn host_header(url: &Url) -> String {
+  let host = url.host_str().unwrap_or_default();
+  match url.port() {
+    Some(port) => format!("{host}:{port}"),
+    None => host.to_string(),
+  }
+}
+
+/// Sends `request`, and reads the body of the response, failing if it has an error status.
+async fn fetch(request: RequestBuilder) -> Result<Vec<u8>, Error> {
+  let response = request.send().await?.error_for_status()?;
+  Ok(response.bytes().await?.to_vec())
+}
+
+fn hex(bytes: &[u8]) -> String {
+  use std::fmt::Write;
+
+  let mut hex = String::with_capacity(bytes.len() * 2);
+  for byte in bytes {
+    let _ = write!(hex, "{byte:02x}");
+  }
+
+  hex
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn endpoints_default_to_https() {
+    assert_eq!(
+      endpoint_url("minio.storage:9000", false).unwrap().as_str(),
+      "https://minio.storage:9000/"
+    );
+    assert_eq!(
+      endpoint_url("minio.storage:9000", true).unwrap().as_str(),
+      "http://minio.storage:9000/"
+    );
+    assert!(matches!(
+      endpoint_url("http://minio.storage", false),
+      Err(Error::InsecureEndpoint(_))
+    ));
+    assert!(matches!(
+      endpoint_url("ftp://minio.storage", true),
+      Err(Error::InvalidEndpoint(_))
+    ));
+  }
+
+  #[test]
+  fn paths_keep_their_slashes() {
+    assert_eq!(
+      encode_path("apps/web app/kustomization.yaml"),
+      "apps/web%20app/kustomization.yaml"
+    );
+    assert_eq!(
+      query_string(&[("prefix", "apps/".into()), ("list-type", "2".into())]),
+      "list-type=2&prefix=apps%2F"
+    );
+  }
+}
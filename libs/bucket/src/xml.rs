@@ -0,0 +1,144 @@
+use crate::{Error, Object};
+use roxmltree::{Document, Node};
+
+/// A page of a listing, and the token to continue the listing with, if there are more objects.
+pub(crate) struct Page {
+  pub(crate) objects: Vec<Object>,
+  pub(crate) next: Option<String>,
+}
+
+fn parse(body: &[u8]) -> Result<Document<'_>, Error> {
+  let text = std::str::from_utf8(body).map_err(|e| Error::InvalidResponse(e.to_string()))?;
+  // Azure starts its responses with a byte order mark
+  Document::parse(text.trim_start_matches('\u{feff}'))
+    .map_err(|e| Error::InvalidResponse(e.to_string()))
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+  node.children().find(|n| n.tag_name().name() == name)
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+  child(node, name).and_then(|n| n.text())
+}
+
+/// Parses a page of the `ListObjectsV2` response of S3.
+pub(crate) fn s3_page(body: &[u8]) -> Result<Page, Error> {
+  let document = parse(body)?;
+  let root = document.root_element();
+  if root.tag_name().name() != "ListBucketResult" {
+    return Err(Error::InvalidResponse(format!(
+      "expected ListBucketResult, got {}",
+      root.tag_name().name()
+    )));
+  }
+
+  let objects = root
+    .children()
+    .filter(|n| n.tag_name().name() == "Contents")
+    .map(|contents| {
+      let key = child_text(contents, "Key")
+        .ok_or_else(|| Error::InvalidResponse("object without a key".into()))?;
+      let etag = child_text(contents, "ETag").unwrap_or_default();
+      Ok(Object {
+        key: key.to_string(),
+        etag: etag.trim_matches('"').to_string(),
+      })
+    })
+    .collect::<Result<_, Error>>()?;
+
+  let truncated = child_text(root, "IsTruncated") == Some("true");
+  let next = child_text(root, "NextContinuationToken")
+    .filter(|_| truncated)
+    .map(Into::into);
+
+  Ok(Page { objects, next })
+}
+
+/// Parses a page of the `List Blobs` response of Azure Blob Storage.
+pub(crate) fn azure_page(body: &[u8]) -> Result<Page, Error> {
+  let document = parse(body)?;
+  let root = document.root_element();
+  if root.tag_name().name() != "EnumerationResults" {
+    return Err(Error::InvalidResponse(format!(
+      "expected EnumerationResults, got {}",
+      root.tag_name().name()
+    )));
+  }
+
+  let blobs = child(root, "Blobs").into_iter().flat_map(|b| b.children());
+  let objects = blobs
+    .filter(|n| n.tag_name().name() == "Blob")
+    .map(|blob| {
+      let key = child_text(blob, "Name")
+        .ok_or_else(|| Error::InvalidResponse("blob without a name".into()))?;
+      let etag = child(blob, "Properties")
+        .and_then(|p| child_text(p, "Etag"))
+        .unwrap_or_default();
+      Ok(Object {
+        key: key.to_string(),
+        etag: etag.trim_matches('"').to_string(),
+      })
+    })
+    .collect::<Result<_, Error>>()?;
+
+  let next = child_text(root, "NextMarker")
+    .filter(|marker| !marker.is_empty())
+    .map(Into::into);
+
+  Ok(Page { objects, next })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_s3_listings() {
+    let body = br#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Name>manifests</Name>
+  <IsTruncated>true</IsTruncated>
+  <Contents><Key>apps/web.yaml</Key><ETag>&quot;9b2cf535f27731c974343645a3985328&quot;</ETag><Size>12</Size></Contents>
+  <Contents><Key>apps/db &amp; cache.yaml</Key><ETag>"1f3870be274f6c49b3e31a0c6728957f"</ETag></Contents>
+  <NextContinuationToken>1ueGcxLPRx1Tr</NextContinuationToken>
+</ListBucketResult>"#;
+
+    let page = s3_page(body).unwrap();
+    assert_eq!(
+      page.objects,
+      [
+        Object {
+          key: "apps/web.yaml".into(),
+          etag: "9b2cf535f27731c974343645a3985328".into(),
+        },
+        Object {
+          key: "apps/db & cache.yaml".into(),
+          etag: "1f3870be274f6c49b3e31a0c6728957f".into(),
+        },
+      ]
+    );
+    assert_eq!(page.next.as_deref(), Some("1ueGcxLPRx1Tr"));
+  }
+
+  #[test]
+  fn parses_azure_listings() {
+    let body = "\u{feff}<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<EnumerationResults ServiceEndpoint=\"https://flux.blob.core.windows.net/\" ContainerName=\"manifests\">
+  <Blobs>
+    <Blob><Name>apps/web.yaml</Name><Properties><Etag>0x8D9F</Etag></Properties></Blob>
+  </Blobs>
+  <NextMarker />
+</EnumerationResults>";
+
+    let page = azure_page(body.as_bytes()).unwrap();
+    assert_eq!(
+      page.objects,
+      [Object {
+        key: "apps/web.yaml".into(),
+        etag: "0x8D9F".into(),
+      }]
+    );
+    assert_eq!(page.next, None);
+  }
+}
@@ -0,0 +1,68 @@
+use crate::ratelimit::Identity;
+use reqwest::header::HeaderValue;
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+};
+
+/// How many responses are cached before the cache is cleared, so that it does not grow with every
+/// path ever requested.
+const MAX_ENTRIES: usize = 1024;
+
+#[derive(Clone)]
+pub(crate) struct CachedResponse {
+  pub(crate) etag: HeaderValue,
+  pub(crate) body: Arc<[u8]>,
+}
+
+/// Caches the last response to every request, by the identity it was made as, so that requests
+/// can be made conditional on the `ETag` of the response. GitHub answers those with a
+/// `304 Not Modified` when nothing changed, which does not count against the rate limit.
+#[derive(Default)]
+pub(crate) struct ResponseCache(Mutex<HashMap<(Identity, String), CachedResponse>>);
+
+impl ResponseCache {
+  pub(crate) fn get(&self, identity: &Identity, url: &str) -> Option<CachedResponse> {
+    let cache = self.0.lock().unwrap();
+    cache.get(&(identity.clone(), url.to_string())).cloned()
+  }
+
+  pub(crate) fn insert(&self, identity: Identity, url: String, response: CachedResponse) {
+    let mut cache = self.0.lock().unwrap();
+    if cache.len() >= MAX_ENTRIES && !cache.contains_key(&(identity.clone(), url.clone())) {
+      cache.clear();
+    }
+
+    cache.insert((identity, url), response);
+  }
+
+  pub(crate) fn remove(&self, identity: &Identity, url: &str) {
+    let mut cache = self.0.lock().unwrap();
+    cache.remove(&(identity.clone(), url.to_string()));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn caches_responses_per_identity() {
+    let cache = ResponseCache::default();
+    let response = CachedResponse {
+      etag: HeaderValue::from_static("\"abc\""),
+      body: Arc::from(&b"[]"[..]),
+    };
+    cache.insert(Identity::Anonymous, "/users/a/keys".into(), response);
+
+    let cached = cache.get(&Identity::Anonymous, "/users/a/keys").unwrap();
+    assert_eq!(cached.etag, "\"abc\"");
+    assert_eq!(&*cached.body, b"[]");
+    assert!(cache
+      .get(&Identity::Token(vec![1]), "/users/a/keys")
+      .is_none());
+
+    cache.remove(&Identity::Anonymous, "/users/a/keys");
+    assert!(cache.get(&Identity::Anonymous, "/users/a/keys").is_none());
+  }
+}
@@ -0,0 +1,156 @@
+use k8s_openapi::ByteString;
+use std::{collections::BTreeMap, fmt};
+use thiserror::Error;
+
+/// Secret key holding a personal access token.
+pub const TOKEN_KEY: &str = "token";
+
+/// Secret key holding the ID of a GitHub App.
+pub const APP_ID_KEY: &str = "githubAppID";
+
+/// Secret key holding the ID of the installation of the GitHub App to authenticate as.
+pub const APP_INSTALLATION_ID_KEY: &str = "githubAppInstallationID";
+
+/// Secret key holding the PEM encoded private key of the GitHub App.
+pub const APP_PRIVATE_KEY_KEY: &str = "githubAppPrivateKey";
+
+/// How requests to GitHub are authenticated.
+#[derive(Clone)]
+pub enum Credentials {
+  Anonymous,
+
+  /// A personal access token.
+  Token(String),
+
+  /// An installation of a GitHub App, which gets much higher rate limits than a user.
+  App(AppCredentials),
+}
+
+#[derive(Clone)]
+pub struct AppCredentials {
+  pub app_id: u64,
+  pub installation_id: u64,
+
+  /// PEM encoded RSA private key, as downloaded from the app settings.
+  pub private_key: String,
+}
+
+#[derive(Debug, Error)]
+pub enum CredentialsError {
+  #[error("secret must contain either '{TOKEN_KEY}', or '{APP_ID_KEY}', '{APP_INSTALLATION_ID_KEY}' and '{APP_PRIVATE_KEY_KEY}'")]
+  Missing,
+
+  #[error("secret is missing '{0}'")]
+  MissingKey(&'static str),
+
+  #[error("'{0}' is not valid utf-8")]
+  InvalidUtf8(&'static str),
+
+  #[error("'{0}' is not a valid id")]
+  InvalidId(&'static str),
+}
+
+impl Credentials {
+  /// Reads credentials from the data of a Secret, preferring GitHub App credentials over a token
+  /// when both are present.
+  pub fn from_secret_data(data: &BTreeMap<String, ByteString>) -> Result<Self, CredentialsError> {
+    let value = |key: &'static str| -> Result<Option<String>, CredentialsError> {
+      match data.get(key) {
+        None => Ok(None),
+        Some(value) => String::from_utf8(value.0.clone())
+          .map(|v| Some(v.trim().to_owned()))
+          .map_err(|_| CredentialsError::InvalidUtf8(key)),
+      }
+    };
+
+    let id = |key: &'static str| -> Result<u64, CredentialsError> {
+      value(key)?
+        .ok_or(CredentialsError::MissingKey(key))?
+        .parse()
+        .map_err(|_| CredentialsError::InvalidId(key))
+    };
+
+    if data.contains_key(APP_ID_KEY) {
+      return Ok(Credentials::App(AppCredentials {
+        app_id: id(APP_ID_KEY)?,
+        installation_id: id(APP_INSTALLATION_ID_KEY)?,
+        private_key: value(APP_PRIVATE_KEY_KEY)?
+          .ok_or(CredentialsError::MissingKey(APP_PRIVATE_KEY_KEY))?,
+      }));
+    }
+
+    match value(TOKEN_KEY)? {
+      Some(token) => Ok(Credentials::Token(token)),
+      None => Err(CredentialsError::Missing),
+    }
+  }
+}
+
+// credentials end up in logs through error reports, so never print the secrets themselves
+impl fmt::Debug for Credentials {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Credentials::Anonymous => f.write_str("Anonymous"),
+      Credentials::Token(_) => f.write_str("Token(..)"),
+      Credentials::App(app) => fmt::Debug::fmt(app, f),
+    }
+  }
+}
+
+impl fmt::Debug for AppCredentials {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("AppCredentials")
+      .field("app_id", &self.app_id)
+      .field("installation_id", &self.installation_id)
+      .finish_non_exhaustive()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn data(entries: &[(&str, &str)]) -> BTreeMap<String, ByteString> {
+    entries
+      .iter()
+      .map(|(k, v)| (k.to_string(), ByteString(v.as_bytes().to_vec())))
+      .collect()
+  }
+
+  #[test]
+  fn reads_token() {
+    let credentials = Credentials::from_secret_data(&data(&[(TOKEN_KEY, "ghp_abc\n")])).unwrap();
+    assert!(matches!(credentials, Credentials::Token(token) if token == "ghp_abc"));
+  }
+
+  #[test]
+  fn prefers_app() {
+    let credentials = Credentials::from_secret_data(&data(&[
+      (TOKEN_KEY, "ghp_abc"),
+      (APP_ID_KEY, "12"),
+      (APP_INSTALLATION_ID_KEY, "34"),
+      (APP_PRIVATE_KEY_KEY, "pem"),
+    ]))
+    .unwrap();
+
+    match credentials {
+      Credentials::App(app) => {
+        assert_eq!(app.app_id, 12);
+        assert_eq!(app.installation_id, 34);
+      }
+      other => panic!("unexpected credentials {other:?}"),
+    }
+  }
+
+  #[test]
+  fn rejects_incomplete_app() {
+    let err = Credentials::from_secret_data(&data(&[(APP_ID_KEY, "12")])).unwrap_err();
+    assert!(matches!(
+      err,
+      CredentialsError::MissingKey(APP_INSTALLATION_ID_KEY)
+    ));
+
+    let err = Credentials::from_secret_data(&data(&[])).unwrap_err();
+    assert!(matches!(err, CredentialsError::Missing));
+  }
+}
@@ -0,0 +1,233 @@
+mod app;
+mod cache;
+mod credentials;
+mod ratelimit;
+
+use app::TokenCache;
+use cache::{CachedResponse, ResponseCache};
+use ratelimit::{Identity, RateLimits};
+use reqwest::{
+  header::{HeaderMap, HeaderValue, ACCEPT, ETAG, IF_NONE_MATCH},
+  StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{sync::Arc, time::Duration};
+use thiserror::Error;
+
+pub use credentials::*;
+
+/// The public GitHub API.
+pub const DEFAULT_BASE_URL: &str = "https://api.github.com";
+
+/// Errors of requests to GitHub. Responses are told apart by status, so that controllers can
+/// report why a request failed, and whether retrying it will help.
+#[derive(Debug, Error)]
+pub enum Error {
+  #[error(transparent)]
+  Http(#[from] reqwest::Error),
+
+  #[error("'{0}' was not found on github")]
+  NotFound(String),
+
+  #[error("github rejected the credentials")]
+  Unauthorized,
+
+  #[error("access to '{0}' is forbidden")]
+  Forbidden(String),
+
+  /// The rate limit of the credentials is used up. Requests made before `retry_after` fail
+  /// without reaching GitHub.
+  #[error("github rate limit exceeded, retry in {}s", .retry_after.as_secs())]
+  RateLimited { retry_after: Duration },
+
+  #[error("github failed with {0}")]
+  Server(StatusCode),
+
+  #[error("unexpected github response {0}")]
+  Status(StatusCode),
+
+  #[error("invalid github response")]
+  InvalidResponse(#[from] serde_json::Error),
+
+  #[error("invalid github app private key")]
+  InvalidPrivateKey,
+
+  #[error("failed to sign github app token")]
+  Signing,
+
+  #[error("invalid installation token expiry '{0}'")]
+  InvalidExpiry(String),
+}
+
+impl Error {
+  /// How long to wait before retrying, if the request was rate limited.
+  pub fn retry_after(&self) -> Option<Duration> {
+    match self {
+      Self::RateLimited { retry_after } => Some(*retry_after),
+      _ => None,
+    }
+  }
+}
+
+/// Fails if a request for `path` was answered with an unsuccessful `status`.
+pub(crate) fn check_status(
+  status: StatusCode,
+  headers: &HeaderMap,
+  path: &str,
+) -> Result<(), Error> {
+  if status.is_success() {
+    return Ok(());
+  }
+
+  if let Some(retry_after) = ratelimit::retry_after(status, headers) {
+    return Err(Error::RateLimited { retry_after });
+  }
+
+  Err(match status {
+    StatusCode::NOT_FOUND => Error::NotFound(path.into()),
+    StatusCode::UNAUTHORIZED => Error::Unauthorized,
+    StatusCode::FORBIDDEN => Error::Forbidden(path.into()),
+    status if status.is_server_error() => Error::Server(status),
+    status => Error::Status(status),
+  })
+}
+
+/// Client for the GitHub API. Credentials are given per request, so a single client can serve
+/// every object of a controller, while installation tokens are cached across requests.
+///
+/// The client keeps track of the rate limit of every set of credentials: once it is used up,
+/// requests fail with [Error::RateLimited] until it resets, without reaching GitHub. Responses
+/// are cached by their `ETag`, and requested again conditionally, so that polling a resource
+/// which did not change does not use up the rate limit.
+pub struct GitHub {
+  http: reqwest::Client,
+  base_url: String,
+  tokens: TokenCache,
+  limits: RateLimits,
+  cache: ResponseCache,
+}
+
+/// How many items are requested per page of paginated listings, the most GitHub allows.
+const PAGE_SIZE: usize = 100;
+
+/// A GitHub user, as listed among the members of a team.
+#[derive(Clone, Debug, Deserialize)]
+pub struct User {
+  pub id: u64,
+  pub login: String,
+}
+
+/// A public SSH key of a GitHub user.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SshKey {
+  pub id: u64,
+  pub key: String,
+}
+
+impl GitHub {
+  pub fn new(user_agent: &str) -> Result<Self, Error> {
+    let http = reqwest::Client::builder()
+      .user_agent(user_agent)
+      .https_only(true)
+      .build()?;
+
+    Ok(Self {
+      http,
+      base_url: DEFAULT_BASE_URL.into(),
+      tokens: TokenCache::default(),
+      limits: RateLimits::default(),
+      cache: ResponseCache::default(),
+    })
+  }
+
+  /// Use the API at `base_url`, for instance that of a GitHub Enterprise Server.
+  pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+    self.base_url = base_url.into();
+    self
+  }
+
+  /// Fetches `path` (relative to the API root) and decodes the JSON response.
+  pub async fn get<T: DeserializeOwned>(
+    &self,
+    credentials: &Credentials,
+    path: &str,
+  ) -> Result<T, Error> {
+    let identity = Identity::of(credentials);
+    self.limits.check(&identity)?;
+
+    let mut request = self.http.get(format!("{}{path}", self.base_url)).header(
+      ACCEPT,
+      HeaderValue::from_static("application/vnd.github.v3+json"),
+    );
+
+    request = match credentials {
+      Credentials::Anonymous => request,
+      Credentials::Token(token) => request.bearer_auth(token),
+      Credentials::App(app) => {
+        let token = self.tokens.token(&self.http, &self.base_url, app).await?;
+        request.bearer_auth(token)
+      }
+    };
+
+    let cached = self.cache.get(&identity, path);
+    if let Some(cached) = &cached {
+      request = request.header(IF_NONE_MATCH, cached.etag.clone());
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+    self.limits.record(&identity, status, response.headers());
+
+    if let (StatusCode::NOT_MODIFIED, Some(cached)) = (status, cached) {
+      return Ok(serde_json::from_slice(&cached.body)?);
+    }
+
+    if let Err(error) = check_status(status, response.headers(), path) {
+      self.cache.remove(&identity, path);
+      return Err(error);
+    }
+
+    let etag = response.headers().get(ETAG).cloned();
+    let body: Arc<[u8]> = Arc::from(&*response.bytes().await?);
+    let value = serde_json::from_slice(&body)?;
+    if let Some(etag) = etag {
+      self
+        .cache
+        .insert(identity, path.into(), CachedResponse { etag, body });
+    }
+
+    Ok(value)
+  }
+
+  /// The public SSH keys of `user`.
+  pub async fn user_ssh_keys(
+    &self,
+    credentials: &Credentials,
+    user: &str,
+  ) -> Result<Vec<SshKey>, Error> {
+    self.get(credentials, &format!("/users/{user}/keys")).await
+  }
+
+  /// The members of the team `team` (by slug) of `org`, including those of its child teams.
+  pub async fn team_members(
+    &self,
+    credentials: &Credentials,
+    org: &str,
+    team: &str,
+  ) -> Result<Vec<User>, Error> {
+    let mut members = Vec::new();
+    // pages are requested until one is not full, rather than following the `Link` header, so
+    // that every page is cached on its own
+    for page in 1.. {
+      let path = format!("/orgs/{org}/teams/{team}/members?per_page={PAGE_SIZE}&page={page}");
+      let users: Vec<User> = self.get(credentials, &path).await?;
+      let last = users.len() < PAGE_SIZE;
+      members.extend(users);
+      if last {
+        break;
+      }
+    }
+
+    Ok(members)
+  }
+}
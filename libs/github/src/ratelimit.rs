@@ -0,0 +1,194 @@
+use crate::{Credentials, Error};
+use reqwest::{
+  header::{HeaderMap, RETRY_AFTER},
+  StatusCode,
+};
+use std::{
+  collections::HashMap,
+  sync::Mutex,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How long to wait after a secondary rate limit which does not say how long to wait for, as
+/// recommended by GitHub.
+const SECONDARY_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// Who requests are rate limited as: GitHub keeps a separate limit for every user, or app
+/// installation, and one per IP address for anonymous requests.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum Identity {
+  Anonymous,
+
+  /// A token, by its SHA256 digest, so the cache does not hold on to it.
+  Token(Vec<u8>),
+
+  App {
+    app_id: u64,
+    installation_id: u64,
+  },
+}
+
+impl Identity {
+  pub(crate) fn of(credentials: &Credentials) -> Self {
+    match credentials {
+      Credentials::Anonymous => Self::Anonymous,
+      Credentials::Token(token) => Self::Token(
+        ring::digest::digest(&ring::digest::SHA256, token.as_bytes())
+          .as_ref()
+          .to_vec(),
+      ),
+      Credentials::App(app) => Self::App {
+        app_id: app.app_id,
+        installation_id: app.installation_id,
+      },
+    }
+  }
+}
+
+fn rejected(status: StatusCode) -> bool {
+  matches!(
+    status,
+    StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+  )
+}
+
+/// How long to wait before `headers` allow another request, if a request was answered with
+/// `status` because it exceeded a rate limit, or the limit is used up.
+fn wait(status: StatusCode, headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+  let header = |name: &str| headers.get(name)?.to_str().ok()?.parse::<u64>().ok();
+  if rejected(status) {
+    if let Some(seconds) = header(RETRY_AFTER.as_str()) {
+      return Some(Duration::from_secs(seconds));
+    }
+  }
+
+  if header("x-ratelimit-remaining") == Some(0) {
+    let reset = UNIX_EPOCH + Duration::from_secs(header("x-ratelimit-reset")?);
+    // waits at least a second, so the reset has passed for GitHub as well
+    let wait = reset.duration_since(now).unwrap_or_default();
+    return Some(wait.max(Duration::from_secs(1)));
+  }
+
+  // secondary rate limits are only told apart from other denials by the status
+  (status == StatusCode::TOO_MANY_REQUESTS).then_some(SECONDARY_LIMIT_WAIT)
+}
+
+/// How long to wait before retrying a request which was answered with `status` and `headers`, if
+/// it was rejected because of a rate limit. Other 403s are denials which retrying will not help.
+pub(crate) fn retry_after(status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+  if !rejected(status) {
+    return None;
+  }
+
+  wait(status, headers, SystemTime::now())
+}
+
+/// Holds off the requests of every identity which used up its rate limit, until the limit resets,
+/// rather than sending requests GitHub will reject, which it may penalize.
+#[derive(Default)]
+pub(crate) struct RateLimits(Mutex<HashMap<Identity, SystemTime>>);
+
+impl RateLimits {
+  /// Fails with [Error::RateLimited] if `identity` has to wait before making another request.
+  pub(crate) fn check(&self, identity: &Identity) -> Result<(), Error> {
+    let mut limits = self.0.lock().unwrap();
+    let until = match limits.get(identity) {
+      Some(until) => *until,
+      None => return Ok(()),
+    };
+
+    match until.duration_since(SystemTime::now()) {
+      Ok(retry_after) if !retry_after.is_zero() => Err(Error::RateLimited { retry_after }),
+      _ => {
+        limits.remove(identity);
+        Ok(())
+      }
+    }
+  }
+
+  /// Records the rate limit of `identity` from a response with `status` and `headers`, holding
+  /// off its next requests if the limit is used up.
+  pub(crate) fn record(&self, identity: &Identity, status: StatusCode, headers: &HeaderMap) {
+    let now = SystemTime::now();
+    if let Some(wait) = wait(status, headers, now) {
+      self.0.lock().unwrap().insert(identity.clone(), now + wait);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn headers(entries: &[(&'static str, &str)]) -> HeaderMap {
+    entries
+      .iter()
+      .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+      .collect()
+  }
+
+  #[test]
+  fn waits_until_the_limit_resets() {
+    let now = UNIX_EPOCH + Duration::from_secs(1000);
+    let used_up = headers(&[
+      ("x-ratelimit-remaining", "0"),
+      ("x-ratelimit-reset", "1300"),
+    ]);
+    assert_eq!(
+      wait(StatusCode::FORBIDDEN, &used_up, now),
+      Some(Duration::from_secs(300))
+    );
+    // the last request of the limit succeeds, but the next one has to wait
+    assert_eq!(
+      wait(StatusCode::OK, &used_up, now),
+      Some(Duration::from_secs(300))
+    );
+
+    let remaining = headers(&[
+      ("x-ratelimit-remaining", "10"),
+      ("x-ratelimit-reset", "1300"),
+    ]);
+    assert_eq!(wait(StatusCode::OK, &remaining, now), None);
+    // denied for other reasons
+    assert_eq!(wait(StatusCode::FORBIDDEN, &remaining, now), None);
+    assert_eq!(retry_after(StatusCode::FORBIDDEN, &remaining), None);
+    assert_eq!(retry_after(StatusCode::OK, &used_up), None);
+  }
+
+  #[test]
+  fn waits_for_secondary_limits() {
+    let now = SystemTime::now();
+    assert_eq!(
+      wait(
+        StatusCode::FORBIDDEN,
+        &headers(&[("retry-after", "30")]),
+        now
+      ),
+      Some(Duration::from_secs(30))
+    );
+    assert_eq!(
+      wait(StatusCode::TOO_MANY_REQUESTS, &HeaderMap::new(), now),
+      Some(SECONDARY_LIMIT_WAIT)
+    );
+  }
+
+  #[test]
+  fn holds_off_requests_until_the_reset() {
+    let limits = RateLimits::default();
+    let identity = Identity::Anonymous;
+    let retry = headers(&[("retry-after", "30")]);
+
+    assert!(limits.check(&identity).is_ok());
+    limits.record(&identity, StatusCode::FORBIDDEN, &retry);
+    assert!(matches!(
+      limits.check(&identity),
+      Err(Error::RateLimited { .. })
+    ));
+
+    let other = Identity::App {
+      app_id: 1,
+      installation_id: 2,
+    };
+    assert!(limits.check(&other).is_ok());
+  }
+}
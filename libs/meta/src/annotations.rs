@@ -1,6 +1,11 @@
 use fluxcd_utils_macros::api_object;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
 use schemars::JsonSchema;
-use std::collections::BTreeMap;
+use std::{
+  collections::BTreeMap,
+  time::{Duration, SystemTime},
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
 /// ReconcileRequestAnnotation is the annotation used for triggering a reconciliation
 /// outside of a defined schedule. The value is interpreted as a token, and any change
@@ -13,6 +18,170 @@ pub fn get_reconcile_annotation_value(annotations: &BTreeMap<String, String>) ->
   annotations.get(RECONCILE_REQUEST_ANNOTATION)
 }
 
+/// ReconcileRequestTime returns the time a reconcile request was made at, if its token is an RFC 3339
+/// timestamp, as written by the flux CLI. Tokens are opaque, so tokens which are not timestamps are
+/// valid too, they just do not tell when they were made.
+pub fn reconcile_request_time(token: &str) -> Option<OffsetDateTime> {
+  OffsetDateTime::parse(token.trim(), &Rfc3339).ok()
+}
+
+/// IsSameReconcileRequest returns whether the tokens `a` and `b` are the same request: the same instant
+/// if both are timestamps, even if written with a different offset or precision, and otherwise the same
+/// string. Tokens are never ordered, as the clocks of the clients making requests may be skewed.
+pub fn is_same_reconcile_request(a: &str, b: &str) -> bool {
+  match (reconcile_request_time(a), reconcile_request_time(b)) {
+    (Some(a), Some(b)) => a == b,
+    _ => a == b,
+  }
+}
+
+/// ReconcileRequestAge returns how long before `now` a reconcile request was made, if its token is a
+/// timestamp. A request from a client whose clock is ahead seems to be made in the future, which counts
+/// as having been made just now, rather than failing.
+pub fn reconcile_request_age(token: &str, now: SystemTime) -> Option<Duration> {
+  let age = OffsetDateTime::from(now) - reconcile_request_time(token)?;
+  Some(age.try_into().unwrap_or(Duration::ZERO))
+}
+
+/// IgnoreAnnotation can be set to "true" on a namespace to make the controllers skip every resource in that
+/// namespace, e.g. to roll them out one tenant at a time.
+pub const IGNORE_ANNOTATION: &str = "fluxcd.yolodev.io/ignore";
+
+/// IsIgnored returns whether the annotations of a namespace opt it out of reconciliation.
+pub fn is_ignored(annotations: &BTreeMap<String, String>) -> bool {
+  annotations
+    .get(IGNORE_ANNOTATION)
+    .is_some_and(|value| value == "true")
+}
+
+/// ForceApplyAnnotation can be set to "true" on an object to make the controllers recreate the
+/// objects they apply for it when a change cannot be applied in place, like a change to an
+/// immutable field.
+pub const FORCE_APPLY_ANNOTATION: &str = "fluxcd.yolodev.io/force";
+
+/// IsForceApply returns whether the annotations of an object ask for its objects to be recreated
+/// when a change cannot be applied in place.
+pub fn is_force_apply(annotations: &BTreeMap<String, String>) -> bool {
+  annotations
+    .get(FORCE_APPLY_ANNOTATION)
+    .is_some_and(|value| value == "true")
+}
+
+/// ChecksumAnnotation records the checksum of the source an object was last applied from, so that
+/// objects whose source did not change can be skipped.
+pub const CHECKSUM_ANNOTATION: &str = "kustomize.toolkit.fluxcd.io/checksum";
+
+/// PruneAnnotation set to PruneDisabledValue keeps an object in the cluster after it was removed
+/// from its source.
+pub const PRUNE_ANNOTATION: &str = "kustomize.toolkit.fluxcd.io/prune";
+
+/// PruneDisabledValue is the value of PruneAnnotation which disables pruning.
+pub const PRUNE_DISABLED_VALUE: &str = "disabled";
+
+/// ForceAnnotation set to ForceEnabledValue makes changes which cannot be applied in place replace
+/// the object instead.
+pub const FORCE_ANNOTATION: &str = "kustomize.toolkit.fluxcd.io/force";
+
+/// ForceEnabledValue is the value of ForceAnnotation which enables replacing objects.
+pub const FORCE_ENABLED_VALUE: &str = "enabled";
+
+/// OriginRevisionAnnotation records the revision of the source an object originates from, like the
+/// commit of a Git repository.
+pub const ORIGIN_REVISION_ANNOTATION: &str = "org.opencontainers.image.revision";
+
+/// FluxAnnotations gives typed access to the well-known Flux annotations of an object.
+pub trait FluxAnnotations {
+  /// ReconcileRequest returns the token of the most recent reconcile request.
+  fn reconcile_request(&self) -> Option<&str>;
+
+  /// Checksum returns the checksum of the source the object was last applied from.
+  fn checksum(&self) -> Option<&str>;
+
+  /// SetChecksum sets the checksum of the source the object was applied from, or removes it.
+  fn set_checksum(&mut self, checksum: Option<impl Into<String>>);
+
+  /// IsPruneDisabled returns whether the object is kept after it was removed from its source.
+  fn is_prune_disabled(&self) -> bool;
+
+  /// SetPruneDisabled sets whether the object is kept after it was removed from its source.
+  fn set_prune_disabled(&mut self, disabled: bool);
+
+  /// IsForceEnabled returns whether the object is replaced when it cannot be changed in place.
+  fn is_force_enabled(&self) -> bool;
+
+  /// SetForceEnabled sets whether the object is replaced when it cannot be changed in place.
+  fn set_force_enabled(&mut self, enabled: bool);
+
+  /// OriginRevision returns the revision of the source the object originates from.
+  fn origin_revision(&self) -> Option<&str>;
+
+  /// SetOriginRevision sets the revision of the source the object originates from, or removes it.
+  fn set_origin_revision(&mut self, revision: Option<impl Into<String>>);
+}
+
+impl FluxAnnotations for ObjectMeta {
+  fn reconcile_request(&self) -> Option<&str> {
+    annotation(self, RECONCILE_REQUEST_ANNOTATION)
+  }
+
+  fn checksum(&self) -> Option<&str> {
+    annotation(self, CHECKSUM_ANNOTATION)
+  }
+
+  fn set_checksum(&mut self, checksum: Option<impl Into<String>>) {
+    set_annotation(self, CHECKSUM_ANNOTATION, checksum.map(Into::into));
+  }
+
+  fn is_prune_disabled(&self) -> bool {
+    annotation(self, PRUNE_ANNOTATION) == Some(PRUNE_DISABLED_VALUE)
+  }
+
+  fn set_prune_disabled(&mut self, disabled: bool) {
+    let value = disabled.then(|| PRUNE_DISABLED_VALUE.to_string());
+    set_annotation(self, PRUNE_ANNOTATION, value);
+  }
+
+  fn is_force_enabled(&self) -> bool {
+    annotation(self, FORCE_ANNOTATION) == Some(FORCE_ENABLED_VALUE)
+  }
+
+  fn set_force_enabled(&mut self, enabled: bool) {
+    let value = enabled.then(|| FORCE_ENABLED_VALUE.to_string());
+    set_annotation(self, FORCE_ANNOTATION, value);
+  }
+
+  fn origin_revision(&self) -> Option<&str> {
+    annotation(self, ORIGIN_REVISION_ANNOTATION)
+  }
+
+  fn set_origin_revision(&mut self, revision: Option<impl Into<String>>) {
+    set_annotation(self, ORIGIN_REVISION_ANNOTATION, revision.map(Into::into));
+  }
+}
+
+fn annotation<'a>(meta: &'a ObjectMeta, name: &str) -> Option<&'a str> {
+  meta.annotations.as_ref()?.get(name).map(String::as_str)
+}
+
+/// Sets the annotation `name` to `value`, or removes it. Objects left without annotations have none
+/// at all, rather than an empty set.
+fn set_annotation(meta: &mut ObjectMeta, name: &str, value: Option<String>) {
+  match value {
+    Some(value) => {
+      let annotations = meta.annotations.get_or_insert_with(BTreeMap::new);
+      annotations.insert(name.to_string(), value);
+    }
+    None => {
+      if let Some(annotations) = &mut meta.annotations {
+        annotations.remove(name);
+        if annotations.is_empty() {
+          meta.annotations = None;
+        }
+      }
+    }
+  }
+}
+
 api_object! {
   /// ReconcileRequestStatus is a struct to embed in a status type, so that all types using the mechanism have the same
   /// field.
@@ -64,10 +233,8 @@ impl StatusWithHandledReconcileRequestSetter for ReconcileRequestStatus {
 
 #[cfg(test)]
 mod tests {
-  use std::time::SystemTime;
-
   use super::*;
-  use time::{self, format_description::well_known::Rfc3339, OffsetDateTime};
+  use test_case::test_case;
 
   struct WhateverStatus {
     reconsiler_request_status: ReconcileRequestStatus,
@@ -100,6 +267,83 @@ mod tests {
     odt.format(&Rfc3339).expect("valid date")
   }
 
+  #[test]
+  fn test_reconcile_request_timestamps() {
+    let time = reconcile_request_time("2022-03-01T10:00:00.5Z").unwrap();
+    assert_eq!(time.unix_timestamp(), 1_646_128_800);
+
+    assert!(is_same_reconcile_request(
+      "2022-03-01T10:00:00.5Z",
+      "2022-03-01T11:00:00.500000000+01:00"
+    ));
+    assert!(!is_same_reconcile_request(
+      "2022-03-01T10:00:00Z",
+      "2022-03-01T10:00:01Z"
+    ));
+
+    let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_646_128_810);
+    assert_eq!(
+      reconcile_request_age("2022-03-01T10:00:00Z", now),
+      Some(Duration::from_secs(10))
+    );
+    // made by a client whose clock is ahead
+    assert_eq!(
+      reconcile_request_age("2022-03-01T10:01:00Z", now),
+      Some(Duration::ZERO)
+    );
+  }
+
+  #[test_case("deploy-42")]
+  #[test_case("1646128800")]
+  #[test_case("2022-03-01 10:00:00")]
+  #[test_case("")]
+  fn test_opaque_reconcile_requests(token: &str) {
+    assert_eq!(reconcile_request_time(token), None);
+    assert_eq!(reconcile_request_age(token, SystemTime::now()), None);
+    assert!(is_same_reconcile_request(token, token));
+    assert!(!is_same_reconcile_request(token, "2022-03-01T10:00:00Z"));
+    assert!(!is_same_reconcile_request(token, "other"));
+  }
+
+  #[test]
+  fn test_is_ignored() {
+    let mut annotations = BTreeMap::new();
+    assert!(!is_ignored(&annotations));
+
+    annotations.insert(IGNORE_ANNOTATION.into(), "false".into());
+    assert!(!is_ignored(&annotations));
+
+    annotations.insert(IGNORE_ANNOTATION.into(), "true".into());
+    assert!(is_ignored(&annotations));
+  }
+
+  #[test]
+  fn test_flux_annotations() {
+    let mut meta = ObjectMeta::default();
+    assert_eq!(meta.checksum(), None);
+    assert!(!meta.is_prune_disabled());
+    assert!(!meta.is_force_enabled());
+
+    meta.set_checksum(Some("sha256:abc"));
+    meta.set_prune_disabled(true);
+    meta.set_force_enabled(true);
+    meta.set_origin_revision(Some("main@sha1:123"));
+    assert_eq!(meta.checksum(), Some("sha256:abc"));
+    assert!(meta.is_prune_disabled());
+    assert!(meta.is_force_enabled());
+    assert_eq!(meta.origin_revision(), Some("main@sha1:123"));
+    assert_eq!(
+      meta.annotations.as_ref().unwrap().get(PRUNE_ANNOTATION),
+      Some(&PRUNE_DISABLED_VALUE.to_string())
+    );
+
+    meta.set_checksum(None::<String>);
+    meta.set_prune_disabled(false);
+    meta.set_force_enabled(false);
+    meta.set_origin_revision(None::<String>);
+    assert_eq!(meta.annotations, None);
+  }
+
   #[test]
   fn test_get_annotation_value() {
     let mut obj = Whatever {
@@ -0,0 +1,91 @@
+use fluxcd_utils_macros::api_object;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+use schemars::JsonSchema;
+
+api_object! {
+  /// Artifact represents the output of a source reconciliation, served over HTTP by the
+  /// controller so that consumers can download it.
+  #[derive(Default, PartialEq, Debug, Clone, JsonSchema)]
+  pub struct Artifact {
+    /// Path is the relative file path of the artifact in the storage of the controller.
+    path: String = "path",
+
+    /// URL is the HTTP address the artifact can be downloaded from.
+    url: String = "url",
+
+    /// Revision is a human readable identifier of the content of the artifact.
+    revision: String = "revision",
+
+    /// Checksum is the SHA256 checksum of the artifact, in the form `sha256:<hex>`.
+    checksum: String = "checksum",
+
+    /// LastUpdateTime is the time the artifact was last updated.
+    last_update_time: Time = "lastUpdateTime",
+  }
+}
+
+impl Artifact {
+  pub fn new(
+    path: impl Into<String>,
+    url: impl Into<String>,
+    revision: impl Into<String>,
+    checksum: impl Into<String>,
+    last_update_time: Time,
+  ) -> Self {
+    Self {
+      path: Some(path.into()),
+      url: Some(url.into()),
+      revision: Some(revision.into()),
+      checksum: Some(checksum.into()),
+      last_update_time: Some(last_update_time),
+    }
+  }
+
+  pub fn path(&self) -> Option<&str> {
+    self.path.as_deref()
+  }
+
+  pub fn url(&self) -> Option<&str> {
+    self.url.as_deref()
+  }
+
+  pub fn revision(&self) -> Option<&str> {
+    self.revision.as_deref()
+  }
+
+  pub fn checksum(&self) -> Option<&str> {
+    self.checksum.as_deref()
+  }
+
+  pub fn last_update_time(&self) -> Option<&Time> {
+    self.last_update_time.as_ref()
+  }
+
+  /// Whether `other` holds the same content at the same location, regardless of when either was
+  /// updated.
+  pub fn has_same_content(&self, other: &Artifact) -> bool {
+    self.path == other.path && self.checksum == other.checksum
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use k8s_openapi::chrono::{TimeZone, Utc};
+
+  #[test]
+  fn same_content_ignores_update_time() {
+    let artifact = |checksum: &str, secs| {
+      Artifact::new(
+        "keys/default/alice/authorized_keys",
+        "http://storage/keys/default/alice/authorized_keys",
+        checksum,
+        checksum,
+        Time(Utc.timestamp(secs, 0)),
+      )
+    };
+
+    assert!(artifact("sha256:a", 1).has_same_content(&artifact("sha256:a", 2)));
+    assert!(!artifact("sha256:a", 1).has_same_content(&artifact("sha256:b", 1)));
+  }
+}
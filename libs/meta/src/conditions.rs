@@ -1,4 +1,14 @@
 use fluxcd_utils_macros::str_enum;
+use k8s_openapi::{
+  apimachinery::pkg::apis::meta::v1::{Condition as KubeCondition, Time},
+  chrono::Utc,
+};
+use schemars::{
+  gen::SchemaGenerator,
+  schema::{ArrayValidation, InstanceType, Schema, SchemaObject},
+  JsonSchema,
+};
+use std::fmt;
 
 str_enum! {
   /// These constants define generic Condition types to be used by GitOps Toolkit components.
@@ -40,6 +50,18 @@ str_enum! {
     /// For more information about polarity patterns, see:
     /// https://github.com/kubernetes/community/blob/master/contributors/devel/sig-architecture/api-conventions.md#typical-status-properties
     Reconciling = "Reconciling",
+
+    /// ContentStaleCondition indicates the upstream of a source could not be refreshed for longer than its maxAge, so
+    /// the content it serves may be outdated.
+    /// The Condition adheres to an "abnormal-true" polarity pattern, and MUST only be present on the resource if the
+    /// Condition is True.
+    ContentStale = "ContentStale",
+
+    /// ReadOnlyCondition indicates the controller is running in read-only mode, and skipped writes it would otherwise
+    /// have made while reconciling the resource. The message lists the skipped writes.
+    /// The Condition adheres to an "abnormal-true" polarity pattern, and MUST only be present on the resource if the
+    /// Condition is True. It does not affect the Ready condition, as the resource was reconciled as far as allowed.
+    ReadOnly = "ReadOnly",
   }
 }
 
@@ -49,10 +71,10 @@ str_enum! {
   /// Making use of a generic Reason is RECOMMENDED whenever it can be applied to a Condition in which it provides
   /// sufficient context together with the type to summarize the meaning of the Condition cause.
   ///
-  /// Where any of the generic Condition reasons does not suffice, GitOps Toolkit components can introduce new reasons to
-  /// their API specification, or use an arbitrary PascalCase string when setting the Condition.
-  /// Declaration of domain common Condition reasons in the API specification is RECOMMENDED, as it eases observations
-  /// for user and computer.
+  /// Where any of the generic Condition reasons does not suffice, components declare the reasons specific to a kind in
+  /// its API crate, as a string enum of their own, and document them in the schema of its conditions with
+  /// [conditions_schema]. Conditions are never set with free-form reasons, so automation can match on them reliably
+  /// across versions: reasons are only ever added, never renamed or removed.
   ///
   /// For more information on Condition reason conventions, see:
   /// https://github.com/kubernetes/community/blob/master/contributors/devel/sig-architecture/api-conventions.md#typical-status-properties
@@ -85,12 +107,221 @@ str_enum! {
     /// SuspendedReason indicates a condition or event has observed a suspension, for
     /// example because a resource has been suspended, or a dependency is.
     Suspended = "Suspended",
+
+    /// PartialFailureReason indicates a reconciliation succeeded for some items of an aggregate resource, but failed
+    /// for others. The failed items are listed in the status of the resource.
+    PartialFailure = "PartialFailure",
+
+    /// PolicyViolationReason indicates a reconciliation failed because a policy vetoed one of the writes it was about to
+    /// make.
+    PolicyViolation = "PolicyViolation",
+
+    /// ValidationFailedReason indicates a reconciliation failed because an object it was about to write does not match
+    /// the schema of the cluster.
+    ValidationFailed = "ValidationFailed",
+
+    /// WritesSkippedReason indicates the controller runs in read-only mode, and skipped writes it would otherwise have
+    /// made. It is the reason of the ReadOnly condition.
+    WritesSkipped = "WritesSkipped",
+
+    /// SecretMissingReason indicates a reconciliation is waiting for a Secret referenced by the spec of the resource,
+    /// which does not exist. It is retried until the Secret is created.
+    SecretMissing = "SecretMissing",
+
+    /// KeyMissingReason indicates a reconciliation is waiting for a key of a Secret referenced by the spec of the
+    /// resource, which the Secret does not hold. It is retried until the key is added.
+    KeyMissing = "KeyMissing",
+
+    /// AccessDeniedReason indicates a reconciliation failed because the spec of the resource references an object it
+    /// is not allowed to access, like one in another namespace while cross-namespace references are disabled.
+    AccessDenied = "AccessDenied",
+  }
+}
+
+/// The conditions adhering to an "abnormal-true" polarity pattern, in the order they take precedence in when
+/// summarizing the Ready condition. They are only present on a resource while they are True.
+pub const NEGATIVE_POLARITY_CONDITIONS: [Condition; 3] = [
+  Condition::Stalled,
+  Condition::Reconciling,
+  Condition::ContentStale,
+];
+
+/// Creates a condition of type `condition`, transitioning now.
+pub fn new_condition(
+  condition: Condition,
+  status: bool,
+  reason: impl fmt::Display,
+  message: impl Into<String>,
+  observed_generation: Option<i64>,
+) -> KubeCondition {
+  KubeCondition {
+    type_: condition.to_string(),
+    status: if status { "True" } else { "False" }.into(),
+    reason: reason.to_string(),
+    message: message.into(),
+    last_transition_time: Time(Utc::now()),
+    observed_generation,
+  }
+}
+
+/// Returns the condition of type `condition` from `conditions`, if present.
+pub fn find_condition(
+  conditions: &[KubeCondition],
+  condition: Condition,
+) -> Option<&KubeCondition> {
+  let type_ = condition.to_string();
+  conditions.iter().find(|c| c.type_ == type_)
+}
+
+/// Whether the condition of type `condition` is present in `conditions`, and True.
+pub fn is_condition_true(conditions: &[KubeCondition], condition: Condition) -> bool {
+  find_condition(conditions, condition).is_some_and(|c| c.status == "True")
+}
+
+/// Sets `condition` in `conditions`, replacing any existing condition of the same type. The last transition time of
+/// the existing condition is kept if its status did not change.
+pub fn set_condition(conditions: &mut Vec<KubeCondition>, mut condition: KubeCondition) {
+  match conditions.iter_mut().find(|c| c.type_ == condition.type_) {
+    Some(existing) => {
+      if existing.status == condition.status {
+        condition.last_transition_time = existing.last_transition_time.clone();
+      }
+
+      *existing = condition;
+    }
+    None => conditions.push(condition),
+  }
+}
+
+/// Removes the condition of type `condition` from `conditions`, if present.
+pub fn remove_condition(conditions: &mut Vec<KubeCondition>, condition: Condition) {
+  let type_ = condition.to_string();
+  conditions.retain(|c| c.type_ != type_);
+}
+
+/// Marks the resource as Ready, as it is fully reconciled. This removes the Stalled and Reconciling conditions.
+pub fn mark_ready(
+  conditions: &mut Vec<KubeCondition>,
+  observed_generation: Option<i64>,
+  reason: impl fmt::Display,
+  message: impl Into<String>,
+) {
+  remove_condition(conditions, Condition::Stalled);
+  remove_condition(conditions, Condition::Reconciling);
+  set_condition(
+    conditions,
+    new_condition(Condition::Ready, true, reason, message, observed_generation),
+  );
+}
+
+/// Marks the reconciliation of the resource as Stalled, as retrying will not help until the resource is changed. This
+/// removes the Reconciling condition, and marks the resource as not Ready for the same reason.
+pub fn mark_stalled(
+  conditions: &mut Vec<KubeCondition>,
+  observed_generation: Option<i64>,
+  reason: impl fmt::Display,
+  message: impl Into<String>,
+) {
+  remove_condition(conditions, Condition::Reconciling);
+  set_condition(
+    conditions,
+    new_condition(
+      Condition::Stalled,
+      true,
+      reason,
+      message,
+      observed_generation,
+    ),
+  );
+  summarize(conditions, observed_generation);
+}
+
+/// Marks the resource as Reconciling, as the controller is still working on it, e.g. while retrying after a transient
+/// error. This removes the Stalled condition, and marks the resource as not Ready for the same reason.
+pub fn mark_reconciling(
+  conditions: &mut Vec<KubeCondition>,
+  observed_generation: Option<i64>,
+  reason: impl fmt::Display,
+  message: impl Into<String>,
+) {
+  remove_condition(conditions, Condition::Stalled);
+  set_condition(
+    conditions,
+    new_condition(
+      Condition::Reconciling,
+      true,
+      reason,
+      message,
+      observed_generation,
+    ),
+  );
+  summarize(conditions, observed_generation);
+}
+
+/// Derives the Ready condition from the negative polarity conditions: while any of them is True, the resource is not
+/// Ready, for the reason of the one which takes precedence. Otherwise the Ready condition is left as is, as only the
+/// controller knows when a resource is fully reconciled.
+pub fn summarize(conditions: &mut Vec<KubeCondition>, observed_generation: Option<i64>) {
+  let current: &[KubeCondition] = conditions;
+  let abnormal = NEGATIVE_POLARITY_CONDITIONS
+    .into_iter()
+    .find(|condition| is_condition_true(current, *condition))
+    .and_then(|condition| find_condition(current, condition))
+    .map(|c| (c.reason.clone(), c.message.clone()));
+
+  if let Some((reason, message)) = abnormal {
+    set_condition(
+      conditions,
+      new_condition(
+        Condition::Ready,
+        false,
+        reason,
+        message,
+        observed_generation,
+      ),
+    );
+  }
+}
+
+/// Schema of the conditions of a kind, which documents the reasons they may have: the generic ones of [Reason], and the
+/// `reasons` specific to the kind. Used with `#[schemars(schema_with = "...")]` on the conditions of its status.
+pub fn conditions_schema(gen: &mut SchemaGenerator, reasons: &[&str]) -> Schema {
+  let mut condition = KubeCondition::json_schema(gen).into_object();
+  if let Some(Schema::Object(reason)) = condition.object().properties.get_mut("reason") {
+    let reasons = Reason::VALUES
+      .iter()
+      .chain(reasons)
+      .map(|reason| format!("`{reason}`"))
+      .collect::<Vec<_>>()
+      .join(", ");
+    let metadata = reason.metadata();
+    let description = metadata.description.take().unwrap_or_default();
+    metadata.description = Some(
+      format!("{description} One of {reasons}; later versions may add reasons.")
+        .trim_start()
+        .into(),
+    );
   }
+
+  SchemaObject {
+    instance_type: Some(InstanceType::Array.into()),
+    array: Some(Box::new(ArrayValidation {
+      items: Some(Schema::Object(condition).into()),
+      ..Default::default()
+    })),
+    ..Default::default()
+  }
+  .into()
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use k8s_openapi::{
+    apimachinery::pkg::apis::meta::v1::Time,
+    chrono::{TimeZone, Utc},
+  };
+  use schemars::schema::SingleOrVec;
   use serde_test::*;
 
   #[test]
@@ -99,4 +330,117 @@ mod tests {
     assert_tokens(&Condition::Stalled, &[Token::Str("Stalled")]);
     assert_tokens(&Condition::Reconciling, &[Token::Str("Reconciling")]);
   }
+
+  fn condition(status: &str, seconds: i64) -> KubeCondition {
+    KubeCondition {
+      type_: Condition::ContentStale.to_string(),
+      status: status.into(),
+      reason: Reason::Failed.to_string(),
+      message: String::new(),
+      last_transition_time: Time(Utc.timestamp(seconds, 0)),
+      observed_generation: None,
+    }
+  }
+
+  #[test]
+  fn set_condition_keeps_transition_time() {
+    let mut conditions = vec![];
+    set_condition(&mut conditions, condition("True", 1));
+    set_condition(&mut conditions, condition("True", 2));
+    assert_eq!(conditions, vec![condition("True", 1)]);
+
+    set_condition(&mut conditions, condition("False", 3));
+    assert_eq!(conditions, vec![condition("False", 3)]);
+
+    remove_condition(&mut conditions, Condition::ContentStale);
+    assert!(conditions.is_empty());
+  }
+
+  fn types(conditions: &[KubeCondition]) -> Vec<(&str, &str)> {
+    conditions
+      .iter()
+      .map(|c| (c.type_.as_str(), c.status.as_str()))
+      .collect()
+  }
+
+  #[test]
+  fn marking_follows_kstatus() {
+    let mut conditions = vec![];
+    mark_reconciling(&mut conditions, Some(1), Reason::Progressing, "retrying");
+    assert_eq!(
+      types(&conditions),
+      [("Reconciling", "True"), ("Ready", "False")]
+    );
+
+    mark_stalled(&mut conditions, Some(1), Reason::Failed, "invalid spec");
+    assert_eq!(
+      types(&conditions),
+      [("Ready", "False"), ("Stalled", "True")]
+    );
+    let ready = find_condition(&conditions, Condition::Ready).unwrap();
+    assert_eq!(ready.reason, "Failed");
+    assert_eq!(ready.message, "invalid spec");
+
+    mark_ready(&mut conditions, Some(2), Reason::Succeeded, "done");
+    assert_eq!(types(&conditions), [("Ready", "True")]);
+    assert!(is_condition_true(&conditions, Condition::Ready));
+    assert_eq!(conditions[0].observed_generation, Some(2));
+  }
+
+  #[test]
+  fn summary_prefers_stalled() {
+    let mut conditions = vec![
+      new_condition(Condition::Ready, true, Reason::Succeeded, "", None),
+      new_condition(
+        Condition::ContentStale,
+        true,
+        "MaxAgeExceeded",
+        "stale",
+        None,
+      ),
+    ];
+    summarize(&mut conditions, None);
+    assert_eq!(
+      find_condition(&conditions, Condition::Ready)
+        .unwrap()
+        .reason,
+      "MaxAgeExceeded"
+    );
+
+    conditions.push(new_condition(
+      Condition::Stalled,
+      true,
+      Reason::Failed,
+      "broken",
+      None,
+    ));
+    summarize(&mut conditions, None);
+    assert_eq!(
+      find_condition(&conditions, Condition::Ready)
+        .unwrap()
+        .message,
+      "broken"
+    );
+  }
+
+  #[test]
+  fn conditions_schema_lists_reasons() {
+    let schema = conditions_schema(&mut SchemaGenerator::default(), &["MaxAgeExceeded"]);
+    let items = match schema.into_object().array.unwrap().items {
+      Some(SingleOrVec::Single(items)) => items.into_object(),
+      items => panic!("unexpected items {items:?}"),
+    };
+    let reason = items.object.unwrap().properties["reason"]
+      .clone()
+      .into_object();
+    let description = reason.metadata.unwrap().description.unwrap();
+    assert!(
+      description.ends_with(
+        "One of `Succeeded`, `Failed`, `Progressing`, `Suspended`, `PartialFailure`, \
+         `PolicyViolation`, `ValidationFailed`, `WritesSkipped`, `MaxAgeExceeded`; \
+         later versions may add reasons."
+      ),
+      "{description}"
+    );
+  }
 }
@@ -1,8 +1,21 @@
 mod annotations;
+mod artifact_types;
 mod conditions;
+mod oci_types;
 mod reference_types;
+mod revision_types;
+mod source_types;
+mod status_types;
 mod time_types;
+mod verification_types;
 
 pub use annotations::*;
+pub use artifact_types::*;
 pub use conditions::*;
+pub use oci_types::*;
+pub use reference_types::*;
+pub use revision_types::*;
+pub use source_types::*;
+pub use status_types::*;
 pub use time_types::*;
+pub use verification_types::*;
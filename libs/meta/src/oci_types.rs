@@ -0,0 +1,92 @@
+use crate::NamespacedObjectReference;
+use fluxcd_utils_macros::api_object;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+use schemars::JsonSchema;
+
+api_object! {
+  /// OciPushTarget is an OCI repository the artifact of a source is pushed to, so that other
+  /// clusters can consume it with an OCIRepository. Experimental, only honored by controllers
+  /// built with the `oci-push` feature.
+  #[derive(Default, PartialEq, Debug, Clone, JsonSchema)]
+  pub struct OciPushTarget {
+    /// URL of the artifact, with the tag it is pushed as, like `oci://ghcr.io/org/keys:latest`.
+    url: String = "url",
+
+    /// SecretRef specifies an image pull Secret, of type `kubernetes.io/dockerconfigjson`, with
+    /// the credentials of the registry. Namespaced sources may only reference a Secret in their
+    /// own namespace.
+    secret_ref: NamespacedObjectReference = "secretRef",
+
+    /// Insecure allows pushing to the registry over plain HTTP.
+    insecure: bool = "insecure",
+  }
+}
+
+impl OciPushTarget {
+  pub fn url(&self) -> Option<&str> {
+    self.url.as_deref()
+  }
+
+  pub fn secret_ref(&self) -> Option<&NamespacedObjectReference> {
+    self.secret_ref.as_ref()
+  }
+
+  pub fn insecure(&self) -> bool {
+    self.insecure.unwrap_or_default()
+  }
+}
+
+api_object! {
+  /// OciPushedArtifact describes the artifact of a source last pushed to an OCI repository.
+  #[derive(Default, PartialEq, Debug, Clone, JsonSchema)]
+  pub struct OciPushedArtifact {
+    /// URL the artifact was pushed to, with its tag.
+    url: String = "url",
+
+    /// Digest is the digest of the manifest of the artifact, in the form `sha256:<hex>`.
+    digest: String = "digest",
+
+    /// Revision is the revision of the source the artifact holds the content of.
+    revision: String = "revision",
+
+    /// LastPushTime is the time the artifact was last pushed.
+    last_push_time: Time = "lastPushTime",
+  }
+}
+
+impl OciPushedArtifact {
+  pub fn new(
+    url: impl Into<String>,
+    digest: impl Into<String>,
+    revision: impl Into<String>,
+    last_push_time: Time,
+  ) -> Self {
+    Self {
+      url: Some(url.into()),
+      digest: Some(digest.into()),
+      revision: Some(revision.into()),
+      last_push_time: Some(last_push_time),
+    }
+  }
+
+  pub fn url(&self) -> Option<&str> {
+    self.url.as_deref()
+  }
+
+  pub fn digest(&self) -> Option<&str> {
+    self.digest.as_deref()
+  }
+
+  pub fn revision(&self) -> Option<&str> {
+    self.revision.as_deref()
+  }
+
+  pub fn last_push_time(&self) -> Option<&Time> {
+    self.last_push_time.as_ref()
+  }
+
+  /// Whether the artifact holds `revision` at `url` already, so it need not be pushed again.
+  pub fn is_pushed(&self, url: &str, revision: &str) -> bool {
+    self.url() == Some(url) && self.revision() == Some(revision)
+  }
+}
@@ -1,8 +1,9 @@
 use fluxcd_utils_macros::api_object;
+use schemars::JsonSchema;
 
 api_object! {
   /// LocalObjectReference contains enough information to locate the referenced Kubernetes resource object.
-  #[derive(Default, PartialEq, Hash, Debug, Clone)]
+  #[derive(Default, PartialEq, Hash, Debug, Clone, JsonSchema)]
   pub struct LocalObjectReference {
     /// Name of the referent.
     name: String = "name",
@@ -12,7 +13,7 @@ api_object! {
 api_object! {
   /// NamespacedObjectReference contains enough information to locate the referenced Kubernetes resource object in any
   /// namespace.
-  #[derive(Default, PartialEq, Hash, Debug, Clone)]
+  #[derive(Default, PartialEq, Hash, Debug, Clone, JsonSchema)]
   pub struct NamespacedObjectReference {
     /// Name of the referent.
     name: String = "name",
@@ -40,3 +41,35 @@ api_object! {
     namespace: String = "namespace",
   }
 }
+
+impl LocalObjectReference {
+  pub fn new(name: impl Into<String>) -> Self {
+    Self {
+      name: Some(name.into()),
+    }
+  }
+
+  /// Name of the referent.
+  pub fn name(&self) -> Option<&str> {
+    self.name.as_deref()
+  }
+}
+
+impl NamespacedObjectReference {
+  pub fn new(name: impl Into<String>, namespace: Option<impl Into<String>>) -> Self {
+    Self {
+      name: Some(name.into()),
+      namespace: namespace.map(Into::into),
+    }
+  }
+
+  /// Name of the referent.
+  pub fn name(&self) -> Option<&str> {
+    self.name.as_deref()
+  }
+
+  /// Namespace of the referent.
+  pub fn namespace(&self) -> Option<&str> {
+    self.namespace.as_deref()
+  }
+}
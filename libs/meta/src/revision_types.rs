@@ -0,0 +1,436 @@
+use schemars::{
+  schema::{InstanceType, SchemaObject, StringValidation},
+  JsonSchema,
+};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+/// Algorithm is a hash algorithm checksums are computed with.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Algorithm {
+  Sha1,
+  Sha256,
+  Sha384,
+  Sha512,
+}
+
+/// The algorithms checksums may be computed with, with their name and the size of their digests in
+/// bytes.
+const ALGORITHMS: &[(&str, Algorithm, usize)] = &[
+  ("sha1", Algorithm::Sha1, 20),
+  ("sha256", Algorithm::Sha256, 32),
+  ("sha384", Algorithm::Sha384, 48),
+  ("sha512", Algorithm::Sha512, 64),
+];
+
+impl Algorithm {
+  fn find(name: &str) -> Option<Self> {
+    ALGORITHMS
+      .iter()
+      .find(|(n, _, _)| *n == name)
+      .map(|(_, algorithm, _)| *algorithm)
+  }
+
+  /// The name of the algorithm, as it prefixes checksums.
+  pub fn as_str(&self) -> &'static str {
+    self.entry().0
+  }
+
+  /// The size of the digests of the algorithm, in bytes.
+  pub fn size(&self) -> usize {
+    self.entry().2
+  }
+
+  fn entry(&self) -> &'static (&'static str, Algorithm, usize) {
+    ALGORITHMS.iter().find(|(_, a, _)| a == self).unwrap()
+  }
+}
+
+impl fmt::Display for Algorithm {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+/// A Checksum is the digest of some content, written as `<algorithm>:<hex>`, like
+/// `sha256:e3b0c442...`. Digests are written in lowercase hex.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Checksum {
+  algorithm: Algorithm,
+  hex: String,
+}
+
+impl Checksum {
+  /// The checksum of `digest`, computed with `algorithm`.
+  pub fn new(algorithm: Algorithm, digest: &[u8]) -> Result<Self, ChecksumParseError> {
+    let hex = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+    if digest.len() != algorithm.size() {
+      return Err(ChecksumParseError::InvalidDigest {
+        input: format!("{algorithm}:{hex}"),
+        algorithm,
+      });
+    }
+
+    Ok(Self { algorithm, hex })
+  }
+
+  pub fn algorithm(&self) -> Algorithm {
+    self.algorithm
+  }
+
+  /// The digest, in lowercase hex.
+  pub fn hex(&self) -> &str {
+    &self.hex
+  }
+}
+
+impl fmt::Display for Checksum {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}:{}", self.algorithm, self.hex)
+  }
+}
+
+#[derive(Debug, Error)]
+pub enum ChecksumParseError {
+  #[error("invalid checksum '{input}', expected '<algorithm>:<hex>'")]
+  Invalid { input: String },
+
+  #[error("unknown algorithm '{algorithm}' in checksum '{input}'")]
+  UnknownAlgorithm { input: String, algorithm: String },
+
+  #[error("invalid {algorithm} digest in checksum '{input}'")]
+  InvalidDigest { input: String, algorithm: Algorithm },
+}
+
+impl TryFrom<&str> for Checksum {
+  type Error = ChecksumParseError;
+
+  fn try_from(value: &str) -> Result<Self, Self::Error> {
+    let (name, hex) = value
+      .split_once(':')
+      .ok_or_else(|| ChecksumParseError::Invalid {
+        input: value.into(),
+      })?;
+    let algorithm = Algorithm::find(name).ok_or_else(|| ChecksumParseError::UnknownAlgorithm {
+      input: value.into(),
+      algorithm: name.into(),
+    })?;
+
+    let is_hex = |c: u8| c.is_ascii_digit() || (b'a'..=b'f').contains(&c);
+    if hex.len() != algorithm.size() * 2 || !hex.bytes().all(is_hex) {
+      return Err(ChecksumParseError::InvalidDigest {
+        input: value.into(),
+        algorithm,
+      });
+    }
+
+    Ok(Self {
+      algorithm,
+      hex: hex.into(),
+    })
+  }
+}
+
+impl FromStr for Checksum {
+  type Err = ChecksumParseError;
+
+  #[inline]
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Checksum::try_from(s)
+  }
+}
+
+/// A Revision identifies the content of a source, in one of the forms of the Flux conventions:
+///
+/// - the checksum of the content, like `sha256:<hex>`, for sources which do not name their
+///   content, like buckets,
+/// - a semantic version, like `1.2.3`, for versioned sources, like Helm charts,
+/// - a name and the checksum it pointed at, like `main@sha1:<hex>` for the commit a branch
+///   pointed at, or `latest@sha256:<hex>` for the manifest an OCI tag pointed at.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Revision {
+  Checksum(Checksum),
+  Version(semver::Version),
+  Named { name: String, checksum: Checksum },
+}
+
+impl Revision {
+  /// The checksum the revision holds, if it is not a version.
+  pub fn checksum(&self) -> Option<&Checksum> {
+    match self {
+      Revision::Checksum(checksum) => Some(checksum),
+      Revision::Version(_) => None,
+      Revision::Named { checksum, .. } => Some(checksum),
+    }
+  }
+
+  /// The name of the revision, like the branch or tag, if it is named.
+  pub fn name(&self) -> Option<&str> {
+    match self {
+      Revision::Named { name, .. } => Some(name),
+      _ => None,
+    }
+  }
+
+  pub fn version(&self) -> Option<&semver::Version> {
+    match self {
+      Revision::Version(version) => Some(version),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for Revision {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Revision::Checksum(checksum) => checksum.fmt(f),
+      Revision::Version(version) => version.fmt(f),
+      Revision::Named { name, checksum } => write!(f, "{name}@{checksum}"),
+    }
+  }
+}
+
+#[derive(Debug, Error)]
+pub enum RevisionParseError {
+  #[error(
+    "invalid revision '{input}', expected a checksum, a semantic version or '<name>@<checksum>'"
+  )]
+  Invalid { input: String },
+
+  #[error(transparent)]
+  Checksum(#[from] ChecksumParseError),
+}
+
+impl TryFrom<&str> for Revision {
+  type Error = RevisionParseError;
+
+  fn try_from(value: &str) -> Result<Self, Self::Error> {
+    // checksums do not contain '@', while names may
+    if let Some((name, checksum)) = value.rsplit_once('@') {
+      if name.is_empty() {
+        return Err(RevisionParseError::Invalid {
+          input: value.into(),
+        });
+      }
+
+      return Ok(Revision::Named {
+        name: name.into(),
+        checksum: checksum.parse()?,
+      });
+    }
+
+    // versions do not contain ':'
+    if value.contains(':') {
+      return Ok(Revision::Checksum(value.parse()?));
+    }
+
+    semver::Version::parse(value)
+      .map(Revision::Version)
+      .map_err(|_| RevisionParseError::Invalid {
+        input: value.into(),
+      })
+  }
+}
+
+impl FromStr for Revision {
+  type Err = RevisionParseError;
+
+  #[inline]
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Revision::try_from(s)
+  }
+}
+
+/// The pattern of checksums, without anchors.
+fn checksum_pattern() -> String {
+  let algorithms = ALGORITHMS
+    .iter()
+    .map(|(name, _, size)| format!("{name}:[0-9a-f]{{{}}}", size * 2))
+    .collect::<Vec<_>>()
+    .join("|");
+  format!("({algorithms})")
+}
+
+/// The pattern of semantic versions, without anchors, as recommended by https://semver.org.
+const VERSION_PATTERN: &str = concat!(
+  r"(0|[1-9][0-9]*)\.(0|[1-9][0-9]*)\.(0|[1-9][0-9]*)",
+  r"(-(0|[1-9][0-9]*|[0-9]*[a-zA-Z-][0-9a-zA-Z-]*)(\.(0|[1-9][0-9]*|[0-9]*[a-zA-Z-][0-9a-zA-Z-]*))*)?",
+  r"(\+[0-9a-zA-Z-]+(\.[0-9a-zA-Z-]+)*)?",
+);
+
+fn revision_pattern() -> String {
+  format!("^((.+@)?{}|{VERSION_PATTERN})$", checksum_pattern())
+}
+
+macro_rules! string_serde {
+  ($ty:ident, $pattern:expr) => {
+    impl<'de> Deserialize<'de> for $ty {
+      fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+      where
+        D: Deserializer<'de>,
+      {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+          type Value = $ty;
+
+          fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(stringify!($ty))
+          }
+
+          fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+          where
+            E: de::Error,
+          {
+            $ty::try_from(v).map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+          }
+        }
+
+        deserializer.deserialize_str(Visitor)
+      }
+    }
+
+    impl Serialize for $ty {
+      fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+      where
+        S: serde::Serializer,
+      {
+        serializer.collect_str(self)
+      }
+    }
+
+    impl JsonSchema for $ty {
+      fn is_referenceable() -> bool {
+        false
+      }
+
+      fn schema_name() -> String {
+        stringify!($ty).to_owned()
+      }
+
+      fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        SchemaObject {
+          instance_type: Some(InstanceType::String.into()),
+          string: Some(Box::new(StringValidation {
+            pattern: Some($pattern),
+            ..Default::default()
+          })),
+          ..Default::default()
+        }
+        .into()
+      }
+    }
+  };
+}
+
+string_serde!(Checksum, format!("^{}$", checksum_pattern()));
+string_serde!(Revision, revision_pattern());
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use proptest::prelude::*;
+  use regex::Regex;
+  use serde_test::{assert_tokens, Token};
+
+  const SHA1: &str = "sha1:5394cb7f48332b2de7c17dd8b8384bbc84b7e738";
+  const SHA256: &str = "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+  fn schema_pattern() -> Regex {
+    Regex::new(&revision_pattern()).unwrap()
+  }
+
+  fn checksums() -> impl Strategy<Value = Checksum> {
+    let algorithms = ALGORITHMS.iter().map(|(_, algorithm, _)| *algorithm);
+    prop::sample::select(algorithms.collect::<Vec<_>>()).prop_flat_map(|algorithm| {
+      prop::collection::vec(any::<u8>(), algorithm.size())
+        .prop_map(move |digest| Checksum::new(algorithm, &digest).unwrap())
+    })
+  }
+
+  fn revisions() -> impl Strategy<Value = Revision> {
+    prop_oneof![
+      checksums().prop_map(Revision::Checksum),
+      (
+        any::<u16>(),
+        any::<u16>(),
+        any::<u16>(),
+        "[a-z][a-z0-9-]{0,4}(\\.[a-z][a-z0-9-]{0,4})*"
+      )
+        .prop_map(|(major, minor, patch, pre)| {
+          let version = format!("{major}.{minor}.{patch}-{pre}+build.1");
+          Revision::Version(semver::Version::parse(&version).unwrap())
+        }),
+      ("[a-zA-Z0-9/@._-]{1,20}", checksums())
+        .prop_map(|(name, checksum)| Revision::Named { name, checksum }),
+    ]
+  }
+
+  proptest! {
+    #[test]
+    fn formatted_revisions_parse_and_match_the_pattern(revision in revisions()) {
+      let string = revision.to_string();
+      prop_assert!(schema_pattern().is_match(&string), "{string} does not match");
+      prop_assert_eq!(Revision::from_str(&string).unwrap(), revision);
+    }
+
+    #[test]
+    fn strings_parsing_match_the_pattern(string in "[a-z0-9.:@+-]{0,20}") {
+      if Revision::from_str(&string).is_ok() {
+        prop_assert!(schema_pattern().is_match(&string), "{string} does not match");
+      }
+    }
+  }
+
+  #[test]
+  fn revision_forms() {
+    let checksum = Checksum::from_str(SHA1).unwrap();
+    assert_eq!(checksum.algorithm(), Algorithm::Sha1);
+    assert_eq!(checksum.hex(), &SHA1[5..]);
+
+    assert_eq!(
+      Revision::from_str(SHA256).unwrap(),
+      Revision::Checksum(SHA256.parse().unwrap())
+    );
+    assert_eq!(
+      Revision::from_str("1.2.3-rc.1").unwrap().version(),
+      Some(&semver::Version::parse("1.2.3-rc.1").unwrap())
+    );
+
+    let named = Revision::from_str(&format!("feature/a@b@{SHA1}")).unwrap();
+    assert_eq!(named.name(), Some("feature/a@b"));
+    assert_eq!(named.checksum(), Some(&checksum));
+  }
+
+  #[test]
+  fn invalid_revisions() {
+    for string in [
+      "",
+      "main",
+      "v1.2.3",
+      "1.2",
+      "md5:d41d8cd98f00b204e9800998ecf8427e",
+      "sha1:5394CB7F48332B2DE7C17DD8B8384BBC84B7E738",
+      "sha256:e3b0c442",
+      &format!("@{SHA1}"),
+      "main@1.2.3",
+    ] {
+      assert!(Revision::from_str(string).is_err(), "{string} parses");
+      assert!(!schema_pattern().is_match(string), "{string} matches");
+    }
+  }
+
+  #[test]
+  fn checksums_of_digests() {
+    let checksum = Checksum::new(Algorithm::Sha256, &[0xab; 32]).unwrap();
+    assert_eq!(checksum.to_string(), format!("sha256:{}", "ab".repeat(32)));
+    assert!(Checksum::new(Algorithm::Sha256, &[0xab; 20]).is_err());
+  }
+
+  #[test]
+  fn serializes_as_strings() {
+    assert_tokens(&Revision::from_str(SHA256).unwrap(), &[Token::Str(SHA256)]);
+    assert_tokens(&Checksum::from_str(SHA1).unwrap(), &[Token::Str(SHA1)]);
+  }
+}
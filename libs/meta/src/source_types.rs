@@ -0,0 +1,14 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// StalePolicy defines what happens to the content of a source once it could not be refreshed for longer than its
+/// maxAge.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize, JsonSchema)]
+pub enum StalePolicy {
+  /// Keep serving the last fetched content, only reporting it as stale.
+  #[default]
+  Keep,
+
+  /// Remove the content, so nothing keeps relying on it.
+  Remove,
+}
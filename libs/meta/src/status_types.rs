@@ -0,0 +1,266 @@
+use fluxcd_utils_macros::api_object;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+use schemars::JsonSchema;
+
+api_object! {
+  /// LastFailure describes the most recent failed reconciliation of an object. It is kept after
+  /// the object recovers, so the cause of a past failure remains visible.
+  #[derive(Default, PartialEq, Debug, Clone, JsonSchema)]
+  pub struct LastFailure {
+    /// Time of the most recent failure.
+    time: Time = "time",
+
+    /// Reason is a brief PascalCase classification of the failure.
+    reason: String = "reason",
+
+    /// Message is a human readable description of the failure.
+    message: String = "message",
+
+    /// Retries is the number of consecutive failed reconciliations, it is reset to zero by a
+    /// successful reconciliation.
+    retries: u32 = "retries",
+  }
+}
+
+impl LastFailure {
+  pub fn time(&self) -> Option<&Time> {
+    self.time.as_ref()
+  }
+
+  pub fn reason(&self) -> Option<&str> {
+    self.reason.as_deref()
+  }
+
+  pub fn message(&self) -> Option<&str> {
+    self.message.as_deref()
+  }
+
+  pub fn retries(&self) -> u32 {
+    self.retries.unwrap_or_default()
+  }
+
+  /// Whether the failure has been followed by a successful reconciliation.
+  pub fn is_resolved(&self) -> bool {
+    self.retries() == 0
+  }
+
+  /// Records a failed reconciliation, counting it as a retry of the previous failure if that was
+  /// not resolved yet.
+  pub fn record(&mut self, time: Time, reason: impl Into<String>, message: impl Into<String>) {
+    self.time = Some(time);
+    self.reason = Some(reason.into());
+    self.message = Some(message.into());
+    self.retries = Some(self.retries().saturating_add(1));
+  }
+
+  /// Marks the failure as resolved by a successful reconciliation, keeping its details.
+  pub fn resolve(&mut self) {
+    self.retries = Some(0);
+  }
+}
+
+api_object! {
+  /// ItemFailure describes an item a reconciliation failed to process, while it succeeded for the
+  /// others, like a single user of a list whose keys could not be fetched.
+  #[derive(Default, PartialEq, Debug, Clone, JsonSchema)]
+  pub struct ItemFailure {
+    /// Item identifies the item which failed.
+    item: String = "item",
+
+    /// Message is a human readable description of the failure.
+    message: String = "message",
+  }
+}
+
+impl ItemFailure {
+  pub fn new(item: impl Into<String>, message: impl Into<String>) -> Self {
+    Self {
+      item: Some(item.into()),
+      message: Some(message.into()),
+    }
+  }
+
+  pub fn item(&self) -> Option<&str> {
+    self.item.as_deref()
+  }
+
+  pub fn message(&self) -> Option<&str> {
+    self.message.as_deref()
+  }
+}
+
+/// The number of failed items listed in [FailedItems], so that a resource with many failing items
+/// does not outgrow the size limit of objects.
+pub const MAX_FAILED_ITEMS: usize = 10;
+
+api_object! {
+  /// FailedItems lists the items an aggregate resource failed to process in its last
+  /// reconciliation, while it succeeded for the others. Only the first items are listed, ordered
+  /// by item, and the others are only counted.
+  ///
+  /// While items are failing, the Ready condition stays True if any item succeeded, with the
+  /// PartialFailure reason and a message summarizing the failed items (see [FailedItems::summary]).
+  /// The reconciliation fails if every item failed. Once no item is failing, the block is cleared.
+  #[derive(Default, PartialEq, Debug, Clone, JsonSchema)]
+  pub struct FailedItems {
+    /// Items are the first failed items.
+    items: Vec<ItemFailure> = "items",
+
+    /// Count is the number of failed items, including those which are not listed.
+    count: u32 = "count",
+  }
+}
+
+impl FailedItems {
+  pub fn new(failures: impl IntoIterator<Item = ItemFailure>) -> Self {
+    let mut failed = Self::default();
+    failed.merge(failures);
+    failed
+  }
+
+  pub fn items(&self) -> &[ItemFailure] {
+    self.items.as_deref().unwrap_or_default()
+  }
+
+  /// The number of failed items, including those which are not listed.
+  pub fn count(&self) -> usize {
+    (self.count.unwrap_or_default() as usize).max(self.items().len())
+  }
+
+  /// The number of failed items which are not listed.
+  pub fn omitted(&self) -> usize {
+    self.count() - self.items().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.count() == 0
+  }
+
+  /// Adds `failures`, like those of another step of the reconciliation. A failure of an item which
+  /// is already listed replaces it, other failures are counted as additional items, so `failures`
+  /// should not repeat the items which are not listed.
+  pub fn merge(&mut self, failures: impl IntoIterator<Item = ItemFailure>) {
+    let mut count = self.count();
+    let mut items = self.items.take().unwrap_or_default();
+    for failure in failures {
+      match items.iter_mut().find(|listed| listed.item == failure.item) {
+        Some(listed) => *listed = failure,
+        None => {
+          items.push(failure);
+          count += 1;
+        }
+      }
+    }
+
+    items.sort_by(|a, b| a.item.cmp(&b.item));
+    items.truncate(MAX_FAILED_ITEMS);
+    self.items = Some(items);
+    self.count = Some(count.try_into().unwrap_or(u32::MAX));
+  }
+
+  /// Removes all failures, once a reconciliation processed every item.
+  pub fn clear(&mut self) {
+    *self = Self::default();
+  }
+
+  /// The message of the Ready condition of a resource, which failed to process these items out of
+  /// `total`, like `2 of 5 items failed: alice: not found; bob: timed out`. Items which are not
+  /// listed are added as `and 3 more`. `None` if no item failed.
+  pub fn summary(&self, total: usize) -> Option<String> {
+    if self.is_empty() {
+      return None;
+    }
+
+    let count = self.count();
+    let mut failures = self
+      .items()
+      .iter()
+      .map(|failure| {
+        let item = failure.item().unwrap_or_default();
+        match failure.message() {
+          Some(message) => format!("{item}: {message}"),
+          None => item.to_string(),
+        }
+      })
+      .collect::<Vec<_>>()
+      .join("; ");
+    if self.omitted() > 0 {
+      failures.push_str(&format!(" and {} more", self.omitted()));
+    }
+
+    if count >= total {
+      Some(format!("all {count} items failed: {failures}"))
+    } else {
+      Some(format!("{count} of {total} items failed: {failures}"))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use k8s_openapi::chrono::{TimeZone, Utc};
+
+  fn time(secs: i64) -> Time {
+    Time(Utc.timestamp(secs, 0))
+  }
+
+  #[test]
+  fn record_counts_consecutive_failures() {
+    let mut failure = LastFailure::default();
+    assert!(failure.is_resolved());
+
+    failure.record(time(1), "Failed", "first");
+    failure.record(time(2), "Failed", "second");
+    assert_eq!(failure.retries(), 2);
+    assert_eq!(failure.message(), Some("second"));
+    assert_eq!(failure.time(), Some(&time(2)));
+
+    failure.resolve();
+    assert!(failure.is_resolved());
+    assert_eq!(failure.reason(), Some("Failed"));
+
+    failure.record(time(3), "DeadlineExceeded", "third");
+    assert_eq!(failure.retries(), 1);
+  }
+
+  #[test]
+  fn failed_items_are_truncated_but_counted() {
+    let mut failed = FailedItems::new(
+      (0..12)
+        .rev()
+        .map(|i| ItemFailure::new(format!("user-{i:02}"), "not found")),
+    );
+    assert_eq!(failed.count(), 12);
+    assert_eq!(failed.items().len(), MAX_FAILED_ITEMS);
+    assert_eq!(failed.items()[0].item(), Some("user-00"));
+    assert_eq!(failed.omitted(), 2);
+
+    failed.merge([ItemFailure::new("user-00", "timed out")]);
+    assert_eq!(failed.count(), 12);
+    assert_eq!(failed.items()[0].message(), Some("timed out"));
+
+    failed.clear();
+    assert!(failed.is_empty());
+    assert_eq!(failed.summary(12), None);
+  }
+
+  #[test]
+  fn failed_items_summary() {
+    let failed = FailedItems::new([
+      ItemFailure::new("bob", "timed out"),
+      ItemFailure::new("alice", "not found"),
+    ]);
+    assert_eq!(
+      failed.summary(5).as_deref(),
+      Some("2 of 5 items failed: alice: not found; bob: timed out")
+    );
+    assert_eq!(
+      failed.summary(2).as_deref(),
+      Some("all 2 items failed: alice: not found; bob: timed out")
+    );
+
+    let failed = FailedItems::new((0..12).map(|i| ItemFailure::new(format!("{i:02}"), "gone")));
+    assert!(failed.summary(20).unwrap().ends_with("09: gone and 2 more"));
+  }
+}
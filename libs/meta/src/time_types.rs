@@ -1,3 +1,5 @@
 mod duration;
+mod timeout;
 
 pub use duration::*;
+pub use timeout::*;
@@ -181,6 +181,36 @@ impl Duration {
     let nsec = (self.0 % Duration::HOUR.0) as f64;
     min + (nsec / (1e9f64 * 60f64 * 64f64))
   }
+
+  /// The duration as a [std::time::Duration], or `None` if it is negative.
+  #[inline]
+  pub fn to_std(self) -> Option<std::time::Duration> {
+    let nanos = u64::try_from(self.0).ok()?;
+    Some(std::time::Duration::from_nanos(nanos))
+  }
+}
+
+/// The units durations are written in, with their symbol.
+const UNITS: &[(&str, Duration)] = &[
+  ("ns", Duration::NANOSECOND),
+  ("us", Duration::MICROSECOND),
+  ("\u{b5}s", Duration::MICROSECOND),  // U+00B5 = micro symbol
+  ("\u{3bc}s", Duration::MICROSECOND), // U+03BC = Greek letter mu
+  ("ms", Duration::MILLISECOND),
+  ("s", Duration::SECOND),
+  ("m", Duration::MINUTE),
+  ("h", Duration::HOUR),
+];
+
+/// The pattern of the strings which parse as durations: an optional sign, followed by either
+/// `0`, or a sequence of numbers with a unit, like `1h30m` or `1.5s`.
+fn pattern() -> String {
+  let units = UNITS
+    .iter()
+    .map(|(unit, _)| *unit)
+    .collect::<Vec<_>>()
+    .join("|");
+  format!(r"^[-+]?(0|(([0-9]+(\.[0-9]*)?|\.[0-9]+)({units}))+)$")
 }
 
 #[derive(Debug, Error)]
@@ -234,17 +264,10 @@ impl TryFrom<&str> for Duration {
 
   fn try_from(value: &str) -> Result<Self, Self::Error> {
     fn unit_base(symbol: &[u8]) -> Option<i64> {
-      match symbol {
-        b"ns" => Some(Duration::NANOSECOND.0),
-        b"us" => Some(Duration::MICROSECOND.0),
-        b"\xC2\xB5s" => Some(Duration::MICROSECOND.0), // U+00B5 = micro symbol
-        b"\xCE\xBCs" => Some(Duration::MICROSECOND.0), // U+03BC = Greek letter mu
-        b"ms" => Some(Duration::MILLISECOND.0),
-        b"s" => Some(Duration::SECOND.0),
-        b"m" => Some(Duration::MINUTE.0),
-        b"h" => Some(Duration::HOUR.0),
-        _ => None,
-      }
+      UNITS
+        .iter()
+        .find(|(unit, _)| unit.as_bytes() == symbol)
+        .map(|(_, base)| base.0)
     }
 
     // leadingInt consumes the leading [0-9]* from s.
@@ -304,7 +327,7 @@ impl TryFrom<&str> for Duration {
       (x, scale)
     }
 
-    // [-+]?([0-9]*(\.[0-9]*)?[a-z]+)+
+    // [-+]?([0-9]*(\.[0-9]*)?[a-z]+)+, see [pattern]
     let mut s = value.as_bytes();
     let mut d = 0u64;
 
@@ -464,7 +487,7 @@ impl JsonSchema for Duration {
     SchemaObject {
       instance_type: Some(InstanceType::String.into()),
       string: Some(Box::new(StringValidation {
-        pattern: Some("[-+]?([0-9]*(\\.[0-9]*)?[^0-9\\.]+)+".into()),
+        pattern: Some(pattern()),
         ..Default::default()
       })),
       ..Default::default()
@@ -496,8 +519,60 @@ impl TryFrom<time::Duration> for Duration {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use proptest::{prelude::*, string::string_regex};
+  use regex::Regex;
   use test_case::test_case;
 
+  fn schema_pattern() -> Regex {
+    Regex::new(&pattern()).unwrap()
+  }
+
+  /// Durations as written by hand, with at most 4 digits on either side of the point of each
+  /// number, so that they do not overflow.
+  fn written() -> impl Strategy<Value = String> {
+    let units = UNITS
+      .iter()
+      .map(|(unit, _)| *unit)
+      .collect::<Vec<_>>()
+      .join("|");
+    let pattern =
+      format!(r"[-+]?(0|(([0-9]{{1,4}}(\.[0-9]{{0,4}})?|\.[0-9]{{1,4}})({units})){{1,4}})");
+    string_regex(&pattern).unwrap()
+  }
+
+  proptest! {
+    #[test]
+    fn strings_matching_the_pattern_parse(string in written()) {
+      prop_assert!(schema_pattern().is_match(&string));
+      prop_assert!(Duration::from_str(&string).is_ok(), "{string} does not parse");
+    }
+
+    #[test]
+    fn strings_parsing_match_the_pattern(string in "[-+]?[0-9.nuµμmshx]{0,10}") {
+      if Duration::from_str(&string).is_ok() {
+        prop_assert!(schema_pattern().is_match(&string), "{string} does not match");
+      }
+    }
+
+    #[test]
+    fn formatted_durations_match_the_pattern(nanos in any::<i64>()) {
+      let string = Duration(nanos).to_string();
+      prop_assert!(schema_pattern().is_match(&string), "{string} does not match");
+      prop_assert_eq!(Duration::from_str(&string).unwrap(), Duration(nanos));
+    }
+  }
+
+  #[test_case("5x")]
+  #[test_case("5")]
+  #[test_case("1h5")]
+  #[test_case(".s")]
+  #[test_case("-")]
+  #[test_case("1hm")]
+  fn invalid_durations_do_not_match_the_pattern(string: &str) {
+    assert!(!schema_pattern().is_match(string));
+    assert!(Duration::from_str(string).is_err());
+  }
+
   #[test_case("0s", 0)]
   #[test_case("1ns", Duration::NANOSECOND.0)]
   #[test_case("1.1µs", 1100 * Duration::NANOSECOND.0)]
@@ -520,4 +595,14 @@ mod tests {
     let round_tripped = Duration::try_from(time::Duration::from(duration)).expect("round trip");
     assert_eq!(round_tripped, duration);
   }
+
+  #[test]
+  fn std_durations() {
+    assert_eq!(
+      Duration(90 * Duration::SECOND.0).to_std(),
+      Some(std::time::Duration::from_secs(90))
+    );
+    assert_eq!(Duration::ZERO.to_std(), Some(std::time::Duration::ZERO));
+    assert_eq!(Duration(-Duration::SECOND.0).to_std(), None);
+  }
 }
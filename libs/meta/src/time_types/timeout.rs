@@ -0,0 +1,42 @@
+use super::Duration;
+
+/// How long an operation of a reconcile, like fetching a source, may take when the spec of the
+/// resource does not set a timeout.
+pub const DEFAULT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// The timeout of an operation of a reconcile, following the Flux convention: the timeout of the
+/// spec, or `default` if it is unset or negative, but never more than the interval of the resource,
+/// so that a reconcile taking the full timeout does not run into the next one.
+pub fn resolve_timeout(
+  spec_timeout: Option<Duration>,
+  default: std::time::Duration,
+  interval: Option<Duration>,
+) -> std::time::Duration {
+  let timeout = spec_timeout.and_then(Duration::to_std).unwrap_or(default);
+
+  match interval.and_then(Duration::to_std) {
+    Some(interval) if !interval.is_zero() => timeout.min(interval),
+    _ => timeout,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use test_case::test_case;
+
+  fn secs(secs: i64) -> Duration {
+    format!("{secs}s").parse().unwrap()
+  }
+
+  #[test_case(None, None => 60; "default")]
+  #[test_case(Some(secs(30)), None => 30; "spec")]
+  #[test_case(Some(secs(-30)), None => 60; "negative spec")]
+  #[test_case(None, Some(secs(20)) => 20; "default capped by interval")]
+  #[test_case(Some(secs(120)), Some(secs(300)) => 120; "spec within interval")]
+  #[test_case(Some(secs(120)), Some(secs(90)) => 90; "spec capped by interval")]
+  #[test_case(Some(secs(30)), Some(Duration::ZERO) => 30; "zero interval")]
+  fn resolves_timeouts(spec: Option<Duration>, interval: Option<Duration>) -> u64 {
+    resolve_timeout(spec, DEFAULT_TIMEOUT, interval).as_secs()
+  }
+}
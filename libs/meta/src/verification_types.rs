@@ -0,0 +1,88 @@
+use fluxcd_utils_macros::api_object;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// VerificationMethod is how the content of a source was verified before it was used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize, JsonSchema)]
+pub enum VerificationMethod {
+  /// The content was used as fetched.
+  #[default]
+  None,
+
+  /// The checksum of the content matched the expected checksum.
+  Checksum,
+
+  /// The content carried a valid signature of a trusted key.
+  Signature,
+}
+
+api_object! {
+  /// Verification describes the content a source last resolved, and how it was verified, so that
+  /// policies can require sources to only serve verified content.
+  #[derive(Default, PartialEq, Debug, Clone, JsonSchema)]
+  pub struct Verification {
+    /// Digest is the SHA256 checksum of the resolved content, in the form `sha256:<hex>`.
+    digest: String = "digest",
+
+    /// Method is how the resolved content was verified.
+    method: VerificationMethod = "method",
+  }
+}
+
+impl Verification {
+  pub fn new(digest: impl Into<String>, method: VerificationMethod) -> Self {
+    Self {
+      digest: Some(digest.into()),
+      method: Some(method),
+    }
+  }
+
+  pub fn digest(&self) -> Option<&str> {
+    self.digest.as_deref()
+  }
+
+  pub fn method(&self) -> VerificationMethod {
+    self.method.unwrap_or_default()
+  }
+
+  /// Whether the content was verified, by any method.
+  pub fn is_verified(&self) -> bool {
+    self.method() != VerificationMethod::None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_test::{assert_ser_tokens, Token};
+
+  #[test]
+  fn serializes_digest_and_method() {
+    let verification = Verification::new("sha256:abc", VerificationMethod::Signature);
+    assert!(verification.is_verified());
+    assert_ser_tokens(
+      &verification,
+      &[
+        Token::Struct {
+          name: "Verification",
+          len: 2,
+        },
+        Token::Str("digest"),
+        Token::Str("sha256:abc"),
+        Token::Str("method"),
+        Token::UnitVariant {
+          name: "VerificationMethod",
+          variant: "Signature",
+        },
+        Token::StructEnd,
+      ],
+    );
+  }
+
+  #[test]
+  fn missing_method_is_unverified() {
+    let verification = Verification::default();
+    assert_eq!(verification.method(), VerificationMethod::None);
+    assert!(!verification.is_verified());
+  }
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+/// How severe an event is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+  Info,
+  Error,
+}
+
+/// The object an event is about.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvolvedObject {
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub api_version: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub kind: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub name: Option<String>,
+
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub namespace: Option<String>,
+}
+
+/// An event of an object, as forwarded by the controllers to the events receiver, in the format
+/// of fluxcd/pkg/apis/event.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+  pub involved_object: InvolvedObject,
+  pub severity: Severity,
+
+  /// When the event happened, in RFC 3339.
+  pub timestamp: String,
+
+  pub message: String,
+  pub reason: String,
+  pub reporting_controller: String,
+
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub reporting_instance: Option<String>,
+}
+
+impl Event {
+  /// The object of the event, as `Kind/namespace/name`.
+  pub fn object(&self) -> String {
+    let object = &self.involved_object;
+    let kind = object.kind.as_deref().unwrap_or_default();
+    let name = object.name.as_deref().unwrap_or_default();
+    match object.namespace.as_deref() {
+      Some(namespace) => format!("{kind}/{namespace}/{name}"),
+      None => format!("{kind}/{name}"),
+    }
+  }
+}
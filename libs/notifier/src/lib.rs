@@ -0,0 +1,263 @@
+mod event;
+
+use reqwest::Url;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+pub use event::*;
+
+/// Color of the notifications of info events, as a hex RGB.
+const INFO_COLOR: &str = "2eb886";
+
+/// Color of the notifications of error events, as a hex RGB.
+const ERROR_COLOR: &str = "a30200";
+
+#[derive(Debug, Error)]
+pub enum Error {
+  #[error(transparent)]
+  Http(#[from] reqwest::Error),
+
+  #[error("invalid address '{0}', expected an http or https URL")]
+  InvalidAddress(String),
+}
+
+/// The kind of chat system, or other service, notifications are posted to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Service {
+  /// A Slack incoming webhook.
+  Slack,
+
+  /// A Discord webhook, through its Slack compatible endpoint.
+  Discord,
+
+  /// A Microsoft Teams incoming webhook, with a message card.
+  MsTeams,
+
+  /// Any webhook, which the event is posted to as it is, as json.
+  Generic,
+}
+
+/// Posts events to a chat system, or to any webhook.
+#[derive(Clone, Debug)]
+pub struct Notifier {
+  service: Service,
+  url: Url,
+  channel: Option<String>,
+  username: Option<String>,
+}
+
+impl Notifier {
+  /// Posts to the webhook of `service` at `address`.
+  pub fn new(service: Service, address: &str) -> Result<Self, Error> {
+    let invalid = || Error::InvalidAddress(address.into());
+    let mut url = Url::parse(address.trim()).map_err(|_| invalid())?;
+    if !matches!(url.scheme(), "http" | "https") || !url.has_host() {
+      return Err(invalid());
+    }
+
+    // discord webhooks accept the payloads of slack at this path
+    if service == Service::Discord && !url.path().ends_with("/slack") {
+      let path = format!("{}/slack", url.path().trim_end_matches('/'));
+      url.set_path(&path);
+    }
+
+    Ok(Self {
+      service,
+      url,
+      channel: None,
+      username: None,
+    })
+  }
+
+  /// Posts to `channel`, rather than the default channel of the webhook, for Slack.
+  pub fn with_channel(self, channel: Option<String>) -> Self {
+    Self { channel, ..self }
+  }
+
+  /// Posts as `username`, rather than the default name of the webhook, for Slack and Discord.
+  pub fn with_username(self, username: Option<String>) -> Self {
+    Self { username, ..self }
+  }
+
+  /// Posts the notification of `event`, with the `summary` of the alert if any.
+  pub async fn post(
+    &self,
+    http: &reqwest::Client,
+    event: &Event,
+    summary: Option<&str>,
+  ) -> Result<(), Error> {
+    http
+      .post(self.url.clone())
+      .json(&self.payload(event, summary))
+      .send()
+      .await?
+      .error_for_status()?;
+
+    Ok(())
+  }
+
+  /// The body of the notification of `event`.
+  fn payload(&self, event: &Event, summary: Option<&str>) -> Value {
+    match self.service {
+      Service::Slack | Service::Discord => self.slack_payload(event, summary),
+      Service::MsTeams => teams_payload(event, summary),
+      Service::Generic => generic_payload(event, summary),
+    }
+  }
+
+  fn slack_payload(&self, event: &Event, summary: Option<&str>) -> Value {
+    let mut fields = vec![json!({
+      "title": "reason",
+      "value": event.reason,
+      "short": true,
+    })];
+    if let Some(summary) = summary {
+      fields.insert(
+        0,
+        json!({
+          "title": "summary",
+          "value": summary,
+          "short": false,
+        }),
+      );
+    }
+
+    let mut payload = json!({
+      "attachments": [{
+        "color": format!("#{}", color(event)),
+        "author_name": event.object(),
+        "text": event.message,
+        "mrkdwn_in": ["text"],
+        "fields": fields,
+        "footer": event.reporting_controller,
+      }],
+    });
+    if let Some(channel) = &self.channel {
+      payload["channel"] = channel.as_str().into();
+    }
+    if let Some(username) = &self.username {
+      payload["username"] = username.as_str().into();
+    }
+
+    payload
+  }
+}
+
+fn color(event: &Event) -> &'static str {
+  match event.severity {
+    Severity::Info => INFO_COLOR,
+    Severity::Error => ERROR_COLOR,
+  }
+}
+
+fn teams_payload(event: &Event, summary: Option<&str>) -> Value {
+  let object = event.object();
+  let mut facts = vec![json!({ "name": "reason", "value": event.reason })];
+  if let Some(summary) = summary {
+    facts.insert(0, json!({ "name": "summary", "value": summary }));
+  }
+
+  json!({
+    "@type": "MessageCard",
+    "@context": "https://schema.org/extensions",
+    "themeColor": color(event),
+    "summary": object,
+    "sections": [{
+      "activityTitle": event.message,
+      "activitySubtitle": object,
+      "facts": facts,
+    }],
+  })
+}
+
+/// The event as it is, with the summary of the alert in its metadata.
+fn generic_payload(event: &Event, summary: Option<&str>) -> Value {
+  let mut payload = serde_json::to_value(event).expect("events always serialize");
+  if let Some(summary) = summary {
+    payload["metadata"] = json!({ "summary": summary });
+  }
+
+  payload
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn event() -> Event {
+    Event {
+      involved_object: InvolvedObject {
+        api_version: Some("source.fluxcd.yolodev.io/v1beta1".into()),
+        kind: Some("Bucket".into()),
+        name: Some("manifests".into()),
+        namespace: Some("flux-system".into()),
+      },
+      severity: Severity::Error,
+      timestamp: "1970-01-01T00:00:00Z".into(),
+      message: "timed out fetching bucket 'manifests'".into(),
+      reason: "Failed".into(),
+      reporting_controller: "source-controller".into(),
+      reporting_instance: None,
+    }
+  }
+
+  #[test]
+  fn addresses_are_urls() {
+    assert!(matches!(
+      Notifier::new(Service::Slack, "hooks.slack.com/services/x"),
+      Err(Error::InvalidAddress(_))
+    ));
+    assert!(matches!(
+      Notifier::new(Service::Generic, "ftp://example.com"),
+      Err(Error::InvalidAddress(_))
+    ));
+
+    let discord = Notifier::new(Service::Discord, "https://discord.com/api/webhooks/1/x").unwrap();
+    assert_eq!(
+      discord.url.as_str(),
+      "https://discord.com/api/webhooks/1/x/slack"
+    );
+  }
+
+  #[test]
+  fn slack_payload() {
+    let slack = Notifier::new(Service::Slack, "https://hooks.slack.com/services/x")
+      .unwrap()
+      .with_channel(Some("#gitops".into()));
+    assert_eq!(
+      slack.payload(&event(), Some("production")),
+      json!({
+        "channel": "#gitops",
+        "attachments": [{
+          "color": "#a30200",
+          "author_name": "Bucket/flux-system/manifests",
+          "text": "timed out fetching bucket 'manifests'",
+          "mrkdwn_in": ["text"],
+          "fields": [
+            { "title": "summary", "value": "production", "short": false },
+            { "title": "reason", "value": "Failed", "short": true },
+          ],
+          "footer": "source-controller",
+        }],
+      })
+    );
+  }
+
+  #[test]
+  fn teams_and_generic_payloads() {
+    let teams = Notifier::new(Service::MsTeams, "https://outlook.office.com/webhook/x").unwrap();
+    let payload = teams.payload(&event(), None);
+    assert_eq!(payload["@type"], "MessageCard");
+    assert_eq!(payload["themeColor"], "a30200");
+    assert_eq!(
+      payload["sections"][0]["facts"],
+      json!([{ "name": "reason", "value": "Failed" }])
+    );
+
+    let generic = Notifier::new(Service::Generic, "http://receiver.local/events").unwrap();
+    let payload = generic.payload(&event(), Some("production"));
+    assert_eq!(payload["severity"], "error");
+    assert_eq!(payload["involvedObject"]["kind"], "Bucket");
+    assert_eq!(payload["metadata"]["summary"], "production");
+  }
+}
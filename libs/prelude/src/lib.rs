@@ -0,0 +1,27 @@
+// The items most controllers need, so a controller crate can `use fluxcd_prelude::*;` rather than
+// importing from each of the crates they come from. `Duration` is the one of the resources; as
+// glob imports yield to explicit ones, `use std::time::Duration;` next to the prelude takes over.
+
+pub use async_trait::async_trait;
+pub use eyre::{bail, eyre, Result, WrapErr};
+pub use fluxcd_meta::{
+  mark_ready, mark_reconciling, mark_stalled, Artifact, Duration, LastFailure,
+  LocalObjectReference, NamespacedObjectReference, Reason, ReconcileRequestStatus, Verification,
+  VerificationMethod, RECONCILE_REQUEST_ANNOTATION,
+};
+pub use fluxcd_utils_cap::{
+  backoff::Backoff,
+  context::ReconcileCtx,
+  flux_controller,
+  metrics::Recorder,
+  predicate::Predicates,
+  requeue::{requeue_after, requeue_after_jittered},
+  requirements::{KubeVersion, Requirements},
+  Controller, ControllerApp,
+};
+pub use kube::runtime::controller::ReconcilerAction;
+
+/// The crates of the prelude, for the items it does not re-export. The code generated by
+/// `flux_controller` refers to `cap` in crates which only depend on the prelude.
+pub use fluxcd_meta as meta;
+pub use fluxcd_utils_cap as cap;
@@ -0,0 +1,315 @@
+use http::HeaderMap;
+use ring::{constant_time, digest, hmac};
+use thiserror::Error;
+
+/// Header of the signature of `generic-hmac` webhooks, like `sha256=<hex digest>`.
+const SIGNATURE_HEADER: &str = "x-signature";
+
+/// Headers of the signature of GitHub webhooks, the SHA-256 one being preferred when both are
+/// sent.
+const GITHUB_SIGNATURE_256_HEADER: &str = "x-hub-signature-256";
+const GITHUB_SIGNATURE_HEADER: &str = "x-hub-signature";
+const GITHUB_EVENT_HEADER: &str = "x-github-event";
+
+/// Event GitHub sends when a webhook is created, which does not mean anything changed.
+const GITHUB_PING_EVENT: &str = "ping";
+
+const GITLAB_TOKEN_HEADER: &str = "x-gitlab-token";
+const GITLAB_EVENT_HEADER: &str = "x-gitlab-event";
+
+const HARBOR_AUTHORIZATION_HEADER: &str = "authorization";
+
+#[derive(Debug, Error)]
+pub enum Error {
+  #[error("missing header '{0}'")]
+  MissingHeader(&'static str),
+
+  #[error("invalid header '{0}'")]
+  InvalidHeader(&'static str),
+
+  #[error("unsupported signature algorithm '{0}'")]
+  UnsupportedAlgorithm(String),
+
+  #[error("the signature does not match the payload")]
+  SignatureMismatch,
+
+  #[error("the token does not match")]
+  TokenMismatch,
+}
+
+/// The kind of service a webhook is sent by, which determines how it is authenticated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Provider {
+  /// Any service, authenticated only by the secret path of the webhook.
+  Generic,
+
+  /// Any service, signing the payload with the token in the `X-Signature` header, like
+  /// `sha256=<hex digest>`.
+  GenericHmac,
+
+  /// GitHub, signing the payload with the token in the `X-Hub-Signature-256` header.
+  GitHub,
+
+  /// GitLab, sending the token in the `X-Gitlab-Token` header.
+  GitLab,
+
+  /// Harbor, sending the token in the `Authorization` header.
+  Harbor,
+}
+
+/// What to do with an authenticated webhook.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+  /// Request the reconciliation of the resources of the receiver.
+  Accept,
+
+  /// Acknowledge the webhook without doing anything, for the given reason.
+  Ignore(String),
+}
+
+/// The path of the webhook of the receiver `name` in `namespace` with `token`. It is derived from
+/// the token, so that it cannot be guessed, and changes along with the token.
+pub fn webhook_path(token: &str, name: &str, namespace: &str) -> String {
+  let digest = digest::digest(
+    &digest::SHA256,
+    format!("{token}{name}{namespace}").as_bytes(),
+  );
+  format!("/hook/{}", hex(digest.as_ref()))
+}
+
+/// Authenticates a webhook with `headers` and `body` from `provider`, with the `token` of the
+/// receiver. Events which are not in `events` are ignored, unless `events` is empty.
+pub fn verify(
+  provider: Provider,
+  headers: &HeaderMap,
+  body: &[u8],
+  token: &str,
+  events: &[String],
+) -> Result<Verdict, Error> {
+  let event = match provider {
+    Provider::Generic => None,
+    Provider::GenericHmac => {
+      let signature = header(headers, SIGNATURE_HEADER)?;
+      verify_signature(signature, SIGNATURE_HEADER, body, token)?;
+      None
+    }
+    Provider::GitHub => {
+      match optional_header(headers, GITHUB_SIGNATURE_256_HEADER)? {
+        Some(signature) => verify_signature(signature, GITHUB_SIGNATURE_256_HEADER, body, token)?,
+        None => {
+          let signature = header(headers, GITHUB_SIGNATURE_HEADER)?;
+          verify_signature(signature, GITHUB_SIGNATURE_HEADER, body, token)?
+        }
+      }
+
+      let event = header(headers, GITHUB_EVENT_HEADER)?;
+      if event == GITHUB_PING_EVENT {
+        return Ok(Verdict::Ignore("ping".into()));
+      }
+
+      Some(event)
+    }
+    Provider::GitLab => {
+      verify_token(header(headers, GITLAB_TOKEN_HEADER)?, token)?;
+      Some(header(headers, GITLAB_EVENT_HEADER)?)
+    }
+    Provider::Harbor => {
+      verify_token(header(headers, HARBOR_AUTHORIZATION_HEADER)?, token)?;
+      None
+    }
+  };
+
+  match event {
+    Some(event) if !events.is_empty() && !events.iter().any(|e| e.eq_ignore_ascii_case(event)) => {
+      Ok(Verdict::Ignore(format!("event '{event}' is not handled")))
+    }
+    _ => Ok(Verdict::Accept),
+  }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &'static str) -> Result<&'a str, Error> {
+  optional_header(headers, name)?.ok_or(Error::MissingHeader(name))
+}
+
+fn optional_header<'a>(
+  headers: &'a HeaderMap,
+  name: &'static str,
+) -> Result<Option<&'a str>, Error> {
+  headers
+    .get(name)
+    .map(|value| value.to_str().map_err(|_| Error::InvalidHeader(name)))
+    .transpose()
+}
+
+/// Checks `signature`, like `sha256=<hex digest>`, is the HMAC of `body` with `token`.
+fn verify_signature(
+  signature: &str,
+  header: &'static str,
+  body: &[u8],
+  token: &str,
+) -> Result<(), Error> {
+  let (algorithm, digest) = signature
+    .split_once('=')
+    .ok_or(Error::InvalidHeader(header))?;
+  let algorithm = match algorithm {
+    "sha1" => hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+    "sha256" => hmac::HMAC_SHA256,
+    "sha512" => hmac::HMAC_SHA512,
+    _ => return Err(Error::UnsupportedAlgorithm(algorithm.into())),
+  };
+  let digest = unhex(digest).ok_or(Error::InvalidHeader(header))?;
+
+  let key = hmac::Key::new(algorithm, token.as_bytes());
+  hmac::verify(&key, body, &digest).map_err(|_| Error::SignatureMismatch)
+}
+
+/// Compares `sent` to `token` in constant time, so the token cannot be guessed from how long
+/// rejecting it takes.
+fn verify_token(sent: &str, token: &str) -> Result<(), Error> {
+  constant_time::verify_slices_are_equal(sent.as_bytes(), token.as_bytes())
+    .map_err(|_| Error::TokenMismatch)
+}
+
+fn hex(bytes: &[u8]) -> String {
+  use std::fmt::Write;
+
+  let mut hex = String::with_capacity(bytes.len() * 2);
+  for byte in bytes {
+    let _ = write!(hex, "{byte:02x}");
+  }
+
+  hex
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+  hex
+    .as_bytes()
+    .chunks(2)
+    .map(|pair| match pair {
+      [high, low] => u8::from_str_radix(std::str::from_utf8(&[*high, *low]).ok()?, 16).ok(),
+      _ => None,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use http::HeaderValue;
+
+  fn headers(entries: &[(&'static str, &str)]) -> HeaderMap {
+    entries
+      .iter()
+      .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_str(value).unwrap()))
+      .collect()
+  }
+
+  fn sign(algorithm: hmac::Algorithm, body: &[u8], token: &str) -> String {
+    hex(hmac::sign(&hmac::Key::new(algorithm, token.as_bytes()), body).as_ref())
+  }
+
+  #[test]
+  fn paths_depend_on_the_token() {
+    let path = webhook_path("token", "github", "flux-system");
+    assert!(path.starts_with("/hook/"));
+    assert_eq!(path.len(), "/hook/".len() + 64);
+    assert_eq!(path, webhook_path("token", "github", "flux-system"));
+    assert_ne!(path, webhook_path("rotated", "github", "flux-system"));
+  }
+
+  #[test]
+  fn hmac_signatures() {
+    let body = br#"{"ref":"refs/heads/main"}"#;
+    let signature = format!("sha256={}", sign(hmac::HMAC_SHA256, body, "token"));
+    let sent = headers(&[(SIGNATURE_HEADER, &signature)]);
+
+    assert_eq!(
+      verify(Provider::GenericHmac, &sent, body, "token", &[]).unwrap(),
+      Verdict::Accept
+    );
+    assert!(matches!(
+      verify(Provider::GenericHmac, &sent, body, "other", &[]),
+      Err(Error::SignatureMismatch)
+    ));
+    assert!(matches!(
+      verify(Provider::GenericHmac, &sent, b"{}", "token", &[]),
+      Err(Error::SignatureMismatch)
+    ));
+    assert!(matches!(
+      verify(Provider::GenericHmac, &HeaderMap::new(), body, "token", &[]),
+      Err(Error::MissingHeader(SIGNATURE_HEADER))
+    ));
+
+    let md5 = headers(&[(SIGNATURE_HEADER, "md5=00")]);
+    assert!(matches!(
+      verify(Provider::GenericHmac, &md5, body, "token", &[]),
+      Err(Error::UnsupportedAlgorithm(_))
+    ));
+  }
+
+  #[test]
+  fn github_events() {
+    let body = b"{}";
+    let signature = format!(
+      "sha1={}",
+      sign(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, body, "token")
+    );
+    let events = vec!["push".to_string()];
+
+    let push = headers(&[
+      (GITHUB_SIGNATURE_HEADER, &signature),
+      (GITHUB_EVENT_HEADER, "push"),
+    ]);
+    assert_eq!(
+      verify(Provider::GitHub, &push, body, "token", &events).unwrap(),
+      Verdict::Accept
+    );
+
+    let ping = headers(&[
+      (GITHUB_SIGNATURE_HEADER, &signature),
+      (GITHUB_EVENT_HEADER, "ping"),
+    ]);
+    assert!(matches!(
+      verify(Provider::GitHub, &ping, body, "token", &[]).unwrap(),
+      Verdict::Ignore(_)
+    ));
+
+    let issues = headers(&[
+      (GITHUB_SIGNATURE_HEADER, &signature),
+      (GITHUB_EVENT_HEADER, "issues"),
+    ]);
+    assert!(matches!(
+      verify(Provider::GitHub, &issues, body, "token", &events).unwrap(),
+      Verdict::Ignore(_)
+    ));
+  }
+
+  #[test]
+  fn tokens() {
+    let gitlab = headers(&[
+      (GITLAB_TOKEN_HEADER, "token"),
+      (GITLAB_EVENT_HEADER, "Push Hook"),
+    ]);
+    assert_eq!(
+      verify(
+        Provider::GitLab,
+        &gitlab,
+        b"",
+        "token",
+        &["push hook".into()]
+      )
+      .unwrap(),
+      Verdict::Accept
+    );
+    assert!(matches!(
+      verify(Provider::GitLab, &gitlab, b"", "tokens", &[]),
+      Err(Error::TokenMismatch)
+    ));
+
+    let harbor = headers(&[(HARBOR_AUTHORIZATION_HEADER, "other")]);
+    assert!(matches!(
+      verify(Provider::Harbor, &harbor, b"", "token", &[]),
+      Err(Error::TokenMismatch)
+    ));
+  }
+}
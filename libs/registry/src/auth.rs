@@ -0,0 +1,146 @@
+use crate::Error;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// The `.dockerconfigjson` of an image pull Secret.
+#[derive(Deserialize)]
+struct DockerConfig {
+  #[serde(default)]
+  auths: BTreeMap<String, DockerAuth>,
+}
+
+#[derive(Deserialize)]
+struct DockerAuth {
+  #[serde(default)]
+  username: Option<String>,
+  #[serde(default)]
+  password: Option<String>,
+  /// `username:password` in base64.
+  #[serde(default)]
+  auth: Option<String>,
+}
+
+/// Username and password for a registry.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+  username: String,
+  password: String,
+}
+
+impl std::fmt::Debug for Credentials {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Credentials")
+      .field("username", &self.username)
+      .finish_non_exhaustive()
+  }
+}
+
+impl Credentials {
+  pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+    Self {
+      username: username.into(),
+      password: password.into(),
+    }
+  }
+
+  /// The credentials for `registry` in the `.dockerconfigjson` of an image pull Secret, whose
+  /// entries are keyed by registry host, with or without a scheme and path, like
+  /// `https://index.docker.io/v1/`.
+  pub fn from_docker_config(config: &[u8], registry: &str) -> Result<Option<Self>, Error> {
+    let invalid = |message: &str| Error::InvalidDockerConfig(message.into());
+    let config: DockerConfig =
+      serde_json::from_slice(config).map_err(|error| invalid(&error.to_string()))?;
+
+    let registry = normalize_host(registry);
+    let auth = match config
+      .auths
+      .into_iter()
+      .find(|(host, _)| normalize_host(host) == registry)
+    {
+      Some((_, auth)) => auth,
+      None => return Ok(None),
+    };
+
+    let credentials = match (auth.username, auth.password, auth.auth) {
+      (Some(username), Some(password), _) => Self::new(username, password),
+      (_, _, Some(auth)) => {
+        let decoded = base64::decode(auth.trim()).map_err(|_| invalid("auth is not base64"))?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid("auth is not utf-8"))?;
+        let (username, password) = decoded
+          .split_once(':')
+          .ok_or_else(|| invalid("auth is not 'username:password'"))?;
+        Self::new(username, password)
+      }
+      _ => {
+        return Err(invalid(
+          "entry has neither a username and password, nor an auth",
+        ))
+      }
+    };
+
+    Ok(Some(credentials))
+  }
+
+  pub fn username(&self) -> &str {
+    &self.username
+  }
+
+  pub fn password(&self) -> &str {
+    &self.password
+  }
+
+  /// The value of a basic `Authorization` header.
+  pub(crate) fn basic(&self) -> String {
+    let encoded = base64::encode(format!("{}:{}", self.username, self.password));
+    format!("Basic {encoded}")
+  }
+}
+
+/// The host of a docker config entry, with Docker Hub under its image name `docker.io`.
+fn normalize_host(host: &str) -> &str {
+  let host = host
+    .trim()
+    .trim_start_matches("https://")
+    .trim_start_matches("http://");
+  let host = host.split('/').next().unwrap_or(host);
+  match host {
+    "index.docker.io" | "registry-1.docker.io" => "docker.io",
+    host => host,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn docker_config_credentials() {
+    let config = br#"{
+      "auths": {
+        "https://index.docker.io/v1/": { "auth": "dXNlcjpzM2NyM3Q6Og==" },
+        "ghcr.io": { "username": "bot", "password": "token" }
+      }
+    }"#;
+
+    assert_eq!(
+      Credentials::from_docker_config(config, "docker.io").unwrap(),
+      Some(Credentials::new("user", "s3cr3t::"))
+    );
+    assert_eq!(
+      Credentials::from_docker_config(config, "ghcr.io").unwrap(),
+      Some(Credentials::new("bot", "token"))
+    );
+    assert_eq!(
+      Credentials::from_docker_config(config, "quay.io").unwrap(),
+      None
+    );
+    assert!(matches!(
+      Credentials::from_docker_config(br#"{"auths":{"quay.io":{}}}"#, "quay.io"),
+      Err(Error::InvalidDockerConfig(_))
+    ));
+    assert_eq!(
+      Credentials::new("bot", "token").basic(),
+      "Basic Ym90OnRva2Vu"
+    );
+  }
+}
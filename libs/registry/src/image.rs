@@ -0,0 +1,126 @@
+use crate::Error;
+use std::fmt;
+
+/// Registry of images whose name does not start with one.
+const DOCKER_HUB: &str = "docker.io";
+
+/// Host the API of Docker Hub is served at.
+const DOCKER_HUB_API: &str = "registry-1.docker.io";
+
+/// The name of an image, without a tag or digest, like `ghcr.io/fluxcd/source-controller`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ImageName {
+  registry: String,
+  repository: String,
+}
+
+impl ImageName {
+  /// Parses `name`, which is either a name as `docker pull` takes it, like `nginx` or
+  /// `ghcr.io/fluxcd/source-controller`, or such a name with a registry of its own, like
+  /// `registry.local:5000/app`.
+  pub fn parse(name: &str) -> Result<Self, Error> {
+    let invalid = || Error::InvalidImage(name.into());
+    let name = name.trim();
+    let (registry, repository) = match name.split_once('/') {
+      Some((host, rest)) if host.contains(['.', ':']) || host == "localhost" => {
+        (host.to_string(), rest.to_string())
+      }
+      _ => (DOCKER_HUB.to_string(), name.to_string()),
+    };
+
+    if name.contains('@') || repository.contains(':') {
+      return Err(Error::TaggedImage(name.into()));
+    }
+
+    let valid_component = |c: &str| {
+      !c.is_empty()
+        && c
+          .chars()
+          .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "._-".contains(c))
+        && c.starts_with(|c: char| c.is_ascii_alphanumeric())
+    };
+    if !repository.split('/').all(valid_component) {
+      return Err(invalid());
+    }
+
+    let repository = match registry.as_str() {
+      DOCKER_HUB if !repository.contains('/') => format!("library/{repository}"),
+      _ => repository,
+    };
+
+    Ok(Self {
+      registry,
+      repository,
+    })
+  }
+
+  /// The registry of the image, like `ghcr.io` or `registry.local:5000`.
+  pub fn registry(&self) -> &str {
+    &self.registry
+  }
+
+  /// The repository of the image in its registry, like `fluxcd/source-controller`.
+  pub fn repository(&self) -> &str {
+    &self.repository
+  }
+
+  /// The host the API of the registry is served at.
+  pub(crate) fn api_host(&self) -> &str {
+    match self.registry.as_str() {
+      DOCKER_HUB => DOCKER_HUB_API,
+      registry => registry,
+    }
+  }
+}
+
+/// The canonical name of the image, with its registry, like `docker.io/library/nginx`.
+impl fmt::Display for ImageName {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}/{}", self.registry, self.repository)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn names_are_canonical() {
+    let canonical = |name: &str| ImageName::parse(name).unwrap().to_string();
+    assert_eq!(canonical("nginx"), "docker.io/library/nginx");
+    assert_eq!(canonical("fluxcd/flux"), "docker.io/fluxcd/flux");
+    assert_eq!(
+      canonical("ghcr.io/fluxcd/source-controller"),
+      "ghcr.io/fluxcd/source-controller"
+    );
+    assert_eq!(
+      canonical("registry.local:5000/app"),
+      "registry.local:5000/app"
+    );
+    assert_eq!(canonical("localhost/app"), "localhost/app");
+    assert_eq!(
+      ImageName::parse("nginx").unwrap().api_host(),
+      DOCKER_HUB_API
+    );
+  }
+
+  #[test]
+  fn names_have_no_tag() {
+    assert!(matches!(
+      ImageName::parse("nginx:1.21"),
+      Err(Error::TaggedImage(_))
+    ));
+    assert!(matches!(
+      ImageName::parse("ghcr.io/app@sha256:abc"),
+      Err(Error::TaggedImage(_))
+    ));
+    assert!(matches!(
+      ImageName::parse("ghcr.io/App"),
+      Err(Error::InvalidImage(_))
+    ));
+    assert!(matches!(
+      ImageName::parse("ghcr.io//app"),
+      Err(Error::InvalidImage(_))
+    ));
+  }
+}
@@ -0,0 +1,321 @@
+mod auth;
+mod image;
+mod policy;
+mod push;
+
+use reqwest::{
+  header::{HeaderMap, AUTHORIZATION, LINK, WWW_AUTHENTICATE},
+  RequestBuilder, StatusCode, Url,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+pub use auth::Credentials;
+pub use image::ImageName;
+pub use policy::{Order, TagFilter, TagPolicy};
+pub use push::OciReference;
+
+#[derive(Debug, Error)]
+pub enum Error {
+  #[error(transparent)]
+  Http(#[from] reqwest::Error),
+
+  #[error("invalid image '{0}', expected a name like 'ghcr.io/org/app'")]
+  InvalidImage(String),
+
+  #[error("image '{0}' has a tag or digest, expected only its name")]
+  TaggedImage(String),
+
+  #[error("invalid docker config: {0}")]
+  InvalidDockerConfig(String),
+
+  #[error("unsupported authentication challenge '{0}'")]
+  UnsupportedChallenge(String),
+
+  #[error("access to image '{0}' was denied")]
+  Unauthorized(String),
+
+  #[error("invalid semver range '{0}'")]
+  InvalidRange(String),
+
+  #[error("invalid tag filter: {0}")]
+  InvalidFilter(String),
+
+  #[error("invalid OCI artifact URL '{0}', expected a URL like 'oci://ghcr.io/org/app:tag'")]
+  InvalidReference(String),
+
+  #[error("upload failed: {0}")]
+  Upload(String),
+}
+
+/// Largest number of pages of tags listed, to not follow a registry linking pages forever.
+const MAX_PAGES: usize = 100;
+
+#[derive(Deserialize)]
+struct TagList {
+  #[serde(default)]
+  tags: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+  #[serde(default)]
+  token: Option<String>,
+  #[serde(default)]
+  access_token: Option<String>,
+}
+
+/// Client of the API of image registries.
+#[derive(Clone, Debug)]
+pub struct Registry {
+  http: reqwest::Client,
+  insecure: bool,
+}
+
+impl Registry {
+  /// Talks to registries with `http`, over https unless `insecure`.
+  pub fn new(http: reqwest::Client, insecure: bool) -> Self {
+    Self { http, insecure }
+  }
+
+  /// Lists every tag of `image`, authenticating with `credentials` if the registry asks to.
+  pub async fn tags(
+    &self,
+    image: &ImageName,
+    credentials: Option<&Credentials>,
+  ) -> Result<Vec<String>, Error> {
+    let base = self.base_url(image);
+    let mut url = base
+      .join(&format!("v2/{}/tags/list", image.repository()))
+      .map_err(|_| Error::InvalidImage(image.to_string()))?;
+
+    let mut session = Session::new(image, credentials, "pull");
+    let mut tags = Vec::new();
+    for _ in 0..MAX_PAGES {
+      let response = self
+        .send(&mut session, || self.http.get(url.clone()))
+        .await?;
+      let next = next_link(response.headers());
+      let page: TagList = response.error_for_status()?.json().await?;
+      tags.extend(page.tags.unwrap_or_default());
+      match next.and_then(|next| base.join(&next).ok()) {
+        Some(next) => url = next,
+        None => break,
+      }
+    }
+
+    Ok(tags)
+  }
+
+  /// The URL of the API of the registry of `image`.
+  fn base_url(&self, image: &ImageName) -> Url {
+    let scheme = if self.insecure { "http" } else { "https" };
+    Url::parse(&format!("{scheme}://{}/", image.api_host())).expect("hosts of images are valid")
+  }
+
+  /// Sends the request built by `request`, authenticating for the session if the registry asks
+  /// to, and then sending it again.
+  async fn send(
+    &self,
+    session: &mut Session<'_>,
+    request: impl Fn() -> RequestBuilder,
+  ) -> Result<reqwest::Response, Error> {
+    let authorized = |authorization: Option<&str>| match authorization {
+      Some(authorization) => request().header(AUTHORIZATION, authorization),
+      None => request(),
+    };
+
+    let mut response = authorized(session.authorization.as_deref()).send().await?;
+    if response.status() == StatusCode::UNAUTHORIZED && session.authorization.is_none() {
+      let challenge = challenge(response.headers()).ok_or_else(|| session.unauthorized())?;
+      session.authorization = Some(self.authorize(session, challenge).await?);
+      response = authorized(session.authorization.as_deref()).send().await?;
+    }
+    if matches!(
+      response.status(),
+      StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+    ) {
+      return Err(session.unauthorized());
+    }
+
+    Ok(response)
+  }
+
+  /// The `Authorization` header answering `challenge`.
+  async fn authorize(&self, session: &Session<'_>, challenge: Challenge) -> Result<String, Error> {
+    let credentials = session.credentials;
+    match challenge {
+      Challenge::Basic => credentials
+        .map(Credentials::basic)
+        .ok_or_else(|| session.unauthorized()),
+      Challenge::Bearer { realm, service } => {
+        let scope = format!(
+          "repository:{}:{}",
+          session.image.repository(),
+          session.actions
+        );
+        let mut request = self.http.get(&realm).query(&[("scope", scope)]);
+        if let Some(service) = service {
+          request = request.query(&[("service", service)]);
+        }
+        if let Some(credentials) = credentials {
+          request = request.basic_auth(credentials.username(), Some(credentials.password()));
+        }
+
+        let response = request.send().await?;
+        if matches!(
+          response.status(),
+          StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+        ) {
+          return Err(session.unauthorized());
+        }
+        let token: TokenResponse = response.error_for_status()?.json().await?;
+        let token = token
+          .token
+          .or(token.access_token)
+          .ok_or_else(|| session.unauthorized())?;
+
+        Ok(format!("Bearer {token}"))
+      }
+      Challenge::Unsupported(scheme) => Err(Error::UnsupportedChallenge(scheme)),
+    }
+  }
+}
+
+/// The requests made on the repository of an image for the same operation, sharing the
+/// authorization the registry granted for `actions`, like `pull,push`.
+struct Session<'a> {
+  image: &'a ImageName,
+  credentials: Option<&'a Credentials>,
+  actions: &'static str,
+  authorization: Option<String>,
+}
+
+impl<'a> Session<'a> {
+  fn new(
+    image: &'a ImageName,
+    credentials: Option<&'a Credentials>,
+    actions: &'static str,
+  ) -> Self {
+    Self {
+      image,
+      credentials,
+      actions,
+      authorization: None,
+    }
+  }
+
+  fn unauthorized(&self) -> Error {
+    Error::Unauthorized(self.image.to_string())
+  }
+}
+
+/// How a registry asks to be authenticated with.
+#[derive(Debug, PartialEq, Eq)]
+enum Challenge {
+  Basic,
+  Bearer {
+    realm: String,
+    service: Option<String>,
+  },
+  Unsupported(String),
+}
+
+/// The challenge of the `WWW-Authenticate` header, like
+/// `Bearer realm="https://ghcr.io/token",service="ghcr.io"`.
+fn challenge(headers: &HeaderMap) -> Option<Challenge> {
+  let header = headers.get(WWW_AUTHENTICATE)?.to_str().ok()?.trim();
+  let (scheme, params) = header.split_once(' ').unwrap_or((header, ""));
+
+  let param = |name: &str| {
+    params.split(',').find_map(|param| {
+      let (key, value) = param.trim().split_once('=')?;
+      key
+        .trim()
+        .eq_ignore_ascii_case(name)
+        .then(|| value.trim().trim_matches('"').to_string())
+    })
+  };
+
+  let challenge = match scheme.to_ascii_lowercase().as_str() {
+    "basic" => Challenge::Basic,
+    "bearer" => Challenge::Bearer {
+      realm: param("realm")?,
+      service: param("service"),
+    },
+    _ => Challenge::Unsupported(scheme.to_string()),
+  };
+
+  Some(challenge)
+}
+
+/// The URL of the next page of the `Link` header, like `</v2/app/tags/list?n=100&last=b>;
+/// rel="next"`.
+fn next_link(headers: &HeaderMap) -> Option<String> {
+  let header = headers.get(LINK)?.to_str().ok()?;
+  header.split(',').find_map(|link| {
+    let (target, params) = link.split_once(';')?;
+    let next = params
+      .split(';')
+      .any(|param| param.trim().replace(' ', "") == "rel=\"next\"");
+    next.then(|| {
+      target
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_string()
+    })
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use reqwest::header::HeaderValue;
+
+  fn headers(name: reqwest::header::HeaderName, value: &'static str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(name, HeaderValue::from_static(value));
+    headers
+  }
+
+  #[test]
+  fn challenges() {
+    assert_eq!(
+      challenge(&headers(
+        WWW_AUTHENTICATE,
+        r#"Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="repository:app:pull""#
+      )),
+      Some(Challenge::Bearer {
+        realm: "https://ghcr.io/token".into(),
+        service: Some("ghcr.io".into()),
+      })
+    );
+    assert_eq!(
+      challenge(&headers(WWW_AUTHENTICATE, r#"Basic realm="registry""#)),
+      Some(Challenge::Basic)
+    );
+    assert_eq!(
+      challenge(&headers(WWW_AUTHENTICATE, "Negotiate")),
+      Some(Challenge::Unsupported("Negotiate".into()))
+    );
+    assert_eq!(challenge(&headers(WWW_AUTHENTICATE, "Bearer")), None);
+    assert_eq!(challenge(&HeaderMap::new()), None);
+  }
+
+  #[test]
+  fn next_links() {
+    assert_eq!(
+      next_link(&headers(
+        LINK,
+        r#"</v2/library/nginx/tags/list?last=1.21&n=100>; rel="next""#
+      )),
+      Some("/v2/library/nginx/tags/list?last=1.21&n=100".into())
+    );
+    assert_eq!(
+      next_link(&headers(LINK, r#"</v2/app/tags/list?last=a>; rel="prev""#)),
+      None
+    );
+    assert_eq!(next_link(&HeaderMap::new()), None);
+  }
+}
@@ -0,0 +1,213 @@
+use crate::Error;
+use regex::Regex;
+use semver::{Version, VersionReq};
+use std::cmp::Ordering;
+
+/// Order in which tags are sorted, the latest tag being the last one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Order {
+  /// The greatest tag is the latest.
+  #[default]
+  Ascending,
+
+  /// The least tag is the latest.
+  Descending,
+}
+
+/// How the latest of the tags of an image is selected.
+#[derive(Clone, Debug)]
+pub enum TagPolicy {
+  /// The greatest version in a semver range, ignoring tags which are not versions. A leading `v`
+  /// is allowed, like in `v1.2.3`.
+  SemVer(VersionReq),
+
+  /// The last tag in alphabetical order.
+  Alphabetical(Order),
+
+  /// The last tag in numerical order, ignoring tags which are not numbers.
+  Numerical(Order),
+}
+
+impl TagPolicy {
+  /// The policy selecting the greatest version in `range`, like `>=1.0.0, <2.0.0`.
+  pub fn semver(range: &str) -> Result<Self, Error> {
+    let range = VersionReq::parse(range).map_err(|_| Error::InvalidRange(range.into()))?;
+    Ok(Self::SemVer(range))
+  }
+
+  /// The latest of `tags`, of those `filter` keeps, if any.
+  pub fn select<'a>(&self, tags: &'a [String], filter: Option<&TagFilter>) -> Option<&'a str> {
+    let candidates = tags.iter().filter_map(|tag| match filter {
+      Some(filter) => Some((tag.as_str(), filter.extract(tag)?)),
+      None => Some((tag.as_str(), tag.clone())),
+    });
+
+    match self {
+      Self::SemVer(range) => candidates
+        .filter_map(|(tag, value)| {
+          let version = Version::parse(value.strip_prefix('v').unwrap_or(&value)).ok()?;
+          range.matches(&version).then_some((tag, version))
+        })
+        // between `1.0.0` and `v1.0.0`, the choice does not depend on the order of the tags
+        .max_by(|(a, a_version), (b, b_version)| a_version.cmp(b_version).then(a.cmp(b)))
+        .map(|(tag, _)| tag),
+      Self::Alphabetical(order) => candidates
+        .max_by(|(a, a_value), (b, b_value)| by(*order, a_value.cmp(b_value).then(a.cmp(b))))
+        .map(|(tag, _)| tag),
+      Self::Numerical(order) => candidates
+        .filter_map(|(tag, value)| {
+          Some((tag, value.parse::<f64>().ok().filter(|n| n.is_finite())?))
+        })
+        .max_by(|(a, a_value), (b, b_value)| {
+          let ordering = a_value.partial_cmp(b_value).unwrap_or(Ordering::Equal);
+          by(*order, ordering.then(a.cmp(b)))
+        })
+        .map(|(tag, _)| tag),
+    }
+  }
+}
+
+fn by(order: Order, ordering: Ordering) -> Ordering {
+  match order {
+    Order::Ascending => ordering,
+    Order::Descending => ordering.reverse(),
+  }
+}
+
+/// Keeps the tags matching a pattern, and compares them on a value extracted from them, like the
+/// timestamp of `main-3f2a1b9-1650000000`.
+#[derive(Clone, Debug)]
+pub struct TagFilter {
+  pattern: Regex,
+  extract: Option<String>,
+}
+
+impl TagFilter {
+  /// Keeps the tags matching `pattern`, comparing them on `extract`, a replacement with the groups
+  /// of the pattern like `$ts`, or else on the whole tag.
+  pub fn new(pattern: &str, extract: Option<String>) -> Result<Self, Error> {
+    let pattern = Regex::new(pattern).map_err(|error| Error::InvalidFilter(error.to_string()))?;
+    if let Some(extract) = &extract {
+      let groups: Vec<_> = pattern.capture_names().flatten().collect();
+      let unknown = extract
+        .split('$')
+        .skip(1)
+        .map(|group| {
+          let group = group.trim_start_matches('{');
+          let end = group
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(group.len());
+          &group[..end]
+        })
+        .find(|group| {
+          !group.is_empty()
+            && !groups.contains(group)
+            && group
+              .parse::<usize>()
+              .map_or(true, |index| index >= pattern.captures_len())
+        });
+      if let Some(group) = unknown {
+        let message = format!("extract references unknown group '{group}'");
+        return Err(Error::InvalidFilter(message));
+      }
+    }
+
+    Ok(Self { pattern, extract })
+  }
+
+  /// The value `tag` is compared on, unless it does not match.
+  fn extract(&self, tag: &str) -> Option<String> {
+    let captures = self.pattern.captures(tag)?;
+    let value = match &self.extract {
+      Some(extract) => {
+        let mut value = String::new();
+        captures.expand(extract, &mut value);
+        value
+      }
+      None => tag.to_string(),
+    };
+
+    Some(value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tags(tags: &[&str]) -> Vec<String> {
+    tags.iter().map(|tag| tag.to_string()).collect()
+  }
+
+  #[test]
+  fn semver_selects_greatest_in_range() {
+    let tags = tags(&["1.0.0", "v1.2.0", "1.10.0-rc.1", "2.0.0", "latest", "1.9.3"]);
+    let policy = TagPolicy::semver(">=1.0.0, <2.0.0").unwrap();
+    assert_eq!(policy.select(&tags, None), Some("1.9.3"));
+
+    let policy = TagPolicy::semver("^3").unwrap();
+    assert_eq!(policy.select(&tags, None), None);
+
+    assert!(matches!(
+      TagPolicy::semver("one"),
+      Err(Error::InvalidRange(_))
+    ));
+  }
+
+  #[test]
+  fn alphabetical_and_numerical_orders() {
+    let tags = tags(&[
+      "RELEASE.2022-01-03",
+      "RELEASE.2022-02-01",
+      "RELEASE.2021-12-30",
+    ]);
+    let policy = TagPolicy::Alphabetical(Order::Ascending);
+    assert_eq!(policy.select(&tags, None), Some("RELEASE.2022-02-01"));
+    let policy = TagPolicy::Alphabetical(Order::Descending);
+    assert_eq!(policy.select(&tags, None), Some("RELEASE.2021-12-30"));
+
+    let numbers = self::tags(&["9", "10", "2.5", "latest"]);
+    let policy = TagPolicy::Numerical(Order::Ascending);
+    assert_eq!(policy.select(&numbers, None), Some("10"));
+    let policy = TagPolicy::Numerical(Order::Descending);
+    assert_eq!(policy.select(&numbers, None), Some("2.5"));
+  }
+
+  #[test]
+  fn filters_extract_values() {
+    let tags = tags(&[
+      "main-3f2a1b9-1650000300",
+      "main-9c0d2e1-1650000020",
+      "feature-aaaaaaa-1660000000",
+      "latest",
+    ]);
+    let filter =
+      TagFilter::new(r"^main-[a-f0-9]+-(?P<ts>[0-9]+)$", Some("$ts".to_string())).unwrap();
+    let policy = TagPolicy::Numerical(Order::Ascending);
+    assert_eq!(
+      policy.select(&tags, Some(&filter)),
+      Some("main-3f2a1b9-1650000300")
+    );
+
+    let filter = TagFilter::new("^feature-", None).unwrap();
+    let policy = TagPolicy::Alphabetical(Order::Ascending);
+    assert_eq!(
+      policy.select(&tags, Some(&filter)),
+      Some("feature-aaaaaaa-1660000000")
+    );
+
+    assert!(matches!(
+      TagFilter::new("^(?P<ts>[0-9]+)$", Some("$version".to_string())),
+      Err(Error::InvalidFilter(_))
+    ));
+    assert!(matches!(
+      TagFilter::new("^([0-9]+)$", Some("${2}".to_string())),
+      Err(Error::InvalidFilter(_))
+    ));
+    assert!(TagFilter::new("^([0-9]+)$", Some("${1}".to_string())).is_ok());
+    assert!(matches!(
+      TagFilter::new("(", None),
+      Err(Error::InvalidFilter(_))
+    ));
+  }
+}
@@ -0,0 +1,265 @@
+use crate::{Credentials, Error, ImageName, Registry, Session};
+use reqwest::{
+  header::{CONTENT_TYPE, LOCATION},
+  StatusCode, Url,
+};
+use ring::digest::{digest, SHA256};
+use serde_json::json;
+use std::{collections::BTreeMap, fmt};
+
+/// Media type of the manifest of an artifact.
+const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+
+/// Media type of the empty config of a Flux artifact.
+const CONFIG_MEDIA_TYPE: &str = "application/vnd.cncf.flux.config.v1+json";
+
+/// Media type of the content of a Flux artifact, a gzipped tarball.
+const LAYER_MEDIA_TYPE: &str = "application/vnd.cncf.flux.content.v1.tar+gzip";
+
+/// Tag an artifact is pushed as when its URL has none.
+const DEFAULT_TAG: &str = "latest";
+
+/// Scheme of the URLs of OCI artifacts.
+const SCHEME: &str = "oci://";
+
+/// The URL of an OCI artifact, like `oci://ghcr.io/org/keys:v1`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OciReference {
+  image: ImageName,
+  tag: String,
+}
+
+impl OciReference {
+  /// Parses `url`, which must have the `oci://` scheme, and is tagged `latest` unless it has a
+  /// tag of its own.
+  pub fn parse(url: &str) -> Result<Self, Error> {
+    let invalid = || Error::InvalidReference(url.into());
+    let name = url.trim().strip_prefix(SCHEME).ok_or_else(invalid)?;
+    if name.contains('@') {
+      return Err(invalid());
+    }
+
+    let (name, tag) = match name.rsplit_once(':') {
+      Some((name, tag)) if !tag.contains('/') => (name, tag),
+      _ => (name, DEFAULT_TAG),
+    };
+    let valid_tag = !tag.is_empty()
+      && tag.len() <= 128
+      && tag
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "._-".contains(c))
+      && !tag.starts_with(['.', '-']);
+    if !valid_tag {
+      return Err(invalid());
+    }
+
+    Ok(Self {
+      image: ImageName::parse(name).map_err(|_| invalid())?,
+      tag: tag.to_string(),
+    })
+  }
+
+  /// The repository of the artifact.
+  pub fn image(&self) -> &ImageName {
+    &self.image
+  }
+
+  pub fn tag(&self) -> &str {
+    &self.tag
+  }
+}
+
+impl fmt::Display for OciReference {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{SCHEME}{}:{}", self.image, self.tag)
+  }
+}
+
+/// The `sha256:<hex>` digest of `content`.
+fn sha256(content: &[u8]) -> String {
+  let hex: String = digest(&SHA256, content)
+    .as_ref()
+    .iter()
+    .map(|byte| format!("{byte:02x}"))
+    .collect();
+  format!("sha256:{hex}")
+}
+
+/// The manifest of an artifact made of the empty `config` and the tarball `layer`, which are
+/// described by their digest and size.
+fn manifest(
+  config: (&str, usize),
+  layer: (&str, usize),
+  annotations: &BTreeMap<String, String>,
+) -> Vec<u8> {
+  let manifest = json!({
+    "schemaVersion": 2,
+    "mediaType": MANIFEST_MEDIA_TYPE,
+    "config": {
+      "mediaType": CONFIG_MEDIA_TYPE,
+      "digest": config.0,
+      "size": config.1,
+    },
+    "layers": [{
+      "mediaType": LAYER_MEDIA_TYPE,
+      "digest": layer.0,
+      "size": layer.1,
+    }],
+    "annotations": annotations,
+  });
+
+  serde_json::to_vec(&manifest).expect("manifests serialize")
+}
+
+impl Registry {
+  /// Pushes `layer`, a gzipped tarball, as a Flux artifact tagged as `reference`, whose manifest
+  /// carries `annotations`, like `org.opencontainers.image.revision`. Returns the digest of the
+  /// manifest.
+  pub async fn push(
+    &self,
+    reference: &OciReference,
+    credentials: Option<&Credentials>,
+    layer: Vec<u8>,
+    annotations: &BTreeMap<String, String>,
+  ) -> Result<String, Error> {
+    let image = reference.image();
+    let mut session = Session::new(image, credentials, "pull,push");
+
+    let config = b"{}".to_vec();
+    let config_digest = sha256(&config);
+    let layer_digest = sha256(&layer);
+    let manifest = manifest(
+      (&config_digest, config.len()),
+      (&layer_digest, layer.len()),
+      annotations,
+    );
+
+    self.upload(&mut session, &config_digest, config).await?;
+    self.upload(&mut session, &layer_digest, layer).await?;
+
+    let url = self.repository_url(image, &format!("manifests/{}", reference.tag()))?;
+    self
+      .send(&mut session, || {
+        self
+          .http
+          .put(url.clone())
+          .header(CONTENT_TYPE, MANIFEST_MEDIA_TYPE)
+          .body(manifest.clone())
+      })
+      .await?
+      .error_for_status()?;
+
+    Ok(sha256(&manifest))
+  }
+
+  /// Uploads the blob `content` of `digest`, unless the repository has it already.
+  async fn upload(
+    &self,
+    session: &mut Session<'_>,
+    digest: &str,
+    content: Vec<u8>,
+  ) -> Result<(), Error> {
+    let image = session.image;
+    let blob = self.repository_url(image, &format!("blobs/{digest}"))?;
+    let response = self.send(session, || self.http.head(blob.clone())).await?;
+    if response.status().is_success() {
+      return Ok(());
+    }
+
+    let uploads = self.repository_url(image, "blobs/uploads/")?;
+    let response = self
+      .send(session, || self.http.post(uploads.clone()))
+      .await?
+      .error_for_status()?;
+    let mut location = response
+      .headers()
+      .get(LOCATION)
+      .and_then(|location| location.to_str().ok())
+      .and_then(|location| uploads.join(location).ok())
+      .ok_or_else(|| Error::Upload(format!("no upload location for blob '{digest}'")))?;
+    location.query_pairs_mut().append_pair("digest", digest);
+
+    let response = self
+      .send(session, || {
+        self
+          .http
+          .put(location.clone())
+          .header(CONTENT_TYPE, "application/octet-stream")
+          .body(content.clone())
+      })
+      .await?;
+    if response.status() != StatusCode::CREATED {
+      response.error_for_status()?;
+    }
+
+    Ok(())
+  }
+
+  /// The URL of `path` in the repository of `image`, like `manifests/latest`.
+  fn repository_url(&self, image: &ImageName, path: &str) -> Result<Url, Error> {
+    self
+      .base_url(image)
+      .join(&format!("v2/{}/{path}", image.repository()))
+      .map_err(|_| Error::InvalidImage(image.to_string()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn references() {
+    let reference = OciReference::parse("oci://ghcr.io/org/keys:v1").unwrap();
+    assert_eq!(reference.image().to_string(), "ghcr.io/org/keys");
+    assert_eq!(reference.tag(), "v1");
+
+    let reference = OciReference::parse("oci://registry.local:5000/keys").unwrap();
+    assert_eq!(reference.image().registry(), "registry.local:5000");
+    assert_eq!(
+      reference.to_string(),
+      "oci://registry.local:5000/keys:latest"
+    );
+
+    for invalid in [
+      "ghcr.io/org/keys",
+      "oci://ghcr.io/org/keys:",
+      "oci://ghcr.io/org/keys@sha256:abc",
+      "oci://ghcr.io/Org/keys",
+    ] {
+      assert!(
+        matches!(
+          OciReference::parse(invalid),
+          Err(Error::InvalidReference(_))
+        ),
+        "{invalid}"
+      );
+    }
+  }
+
+  #[test]
+  fn manifests() {
+    assert_eq!(
+      sha256(b"{}"),
+      "sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+    );
+
+    let annotations = [(
+      "org.opencontainers.image.revision".to_string(),
+      "abc".to_string(),
+    )]
+    .into_iter()
+    .collect();
+    let manifest: serde_json::Value =
+      serde_json::from_slice(&manifest(("sha256:c", 2), ("sha256:l", 10), &annotations)).unwrap();
+
+    assert_eq!(manifest["mediaType"], MANIFEST_MEDIA_TYPE);
+    assert_eq!(manifest["config"]["mediaType"], CONFIG_MEDIA_TYPE);
+    assert_eq!(manifest["layers"][0]["digest"], "sha256:l");
+    assert_eq!(manifest["layers"][0]["size"], 10);
+    assert_eq!(
+      manifest["annotations"]["org.opencontainers.image.revision"],
+      "abc"
+    );
+  }
+}
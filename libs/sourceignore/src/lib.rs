@@ -0,0 +1,254 @@
+use ignore::{
+  gitignore::{Gitignore, GitignoreBuilder},
+  Match,
+};
+use thiserror::Error;
+
+/// Name of the files holding patterns of the files to exclude from the artifact of a source, in
+/// the directory they are in and below.
+pub const IGNORE_FILE: &str = ".sourceignore";
+
+/// Files of version control systems, which are never part of an artifact.
+pub const EXCLUDE_VCS: &[&str] = &[".git/", ".gitignore", ".gitmodules", ".gitattributes"];
+
+/// Files which are usually not manifests, like media and archives.
+pub const EXCLUDE_EXT: &[&str] = &[
+  "*.jpg", "*.jpeg", "*.gif", "*.png", "*.wmv", "*.flv", "*.tar.gz", "*.zip",
+];
+
+/// Configuration of continuous integration services.
+pub const EXCLUDE_CI: &[&str] = &[
+  ".github/",
+  ".circleci/",
+  ".travis.yml",
+  ".gitlab-ci.yml",
+  "appveyor.yml",
+  ".drone.yml",
+  "cloudbuild.yaml",
+  "codeship-services.yml",
+  "codeship-steps.yml",
+];
+
+/// Configuration of other tools, which look like manifests.
+pub const EXCLUDE_EXTRA: &[&str] = &["**/.goreleaser.yml", "**/.sops.yaml", "**/.flux.yaml"];
+
+#[derive(Debug, Error)]
+#[error("invalid ignore pattern '{pattern}'")]
+pub struct InvalidPattern {
+  pattern: String,
+  #[source]
+  source: ignore::Error,
+}
+
+impl InvalidPattern {
+  pub fn pattern(&self) -> &str {
+    &self.pattern
+  }
+}
+
+/// The patterns of a single source of patterns, which apply to the files in `domain` and below.
+struct Rules {
+  domain: String,
+  rules: Gitignore,
+}
+
+/// Matches the files of a source to exclude from its artifact, following the `.sourceignore`
+/// semantics of Flux: the patterns are those of `.gitignore` files, and those added last take
+/// precedence, so that a `!` pattern re-includes the files excluded by those before it.
+pub struct SourceIgnore {
+  rules: Vec<Rules>,
+}
+
+impl SourceIgnore {
+  /// Whether the file (or directory, if `is_dir`) at `path`, relative to the root of the source
+  /// and separated with `/`, is excluded, either itself or through one of its parents.
+  pub fn matches(&self, path: &str, is_dir: bool) -> bool {
+    let path = path.trim_start_matches('/');
+    let mut ignored = false;
+    for rules in &self.rules {
+      let relative = if rules.domain.is_empty() {
+        path
+      } else {
+        match path
+          .strip_prefix(&*rules.domain)
+          .and_then(|p| p.strip_prefix('/'))
+        {
+          Some(relative) => relative,
+          None => continue,
+        }
+      };
+
+      match rules.rules.matched_path_or_any_parents(relative, is_dir) {
+        Match::Ignore(_) => ignored = true,
+        Match::Whitelist(_) => ignored = false,
+        Match::None => {}
+      }
+    }
+
+    ignored
+  }
+}
+
+/// Builds a [SourceIgnore] from the patterns of a source, which are added in the order of
+/// precedence Flux uses: the defaults, then the [IGNORE_FILE]s of the source, then the `ignore` of
+/// its spec.
+pub struct SourceIgnoreBuilder {
+  rules: Vec<Rules>,
+}
+
+impl SourceIgnoreBuilder {
+  /// Starts with the [EXCLUDE_VCS] patterns, as sources with a `spec.ignore` do.
+  pub fn new() -> Self {
+    let mut builder = Self { rules: Vec::new() };
+    builder.push("", EXCLUDE_VCS.iter().copied()).unwrap();
+
+    builder
+  }
+
+  /// Starts with all of the default patterns, as sources without a `spec.ignore` do: the
+  /// [EXCLUDE_VCS], [EXCLUDE_EXT], [EXCLUDE_CI] and [EXCLUDE_EXTRA] ones.
+  pub fn with_default_excludes() -> Self {
+    let mut builder = Self::new();
+    let defaults = EXCLUDE_EXT.iter().chain(EXCLUDE_CI).chain(EXCLUDE_EXTRA);
+    builder.push("", defaults.copied()).unwrap();
+
+    builder
+  }
+
+  /// Adds the patterns of the [IGNORE_FILE] at `path`, like `deploy/.sourceignore`, which apply
+  /// to the files of its directory.
+  pub fn add_ignore_file(
+    &mut self,
+    path: &str,
+    content: &str,
+  ) -> Result<&mut Self, InvalidPattern> {
+    let path = path.trim_start_matches('/');
+    let domain = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+    self.push(domain, content.lines())?;
+
+    Ok(self)
+  }
+
+  /// Adds `patterns`, one per line like those of the `ignore` of a spec, which apply to all of the
+  /// files of the source.
+  pub fn add_patterns(&mut self, patterns: &str) -> Result<&mut Self, InvalidPattern> {
+    self.push("", patterns.lines())?;
+
+    Ok(self)
+  }
+
+  fn push<'a>(
+    &mut self,
+    domain: &str,
+    patterns: impl IntoIterator<Item = &'a str>,
+  ) -> Result<(), InvalidPattern> {
+    let mut builder = GitignoreBuilder::new("");
+    for pattern in patterns {
+      builder
+        .add_line(None, pattern)
+        .map_err(|source| InvalidPattern {
+          pattern: pattern.into(),
+          source,
+        })?;
+    }
+
+    let rules = builder.build().map_err(|source| InvalidPattern {
+      pattern: String::new(),
+      source,
+    })?;
+    self.rules.push(Rules {
+      domain: domain.into(),
+      rules,
+    });
+
+    Ok(())
+  }
+
+  pub fn build(self) -> SourceIgnore {
+    SourceIgnore { rules: self.rules }
+  }
+}
+
+impl Default for SourceIgnoreBuilder {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn excludes_vcs_files() {
+    let ignore = SourceIgnoreBuilder::new().build();
+    assert!(ignore.matches(".git", true));
+    assert!(ignore.matches(".git/HEAD", false));
+    assert!(ignore.matches("apps/.gitignore", false));
+    assert!(!ignore.matches("apps/deploy.yaml", false));
+    // without the default excludes
+    assert!(!ignore.matches("logo.png", false));
+  }
+
+  #[test]
+  fn excludes_defaults_without_spec_ignore() {
+    let ignore = SourceIgnoreBuilder::with_default_excludes().build();
+    assert!(ignore.matches("docs/logo.png", false));
+    assert!(ignore.matches(".github/workflows/ci.yaml", false));
+    assert!(ignore.matches("apps/.sops.yaml", false));
+    assert!(!ignore.matches("apps/deploy.yaml", false));
+  }
+
+  #[test]
+  fn ignore_files_apply_to_their_directory() {
+    let mut builder = SourceIgnoreBuilder::new();
+    builder
+      .add_ignore_file(IGNORE_FILE, "*.md\n/tests/\n")
+      .unwrap()
+      .add_ignore_file("apps/.sourceignore", "# comment\n/secrets.yaml\n")
+      .unwrap();
+    let ignore = builder.build();
+
+    assert!(ignore.matches("README.md", false));
+    assert!(ignore.matches("apps/README.md", false));
+    assert!(ignore.matches("tests/deploy.yaml", false));
+    assert!(!ignore.matches("apps/tests/deploy.yaml", false));
+    assert!(ignore.matches("apps/secrets.yaml", false));
+    assert!(!ignore.matches("secrets.yaml", false));
+    assert!(!ignore.matches("other/apps/secrets.yaml", false));
+  }
+
+  #[test]
+  fn later_patterns_take_precedence() {
+    let mut builder = SourceIgnoreBuilder::new();
+    builder
+      .add_ignore_file(IGNORE_FILE, "*.yaml\n")
+      .unwrap()
+      .add_patterns("!deploy.yaml\n")
+      .unwrap();
+    let ignore = builder.build();
+
+    assert!(ignore.matches("kustomization.yaml", false));
+    assert!(!ignore.matches("apps/deploy.yaml", false));
+  }
+
+  #[test]
+  fn directory_patterns_only_match_directories() {
+    let mut builder = SourceIgnoreBuilder::new();
+    builder.add_patterns("build/").unwrap();
+    let ignore = builder.build();
+
+    assert!(ignore.matches("build", true));
+    assert!(ignore.matches("build/deploy.yaml", false));
+    assert!(!ignore.matches("build", false));
+  }
+
+  #[test]
+  fn rejects_invalid_patterns() {
+    let error = SourceIgnoreBuilder::new()
+      .add_patterns("ok\n[z-a]\n")
+      .err()
+      .unwrap();
+    assert_eq!(error.pattern(), "[z-a]");
+  }
+}
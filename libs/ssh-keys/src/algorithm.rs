@@ -0,0 +1,74 @@
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+/// The public key algorithms supported by OpenSSH.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Algorithm {
+  Rsa,
+  Dsa,
+  EcdsaP256,
+  EcdsaP384,
+  EcdsaP521,
+  Ed25519,
+  SkEcdsaP256,
+  SkEd25519,
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported public key algorithm '{0}'")]
+pub struct UnsupportedAlgorithm(pub String);
+
+impl Algorithm {
+  pub const ALL: &'static [Algorithm] = &[
+    Algorithm::Rsa,
+    Algorithm::Dsa,
+    Algorithm::EcdsaP256,
+    Algorithm::EcdsaP384,
+    Algorithm::EcdsaP521,
+    Algorithm::Ed25519,
+    Algorithm::SkEcdsaP256,
+    Algorithm::SkEd25519,
+  ];
+
+  /// The name of the algorithm, as used in `authorized_keys` files.
+  pub const fn name(&self) -> &'static str {
+    match self {
+      Algorithm::Rsa => "ssh-rsa",
+      Algorithm::Dsa => "ssh-dss",
+      Algorithm::EcdsaP256 => "ecdsa-sha2-nistp256",
+      Algorithm::EcdsaP384 => "ecdsa-sha2-nistp384",
+      Algorithm::EcdsaP521 => "ecdsa-sha2-nistp521",
+      Algorithm::Ed25519 => "ssh-ed25519",
+      Algorithm::SkEcdsaP256 => "sk-ecdsa-sha2-nistp256@openssh.com",
+      Algorithm::SkEd25519 => "sk-ssh-ed25519@openssh.com",
+    }
+  }
+
+  /// The curve identifier embedded in ECDSA keys.
+  pub(crate) const fn curve(&self) -> Option<&'static str> {
+    match self {
+      Algorithm::EcdsaP256 | Algorithm::SkEcdsaP256 => Some("nistp256"),
+      Algorithm::EcdsaP384 => Some("nistp384"),
+      Algorithm::EcdsaP521 => Some("nistp521"),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for Algorithm {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.name())
+  }
+}
+
+impl FromStr for Algorithm {
+  type Err = UnsupportedAlgorithm;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Algorithm::ALL
+      .iter()
+      .copied()
+      .find(|a| a.name() == s)
+      .ok_or_else(|| UnsupportedAlgorithm(s.into()))
+  }
+}
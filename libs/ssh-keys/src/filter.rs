@@ -0,0 +1,50 @@
+use crate::{Algorithm, PublicKey};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Selects which keys are accepted, e.g. to reject legacy algorithms or short RSA keys. The
+/// default filter accepts every key.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+  algorithms: Option<BTreeSet<Algorithm>>,
+  min_bits: BTreeMap<Algorithm, u32>,
+}
+
+impl Filter {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Only accept keys using one of `algorithms`.
+  pub fn algorithms(mut self, algorithms: impl IntoIterator<Item = Algorithm>) -> Self {
+    self.algorithms = Some(algorithms.into_iter().collect());
+    self
+  }
+
+  /// Only accept keys using `algorithm` which are at least `bits` long.
+  pub fn min_bits(mut self, algorithm: Algorithm, bits: u32) -> Self {
+    self.min_bits.insert(algorithm, bits);
+    self
+  }
+
+  pub fn matches(&self, key: &PublicKey) -> bool {
+    let algorithm = key.algorithm();
+    let allowed = self
+      .algorithms
+      .as_ref()
+      .is_none_or(|algorithms| algorithms.contains(&algorithm));
+
+    allowed
+      && self
+        .min_bits
+        .get(&algorithm)
+        .is_none_or(|&bits| key.bits() >= bits)
+  }
+
+  /// The keys of `keys` accepted by the filter.
+  pub fn apply<'a>(
+    &'a self,
+    keys: impl IntoIterator<Item = PublicKey> + 'a,
+  ) -> impl Iterator<Item = PublicKey> + 'a {
+    keys.into_iter().filter(|key| self.matches(key))
+  }
+}
@@ -0,0 +1,277 @@
+mod algorithm;
+mod filter;
+mod wire;
+
+use ring::digest::{digest, SHA256};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+use thiserror::Error;
+
+pub use algorithm::{Algorithm, UnsupportedAlgorithm};
+pub use filter::Filter;
+
+/// An OpenSSH public key, as found in `authorized_keys` files.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PublicKey {
+  algorithm: Algorithm,
+  blob: Vec<u8>,
+  bits: u32,
+  comment: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+  #[error("public key is missing its key data")]
+  MissingKeyData,
+
+  #[error("public key data is not valid base64")]
+  InvalidBase64(#[from] base64::DecodeError),
+
+  #[error(transparent)]
+  UnsupportedAlgorithm(#[from] UnsupportedAlgorithm),
+
+  #[error("public key data is malformed")]
+  InvalidKeyData,
+
+  #[error("public key data is for a '{found}' key, not '{expected}'")]
+  AlgorithmMismatch { expected: Algorithm, found: String },
+}
+
+impl PublicKey {
+  pub fn algorithm(&self) -> Algorithm {
+    self.algorithm
+  }
+
+  /// The size of the key in bits, i.e. the modulus for RSA, `p` for DSA, and the curve size for
+  /// elliptic curve keys.
+  pub fn bits(&self) -> u32 {
+    self.bits
+  }
+
+  /// The decoded key data.
+  pub fn blob(&self) -> &[u8] {
+    &self.blob
+  }
+
+  pub fn comment(&self) -> Option<&str> {
+    self.comment.as_deref()
+  }
+
+  /// The SHA256 fingerprint of the key, in the format used by OpenSSH (`SHA256:<base64>`).
+  pub fn fingerprint(&self) -> String {
+    let hash = digest(&SHA256, &self.blob);
+    format!(
+      "SHA256:{}",
+      base64::encode_config(hash, base64::STANDARD_NO_PAD)
+    )
+  }
+
+  /// The fingerprint, encoded so it can be used as the key of a Secret or ConfigMap entry.
+  pub fn fingerprint_key(&self) -> String {
+    let hash = digest(&SHA256, &self.blob);
+    format!(
+      "SHA256-{}",
+      base64::encode_config(hash, base64::URL_SAFE_NO_PAD)
+    )
+  }
+}
+
+impl FromStr for PublicKey {
+  type Err = ParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut parts = s.split_whitespace();
+    let algorithm = parts.next().ok_or(ParseError::MissingKeyData)?;
+    let blob = parts.next().ok_or(ParseError::MissingKeyData)?;
+    let comment = parts.collect::<Vec<_>>().join(" ");
+
+    let algorithm = algorithm.parse()?;
+    let blob = base64::decode(blob)?;
+    let bits = wire::validate(algorithm, &blob)?;
+
+    Ok(Self {
+      algorithm,
+      blob,
+      bits,
+      comment: Some(comment).filter(|c| !c.is_empty()),
+    })
+  }
+}
+
+/// Formats the key as a line of an `authorized_keys` file.
+impl fmt::Display for PublicKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} {}", self.algorithm, base64::encode(&self.blob))?;
+    if let Some(comment) = &self.comment {
+      write!(f, " {comment}")?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Parses the keys of an `authorized_keys` file, skipping empty lines and comments.
+pub fn parse_authorized_keys(
+  content: &str,
+) -> impl Iterator<Item = Result<PublicKey, ParseError>> + '_ {
+  content
+    .lines()
+    .map(str::trim)
+    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    .map(str::parse)
+}
+
+/// All keys as a single `authorized_keys` file.
+pub fn combined<'a>(keys: impl IntoIterator<Item = &'a PublicKey>) -> String {
+  keys.into_iter().map(|key| format!("{key}\n")).collect()
+}
+
+/// The keys of several users as a single set, like those of the members of a team: sorted, so the
+/// set does not depend on the order the keys were fetched in, and with the keys listed more than
+/// once (whatever their comment) only kept the first time.
+pub fn aggregate(keys: impl IntoIterator<Item = PublicKey>) -> Vec<PublicKey> {
+  let mut keys = keys.into_iter().collect::<Vec<_>>();
+  // stable, so the first of the same keys is kept
+  keys.sort_by(|a, b| (a.algorithm, &a.blob).cmp(&(b.algorithm, &b.blob)));
+  keys.dedup_by(|a, b| a.algorithm == b.algorithm && a.blob == b.blob);
+
+  keys
+}
+
+/// Every key as its own entry, keyed by its fingerprint. The keys are stable for as long as the
+/// key itself does not change, so consumers can mount or reference individual keys.
+pub fn per_key<'a>(keys: impl IntoIterator<Item = &'a PublicKey>) -> BTreeMap<String, String> {
+  keys
+    .into_iter()
+    .map(|key| (key.fingerprint_key(), format!("{key}\n")))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ED25519: &str =
+    "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl alice@example";
+
+  #[test]
+  fn parse_key() {
+    let key: PublicKey = ED25519.parse().unwrap();
+    assert_eq!(key.algorithm(), Algorithm::Ed25519);
+    assert_eq!(key.bits(), 256);
+    assert_eq!(key.comment(), Some("alice@example"));
+    assert_eq!(key.to_string(), ED25519);
+  }
+
+  const RSA: &str = "ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAAAgQDFWDRjxnQsjy3pLM/+SUtixKdTIMGt8FB5KsT3DtIvD9IxXDtmgW4kEJE3psUZRiwS7bK6YrPNitq43+IHhAEK9XsRoV7Is76jDW7ewkC4zHP5XKIQcofrMuDGvQ2TAgL4ezbQov3zuOiO8hEX5sSuusNITm+S23Sh60xqjML0pQ== rsa@example";
+
+  const ECDSA: &str = "ecdsa-sha2-nistp384 AAAAE2VjZHNhLXNoYTItbmlzdHAzODQAAAAIbmlzdHAzODQAAABhBKOnpKcKz3zvvng2q/QQoXd8c0i5jHKhxGaxPpJaWIAJKMG37PVKyJYVGv0WPRe2PSbyDXstNmUDPZymQVl5GCezGdvKmf8tovTanzWDcqc289ea9ibh3yClXTH2jKnrfw== ecdsa@example";
+
+  #[test]
+  fn parse_key_sizes() {
+    let rsa: PublicKey = RSA.parse().unwrap();
+    assert_eq!(rsa.algorithm(), Algorithm::Rsa);
+    assert_eq!(rsa.bits(), 1024);
+    assert_eq!(
+      rsa.fingerprint(),
+      "SHA256:GVrP9OAtB8Ww4tjsKuT7B9E4cugcUI0HYahho0uV/io"
+    );
+
+    let ecdsa: PublicKey = ECDSA.parse().unwrap();
+    assert_eq!(ecdsa.algorithm(), Algorithm::EcdsaP384);
+    assert_eq!(ecdsa.bits(), 384);
+    assert_eq!(
+      ecdsa.fingerprint(),
+      "SHA256:0fCDrUc0AcQ0Ol9kRPmU6ND6opu82/qXr5QVBKhFXd8"
+    );
+  }
+
+  #[test]
+  fn reject_invalid_keys() {
+    let data = ED25519.split_whitespace().nth(1).unwrap();
+    assert!(matches!(
+      format!("ssh-rsa {data}").parse::<PublicKey>(),
+      Err(ParseError::AlgorithmMismatch {
+        expected: Algorithm::Rsa,
+        ..
+      })
+    ));
+    assert!(matches!(
+      format!("ssh-foo {data}").parse::<PublicKey>(),
+      Err(ParseError::UnsupportedAlgorithm(_))
+    ));
+
+    let truncated = base64::encode(&base64::decode(data).unwrap()[..40]);
+    assert!(matches!(
+      format!("ssh-ed25519 {truncated}").parse::<PublicKey>(),
+      Err(ParseError::InvalidKeyData)
+    ));
+  }
+
+  #[test]
+  fn filter_keys() {
+    let keys = [ED25519, RSA, ECDSA]
+      .into_iter()
+      .map(|line| line.parse::<PublicKey>().unwrap())
+      .collect::<Vec<_>>();
+
+    let filter = Filter::new()
+      .algorithms([Algorithm::Ed25519, Algorithm::Rsa])
+      .min_bits(Algorithm::Rsa, 2048);
+    let accepted = filter.apply(keys.clone()).collect::<Vec<_>>();
+    assert_eq!(accepted, &keys[..1]);
+
+    let accepted = Filter::new().apply(keys.clone()).count();
+    assert_eq!(accepted, 3);
+  }
+
+  #[test]
+  fn fingerprint() {
+    let key: PublicKey = ED25519.parse().unwrap();
+    assert_eq!(
+      key.fingerprint(),
+      "SHA256:+DiY3wvvV6TuJJhbpZisF/zLDA0zPMSvHdkr4UvCOqU"
+    );
+    assert_eq!(
+      key.fingerprint_key(),
+      "SHA256--DiY3wvvV6TuJJhbpZisF_zLDA0zPMSvHdkr4UvCOqU"
+    );
+  }
+
+  #[test]
+  fn parse_file() {
+    let content = format!("# keys\n\n{ED25519}\nssh-rsa\n");
+    let keys = parse_authorized_keys(&content).collect::<Vec<_>>();
+    assert_eq!(keys.len(), 2);
+    assert!(keys[0].is_ok());
+    assert!(matches!(keys[1], Err(ParseError::MissingKeyData)));
+  }
+
+  #[test]
+  fn aggregate_keys() {
+    let keys = [RSA, ED25519, ECDSA]
+      .into_iter()
+      .map(|line| line.parse::<PublicKey>().unwrap())
+      .collect::<Vec<_>>();
+    let shared = format!("{} bob@example", ED25519.rsplit_once(' ').unwrap().0);
+    let shared: PublicKey = shared.parse().unwrap();
+
+    let alice = [keys[0].clone(), keys[1].clone()];
+    let bob = [keys[2].clone(), shared.clone(), keys[0].clone()];
+    let a = aggregate(alice.iter().chain(&bob).cloned());
+    let b = aggregate(bob.iter().chain(&alice).cloned());
+    assert_eq!(a.len(), 3);
+    assert_eq!(combined(&a), combined(&aggregate(a.clone())));
+    // only the comment of the kept key differs
+    assert_eq!(a[2].comment(), Some("alice@example"));
+    assert_eq!(b[2].comment(), Some("bob@example"));
+  }
+
+  #[test]
+  fn project_per_key() {
+    let key: PublicKey = ED25519.parse().unwrap();
+    let data = per_key([&key, &key]);
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[&key.fingerprint_key()], format!("{ED25519}\n"));
+    assert_eq!(combined([&key]), format!("{ED25519}\n"));
+  }
+}
@@ -0,0 +1,98 @@
+use crate::{Algorithm, ParseError};
+
+/// Reads values in the SSH wire format (RFC 4251).
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+  fn string(&mut self) -> Result<&'a [u8], ParseError> {
+    let (len, rest) = self
+      .0
+      .split_at_checked(4)
+      .ok_or(ParseError::InvalidKeyData)?;
+    let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+    let (value, rest) = rest
+      .split_at_checked(len)
+      .ok_or(ParseError::InvalidKeyData)?;
+    self.0 = rest;
+    Ok(value)
+  }
+
+  /// Reads a multiple precision integer, returning its size in bits.
+  fn mpint_bits(&mut self) -> Result<u32, ParseError> {
+    let value = self.string()?;
+    let value = match value.iter().position(|&b| b != 0) {
+      Some(start) => &value[start..],
+      None => return Ok(0),
+    };
+
+    Ok((value.len() as u32 - 1) * 8 + (8 - value[0].leading_zeros()))
+  }
+
+  fn finish(self) -> Result<(), ParseError> {
+    match self.0.is_empty() {
+      true => Ok(()),
+      false => Err(ParseError::InvalidKeyData),
+    }
+  }
+}
+
+/// Validates the structure of the key data of a key of `algorithm`, returning the size of the
+/// key in bits.
+pub(crate) fn validate(algorithm: Algorithm, blob: &[u8]) -> Result<u32, ParseError> {
+  let mut reader = Reader(blob);
+  let name = reader.string()?;
+  if name != algorithm.name().as_bytes() {
+    return Err(ParseError::AlgorithmMismatch {
+      expected: algorithm,
+      found: String::from_utf8_lossy(name).into_owned(),
+    });
+  }
+
+  let bits = match algorithm {
+    Algorithm::Rsa => {
+      let _exponent = reader.string()?;
+      reader.mpint_bits()?
+    }
+
+    Algorithm::Dsa => {
+      let bits = reader.mpint_bits()?;
+      for _ in 0..3 {
+        reader.string()?;
+      }
+
+      bits
+    }
+
+    Algorithm::EcdsaP256 | Algorithm::EcdsaP384 | Algorithm::EcdsaP521 | Algorithm::SkEcdsaP256 => {
+      if Some(reader.string()?) != algorithm.curve().map(str::as_bytes) {
+        return Err(ParseError::InvalidKeyData);
+      }
+
+      let _point = reader.string()?;
+      if algorithm == Algorithm::SkEcdsaP256 {
+        let _application = reader.string()?;
+      }
+
+      match algorithm {
+        Algorithm::EcdsaP384 => 384,
+        Algorithm::EcdsaP521 => 521,
+        _ => 256,
+      }
+    }
+
+    Algorithm::Ed25519 | Algorithm::SkEd25519 => {
+      if reader.string()?.len() != 32 {
+        return Err(ParseError::InvalidKeyData);
+      }
+
+      if algorithm == Algorithm::SkEd25519 {
+        let _application = reader.string()?;
+      }
+
+      256
+    }
+  };
+
+  reader.finish()?;
+  Ok(bits)
+}
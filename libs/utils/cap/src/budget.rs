@@ -0,0 +1,233 @@
+use std::{
+  num::NonZeroU32,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+use tokio::{sync::Notify, time::Instant};
+
+/// The time slice the budget is handed out in.
+const TICK: Duration = Duration::from_secs(1);
+
+/// How far a controller of weight 1 falls back in line for every reconcile it starts.
+const STRIDE: u64 = 1 << 20;
+
+/// Caps how many reconciles the controllers of an app start per second, to protect the API server
+/// and external providers when many objects change at once (like when a CRD is reinstalled).
+///
+/// Controllers take their reconciles from the budget through a [BudgetShare]. While several
+/// controllers are waiting, the budget is shared out in proportion to their weights, and whatever
+/// a controller leaves unused goes to the others.
+pub(crate) struct ReconcileBudget {
+  per_tick: u32,
+  state: Mutex<BudgetState>,
+  started: Notify,
+}
+
+impl ReconcileBudget {
+  pub(crate) fn new(per_second: NonZeroU32) -> Arc<Self> {
+    Arc::new(Self {
+      per_tick: per_second.get(),
+      state: Mutex::new(BudgetState::new(Instant::now(), per_second.get())),
+      started: Notify::new(),
+    })
+  }
+
+  /// Registers a controller, which gets a share of the budget in proportion to `weight`.
+  pub(crate) fn share(self: &Arc<Self>, weight: u32) -> BudgetShare {
+    let id = self.state.lock().unwrap().register(weight);
+    BudgetShare {
+      budget: self.clone(),
+      id,
+    }
+  }
+}
+
+/// The share of a single controller in a [ReconcileBudget].
+pub(crate) struct BudgetShare {
+  budget: Arc<ReconcileBudget>,
+  id: usize,
+}
+
+impl BudgetShare {
+  /// Waits until the controller may start a reconcile.
+  pub(crate) async fn start(&self) {
+    let budget = &*self.budget;
+    let _waiting = Waiting::enqueue(self);
+
+    loop {
+      // registered before checking the budget, so that no start in between is missed
+      let started = budget.started.notified();
+      let next_tick =
+        budget
+          .state
+          .lock()
+          .unwrap()
+          .try_start(self.id, Instant::now(), budget.per_tick);
+
+      match next_tick {
+        Ok(()) => return,
+        Err(next_tick) => tokio::select! {
+          _ = started => {}
+          _ = tokio::time::sleep_until(next_tick) => {}
+        },
+      }
+    }
+  }
+}
+
+/// Keeps a controller in line for the budget, until dropped.
+struct Waiting<'a>(&'a BudgetShare);
+
+impl<'a> Waiting<'a> {
+  fn enqueue(share: &'a BudgetShare) -> Self {
+    share.budget.state.lock().unwrap().enqueue(share.id);
+    Self(share)
+  }
+}
+
+impl Drop for Waiting<'_> {
+  fn drop(&mut self) {
+    let budget = &self.0.budget;
+    budget.state.lock().unwrap().dequeue(self.0.id);
+
+    // whoever is next in line may start now
+    budget.started.notify_waiters();
+  }
+}
+
+struct BudgetState {
+  tick: Instant,
+  left: u32,
+
+  /// Pass of the last share which started a reconcile. Shares which were idle catch up to it once
+  /// they wait again, so that they do not make up for the time they were idle.
+  pass: u64,
+  shares: Vec<ShareState>,
+}
+
+/// Controllers take turns by stride scheduling: the waiting share with the lowest pass goes next,
+/// and then advances its pass by its stride, which is shorter the higher its weight.
+struct ShareState {
+  stride: u64,
+  pass: u64,
+  waiting: usize,
+}
+
+impl BudgetState {
+  fn new(now: Instant, per_tick: u32) -> Self {
+    Self {
+      tick: now,
+      left: per_tick,
+      pass: 0,
+      shares: Vec::new(),
+    }
+  }
+
+  fn register(&mut self, weight: u32) -> usize {
+    self.shares.push(ShareState {
+      stride: STRIDE / u64::from(weight.max(1)),
+      pass: self.pass,
+      waiting: 0,
+    });
+
+    self.shares.len() - 1
+  }
+
+  fn enqueue(&mut self, id: usize) {
+    let share = &mut self.shares[id];
+    if share.waiting == 0 {
+      share.pass = share.pass.max(self.pass);
+    }
+
+    share.waiting += 1;
+  }
+
+  fn dequeue(&mut self, id: usize) {
+    self.shares[id].waiting -= 1;
+  }
+
+  /// The waiting share which is next in line.
+  fn next(&self) -> Option<usize> {
+    self
+      .shares
+      .iter()
+      .enumerate()
+      .filter(|(_, share)| share.waiting > 0)
+      .min_by_key(|(_, share)| share.pass)
+      .map(|(id, _)| id)
+  }
+
+  /// Starts a reconcile of share `id` if it is next in line and the current tick has budget left,
+  /// and otherwise returns when the next tick starts.
+  fn try_start(&mut self, id: usize, now: Instant, per_tick: u32) -> Result<(), Instant> {
+    if now >= self.tick + TICK {
+      self.tick = now;
+      self.left = per_tick;
+    }
+
+    if self.left == 0 || self.next() != Some(id) {
+      return Err(self.tick + TICK);
+    }
+
+    let share = &mut self.shares[id];
+    self.left -= 1;
+    self.pass = share.pass;
+    share.pass += share.stride;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn budget_is_shared_by_weight() {
+    let now = Instant::now();
+    let mut state = BudgetState::new(now, 10);
+    let light = state.register(1);
+    let heavy = state.register(4);
+    for _ in 0..10 {
+      state.enqueue(light);
+      state.enqueue(heavy);
+    }
+
+    let mut started = [0, 0];
+    while let Some(id) = state.next() {
+      if state.try_start(id, now, 10).is_err() {
+        break;
+      }
+
+      state.dequeue(id);
+      started[id] += 1;
+    }
+
+    assert_eq!(started, [2, 8]);
+    assert_eq!(state.try_start(light, now, 10), Err(now + TICK));
+
+    // the next tick brings a fresh budget
+    assert_eq!(state.try_start(light, now + TICK, 10), Ok(()));
+  }
+
+  #[test]
+  fn unused_budget_goes_to_others() {
+    let now = Instant::now();
+    let mut state = BudgetState::new(now, 4);
+    let idle = state.register(10);
+    let busy = state.register(1);
+    for _ in 0..4 {
+      state.enqueue(busy);
+      assert_eq!(state.try_start(busy, now, 4), Ok(()));
+      state.dequeue(busy);
+    }
+
+    // once waiting again, the idle controller goes first, without making up for being idle
+    state.enqueue(idle);
+    state.enqueue(busy);
+    let later = now + TICK;
+    assert_eq!(state.try_start(busy, later, 4), Err(later + TICK));
+    assert_eq!(state.try_start(idle, later, 4), Ok(()));
+    state.dequeue(idle);
+    assert_eq!(state.try_start(busy, later, 4), Ok(()));
+  }
+}
@@ -1,16 +1,141 @@
+mod output;
+
 use clap::{Parser, Subcommand};
+use eyre::WrapErr;
+use fluxcd_meta::{Condition, RECONCILE_REQUEST_ANNOTATION};
+use fluxcd_utils_cops::{
+  artifact::ArtifactStorage,
+  events::EventForwarder,
+  openapi::OpenApiSchemas,
+  policy::{PolicySet, POLICY_FILE_ENV},
+  rbac::READ,
+  requirements::KubeVersion,
+};
+use fluxcd_utils_kube::{ConfigMapResolver, SecretResolver};
+use fluxcd_utils_telemetry::{LogConfig, LogFormat, TracingConfig};
+use futures::{
+  future::{self, Either},
+  Future, FutureExt, StreamExt,
+};
+use k8s_openapi::{
+  apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition,
+  apimachinery::pkg::apis::meta::v1::Time,
+  chrono::{SecondsFormat, Utc},
+};
+use kube::{
+  api::{ListParams, Patch, PatchParams, PostParams},
+  core::DynamicObject,
+  runtime::events::Reporter,
+  Api, Client, CustomResourceExt, ResourceExt,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+  fs, io,
+  net::SocketAddr,
+  num::{NonZeroU32, NonZeroUsize},
+  path::{Path, PathBuf},
+  sync::Arc,
+  time::Duration,
+};
+use tokio::time;
+use tracing::{debug, info, warn};
+
+use output::{age, due, Column, OutputFormat, Table};
 
-use crate::DynController;
+use crate::{
+  budget::ReconcileBudget,
+  bundle::{self, BundleOptions},
+  client::{ApiClients, DEFAULT_SLOW_REQUEST_THRESHOLD},
+  debug::{DebugServer, SCHEDULE_PATH},
+  discovery::{Discovery, DEFAULT_DISCOVERY_TIMEOUT},
+  gc::{ArtifactCollector, ArtifactReferences, GcSettings},
+  health::{self, ControllerStatus, REPORT_INTERVAL},
+  install::{self, ApplyOptions},
+  leader::{self, LeaderElection},
+  manifests::{self, ExportOptions},
+  migrate,
+  namespaces::NamespaceCache,
+  probe::{AppInfo, ProbeServer},
+  pushgateway::{MetricsPusher, DEFAULT_PUSH_INTERVAL},
+  replay::EventLog,
+  roles,
+  schedule::ScheduledReconcile,
+  scrape::{AppMetrics, MetricsServer},
+  shutdown::{InFlight, Phase, ShutdownCoordinator, DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT},
+  signals::{self, Signal},
+  sink::{EventSink, SinkTarget, DEFAULT_SINK_BUFFER},
+  storage::{self, StorageServer},
+  tasks::TaskGroup,
+  uninstall::{self, UninstallOptions},
+  watch::WatchSettings,
+  ControllerEnv, DynController, DynService,
+};
 
 #[derive(Parser)]
 struct Cli {
+  /// OTLP/HTTP endpoint to export traces to, e.g. `http://otel-collector:4318/v1/traces`. Traces
+  /// are sent to the local Jaeger agent otherwise
+  #[clap(long, global = true, env = "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT")]
+  otlp_endpoint: Option<String>,
+
+  /// Headers to export traces with, like credentials of the collector, as `key=value` pairs
+  /// separated by commas
+  #[clap(long, global = true, env = "OTEL_EXPORTER_OTLP_HEADERS")]
+  otlp_headers: Option<String>,
+
+  /// Fraction of the traces started by the app to export, from 0 to 1. Traces continued from a
+  /// sampled parent are always exported
+  #[clap(
+    long,
+    global = true,
+    env = "OTEL_TRACES_SAMPLER_ARG",
+    default_value = "1"
+  )]
+  trace_sample_ratio: f64,
+
+  /// Format of the logs, `json` for a JSON object per line carrying the kind, namespace and name
+  /// of the object being reconciled and the ID of the reconcile
+  #[clap(
+    long,
+    global = true,
+    env = "LOG_FORMAT",
+    default_value = "console",
+    possible_values = ["console", "json"]
+  )]
+  log_format: LogFormat,
+
+  /// Logs to write, like `info` or `info,kube=warn`. Taken from `RUST_LOG` if not given
+  #[clap(long, global = true, env = "LOG_LEVEL")]
+  log_level: Option<String>,
+
   #[clap(subcommand)]
   command: Command,
 }
 
 impl Cli {
-  async fn run(self, controllers: Vec<DynController<'_>>) -> eyre::Result<()> {
-    self.command.run(controllers).await
+  async fn run(
+    self,
+    name: &str,
+    version: &str,
+    controllers: Vec<DynController<'_>>,
+    services: Vec<DynService<'_>>,
+  ) -> eyre::Result<()> {
+    let tracing = TracingConfig {
+      otlp_endpoint: self.otlp_endpoint,
+      otlp_headers: match &self.otlp_headers {
+        Some(headers) => fluxcd_utils_telemetry::parse_headers(headers)?,
+        None => Default::default(),
+      },
+      sample_ratio: self.trace_sample_ratio,
+    };
+    let logs = LogConfig {
+      format: self.log_format,
+      level: self.log_level,
+    };
+    fluxcd_utils_telemetry::setup(name, &tracing, &logs)?;
+
+    self.command.run(name, version, controllers, services).await
   }
 }
 
@@ -29,27 +154,344 @@ pub enum Command {
     #[clap(subcommand)]
     command: Option<CrdCommand>,
   },
+
+  /// Check that the cluster meets the requirements of all controllers
+  Check,
+
+  /// Print the RBAC roles the app needs to run its controllers, as a ClusterRole named after the
+  /// app. Objects only written by services, like those receivers request reconciles of, are not
+  /// included and must be granted separately
+  Rbac {
+    /// Grant the permissions on namespaced resources through a Role in this namespace instead,
+    /// for apps run with `--watch-namespace`
+    #[clap(short, long)]
+    namespace: Option<String>,
+  },
+
+  /// List the objects of a kind, with their readiness
+  Get {
+    /// Name or full path of the kind of objects to list
+    kind: String,
+
+    /// Label selector the objects must match, e.g. `app=foo`
+    #[clap(short = 'l', long)]
+    selector: Option<String>,
+
+    /// Only list objects in this namespace
+    #[clap(short, long)]
+    namespace: Option<String>,
+
+    /// Output format
+    #[clap(short, long, arg_enum, default_value = "table")]
+    output: OutputFormat,
+  },
+
+  /// List the ControllerStatus objects reported by running controllers
+  Status {
+    /// Output format
+    #[clap(short, long, arg_enum, default_value = "table")]
+    output: OutputFormat,
+  },
+
+  /// List the upcoming reconciles of a running app, with when and why each of them is due
+  Schedule {
+    /// Address the app serves its debugging endpoints at, as given to `run --debug-addr`
+    #[clap(long, default_value = "127.0.0.1:9091")]
+    addr: String,
+
+    /// Only list objects of this kind, by name or full path
+    #[clap(long)]
+    kind: Option<String>,
+
+    /// Output format
+    #[clap(short, long, arg_enum, default_value = "table")]
+    output: OutputFormat,
+  },
+
+  /// Gather the state of the app and of its objects into an archive to attach to bug reports:
+  /// the versions of the app, the cluster and the CRDs, the status of all objects, the metrics
+  /// and the recent logs of the app. Secret material, like passwords, tokens and private keys, is
+  /// redacted
+  SupportBundle {
+    /// File to write the archive to, as a gzipped tarball
+    #[clap(short, long, default_value = "support-bundle.tar.gz")]
+    output: PathBuf,
+
+    /// Address the app serves its metrics at, as given to `run --metrics-addr`
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    metrics_addr: String,
+
+    /// Label selector of the pods of the app, e.g. `app=source-controller`, to include their
+    /// recent logs
+    #[clap(long)]
+    pod_selector: Option<String>,
+
+    /// Namespace of the pods of the app. Defaults to the namespace of the current context
+    #[clap(short, long, requires = "pod_selector")]
+    namespace: Option<String>,
+
+    /// Number of recent log lines to include per pod
+    #[clap(long, default_value = "1000")]
+    log_lines: i64,
+  },
+
+  /// Install the app in the cluster: its namespace, the CRDs, a ServiceAccount with the roles
+  /// printed by `rbac`, a Deployment running the controllers, and a Service exposing their metrics
+  Install {
+    /// Print the manifests to stdout instead, to apply them with `kubectl apply -f -` or commit
+    /// them to a repository. Installing directly is not supported yet, so this is required
+    #[clap(long)]
+    export: bool,
+
+    /// Namespace to install the app in
+    #[clap(short, long, default_value = "flux-system")]
+    namespace: String,
+
+    /// Image of the app. Defaults to that of this version of the app, from ghcr.io/yolodev
+    #[clap(long)]
+    image: Option<String>,
+  },
+
+  /// Remove the project from the cluster: the finalizers of the project are removed from all
+  /// objects, which are then deleted along with the objects generated from them, and finally the
+  /// CRDs are deleted. Stop the controllers first, so that they do not recreate what is removed
+  Uninstall {
+    /// Keep the objects generated from the objects of the project, like Secrets and ConfigMaps
+    #[clap(long)]
+    keep_children: bool,
+
+    /// Only print what would be removed, without removing it
+    #[clap(long)]
+    dry_run: bool,
+  },
+
+  /// Run the controllers
+  Run {
+    /// Address to serve the metrics of the controllers at, for Prometheus to scrape
+    #[clap(long, default_value = "0.0.0.0:8080")]
+    metrics_addr: SocketAddr,
+
+    /// Also push the metrics to this Prometheus Pushgateway, e.g. `http://pushgateway:9091`, for
+    /// clusters Prometheus cannot scrape. The metrics are grouped by the name of the app and the
+    /// pod. Credentials are read from METRICS_PUSH_USERNAME and METRICS_PUSH_PASSWORD, or from
+    /// METRICS_PUSH_TOKEN
+    #[clap(long, env = "METRICS_PUSH_URL")]
+    metrics_push_url: Option<String>,
+
+    /// Seconds between pushes of the metrics. Defaults to 30
+    #[clap(long, requires = "metrics_push_url")]
+    metrics_push_interval: Option<u64>,
+
+    /// Address to serve the `/healthz` and `/readyz` probes at, along with `/info`, which
+    /// describes the app and whether the API server serves the groups of its controllers
+    #[clap(long, env = "PROBE_ADDR", default_value = "0.0.0.0:8081")]
+    probe_addr: SocketAddr,
+
+    /// Seconds to wait at startup for the API server to serve the groups of the controllers,
+    /// before giving up. Defaults to 30
+    #[clap(long)]
+    discovery_timeout: Option<u64>,
+
+    /// Maintain a cluster-scoped ControllerStatus object reporting the health of the controllers
+    #[clap(long)]
+    report_status: bool,
+
+    /// Write every reconcile, with the resource as it was watched and how reconciling it ended,
+    /// to this file as json lines. The recording can be fed back through a controller with
+    /// `fluxcd_utils_cap::replay`.
+    #[clap(long)]
+    record_events: Option<PathBuf>,
+
+    /// Directory to keep the artifacts produced by the controllers in, which are served over
+    /// HTTP. Controllers only produce artifacts when this is set.
+    #[clap(long)]
+    storage_path: Option<PathBuf>,
+
+    /// Address to serve the artifacts at
+    #[clap(long, default_value = "0.0.0.0:9090")]
+    storage_addr: SocketAddr,
+
+    /// Address the artifacts are advertised at in the status of objects, e.g. the address of a
+    /// Service in front of the controller. Defaults to the host name of the pod, with the port of
+    /// --storage-addr. The artifacts of an object listed at `<kind>/<namespace>/<name>/` are at
+    /// the host the listing was requested at instead
+    #[clap(long)]
+    storage_adv_addr: Option<String>,
+
+    /// Seconds between garbage collections of the artifact storage, which remove the artifacts
+    /// that the status of no object references anymore. Defaults to 600
+    #[clap(long, requires = "storage_path")]
+    artifact_gc_interval: Option<u64>,
+
+    /// Seconds an artifact is kept for once no object references it anymore, so that consumers
+    /// can still download it. Defaults to 60
+    #[clap(long, requires = "storage_path")]
+    artifact_retention_ttl: Option<u64>,
+
+    /// Maximum number of artifacts kept for each object, including the one it references.
+    /// Defaults to 2
+    #[clap(long, requires = "storage_path")]
+    artifact_retention_records: Option<usize>,
+
+    /// Maximum number of reconciles to start per second, across all controllers, to protect the
+    /// API server and external providers when many objects change at once. While several
+    /// controllers are waiting, the budget is shared out by their weights. Unlimited by default
+    #[clap(long)]
+    reconcile_budget: Option<NonZeroU32>,
+
+    /// Only watch and reconcile objects in this namespace. Cluster-scoped objects are still
+    /// reconciled across the cluster
+    #[clap(long)]
+    watch_namespace: Option<String>,
+
+    /// Only watch and reconcile objects matching this label selector, e.g. `shard=a`, so that
+    /// several instances of the app can split the objects between them
+    #[clap(long)]
+    watch_label_selector: Option<String>,
+
+    /// Server-side timeout of the watches of the controllers, in seconds, after which they are
+    /// resumed from the last resource version they saw. At most 294, defaults to 290
+    #[clap(long)]
+    watch_timeout: Option<u64>,
+
+    /// Do not ask the API server for watch bookmarks. Without them, watches resumed after a quiet
+    /// period are more likely to have expired, making the controllers list all of their objects
+    /// again, as counted by gotk_watch_relists_total
+    #[clap(long)]
+    no_watch_bookmarks: bool,
+
+    /// Maximum number of objects each controller reconciles at once. Unlimited by default
+    #[clap(long)]
+    concurrent: Option<NonZeroUsize>,
+
+    /// Log requests to the Kubernetes API taking longer than this many milliseconds, with their
+    /// verb and resource. Defaults to 1000, 0 disables the logging
+    #[clap(long)]
+    slow_request_threshold: Option<u64>,
+
+    /// Address to serve debugging endpoints at, like the schedule listed by the `schedule`
+    /// command, e.g. 127.0.0.1:9091. They reveal which objects the controllers manage, so they
+    /// are not served by default
+    #[clap(long)]
+    debug_addr: Option<SocketAddr>,
+
+    /// Only run the controllers while this instance is the elected leader, so that several
+    /// replicas can run without reconciling the same objects. The leader is elected through a
+    /// Lease in the namespace of the app
+    #[clap(long)]
+    leader_elect: bool,
+
+    /// Name of the Lease the leader is elected through. Defaults to the name of the app
+    #[clap(long, requires = "leader_elect")]
+    leader_election_id: Option<String>,
+
+    /// Fetch and compute the status of objects as usual, but skip creating, updating or deleting
+    /// any other objects. Skipped writes are reported as events, and in the ReadOnly condition of
+    /// the objects, so that an app can be staged before it is granted write access
+    #[clap(long)]
+    read_only: bool,
+
+    /// Reject the references of objects to objects in other namespaces, like a secretRef naming
+    /// the namespace of another tenant. References of cluster-scoped objects are still allowed
+    #[clap(long, env = "NO_CROSS_NAMESPACE_REFS")]
+    no_cross_namespace_refs: bool,
+
+    /// Address of an events receiver, like the notification-controller of Flux, to forward the
+    /// events of objects to, on top of recording them as Kubernetes Events
+    #[clap(long, env = "EVENTS_ADDR")]
+    events_addr: Option<String>,
+
+    /// Deliver every reconcile to this sink, to follow the activity of the controllers in an
+    /// external system: a webhook (`https://...`), which the reconciles are posted to as json, or
+    /// a NATS subject (`nats://<host>[:<port>]/<subject>`). Reconciles are buffered while the
+    /// sink is unavailable, and delivered with retries
+    #[clap(long, env = "EVENT_SINK")]
+    event_sink: Option<String>,
+
+    /// Maximum number of reconciles buffered for the sink, further ones are dropped. Defaults to
+    /// 1024
+    #[clap(long, requires = "event_sink")]
+    event_sink_buffer: Option<NonZeroUsize>,
+
+    /// Seconds to wait for the in-flight reconciles to drain and the app to stop once it is
+    /// signalled to, before exiting with a non-zero code anyway. Defaults to 25, below the grace
+    /// period Kubernetes gives pods before killing them
+    #[clap(long)]
+    graceful_shutdown_timeout: Option<u64>,
+  },
+
+  /// Request an immediate reconcile of all matching objects
+  Reconcile {
+    /// Name or full path of the kind of objects to reconcile
+    #[clap(long)]
+    kind: String,
+
+    /// Label selector the objects must match, e.g. `app=foo`
+    #[clap(short = 'l', long)]
+    selector: Option<String>,
+
+    /// Only reconcile objects in this namespace
+    #[clap(short, long)]
+    namespace: Option<String>,
+
+    /// Maximum number of objects to annotate per second
+    #[clap(long, default_value = "10")]
+    rate: u32,
+  },
+
+  /// Convert upstream Flux manifests to the equivalent kinds of this project
+  #[clap(args_conflicts_with_subcommands = true)]
+  Migrate {
+    /// File containing the manifests, or `-` to read from stdin
+    #[clap(default_value = "-")]
+    file: PathBuf,
+
+    #[clap(subcommand)]
+    command: Option<MigrateCommand>,
+  },
 }
 
 impl Command {
-  async fn run(self, controllers: Vec<DynController<'_>>) -> eyre::Result<()> {
+  async fn run(
+    self,
+    name: &str,
+    version: &str,
+    controllers: Vec<DynController<'_>>,
+    services: Vec<DynService<'_>>,
+  ) -> eyre::Result<()> {
     match self {
-      Command::Crd { all: true, .. } => todo!(),
+      Command::Crd { all: true, .. } => {
+        let crds = controllers
+          .into_iter()
+          .map(|c| c.crd())
+          .chain(std::iter::once(ControllerStatus::crd()));
+
+        // every document starts with `---`, so the output can be applied as a single stream
+        for crd in crds {
+          print!("{}", serde_yaml::to_string(&crd)?);
+        }
+
+        Ok(())
+      }
+      Command::Crd {
+        name: Some(crd), ..
+      } if health::matches(&crd) => {
+        let crd = ControllerStatus::crd();
+        let yaml = serde_yaml::to_string(&crd)?;
+
+        println!("{yaml}");
+        Ok(())
+      }
       Command::Crd {
         name: Some(crd), ..
       } => {
-        let crd = controllers.into_iter().find(|c| {
-          let info = &c.info;
-          let group = &*info.group;
-          let kind = &*info.kind;
-          kind == crd || format!("{group}/{kind}") == crd
-        });
+        let ctrl = controllers.into_iter().find(|c| c.info.matches(&crd));
 
-        match crd {
-          None => todo!("not found error message"),
+        match ctrl {
+          None => eyre::bail!("no CRD named '{crd}', see `crd list`"),
           Some(c) => {
             let crd = c.crd();
-            let yaml = serde_yaml::to_string(&crd).unwrap();
+            let yaml = serde_yaml::to_string(&crd)?;
 
             println!("{yaml}");
             Ok(())
@@ -58,8 +500,157 @@ impl Command {
       }
       Command::Crd {
         command: Some(cmd), ..
+      } => cmd.run(name, controllers).await,
+      Command::Check => check(controllers).await,
+      Command::Rbac { namespace } => roles::print_roles(name, &controllers, namespace.as_deref()),
+      Command::Get {
+        kind,
+        selector,
+        namespace,
+        output,
+      } => {
+        let ctrl = controllers.into_iter().find(|c| c.info.matches(&kind));
+        match ctrl {
+          None => eyre::bail!("no controller for kind '{kind}'"),
+          Some(c) => get(c, selector, namespace, output).await,
+        }
+      }
+      Command::Status { output } => status(output).await,
+      Command::Schedule { addr, kind, output } => schedule(&addr, kind.as_deref(), output).await,
+      Command::SupportBundle {
+        output,
+        metrics_addr,
+        pod_selector,
+        namespace,
+        log_lines,
+      } => {
+        let options = BundleOptions {
+          output,
+          metrics_addr,
+          pod_selector,
+          namespace,
+          log_lines,
+        };
+        bundle::support_bundle(name, version, controllers, options).await
+      }
+      Command::Install {
+        export,
+        namespace,
+        image,
+      } => {
+        if !export {
+          eyre::bail!("installing directly is not supported yet, use `install --export`");
+        }
+
+        let options = ExportOptions { namespace, image };
+        print!(
+          "{}",
+          manifests::export(name, version, controllers, &options)?
+        );
+        Ok(())
+      }
+      Command::Uninstall {
+        keep_children,
+        dry_run,
+      } => {
+        let options = UninstallOptions {
+          keep_children,
+          dry_run,
+        };
+        uninstall::uninstall(controllers, options).await
+      }
+      Command::Run {
+        metrics_addr,
+        metrics_push_url,
+        metrics_push_interval,
+        probe_addr,
+        discovery_timeout,
+        report_status,
+        record_events,
+        storage_path,
+        storage_addr,
+        storage_adv_addr,
+        artifact_gc_interval,
+        artifact_retention_ttl,
+        artifact_retention_records,
+        reconcile_budget,
+        watch_namespace,
+        watch_label_selector,
+        watch_timeout,
+        no_watch_bookmarks,
+        concurrent,
+        slow_request_threshold,
+        debug_addr,
+        leader_elect,
+        leader_election_id,
+        read_only,
+        no_cross_namespace_refs,
+        events_addr,
+        event_sink,
+        event_sink_buffer,
+        graceful_shutdown_timeout,
+      } => {
+        let storage = storage_path.map(|path| {
+          let advertised = storage::advertised_addr(storage_addr, storage_adv_addr.as_deref());
+          StorageServer {
+            storage: Arc::new(ArtifactStorage::new(path, &advertised)),
+            addr: storage_addr,
+            gc: GcSettings {
+              interval: artifact_gc_interval.map(Duration::from_secs),
+              ttl: artifact_retention_ttl.map(Duration::from_secs),
+              records: artifact_retention_records,
+            },
+          }
+        });
+
+        let options = RunOptions {
+          metrics_addr: Some(metrics_addr),
+          metrics_push_url,
+          metrics_push_interval: metrics_push_interval.map(Duration::from_secs),
+          probe_addr: Some(probe_addr),
+          discovery_timeout: discovery_timeout.map(Duration::from_secs),
+          report_status,
+          record_events,
+          storage,
+          budget: reconcile_budget.map(ReconcileBudget::new),
+          watch_namespace,
+          watch_label_selector,
+          watch_settings: WatchSettings {
+            timeout: watch_timeout.map(Duration::from_secs),
+            no_bookmarks: no_watch_bookmarks,
+          },
+          concurrency: concurrent,
+          slow_request_threshold: slow_request_threshold.map(Duration::from_millis),
+          debug_addr,
+          leader_election: leader_elect.then(|| leader_election_id.unwrap_or_else(|| name.into())),
+          read_only,
+          no_cross_namespace_refs,
+          events_addr,
+          event_sink,
+          event_sink_buffer,
+          graceful_shutdown_timeout: graceful_shutdown_timeout.map(Duration::from_secs),
+        };
+
+        let signal = Signal::shared()?;
+        run_controllers(name, version, options, signal, controllers, services).await
+      }
+      Command::Reconcile {
+        kind,
+        selector,
+        namespace,
+        rate,
+      } => {
+        let ctrl = controllers.into_iter().find(|c| c.info.matches(&kind));
+        match ctrl {
+          None => eyre::bail!("no controller for kind '{kind}'"),
+          Some(c) => request_reconcile(c, selector, namespace, rate).await,
+        }
+      }
+      Command::Migrate {
+        command: Some(cmd), ..
       } => cmd.run(controllers).await,
-      _ => todo!("{:?}", self),
+      Command::Migrate { file, .. } => migrate_manifests(controllers, &file),
+      Command::Crd { .. } => eyre::bail!("name a CRD or pass --all, see `crd list`"),
     }
   }
 }
@@ -67,29 +658,959 @@ impl Command {
 #[derive(Subcommand, Debug)]
 pub enum CrdCommand {
   /// List all CRDs
-  List,
+  List {
+    /// Output format
+    #[clap(short, long, arg_enum, default_value = "table")]
+    output: OutputFormat,
+  },
+
+  /// Install or upgrade all CRDs in the cluster, printing how each of them changes, and wait for
+  /// them to be established
+  Apply {
+    /// Only print the changes, without applying them
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Seconds to wait for the CRDs to be established
+    #[clap(long, default_value = "60")]
+    timeout: u64,
+  },
 }
 
 impl CrdCommand {
+  async fn run(self, name: &str, controllers: Vec<DynController<'_>>) -> eyre::Result<()> {
+    match self {
+      CrdCommand::Apply { dry_run, timeout } => {
+        let crds = controllers
+          .into_iter()
+          .map(|c| c.crd())
+          .chain(std::iter::once(ControllerStatus::crd()))
+          .collect();
+        let options = ApplyOptions {
+          field_manager: name.into(),
+          dry_run,
+          timeout: Duration::from_secs(timeout),
+        };
+        install::apply(crds, options).await
+      }
+      CrdCommand::List { output } => {
+        let mut table = Table::new([
+          Column::new("VERSION"),
+          Column::new("SCOPE"),
+          Column::new("CRD").wide(),
+        ]);
+
+        let crds = controllers
+          .into_iter()
+          .map(|c| c.crd())
+          .chain(std::iter::once(ControllerStatus::crd()));
+        for crd in crds {
+          let name = format!("{}/{}", crd.spec.group, crd.spec.names.kind);
+          let version = crd
+            .spec
+            .versions
+            .iter()
+            .find(|v| v.storage)
+            .map(|v| v.name.clone())
+            .unwrap_or_default();
+          let cells = vec![version, crd.spec.scope.clone(), crd.name()];
+          table.push(name, cells, &crd)?;
+        }
+
+        table.print(output)
+      }
+    }
+  }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MigrateCommand {
+  /// Rewrite all stored objects of a kind in the storage version of its CRD, so that the versions
+  /// it no longer serves can be dropped
+  StorageVersion {
+    /// Name or full path of the kind of objects to rewrite
+    kind: String,
+  },
+}
+
+impl MigrateCommand {
   async fn run(self, controllers: Vec<DynController<'_>>) -> eyre::Result<()> {
     match self {
-      CrdCommand::List => {
-        for ctrl in controllers {
-          let info = ctrl.info;
-          let group = info.group;
-          let kind = info.kind;
-          println!("{group}/{kind}");
+      MigrateCommand::StorageVersion { kind } => {
+        let ctrl = controllers.into_iter().find(|c| c.info.matches(&kind));
+        match ctrl {
+          None => eyre::bail!("no controller for kind '{kind}'"),
+          Some(c) => migrate_storage_version(c).await,
+        }
+      }
+    }
+  }
+}
+
+async fn check(controllers: Vec<DynController<'_>>) -> eyre::Result<()> {
+  let client = Client::try_default().await?;
+  let version = KubeVersion::try_from(&client.apiserver_version().await?)?;
+  let mut served = client.list_core_api_versions().await?.versions;
+  for group in client.list_api_groups().await?.groups {
+    served.extend(group.versions.into_iter().map(|v| v.group_version));
+  }
+
+  let mut satisfied = true;
+  for ctrl in controllers {
+    let info = ctrl.info;
+    let group = info.group;
+    let kind = info.kind;
+    let mut unmet = ctrl.requirements.check(version, &served).peekable();
+
+    if unmet.peek().is_none() {
+      println!("{group}/{kind}: ok");
+    }
+
+    for requirement in unmet {
+      satisfied = false;
+      println!("{group}/{kind}: {requirement}");
+    }
+  }
+
+  if !satisfied {
+    eyre::bail!(
+      "the cluster (kubernetes {version}) does not meet the requirements of all controllers"
+    );
+  }
+
+  Ok(())
+}
+
+async fn request_reconcile(
+  ctrl: DynController<'_>,
+  selector: Option<String>,
+  namespace: Option<String>,
+  rate: u32,
+) -> eyre::Result<()> {
+  if namespace.is_some() && !ctrl.info.namespaced {
+    eyre::bail!(
+      "{} is cluster-scoped, it can not be filtered by namespace",
+      ctrl.info.kind
+    );
+  }
+
+  let client = Client::try_default().await?;
+  let resource = ctrl.info.api_resource();
+  let api = |namespace: Option<&str>| match namespace {
+    Some(namespace) => Api::<DynamicObject>::namespaced_with(client.clone(), namespace, &resource),
+    None => Api::<DynamicObject>::all_with(client.clone(), &resource),
+  };
+
+  let mut params = ListParams::default();
+  if let Some(selector) = &selector {
+    params = params.labels(selector);
+  }
+
+  let objects = api(namespace.as_deref()).list(&params).await?;
+  let token = Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true);
+  let patch = json!({
+    "metadata": {
+      "annotations": {
+        RECONCILE_REQUEST_ANNOTATION: token,
+      }
+    }
+  });
+
+  // spread the patches out, to avoid flooding the api server and the controller with work
+  let mut ticks = time::interval(Duration::from_secs(1) / rate.max(1));
+  for obj in objects {
+    ticks.tick().await;
+    let name = obj.name();
+    api(obj.namespace().as_deref())
+      .patch(&name, &PatchParams::default(), &Patch::Merge(&patch))
+      .await?;
+    println!("{}/{name}: reconcile requested", resource.kind);
+  }
+
+  Ok(())
+}
+
+/// Rewrites every object of the kind of `ctrl` unchanged, which makes the API server store it in
+/// the storage version of the CRD, and then records that version as the only one in use.
+async fn migrate_storage_version(ctrl: DynController<'_>) -> eyre::Result<()> {
+  let client = Client::try_default().await?;
+  let info = ctrl.info;
+  let crds = Api::<CustomResourceDefinition>::all(client.clone());
+  let name = format!("{}.{}", info.plural, info.group);
+  let crd = crds.get(&name).await?;
+
+  let storage = match crd.spec.versions.iter().find(|v| v.storage) {
+    Some(version) => version.name.clone(),
+    None => eyre::bail!("{name} has no storage version"),
+  };
+  if storage != *info.version {
+    eyre::bail!(
+      "{name} is stored as {storage}, but the controller uses {}, apply its CRD first",
+      info.version
+    );
+  }
+
+  let stored = crd
+    .status
+    .and_then(|status| status.stored_versions)
+    .unwrap_or_default();
+  if matches!(stored.as_slice(), [version] if *version == storage) {
+    println!("{name}: all objects are stored as {storage}");
+    return Ok(());
+  }
+
+  let resource = info.api_resource();
+  let api = |namespace: Option<&str>| match namespace {
+    Some(namespace) => Api::<DynamicObject>::namespaced_with(client.clone(), namespace, &resource),
+    None => Api::<DynamicObject>::all_with(client.clone(), &resource),
+  };
+
+  let mut failed = 0;
+  for obj in api(None).list(&ListParams::default()).await? {
+    let name = obj.name();
+    match api(obj.namespace().as_deref())
+      .replace(&name, &PostParams::default(), &obj)
+      .await
+    {
+      Ok(_) => println!("{}/{name}: stored as {storage}", resource.kind),
+      // written since it was listed, which stored it in the storage version as well
+      Err(kube::Error::Api(e)) if e.code == 409 => {
+        println!("{}/{name}: stored as {storage}", resource.kind)
+      }
+      // deleted since it was listed
+      Err(kube::Error::Api(e)) if e.code == 404 => (),
+      Err(e) => {
+        failed += 1;
+        eprintln!("{}/{name}: {e}", resource.kind);
+      }
+    }
+  }
+
+  if failed > 0 {
+    eyre::bail!("{failed} object(s) could not be rewritten, the stored versions are unchanged");
+  }
+
+  let patch = json!({
+    "status": {
+      "storedVersions": [storage],
+    }
+  });
+  crds
+    .patch_status(&name, &PatchParams::default(), &Patch::Merge(&patch))
+    .await?;
+  println!("{name}: all objects are stored as {storage}");
+
+  Ok(())
+}
+
+/// The `status` and `message` of the `Ready` condition of `obj`, if it has one.
+fn ready(obj: &DynamicObject) -> (String, String) {
+  let ready = Condition::Ready.to_string();
+  let condition = obj
+    .data
+    .pointer("/status/conditions")
+    .and_then(|c| c.as_array())
+    .into_iter()
+    .flatten()
+    .find(|c| c.get("type").and_then(|t| t.as_str()) == Some(&ready));
+
+  let field = |name: &str| {
+    condition
+      .and_then(|c| c.get(name))
+      .and_then(|v| v.as_str())
+      .map(String::from)
+  };
+
+  (
+    field("status").unwrap_or_else(|| "Unknown".into()),
+    field("message").unwrap_or_default(),
+  )
+}
+
+async fn get(
+  ctrl: DynController<'_>,
+  selector: Option<String>,
+  namespace: Option<String>,
+  output: OutputFormat,
+) -> eyre::Result<()> {
+  if namespace.is_some() && !ctrl.info.namespaced {
+    eyre::bail!(
+      "{} is cluster-scoped, it can not be filtered by namespace",
+      ctrl.info.kind
+    );
+  }
+
+  let client = Client::try_default().await?;
+  let resource = ctrl.info.api_resource();
+  let api = match &namespace {
+    Some(namespace) => Api::<DynamicObject>::namespaced_with(client, namespace, &resource),
+    None => Api::<DynamicObject>::all_with(client, &resource),
+  };
+
+  let mut params = ListParams::default();
+  if let Some(selector) = &selector {
+    params = params.labels(selector);
+  }
+
+  // objects of all namespaces are listed unless one is given, so show where each of them lives
+  let all_namespaces = ctrl.info.namespaced && namespace.is_none();
+  let mut columns = Vec::new();
+  if all_namespaces {
+    columns.push(Column::new("NAMESPACE"));
+  }
+
+  columns.extend([
+    Column::new("READY").status(),
+    Column::new("STATUS"),
+    Column::new("AGE"),
+    Column::new("SUSPENDED").wide(),
+    Column::new("NEXT RECONCILE").wide(),
+  ]);
+
+  let mut table = Table::new(columns);
+  for obj in api.list(&params).await? {
+    let (ready, message) = ready(&obj);
+    let suspended = obj
+      .data
+      .pointer("/spec/suspend")
+      .and_then(|s| s.as_bool())
+      .unwrap_or_default();
+    let next_reconcile_at = obj
+      .data
+      .pointer("/status/nextReconcileAt")
+      .and_then(|t| t.as_str())
+      .unwrap_or_default();
+
+    let mut cells = Vec::new();
+    if all_namespaces {
+      cells.push(obj.namespace().unwrap_or_default());
+    }
+
+    cells.extend([
+      ready,
+      message,
+      age(obj.metadata.creation_timestamp.as_ref()),
+      suspended.to_string(),
+      next_reconcile_at.into(),
+    ]);
+    table.push(obj.name(), cells, &obj)?;
+  }
+
+  table.print(output)
+}
+
+async fn status(output: OutputFormat) -> eyre::Result<()> {
+  let client = Client::try_default().await?;
+  let api = Api::<ControllerStatus>::all(client);
+
+  let mut table = Table::new([
+    Column::new("READY").status(),
+    Column::new("VERSION"),
+    Column::new("LEADER"),
+    Column::new("LAST UPDATE"),
+    Column::new("OBJECTS").wide(),
+    Column::new("FAILURES").wide(),
+  ]);
+
+  for obj in api.list(&ListParams::default()).await? {
+    let status = obj.status.clone().unwrap_or_default();
+    let last_update = status.last_update_time.as_ref();
+
+    // a running controller updates its status every REPORT_INTERVAL, so a status which has not
+    // been updated for twice as long means the controller is gone or stuck
+    let ready = match last_update {
+      None => "Unknown",
+      Some(Time(time)) => {
+        let fresh = k8s_openapi::chrono::Duration::from_std(2 * REPORT_INTERVAL)
+          .map(|interval| Utc::now() - *time < interval)
+          .unwrap_or_default();
+        if fresh {
+          "True"
+        } else {
+          "False"
         }
-        Ok(())
       }
+    };
+
+    let objects = status.kinds.iter().map(|k| k.objects).sum::<u64>();
+    let failures = status.kinds.iter().map(|k| k.failures).sum::<u64>();
+    let cells = vec![
+      ready.into(),
+      status.version.clone(),
+      status.leader.clone().unwrap_or_default(),
+      age(last_update),
+      objects.to_string(),
+      failures.to_string(),
+    ];
+    table.push(obj.name(), cells, &obj)?;
+  }
+
+  table.print(output)
+}
+
+/// Lists the upcoming reconciles of the app serving its debugging endpoints at `addr`.
+async fn schedule(addr: &str, kind: Option<&str>, output: OutputFormat) -> eyre::Result<()> {
+  let uri = format!("http://{addr}{SCHEDULE_PATH}").parse::<hyper::Uri>()?;
+  let response = hyper::Client::new().get(uri).await?;
+  let code = response.status();
+  let body = hyper::body::to_bytes(response.into_body()).await?;
+  if !code.is_success() {
+    eyre::bail!("{addr} answered {code}: {}", String::from_utf8_lossy(&body));
+  }
+
+  let upcoming: Vec<ScheduledReconcile> = serde_json::from_slice(&body)?;
+  let mut table = Table::new([
+    Column::new("KIND"),
+    Column::new("NAMESPACE"),
+    Column::new("REASON"),
+    Column::new("DUE"),
+    Column::new("DUE AT").wide(),
+  ]);
+
+  // kinds are listed by their full path, but can be filtered by name as well
+  let matches = |scheduled: &ScheduledReconcile| {
+    kind.is_none_or(|kind| {
+      scheduled.kind == kind || scheduled.kind.rsplit_once('/').map(|(_, name)| name) == Some(kind)
+    })
+  };
+
+  for scheduled in upcoming.iter().filter(|scheduled| matches(scheduled)) {
+    let cells = vec![
+      scheduled.kind.clone(),
+      scheduled.namespace.clone().unwrap_or_default(),
+      scheduled.reason.as_str().into(),
+      due(&scheduled.due),
+      scheduled.due.0.to_rfc3339_opts(SecondsFormat::Secs, true),
+    ];
+    table.push(&scheduled.name, cells, scheduled)?;
+  }
+
+  table.print(output)
+}
+
+fn migrate_manifests(controllers: Vec<DynController<'_>>, file: &Path) -> eyre::Result<()> {
+  let input = if file == Path::new("-") {
+    io::read_to_string(io::stdin())?
+  } else {
+    fs::read_to_string(file)?
+  };
+
+  let crds = controllers.into_iter().map(|c| c.crd()).collect::<Vec<_>>();
+  let mut failed = 0;
+  for document in serde_yaml::Deserializer::from_str(&input) {
+    let upstream = serde_json::Value::deserialize(document)?;
+    if upstream.is_null() {
+      continue;
     }
+
+    let name = upstream
+      .pointer("/metadata/name")
+      .and_then(|n| n.as_str())
+      .unwrap_or("<unnamed>");
+
+    match migrate::migrate(&upstream, &crds) {
+      Ok(migration) => {
+        for field in &migration.unsupported {
+          eprintln!("{name}: dropped unsupported field {field}");
+        }
+
+        print!("{}", serde_yaml::to_string(&migration.object)?);
+      }
+      Err(e) => {
+        failed += 1;
+        eprintln!("{name}: {e}");
+      }
+    }
+  }
+
+  if failed > 0 {
+    eyre::bail!("{failed} manifest(s) could not be converted");
   }
+
+  Ok(())
+}
+
+/// How the controllers of an app are run, on top of running them.
+#[derive(Default)]
+pub(crate) struct RunOptions {
+  pub(crate) metrics_addr: Option<SocketAddr>,
+  pub(crate) metrics_push_url: Option<String>,
+  pub(crate) metrics_push_interval: Option<Duration>,
+  pub(crate) probe_addr: Option<SocketAddr>,
+  pub(crate) discovery_timeout: Option<Duration>,
+  pub(crate) report_status: bool,
+  pub(crate) record_events: Option<PathBuf>,
+  pub(crate) storage: Option<StorageServer>,
+  pub(crate) budget: Option<Arc<ReconcileBudget>>,
+  pub(crate) watch_namespace: Option<String>,
+  pub(crate) watch_label_selector: Option<String>,
+  pub(crate) watch_settings: WatchSettings,
+  pub(crate) concurrency: Option<NonZeroUsize>,
+  pub(crate) slow_request_threshold: Option<Duration>,
+  pub(crate) debug_addr: Option<SocketAddr>,
+  pub(crate) leader_election: Option<String>,
+  pub(crate) read_only: bool,
+  pub(crate) no_cross_namespace_refs: bool,
+  pub(crate) events_addr: Option<String>,
+  pub(crate) event_sink: Option<String>,
+  pub(crate) event_sink_buffer: Option<NonZeroUsize>,
+  pub(crate) graceful_shutdown_timeout: Option<Duration>,
+}
+
+/// Runs `controllers`, and the `services` next to them, until `signal` completes, and then shuts
+/// them down gracefully.
+pub(crate) async fn run_controllers(
+  name: &str,
+  version: &str,
+  options: RunOptions,
+  signal: impl Future<Output = ()>,
+  controllers: Vec<DynController<'_>>,
+  services: Vec<DynService<'_>>,
+) -> eyre::Result<()> {
+  let RunOptions {
+    metrics_addr,
+    metrics_push_url,
+    metrics_push_interval,
+    probe_addr,
+    discovery_timeout,
+    report_status,
+    record_events,
+    storage,
+    budget,
+    watch_namespace,
+    watch_label_selector,
+    watch_settings,
+    concurrency,
+    slow_request_threshold,
+    debug_addr,
+    leader_election,
+    read_only,
+    no_cross_namespace_refs,
+    events_addr,
+    event_sink,
+    event_sink_buffer,
+    graceful_shutdown_timeout,
+  } = options;
+  let record_events = record_events.as_deref();
+  let slow_request = slow_request_threshold.unwrap_or(DEFAULT_SLOW_REQUEST_THRESHOLD);
+  let clients = ApiClients::infer(slow_request).await?;
+  let client = clients.client(name)?;
+  let reporter = Reporter {
+    controller: name.into(),
+    instance: std::env::var("POD_NAME").ok(),
+  };
+  let mut shutdown = ShutdownCoordinator::new();
+  let in_flight = InFlight::default();
+
+  let policies = PolicySet::from_env()?;
+  if !policies.is_empty() {
+    info!(file = ?std::env::var_os(POLICY_FILE_ENV), "loaded write policies");
+  }
+
+  if let Some(namespace) = &watch_namespace {
+    info!(%namespace, "only watching a single namespace");
+  }
+
+  if let Some(selector) = &watch_label_selector {
+    info!(%selector, "only watching objects matching a label selector");
+  }
+
+  watch_settings.validate()?;
+  if let Some(server) = &storage {
+    server.gc.validate()?;
+  }
+  if watch_settings.no_bookmarks {
+    info!("watching without bookmarks");
+  }
+
+  if read_only {
+    info!("running read-only, skipping writes of objects other than status");
+  }
+
+  if no_cross_namespace_refs {
+    info!("rejecting cross-namespace references");
+  }
+
+  let events = record_events.map(EventLog::create).transpose()?;
+  if let Some(path) = record_events {
+    info!(file = %path.display(), "recording reconciles");
+  }
+
+  let forwarder = events_addr
+    .as_deref()
+    .map(EventForwarder::new)
+    .transpose()
+    .wrap_err("invalid events receiver address")?;
+  if let Some(forwarder) = &forwarder {
+    info!(addr = %forwarder.addr(), "forwarding events");
+  }
+
+  let (sink, delivery) = match event_sink {
+    Some(target) => {
+      let target = target
+        .parse::<SinkTarget>()
+        .wrap_err("invalid event sink")?;
+      let buffer = event_sink_buffer.map_or(DEFAULT_SINK_BUFFER, NonZeroUsize::get);
+      let (sink, delivery) = EventSink::new(target, buffer, name)?;
+      (Some(sink), Some(delivery))
+    }
+    None => (None, None),
+  };
+
+  let (namespaces, watch_namespaces) = NamespaceCache::new(client.clone());
+
+  // the Secrets and ConfigMaps referenced by the resources are cached when the controllers are
+  // allowed to watch them, and read from the API server otherwise
+  let reads = |resource: &str| {
+    controllers
+      .iter()
+      .any(|ctrl| ctrl.permissions.grants("", resource, READ))
+  };
+  let (secrets, watch_secrets) = match watch_namespace.as_deref() {
+    Some(namespace) => {
+      let (secrets, watch) = SecretResolver::namespaced(client.clone(), namespace);
+      (secrets, watch.boxed_local())
+    }
+    None => {
+      let (secrets, watch) = SecretResolver::new(client.clone());
+      (secrets, watch.boxed_local())
+    }
+  };
+  let (config_maps, watch_config_maps) = match watch_namespace.as_deref() {
+    Some(namespace) => {
+      let (config_maps, watch) = ConfigMapResolver::namespaced(client.clone(), namespace);
+      (config_maps, watch.boxed_local())
+    }
+    None => {
+      let (config_maps, watch) = ConfigMapResolver::new(client.clone());
+      (config_maps, watch.boxed_local())
+    }
+  };
+  let caches = [
+    ("secret-cache", reads("secrets").then(|| watch_secrets)),
+    (
+      "config-map-cache",
+      reads("configmaps").then(|| watch_config_maps),
+    ),
+  ];
+
+  let metrics = AppMetrics::default();
+  let artifact_refs = ArtifactReferences::default();
+  let pusher = metrics_push_url
+    .map(|url| {
+      let interval = metrics_push_interval.unwrap_or(DEFAULT_PUSH_INTERVAL);
+      MetricsPusher::new(metrics.clone(), &url, interval, name)
+    })
+    .transpose()
+    .wrap_err("invalid metrics push URL")?;
+  let env = ControllerEnv {
+    client: client.clone(),
+    reporter: reporter.clone(),
+    namespaces,
+    policies: Arc::new(policies),
+    schemas: Arc::new(OpenApiSchemas::new(client.clone())),
+    artifacts: storage.as_ref().map(|server| server.storage.clone()),
+    artifact_refs: artifact_refs.clone(),
+    secrets,
+    config_maps,
+    events,
+    forwarder,
+    sink,
+    metrics: metrics.clone(),
+    budget,
+    watch_namespace: watch_namespace.map(Into::into),
+    watch_labels: watch_label_selector.map(Into::into),
+    watch_settings,
+    concurrency,
+    read_only,
+    cross_namespace_refs: !no_cross_namespace_refs,
+    in_flight: in_flight.clone(),
+  };
+
+  let kinds = controllers
+    .iter()
+    .map(|ctrl| {
+      let kind = format!("{}/{}", ctrl.info.group, ctrl.info.kind);
+      (kind, ctrl.health.clone())
+    })
+    .collect::<Vec<_>>();
+
+  let debug = debug_addr.map(|addr| DebugServer {
+    schedules: controllers
+      .iter()
+      .map(|ctrl| ctrl.schedule.clone())
+      .collect(),
+    addr,
+  });
+
+  let election = leader_election.map(|lease| {
+    let identity = leader::identity(reporter.instance.as_deref());
+    Arc::new(LeaderElection::new(client.clone(), lease, identity))
+  });
+
+  let discovery = Arc::new(Discovery::new(controllers.iter().map(|ctrl| {
+    let info = &ctrl.info;
+    (&*info.api_version, &*info.kind, &*info.plural)
+  })));
+
+  let probes = probe_addr.map(|addr| ProbeServer {
+    kinds: kinds.clone(),
+    election: election.clone(),
+    info: Arc::new(AppInfo {
+      name: name.into(),
+      version: version.into(),
+      discovery: discovery.clone(),
+    }),
+    addr,
+  });
+
+  // every controller, and every subsystem next to them, is a task of its own, so that the app
+  // shuts down as soon as any of them fails, attributing the failure to it
+  let mut tasks = TaskGroup::new();
+
+  // rather than the watches of the controllers waiting on groups the API server does not serve,
+  // the app fails once they are still not served after the timeout
+  let priming = shutdown.register(Phase::Reconcilers);
+  let primed = discovery.clone();
+  let discovery_client = client.clone();
+  let discovery_timeout = discovery_timeout.unwrap_or(DEFAULT_DISCOVERY_TIMEOUT);
+  tasks.spawn("discovery", async move {
+    let prime = primed.prime(&discovery_client, discovery_timeout);
+    let stop = priming.signal();
+    futures::pin_mut!(prime);
+    match future::select(prime, stop).await {
+      Either::Left((result, _)) => Ok(result?),
+      Either::Right(_) => Ok(()),
+    }
+  });
+
+  for ctrl in controllers {
+    let kind = format!("{}/{}", ctrl.info.group, ctrl.info.kind);
+    let handle = shutdown.register(Phase::Reconcilers);
+    let env = ControllerEnv {
+      client: clients.client(&kind)?,
+      ..env.clone()
+    };
+    let election = election.clone();
+    let discovery = discovery.clone();
+    tasks.spawn(format!("controller {kind}"), async move {
+      let served = discovery.served();
+      let stop = handle.signal();
+      futures::pin_mut!(served);
+      if let Either::Right(_) = future::select(served, stop).await {
+        return Ok(());
+      }
+
+      // only the leader runs the controllers
+      if let Some(election) = &election {
+        let elected = election.elected();
+        let stop = handle.signal();
+        futures::pin_mut!(elected);
+        if let Either::Right(_) = future::select(elected, stop).await {
+          return Ok(());
+        }
+      }
+
+      (ctrl.factory)(env, handle.signal())
+        .for_each(|result| async move {
+          match result {
+            Ok((obj, action)) => debug!(%obj, ?action, "reconciled"),
+            Err(e) => warn!(error = %e, "reconcile failed"),
+          }
+        })
+        .await;
+
+      // the controller counts as stopped once its handle is dropped, which it only should be
+      // once the app shuts down
+      if handle.signal().now_or_never().is_none() {
+        eyre::bail!("stopped reconciling before the app shut down");
+      }
+
+      Ok(())
+    });
+  }
+
+  // the namespace cache is only needed for as long as the controllers are running
+  let watching = shutdown.register(Phase::Events);
+  tasks.spawn("namespace-cache", async move {
+    let stop = watching.signal();
+    futures::pin_mut!(watch_namespaces);
+    future::select(watch_namespaces, stop).await;
+    Ok(())
+  });
+
+  // and so are the caches of Secrets and ConfigMaps
+  for (label, watch) in caches {
+    if let Some(watch) = watch {
+      let watching = shutdown.register(Phase::Events);
+      tasks.spawn(label, async move {
+        let stop = watching.signal();
+        future::select(watch, stop).await;
+        Ok(())
+      });
+    }
+  }
+
+  // the lease is only released once the reconcilers have stopped, so that the next leader does
+  // not start reconciling alongside them
+  if let Some(election) = election.clone() {
+    let electing = shutdown.register(Phase::Events);
+    tasks.spawn("leader-election", async move {
+      let acquire = election.acquire();
+      let stop = electing.signal();
+      futures::pin_mut!(acquire);
+      if let Either::Right(_) = future::select(acquire, stop).await {
+        return Ok(());
+      }
+
+      // once leadership is lost, the app shuts down so that it can run for election again
+      let hold = election.hold();
+      let stop = electing.signal();
+      futures::pin_mut!(hold);
+      if let Either::Left(_) = future::select(hold, stop).await {
+        eyre::bail!("lost leadership, exiting to run for election again");
+      }
+
+      election.release().await;
+      Ok(())
+    });
+  }
+
+  let telemetry = shutdown.register(Phase::Events);
+  tasks.spawn("telemetry", async move {
+    telemetry.signal().await;
+    fluxcd_utils_telemetry::flush();
+    Ok(())
+  });
+
+  if report_status {
+    let status = shutdown.register(Phase::Events);
+    tasks.spawn("status-report", async move {
+      // only the leader reports, as it is the one running the controllers
+      let leader = match &election {
+        Some(election) => {
+          let elected = election.elected();
+          let stop = status.signal();
+          futures::pin_mut!(elected);
+          if let Either::Right(_) = future::select(elected, stop).await {
+            return Ok(());
+          }
+
+          Some(election.identity().to_string())
+        }
+        None => reporter.instance.clone(),
+      };
+
+      health::report(client, name, version, leader, kinds, status.signal()).await;
+      Ok(())
+    });
+  }
+
+  // artifacts stay available until the reconcilers producing them have stopped
+  if let Some(server) = storage {
+    let collector = ArtifactCollector {
+      storage: server.storage.clone(),
+      references: artifact_refs,
+      settings: server.gc.clone(),
+    };
+    let collecting = shutdown.register(Phase::Reconcilers);
+    tasks.spawn("artifact-gc", async move {
+      collector.run(collecting.signal()).await;
+      Ok(())
+    });
+
+    let serving = shutdown.register(Phase::Events);
+    tasks.spawn("artifact-server", async move {
+      server
+        .serve(serving.signal())
+        .await
+        .wrap_err("failed to serve artifacts")
+    });
+  }
+
+  // metrics stay available until everything recording them has stopped
+  if let Some(addr) = metrics_addr {
+    let scraping = shutdown.register(Phase::Servers);
+    tasks.spawn("metrics-server", async move {
+      let server = MetricsServer { metrics, addr };
+      server
+        .serve(scraping.signal())
+        .await
+        .wrap_err("failed to serve metrics")
+    });
+  }
+
+  // the final metrics are pushed once everything recording them has stopped
+  if let Some(pusher) = pusher {
+    let pushing = shutdown.register(Phase::Servers);
+    tasks.spawn("metrics-push", async move {
+      pusher.run(pushing.signal()).await;
+      Ok(())
+    });
+  }
+
+  // probes keep answering while the app shuts down, so that it is not restarted meanwhile
+  if let Some(server) = probes {
+    let probing = shutdown.register(Phase::Servers);
+    tasks.spawn("probe-server", async move {
+      server
+        .serve(probing.signal())
+        .await
+        .wrap_err("failed to serve probes")
+    });
+  }
+
+  if let Some(server) = debug {
+    let debugging = shutdown.register(Phase::Servers);
+    tasks.spawn("debug-server", async move {
+      server
+        .serve(debugging.signal())
+        .await
+        .wrap_err("failed to serve debugging endpoints")
+    });
+  }
+
+  // services, like the webhook receivers, stop first, so that they do not request reconciles
+  // which will not run anymore
+  for service in services {
+    let running = shutdown.register(Phase::Webhooks);
+    let run = (service.factory)(client.clone(), running.signal());
+    tasks.spawn(service.label, async move {
+      let result = run.await;
+      drop(running);
+      result
+    });
+  }
+
+  // reconciles are delivered until the reconcilers have stopped, and then the buffer is flushed
+  if let Some(delivery) = delivery {
+    let delivering = shutdown.register(Phase::Events);
+    tasks.spawn("event-sink", async move {
+      delivery.run(delivering.signal()).await;
+      Ok(())
+    });
+  }
+
+  let failure = tasks.failure();
+  let graceful_shutdown_timeout =
+    graceful_shutdown_timeout.unwrap_or(DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT);
+  let shutdown = async move {
+    let failed = failure.cancelled();
+    futures::pin_mut!(signal, failed);
+    future::select(signal, failed).await;
+    signals::drain_or_exit(shutdown.shutdown(), graceful_shutdown_timeout, &in_flight).await;
+  };
+
+  let (summary, ()) = futures::join!(tasks.join(), shutdown);
+  info!(%summary, "stopped");
+  summary.into_result()
 }
 
 pub(crate) async fn run<'a>(
   name: &str,
   version: &str,
   controllers: Vec<DynController<'a>>,
+  services: Vec<DynService<'a>>,
 ) -> eyre::Result<()> {
   let cmd = clap::Command::new(name).version(version);
   let cmd = <Cli as clap::Args>::augment_args(cmd);
@@ -97,5 +1618,5 @@ pub(crate) async fn run<'a>(
   let args = cmd.clone().get_matches();
   let parsed = <Cli as clap::FromArgMatches>::from_arg_matches(&args)?;
 
-  parsed.run(controllers).await
+  parsed.run(name, version, controllers, services).await
 }
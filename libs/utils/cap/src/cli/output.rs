@@ -0,0 +1,293 @@
+use clap::ArgEnum;
+use k8s_openapi::{apimachinery::pkg::apis::meta::v1::Time, chrono::Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+  env,
+  io::{self, IsTerminal, Write},
+};
+
+/// How a listing command prints the objects it lists.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+  /// A table of the most important columns
+  Table,
+  /// A table of all columns
+  Wide,
+  /// A `List` of the objects, as json
+  Json,
+  /// A `List` of the objects, as yaml
+  Yaml,
+  /// Only the names of the objects
+  Name,
+}
+
+/// A column of a [Table], after the leading `NAME` column.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Column {
+  header: &'static str,
+  wide: bool,
+  status: bool,
+}
+
+impl Column {
+  pub(crate) const fn new(header: &'static str) -> Self {
+    Self {
+      header,
+      wide: false,
+      status: false,
+    }
+  }
+
+  /// Only shows the column with `-o wide`.
+  pub(crate) const fn wide(self) -> Self {
+    Self { wide: true, ..self }
+  }
+
+  /// Marks the cells of the column as condition statuses (`True`, `False` or `Unknown`), which are
+  /// colorized when printing to a terminal.
+  pub(crate) const fn status(self) -> Self {
+    Self {
+      status: true,
+      ..self
+    }
+  }
+}
+
+struct Row {
+  name: String,
+  cells: Vec<String>,
+  object: Value,
+}
+
+/// The output of a listing command, which is printed as a table or as the listed objects
+/// themselves, depending on the [OutputFormat]. Columns are always printed in the order they were
+/// declared in, and rows in the order they were pushed.
+pub(crate) struct Table {
+  columns: Vec<Column>,
+  rows: Vec<Row>,
+}
+
+impl Table {
+  pub(crate) fn new(columns: impl IntoIterator<Item = Column>) -> Self {
+    Self {
+      columns: columns.into_iter().collect(),
+      rows: Vec::new(),
+    }
+  }
+
+  /// Adds a row for `object`, with a cell for every column of the table.
+  pub(crate) fn push<T: Serialize>(
+    &mut self,
+    name: impl Into<String>,
+    cells: Vec<String>,
+    object: &T,
+  ) -> eyre::Result<()> {
+    debug_assert_eq!(cells.len(), self.columns.len());
+    self.rows.push(Row {
+      name: name.into(),
+      cells,
+      object: serde_json::to_value(object)?,
+    });
+
+    Ok(())
+  }
+
+  /// Prints the table to stdout, colorizing it if stdout is a terminal.
+  pub(crate) fn print(&self, format: OutputFormat) -> eyre::Result<()> {
+    let stdout = io::stdout();
+    let color = stdout.is_terminal() && env::var_os("NO_COLOR").is_none();
+    self.write(&mut stdout.lock(), format, color)
+  }
+
+  fn write(&self, out: &mut impl Write, format: OutputFormat, color: bool) -> eyre::Result<()> {
+    match format {
+      OutputFormat::Table => self.write_table(out, false, color)?,
+      OutputFormat::Wide => self.write_table(out, true, color)?,
+      OutputFormat::Json => {
+        serde_json::to_writer_pretty(&mut *out, &self.list())?;
+        writeln!(out)?;
+      }
+      OutputFormat::Yaml => serde_yaml::to_writer(&mut *out, &self.list())?,
+      OutputFormat::Name => {
+        for row in &self.rows {
+          writeln!(out, "{}", row.name)?;
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  fn list(&self) -> Value {
+    let items = self.rows.iter().map(|row| &row.object).collect::<Vec<_>>();
+    json!({
+      "apiVersion": "v1",
+      "kind": "List",
+      "items": items,
+    })
+  }
+
+  fn write_table(&self, out: &mut impl Write, wide: bool, color: bool) -> io::Result<()> {
+    let shown = self
+      .columns
+      .iter()
+      .enumerate()
+      .filter(|(_, column)| wide || !column.wide)
+      .collect::<Vec<_>>();
+
+    let mut widths = Vec::with_capacity(shown.len() + 1);
+    widths.push(
+      self
+        .rows
+        .iter()
+        .map(|row| row.name.chars().count())
+        .fold("NAME".len(), usize::max),
+    );
+    for (index, column) in &shown {
+      widths.push(
+        self
+          .rows
+          .iter()
+          .map(|row| row.cells[*index].chars().count())
+          .fold(column.header.len(), usize::max),
+      );
+    }
+
+    let headers = std::iter::once("NAME").chain(shown.iter().map(|(_, column)| column.header));
+    let line = headers
+      .zip(&widths)
+      .map(|(header, width)| format!("{header:width$}"))
+      .collect::<Vec<_>>();
+    writeln!(out, "{}", line.join("   ").trim_end())?;
+
+    for row in &self.rows {
+      let mut line = vec![format!("{:width$}", row.name, width = widths[0])];
+      for ((index, column), width) in shown.iter().zip(&widths[1..]) {
+        // pad before colorizing, as the escape codes take up no room on the terminal
+        let cell = &row.cells[*index];
+        let padded = format!("{cell:width$}");
+        match status_color(cell).filter(|_| color && column.status) {
+          Some(code) => {
+            let padding = &padded[cell.len()..];
+            line.push(format!("\x1b[{code}m{cell}\x1b[0m{padding}"));
+          }
+          None => line.push(padded),
+        }
+      }
+
+      writeln!(out, "{}", line.join("   ").trim_end())?;
+    }
+
+    Ok(())
+  }
+}
+
+/// The ANSI color code used for a condition status.
+fn status_color(status: &str) -> Option<&'static str> {
+  match status {
+    "True" => Some("32"),
+    "False" => Some("31"),
+    "Unknown" => Some("33"),
+    _ => None,
+  }
+}
+
+/// The time since `time`, in the short form used by kubectl, e.g. `5m` or `3d`.
+pub(crate) fn age(time: Option<&Time>) -> String {
+  let time = match time {
+    Some(time) => time,
+    None => return "<unknown>".into(),
+  };
+
+  let seconds = (Utc::now() - time.0).num_seconds().max(0);
+  duration(seconds)
+}
+
+/// How long until `time`, like `in 5m`, or how long ago it was, like `5m ago`.
+pub(crate) fn due(time: &Time) -> String {
+  let seconds = (time.0 - Utc::now()).num_seconds();
+  if seconds >= 0 {
+    format!("in {}", duration(seconds))
+  } else {
+    format!("{} ago", duration(-seconds))
+  }
+}
+
+fn duration(seconds: i64) -> String {
+  match seconds {
+    s if s < 120 => format!("{s}s"),
+    s if s < 2 * 3600 => format!("{}m", s / 60),
+    s if s < 2 * 86400 => format!("{}h", s / 3600),
+    s => format!("{}d", s / 86400),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn table() -> Table {
+    let mut table = Table::new([Column::new("READY").status(), Column::new("MESSAGE").wide()]);
+    table
+      .push(
+        "first",
+        vec!["True".into(), "ok".into()],
+        &json!({ "n": 1 }),
+      )
+      .unwrap();
+    table
+      .push(
+        "second-object",
+        vec!["False".into(), "failed".into()],
+        &json!({ "n": 2 }),
+      )
+      .unwrap();
+    table
+  }
+
+  fn write(format: OutputFormat, color: bool) -> String {
+    let mut out = Vec::new();
+    table().write(&mut out, format, color).unwrap();
+    String::from_utf8(out).unwrap()
+  }
+
+  #[test]
+  fn table_hides_wide_columns() {
+    assert_eq!(
+      write(OutputFormat::Table, false),
+      "NAME            READY\nfirst           True\nsecond-object   False\n"
+    );
+  }
+
+  #[test]
+  fn wide_table_shows_all_columns() {
+    assert_eq!(
+      write(OutputFormat::Wide, false),
+      concat!(
+        "NAME            READY   MESSAGE\n",
+        "first           True    ok\n",
+        "second-object   False   failed\n",
+      )
+    );
+  }
+
+  #[test]
+  fn status_cells_are_colorized() {
+    let out = write(OutputFormat::Wide, true);
+    assert!(out.contains("\x1b[32mTrue\x1b[0m    ok"));
+    assert!(out.contains("\x1b[31mFalse\x1b[0m   failed"));
+  }
+
+  #[test]
+  fn name_prints_only_names() {
+    assert_eq!(write(OutputFormat::Name, false), "first\nsecond-object\n");
+  }
+
+  #[test]
+  fn json_prints_a_list() {
+    let list: Value = serde_json::from_str(&write(OutputFormat::Json, false)).unwrap();
+    assert_eq!(list["kind"], "List");
+    assert_eq!(list["items"], json!([{ "n": 1 }, { "n": 2 }]));
+  }
+}
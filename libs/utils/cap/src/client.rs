@@ -0,0 +1,299 @@
+use futures::future::BoxFuture;
+use http::{Request, Response, StatusCode};
+use hyper::client::HttpConnector;
+use hyper_timeout::TimeoutConnector;
+use kube::{client::ConfigExt, Client, Config};
+use prometheus::{exponential_buckets, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+use std::{
+  sync::Arc,
+  task::{Context, Poll},
+  time::{Duration, Instant},
+};
+use tower::{Layer, Service, ServiceBuilder};
+use tracing::{field, Instrument};
+
+/// How long a request to the Kubernetes API may take before it is logged as slow, by default.
+pub(crate) const DEFAULT_SLOW_REQUEST_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// Metrics of the requests made to the Kubernetes API.
+pub(crate) struct ApiMetrics {
+  requests: IntCounterVec,
+  duration: HistogramVec,
+  conflicts: IntCounterVec,
+  throttled: IntCounterVec,
+}
+
+impl ApiMetrics {
+  fn new() -> prometheus::Result<Self> {
+    let counter = |name: &str, help: &str, labels: &[&str]| {
+      let opts = Opts::new(name, help)
+        .subsystem("kube_api")
+        .namespace("gotk");
+      IntCounterVec::new(opts, labels)
+    };
+
+    let requests = counter(
+      "requests_total",
+      "The number of requests made to the Kubernetes API.",
+      &["verb", "resource", "code"],
+    )?;
+
+    let duration = HistogramVec::new(
+      HistogramOpts::new(
+        "request_duration_seconds",
+        "The duration in seconds of requests made to the Kubernetes API.",
+      )
+      .subsystem("kube_api")
+      .namespace("gotk")
+      .buckets(exponential_buckets(0.005, 2f64, 12)?),
+      &["verb", "resource"],
+    )?;
+
+    let conflicts = counter(
+      "conflicts_total",
+      "The number of requests to the Kubernetes API rejected with a conflict (409), by controller.",
+      &["controller", "verb", "resource"],
+    )?;
+
+    let throttled = counter(
+      "throttled_total",
+      "The number of requests to the Kubernetes API throttled (429), by controller.",
+      &["controller", "verb", "resource"],
+    )?;
+
+    Ok(Self {
+      requests,
+      duration,
+      conflicts,
+      throttled,
+    })
+  }
+
+  fn register(&self, registry: &Registry) -> prometheus::Result<()> {
+    registry.register(Box::new(self.requests.clone()))?;
+    registry.register(Box::new(self.duration.clone()))?;
+    registry.register(Box::new(self.conflicts.clone()))?;
+    registry.register(Box::new(self.throttled.clone()))?;
+    Ok(())
+  }
+}
+
+/// The resource a request made to the Kubernetes API is about, as derived from its path.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ApiTarget {
+  resource: String,
+  namespace: Option<String>,
+  name: Option<String>,
+}
+
+impl ApiTarget {
+  /// Parses paths like `/api/v1/namespaces/{namespace}/{resource}/{name}` and
+  /// `/apis/{group}/{version}/{resource}`.
+  fn from_path(path: &str) -> Self {
+    let mut segments = path.trim_matches('/').split('/');
+    let group = match segments.next() {
+      Some("api") => {
+        segments.next();
+        None
+      }
+      Some("apis") => {
+        let group = segments.next();
+        segments.next();
+        group
+      }
+      _ => return Self::default(),
+    };
+
+    let mut rest = segments.collect::<Vec<_>>();
+    let mut namespace = None;
+    if rest.len() > 2 && rest[0] == "namespaces" {
+      namespace = Some(rest[1].to_string());
+      rest.drain(..2);
+    }
+
+    let resource = match (group, rest.first()) {
+      (_, None) => String::new(),
+      (None, Some(resource)) => resource.to_string(),
+      (Some(group), Some(resource)) => format!("{resource}.{group}"),
+    };
+
+    Self {
+      resource,
+      namespace,
+      name: rest.get(1).map(|name| name.to_string()),
+    }
+  }
+}
+
+/// Traces every request made to the Kubernetes API in a span, which is nested in the span of the
+/// caller (e.g. the reconcile), and records it in the [ApiMetrics]. Requests taking longer than
+/// `slow_request` are logged.
+#[derive(Clone)]
+struct ApiTraceLayer {
+  metrics: Arc<ApiMetrics>,
+  controller: Arc<str>,
+  slow_request: Option<Duration>,
+}
+
+#[derive(Clone)]
+struct ApiTrace<S> {
+  inner: S,
+  layer: ApiTraceLayer,
+}
+
+impl<S> Layer<S> for ApiTraceLayer {
+  type Service = ApiTrace<S>;
+
+  fn layer(&self, inner: S) -> Self::Service {
+    ApiTrace {
+      inner,
+      layer: self.clone(),
+    }
+  }
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for ApiTrace<S>
+where
+  S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+  S::Future: Send + 'static,
+{
+  type Response = S::Response;
+  type Error = S::Error;
+  type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    self.inner.poll_ready(cx)
+  }
+
+  fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+    // kube tags its requests with the verb, fall back to the method for raw requests
+    let verb = match req.extensions().get::<&'static str>() {
+      Some(verb) => verb.to_string(),
+      None => req.method().as_str().to_lowercase(),
+    };
+    let target = ApiTarget::from_path(req.uri().path());
+    let span = tracing::debug_span!(
+      "kube_api",
+      api.verb = %verb,
+      api.resource = %target.resource,
+      api.namespace = target.namespace.as_deref().unwrap_or_default(),
+      api.name = target.name.as_deref().unwrap_or_default(),
+      http.status_code = field::Empty,
+      latency_ms = field::Empty,
+    );
+
+    let ApiTraceLayer {
+      metrics,
+      controller,
+      slow_request,
+    } = self.layer.clone();
+    let future = span.in_scope(|| self.inner.call(req));
+    let record_span = span.clone();
+    Box::pin(
+      async move {
+        let start = Instant::now();
+        let result = future.await;
+        let elapsed = start.elapsed();
+
+        let status = result.as_ref().ok().map(|response| response.status());
+        let code = match status {
+          Some(status) => status.as_u16().to_string(),
+          None => "error".to_string(),
+        };
+        record_span.record("http.status_code", &code.as_str());
+        record_span.record("latency_ms", &(elapsed.as_millis() as u64));
+        tracing::debug!("kube api request completed");
+
+        metrics
+          .requests
+          .with_label_values(&[&verb, &target.resource, &code])
+          .inc();
+        metrics
+          .duration
+          .with_label_values(&[&verb, &target.resource])
+          .observe(elapsed.as_secs_f64());
+
+        let labels = [&*controller, verb.as_str(), target.resource.as_str()];
+        match status {
+          Some(StatusCode::CONFLICT) => metrics.conflicts.with_label_values(&labels).inc(),
+          Some(StatusCode::TOO_MANY_REQUESTS) => metrics.throttled.with_label_values(&labels).inc(),
+          _ => {}
+        }
+
+        // watches are held open on purpose
+        let slow = matches!(slow_request, Some(threshold) if elapsed > threshold);
+        if slow && verb != "watch" {
+          tracing::warn!(
+            %controller,
+            api.verb = %verb,
+            api.resource = %target.resource,
+            api.namespace = target.namespace.as_deref().unwrap_or_default(),
+            api.name = target.name.as_deref().unwrap_or_default(),
+            http.status_code = %code,
+            latency_ms = elapsed.as_millis() as u64,
+            "slow kube api request"
+          );
+        }
+
+        result
+      }
+      .instrument(span),
+    )
+  }
+}
+
+/// Creates the clients of an app from the inferred configuration, like [Client::try_default],
+/// with every API request traced and recorded in the API metrics of the default registry.
+///
+/// Every controller gets a client of its own, so that the conflicts and throttles of its requests
+/// are counted separately, and each of them keeps its own connections.
+pub(crate) struct ApiClients {
+  config: Config,
+  metrics: Arc<ApiMetrics>,
+  slow_request: Option<Duration>,
+}
+
+impl ApiClients {
+  /// Infers the configuration. Requests taking longer than `slow_request` are logged, unless it is
+  /// zero.
+  pub(crate) async fn infer(slow_request: Duration) -> eyre::Result<Self> {
+    let config = Config::infer().await?;
+    let metrics = ApiMetrics::new()?;
+    if let Err(error) = metrics.register(prometheus::default_registry()) {
+      tracing::warn!(%error, "failed to register kube api metrics");
+    }
+
+    Ok(Self {
+      config,
+      metrics: Arc::new(metrics),
+      slow_request: Some(slow_request).filter(|threshold| !threshold.is_zero()),
+    })
+  }
+
+  /// Creates a client whose requests are attributed to `controller`, like
+  /// `source.fluxcd.yolodev.io/Bucket`, or to the app for the requests of the runtime.
+  pub(crate) fn client(&self, controller: &str) -> eyre::Result<Client> {
+    let config = &self.config;
+    let mut connector = HttpConnector::new();
+    connector.enforce_http(false);
+    let connector =
+      hyper_rustls::HttpsConnector::from((connector, Arc::new(config.rustls_client_config()?)));
+    let mut connector = TimeoutConnector::new(connector);
+    connector.set_connect_timeout(config.timeout);
+    connector.set_read_timeout(config.timeout);
+    let http = hyper::Client::builder().build(connector);
+
+    let service = ServiceBuilder::new()
+      .layer(ApiTraceLayer {
+        metrics: self.metrics.clone(),
+        controller: controller.into(),
+        slow_request: self.slow_request,
+      })
+      .layer(config.base_uri_layer())
+      .option_layer(config.auth_layer()?)
+      .layer(config.extra_headers_layer()?)
+      .service(http);
+
+    Ok(Client::new(service, config.default_namespace.clone()))
+  }
+}
@@ -0,0 +1,70 @@
+use crate::{
+  problem::{self, Problem},
+  schedule::{self, Schedule},
+};
+use hyper::{
+  header::CONTENT_TYPE,
+  service::{make_service_fn, service_fn},
+  Body, Method, Request, Response, Server, StatusCode,
+};
+use std::{convert::Infallible, future::Future, net::SocketAddr, sync::Arc};
+use tracing::info;
+
+/// Path of the endpoint listing the upcoming reconciles of all controllers.
+pub(crate) const SCHEDULE_PATH: &str = "/debug/schedule";
+
+/// Endpoints to inspect the state of the controllers of a running app, and the address they are
+/// served at. They reveal which objects the controllers manage, so they are only served when
+/// asked for.
+pub(crate) struct DebugServer {
+  pub(crate) schedules: Vec<Arc<dyn Schedule>>,
+  pub(crate) addr: SocketAddr,
+}
+
+impl DebugServer {
+  /// Serves the debugging endpoints over HTTP, until `signal` completes.
+  pub(crate) async fn serve(self, signal: impl Future<Output = ()>) -> eyre::Result<()> {
+    let schedules = Arc::new(self.schedules);
+    let make_service = make_service_fn(move |_| {
+      let schedules = schedules.clone();
+      let service = service_fn(move |request| {
+        let schedules = schedules.clone();
+        problem::handle("debug", request, |request| respond(schedules, request))
+      });
+
+      async move { Ok::<_, Infallible>(service) }
+    });
+
+    let server = Server::try_bind(&self.addr)?.serve(make_service);
+    info!(addr = %self.addr, "serving debugging endpoints");
+    server.with_graceful_shutdown(signal).await?;
+
+    Ok(())
+  }
+}
+
+async fn respond(
+  schedules: Arc<Vec<Arc<dyn Schedule>>>,
+  request: Request<Body>,
+) -> Result<Response<Body>, Problem> {
+  if request.uri().path() != SCHEDULE_PATH {
+    return Err(Problem::new(StatusCode::NOT_FOUND).detail("there is no endpoint at this path"));
+  }
+
+  if request.method() != Method::GET {
+    return Err(
+      Problem::new(StatusCode::METHOD_NOT_ALLOWED).detail("the schedule only supports GET"),
+    );
+  }
+
+  let upcoming = schedule::upcoming(&schedules);
+  let body = serde_json::to_vec(&upcoming).map_err(|_| {
+    Problem::new(StatusCode::INTERNAL_SERVER_ERROR).detail("the schedule could not be serialized")
+  })?;
+  let response = Response::builder()
+    .header(CONTENT_TYPE, "application/json")
+    .body(Body::from(body))
+    .expect("response is valid");
+
+  Ok(response)
+}
@@ -0,0 +1,273 @@
+use kube::Client;
+use serde::Serialize;
+use std::{sync::Mutex, time::Duration};
+use thiserror::Error;
+use tokio::time::{self, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// How long to wait for the groups to be served, unless configured otherwise.
+pub(crate) const DEFAULT_DISCOVERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait between attempts to discover a group, while it is not served yet.
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Error)]
+pub(crate) enum DiscoveryError {
+  #[error("the API server does not serve {api_version}, is its CRD installed?")]
+  MissingGroup { api_version: String },
+
+  #[error("the API server serves {api_version}, but not {kinds}, are their CRDs up to date?")]
+  MissingKinds { api_version: String, kinds: String },
+
+  #[error("failed to discover {api_version}")]
+  Unreachable {
+    api_version: String,
+    #[source]
+    source: kube::Error,
+  },
+
+  #[error("the API server did not answer the discovery of {api_version} within {timeout:?}")]
+  TimedOut {
+    api_version: String,
+    timeout: Duration,
+  },
+}
+
+/// Where the discovery of a group stands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub(crate) enum DiscoveryState {
+  /// Not discovered yet, nor did any attempt fail.
+  Pending,
+
+  /// The API server serves the group, and every kind of it the app has a controller for.
+  Served,
+
+  /// The API server does not serve the group, or some of its kinds.
+  Missing,
+
+  /// The API server could not be reached.
+  Unreachable,
+
+  /// The API server did not answer in time.
+  TimedOut,
+}
+
+/// The groups the controllers of an app watch, and whether the API server serves them. They are
+/// discovered once at startup, so that the app fails with a clear error when they are missing or
+/// the API server is unreachable, rather than its watches waiting on them forever.
+pub(crate) struct Discovery {
+  groups: Vec<GroupDiscovery>,
+  served: CancellationToken,
+}
+
+struct GroupDiscovery {
+  api_version: String,
+
+  /// The kinds of the group, with their plural name as listed by the API server.
+  kinds: Vec<(String, String)>,
+
+  status: Mutex<GroupStatus>,
+}
+
+/// The discovery of a group, as listed by the `/info` endpoint.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GroupStatus {
+  pub(crate) api_version: String,
+  pub(crate) kinds: Vec<String>,
+  pub(crate) state: DiscoveryState,
+
+  /// Why the group is not served, if the last attempt failed.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) message: Option<String>,
+}
+
+impl Discovery {
+  /// Discovery of the groups of `kinds`, given as their api version, kind and plural name.
+  pub(crate) fn new<'a>(kinds: impl IntoIterator<Item = (&'a str, &'a str, &'a str)>) -> Self {
+    let mut grouped = Vec::<(String, Vec<(String, String)>)>::new();
+    for (api_version, kind, plural) in kinds {
+      let kind = (kind.to_string(), plural.to_string());
+      match grouped.iter_mut().find(|(group, _)| group == api_version) {
+        Some((_, kinds)) => kinds.push(kind),
+        None => grouped.push((api_version.into(), vec![kind])),
+      }
+    }
+
+    let groups = grouped
+      .into_iter()
+      .map(|(api_version, kinds)| GroupDiscovery {
+        status: Mutex::new(GroupStatus {
+          api_version: api_version.clone(),
+          kinds: kinds.iter().map(|(kind, _)| kind.clone()).collect(),
+          state: DiscoveryState::Pending,
+          message: None,
+        }),
+        api_version,
+        kinds,
+      })
+      .collect();
+
+    Self {
+      groups,
+      served: CancellationToken::new(),
+    }
+  }
+
+  /// Discovers every group, retrying while they are missing or the API server is unreachable,
+  /// for at most `timeout`. Fails with why a group is still not served once the time is up.
+  pub(crate) async fn prime(
+    &self,
+    client: &Client,
+    timeout: Duration,
+  ) -> Result<(), DiscoveryError> {
+    let deadline = Instant::now() + timeout;
+    let primes = self
+      .groups
+      .iter()
+      .map(|group| group.prime(client, deadline, timeout));
+    futures::future::try_join_all(primes).await?;
+
+    info!(
+      groups = self.groups.len(),
+      "discovered the groups of the controllers"
+    );
+    self.served.cancel();
+    Ok(())
+  }
+
+  /// Completes once every group has been discovered.
+  pub(crate) async fn served(&self) {
+    self.served.cancelled().await
+  }
+
+  /// The discovery of every group.
+  pub(crate) fn status(&self) -> Vec<GroupStatus> {
+    self
+      .groups
+      .iter()
+      .map(|group| group.status.lock().unwrap().clone())
+      .collect()
+  }
+}
+
+impl GroupDiscovery {
+  async fn prime(
+    &self,
+    client: &Client,
+    deadline: Instant,
+    timeout: Duration,
+  ) -> Result<(), DiscoveryError> {
+    loop {
+      let error = match time::timeout_at(deadline, self.discover(client)).await {
+        Ok(Ok(())) => {
+          self.record(DiscoveryState::Served, None);
+          return Ok(());
+        }
+        Ok(Err(error)) => error,
+        Err(_) => DiscoveryError::TimedOut {
+          api_version: self.api_version.clone(),
+          timeout,
+        },
+      };
+
+      let state = match &error {
+        DiscoveryError::MissingGroup { .. } | DiscoveryError::MissingKinds { .. } => {
+          DiscoveryState::Missing
+        }
+        DiscoveryError::Unreachable { .. } => DiscoveryState::Unreachable,
+        DiscoveryError::TimedOut { .. } => DiscoveryState::TimedOut,
+      };
+      let message = match &error {
+        DiscoveryError::Unreachable { source, .. } => format!("{error}: {source}"),
+        error => error.to_string(),
+      };
+      self.record(state, Some(message.clone()));
+
+      if state == DiscoveryState::TimedOut || Instant::now() + RETRY_INTERVAL >= deadline {
+        return Err(error);
+      }
+
+      warn!(error = %message, "group not served yet, retrying");
+      time::sleep(RETRY_INTERVAL).await;
+    }
+  }
+
+  async fn discover(&self, client: &Client) -> Result<(), DiscoveryError> {
+    let resources = match client.list_api_group_resources(&self.api_version).await {
+      Ok(list) => list.resources,
+      Err(kube::Error::Api(e)) if e.code == 404 => {
+        return Err(DiscoveryError::MissingGroup {
+          api_version: self.api_version.clone(),
+        })
+      }
+      Err(source) => {
+        return Err(DiscoveryError::Unreachable {
+          api_version: self.api_version.clone(),
+          source,
+        })
+      }
+    };
+
+    let missing = self
+      .kinds
+      .iter()
+      .filter(|(_, plural)| !resources.iter().any(|r| &r.name == plural))
+      .map(|(kind, _)| kind.as_str())
+      .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+      Ok(())
+    } else {
+      Err(DiscoveryError::MissingKinds {
+        api_version: self.api_version.clone(),
+        kinds: missing.join(", "),
+      })
+    }
+  }
+
+  fn record(&self, state: DiscoveryState, message: Option<String>) {
+    let mut status = self.status.lock().unwrap();
+    status.state = state;
+    status.message = message;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn groups_kinds_by_api_version() {
+    let discovery = Discovery::new([
+      (
+        "source.fluxcd.yolodev.io/v1beta1",
+        "HttpEndpoint",
+        "httpendpoints",
+      ),
+      (
+        "runtime.fluxcd.yolodev.io/v1alpha1",
+        "ControllerStatus",
+        "controllerstatuses",
+      ),
+      ("source.fluxcd.yolodev.io/v1beta1", "Bucket", "buckets"),
+    ]);
+
+    assert_eq!(
+      serde_json::to_value(discovery.status()).unwrap(),
+      serde_json::json!([
+        {
+          "apiVersion": "source.fluxcd.yolodev.io/v1beta1",
+          "kinds": ["HttpEndpoint", "Bucket"],
+          "state": "Pending",
+        },
+        {
+          "apiVersion": "runtime.fluxcd.yolodev.io/v1alpha1",
+          "kinds": ["ControllerStatus"],
+          "state": "Pending",
+        },
+      ])
+    );
+  }
+}
@@ -0,0 +1,164 @@
+use crate::ControllerResourceInfo;
+use kube::runtime::watcher;
+use thiserror::Error;
+
+/// A failure of the watch driving a controller, classified so that the operator gets an
+/// actionable message rather than the raw API error.
+#[derive(Debug, Error)]
+pub enum WatchFailure {
+  #[error("access to {resource} was denied, grant list and watch on {resource} to the controller's service account")]
+  Forbidden {
+    resource: String,
+    #[source]
+    source: watcher::Error,
+  },
+
+  #[error("{resource} is not served by the API server, make sure the {kind} CRD is installed")]
+  ResourceMissing {
+    resource: String,
+    kind: String,
+    #[source]
+    source: watcher::Error,
+  },
+
+  #[error("the watch on {resource} expired, it will be restarted from a fresh list")]
+  Expired {
+    resource: String,
+    #[source]
+    source: watcher::Error,
+  },
+
+  #[error("could not reach the API server while watching {resource}, check the network connectivity to the cluster")]
+  Network {
+    resource: String,
+    #[source]
+    source: watcher::Error,
+  },
+
+  #[error("watching {resource} failed")]
+  Other {
+    resource: String,
+    #[source]
+    source: watcher::Error,
+  },
+}
+
+impl WatchFailure {
+  /// The class of the failure, like `Forbidden`, for the probes to report.
+  pub fn reason(&self) -> &'static str {
+    match self {
+      Self::Forbidden { .. } => "Forbidden",
+      Self::ResourceMissing { .. } => "ResourceMissing",
+      Self::Expired { .. } => "Expired",
+      Self::Network { .. } => "Network",
+      Self::Other { .. } => "Other",
+    }
+  }
+
+  pub(crate) fn new(info: &ControllerResourceInfo, source: watcher::Error) -> Self {
+    let resource = format!("{}.{}", info.plural, info.group);
+
+    let code = match &source {
+      watcher::Error::InitialListFailed(e)
+      | watcher::Error::WatchStartFailed(e)
+      | watcher::Error::WatchFailed(e) => match e {
+        kube::Error::Api(response) => Some(response.code),
+        kube::Error::HyperError(_) | kube::Error::Service(_) => {
+          return Self::Network { resource, source }
+        }
+        _ => None,
+      },
+      watcher::Error::WatchError(response) => Some(response.code),
+      watcher::Error::TooManyObjects => None,
+    };
+
+    match code {
+      Some(403) => Self::Forbidden { resource, source },
+      Some(404) => Self::ResourceMissing {
+        resource,
+        kind: info.kind.to_string(),
+        source,
+      },
+      Some(410) => Self::Expired { resource, source },
+      _ => Self::Other { resource, source },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use kube::error::ErrorResponse;
+  use std::io;
+
+  fn info() -> ControllerResourceInfo {
+    ControllerResourceInfo {
+      group: "source.fluxcd.yolodev.io".into(),
+      kind: "GitHubUserSshKeys".into(),
+      plural: "githubusersshkeys".into(),
+      version: "v1beta1".into(),
+      api_version: "source.fluxcd.yolodev.io/v1beta1".into(),
+      namespaced: true,
+    }
+  }
+
+  fn api_error(code: u16, reason: &str) -> kube::Error {
+    kube::Error::Api(ErrorResponse {
+      status: "Failure".into(),
+      message: reason.into(),
+      reason: reason.into(),
+      code,
+    })
+  }
+
+  #[test]
+  fn classifies_watch_errors() {
+    let resource = "githubusersshkeys.source.fluxcd.yolodev.io";
+    let cases = [
+      (
+        watcher::Error::InitialListFailed(api_error(403, "Forbidden")),
+        "Forbidden",
+        format!("access to {resource} was denied, grant list and watch on {resource} to the controller's service account"),
+      ),
+      (
+        watcher::Error::WatchStartFailed(api_error(404, "NotFound")),
+        "ResourceMissing",
+        format!("{resource} is not served by the API server, make sure the GitHubUserSshKeys CRD is installed"),
+      ),
+      (
+        watcher::Error::WatchFailed(api_error(410, "Expired")),
+        "Expired",
+        format!("the watch on {resource} expired, it will be restarted from a fresh list"),
+      ),
+      (
+        watcher::Error::WatchError(ErrorResponse {
+          status: "Failure".into(),
+          message: "too old resource version".into(),
+          reason: "Expired".into(),
+          code: 410,
+        }),
+        "Expired",
+        format!("the watch on {resource} expired, it will be restarted from a fresh list"),
+      ),
+      (
+        watcher::Error::WatchFailed(kube::Error::Service(Box::new(io::Error::new(
+          io::ErrorKind::ConnectionRefused,
+          "connection refused",
+        )))),
+        "Network",
+        format!("could not reach the API server while watching {resource}, check the network connectivity to the cluster"),
+      ),
+      (
+        watcher::Error::InitialListFailed(api_error(500, "InternalError")),
+        "Other",
+        format!("watching {resource} failed"),
+      ),
+    ];
+
+    for (source, reason, hint) in cases {
+      let failure = WatchFailure::new(&info(), source);
+      assert_eq!(failure.reason(), reason);
+      assert_eq!(failure.to_string(), hint);
+    }
+  }
+}
@@ -0,0 +1,157 @@
+use fluxcd_meta::{get_reconcile_annotation_value, is_same_reconcile_request};
+use fluxcd_utils_cops::predicate::{Fingerprint, Predicates};
+use kube::{
+  runtime::{controller::ReconcilerAction, reflector::ObjectRef},
+  Resource, ResourceExt,
+};
+use std::{
+  collections::{HashMap, HashSet},
+  hash,
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+/// Scheduled requeues may fire slightly before the recorded due time, as they are not tracked by
+/// the same clock reading.
+const SCHEDULE_SLACK: Duration = Duration::from_secs(1);
+
+struct Seen {
+  fingerprint: Fingerprint,
+  due: Option<Instant>,
+  /// The reconcile request the resource had when it was reconciled, which was handled then.
+  request: Option<String>,
+}
+
+/// Keeps track of the state each resource was last successfully reconciled in, so that events
+/// which do not pass the [Predicates] of the controller can be skipped.
+pub(crate) struct PredicateFilter<R>
+where
+  R: Resource,
+  R::DynamicType: Eq + hash::Hash,
+{
+  predicates: Predicates,
+  seen: Mutex<HashMap<ObjectRef<R>, Seen>>,
+}
+
+impl<R> PredicateFilter<R>
+where
+  R: Resource,
+  R::DynamicType: Eq + hash::Hash + Clone,
+{
+  pub(crate) fn new(predicates: Predicates) -> Self {
+    Self {
+      predicates,
+      seen: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// Whether `resource` can be skipped, because it has not changed since it was last reconciled
+  /// and its next scheduled reconcile is not due yet. Returns the action which keeps the schedule
+  /// of the resource intact.
+  ///
+  /// A `pending` reconcile request, which the status does not record as handled, is never
+  /// skipped, even if the predicates ignore the annotation, unless it was handled by the last
+  /// reconcile and only its status is not up to date yet.
+  pub(crate) fn skip(
+    &self,
+    obj: &ObjectRef<R>,
+    resource: &R,
+    pending: Option<&str>,
+  ) -> Option<ReconcilerAction> {
+    let fingerprint = self.predicates.fingerprint(resource.meta())?;
+    let seen = self.seen.lock().unwrap();
+    let seen = seen
+      .get(obj)
+      .filter(|seen| seen.fingerprint == fingerprint)?;
+
+    if let Some(token) = pending {
+      let handled = seen.request.as_deref();
+      if !handled.is_some_and(|handled| is_same_reconcile_request(handled, token)) {
+        return None;
+      }
+    }
+
+    let now = Instant::now();
+    match seen.due {
+      Some(due) if now + SCHEDULE_SLACK >= due => None,
+      due => Some(ReconcilerAction {
+        requeue_after: due.map(|due| due - now),
+      }),
+    }
+  }
+
+  /// Records a successful reconcile of `resource`, which asked to be requeued as per `action`.
+  pub(crate) fn record(&self, obj: ObjectRef<R>, resource: &R, action: &ReconcilerAction) {
+    if let Some(fingerprint) = self.predicates.fingerprint(resource.meta()) {
+      let due = action.requeue_after.map(|after| Instant::now() + after);
+      let request = get_reconcile_annotation_value(resource.annotations()).map(Into::into);
+      let seen = Seen {
+        fingerprint,
+        due,
+        request,
+      };
+      self.seen.lock().unwrap().insert(obj, seen);
+    }
+  }
+
+  /// Forgets `obj`, so the next event for it is always reconciled. Used after failed reconciles,
+  /// whose retries must not be skipped.
+  pub(crate) fn forget(&self, obj: &ObjectRef<R>) {
+    self.seen.lock().unwrap().remove(obj);
+  }
+
+  pub(crate) fn retain(&self, live: &HashSet<ObjectRef<R>>) {
+    self
+      .seen
+      .lock()
+      .unwrap()
+      .retain(|obj, _| live.contains(obj));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fluxcd_meta::RECONCILE_REQUEST_ANNOTATION;
+  use k8s_openapi::{api::core::v1::ConfigMap, apimachinery::pkg::apis::meta::v1::ObjectMeta};
+
+  fn config_map(token: Option<&str>) -> ConfigMap {
+    let annotations = token.map(|token| {
+      [(RECONCILE_REQUEST_ANNOTATION.to_string(), token.to_string())]
+        .into_iter()
+        .collect()
+    });
+
+    ConfigMap {
+      metadata: ObjectMeta {
+        name: Some("web".into()),
+        namespace: Some("default".into()),
+        generation: Some(1),
+        annotations,
+        ..ObjectMeta::default()
+      },
+      ..ConfigMap::default()
+    }
+  }
+
+  #[test]
+  fn pending_requests_are_not_skipped() {
+    // the annotation is not a predicate, so requests do not change the fingerprint
+    let filter = PredicateFilter::new(Predicates::generation());
+    let handled = config_map(Some("1"));
+    let obj = ObjectRef::from_obj(&handled);
+    let action = ReconcilerAction {
+      requeue_after: Some(Duration::from_secs(600)),
+    };
+    filter.record(obj.clone(), &handled, &action);
+
+    // handled by the last reconcile, the status just does not record it yet
+    assert!(filter.skip(&obj, &handled, Some("1")).is_some());
+    assert!(filter.skip(&obj, &handled, None).is_some());
+
+    let requested = config_map(Some("2"));
+    assert!(filter.skip(&obj, &requested, Some("2")).is_none());
+    // already handled, as recorded in the status
+    assert!(filter.skip(&obj, &requested, None).is_some());
+  }
+}
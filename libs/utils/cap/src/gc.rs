@@ -0,0 +1,343 @@
+use fluxcd_utils_cops::artifact::ArtifactStorage;
+use prometheus::{IntCounter, Opts};
+use std::{
+  collections::HashSet,
+  fs, io,
+  path::Path,
+  sync::{Arc, Mutex},
+  time::{Duration, SystemTime},
+};
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{debug, info, warn};
+
+/// How often the artifact storage is collected, unless configured otherwise.
+pub(crate) const DEFAULT_GC_INTERVAL: Duration = Duration::from_secs(600);
+
+/// How long artifacts which are no longer referenced are kept, unless configured otherwise.
+pub(crate) const DEFAULT_RETENTION_TTL: Duration = Duration::from_secs(60);
+
+/// How many artifacts are kept for each object, unless configured otherwise.
+pub(crate) const DEFAULT_RETENTION_RECORDS: usize = 2;
+
+/// How the artifacts which are not referenced by the status of any object are collected.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct GcSettings {
+  /// How often the storage is collected.
+  pub(crate) interval: Option<Duration>,
+
+  /// How long artifacts which are no longer referenced are kept.
+  pub(crate) ttl: Option<Duration>,
+
+  /// How many artifacts are kept for each object, the referenced ones included.
+  pub(crate) records: Option<usize>,
+}
+
+impl GcSettings {
+  pub(crate) fn validate(&self) -> eyre::Result<()> {
+    if matches!(self.interval, Some(interval) if interval.is_zero()) {
+      eyre::bail!("the artifact GC interval must be at least 1 second");
+    }
+
+    Ok(())
+  }
+}
+
+type ReferencesFn = Box<dyn Fn() -> Option<HashSet<String>> + Send + Sync>;
+
+/// The artifacts referenced by the objects of the controllers of an app, which are never
+/// collected.
+#[derive(Clone, Default)]
+pub(crate) struct ArtifactReferences {
+  kinds: Arc<Mutex<Vec<(String, ReferencesFn)>>>,
+}
+
+impl ArtifactReferences {
+  /// Adds the artifacts of a controller, stored under the directory `kind` of the storage, as
+  /// returned by `references`: their paths, or `None` until the controller knows all of its
+  /// objects.
+  pub(crate) fn add(
+    &self,
+    kind: String,
+    references: impl Fn() -> Option<HashSet<String>> + Send + Sync + 'static,
+  ) {
+    self
+      .kinds
+      .lock()
+      .unwrap()
+      .push((kind, Box::new(references)));
+  }
+
+  /// The directories of the kinds, and the paths of the referenced artifacts, unless one of the
+  /// controllers does not know all of its objects yet.
+  fn collect(&self) -> Option<(HashSet<String>, HashSet<String>)> {
+    let kinds = self.kinds.lock().unwrap();
+    let mut dirs = HashSet::with_capacity(kinds.len());
+    let mut referenced = HashSet::new();
+    for (kind, references) in kinds.iter() {
+      dirs.insert(kind.clone());
+      referenced.extend(references()?);
+    }
+
+    Some((dirs, referenced))
+  }
+}
+
+/// The path of the artifact in the status of `object`, if any, as all of the sources record it.
+pub(crate) fn artifact_path(object: &impl serde::Serialize) -> Option<String> {
+  let object = serde_json::to_value(object).ok()?;
+  let path = object.pointer("/status/artifact/path")?.as_str()?;
+
+  Some(path.trim_start_matches('/').to_string())
+}
+
+struct GcMetrics {
+  removed: IntCounter,
+  reclaimed: IntCounter,
+}
+
+impl GcMetrics {
+  fn new() -> prometheus::Result<Self> {
+    let counter = |name: &str, help: &str| {
+      let opts = Opts::new(name, help).subsystem("storage").namespace("gotk");
+      IntCounter::with_opts(opts)
+    };
+
+    Ok(Self {
+      removed: counter(
+        "gc_removed_artifacts_total",
+        "The number of artifacts removed from the storage by garbage collection.",
+      )?,
+      reclaimed: counter(
+        "gc_reclaimed_bytes_total",
+        "The number of bytes reclaimed in the storage by garbage collection.",
+      )?,
+    })
+  }
+
+  fn register(&self) -> prometheus::Result<()> {
+    let registry = prometheus::default_registry();
+    registry.register(Box::new(self.removed.clone()))?;
+    registry.register(Box::new(self.reclaimed.clone()))?;
+    Ok(())
+  }
+}
+
+/// What a collection removed.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Collected {
+  artifacts: u64,
+  bytes: u64,
+}
+
+/// Periodically removes the artifacts of the storage which are not referenced by any object, for
+/// `run --storage-path`: those of deleted objects, and those an object no longer points at. Of
+/// the artifacts of an object which are not referenced, the most recent ones are kept for a
+/// while, so that consumers can still download the artifact they were told about.
+pub(crate) struct ArtifactCollector {
+  pub(crate) storage: Arc<ArtifactStorage>,
+  pub(crate) references: ArtifactReferences,
+  pub(crate) settings: GcSettings,
+}
+
+impl ArtifactCollector {
+  /// Collects the storage every interval, until `signal` completes.
+  pub(crate) async fn run(self, signal: impl std::future::Future<Output = ()>) {
+    let metrics = GcMetrics::new().expect("metrics are valid");
+    if let Err(error) = metrics.register() {
+      warn!(%error, "failed to register artifact GC metrics");
+    }
+
+    let interval = self.settings.interval.unwrap_or(DEFAULT_GC_INTERVAL);
+    let ttl = self.settings.ttl.unwrap_or(DEFAULT_RETENTION_TTL);
+    let records = self.settings.records.unwrap_or(DEFAULT_RETENTION_RECORDS);
+
+    futures::pin_mut!(signal);
+    let mut ticks = time::interval(interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+      tokio::select! {
+        _ = ticks.tick() => {}
+        _ = &mut signal => break,
+      }
+
+      // an artifact of an object which is not known yet would look unreferenced
+      let (kinds, referenced) = match self.references.collect() {
+        Some(references) => references,
+        None => {
+          debug!("skipping artifact GC until the watch caches have synced");
+          continue;
+        }
+      };
+
+      let root = self.storage.root().to_path_buf();
+      let collected = tokio::task::spawn_blocking(move || {
+        let now = SystemTime::now();
+        let mut collected = Collected::default();
+        for kind in &kinds {
+          let retention = Retention {
+            referenced: &referenced,
+            ttl,
+            records,
+            now,
+          };
+          collect(&root, kind, &retention, &mut collected)?;
+        }
+
+        io::Result::Ok(collected)
+      })
+      .await;
+
+      match collected {
+        Ok(Ok(collected)) => {
+          metrics.removed.inc_by(collected.artifacts);
+          metrics.reclaimed.inc_by(collected.bytes);
+          if collected.artifacts > 0 {
+            info!(
+              artifacts = collected.artifacts,
+              bytes = collected.bytes,
+              "collected unreferenced artifacts"
+            );
+          }
+        }
+        Ok(Err(error)) => warn!(%error, "failed to collect artifacts"),
+        Err(error) => warn!(%error, "artifact GC panicked"),
+      }
+    }
+  }
+}
+
+struct Retention<'a> {
+  referenced: &'a HashSet<String>,
+  ttl: Duration,
+  records: usize,
+  now: SystemTime,
+}
+
+/// Collects the artifacts in the directory `path` of the storage at `root`, and in those below
+/// it, removing the directories left empty.
+fn collect(
+  root: &Path,
+  path: &str,
+  retention: &Retention<'_>,
+  collected: &mut Collected,
+) -> io::Result<()> {
+  let dir = root.join(path);
+  let entries = match fs::read_dir(&dir) {
+    Ok(entries) => entries,
+    Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+    Err(error) => return Err(error),
+  };
+
+  let mut files = Vec::new();
+  for entry in entries {
+    let entry = entry?;
+    let name = entry.file_name().to_string_lossy().into_owned();
+    // artifacts being written
+    if name.starts_with('.') {
+      continue;
+    }
+
+    let file_type = entry.file_type()?;
+    if file_type.is_dir() {
+      collect(root, &format!("{path}/{name}"), retention, collected)?;
+    } else if file_type.is_file() {
+      let metadata = entry.metadata()?;
+      let modified = metadata.modified()?;
+      files.push((format!("{path}/{name}"), modified, metadata.len()));
+    }
+  }
+
+  // the most recent artifacts first
+  files.sort_by(|(_, a, _), (_, b, _)| b.cmp(a));
+  for (i, (file, modified, size)) in files.into_iter().enumerate() {
+    if retention.referenced.contains(&file) {
+      continue;
+    }
+
+    let age = retention.now.duration_since(modified).unwrap_or_default();
+    if i >= retention.records || age > retention.ttl {
+      // an artifact which cannot be removed does not keep the others from being collected
+      match fs::remove_file(root.join(&file)) {
+        Ok(()) => {}
+        Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+        Err(error) => {
+          warn!(artifact = %file, %error, "failed to remove unreferenced artifact");
+          continue;
+        }
+      }
+
+      debug!(artifact = %file, "removed unreferenced artifact");
+      collected.artifacts += 1;
+      collected.bytes += size;
+    }
+  }
+
+  // only fails if the directory is not empty, or already gone
+  let _ = fs::remove_dir(&dir);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn collects_unreferenced_artifacts() {
+    let root = std::env::temp_dir().join(format!("fluxcd-gc-{}", std::process::id()));
+    let write = |path: &str, content: &str| {
+      let file = root.join(path);
+      fs::create_dir_all(file.parent().unwrap()).unwrap();
+      fs::write(file, content).unwrap();
+    };
+    write("bucket/default/live/bucket.tar.gz", "live");
+    write("bucket/default/deleted/bucket.tar.gz", "deleted");
+    write("bucket/default/deleted/.bucket.tar.gz.tmp", "partial");
+    write("other/default/name/file", "other");
+
+    let referenced = HashSet::from(["bucket/default/live/bucket.tar.gz".to_string()]);
+    let mut retention = Retention {
+      referenced: &referenced,
+      ttl: Duration::from_secs(60),
+      records: 2,
+      now: SystemTime::now(),
+    };
+    let mut collected = Collected::default();
+
+    // unreferenced artifacts are kept for a while
+    collect(&root, "bucket", &retention, &mut collected).unwrap();
+    assert_eq!(collected, Collected::default());
+
+    retention.now += Duration::from_secs(120);
+    collect(&root, "bucket", &retention, &mut collected).unwrap();
+    let exists = |path: &str| root.join(path).exists();
+    let result = (
+      exists("bucket/default/live/bucket.tar.gz"),
+      exists("bucket/default/deleted/bucket.tar.gz"),
+      exists("bucket/default/deleted/.bucket.tar.gz.tmp"),
+      exists("other/default/name/file"),
+    );
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(
+      collected,
+      Collected {
+        artifacts: 1,
+        bytes: 7
+      }
+    );
+    assert_eq!(result, (true, false, true, true));
+  }
+
+  #[test]
+  fn finds_the_artifact_of_sources() {
+    let object = serde_json::json!({
+      "metadata": { "name": "a" },
+      "status": { "artifact": { "path": "bucket/default/a/bucket.tar.gz" } },
+    });
+    assert_eq!(
+      artifact_path(&object).as_deref(),
+      Some("bucket/default/a/bucket.tar.gz")
+    );
+    assert_eq!(artifact_path(&serde_json::json!({ "status": {} })), None);
+  }
+}
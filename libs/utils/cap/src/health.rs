@@ -0,0 +1,252 @@
+use crate::WatchFailure;
+use k8s_openapi::{apimachinery::pkg::apis::meta::v1::Time, chrono::Utc};
+use kube::{
+  api::{ListParams, Patch, PatchParams},
+  runtime::reflector::Store,
+  Api, Client, CustomResource, CustomResourceExt, Resource,
+};
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use std::{
+  fmt, hash,
+  sync::{
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
+  },
+  time::{Duration, SystemTime},
+};
+use tracing::{debug, warn};
+
+/// How often the `ControllerStatus` object is updated.
+pub(crate) const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the watch cache of a controller is checked until it has synced.
+const SYNC_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Health of a controller binary, maintained by the binary itself so it can be inspected from
+/// within the cluster.
+#[derive(CustomResource, Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[kube(
+  group = "runtime.fluxcd.yolodev.io",
+  version = "v1alpha1",
+  kind = "ControllerStatus",
+  status = "ControllerStatusStatus"
+)]
+pub struct ControllerStatusSpec {}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ControllerStatusStatus {
+  /// Version of the controller binary.
+  #[serde(default)]
+  pub version: String,
+
+  /// Identity of the instance currently running the controllers.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub leader: Option<String>,
+
+  /// Health of the controller of every kind served by the binary.
+  #[serde(default)]
+  pub kinds: Vec<KindStatus>,
+
+  /// The last time this status was updated.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_update_time: Option<Time>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct KindStatus {
+  /// Full name of the kind, e.g. `source.fluxcd.yolodev.io/HttpEndpoint`.
+  pub kind: String,
+
+  /// Number of objects of the kind in the watch cache.
+  pub objects: u64,
+
+  /// Number of reconciles completed since the previous update of the status.
+  pub reconciles: u64,
+
+  /// Number of those reconciles which failed.
+  pub failures: u64,
+
+  /// The last time an object of the kind was reconciled successfully.
+  #[serde(skip_serializing_if = "Option::is_none", default)]
+  pub last_sync_time: Option<Time>,
+}
+
+/// The most recent failure of the watch of a kind, as reported by the probes.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LastWatchFailure {
+  /// The class of the failure, like `Forbidden`.
+  pub(crate) reason: &'static str,
+
+  /// What the operator can do about it.
+  pub(crate) message: String,
+
+  pub(crate) time: Time,
+}
+
+/// Health counters of the controller of a single kind.
+#[derive(Default)]
+pub(crate) struct KindHealth {
+  objects: AtomicUsize,
+  reconciles: AtomicU64,
+  failures: AtomicU64,
+  last_sync: Mutex<Option<SystemTime>>,
+  last_watch_failure: Mutex<Option<LastWatchFailure>>,
+  synced: AtomicBool,
+  stopped: AtomicBool,
+}
+
+impl KindHealth {
+  pub(crate) fn set_objects(&self, count: usize) {
+    self.objects.store(count, Ordering::Relaxed);
+  }
+
+  pub(crate) fn record_success(&self) {
+    self.reconciles.fetch_add(1, Ordering::Relaxed);
+    *self.last_sync.lock().unwrap() = Some(SystemTime::now());
+  }
+
+  pub(crate) fn record_failure(&self) {
+    self.reconciles.fetch_add(1, Ordering::Relaxed);
+    self.failures.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Records that the watch of the controller failed, which the probes report until it fails
+  /// again.
+  pub(crate) fn record_watch_failure(&self, failure: &WatchFailure) {
+    *self.last_watch_failure.lock().unwrap() = Some(LastWatchFailure {
+      reason: failure.reason(),
+      message: failure.to_string(),
+      time: Time(Utc::now()),
+    });
+  }
+
+  pub(crate) fn last_watch_failure(&self) -> Option<LastWatchFailure> {
+    self.last_watch_failure.lock().unwrap().clone()
+  }
+
+  /// Records that the watch cache of the controller has synced, after which it is ready.
+  pub(crate) fn record_synced(&self) {
+    self.synced.store(true, Ordering::Relaxed);
+  }
+
+  pub(crate) fn is_synced(&self) -> bool {
+    self.synced.load(Ordering::Relaxed)
+  }
+
+  /// Records that the controller stopped while the app was still running, after which the app is
+  /// unhealthy.
+  pub(crate) fn record_stopped(&self) {
+    self.stopped.store(true, Ordering::Relaxed);
+  }
+
+  pub(crate) fn is_stopped(&self) -> bool {
+    self.stopped.load(Ordering::Relaxed)
+  }
+
+  /// The status of the kind, resetting the reconcile counters.
+  fn take(&self, kind: String) -> KindStatus {
+    KindStatus {
+      kind,
+      objects: self.objects.load(Ordering::Relaxed) as u64,
+      reconciles: self.reconciles.swap(0, Ordering::Relaxed),
+      failures: self.failures.swap(0, Ordering::Relaxed),
+      last_sync_time: self.last_sync.lock().unwrap().map(|t| Time(t.into())),
+    }
+  }
+}
+
+/// Waits for the watch cache `store` of a controller to sync, and records it in `health`. The
+/// cache has synced once it holds objects, or once `api` lists none matching `labels` (the objects
+/// the controller watches).
+pub(crate) async fn wait_for_sync<R>(
+  store: Store<R>,
+  api: Api<R>,
+  labels: Option<String>,
+  health: Arc<KindHealth>,
+) where
+  R: Resource + Clone + fmt::Debug + DeserializeOwned + 'static,
+  R::DynamicType: Eq + hash::Hash + Clone,
+{
+  let mut params = ListParams::default().limit(1);
+  if let Some(labels) = &labels {
+    params = params.labels(labels);
+  }
+  let mut ticks = tokio::time::interval(SYNC_CHECK_INTERVAL);
+  loop {
+    ticks.tick().await;
+    if !store.state().is_empty() {
+      break;
+    }
+
+    match api.list(&params).await {
+      Ok(list) if list.items.is_empty() => break,
+      Ok(_) => {}
+      Err(error) => debug!(%error, "failed to check whether the watch cache has synced"),
+    }
+  }
+
+  health.record_synced();
+}
+
+/// Whether `name` refers to the `ControllerStatus` kind, either as `kind` or as `group/kind`.
+pub(crate) fn matches(name: &str) -> bool {
+  let crd = ControllerStatus::crd();
+  let kind = &crd.spec.names.kind;
+  let group = &crd.spec.group;
+  *kind == name || format!("{group}/{kind}") == name
+}
+
+/// Maintains the `ControllerStatus` object named `name`, updating it every [REPORT_INTERVAL]
+/// until `stop` resolves, and once more right after.
+pub(crate) async fn report(
+  client: Client,
+  name: &str,
+  version: &str,
+  leader: Option<String>,
+  kinds: Vec<(String, Arc<KindHealth>)>,
+  stop: impl std::future::Future<Output = ()>,
+) {
+  let api = Api::<ControllerStatus>::all(client);
+  let params = PatchParams::apply(name).force();
+  let object = ControllerStatus::new(name, ControllerStatusSpec::default());
+  if let Err(error) = api.patch(name, &params, &Patch::Apply(&object)).await {
+    warn!(%error, "failed to create ControllerStatus, not reporting status");
+    return;
+  }
+
+  let update = || async {
+    let status = ControllerStatusStatus {
+      version: version.into(),
+      leader: leader.clone(),
+      kinds: kinds
+        .iter()
+        .map(|(kind, health)| health.take(kind.clone()))
+        .collect(),
+      last_update_time: Some(Time(Utc::now())),
+    };
+
+    let patch = json!({ "status": status });
+    if let Err(error) = api
+      .patch_status(name, &PatchParams::default(), &Patch::Merge(&patch))
+      .await
+    {
+      warn!(%error, "failed to update ControllerStatus");
+    }
+  };
+
+  tokio::pin!(stop);
+  let mut ticks = tokio::time::interval(REPORT_INTERVAL);
+  loop {
+    tokio::select! {
+      _ = ticks.tick() => update().await,
+      _ = &mut stop => break,
+    }
+  }
+
+  update().await;
+}
@@ -0,0 +1,206 @@
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use kube::{
+  api::{Patch, PatchParams},
+  Api, Client, ResourceExt,
+};
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+/// How many unchanged lines are printed around the changes of a CRD.
+const DIFF_CONTEXT: usize = 2;
+
+/// How the CRDs are applied.
+pub(crate) struct ApplyOptions {
+  /// Field manager of the applied CRDs, the name of the app.
+  pub(crate) field_manager: String,
+
+  /// Only print the changes, through server-side dry runs.
+  pub(crate) dry_run: bool,
+
+  /// How long to wait for the CRDs to be established.
+  pub(crate) timeout: Duration,
+}
+
+/// Installs or upgrades `crds` in the cluster, for `crd apply`: every CRD is server-side applied,
+/// after printing how it changes, and the command waits for all of them to be established, so that
+/// objects of their kinds can be created right after.
+pub(crate) async fn apply(
+  crds: Vec<CustomResourceDefinition>,
+  options: ApplyOptions,
+) -> eyre::Result<()> {
+  let client = Client::try_default().await?;
+  let api = Api::<CustomResourceDefinition>::all(client);
+  let params = PatchParams::apply(&options.field_manager).force();
+  let dry_run = PatchParams {
+    dry_run: true,
+    ..params.clone()
+  };
+
+  let mut applied = Vec::new();
+  for crd in &crds {
+    let name = crd.name();
+    let current = match api.get(&name).await {
+      Ok(current) => Some(current),
+      Err(kube::Error::Api(e)) if e.code == 404 => None,
+      Err(e) => eyre::bail!("{name}: {e}"),
+    };
+
+    // diffed against a dry run, so that the defaults of the API server are not reported as changes
+    let desired = api
+      .patch(&name, &dry_run, &Patch::Apply(crd))
+      .await
+      .map_err(|e| eyre::eyre!("{name}: {e}"))?;
+    let changes = match &current {
+      None => {
+        println!("{name}: created{}", dry_run_suffix(&options));
+        applied.push(name);
+        continue;
+      }
+      Some(current) => diff(&spec_yaml(current)?, &spec_yaml(&desired)?),
+    };
+
+    if changes.is_empty() {
+      println!("{name}: unchanged");
+      continue;
+    }
+
+    println!("{name}: configured{}", dry_run_suffix(&options));
+    for line in changes {
+      println!("  {line}");
+    }
+    applied.push(name);
+  }
+
+  if options.dry_run {
+    return Ok(());
+  }
+
+  for name in &applied {
+    let crd = crds.iter().find(|crd| crd.name() == *name);
+    let crd = crd.expect("applied CRDs are of the given ones");
+    api.patch(name, &params, &Patch::Apply(crd)).await?;
+  }
+
+  let deadline = Instant::now() + options.timeout;
+  for name in &applied {
+    loop {
+      let crd = api.get(name).await?;
+      if is_established(&crd) {
+        println!("{name}: established");
+        break;
+      }
+      if Instant::now() >= deadline {
+        eyre::bail!("{name} was not established in time, check its status");
+      }
+
+      time::sleep(Duration::from_secs(1)).await;
+    }
+  }
+
+  Ok(())
+}
+
+fn dry_run_suffix(options: &ApplyOptions) -> &'static str {
+  if options.dry_run {
+    " (dry run)"
+  } else {
+    ""
+  }
+}
+
+/// Whether the API server serves the kind of `crd`.
+fn is_established(crd: &CustomResourceDefinition) -> bool {
+  let conditions = crd.status.as_ref().and_then(|s| s.conditions.as_ref());
+  conditions
+    .into_iter()
+    .flatten()
+    .any(|c| c.type_ == "Established" && c.status == "True")
+}
+
+fn spec_yaml(crd: &CustomResourceDefinition) -> eyre::Result<String> {
+  Ok(serde_yaml::to_string(&crd.spec)?)
+}
+
+/// The lines which differ between `old` and `new`, prefixed with `-` or `+`, along with
+/// [DIFF_CONTEXT] unchanged lines around them. Distant changes are separated by `...`.
+fn diff(old: &str, new: &str) -> Vec<String> {
+  let old = old.lines().collect::<Vec<_>>();
+  let new = new.lines().collect::<Vec<_>>();
+
+  // the length of the longest common subsequence of the lines from `old[i..]` and `new[j..]`
+  let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+  for i in (0..old.len()).rev() {
+    for j in (0..new.len()).rev() {
+      common[i][j] = if old[i] == new[j] {
+        common[i + 1][j + 1] + 1
+      } else {
+        common[i + 1][j].max(common[i][j + 1])
+      };
+    }
+  }
+
+  let mut lines = Vec::new();
+  let (mut i, mut j) = (0, 0);
+  while i < old.len() || j < new.len() {
+    if i < old.len() && j < new.len() && old[i] == new[j] {
+      lines.push((' ', old[i]));
+      i += 1;
+      j += 1;
+    } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+      lines.push(('-', old[i]));
+      i += 1;
+    } else {
+      lines.push(('+', new[j]));
+      j += 1;
+    }
+  }
+
+  let changed = lines
+    .iter()
+    .enumerate()
+    .filter(|(_, (sign, _))| *sign != ' ')
+    .map(|(index, _)| index)
+    .collect::<Vec<_>>();
+  let shown = |index: usize| {
+    changed
+      .iter()
+      .any(|&c| index + DIFF_CONTEXT >= c && index <= c + DIFF_CONTEXT)
+  };
+
+  let mut printed = Vec::new();
+  let mut skipped = false;
+  for (index, (sign, line)) in lines.into_iter().enumerate() {
+    if !shown(index) {
+      skipped = true;
+      continue;
+    }
+    if skipped && !printed.is_empty() {
+      printed.push("...".to_string());
+    }
+
+    skipped = false;
+    printed.push(format!("{sign} {line}"));
+  }
+
+  printed
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn identical_documents_have_no_diff() {
+    assert!(diff("a\nb\n", "a\nb\n").is_empty());
+  }
+
+  #[test]
+  fn diffs_show_changes_with_context() {
+    let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+    let new = "a\nb\nc\nD\ne\nf\ng\nh\ni\nj\n";
+    assert_eq!(
+      diff(old, new),
+      ["  b", "  c", "- d", "+ D", "  e", "  f", "...", "  h", "  i", "+ j"]
+    );
+  }
+}
@@ -0,0 +1,326 @@
+use k8s_openapi::{
+  api::coordination::v1::{Lease, LeaseSpec},
+  apimachinery::pkg::apis::meta::v1::MicroTime,
+  chrono::{DateTime, Duration as ChronoDuration, Utc},
+};
+use kube::{
+  api::{ObjectMeta, PostParams},
+  Api, Client,
+};
+use std::time::Duration;
+use tokio::{
+  sync::watch,
+  time::{self, Instant},
+};
+use tracing::{debug, info, warn};
+
+/// How long a lease stays with its holder without being renewed, before other instances may take
+/// it over.
+const LEASE_DURATION: Duration = Duration::from_secs(15);
+
+/// How long the leader keeps trying to renew its lease before giving up on it, which is shorter
+/// than [LEASE_DURATION] so that it stops before another instance may take over.
+const RENEW_DEADLINE: Duration = Duration::from_secs(10);
+
+/// How often candidates try to acquire the lease, and the leader renews it.
+const RETRY_PERIOD: Duration = Duration::from_secs(2);
+
+/// Elects a single instance of an app to run the controllers, through a `coordination.k8s.io/v1`
+/// Lease that the leader keeps renewing. Other instances wait for the lease to expire or be
+/// released, so that objects are not reconciled by several instances at once.
+pub(crate) struct LeaderElection {
+  api: Api<Lease>,
+  lease: String,
+  identity: String,
+  leading: watch::Sender<bool>,
+  elected: watch::Receiver<bool>,
+}
+
+impl LeaderElection {
+  /// Elects the leader through the Lease named `lease`, in the namespace of the client.
+  pub(crate) fn new(client: Client, lease: impl Into<String>, identity: impl Into<String>) -> Self {
+    let (leading, elected) = watch::channel(false);
+
+    Self {
+      api: Api::default_namespaced(client),
+      lease: lease.into(),
+      identity: identity.into(),
+      leading,
+      elected,
+    }
+  }
+
+  /// The identity this instance holds the lease under.
+  pub(crate) fn identity(&self) -> &str {
+    &self.identity
+  }
+
+  /// Waits until this instance is elected leader.
+  pub(crate) async fn acquire(&self) {
+    info!(lease = %self.lease, identity = %self.identity, "waiting to be elected leader");
+    loop {
+      match self.try_claim().await {
+        Ok(true) => break,
+        Ok(false) => {}
+        Err(error) => warn!(%error, lease = %self.lease, "failed to acquire the lease"),
+      }
+
+      time::sleep(RETRY_PERIOD).await;
+    }
+
+    info!(lease = %self.lease, "elected leader");
+    let _ = self.leading.send(true);
+  }
+
+  /// Whether this instance currently holds the lease.
+  pub(crate) fn is_leader(&self) -> bool {
+    *self.elected.borrow()
+  }
+
+  /// Waits until this instance is elected leader, without trying to become it.
+  pub(crate) async fn elected(&self) {
+    let mut elected = self.elected.clone();
+    while !*elected.borrow() {
+      if elected.changed().await.is_err() {
+        return;
+      }
+    }
+  }
+
+  /// Keeps renewing the lease, and returns once it is lost: either because another instance took
+  /// it over, or because it could not be renewed within [RENEW_DEADLINE]. Requests which hang are
+  /// cut off at the deadline, rather than keeping the leadership past the expiry of the lease.
+  pub(crate) async fn hold(&self) {
+    let mut renewed = Instant::now();
+    loop {
+      time::sleep(RETRY_PERIOD).await;
+      let remaining = RENEW_DEADLINE.saturating_sub(renewed.elapsed());
+      match time::timeout(remaining, self.try_claim()).await {
+        Ok(Ok(true)) => renewed = Instant::now(),
+        Ok(Ok(false)) => break,
+        Ok(Err(error)) if renewed.elapsed() >= RENEW_DEADLINE => {
+          warn!(%error, lease = %self.lease, "failed to renew the lease in time");
+          break;
+        }
+        Ok(Err(error)) => debug!(%error, lease = %self.lease, "failed to renew the lease"),
+        Err(_) => {
+          warn!(lease = %self.lease, "timed out renewing the lease");
+          break;
+        }
+      }
+    }
+
+    warn!(lease = %self.lease, "lost leadership");
+    let _ = self.leading.send(false);
+  }
+
+  /// Gives up the lease, so that another instance can take over right away instead of waiting for
+  /// the lease to expire.
+  pub(crate) async fn release(&self) {
+    let lease = match self.api.get(&self.lease).await {
+      Ok(lease) => lease,
+      Err(error) => {
+        warn!(%error, lease = %self.lease, "failed to release the lease");
+        return;
+      }
+    };
+
+    let spec = match released(lease.spec.as_ref(), &self.identity, Utc::now()) {
+      Some(spec) => spec,
+      None => return,
+    };
+
+    let lease = Lease {
+      spec: Some(spec),
+      ..lease
+    };
+    match self
+      .api
+      .replace(&self.lease, &PostParams::default(), &lease)
+      .await
+    {
+      Ok(_) => info!(lease = %self.lease, "released the lease"),
+      Err(error) => warn!(%error, lease = %self.lease, "failed to release the lease"),
+    }
+  }
+
+  /// Acquires or renews the lease, returning whether this instance holds it.
+  async fn try_claim(&self) -> Result<bool, kube::Error> {
+    let now = Utc::now();
+    let lease = match self.api.get(&self.lease).await {
+      Ok(lease) => lease,
+      Err(kube::Error::Api(error)) if error.code == 404 => {
+        let lease = Lease {
+          metadata: ObjectMeta {
+            name: Some(self.lease.clone()),
+            ..Default::default()
+          },
+          spec: claimed(None, &self.identity, now),
+        };
+
+        return match self.api.create(&PostParams::default(), &lease).await {
+          Ok(_) => Ok(true),
+          // another instance created it first
+          Err(kube::Error::Api(error)) if error.code == 409 => Ok(false),
+          Err(error) => Err(error),
+        };
+      }
+      Err(error) => return Err(error),
+    };
+
+    let spec = match claimed(lease.spec.as_ref(), &self.identity, now) {
+      Some(spec) => spec,
+      None => return Ok(false),
+    };
+
+    // the resource version of the lease makes this fail if another instance updated it meanwhile
+    let lease = Lease {
+      spec: Some(spec),
+      ..lease
+    };
+    match self
+      .api
+      .replace(&self.lease, &PostParams::default(), &lease)
+      .await
+    {
+      Ok(_) => Ok(true),
+      Err(kube::Error::Api(error)) if error.code == 409 => Ok(false),
+      Err(error) => Err(error),
+    }
+  }
+}
+
+/// The identity of this instance: the name of its pod, or otherwise its host name and process id.
+pub(crate) fn identity(instance: Option<&str>) -> String {
+  if let Some(instance) = instance {
+    return instance.to_string();
+  }
+
+  let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "localhost".to_string());
+  format!("{host}_{}", std::process::id())
+}
+
+/// The lease spec claiming the lease for `identity` at `now`, or `None` if another instance
+/// holds a lease which has not expired yet.
+fn claimed(spec: Option<&LeaseSpec>, identity: &str, now: DateTime<Utc>) -> Option<LeaseSpec> {
+  let spec = spec.cloned().unwrap_or_default();
+  let holder = spec.holder_identity.as_deref().filter(|h| !h.is_empty());
+  let held = holder == Some(identity);
+  if holder.is_some() && !held && !is_expired(&spec, now) {
+    return None;
+  }
+
+  let transitions = spec.lease_transitions.unwrap_or_default();
+  Some(LeaseSpec {
+    holder_identity: Some(identity.to_string()),
+    lease_duration_seconds: Some(LEASE_DURATION.as_secs() as i32),
+    acquire_time: if held {
+      spec.acquire_time
+    } else {
+      Some(MicroTime(now))
+    },
+    renew_time: Some(MicroTime(now)),
+    lease_transitions: Some(if held || holder.is_none() {
+      transitions
+    } else {
+      transitions + 1
+    }),
+  })
+}
+
+/// The lease spec giving up the lease of `identity`, or `None` if it does not hold the lease.
+fn released(spec: Option<&LeaseSpec>, identity: &str, now: DateTime<Utc>) -> Option<LeaseSpec> {
+  let spec = spec?;
+  if spec.holder_identity.as_deref() != Some(identity) {
+    return None;
+  }
+
+  Some(LeaseSpec {
+    holder_identity: None,
+    lease_duration_seconds: Some(1),
+    renew_time: Some(MicroTime(now)),
+    ..spec.clone()
+  })
+}
+
+fn is_expired(spec: &LeaseSpec, now: DateTime<Utc>) -> bool {
+  let renewed = match spec.renew_time.as_ref().or(spec.acquire_time.as_ref()) {
+    Some(MicroTime(renewed)) => *renewed,
+    None => return true,
+  };
+
+  let duration = spec
+    .lease_duration_seconds
+    .map_or(LEASE_DURATION.as_secs() as i64, i64::from);
+  renewed + ChronoDuration::seconds(duration) <= now
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use k8s_openapi::chrono::TimeZone;
+
+  fn time(secs: i64) -> DateTime<Utc> {
+    Utc.timestamp(secs, 0)
+  }
+
+  #[test]
+  fn lease_held_by_another_is_only_taken_once_expired() {
+    let held = claimed(None, "a", time(0)).unwrap();
+    assert_eq!(held.lease_transitions, Some(0));
+    assert_eq!(claimed(Some(&held), "b", time(10)), None);
+
+    let taken = claimed(Some(&held), "b", time(15)).unwrap();
+    assert_eq!(taken.holder_identity.as_deref(), Some("b"));
+    assert_eq!(taken.acquire_time, Some(MicroTime(time(15))));
+    assert_eq!(taken.lease_transitions, Some(1));
+  }
+
+  #[test]
+  fn renewing_keeps_acquire_time() {
+    let held = claimed(None, "a", time(0)).unwrap();
+    let renewed = claimed(Some(&held), "a", time(5)).unwrap();
+    assert_eq!(renewed.acquire_time, Some(MicroTime(time(0))));
+    assert_eq!(renewed.renew_time, Some(MicroTime(time(5))));
+    assert_eq!(renewed.lease_transitions, Some(0));
+  }
+
+  #[test]
+  fn released_lease_is_taken_over_right_away() {
+    let held = claimed(None, "a", time(0)).unwrap();
+    assert_eq!(released(Some(&held), "b", time(1)), None);
+
+    let released = released(Some(&held), "a", time(1)).unwrap();
+    assert_eq!(released.holder_identity, None);
+    let taken = claimed(Some(&released), "b", time(1)).unwrap();
+    assert_eq!(taken.holder_identity.as_deref(), Some("b"));
+  }
+
+  #[test]
+  fn leases_expire_after_their_duration() {
+    let held = claimed(None, "a", time(0)).unwrap();
+    assert_eq!(claimed(Some(&held), "b", time(14)), None);
+    assert!(claimed(Some(&held), "b", time(15)).is_some());
+
+    // the duration the lease was written with wins over the default
+    let long = LeaseSpec {
+      lease_duration_seconds: Some(60),
+      ..held.clone()
+    };
+    assert_eq!(claimed(Some(&long), "b", time(59)), None);
+    assert!(claimed(Some(&long), "b", time(60)).is_some());
+
+    // a lease which was never renewed is expired
+    let stale = LeaseSpec {
+      holder_identity: Some("a".into()),
+      ..Default::default()
+    };
+    assert!(claimed(Some(&stale), "b", time(0)).is_some());
+
+    // a released lease expires a second after its release
+    let released = released(Some(&held), "a", time(5)).unwrap();
+    assert_eq!(released.lease_duration_seconds, Some(1));
+    assert!(!is_expired(&released, time(5)));
+    assert!(is_expired(&released, time(6)));
+  }
+}
@@ -1,71 +1,248 @@
+mod budget;
+mod bundle;
 mod cli;
+mod client;
+mod debug;
+mod discovery;
+mod failure;
+mod filter;
+mod gc;
+mod health;
+mod install;
+mod leader;
+mod manifests;
+mod migrate;
+mod namespaces;
+mod overdue;
+mod probe;
+mod problem;
+mod pushgateway;
+mod replay;
+mod roles;
+mod schedule;
+mod scrape;
+mod shutdown;
 mod signals;
+mod sink;
+mod status;
+mod storage;
+mod suspend;
+mod tasks;
+mod uninstall;
+mod watch;
 
+use budget::ReconcileBudget;
 use eyre::Report;
-use futures::{Future, Stream, StreamExt, TryFutureExt};
-use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use filter::PredicateFilter;
+use fluxcd_meta::Reason;
+use fluxcd_utils_cops::{
+  artifact::ArtifactStorage,
+  backoff::BackoffTracker,
+  context::{ReconcileAborted, ReconcileCtx},
+  events::{EventForwarder, EventRecorder},
+  metrics::ReconcileResult,
+  openapi::{OpenApiSchemas, SchemaViolation},
+  policy::{PolicySet, PolicyViolation},
+  ratelimit::RateLimiter,
+  rbac::Permissions,
+  requirements::Requirements,
+};
+use fluxcd_utils_kube::{ConfigMapResolver, SecretResolver};
+use futures::{
+  future::{self, LocalBoxFuture},
+  stream, Future, FutureExt, Stream, StreamExt,
+};
+use gc::ArtifactReferences;
+use health::KindHealth;
+use k8s_openapi::{
+  apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition,
+  apimachinery::pkg::apis::meta::v1::Time, chrono::Utc,
+};
 use kube::{
-  core::DynamicObject,
+  core::{ApiResource, DynamicObject},
   runtime::{
     controller::{self, Context, ReconcilerAction},
+    events::{Event, EventType, Reporter},
     reflector::ObjectRef,
-    watcher,
   },
-  Client, CustomResourceExt, Resource,
+  Api, Client, CustomResourceExt, Resource,
 };
-use serde::Deserialize;
-use std::{fmt, hash, pin::Pin, sync::Arc};
-use tokio::runtime::Runtime;
-use tracing::{info, warn};
+use namespaces::NamespaceCache;
+use overdue::ReconcileLog;
+use prometheus::core::Collector;
+use replay::{EventLog, Outcome};
+use schedule::{RequeueReason, Schedule};
+use scrape::AppMetrics;
+use serde::{Deserialize, Serialize};
+use shutdown::InFlight;
+use sink::EventSink;
+use status::{StatusBatcher, StatusWriter};
+use std::{fmt, hash, num::NonZeroUsize, pin::Pin, sync::Arc, time::Duration};
+use suspend::IgnoredRequests;
+use tokio::{runtime::Runtime, sync::Semaphore, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, field, info, warn, Instrument, Span};
+use watch::WatchSettings;
 
+pub use failure::WatchFailure;
+pub use fluxcd_utils_cops::archive;
+pub use fluxcd_utils_cops::artifact;
+pub use fluxcd_utils_cops::backoff;
+pub use fluxcd_utils_cops::batching;
+pub use fluxcd_utils_cops::canonical;
+pub use fluxcd_utils_cops::context;
+pub use fluxcd_utils_cops::events;
+pub use fluxcd_utils_cops::fanout;
+pub use fluxcd_utils_cops::http;
 pub use fluxcd_utils_cops::metrics;
+pub use fluxcd_utils_cops::openapi;
+pub use fluxcd_utils_cops::output;
+pub use fluxcd_utils_cops::owned;
+pub use fluxcd_utils_cops::policy;
+pub use fluxcd_utils_cops::predicate;
+pub use fluxcd_utils_cops::ratelimit;
+pub use fluxcd_utils_cops::rbac;
+pub use fluxcd_utils_cops::references;
+pub use fluxcd_utils_cops::requeue;
+pub use fluxcd_utils_cops::requirements;
+pub use fluxcd_utils_cops::secrets;
 pub use fluxcd_utils_cops::Controller;
+pub use fluxcd_utils_macros_controller::flux_controller;
+pub use health::{ControllerStatus, ControllerStatusSpec, ControllerStatusStatus, KindStatus};
+pub use replay::{replay, RecordedReconcile, Replayed};
 
-pub struct ReportWrapper(Report);
+pub struct ReportWrapper {
+  report: Report,
+  /// The object whose reconcile failed, for the error policy to record when it is retried.
+  object: ObjectRef<DynamicObject>,
+}
 
 impl std::fmt::Display for ReportWrapper {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    <Report as std::fmt::Display>::fmt(&self.0, f)
+    <Report as std::fmt::Display>::fmt(&self.report, f)
   }
 }
 
 impl std::fmt::Debug for ReportWrapper {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    <Report as std::fmt::Debug>::fmt(&self.0, f)
+    <Report as std::fmt::Debug>::fmt(&self.report, f)
   }
 }
 
 impl std::error::Error for ReportWrapper {
   fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-    self.0.source()
+    self.report.source()
   }
 }
 
 impl ReportWrapper {
   pub fn into_inner(self) -> Report {
-    self.0
+    self.report
+  }
+}
+
+/// Aborts a background task once dropped, tying its lifetime to whatever owns the guard.
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+  fn drop(&mut self) {
+    self.0.abort();
   }
 }
 
 type ShutdownSignalFuture = Pin<Box<dyn Future<Output = ()> + Send + Sync + 'static>>;
 type ReconcilerSuccessResult = (ObjectRef<DynamicObject>, ReconcilerAction);
-type ReconcilerErrorResult = controller::Error<ReportWrapper, watcher::Error>;
+type ReconcilerErrorResult = Box<controller::Error<ReportWrapper, WatchFailure>>;
 type ReconcilerResult = Result<ReconcilerSuccessResult, ReconcilerErrorResult>;
 type ReconcilerStream<'a> = Pin<Box<dyn Stream<Item = ReconcilerResult> + 'a>>;
 
 type DynControllerCrd<'a> = Box<dyn FnOnce() -> CustomResourceDefinition + 'a>;
+type DynServiceFactory<'a> =
+  Box<dyn FnOnce(Client, ShutdownSignalFuture) -> LocalBoxFuture<'a, eyre::Result<()>> + 'a>;
 type DynControllerFactory<'a> =
-  Box<dyn FnOnce(Client, ShutdownSignalFuture) -> ReconcilerStream<'a> + 'a>;
+  Box<dyn FnOnce(ControllerEnv, ShutdownSignalFuture) -> ReconcilerStream<'a> + 'a>;
 
+/// How often resources in ignored namespaces are checked again.
+const IGNORED_RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What the controllers of an app share while running.
+#[derive(Clone)]
+struct ControllerEnv {
+  client: Client,
+  reporter: Reporter,
+  namespaces: NamespaceCache,
+  policies: Arc<PolicySet>,
+  schemas: Arc<OpenApiSchemas>,
+  artifacts: Option<Arc<ArtifactStorage>>,
+  artifact_refs: ArtifactReferences,
+  secrets: SecretResolver,
+  config_maps: ConfigMapResolver,
+  events: Option<EventLog>,
+  forwarder: Option<EventForwarder>,
+  sink: Option<EventSink>,
+  metrics: AppMetrics,
+  budget: Option<Arc<ReconcileBudget>>,
+  watch_namespace: Option<Arc<str>>,
+  watch_labels: Option<Arc<str>>,
+  watch_settings: WatchSettings,
+  concurrency: Option<NonZeroUsize>,
+  read_only: bool,
+  cross_namespace_refs: bool,
+  in_flight: InFlight,
+}
+
+/// The label selector a controller watches its objects with: that of the app, and that of the
+/// controller itself, which the objects must both match.
+fn watch_selector(app: Option<&str>, controller: Option<String>) -> Option<String> {
+  match (app, controller) {
+    (Some(app), Some(controller)) => Some(format!("{app},{controller}")),
+    (Some(app), None) => Some(app.to_string()),
+    (None, controller) => controller,
+  }
+}
+
+#[derive(Clone)]
 struct ControllerResourceInfo {
   group: Arc<str>,
   kind: Arc<str>,
-  // version: String,
-  // api_version: String,
+  plural: Arc<str>,
+  version: Arc<str>,
+  api_version: Arc<str>,
+  namespaced: bool,
+}
+
+impl ControllerResourceInfo {
+  /// Whether `name` refers to this resource, either as `kind` or as `group/kind`.
+  fn matches(&self, name: &str) -> bool {
+    let group = &*self.group;
+    let kind = &*self.kind;
+    kind == name || format!("{group}/{kind}") == name
+  }
+
+  fn api_resource(&self) -> ApiResource {
+    ApiResource {
+      group: self.group.to_string(),
+      version: self.version.to_string(),
+      api_version: self.api_version.to_string(),
+      kind: self.kind.to_string(),
+      plural: self.plural.to_string(),
+    }
+  }
+}
+
+/// A server run next to the controllers of an app, like the webhook receiver of the notification
+/// controllers.
+struct DynService<'a> {
+  label: String,
+  factory: DynServiceFactory<'a>,
 }
 
 struct DynController<'a> {
   info: ControllerResourceInfo,
+  health: Arc<KindHealth>,
+  schedule: Arc<dyn Schedule>,
+  requirements: Requirements,
+  permissions: Permissions,
   crd: DynControllerCrd<'a>,
   factory: DynControllerFactory<'a>,
 }
@@ -73,18 +250,21 @@ struct DynController<'a> {
 impl<'a> DynController<'a> {
   fn crd(self) -> CustomResourceDefinition {
     let crd = self.crd;
-    crd()
+    let mut crd = crd();
+    self.requirements.annotate(&mut crd);
+    crd
   }
 
   fn new<C, R>(controller: C) -> Self
   where
-    C: Controller<R> + 'static,
+    C: Controller<R> + Send + Sync + 'static,
     R: CustomResourceExt
       + Clone
       + Resource
       + fmt::Debug
       + Send
       + Sync
+      + Serialize
       + for<'de> Deserialize<'de>
       + 'static,
     <R as Resource>::DynamicType: Eq + hash::Hash + Default + Clone + fmt::Debug + Unpin,
@@ -95,72 +275,505 @@ impl<'a> DynController<'a> {
       ControllerResourceInfo {
         group: <R as Resource>::group(&dt).into(),
         kind: <R as Resource>::kind(&dt).into(),
-        // version: <R as Resource>::version(&dt).into(),
-        // api_version: <R as Resource>::api_version(&dt).into(),
+        plural: <R as Resource>::plural(&dt).into(),
+        version: <R as Resource>::version(&dt).into(),
+        api_version: <R as Resource>::api_version(&dt).into(),
+        namespaced: R::crd().spec.scope == "Namespaced",
       }
     };
 
+    let mut permissions = roles::resource_permissions(&info);
+    permissions.merge(&C::permissions());
+
     let crd: DynControllerCrd<'a> = Box::new(|| C::crd());
     let kind = info.kind.clone();
-    let factory: DynControllerFactory<'a> = Box::new(move |client, signal| {
+    let recorded_kind: Arc<str> = format!("{}/{}", info.group, info.kind).into();
+    let watch_info = info.clone();
+    let health = Arc::new(KindHealth::default());
+    let kind_health = health.clone();
+    let log = Arc::new(ReconcileLog::<R>::new(recorded_kind.clone()));
+    let schedule = log.clone();
+    let factory: DynControllerFactory<'a> = Box::new(move |env, signal| {
+      let ControllerEnv {
+        client,
+        reporter,
+        namespaces,
+        policies,
+        schemas,
+        artifacts,
+        artifact_refs,
+        secrets,
+        config_maps,
+        events,
+        forwarder,
+        sink,
+        metrics,
+        budget,
+        watch_namespace,
+        watch_labels,
+        watch_settings,
+        concurrency,
+        read_only,
+        cross_namespace_refs,
+        in_flight,
+      } = env;
       let ctxt = Context::new(controller);
-      let ctrl = C::create(client);
+      {
+        let controller = ctxt.clone().into_inner();
+        metrics.add(move || controller.metrics().collect());
+      }
+      let labels = watch_selector(watch_labels.as_deref(), C::label_selector());
+      let ctrl = C::create(
+        client.clone(),
+        watch_namespace.as_deref(),
+        watch_settings.params(labels.as_deref()),
+      );
       let ctrl = C::configure(ctxt.clone().into_inner(), ctrl);
+      let filter = Arc::new(PredicateFilter::new(C::predicates()));
+      let ignored = Arc::new(IgnoredRequests::new());
+      let backoff = Arc::new(BackoffTracker::new(C::backoff()));
+      let limiter = Arc::new(RateLimiter::new(C::rate_limit()));
+      let sweep = AbortOnDrop(tokio::spawn(overdue::sweep(
+        ctxt.clone().into_inner(),
+        ctrl.store(),
+        log.clone(),
+        filter.clone(),
+        ignored.clone(),
+        backoff.clone(),
+        limiter.clone(),
+        kind_health.clone(),
+        kind.clone(),
+      )));
+
+      // the artifacts of the objects are kept until they are no longer referenced
+      if artifacts.is_some() {
+        let store = ctrl.store();
+        let health = kind_health.clone();
+        artifact_refs.add(ArtifactStorage::kind_dir(&kind), move || {
+          let objects = health.is_synced().then(|| store.state())?;
+          Some(
+            objects
+              .iter()
+              .filter_map(|obj| gc::artifact_path(&**obj))
+              .collect(),
+          )
+        });
+      }
+
+      let api = match watch_namespace.as_deref() {
+        Some(namespace) if watch_info.namespaced => Api::<R>::namespaced(client.clone(), namespace),
+        _ => Api::<R>::all(client.clone()),
+      };
+      let sync = AbortOnDrop(tokio::spawn(health::wait_for_sync(
+        ctrl.store(),
+        api,
+        labels,
+        kind_health.clone(),
+      )));
+
+      let batcher = C::status_batching().map(|config| Arc::new(StatusBatcher::new(config)));
+      let flush = batcher.clone().map(|batcher| {
+        AbortOnDrop(tokio::spawn(status::run_batcher(
+          ctxt.clone().into_inner(),
+          client.clone(),
+          batcher,
+          kind.clone(),
+        )))
+      });
+      let writer = Arc::new(StatusWriter::new(client.clone(), batcher.clone()));
+      let budget = budget.map(|budget| Arc::new(budget.share(C::reconcile_weight())));
+      let slots = concurrency.map(|concurrency| Arc::new(Semaphore::new(concurrency.get())));
+
+      // send the status patches still queued once the controller has stopped
+      let drain = {
+        let client = client.clone();
+        async move {
+          if let Some(batcher) = batcher {
+            batcher.flush(&client).await;
+          }
+        }
+      };
+
+      // cancelled once shutdown starts, so that in-flight reconciles are aborted instead of
+      // holding up the drain
+      let cancellation = CancellationToken::new();
+      let signal = {
+        let cancellation = cancellation.clone();
+        async move {
+          signal.await;
+          cancellation.cancel();
+        }
+      };
+
+      // the controller only stops on its own if its watch ended, which leaves the app unhealthy
+      let stopped = {
+        let cancellation = cancellation.clone();
+        let health = kind_health.clone();
+        let kind = kind.clone();
+        async move {
+          if !cancellation.is_cancelled() {
+            warn!(controller.kind = %kind, "controller stopped unexpectedly");
+            health.record_stopped();
+          }
+        }
+      };
 
+      let watch_health = kind_health.clone();
       let reconciler = {
         let kind = kind.clone();
+        let log = log.clone();
+        let backoff = backoff.clone();
         move |resource: Arc<R>, ctx: Context<C>| {
           let meta = resource.meta();
           let name = meta.name.as_deref().unwrap_or("<NULL>");
           let namespace = meta.namespace.as_deref().unwrap_or("<NULL>");
-          let _span = tracing::info_span!("reconcile", controller.kind = %kind, resource.namespace = %namespace, resource.name = %name);
-          info!("reconcile...");
-          C::reconcile(ctx.into_inner(), resource).map_err(ReportWrapper)
+          let revision = meta.resource_version.as_deref().unwrap_or_default();
+          // every reconcile is a trace of its own, linked to the previous reconcile of the object
+          let span = tracing::info_span!(
+            parent: None,
+            "reconcile",
+            controller.kind = %kind,
+            resource.namespace = %namespace,
+            resource.name = %name,
+            resource.revision = %revision,
+            reconcile.id = field::Empty,
+            reconcile.previous_span_id = field::Empty,
+          );
+          span.record(
+            "reconcile.id",
+            &fluxcd_utils_telemetry::correlation_id(&span).as_str(),
+          );
+          let obj_ref = ObjectRef::from_obj(&*resource);
+          let object = obj_ref.clone().erase();
+          let log = log.clone();
+          let filter = filter.clone();
+          let ignored = ignored.clone();
+          let backoff = backoff.clone();
+          let limiter = limiter.clone();
+          let kind = kind.clone();
+          let health = kind_health.clone();
+          let writer = writer.clone();
+          let namespaces = namespaces.clone();
+          let events = events.clone();
+          let sink = sink.clone();
+          let budget = budget.clone();
+          let slots = slots.clone();
+          let recorded_kind = recorded_kind.clone();
+          let in_flight = in_flight.clone();
+          let recorded = resource.clone();
+          let meter = (ctx.clone().into_inner(), kind.clone());
+          let recorder = EventRecorder::new(
+            client.clone(),
+            reporter.clone(),
+            resource.object_ref(&Default::default()),
+          )
+          .with_forwarder(forwarder.clone());
+          let timeout = C::reconcile_timeout(&resource);
+          let reconcile_ctx = ReconcileCtx::new(
+            client.clone(),
+            recorder,
+            policies.clone(),
+            schemas.clone(),
+            artifacts.clone(),
+            timeout,
+            cancellation.child_token(),
+          )
+          .with_resolvers(secrets.clone(), config_maps.clone())
+          .with_read_only(read_only)
+          .with_cross_namespace_refs(cross_namespace_refs);
+
+          let reconcile = async move {
+            let namespace = resource.meta().namespace.as_deref();
+            if let Some(namespace) = namespace.filter(|ns| namespaces.is_ignored(ns)) {
+              debug!("namespace is ignored, skipping");
+              filter.forget(&obj_ref);
+              log.schedule(
+                &obj_ref,
+                Some(IGNORED_RECHECK_INTERVAL),
+                RequeueReason::IgnoredNamespace,
+              );
+              status::record_ignored(&writer, &*resource, namespace).await;
+              let action = ReconcilerAction {
+                requeue_after: Some(IGNORED_RECHECK_INTERVAL),
+              };
+              return Ok((action, ReconcileResult::Requeue));
+            }
+
+            if C::suspended(&resource) {
+              debug!("resource is suspended, skipping");
+              filter.forget(&obj_ref);
+              log.schedule(&obj_ref, None, RequeueReason::Interval);
+              suspend::report_ignored_request(&ignored, obj_ref, &*resource, &reconcile_ctx).await;
+              let action = ReconcilerAction {
+                requeue_after: None,
+              };
+              return Ok((action, ReconcileResult::Success));
+            }
+
+            ignored.forget(&obj_ref);
+            let requested = status::pending_reconcile_request(&*resource);
+            if let Some(action) = filter.skip(&obj_ref, &resource, requested) {
+              debug!("unchanged since the last reconcile, skipping");
+              return Ok((action, ReconcileResult::Success));
+            }
+            if let Some(token) = requested {
+              debug!(%token, "reconcile requested, not waiting for the schedule");
+            }
+
+            // an object reconciled too often is retried once its rate limit allows
+            if let Some(delay) = limiter.acquire(obj_ref.clone()) {
+              debug!(?delay, "reconciled too often, delaying");
+              ctx.get_ref().metrics().record_throttled(&kind);
+              log.schedule(&obj_ref, Some(delay), RequeueReason::Throttled);
+              let action = ReconcilerAction {
+                requeue_after: Some(delay),
+              };
+              return Ok((action, ReconcileResult::Requeue));
+            }
+
+            let span = Span::current();
+            if let Some(previous) = log.chain(&obj_ref, &span) {
+              span.record("reconcile.previous_span_id", &previous.as_str());
+            }
+
+            let throttled = slots.is_some() || budget.is_some();
+            let start = async {
+              let slot = match &slots {
+                Some(slots) => slots.acquire().await.ok(),
+                None => None,
+              };
+              if let Some(budget) = &budget {
+                budget.start().await;
+              }
+
+              slot
+            };
+
+            let queued = ctx.get_ref().metrics().queue_reconcile(&kind);
+            let _slot = tokio::select! {
+              slot = start => slot,
+              _ = reconcile_ctx.cancellation().cancelled() => {
+                filter.forget(&obj_ref);
+                return Err(ReconcileAborted::Cancelled.into());
+              }
+            };
+            drop(queued);
+
+            // waiting for a free slot or the budget does not count towards the deadline of the
+            // reconcile
+            let reconcile_ctx = if throttled {
+              reconcile_ctx.restarted(timeout)
+            } else {
+              reconcile_ctx
+            };
+
+            let _in_flight = match &obj_ref.namespace {
+              Some(namespace) => {
+                in_flight.start(format!("{recorded_kind} {namespace}/{}", obj_ref.name))
+              }
+              None => in_flight.start(format!("{recorded_kind} {}", obj_ref.name)),
+            };
+            info!("reconcile...");
+            let controller = ctx.into_inner();
+            let _active = controller.metrics().start_reconcile(&kind);
+            let result = reconcile_ctx
+              .run(C::reconcile(
+                controller.clone(),
+                resource.clone(),
+                reconcile_ctx.clone(),
+              ))
+              .await;
+
+            if !matches!(result, Ok(Ok(_))) {
+              filter.forget(&obj_ref);
+            }
+
+            match result {
+              Ok(Ok(action)) => {
+                let next_reconcile_at = action
+                  .requeue_after
+                  .and_then(|after| k8s_openapi::chrono::Duration::from_std(after).ok())
+                  .map(|after| Time(Utc::now() + after));
+                status::record_success(&writer, &*resource, next_reconcile_at).await;
+                if reconcile_ctx.is_read_only() {
+                  let skipped = reconcile_ctx.skipped();
+                  status::record_read_only(&writer, &*resource, &skipped).await;
+                }
+                filter.record(obj_ref.clone(), &resource, &action);
+                if backoff.failures(&obj_ref) > 0 {
+                  backoff.reset(&obj_ref);
+                  let (backing_off, longest) = backoff.levels();
+                  controller
+                    .metrics()
+                    .record_backoff(&kind, backing_off, longest);
+                }
+                log.schedule(&obj_ref, action.requeue_after, RequeueReason::Interval);
+                health.record_success();
+                log.record(obj_ref);
+                Ok((action, ReconcileResult::Success))
+              }
+              Ok(Err(error)) => {
+                health.record_failure();
+                let message = format!("{error:#}");
+                let rejection = match error.downcast_ref::<PolicyViolation>() {
+                  Some(violation) => Some((Reason::PolicyViolation, violation.to_string())),
+                  None => error
+                    .downcast_ref::<SchemaViolation>()
+                    .map(|violation| (Reason::ValidationFailed, violation.to_string())),
+                };
+                let reason = match rejection {
+                  Some((reason, note)) => {
+                    let event = Event {
+                      type_: EventType::Warning,
+                      reason: reason.to_string(),
+                      note: Some(note),
+                      action: "Reconcile".into(),
+                      secondary: None,
+                    };
+                    if let Err(error) = reconcile_ctx.recorder().publish(event).await {
+                      warn!(%error, "failed to publish event");
+                    }
+
+                    reason
+                  }
+                  None => Reason::Failed,
+                };
+                status::record_failure(&writer, &*resource, reason.to_string(), message).await;
+                Err(error)
+              }
+              // shutting down is not a failure of the object itself
+              Err(aborted @ ReconcileAborted::Cancelled) => Err(aborted.into()),
+              Err(aborted @ ReconcileAborted::DeadlineExceeded) => {
+                health.record_failure();
+                let message = aborted.to_string();
+                status::record_failure(&writer, &*resource, "DeadlineExceeded", message).await;
+                Err(aborted.into())
+              }
+            }
+          };
+
+          async move {
+            let (controller, kind) = meter;
+            let result = match reconcile.await {
+              Ok((action, result)) => {
+                controller.metrics().record_reconcile(&kind, result);
+                Ok(action)
+              }
+              Err(error) => {
+                controller
+                  .metrics()
+                  .record_reconcile(&kind, ReconcileResult::Error);
+                Err(error)
+              }
+            };
+            if let Some(sink) = &sink {
+              let outcome = Outcome::from_result(result.as_ref());
+              sink.send(&recorded_kind, &object, outcome);
+            }
+
+            if let Some(events) = &events {
+              let outcome = Outcome::from_result(result.as_ref());
+              events.record(&recorded_kind, &*recorded, outcome);
+            }
+
+            result.map_err(|report| ReportWrapper { report, object })
+          }
+          .instrument(span)
         }
       };
       let error_policy = {
-        // let kind = kind.clone();
         move |error: &ReportWrapper, ctx: Context<C>| {
           let _span = tracing::info_span!("error_policy", controller.kind = %kind);
-          C::error_policy(ctx.into_inner(), &error.0)
+          let object = &error.object;
+          let obj_ref = ObjectRef::<R>::new(&object.name);
+          let obj_ref = match &object.namespace {
+            Some(namespace) => obj_ref.within(namespace),
+            None => obj_ref,
+          };
+
+          let controller = ctx.into_inner();
+          let retry = backoff.next(obj_ref.clone());
+          let (backing_off, longest) = backoff.levels();
+          controller
+            .metrics()
+            .record_backoff(&kind, backing_off, longest);
+          let action = C::error_policy(controller, &error.report, retry);
+          log.schedule(&obj_ref, action.requeue_after, RequeueReason::Backoff);
+
+          action
         }
       };
 
+      let relists = ctxt.clone().into_inner();
       let stream = ctrl
         .graceful_shutdown_on(signal)
         .run(reconciler, error_policy, ctxt)
-        .map(|result| match result {
-          Ok((obj, action)) => Ok((obj.erase(), action)),
-          Err(e) => Err(e),
-        });
+        .map(move |result| {
+          // keep the sweep, the sync check and the status flush alive for as long as the controller is running
+          let _ = (&sweep, &flush, &sync);
+          match result {
+            Ok((obj, action)) => Ok((obj.erase(), action)),
+            Err(controller::Error::QueueError(e)) => {
+              let failure = WatchFailure::new(&watch_info, e);
+              warn!(controller.kind = %watch_info.kind, error = %failure, "watch failed");
+              watch_health.record_watch_failure(&failure);
+              // the watcher lists all of the objects again after its watch expired
+              if let WatchFailure::Expired { .. } = failure {
+                relists.metrics().record_relist(&watch_info.kind);
+              }
+              Err(Box::new(controller::Error::QueueError(failure)))
+            }
+            Err(controller::Error::ObjectNotFound(obj)) => {
+              Err(Box::new(controller::Error::ObjectNotFound(obj)))
+            }
+            Err(controller::Error::ReconcilerFailed(e, obj)) => {
+              Err(Box::new(controller::Error::ReconcilerFailed(e, obj)))
+            }
+            Err(controller::Error::SchedulerDequeueFailed(e)) => {
+              Err(Box::new(controller::Error::SchedulerDequeueFailed(e)))
+            }
+          }
+        })
+        .chain(stream::once(future::join(stopped, drain)).filter_map(|_| future::ready(None)));
 
       Box::pin(stream)
     });
 
-    DynController { info, crd, factory }
+    DynController {
+      info,
+      health,
+      schedule,
+      requirements: C::requirements(),
+      permissions,
+      crd,
+      factory,
+    }
   }
 }
 
 pub struct ControllerApp<'a> {
   controllers: Vec<DynController<'a>>,
+  services: Vec<DynService<'a>>,
 }
 
 impl<'a> ControllerApp<'a> {
   fn new() -> Self {
     Self {
       controllers: Vec::new(),
+      services: Vec::new(),
     }
   }
 
   pub fn controller<C, R>(mut self, controller: C) -> Self
   where
-    C: fluxcd_utils_cops::Controller<R> + 'static,
+    C: fluxcd_utils_cops::Controller<R> + Send + Sync + 'static,
     R: CustomResourceExt
       + Clone
       + Resource
       + fmt::Debug
       + Send
       + Sync
+      + Serialize
       + for<'de> Deserialize<'de>
       + 'static,
     <R as Resource>::DynamicType: Eq + hash::Hash + Default + Clone + fmt::Debug + Unpin,
@@ -169,8 +782,54 @@ impl<'a> ControllerApp<'a> {
     self
   }
 
+  /// Runs `service` next to the controllers, under `label` in the logs. It is given a client,
+  /// and a signal which completes as soon as the app starts shutting down, before the controllers
+  /// stop, after which it should stop too. The app shuts down if the service fails.
+  pub fn service<F, Fut>(mut self, label: impl Into<String>, service: F) -> Self
+  where
+    F: FnOnce(Client, ShutdownSignalFuture) -> Fut + 'a,
+    Fut: Future<Output = eyre::Result<()>> + 'a,
+  {
+    self.services.push(DynService {
+      label: label.into(),
+      factory: Box::new(move |client, signal| service(client, signal).boxed_local()),
+    });
+    self
+  }
+
+  /// Registers `collector`, like the gauges of a provider, so that its metrics are served and
+  /// pushed along with those of the controllers. Its metrics must be named
+  /// `gotk_<subsystem>_<name>`, in a subsystem of their own: the `reconcile`, `kube_api` and
+  /// `http_client` subsystems are those of the runtime.
+  pub fn collector(self, collector: impl Collector + 'static) -> eyre::Result<Self> {
+    scrape::register_collector(Box::new(collector))?;
+    Ok(self)
+  }
+
   async fn run(self, name: &str, version: &str) -> eyre::Result<()> {
-    cli::run(name, version, self.controllers).await
+    cli::run(name, version, self.controllers, self.services).await
+  }
+
+  /// Runs the controllers set up by `setup` until `shutdown` completes, without parsing the command
+  /// line, for tools which embed the controllers (like the stress test). The API metrics are
+  /// recorded in the default registry, as with the `run` command.
+  pub async fn run_until(
+    name: &str,
+    version: &str,
+    setup: impl for<'b> FnOnce(ControllerApp<'b>) -> eyre::Result<ControllerApp<'b>>,
+    shutdown: impl Future<Output = ()>,
+  ) -> eyre::Result<()> {
+    let app = setup(Self::new())?;
+    let options = cli::RunOptions::default();
+    cli::run_controllers(
+      name,
+      version,
+      options,
+      shutdown,
+      app.controllers,
+      app.services,
+    )
+    .await
   }
 
   pub fn main(
@@ -183,7 +842,7 @@ impl<'a> ControllerApp<'a> {
 
     let rt = Runtime::new()?;
     rt.block_on(async {
-      fluxcd_utils_telemetry::setup()?;
+      // telemetry is set up once the command line is parsed, as it configures the export
       let result = app.run(name, version).await;
       fluxcd_utils_telemetry::teardown();
 
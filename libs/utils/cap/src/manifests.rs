@@ -0,0 +1,230 @@
+use crate::{health::ControllerStatus, roles, DynController};
+use k8s_openapi::{
+  api::{
+    apps::v1::{Deployment, DeploymentSpec},
+    core::v1::{
+      Capabilities, Container, ContainerPort, HTTPGetAction, Namespace, PodSpec, PodTemplateSpec,
+      Probe, SecurityContext, Service, ServiceAccount, ServicePort, ServiceSpec,
+    },
+    rbac::v1::{ClusterRoleBinding, RoleRef, Subject},
+  },
+  apimachinery::pkg::{apis::meta::v1::LabelSelector, util::intstr::IntOrString},
+};
+use kube::{api::ObjectMeta, CustomResourceExt};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Registry the images of the apps are published to, as `<registry>/<app>:<version>`.
+const IMAGE_REGISTRY: &str = "ghcr.io/yolodev";
+
+/// Port the metrics are served at, that of the default `run --metrics-addr`.
+const METRICS_PORT: i32 = 8080;
+
+/// Port the probes are served at, that of the default `run --probe-addr`.
+const PROBE_PORT: i32 = 8081;
+
+/// What the installation manifests of an app are rendered for.
+pub(crate) struct ExportOptions {
+  /// Namespace the app is installed in.
+  pub(crate) namespace: String,
+
+  /// Image of the app, `<registry>/<app>:<version>` of the running binary by default.
+  pub(crate) image: Option<String>,
+}
+
+/// Renders the manifests installing the app `name` at `version` with `controllers`, for
+/// `install --export`: its namespace, the CRDs, a ServiceAccount bound to the ClusterRole printed
+/// by `rbac`, a Deployment running the controllers, and a Service exposing their metrics. Every
+/// document starts with `---`, so the output can be applied as a single stream.
+pub(crate) fn export(
+  name: &str,
+  version: &str,
+  controllers: Vec<DynController<'_>>,
+  options: &ExportOptions,
+) -> eyre::Result<String> {
+  let namespace = &options.namespace;
+  let image = match &options.image {
+    Some(image) => image.clone(),
+    None => format!("{IMAGE_REGISTRY}/{name}:{version}"),
+  };
+  let cluster_role = roles::cluster_role(name, &controllers);
+
+  // the namespace may be shared with other apps, so it is not labelled as part of this one
+  let mut documents = vec![yaml(&Namespace {
+    metadata: ObjectMeta {
+      name: Some(namespace.clone()),
+      ..ObjectMeta::default()
+    },
+    ..Namespace::default()
+  })?];
+  for crd in controllers.into_iter().map(|c| c.crd()) {
+    documents.push(yaml(&crd)?);
+  }
+  documents.push(yaml(&ControllerStatus::crd())?);
+
+  documents.push(yaml(&ServiceAccount {
+    metadata: metadata(name, version, name, Some(namespace)),
+    ..ServiceAccount::default()
+  })?);
+  documents.push(yaml(&cluster_role)?);
+  documents.push(yaml(&cluster_role_binding(name, version, namespace))?);
+  documents.push(yaml(&deployment(name, version, namespace, &image))?);
+  documents.push(yaml(&service(name, version, namespace))?);
+
+  Ok(documents.concat())
+}
+
+fn yaml(object: &impl Serialize) -> eyre::Result<String> {
+  Ok(serde_yaml::to_string(object)?)
+}
+
+/// The labels of all of the objects of the app, the first of which selects its pods.
+fn labels(name: &str, version: &str) -> BTreeMap<String, String> {
+  BTreeMap::from([
+    ("app".into(), name.into()),
+    ("app.kubernetes.io/part-of".into(), "fluxcd".into()),
+    ("app.kubernetes.io/version".into(), version.into()),
+  ])
+}
+
+fn selector(name: &str) -> BTreeMap<String, String> {
+  BTreeMap::from([("app".into(), name.into())])
+}
+
+fn metadata(app: &str, version: &str, name: &str, namespace: Option<&str>) -> ObjectMeta {
+  ObjectMeta {
+    name: Some(name.into()),
+    namespace: namespace.map(Into::into),
+    labels: Some(labels(app, version)),
+    ..ObjectMeta::default()
+  }
+}
+
+fn cluster_role_binding(name: &str, version: &str, namespace: &str) -> ClusterRoleBinding {
+  ClusterRoleBinding {
+    metadata: metadata(name, version, name, None),
+    role_ref: RoleRef {
+      api_group: "rbac.authorization.k8s.io".into(),
+      kind: "ClusterRole".into(),
+      name: name.into(),
+    },
+    subjects: Some(vec![Subject {
+      kind: "ServiceAccount".into(),
+      name: name.into(),
+      namespace: Some(namespace.into()),
+      ..Subject::default()
+    }]),
+  }
+}
+
+fn deployment(name: &str, version: &str, namespace: &str, image: &str) -> Deployment {
+  let probe = |path: &str| Probe {
+    http_get: Some(HTTPGetAction {
+      path: Some(path.into()),
+      port: IntOrString::String("healthz".into()),
+      ..HTTPGetAction::default()
+    }),
+    ..Probe::default()
+  };
+
+  let container = Container {
+    name: "manager".into(),
+    image: Some(image.into()),
+    args: Some(vec!["run".into()]),
+    ports: Some(vec![
+      ContainerPort {
+        name: Some("http-metrics".into()),
+        container_port: METRICS_PORT,
+        ..ContainerPort::default()
+      },
+      ContainerPort {
+        name: Some("healthz".into()),
+        container_port: PROBE_PORT,
+        ..ContainerPort::default()
+      },
+    ]),
+    liveness_probe: Some(probe("/healthz")),
+    readiness_probe: Some(probe("/readyz")),
+    security_context: Some(SecurityContext {
+      allow_privilege_escalation: Some(false),
+      read_only_root_filesystem: Some(true),
+      run_as_non_root: Some(true),
+      capabilities: Some(Capabilities {
+        drop: Some(vec!["ALL".into()]),
+        ..Capabilities::default()
+      }),
+      ..SecurityContext::default()
+    }),
+    ..Container::default()
+  };
+
+  Deployment {
+    metadata: metadata(name, version, name, Some(namespace)),
+    spec: Some(DeploymentSpec {
+      replicas: Some(1),
+      selector: LabelSelector {
+        match_labels: Some(selector(name)),
+        ..LabelSelector::default()
+      },
+      template: PodTemplateSpec {
+        metadata: Some(ObjectMeta {
+          labels: Some(labels(name, version)),
+          ..ObjectMeta::default()
+        }),
+        spec: Some(PodSpec {
+          service_account_name: Some(name.into()),
+          containers: vec![container],
+          ..PodSpec::default()
+        }),
+      },
+      ..DeploymentSpec::default()
+    }),
+    ..Deployment::default()
+  }
+}
+
+fn service(name: &str, version: &str, namespace: &str) -> Service {
+  Service {
+    metadata: metadata(name, version, name, Some(namespace)),
+    spec: Some(ServiceSpec {
+      selector: Some(selector(name)),
+      ports: Some(vec![ServicePort {
+        name: Some("http-metrics".into()),
+        port: METRICS_PORT,
+        target_port: Some(IntOrString::String("http-metrics".into())),
+        ..ServicePort::default()
+      }]),
+      ..ServiceSpec::default()
+    }),
+    ..Service::default()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn deployment_runs_the_app_as_its_service_account() {
+    let deployment = deployment("github-keys", "0.3.1", "flux-system", "registry/keys:0.3.1");
+    let spec = deployment.spec.unwrap();
+    let pod = spec.template.spec.unwrap();
+    assert_eq!(pod.service_account_name.as_deref(), Some("github-keys"));
+    assert_eq!(
+      pod.containers[0].image.as_deref(),
+      Some("registry/keys:0.3.1")
+    );
+    assert_eq!(pod.containers[0].args, Some(vec!["run".to_string()]));
+
+    // the pods must be selected by the Service and the Deployment
+    let pod_labels = spec.template.metadata.unwrap().labels.unwrap();
+    let service_selector = service("github-keys", "0.3.1", "flux-system")
+      .spec
+      .unwrap()
+      .selector
+      .unwrap();
+    for selector in [spec.selector.match_labels.unwrap(), service_selector] {
+      assert!(selector.iter().all(|(k, v)| pod_labels.get(k) == Some(v)));
+    }
+  }
+}
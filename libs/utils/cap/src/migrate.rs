@@ -0,0 +1,271 @@
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+  CustomResourceDefinition, JSONSchemaProps, JSONSchemaPropsOrArray, JSONSchemaPropsOrBool,
+};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+/// Suffix of the API groups of upstream Flux kinds.
+const UPSTREAM_GROUP_SUFFIX: &str = ".toolkit.fluxcd.io";
+
+/// Suffix of the API groups of the kinds of this project.
+const GROUP_SUFFIX: &str = ".fluxcd.yolodev.io";
+
+/// Metadata fields carried over from the upstream object, everything else is owned by the cluster.
+const METADATA_FIELDS: &[&str] = &["name", "namespace", "labels", "annotations"];
+
+#[derive(Debug, Error)]
+pub(crate) enum MigrateError {
+  #[error("document is not a kubernetes object")]
+  NotAnObject,
+
+  #[error("'{0}' is not an upstream flux kind")]
+  NotUpstream(String),
+
+  #[error("there is no equivalent of '{0}' in this project")]
+  NoEquivalent(String),
+}
+
+/// The result of converting an upstream object.
+pub(crate) struct Migration {
+  pub(crate) object: Value,
+
+  /// Paths of the upstream fields which have no equivalent, and were dropped.
+  pub(crate) unsupported: Vec<String>,
+}
+
+/// Converts an upstream Flux object to the equivalent kind in `crds`, which is the kind with the
+/// same name in the corresponding API group (e.g. `source.toolkit.fluxcd.io` becomes
+/// `source.fluxcd.yolodev.io`).
+pub(crate) fn migrate(
+  upstream: &Value,
+  crds: &[CustomResourceDefinition],
+) -> Result<Migration, MigrateError> {
+  let api_version = upstream
+    .get("apiVersion")
+    .and_then(Value::as_str)
+    .ok_or(MigrateError::NotAnObject)?;
+  let kind = upstream
+    .get("kind")
+    .and_then(Value::as_str)
+    .ok_or(MigrateError::NotAnObject)?;
+  let full_kind = format!("{api_version}/{kind}");
+
+  let group = api_version
+    .split_once('/')
+    .and_then(|(group, _)| group.strip_suffix(UPSTREAM_GROUP_SUFFIX))
+    .ok_or_else(|| MigrateError::NotUpstream(full_kind.clone()))?;
+  let group = format!("{group}{GROUP_SUFFIX}");
+
+  let (crd, version) = crds
+    .iter()
+    .filter(|crd| crd.spec.group == group && crd.spec.names.kind == kind)
+    .find_map(|crd| {
+      let version = crd.spec.versions.iter().find(|v| v.storage)?;
+      Some((crd, version))
+    })
+    .ok_or(MigrateError::NoEquivalent(full_kind))?;
+
+  let mut unsupported = Vec::new();
+  let mut object = Map::new();
+  object.insert(
+    "apiVersion".into(),
+    format!("{}/{}", crd.spec.group, version.name).into(),
+  );
+  object.insert("kind".into(), kind.into());
+
+  if let Some(Value::Object(upstream_metadata)) = upstream.get("metadata") {
+    let metadata = METADATA_FIELDS
+      .iter()
+      .filter_map(|&field| Some((field.into(), upstream_metadata.get(field)?.clone())))
+      .collect::<Map<_, _>>();
+    object.insert("metadata".into(), metadata.into());
+  }
+
+  if let Some(spec) = upstream.get("spec") {
+    let schema = version
+      .schema
+      .as_ref()
+      .and_then(|s| s.open_api_v3_schema.as_ref())
+      .and_then(|s| s.properties.as_ref())
+      .and_then(|p| p.get("spec"));
+
+    match schema {
+      Some(schema) => {
+        object.insert(
+          "spec".into(),
+          convert(spec, schema, "spec", &mut unsupported),
+        );
+      }
+      None => unsupported.push("spec".into()),
+    }
+  }
+
+  Ok(Migration {
+    object: object.into(),
+    unsupported,
+  })
+}
+
+/// Keeps the parts of `value` described by `schema`, recording the paths of everything else in
+/// `unsupported`.
+fn convert(
+  value: &Value,
+  schema: &JSONSchemaProps,
+  path: &str,
+  unsupported: &mut Vec<String>,
+) -> Value {
+  if schema.x_kubernetes_preserve_unknown_fields == Some(true) {
+    return value.clone();
+  }
+
+  match value {
+    Value::Object(fields) => {
+      if let Some(properties) = &schema.properties {
+        let mut converted = Map::new();
+        for (name, value) in fields {
+          let path = format!("{path}.{name}");
+          match properties.get(name) {
+            Some(schema) => {
+              converted.insert(name.clone(), convert(value, schema, &path, unsupported));
+            }
+            None => unsupported.push(path),
+          }
+        }
+
+        return converted.into();
+      }
+
+      match &schema.additional_properties {
+        Some(JSONSchemaPropsOrBool::Schema(schema)) => fields
+          .iter()
+          .map(|(name, value)| {
+            let path = format!("{path}.{name}");
+            (name.clone(), convert(value, schema, &path, unsupported))
+          })
+          .collect::<Map<_, _>>()
+          .into(),
+
+        _ => value.clone(),
+      }
+    }
+
+    Value::Array(items) => match &schema.items {
+      Some(JSONSchemaPropsOrArray::Schema(schema)) => items
+        .iter()
+        .enumerate()
+        .map(|(index, value)| convert(value, schema, &format!("{path}[{index}]"), unsupported))
+        .collect::<Vec<_>>()
+        .into(),
+
+      _ => value.clone(),
+    },
+
+    _ => value.clone(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn bucket_crd() -> CustomResourceDefinition {
+    serde_json::from_value(json!({
+      "apiVersion": "apiextensions.k8s.io/v1",
+      "kind": "CustomResourceDefinition",
+      "metadata": { "name": "buckets.source.fluxcd.yolodev.io" },
+      "spec": {
+        "group": "source.fluxcd.yolodev.io",
+        "names": { "kind": "Bucket", "plural": "buckets" },
+        "scope": "Namespaced",
+        "versions": [{
+          "name": "v1beta1",
+          "served": true,
+          "storage": true,
+          "schema": {
+            "openAPIV3Schema": {
+              "type": "object",
+              "properties": {
+                "spec": {
+                  "type": "object",
+                  "properties": {
+                    "bucketName": { "type": "string" },
+                    "endpoint": { "type": "string" },
+                    "interval": { "type": "string" },
+                    "provider": { "type": "string" },
+                    "secretRef": {
+                      "type": "object",
+                      "properties": { "name": { "type": "string" } }
+                    }
+                  }
+                }
+              }
+            }
+          }
+        }]
+      }
+    }))
+    .unwrap()
+  }
+
+  #[test]
+  fn migrate_bucket() {
+    let upstream = json!({
+      "apiVersion": "source.toolkit.fluxcd.io/v1beta2",
+      "kind": "Bucket",
+      "metadata": {
+        "name": "podinfo",
+        "namespace": "flux-system",
+        "uid": "1d2a1e0c-7b61-4bd5-a1c6-4bd0d3c0f4b6",
+        "resourceVersion": "42",
+      },
+      "spec": {
+        "bucketName": "podinfo",
+        "endpoint": "minio.minio.svc:9000",
+        "interval": "5m",
+        "provider": "generic",
+        "secretRef": { "name": "minio-credentials" },
+        "sts": { "provider": "ldap", "endpoint": "https://minio.example.com" },
+      },
+    });
+
+    let migration = migrate(&upstream, &[bucket_crd()]).unwrap();
+    assert_eq!(
+      migration.object,
+      json!({
+        "apiVersion": "source.fluxcd.yolodev.io/v1beta1",
+        "kind": "Bucket",
+        "metadata": { "name": "podinfo", "namespace": "flux-system" },
+        "spec": {
+          "bucketName": "podinfo",
+          "endpoint": "minio.minio.svc:9000",
+          "interval": "5m",
+          "provider": "generic",
+          "secretRef": { "name": "minio-credentials" },
+        },
+      })
+    );
+    assert_eq!(migration.unsupported, vec!["spec.sts".to_string()]);
+  }
+
+  #[test]
+  fn migrate_rejects_unknown_kinds() {
+    let crds = [bucket_crd()];
+    let own = json!({ "apiVersion": "source.fluxcd.yolodev.io/v1beta1", "kind": "Bucket" });
+    assert!(matches!(
+      migrate(&own, &crds),
+      Err(MigrateError::NotUpstream(kind)) if kind == "source.fluxcd.yolodev.io/v1beta1/Bucket"
+    ));
+
+    let git = json!({ "apiVersion": "source.toolkit.fluxcd.io/v1beta2", "kind": "GitRepository" });
+    assert!(matches!(
+      migrate(&git, &crds),
+      Err(MigrateError::NoEquivalent(kind)) if kind == "source.toolkit.fluxcd.io/v1beta2/GitRepository"
+    ));
+
+    assert!(matches!(
+      migrate(&json!({ "kind": "Bucket" }), &crds),
+      Err(MigrateError::NotAnObject)
+    ));
+  }
+}
@@ -0,0 +1,46 @@
+use fluxcd_meta::is_ignored;
+use futures::{Future, StreamExt};
+use k8s_openapi::api::core::v1::Namespace;
+use kube::{
+  api::ListParams,
+  runtime::{
+    reflector,
+    reflector::{store::Writer, ObjectRef, Store},
+    watcher,
+  },
+  Api, Client,
+};
+use tracing::warn;
+
+/// Cache of the namespaces of the cluster, used to check whether they opted out of
+/// reconciliation.
+#[derive(Clone)]
+pub(crate) struct NamespaceCache {
+  store: Store<Namespace>,
+}
+
+impl NamespaceCache {
+  /// Creates the cache, and the future which keeps it up to date for as long as it is polled.
+  pub(crate) fn new(client: Client) -> (Self, impl Future<Output = ()>) {
+    let writer = Writer::default();
+    let store = writer.as_reader();
+    let api = Api::<Namespace>::all(client);
+    let watch = reflector(writer, watcher(api, ListParams::default())).for_each(|event| async {
+      if let Err(error) = event {
+        warn!(%error, "namespace watch failed");
+      }
+    });
+
+    (Self { store }, watch)
+  }
+
+  /// Whether the resources in `namespace` should be skipped. Namespaces which are not (yet) in
+  /// the cache are not ignored.
+  pub(crate) fn is_ignored(&self, namespace: &str) -> bool {
+    self
+      .store
+      .get(&ObjectRef::new(namespace))
+      .and_then(|ns| ns.metadata.annotations.as_ref().map(is_ignored))
+      .unwrap_or(false)
+  }
+}
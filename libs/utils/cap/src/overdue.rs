@@ -0,0 +1,260 @@
+use crate::{
+  filter::PredicateFilter,
+  health::KindHealth,
+  schedule::{RequeueReason, Schedule, ScheduledReconcile},
+  suspend::IgnoredRequests,
+  Controller,
+};
+use fluxcd_utils_cops::{backoff::BackoffTracker, ratelimit::RateLimiter};
+use fluxcd_utils_telemetry::SpanLink;
+use k8s_openapi::{
+  api::core::v1::ObjectReference,
+  apimachinery::pkg::apis::meta::v1::Time,
+  chrono::{self, Utc},
+};
+use kube::{
+  runtime::reflector::{ObjectRef, Store},
+  CustomResourceExt, Resource,
+};
+use serde::Deserialize;
+use std::{
+  collections::{HashMap, HashSet},
+  fmt, hash, mem,
+  sync::{Arc, Mutex},
+  time::{Duration, SystemTime},
+};
+use tracing::{debug, Span};
+
+/// Resources are reported as overdue once their last reconcile is older than this many intervals.
+const OVERDUE_INTERVALS: u32 = 2;
+
+/// How often the watch cache is swept for overdue resources.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Keeps track of when each resource was last successfully reconciled by this process. This is
+/// used as a fallback for resources which do not record the time in their status. The log also
+/// keeps the next reconcile scheduled for each resource, to tell when and why it is due.
+pub(crate) struct ReconcileLog<R>
+where
+  R: Resource,
+  R::DynamicType: Eq + hash::Hash,
+{
+  kind: Arc<str>,
+  last: Mutex<HashMap<ObjectRef<R>, SystemTime>>,
+  spans: Mutex<HashMap<ObjectRef<R>, SpanLink>>,
+  next: Mutex<HashMap<ObjectRef<R>, (Time, RequeueReason)>>,
+}
+
+impl<R> ReconcileLog<R>
+where
+  R: Resource,
+  R::DynamicType: Eq + hash::Hash + Clone,
+{
+  /// Creates the log of the resources of `kind`, the full path of the kind (`group/kind`).
+  pub(crate) fn new(kind: Arc<str>) -> Self {
+    Self {
+      kind,
+      last: Mutex::new(HashMap::new()),
+      spans: Mutex::new(HashMap::new()),
+      next: Mutex::new(HashMap::new()),
+    }
+  }
+
+  pub(crate) fn record(&self, obj: ObjectRef<R>) {
+    self.last.lock().unwrap().insert(obj, SystemTime::now());
+  }
+
+  /// Links `span` to the span of the previous reconcile of `obj`, and remembers it as the span to
+  /// link the next reconcile to. Returns the span id of the previous reconcile, if any.
+  pub(crate) fn chain(&self, obj: &ObjectRef<R>, span: &Span) -> Option<String> {
+    let mut spans = self.spans.lock().unwrap();
+    let previous = match SpanLink::to(span) {
+      Some(link) => spans.insert(obj.clone(), link),
+      None => spans.get(obj).cloned(),
+    }?;
+
+    previous.add_to(span);
+    Some(previous.span_id())
+  }
+
+  /// Records that `obj` is reconciled again `after` from now, because of `reason`. Without a
+  /// requeue, `obj` is only reconciled again once it changes.
+  pub(crate) fn schedule(
+    &self,
+    obj: &ObjectRef<R>,
+    after: Option<Duration>,
+    reason: RequeueReason,
+  ) {
+    let mut next = self.next.lock().unwrap();
+    match after.and_then(|after| chrono::Duration::from_std(after).ok()) {
+      Some(after) => next.insert(obj.clone(), (Time(Utc::now() + after), reason)),
+      None => next.remove(obj),
+    };
+  }
+
+  fn get(&self, obj: &ObjectRef<R>) -> Option<SystemTime> {
+    self.last.lock().unwrap().get(obj).copied()
+  }
+
+  fn retain(&self, live: &HashSet<ObjectRef<R>>) {
+    self
+      .last
+      .lock()
+      .unwrap()
+      .retain(|obj, _| live.contains(obj));
+    self
+      .spans
+      .lock()
+      .unwrap()
+      .retain(|obj, _| live.contains(obj));
+    self
+      .next
+      .lock()
+      .unwrap()
+      .retain(|obj, _| live.contains(obj));
+  }
+}
+
+impl<R> Schedule for ReconcileLog<R>
+where
+  R: Resource + Send + Sync,
+  R::DynamicType: Eq + hash::Hash + Send + Sync,
+{
+  fn scheduled(&self) -> Vec<ScheduledReconcile> {
+    let next = self.next.lock().unwrap();
+    next
+      .iter()
+      .map(|(obj, (due, reason))| ScheduledReconcile {
+        kind: self.kind.to_string(),
+        namespace: obj.namespace.clone(),
+        name: obj.name.clone(),
+        due: due.clone(),
+        reason: *reason,
+      })
+      .collect()
+  }
+}
+
+/// How far behind schedule a resource reconciled every `interval` and last at `last` is at `now`,
+/// if it is overdue.
+fn overdue_by(interval: Duration, last: SystemTime, now: SystemTime) -> Option<Duration> {
+  let elapsed = now.duration_since(last).unwrap_or_default();
+  (elapsed > interval * OVERDUE_INTERVALS).then(|| elapsed - interval)
+}
+
+/// Periodically walks the watch cache and records how far behind schedule the controller is,
+/// based on the interval and last reconcile time of each resource. Resources which disappeared
+/// from the watch cache since the previous sweep have been deleted, which is recorded in their
+/// metrics and reported to the controller, and the state kept for them is dropped.
+pub(crate) async fn sweep<C, R>(
+  controller: Arc<C>,
+  store: Store<R>,
+  log: Arc<ReconcileLog<R>>,
+  filter: Arc<PredicateFilter<R>>,
+  ignored: Arc<IgnoredRequests<R>>,
+  backoff: Arc<BackoffTracker<ObjectRef<R>>>,
+  limiter: Arc<RateLimiter<ObjectRef<R>>>,
+  health: Arc<KindHealth>,
+  kind: Arc<str>,
+) where
+  C: Controller<R>,
+  R: CustomResourceExt
+    + Clone
+    + Resource
+    + fmt::Debug
+    + Send
+    + Sync
+    + for<'de> Deserialize<'de>
+    + 'static,
+  R::DynamicType: Eq + hash::Hash + Default + Clone,
+{
+  let dt = R::DynamicType::default();
+  let mut ticks = tokio::time::interval(SWEEP_INTERVAL);
+
+  // the resources found by the previous sweep, those which are now gone have been deleted
+  let mut known = HashMap::<ObjectRef<R>, ObjectReference>::new();
+  loop {
+    ticks.tick().await;
+
+    let now = SystemTime::now();
+    let objects = store.state();
+    let mut live = HashSet::with_capacity(objects.len());
+    let mut references = HashMap::with_capacity(objects.len());
+    let mut overdue = Duration::ZERO;
+    let mut count = 0usize;
+
+    for obj in objects {
+      let obj_ref = ObjectRef::from_obj(&*obj);
+      let interval = C::reconcile_interval(&obj);
+      let last = C::last_reconciled(&obj)
+        .or_else(|| log.get(&obj_ref))
+        .or_else(|| obj.meta().creation_timestamp.as_ref().map(|t| t.0.into()));
+
+      let reference = obj.object_ref(&dt);
+      controller.metrics().touch(&reference);
+      references.insert(obj_ref.clone(), reference);
+      live.insert(obj_ref);
+      let (interval, last) = match (interval, last) {
+        (Some(interval), Some(last)) => (interval, last),
+        _ => continue,
+      };
+
+      if let Some(behind) = overdue_by(interval, last, now) {
+        count += 1;
+        overdue = overdue.max(behind);
+      }
+    }
+
+    known.retain(|obj, _| !live.contains(obj));
+    for (obj, reference) in mem::replace(&mut known, references) {
+      debug!(%obj, "resource deleted");
+      controller.metrics().record_deleted(&reference);
+      controller.deleted(&reference);
+    }
+
+    log.retain(&live);
+    filter.retain(&live);
+    ignored.retain(&live);
+    backoff.retain(&live);
+    limiter.retain(&live);
+    health.set_objects(live.len());
+    controller.metrics().record_overdue(&kind, overdue, count);
+    let (backing_off, longest) = backoff.levels();
+    controller
+      .metrics()
+      .record_backoff(&kind, backing_off, longest);
+    controller.metrics().expire();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn counts_resources_behind_by_more_than_two_intervals() {
+    let now = SystemTime::now();
+    let minute = Duration::from_secs(60);
+    let resources = [
+      // reconciled on schedule
+      (minute, now - minute / 2),
+      // exactly two intervals ago is not overdue yet
+      (minute, now - minute * 2),
+      (minute, now - minute * 3),
+      (minute * 10, now - minute * 25),
+      // clock skew puts the last reconcile in the future
+      (minute, now + minute),
+    ];
+
+    let (overdue, count) = resources
+      .iter()
+      .filter_map(|&(interval, last)| overdue_by(interval, last, now))
+      .fold((Duration::ZERO, 0), |(overdue, count), behind| {
+        (overdue.max(behind), count + 1)
+      });
+
+    assert_eq!(count, 2);
+    assert_eq!(overdue, minute * 15);
+    assert_eq!(overdue_by(minute, now - minute * 3, now), Some(minute * 2));
+  }
+}
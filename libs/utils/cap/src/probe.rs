@@ -0,0 +1,232 @@
+use crate::{
+  discovery::{Discovery, GroupStatus},
+  health::{KindHealth, LastWatchFailure},
+  leader::LeaderElection,
+  problem::{self, Problem},
+};
+use hyper::{
+  header::CONTENT_TYPE,
+  service::{make_service_fn, service_fn},
+  Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::Serialize;
+use std::{convert::Infallible, future::Future, net::SocketAddr, sync::Arc};
+use tracing::info;
+
+/// Path of the endpoint describing the app, and whether the API server serves the groups of its
+/// controllers.
+const INFO_PATH: &str = "/info";
+
+/// The health of the controllers of an app, and the address the probes of Kubernetes check it
+/// at: `/healthz` fails once a controller stopped unexpectedly, and `/readyz` only succeeds once
+/// the watch caches of all controllers have synced. Instances waiting to be elected leader do not
+/// run the controllers, so they are ready right away. `/info` describes the app next to them.
+/// Both the failures of the probes and `/info` report the last watch failure of every kind.
+pub(crate) struct ProbeServer {
+  pub(crate) kinds: Vec<(String, Arc<KindHealth>)>,
+  pub(crate) election: Option<Arc<LeaderElection>>,
+  pub(crate) info: Arc<AppInfo>,
+  pub(crate) addr: SocketAddr,
+}
+
+/// What the `/info` endpoint describes.
+pub(crate) struct AppInfo {
+  pub(crate) name: String,
+  pub(crate) version: String,
+  pub(crate) discovery: Arc<Discovery>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InfoResponse<'a> {
+  name: &'a str,
+  version: &'a str,
+  discovery: Vec<GroupStatus>,
+  kinds: Vec<KindInfo<'a>>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct KindInfo<'a> {
+  kind: &'a str,
+  synced: bool,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  last_watch_failure: Option<LastWatchFailure>,
+}
+
+impl ProbeServer {
+  /// Serves the probes over HTTP, until `signal` completes.
+  pub(crate) async fn serve(self, signal: impl Future<Output = ()>) -> eyre::Result<()> {
+    let kinds = Arc::new(self.kinds);
+    let election = self.election;
+    let info = self.info;
+    let make_service = make_service_fn(move |_| {
+      let kinds = kinds.clone();
+      let election = election.clone();
+      let info = info.clone();
+      let service = service_fn(move |request| {
+        let kinds = kinds.clone();
+        let info = info.clone();
+        let standby = election.as_ref().is_some_and(|e| !e.is_leader());
+        problem::handle("probes", request, move |request| {
+          respond(kinds, standby, info, request)
+        })
+      });
+
+      async move { Ok::<_, Infallible>(service) }
+    });
+
+    let server = Server::try_bind(&self.addr)?.serve(make_service);
+    info!(addr = %self.addr, "serving probes");
+    server.with_graceful_shutdown(signal).await?;
+
+    Ok(())
+  }
+}
+
+async fn respond(
+  kinds: Arc<Vec<(String, Arc<KindHealth>)>>,
+  standby: bool,
+  info: Arc<AppInfo>,
+  request: Request<Body>,
+) -> Result<Response<Body>, Problem> {
+  if request.uri().path() == INFO_PATH {
+    return respond_info(&info, &kinds, request);
+  }
+
+  let failing = match request.uri().path() {
+    "/healthz" => failing(&kinds, KindHealth::is_stopped, "stopped"),
+    "/readyz" if standby => None,
+    "/readyz" => failing(&kinds, |health| !health.is_synced(), "not synced yet"),
+    _ => {
+      return Err(Problem::new(StatusCode::NOT_FOUND).detail("there is no probe at this path"));
+    }
+  };
+
+  if request.method() != Method::GET && request.method() != Method::HEAD {
+    return Err(
+      Problem::new(StatusCode::METHOD_NOT_ALLOWED).detail("probes only support GET and HEAD"),
+    );
+  }
+
+  if let Some(detail) = failing {
+    return Err(Problem::new(StatusCode::SERVICE_UNAVAILABLE).detail(detail));
+  }
+
+  let response = Response::builder()
+    .header(CONTENT_TYPE, "text/plain")
+    .body(Body::from("ok"))
+    .expect("response is valid");
+
+  Ok(response)
+}
+
+fn respond_info(
+  info: &AppInfo,
+  kinds: &[(String, Arc<KindHealth>)],
+  request: Request<Body>,
+) -> Result<Response<Body>, Problem> {
+  if request.method() != Method::GET {
+    return Err(Problem::new(StatusCode::METHOD_NOT_ALLOWED).detail("the info only supports GET"));
+  }
+
+  let response = InfoResponse {
+    name: &info.name,
+    version: &info.version,
+    discovery: info.discovery.status(),
+    kinds: kind_infos(kinds),
+  };
+  let body = serde_json::to_vec(&response).map_err(|_| {
+    Problem::new(StatusCode::INTERNAL_SERVER_ERROR).detail("the info could not be serialized")
+  })?;
+  let response = Response::builder()
+    .header(CONTENT_TYPE, "application/json")
+    .body(Body::from(body))
+    .expect("response is valid");
+
+  Ok(response)
+}
+
+fn kind_infos(kinds: &[(String, Arc<KindHealth>)]) -> Vec<KindInfo<'_>> {
+  kinds
+    .iter()
+    .map(|(kind, health)| KindInfo {
+      kind,
+      synced: health.is_synced(),
+      last_watch_failure: health.last_watch_failure(),
+    })
+    .collect()
+}
+
+/// Describes the kinds whose controller `fails` the probe, if any, with the last failure of their
+/// watch, which usually tells why.
+fn failing(
+  kinds: &[(String, Arc<KindHealth>)],
+  fails: impl Fn(&KindHealth) -> bool,
+  reason: &str,
+) -> Option<String> {
+  let failing = kinds
+    .iter()
+    .filter(|(_, health)| fails(health))
+    .map(|(kind, health)| match health.last_watch_failure() {
+      Some(failure) => format!("{kind} (watch failed: {})", failure.message),
+      None => kind.clone(),
+    })
+    .collect::<Vec<_>>();
+
+  if failing.is_empty() {
+    None
+  } else {
+    Some(format!("{reason}: {}", failing.join(", ")))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::WatchFailure;
+  use kube::runtime::watcher;
+
+  #[test]
+  fn lists_failing_kinds() {
+    let synced = Arc::new(KindHealth::default());
+    synced.record_synced();
+    let kinds = vec![
+      ("source.fluxcd.yolodev.io/HttpEndpoint".to_string(), synced),
+      (
+        "source.fluxcd.yolodev.io/DnsRecords".to_string(),
+        Arc::new(KindHealth::default()),
+      ),
+    ];
+
+    assert_eq!(
+      failing(&kinds, |health| !health.is_synced(), "not synced yet").as_deref(),
+      Some("not synced yet: source.fluxcd.yolodev.io/DnsRecords")
+    );
+    assert_eq!(failing(&kinds, KindHealth::is_stopped, "stopped"), None);
+  }
+
+  #[test]
+  fn reports_watch_failures() {
+    let health = Arc::new(KindHealth::default());
+    health.record_watch_failure(&WatchFailure::Forbidden {
+      resource: "dnsrecords.source.fluxcd.yolodev.io".into(),
+      source: watcher::Error::TooManyObjects,
+    });
+    let kinds = vec![("source.fluxcd.yolodev.io/DnsRecords".to_string(), health)];
+
+    assert_eq!(
+      failing(&kinds, |health| !health.is_synced(), "not synced yet").as_deref(),
+      Some(
+        "not synced yet: source.fluxcd.yolodev.io/DnsRecords (watch failed: access to \
+         dnsrecords.source.fluxcd.yolodev.io was denied, grant list and watch on \
+         dnsrecords.source.fluxcd.yolodev.io to the controller's service account)"
+      )
+    );
+
+    let info = serde_json::to_value(kind_infos(&kinds)).unwrap();
+    assert_eq!(info[0]["synced"], false);
+    assert_eq!(info[0]["lastWatchFailure"]["reason"], "Forbidden");
+    assert!(info[0]["lastWatchFailure"]["time"].is_string());
+  }
+}
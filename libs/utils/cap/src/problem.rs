@@ -0,0 +1,143 @@
+use hyper::{
+  header::{HeaderName, HeaderValue, CONTENT_TYPE},
+  Body, Request, Response, StatusCode,
+};
+use serde::Serialize;
+use std::{convert::Infallible, future::Future};
+use tracing::{debug, field, info_span, warn, Instrument};
+
+/// Header carrying the correlation ID of a request in every response.
+const CORRELATION_ID_HEADER: &str = "x-correlation-id";
+
+const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Why an HTTP endpoint of the app failed to handle a request, sent to the client as an
+/// `application/problem+json` document (RFC 7807).
+#[derive(Debug)]
+pub(crate) struct Problem {
+  status: StatusCode,
+  detail: Option<String>,
+}
+
+/// The `application/problem+json` document of a [Problem].
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProblemDocument<'a> {
+  #[serde(rename = "type")]
+  type_: &'static str,
+  title: &'static str,
+  status: u16,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  detail: Option<&'a str>,
+  instance: &'a str,
+  correlation_id: &'a str,
+}
+
+impl Problem {
+  pub(crate) fn new(status: StatusCode) -> Self {
+    Self {
+      status,
+      detail: None,
+    }
+  }
+
+  /// Explains the problem to the client. The detail is sent as is, so it must not reveal
+  /// anything about the internals of the app.
+  pub(crate) fn detail(mut self, detail: impl Into<String>) -> Self {
+    self.detail = Some(detail.into());
+    self
+  }
+
+  fn document<'a>(&'a self, instance: &'a str, correlation_id: &'a str) -> ProblemDocument<'a> {
+    ProblemDocument {
+      type_: "about:blank",
+      title: self.status.canonical_reason().unwrap_or_default(),
+      status: self.status.as_u16(),
+      detail: self.detail.as_deref(),
+      instance,
+      correlation_id,
+    }
+  }
+
+  fn into_response(self, instance: &str, correlation_id: &str) -> Response<Body> {
+    let body = serde_json::to_vec(&self.document(instance, correlation_id))
+      .expect("problem documents always serialize");
+
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = self.status;
+    response
+      .headers_mut()
+      .insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_CONTENT_TYPE));
+    response
+  }
+}
+
+/// Handles `request` to `endpoint` with `handler`, within a span of its own. Problems are logged
+/// and sent as `application/problem+json` documents, and every response carries the correlation
+/// ID of the request (the trace ID, when traces are exported) in the `X-Correlation-Id` header,
+/// to find the request in the logs and traces of the app.
+pub(crate) async fn handle<F, Fut>(
+  endpoint: &'static str,
+  request: Request<Body>,
+  handler: F,
+) -> Result<Response<Body>, Infallible>
+where
+  F: FnOnce(Request<Body>) -> Fut,
+  Fut: Future<Output = Result<Response<Body>, Problem>>,
+{
+  let span = info_span!(
+    "http request",
+    http.endpoint = endpoint,
+    http.method = %request.method(),
+    http.path = %request.uri().path(),
+    http.status = field::Empty,
+  );
+  let correlation_id = fluxcd_utils_telemetry::correlation_id(&span);
+  let instance = request.uri().path().to_string();
+
+  let mut response = match handler(request).instrument(span.clone()).await {
+    Ok(response) => response,
+    Err(problem) => {
+      span.in_scope(|| {
+        let (status, detail) = (problem.status, &problem.detail);
+        if status.is_server_error() {
+          warn!(%status, ?detail, %correlation_id, "request failed");
+        } else {
+          debug!(%status, ?detail, %correlation_id, "request rejected");
+        }
+      });
+      problem.into_response(&instance, &correlation_id)
+    }
+  };
+
+  span.record("http.status", &response.status().as_u16());
+  if let Ok(value) = HeaderValue::from_str(&correlation_id) {
+    response
+      .headers_mut()
+      .insert(HeaderName::from_static(CORRELATION_ID_HEADER), value);
+  }
+
+  Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn problem_document() {
+    let problem = Problem::new(StatusCode::NOT_FOUND).detail("no artifact at this path");
+    let document = serde_json::to_value(problem.document("/kind/name/keys", "abc")).unwrap();
+    assert_eq!(
+      document,
+      serde_json::json!({
+        "type": "about:blank",
+        "title": "Not Found",
+        "status": 404,
+        "detail": "no artifact at this path",
+        "instance": "/kind/name/keys",
+        "correlationId": "abc",
+      })
+    );
+  }
+}
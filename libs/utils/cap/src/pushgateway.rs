@@ -0,0 +1,172 @@
+use crate::scrape::AppMetrics;
+use eyre::WrapErr;
+use prometheus::{Encoder, TextEncoder};
+use reqwest::{header::CONTENT_TYPE, Url};
+use std::{future::Future, time::Duration};
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{debug, info, warn};
+
+/// How often the metrics are pushed, unless configured otherwise.
+pub(crate) const DEFAULT_PUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long pushing the metrics may take.
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Variables holding the credentials of the Pushgateway: either a user name and a password, or a
+/// bearer token.
+const USERNAME_ENV: &str = "METRICS_PUSH_USERNAME";
+const PASSWORD_ENV: &str = "METRICS_PUSH_PASSWORD";
+const TOKEN_ENV: &str = "METRICS_PUSH_TOKEN";
+
+/// How the app authenticates to the Pushgateway.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PushAuth {
+  None,
+  Basic { username: String, password: String },
+  Bearer(String),
+}
+
+impl PushAuth {
+  fn from_env() -> eyre::Result<Self> {
+    let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+    match (var(USERNAME_ENV), var(PASSWORD_ENV), var(TOKEN_ENV)) {
+      (None, None, None) => Ok(Self::None),
+      (None, None, Some(token)) => Ok(Self::Bearer(token)),
+      (Some(username), password, None) => Ok(Self::Basic {
+        username,
+        password: password.unwrap_or_default(),
+      }),
+      (None, Some(_), _) => eyre::bail!("{PASSWORD_ENV} is set without {USERNAME_ENV}"),
+      (Some(_), _, Some(_)) => {
+        eyre::bail!("only one of {USERNAME_ENV} and {TOKEN_ENV} can be set")
+      }
+    }
+  }
+}
+
+/// The URL the metrics of `job` and `instance` are pushed to, grouped by both on the Pushgateway
+/// at `gateway`.
+fn grouping_url(gateway: &Url, job: &str, instance: &str) -> eyre::Result<Url> {
+  let mut url = gateway.clone();
+  url
+    .path_segments_mut()
+    .map_err(|_| eyre::eyre!("'{gateway}' cannot be a base URL"))?
+    .pop_if_empty()
+    .extend(["metrics", "job", job, "instance", instance]);
+
+  Ok(url)
+}
+
+/// Pushes the metrics of the app to a Prometheus Pushgateway, for `run --metrics-push-url`, so
+/// that they reach Prometheus when it cannot scrape the pods, like on edge clusters. The metrics
+/// are still served for scraping as well.
+///
+/// The metrics are grouped by the name of the app as the job, and by the pod as the instance, so
+/// that every replica replaces its own metrics on each push.
+pub(crate) struct MetricsPusher {
+  metrics: AppMetrics,
+  url: Url,
+  interval: Duration,
+  auth: PushAuth,
+  http: reqwest::Client,
+}
+
+impl MetricsPusher {
+  /// Creates the pusher of the metrics of the app `name` to the Pushgateway at `gateway`, every
+  /// `interval`. The credentials are read from the environment.
+  pub(crate) fn new(
+    metrics: AppMetrics,
+    gateway: &str,
+    interval: Duration,
+    name: &str,
+  ) -> eyre::Result<Self> {
+    let gateway = Url::parse(gateway)?;
+    if !matches!(gateway.scheme(), "http" | "https") {
+      eyre::bail!(
+        "unsupported scheme '{}', expected http or https",
+        gateway.scheme()
+      );
+    }
+
+    let instance = std::env::var("POD_NAME")
+      .or_else(|_| std::env::var("HOSTNAME"))
+      .unwrap_or_else(|_| "localhost".to_string());
+    let http = reqwest::Client::builder().timeout(PUSH_TIMEOUT).build()?;
+
+    Ok(Self {
+      metrics,
+      url: grouping_url(&gateway, name, &instance)?,
+      interval: interval.max(Duration::from_secs(1)),
+      auth: PushAuth::from_env()?,
+      http,
+    })
+  }
+
+  /// Pushes the metrics every interval until `signal` completes, and once more then, so that the
+  /// Pushgateway holds the final values. Failed pushes are logged and retried on the next one.
+  pub(crate) async fn run(self, signal: impl Future<Output = ()>) {
+    info!(url = %self.url, interval = ?self.interval, "pushing metrics");
+    futures::pin_mut!(signal);
+    let mut ticks = time::interval(self.interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+      tokio::select! {
+        _ = ticks.tick() => {}
+        _ = &mut signal => break,
+      }
+
+      match self.push().await {
+        Ok(()) => debug!("pushed metrics"),
+        Err(error) => warn!(error = %format!("{error:#}"), "failed to push metrics"),
+      }
+    }
+
+    if let Err(error) = self.push().await {
+      warn!(error = %format!("{error:#}"), "failed to push the final metrics");
+    }
+  }
+
+  async fn push(&self) -> eyre::Result<()> {
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    encoder
+      .encode(&self.metrics.gather(), &mut body)
+      .wrap_err("failed to encode metrics")?;
+
+    let request = self
+      .http
+      .put(self.url.clone())
+      .header(CONTENT_TYPE, encoder.format_type())
+      .body(body);
+    let request = match &self.auth {
+      PushAuth::None => request,
+      PushAuth::Basic { username, password } => request.basic_auth(username, Some(password)),
+      PushAuth::Bearer(token) => request.bearer_auth(token),
+    };
+
+    request.send().await?.error_for_status()?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn metrics_are_grouped_by_job_and_instance() {
+    let gateway = Url::parse("https://pushgateway.monitoring:9091").unwrap();
+    let url = grouping_url(&gateway, "source-controller", "source-controller-7d9f").unwrap();
+    assert_eq!(
+      url.as_str(),
+      "https://pushgateway.monitoring:9091/metrics/job/source-controller/instance/source-controller-7d9f"
+    );
+
+    let gateway = Url::parse("http://gateway/prefix/").unwrap();
+    let url = grouping_url(&gateway, "app", "a/b").unwrap();
+    assert_eq!(
+      url.as_str(),
+      "http://gateway/prefix/metrics/job/app/instance/a%2Fb"
+    );
+  }
+}
@@ -0,0 +1,228 @@
+use eyre::{Report, WrapErr};
+use fluxcd_utils_cops::{context::ReconcileCtx, events::EventRecorder, Controller};
+use k8s_openapi::{apimachinery::pkg::apis::meta::v1::Time, chrono::Utc};
+use kube::{
+  core::DynamicObject,
+  runtime::{controller::ReconcilerAction, events::Reporter, reflector::ObjectRef},
+  Client, CustomResourceExt, Resource,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+  fmt,
+  fs::File,
+  hash,
+  io::{BufRead, BufReader, LineWriter, Write},
+  path::Path,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// How a reconcile ended.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "camelCase")]
+pub enum Outcome {
+  #[serde(rename_all = "camelCase")]
+  Success {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    requeue_after_millis: Option<u64>,
+  },
+  Failed {
+    message: String,
+  },
+}
+
+impl Outcome {
+  fn success(requeue_after: Option<Duration>) -> Self {
+    Self::Success {
+      requeue_after_millis: requeue_after.map(|after| after.as_millis() as u64),
+    }
+  }
+
+  fn failed(error: &Report) -> Self {
+    Self::Failed {
+      message: format!("{error:#}"),
+    }
+  }
+
+  pub(crate) fn from_result(result: Result<&ReconcilerAction, &Report>) -> Self {
+    match result {
+      Ok(action) => Self::success(action.requeue_after),
+      Err(error) => Self::failed(error),
+    }
+  }
+}
+
+/// A single line of an event recording: the resource as the watch delivered it to the reconciler,
+/// and how reconciling it ended.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedReconcile {
+  pub time: Time,
+  /// The `group/kind` of the resource.
+  pub kind: String,
+  pub object: Value,
+  pub outcome: Outcome,
+}
+
+/// Writes every reconcile of the controllers to a file as json lines, for `run --record-events`.
+#[derive(Clone)]
+pub(crate) struct EventLog {
+  out: Arc<Mutex<LineWriter<File>>>,
+}
+
+impl EventLog {
+  pub(crate) fn create(path: &Path) -> eyre::Result<Self> {
+    let file = File::create(path)
+      .wrap_err_with(|| format!("failed to create event recording '{}'", path.display()))?;
+    Ok(Self {
+      out: Arc::new(Mutex::new(LineWriter::new(file))),
+    })
+  }
+
+  /// Appends a reconcile of `object` to the recording. Failing to do so is logged, but does not
+  /// affect the reconcile.
+  pub(crate) fn record<R: Serialize>(&self, kind: &str, object: &R, outcome: Outcome) {
+    let line = serde_json::to_value(object).and_then(|object| {
+      serde_json::to_string(&RecordedReconcile {
+        time: Time(Utc::now()),
+        kind: kind.into(),
+        object,
+        outcome,
+      })
+    });
+
+    let result = match line {
+      Ok(line) => writeln!(self.out.lock().unwrap(), "{line}"),
+      Err(e) => Err(e.into()),
+    };
+    if let Err(error) = result {
+      warn!(%error, "failed to record reconcile");
+    }
+  }
+}
+
+/// A recorded reconcile which was run again by [replay].
+#[derive(Clone, Debug)]
+pub struct Replayed {
+  pub object: ObjectRef<DynamicObject>,
+  pub recorded: Outcome,
+  pub replayed: Outcome,
+}
+
+impl Replayed {
+  pub fn matches(&self) -> bool {
+    self.recorded == self.replayed
+  }
+}
+
+/// Feeds the reconciles of `R` in the recording at `path` (made with `run --record-events`) back
+/// through `controller`, in the order they were recorded, and returns how each of them ended then
+/// and now. Requests made by the controller go to `client`, which tests typically back with a
+/// mock service.
+///
+/// Suspended resources are skipped like the runtime does, but the predicates and namespace filters
+/// are not applied, so every recorded reconcile is run in full. Writes are neither checked against
+/// policies nor validated against the schema of the cluster.
+pub async fn replay<C, R>(controller: C, client: Client, path: &Path) -> eyre::Result<Vec<Replayed>>
+where
+  C: Controller<R> + Send + Sync + 'static,
+  R: CustomResourceExt
+    + Clone
+    + Resource
+    + fmt::Debug
+    + Send
+    + Sync
+    + Serialize
+    + for<'de> Deserialize<'de>
+    + 'static,
+  <R as Resource>::DynamicType: Eq + hash::Hash + Default + Clone,
+{
+  let dt = <R as Resource>::DynamicType::default();
+  let kind = format!("{}/{}", R::group(&dt), R::kind(&dt));
+  let file = File::open(path)
+    .wrap_err_with(|| format!("failed to open event recording '{}'", path.display()))?;
+  let reporter = Reporter {
+    controller: "replay".into(),
+    instance: None,
+  };
+
+  let controller = Arc::new(controller);
+  let mut replayed = Vec::new();
+  for (index, line) in BufReader::new(file).lines().enumerate() {
+    let line = line?;
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    let record: RecordedReconcile = serde_json::from_str(&line)
+      .wrap_err_with(|| format!("invalid record on line {}", index + 1))?;
+    if record.kind != kind {
+      continue;
+    }
+
+    let resource: Arc<R> = Arc::new(
+      serde_json::from_value(record.object)
+        .wrap_err_with(|| format!("invalid {kind} on line {}", index + 1))?,
+    );
+    let outcome = if C::suspended(&resource) {
+      Outcome::success(None)
+    } else {
+      let recorder = EventRecorder::new(client.clone(), reporter.clone(), resource.object_ref(&dt));
+      let ctx = ReconcileCtx::new(
+        client.clone(),
+        recorder,
+        Arc::default(),
+        Arc::default(),
+        None,
+        C::reconcile_timeout(&resource),
+        CancellationToken::new(),
+      );
+      match ctx
+        .run(C::reconcile(
+          controller.clone(),
+          resource.clone(),
+          ctx.clone(),
+        ))
+        .await
+      {
+        Ok(result) => Outcome::from_result(result.as_ref()),
+        Err(aborted) => Outcome::failed(&aborted.into()),
+      }
+    };
+
+    replayed.push(Replayed {
+      object: ObjectRef::from_obj(&*resource).erase(),
+      recorded: record.outcome,
+      replayed: outcome,
+    });
+  }
+
+  Ok(replayed)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn outcomes_round_trip() {
+    let success = Outcome::success(Some(Duration::from_secs(60)));
+    let json = serde_json::to_value(&success).unwrap();
+    assert_eq!(
+      json,
+      serde_json::json!({ "result": "success", "requeueAfterMillis": 60000 })
+    );
+    assert_eq!(serde_json::from_value::<Outcome>(json).unwrap(), success);
+
+    let failed = Outcome::failed(&eyre::eyre!("boom").wrap_err("reconcile"));
+    assert_eq!(
+      failed,
+      Outcome::Failed {
+        message: "reconcile: boom".into()
+      }
+    );
+  }
+}
@@ -0,0 +1,131 @@
+use crate::{health::ControllerStatus, ControllerResourceInfo, DynController};
+use fluxcd_utils_cops::rbac::{Permissions, Scope, READ};
+use k8s_openapi::api::rbac::v1::{ClusterRole, Role};
+use kube::{api::ObjectMeta, Resource};
+
+/// The permissions the runtime needs on the resource reconciled by a controller: watching it,
+/// patching its finalizers and updating its status.
+pub(crate) fn resource_permissions(info: &ControllerResourceInfo) -> Permissions {
+  let scope = if info.namespaced {
+    Scope::Namespaced
+  } else {
+    Scope::Cluster
+  };
+  let status = format!("{}/status", info.plural);
+
+  Permissions::default()
+    .allow(scope, &info.group, &info.plural, READ)
+    .allow(scope, &info.group, &info.plural, ["patch", "update"])
+    .allow(scope, &info.group, &status, ["get", "patch", "update"])
+}
+
+/// The permissions the runtime needs whatever the controllers: recording events, caching the
+/// namespaces, electing the leader for `run --leader-elect` and reporting the status for
+/// `run --report-status`.
+fn runtime_permissions() -> Permissions {
+  let dt = ();
+  let group = ControllerStatus::group(&dt);
+  let plural = ControllerStatus::plural(&dt);
+  let status = format!("{plural}/status");
+
+  Permissions::default()
+    .namespaced("events.k8s.io", "events", ["create", "patch"])
+    .namespaced("coordination.k8s.io", "leases", ["get", "create", "update"])
+    .cluster("", "namespaces", READ)
+    .cluster(&group, &plural, ["get", "create", "patch"])
+    .cluster(&group, &status, ["patch"])
+}
+
+/// The permissions the app needs to run `controllers`.
+fn app_permissions(controllers: &[DynController<'_>]) -> Permissions {
+  let mut permissions = runtime_permissions();
+  for ctrl in controllers {
+    permissions.merge(&ctrl.permissions);
+  }
+
+  permissions
+}
+
+/// The ClusterRole granting the app `name` all of the permissions it needs to run `controllers`.
+pub(crate) fn cluster_role(name: &str, controllers: &[DynController<'_>]) -> ClusterRole {
+  let permissions = app_permissions(controllers);
+  let mut rules = permissions.rules(Scope::Namespaced);
+  rules.extend(permissions.rules(Scope::Cluster));
+
+  ClusterRole {
+    metadata: metadata(name, None),
+    rules: Some(rules),
+    ..ClusterRole::default()
+  }
+}
+
+/// Prints the RBAC roles the app `name` needs to run `controllers`, for `rbac`. All of the
+/// permissions are granted by a ClusterRole named after the app, unless the app only watches
+/// `namespace`, in which case those on namespaced resources are granted by a Role in that
+/// namespace instead.
+pub(crate) fn print_roles(
+  name: &str,
+  controllers: &[DynController<'_>],
+  namespace: Option<&str>,
+) -> eyre::Result<()> {
+  let namespace = match namespace {
+    None => {
+      print!(
+        "{}",
+        serde_yaml::to_string(&cluster_role(name, controllers))?
+      );
+      return Ok(());
+    }
+    Some(namespace) => namespace,
+  };
+
+  let permissions = app_permissions(controllers);
+  let role = Role {
+    metadata: metadata(name, Some(namespace)),
+    rules: Some(permissions.rules(Scope::Namespaced)),
+  };
+  let cluster_role = ClusterRole {
+    metadata: metadata(name, None),
+    rules: Some(permissions.rules(Scope::Cluster)),
+    ..ClusterRole::default()
+  };
+
+  print!("{}", serde_yaml::to_string(&role)?);
+  print!("{}", serde_yaml::to_string(&cluster_role)?);
+  Ok(())
+}
+
+fn metadata(name: &str, namespace: Option<&str>) -> ObjectMeta {
+  ObjectMeta {
+    name: Some(name.into()),
+    namespace: namespace.map(Into::into),
+    ..ObjectMeta::default()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use k8s_openapi::api::rbac::v1::PolicyRule;
+
+  #[test]
+  fn controllers_can_manage_their_resource() {
+    let info = ControllerResourceInfo {
+      group: "source.fluxcd.yolodev.io".into(),
+      kind: "GitHubKeys".into(),
+      plural: "githubkeys".into(),
+      version: "v1alpha1".into(),
+      api_version: "source.fluxcd.yolodev.io/v1alpha1".into(),
+      namespaced: true,
+    };
+
+    let rules = resource_permissions(&info).rules(Scope::Namespaced);
+    let resources = |rule: &PolicyRule| rule.resources.clone().unwrap_or_default();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(resources(&rules[0]), ["githubkeys"]);
+    assert_eq!(rules[0].verbs, ["get", "list", "patch", "update", "watch"]);
+    assert_eq!(resources(&rules[1]), ["githubkeys/status"]);
+    assert_eq!(rules[1].verbs, ["get", "patch", "update"]);
+    assert!(resource_permissions(&info).rules(Scope::Cluster).is_empty());
+  }
+}
@@ -0,0 +1,108 @@
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Why an object is reconciled again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum RequeueReason {
+  /// The object is reconciled on an interval.
+  Interval,
+  /// The last reconcile failed, and is retried.
+  Backoff,
+  /// The namespace of the object opted out of reconciliation, and is checked again.
+  IgnoredNamespace,
+  /// The object was reconciled too often, and is reconciled once its rate limit allows.
+  Throttled,
+}
+
+impl RequeueReason {
+  pub(crate) fn as_str(self) -> &'static str {
+    match self {
+      RequeueReason::Interval => "Interval",
+      RequeueReason::Backoff => "Backoff",
+      RequeueReason::IgnoredNamespace => "IgnoredNamespace",
+      RequeueReason::Throttled => "Throttled",
+    }
+  }
+}
+
+/// An upcoming reconcile of an object, as scheduled by its controller.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ScheduledReconcile {
+  /// Full path of the kind of the object, `group/kind`.
+  pub(crate) kind: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) namespace: Option<String>,
+  pub(crate) name: String,
+  pub(crate) due: Time,
+  pub(crate) reason: RequeueReason,
+}
+
+/// The upcoming reconciles of a controller.
+pub(crate) trait Schedule: Send + Sync {
+  fn scheduled(&self) -> Vec<ScheduledReconcile>;
+}
+
+/// The upcoming reconciles of all `schedules`, the earliest first.
+pub(crate) fn upcoming(schedules: &[Arc<dyn Schedule>]) -> Vec<ScheduledReconcile> {
+  let mut upcoming = schedules
+    .iter()
+    .flat_map(|schedule| schedule.scheduled())
+    .collect::<Vec<_>>();
+  upcoming.sort_by(|a, b| a.due.0.cmp(&b.due.0));
+  upcoming
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use k8s_openapi::chrono::{TimeZone, Utc};
+
+  struct Fixed(Vec<ScheduledReconcile>);
+
+  impl Schedule for Fixed {
+    fn scheduled(&self) -> Vec<ScheduledReconcile> {
+      self.0.clone()
+    }
+  }
+
+  fn scheduled(name: &str, secs: i64, reason: RequeueReason) -> ScheduledReconcile {
+    ScheduledReconcile {
+      kind: "source.fluxcd.yolodev.io/HttpEndpoint".into(),
+      namespace: Some("default".into()),
+      name: name.into(),
+      due: Time(Utc.timestamp(secs, 0)),
+      reason,
+    }
+  }
+
+  #[test]
+  fn upcoming_reconciles_come_earliest_first() {
+    let schedules: Vec<Arc<dyn Schedule>> = vec![
+      Arc::new(Fixed(vec![scheduled("late", 30, RequeueReason::Interval)])),
+      Arc::new(Fixed(vec![scheduled("early", 10, RequeueReason::Backoff)])),
+    ];
+
+    let names = upcoming(&schedules)
+      .into_iter()
+      .map(|scheduled| scheduled.name)
+      .collect::<Vec<_>>();
+    assert_eq!(names, ["early", "late"]);
+  }
+
+  #[test]
+  fn scheduled_reconciles_serialize_as_json() {
+    let json = serde_json::to_value(scheduled("web", 0, RequeueReason::Backoff)).unwrap();
+    assert_eq!(
+      json,
+      serde_json::json!({
+        "kind": "source.fluxcd.yolodev.io/HttpEndpoint",
+        "namespace": "default",
+        "name": "web",
+        "due": "1970-01-01T00:00:00Z",
+        "reason": "Backoff",
+      })
+    );
+  }
+}
@@ -0,0 +1,224 @@
+use crate::problem::{self, Problem};
+use hyper::{
+  header::CONTENT_TYPE,
+  service::{make_service_fn, service_fn},
+  Body, Method, Request, Response, Server, StatusCode,
+};
+use prometheus::{core::Collector, proto::MetricFamily, Encoder, TextEncoder};
+use std::{
+  collections::BTreeMap,
+  convert::Infallible,
+  future::Future,
+  net::SocketAddr,
+  sync::{Arc, Mutex},
+};
+use thiserror::Error;
+use tracing::info;
+
+/// Path of the Prometheus scrape endpoint.
+pub(crate) const METRICS_PATH: &str = "/metrics";
+
+/// Namespace of the metrics of the apps, which the metrics of controllers are named in as well.
+const METRICS_NAMESPACE: &str = "gotk";
+
+/// Subsystems of the metrics recorded by the runtime, which controllers cannot name theirs in.
+const RESERVED_SUBSYSTEMS: [&str; 6] = [
+  "reconcile",
+  "kube_api",
+  "http_client",
+  "watch",
+  "workqueue",
+  "storage",
+];
+
+#[derive(Debug, Error)]
+pub(crate) enum CollectorError {
+  #[error("metric '{0}' must be named '{METRICS_NAMESPACE}_<subsystem>_<name>'")]
+  Unnamespaced(String),
+
+  #[error("metric '{0}' is in a subsystem of the runtime, which are {RESERVED_SUBSYSTEMS:?}")]
+  Reserved(String),
+
+  #[error(transparent)]
+  Registration(#[from] prometheus::Error),
+}
+
+/// Checks that the metric `name` is named in the namespace of the apps, and in a subsystem of its
+/// own, so that it cannot clash with the metrics of the runtime.
+fn check_name(name: &str) -> Result<(), CollectorError> {
+  let subsystem = name
+    .strip_prefix(METRICS_NAMESPACE)
+    .and_then(|name| name.strip_prefix('_'))
+    .filter(|name| name.contains('_'))
+    .ok_or_else(|| CollectorError::Unnamespaced(name.into()))?;
+
+  let reserved = RESERVED_SUBSYSTEMS
+    .iter()
+    .any(|reserved| subsystem.starts_with(&format!("{reserved}_")));
+  if reserved {
+    return Err(CollectorError::Reserved(name.into()));
+  }
+
+  Ok(())
+}
+
+/// Registers `collector` of a controller in the default registry, whose metrics are served and
+/// pushed along with those of the runtime. Its metrics must be named `gotk_<subsystem>_<name>`, in
+/// a subsystem other than those of the runtime, and cannot be registered twice.
+pub(crate) fn register_collector(collector: Box<dyn Collector>) -> Result<(), CollectorError> {
+  for desc in collector.desc() {
+    check_name(&desc.fq_name)?;
+  }
+
+  prometheus::default_registry().register(collector)?;
+  Ok(())
+}
+
+type CollectFn = Box<dyn Fn() -> Vec<MetricFamily> + Send + Sync>;
+
+/// The metrics of the controllers of an app. Every controller records the same metrics, labelled
+/// by the kind of the objects, so their recorders cannot be registered side by side in a
+/// registry: they are collected on every scrape instead, and merged with the metrics of the
+/// default registry (like those of the API client).
+#[derive(Clone, Default)]
+pub(crate) struct AppMetrics {
+  controllers: Arc<Mutex<Vec<CollectFn>>>,
+}
+
+impl AppMetrics {
+  /// Adds the metrics of a controller, as returned by `collect`.
+  pub(crate) fn add(&self, collect: impl Fn() -> Vec<MetricFamily> + Send + Sync + 'static) {
+    self.controllers.lock().unwrap().push(Box::new(collect));
+  }
+
+  /// Gathers all metrics of the app, merging the families of the same name.
+  pub(crate) fn gather(&self) -> Vec<MetricFamily> {
+    let mut families = BTreeMap::<String, MetricFamily>::new();
+    let controllers = self.controllers.lock().unwrap();
+    let collected = controllers.iter().flat_map(|collect| collect());
+    for mut family in prometheus::gather().into_iter().chain(collected) {
+      match families.get_mut(family.get_name()) {
+        Some(merged) => merged.mut_metric().extend(family.take_metric()),
+        None => {
+          families.insert(family.get_name().to_string(), family);
+        }
+      }
+    }
+
+    families
+      .into_values()
+      .filter(|family| !family.get_metric().is_empty())
+      .collect()
+  }
+}
+
+/// The metrics of an app, and the address they are served at.
+pub(crate) struct MetricsServer {
+  pub(crate) metrics: AppMetrics,
+  pub(crate) addr: SocketAddr,
+}
+
+impl MetricsServer {
+  /// Serves the metrics for Prometheus to scrape, until `signal` completes.
+  pub(crate) async fn serve(self, signal: impl Future<Output = ()>) -> eyre::Result<()> {
+    let metrics = self.metrics;
+    let make_service = make_service_fn(move |_| {
+      let metrics = metrics.clone();
+      let service = service_fn(move |request| {
+        let metrics = metrics.clone();
+        problem::handle("metrics", request, |request| respond(metrics, request))
+      });
+
+      async move { Ok::<_, Infallible>(service) }
+    });
+
+    let server = Server::try_bind(&self.addr)?.serve(make_service);
+    info!(addr = %self.addr, "serving metrics");
+    server.with_graceful_shutdown(signal).await?;
+
+    Ok(())
+  }
+}
+
+async fn respond(metrics: AppMetrics, request: Request<Body>) -> Result<Response<Body>, Problem> {
+  if request.uri().path() != METRICS_PATH {
+    return Err(Problem::new(StatusCode::NOT_FOUND).detail("there is no endpoint at this path"));
+  }
+
+  if request.method() != Method::GET {
+    return Err(Problem::new(StatusCode::METHOD_NOT_ALLOWED).detail("metrics only support GET"));
+  }
+
+  let encoder = TextEncoder::new();
+  let mut body = Vec::new();
+  encoder.encode(&metrics.gather(), &mut body).map_err(|_| {
+    Problem::new(StatusCode::INTERNAL_SERVER_ERROR).detail("the metrics could not be encoded")
+  })?;
+
+  let response = Response::builder()
+    .header(CONTENT_TYPE, encoder.format_type())
+    .body(Body::from(body))
+    .expect("response is valid");
+
+  Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use prometheus::{core::Collector, IntCounterVec, Opts};
+
+  #[test]
+  fn families_of_controllers_are_merged() {
+    let metrics = AppMetrics::default();
+    for kind in ["HttpEndpoint", "DnsRecords"] {
+      let counter = IntCounterVec::new(
+        Opts::new("test_merged_total", "Merged across controllers"),
+        &["kind"],
+      )
+      .unwrap();
+      counter.with_label_values(&[kind]).inc();
+      metrics.add(move || counter.collect());
+    }
+
+    let families = metrics.gather();
+    let merged = families
+      .iter()
+      .find(|family| family.get_name() == "test_merged_total")
+      .unwrap();
+    assert_eq!(merged.get_metric().len(), 2);
+  }
+
+  #[test]
+  fn collectors_are_namespaced() {
+    assert!(check_name("gotk_bucket_requests_total").is_ok());
+    assert!(matches!(
+      check_name("bucket_requests_total"),
+      Err(CollectorError::Unnamespaced(_))
+    ));
+    assert!(matches!(
+      check_name("gotk_total"),
+      Err(CollectorError::Unnamespaced(_))
+    ));
+    assert!(matches!(
+      check_name("gotk_reconcile_requests_total"),
+      Err(CollectorError::Reserved(_))
+    ));
+  }
+
+  #[test]
+  fn collectors_are_registered_once() {
+    let counter = || {
+      let opts = Opts::new("registered_total", "Registered once")
+        .subsystem("test_collectors")
+        .namespace(METRICS_NAMESPACE);
+      IntCounterVec::new(opts, &["kind"]).unwrap()
+    };
+
+    register_collector(Box::new(counter())).unwrap();
+    assert!(matches!(
+      register_collector(Box::new(counter())),
+      Err(CollectorError::Registration(_))
+    ));
+  }
+}
@@ -0,0 +1,159 @@
+use crate::ShutdownSignalFuture;
+use std::{
+  collections::BTreeMap,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+use tokio::sync::{mpsc, watch};
+use tracing::debug;
+
+/// How long the app waits for the shutdown to complete by default, before exiting anyway. Below
+/// the 30 seconds Kubernetes waits for pods to stop before killing them.
+pub(crate) const DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// The phases of an orderly shutdown, in the order they are executed. A phase only starts once
+/// every subsystem registered for the previous phase has stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Phase {
+  /// Stop accepting webhooks, so that they do not trigger reconciles which will not run anymore.
+  Webhooks,
+
+  /// Stop starting new reconciles, and drain the ones in flight.
+  Reconcilers,
+
+  /// Flush pending events and telemetry, once nothing is producing them anymore.
+  Events,
+
+  /// Stop the metrics, probe and debug servers, which keep answering until everything else has
+  /// stopped.
+  Servers,
+}
+
+/// Coordinates the shutdown of the subsystems of the app, stopping them phase by phase.
+pub(crate) struct ShutdownCoordinator {
+  phase: watch::Sender<Option<Phase>>,
+  phases: BTreeMap<Phase, (mpsc::Sender<()>, mpsc::Receiver<()>)>,
+}
+
+/// Registration of a subsystem with the [ShutdownCoordinator]. The subsystem is considered
+/// stopped once the handle is dropped.
+pub(crate) struct ShutdownHandle {
+  phase: Phase,
+  current: watch::Receiver<Option<Phase>>,
+  _running: mpsc::Sender<()>,
+}
+
+impl ShutdownCoordinator {
+  pub(crate) fn new() -> Self {
+    let (phase, _) = watch::channel(None);
+
+    Self {
+      phase,
+      phases: BTreeMap::new(),
+    }
+  }
+
+  /// Registers a subsystem to be stopped during `phase`.
+  pub(crate) fn register(&mut self, phase: Phase) -> ShutdownHandle {
+    let (running, _) = self.phases.entry(phase).or_insert_with(|| mpsc::channel(1));
+
+    ShutdownHandle {
+      phase,
+      current: self.phase.subscribe(),
+      _running: running.clone(),
+    }
+  }
+
+  /// Runs through the shutdown phases, waiting for the subsystems of each phase to stop before
+  /// moving on to the next one.
+  pub(crate) async fn shutdown(self) {
+    for (phase, (running, mut stopped)) in self.phases {
+      debug!(?phase, "shutdown phase started");
+      drop(running);
+      let _ = self.phase.send(Some(phase));
+
+      // all senders are dropped once every subsystem in the phase has stopped
+      while stopped.recv().await.is_some() {}
+      debug!(?phase, "shutdown phase completed");
+    }
+  }
+}
+
+impl ShutdownHandle {
+  /// A future which resolves once the subsystem should start shutting down.
+  pub(crate) fn signal(&self) -> ShutdownSignalFuture {
+    let phase = self.phase;
+    let mut current = self.current.clone();
+
+    Box::pin(async move {
+      while !matches!(*current.borrow(), Some(current) if current >= phase) {
+        if current.changed().await.is_err() {
+          // the coordinator is gone, so there is nothing left to wait for
+          return;
+        }
+      }
+    })
+  }
+}
+
+/// The reconciles running across the controllers of the app, so that those still running when it
+/// shuts down can be reported.
+#[derive(Clone, Default)]
+pub(crate) struct InFlight {
+  reconciles: Arc<Mutex<(u64, BTreeMap<u64, String>)>>,
+}
+
+/// A reconcile registered with [InFlight], until the guard is dropped.
+pub(crate) struct InFlightGuard {
+  id: u64,
+  reconciles: Arc<Mutex<(u64, BTreeMap<u64, String>)>>,
+}
+
+impl InFlight {
+  /// Registers the reconcile of `object`, like `source.fluxcd.yolodev.io/Bucket flux-system/podinfo`.
+  pub(crate) fn start(&self, object: String) -> InFlightGuard {
+    let mut reconciles = self.reconciles.lock().unwrap();
+    let (next, running) = &mut *reconciles;
+    let id = *next;
+    *next += 1;
+    running.insert(id, object);
+
+    InFlightGuard {
+      id,
+      reconciles: self.reconciles.clone(),
+    }
+  }
+
+  /// The objects being reconciled, in the order their reconciles started.
+  pub(crate) fn objects(&self) -> Vec<String> {
+    let reconciles = self.reconciles.lock().unwrap();
+    reconciles.1.values().cloned().collect()
+  }
+}
+
+impl Drop for InFlightGuard {
+  fn drop(&mut self) {
+    self.reconciles.lock().unwrap().1.remove(&self.id);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reconciles_are_in_flight_until_dropped() {
+    let in_flight = InFlight::default();
+    let first = in_flight.start("Bucket flux-system/a".into());
+    let second = in_flight.start("Bucket flux-system/b".into());
+    assert_eq!(
+      in_flight.objects(),
+      ["Bucket flux-system/a", "Bucket flux-system/b"]
+    );
+
+    drop(first);
+    assert_eq!(in_flight.objects(), ["Bucket flux-system/b"]);
+    drop(second);
+    assert!(in_flight.objects().is_empty());
+  }
+}
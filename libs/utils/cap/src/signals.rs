@@ -1,12 +1,15 @@
-use crate::ShutdownSignalFuture;
+use crate::{shutdown::InFlight, ShutdownSignalFuture};
 use futures::{
   future::{ready, Shared},
   FutureExt, Stream, StreamExt,
 };
 use signal_hook_tokio::Signals;
-use std::{convert::TryFrom, fmt, future::Future, io, pin::Pin};
+use std::{convert::TryFrom, fmt, future::Future, io, pin::Pin, time::Duration};
 use thiserror::Error;
-use tracing::{event, Level};
+use tracing::{error, event, info, Level};
+
+/// Exit code of the app when it had to be stopped before its shutdown completed.
+const FORCED_EXIT_CODE: i32 = 1;
 
 macro_rules! define_signals {
   (
@@ -102,11 +105,62 @@ impl Signal {
     Ok(signals.filter_map(|s| ready(Signal::try_from(s).ok())))
   }
 
+  /// A signal completing on the first termination signal, which shuts the app down gracefully. A
+  /// second termination signal, like pressing Ctrl-C again while a stuck reconcile holds up the
+  /// shutdown, exits the process right away.
   pub fn shared() -> Result<SharedSignal, SignalWatchError> {
     let mut stream = Self::watch()?;
 
     Ok(SharedSignal::new(Box::pin(async move {
-      let _ = stream.next().await;
+      let signal = match stream.next().await {
+        Some(signal) => signal,
+        None => return,
+      };
+      info!(%signal, "shutting down gracefully, send the signal again to exit immediately");
+
+      tokio::spawn(async move {
+        if let Some(signal) = stream.next().await {
+          error!(%signal, "received a second termination signal, exiting immediately");
+          // like shells report processes killed by a signal
+          exit(128 + signal as i32);
+        }
+      });
     })))
   }
 }
+
+/// Waits for `shutdown` to drain the app, after logging the reconciles still `in_flight`. If
+/// draining takes longer than `timeout`, the process exits right away with a non-zero code, after
+/// logging the reconciles holding it up, so that a stuck reconcile cannot keep the app from
+/// stopping until it is killed.
+pub(crate) async fn drain_or_exit(
+  shutdown: impl Future<Output = ()>,
+  timeout: Duration,
+  in_flight: &InFlight,
+) {
+  let reconciles = in_flight.objects();
+  info!(
+    in_flight = reconciles.len(),
+    ?reconciles,
+    ?timeout,
+    "shutting down, draining in-flight reconciles"
+  );
+
+  if tokio::time::timeout(timeout, shutdown).await.is_err() {
+    let reconciles = in_flight.objects();
+    error!(
+      in_flight = reconciles.len(),
+      ?reconciles,
+      ?timeout,
+      "graceful shutdown timed out, exiting"
+    );
+    exit(FORCED_EXIT_CODE);
+  }
+}
+
+/// Exits the process with `code` without shutting down, after exporting the pending spans so that
+/// the traces of what held up the shutdown are not lost.
+fn exit(code: i32) -> ! {
+  fluxcd_utils_telemetry::flush();
+  std::process::exit(code)
+}
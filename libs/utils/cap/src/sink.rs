@@ -0,0 +1,379 @@
+use crate::replay::Outcome;
+use eyre::WrapErr;
+use k8s_openapi::{apimachinery::pkg::apis::meta::v1::Time, chrono::Utc};
+use kube::{core::DynamicObject, runtime::reflector::ObjectRef};
+use reqwest::{header::CONTENT_TYPE, Url};
+use serde::Serialize;
+use std::{fmt, future::Future, str::FromStr, sync::Arc, time::Duration};
+use tokio::{
+  io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+  net::TcpStream,
+  sync::mpsc::{self, error::TrySendError},
+  time::{self, Instant},
+};
+use tracing::{debug, info, warn};
+
+/// How many events are buffered while the sink is unavailable, unless configured otherwise.
+pub(crate) const DEFAULT_SINK_BUFFER: usize = 1024;
+
+/// How often delivering an event is attempted before it is dropped.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// How long to wait before the first retry of a delivery, doubling for every further retry.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// How long delivering a single event may take.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the buffered events are delivered for once the app shuts down.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Port of NATS servers, if the address of the sink does not have one.
+const NATS_PORT: u16 = 4222;
+
+/// Where the reconcile events of the app are delivered to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum SinkTarget {
+  /// Every event is posted as json to the URL (`http://` or `https://`).
+  Webhook(Url),
+
+  /// Every event is published as json on a subject of a NATS server, from
+  /// `nats://<host>[:<port>]/<subject>`.
+  Nats { addr: String, subject: String },
+}
+
+impl FromStr for SinkTarget {
+  type Err = eyre::Report;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let url = Url::parse(s)?;
+    match url.scheme() {
+      "http" | "https" => Ok(Self::Webhook(url)),
+      "nats" => {
+        let host = match url.host_str() {
+          Some(host) => host,
+          None => eyre::bail!("the address of the NATS server is missing"),
+        };
+        let subject = url.path().trim_start_matches('/');
+        if subject.is_empty() || subject.contains(char::is_whitespace) {
+          eyre::bail!("the path must be the subject to publish on, like nats://nats:4222/gitops");
+        }
+
+        Ok(Self::Nats {
+          addr: format!("{host}:{}", url.port().unwrap_or(NATS_PORT)),
+          subject: subject.to_string(),
+        })
+      }
+      scheme => eyre::bail!("unsupported scheme '{scheme}', expected http, https or nats"),
+    }
+  }
+}
+
+impl fmt::Display for SinkTarget {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Webhook(url) => write!(f, "{url}"),
+      Self::Nats { addr, subject } => write!(f, "nats://{addr}/{subject}"),
+    }
+  }
+}
+
+/// A reconcile of an object, as delivered to the sink.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReconcileEvent {
+  time: Time,
+  controller: String,
+  /// The `group/kind` of the object.
+  kind: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  namespace: Option<String>,
+  name: String,
+  #[serde(flatten)]
+  outcome: Outcome,
+}
+
+/// Queues the reconciles of the controllers for delivery to an external system, for `run
+/// --event-sink`, so that the activity of many clusters can be followed in a single place.
+///
+/// Events are buffered in memory while the sink is unavailable, and delivered in order with
+/// retries. Once the buffer is full, further events are dropped, so that an unavailable sink never
+/// holds up the reconciles.
+#[derive(Clone)]
+pub(crate) struct EventSink {
+  queue: mpsc::Sender<ReconcileEvent>,
+  controller: Arc<str>,
+}
+
+impl EventSink {
+  /// Creates the sink of the app `controller`, and the delivery which has to run for the events to
+  /// reach `target`.
+  pub(crate) fn new(
+    target: SinkTarget,
+    buffer: usize,
+    controller: &str,
+  ) -> eyre::Result<(Self, SinkDelivery)> {
+    let (queue, events) = mpsc::channel(buffer.max(1));
+    let http = reqwest::Client::builder()
+      .timeout(DELIVERY_TIMEOUT)
+      .build()?;
+    let sink = Self {
+      queue,
+      controller: controller.into(),
+    };
+    let delivery = SinkDelivery {
+      target,
+      events,
+      http,
+      nats: None,
+      controller: sink.controller.clone(),
+    };
+
+    Ok((sink, delivery))
+  }
+
+  /// Queues the reconcile of `object`, of kind `kind`, which ended with `outcome`.
+  pub(crate) fn send(&self, kind: &str, object: &ObjectRef<DynamicObject>, outcome: Outcome) {
+    let event = ReconcileEvent {
+      time: Time(Utc::now()),
+      controller: self.controller.to_string(),
+      kind: kind.into(),
+      namespace: object.namespace.clone(),
+      name: object.name.clone(),
+      outcome,
+    };
+
+    match self.queue.try_send(event) {
+      Ok(()) => {}
+      Err(TrySendError::Full(_)) => warn!("event sink buffer is full, dropping event"),
+      // the delivery stopped, as the app is shutting down
+      Err(TrySendError::Closed(_)) => {}
+    }
+  }
+}
+
+/// Delivers the events queued by an [EventSink].
+pub(crate) struct SinkDelivery {
+  target: SinkTarget,
+  events: mpsc::Receiver<ReconcileEvent>,
+  http: reqwest::Client,
+  nats: Option<BufReader<TcpStream>>,
+  controller: Arc<str>,
+}
+
+impl SinkDelivery {
+  /// Delivers events until `signal` completes, and then the events still buffered, for up to
+  /// [FLUSH_TIMEOUT]. Events which cannot be delivered after [MAX_ATTEMPTS] are dropped.
+  pub(crate) async fn run(mut self, signal: impl Future<Output = ()>) {
+    info!(sink = %self.target, "delivering reconcile events");
+    futures::pin_mut!(signal);
+    let mut flush_deadline = None;
+    loop {
+      let event = match flush_deadline {
+        None => tokio::select! {
+          event = self.events.recv() => event,
+          _ = &mut signal => {
+            self.events.close();
+            flush_deadline = Some(Instant::now() + FLUSH_TIMEOUT);
+            continue;
+          }
+        },
+        Some(deadline) if Instant::now() >= deadline => {
+          let mut dropped = 0;
+          while self.events.try_recv().is_ok() {
+            dropped += 1;
+          }
+          if dropped > 0 {
+            warn!(
+              dropped,
+              "failed to deliver buffered events before shutting down"
+            );
+          }
+
+          return;
+        }
+        Some(_) => self.events.recv().await,
+      };
+
+      let event = match event {
+        Some(event) => event,
+        None => return,
+      };
+      let body = match serde_json::to_vec(&event) {
+        Ok(body) => body,
+        Err(error) => {
+          warn!(%error, "failed to encode event");
+          continue;
+        }
+      };
+
+      let mut backoff = INITIAL_BACKOFF;
+      for attempt in 1..=MAX_ATTEMPTS {
+        let error = match self.deliver(&body).await {
+          Ok(()) => break,
+          Err(error) => format!("{error:#}"),
+        };
+
+        // events are only delivered once more while shutting down
+        if attempt == MAX_ATTEMPTS || flush_deadline.is_some() {
+          warn!(%error, sink = %self.target, "failed to deliver event, dropping it");
+          break;
+        }
+
+        debug!(%error, attempt, "failed to deliver event, retrying");
+        tokio::select! {
+          _ = time::sleep(backoff) => {}
+          _ = &mut signal, if flush_deadline.is_none() => {
+            self.events.close();
+            flush_deadline = Some(Instant::now() + FLUSH_TIMEOUT);
+          }
+        }
+        backoff *= 2;
+      }
+    }
+  }
+
+  async fn deliver(&mut self, body: &[u8]) -> eyre::Result<()> {
+    let Self {
+      target,
+      http,
+      nats,
+      controller,
+      ..
+    } = self;
+    match target {
+      SinkTarget::Webhook(url) => {
+        http
+          .post(url.clone())
+          .header(CONTENT_TYPE, "application/json")
+          .body(body.to_vec())
+          .send()
+          .await?
+          .error_for_status()?;
+
+        Ok(())
+      }
+      SinkTarget::Nats { addr, subject } => {
+        let result = time::timeout(DELIVERY_TIMEOUT, async {
+          let connection = match nats {
+            Some(connection) => connection,
+            None => nats.insert(nats_connect(addr, controller).await?),
+          };
+          nats_publish(connection, subject, body).await
+        })
+        .await
+        .unwrap_or_else(|_| Err(eyre::eyre!("timed out publishing to NATS")));
+
+        // connect again for the next attempt, as the connection is in an unknown state
+        if result.is_err() {
+          *nats = None;
+        }
+
+        result
+      }
+    }
+  }
+}
+
+/// Connects to the NATS server at `addr`, as the client `name`.
+async fn nats_connect(addr: &str, name: &str) -> eyre::Result<BufReader<TcpStream>> {
+  let stream = TcpStream::connect(addr)
+    .await
+    .wrap_err_with(|| format!("failed to connect to NATS at {addr}"))?;
+  let mut connection = BufReader::new(stream);
+
+  // the server greets clients with its INFO
+  let mut line = String::new();
+  connection.read_line(&mut line).await?;
+  if !line.starts_with("INFO") {
+    eyre::bail!("unexpected greeting from NATS: {}", line.trim_end());
+  }
+
+  let options = serde_json::json!({ "verbose": false, "pedantic": false, "name": name });
+  let connect = format!("CONNECT {options}\r\n");
+  connection.get_mut().write_all(connect.as_bytes()).await?;
+
+  Ok(connection)
+}
+
+/// Publishes `payload` on `subject`, and waits for the server to confirm that it processed it.
+async fn nats_publish(
+  connection: &mut BufReader<TcpStream>,
+  subject: &str,
+  payload: &[u8],
+) -> eyre::Result<()> {
+  let mut message = format!("PUB {subject} {}\r\n", payload.len()).into_bytes();
+  message.extend_from_slice(payload);
+  // the PONG answering the PING is only sent once the server processed the message before it
+  message.extend_from_slice(b"\r\nPING\r\n");
+  connection.get_mut().write_all(&message).await?;
+
+  loop {
+    let mut line = String::new();
+    if connection.read_line(&mut line).await? == 0 {
+      eyre::bail!("NATS closed the connection");
+    }
+
+    match line.trim_end() {
+      "PONG" => return Ok(()),
+      "PING" => connection.get_mut().write_all(b"PONG\r\n").await?,
+      error if error.starts_with("-ERR") => eyre::bail!("NATS rejected the event: {error}"),
+      // like INFO updates of the cluster
+      _ => {}
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use k8s_openapi::chrono::TimeZone;
+
+  #[test]
+  fn parses_targets() {
+    assert_eq!(
+      "nats://nats.messaging/gitops.events"
+        .parse::<SinkTarget>()
+        .unwrap(),
+      SinkTarget::Nats {
+        addr: "nats.messaging:4222".into(),
+        subject: "gitops.events".into(),
+      }
+    );
+    assert!(matches!(
+      "https://hooks.example.com/flux"
+        .parse::<SinkTarget>()
+        .unwrap(),
+      SinkTarget::Webhook(_)
+    ));
+    assert!("nats://nats:4222".parse::<SinkTarget>().is_err());
+    assert!("ftp://example.com/events".parse::<SinkTarget>().is_err());
+  }
+
+  #[test]
+  fn events_include_the_outcome() {
+    let event = ReconcileEvent {
+      time: Time(Utc.timestamp(0, 0)),
+      controller: "source-controller".into(),
+      kind: "source.fluxcd.yolodev.io/HttpEndpoint".into(),
+      namespace: Some("default".into()),
+      name: "web".into(),
+      outcome: Outcome::Failed {
+        message: "connection refused".into(),
+      },
+    };
+
+    assert_eq!(
+      serde_json::to_value(&event).unwrap(),
+      serde_json::json!({
+        "time": "1970-01-01T00:00:00Z",
+        "controller": "source-controller",
+        "kind": "source.fluxcd.yolodev.io/HttpEndpoint",
+        "namespace": "default",
+        "name": "web",
+        "result": "failed",
+        "message": "connection refused",
+      })
+    );
+  }
+}
@@ -0,0 +1,375 @@
+use crate::Controller;
+use fluxcd_meta::{
+  find_condition, get_reconcile_annotation_value, is_same_reconcile_request, new_condition,
+  reconcile_request_age, remove_condition, set_condition, Condition as MetaCondition, LastFailure,
+  Reason, IGNORE_ANNOTATION,
+};
+use fluxcd_utils_cops::batching::StatusBatching;
+use futures::StreamExt;
+use k8s_openapi::{
+  apimachinery::pkg::apis::meta::v1::{Condition, Time},
+  chrono::Utc,
+};
+use kube::{
+  api::{Patch, PatchParams},
+  runtime::reflector::ObjectRef,
+  Api, Client, CustomResourceExt, Resource, ResourceExt,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+  collections::HashMap,
+  fmt, hash, mem,
+  sync::{Arc, Mutex},
+  time::{Instant, SystemTime},
+};
+use tracing::{debug, warn};
+
+/// Sends the status patches made by the runtime, either right away or in batches.
+pub(crate) struct StatusWriter<R>
+where
+  R: Resource,
+  R::DynamicType: Eq + hash::Hash,
+{
+  client: Client,
+  batcher: Option<Arc<StatusBatcher<R>>>,
+}
+
+/// Queue of status patches waiting to be sent, holding at most one (merged) patch per object.
+pub(crate) struct StatusBatcher<R>
+where
+  R: Resource,
+  R::DynamicType: Eq + hash::Hash,
+{
+  config: StatusBatching,
+  pending: Mutex<HashMap<ObjectRef<R>, Value>>,
+}
+
+impl<R> StatusWriter<R>
+where
+  R: Resource + DeserializeOwned + Clone + fmt::Debug,
+  R::DynamicType: Eq + hash::Hash + Clone + Default,
+{
+  pub(crate) fn new(client: Client, batcher: Option<Arc<StatusBatcher<R>>>) -> Self {
+    Self { client, batcher }
+  }
+
+  /// The latest version of `resource` in the cluster, with any changes made to its status since.
+  async fn latest(&self, resource: &R) -> Option<R> {
+    let api = match resource.namespace() {
+      Some(namespace) => Api::<R>::namespaced(self.client.clone(), &namespace),
+      None => Api::<R>::all(self.client.clone()),
+    };
+
+    match api.get_status(&resource.name()).await {
+      Ok(latest) => Some(latest),
+      Err(error) => {
+        warn!(%error, "failed to get status");
+        None
+      }
+    }
+  }
+
+  async fn patch(&self, resource: &R, status: Value) {
+    match &self.batcher {
+      Some(batcher) => batcher.enqueue(ObjectRef::from_obj(resource), status),
+      None => {
+        patch::<R>(
+          &self.client,
+          resource.namespace(),
+          &resource.name(),
+          &status,
+        )
+        .await
+      }
+    }
+  }
+}
+
+impl<R> StatusBatcher<R>
+where
+  R: Resource + DeserializeOwned + Clone + fmt::Debug,
+  R::DynamicType: Eq + hash::Hash + Clone + Default,
+{
+  pub(crate) fn new(config: StatusBatching) -> Self {
+    Self {
+      config,
+      pending: Mutex::new(HashMap::new()),
+    }
+  }
+
+  fn enqueue(&self, obj: ObjectRef<R>, status: Value) {
+    let mut pending = self.pending.lock().unwrap();
+    match pending.get_mut(&obj) {
+      Some(queued) => merge(queued, status),
+      None => {
+        pending.insert(obj, status);
+      }
+    }
+  }
+
+  fn len(&self) -> usize {
+    self.pending.lock().unwrap().len()
+  }
+
+  /// Sends every queued patch.
+  pub(crate) async fn flush(&self, client: &Client) {
+    let pending = mem::take(&mut *self.pending.lock().unwrap());
+    futures::stream::iter(pending)
+      .for_each_concurrent(
+        self.config.max_in_flight.max(1),
+        |(obj, status)| async move { patch::<R>(client, obj.namespace, &obj.name, &status).await },
+      )
+      .await;
+  }
+}
+
+/// Periodically sends the patches queued in `batcher`, recording the queue depth and how long
+/// each flush took.
+pub(crate) async fn run_batcher<C, R>(
+  controller: Arc<C>,
+  client: Client,
+  batcher: Arc<StatusBatcher<R>>,
+  kind: Arc<str>,
+) where
+  C: Controller<R>,
+  R: CustomResourceExt
+    + Clone
+    + Resource
+    + fmt::Debug
+    + Send
+    + Sync
+    + for<'de> Deserialize<'de>
+    + 'static,
+  R::DynamicType: Eq + hash::Hash + Default + Clone,
+{
+  let mut ticks = tokio::time::interval(batcher.config.flush_interval);
+  loop {
+    ticks.tick().await;
+    controller
+      .metrics()
+      .record_status_queue(&kind, batcher.len());
+
+    let start = Instant::now();
+    batcher.flush(&client).await;
+    controller
+      .metrics()
+      .record_status_flush(&kind, start.elapsed());
+  }
+}
+
+/// Merges the JSON merge patch `patch` into `target`, so that applying the result is the same as
+/// applying both in order.
+fn merge(target: &mut Value, patch: Value) {
+  match (target, patch) {
+    (Value::Object(target), Value::Object(patch)) => {
+      for (key, value) in patch {
+        match target.get_mut(&key) {
+          Some(existing) if value.is_object() => merge(existing, value),
+          _ => {
+            target.insert(key, value);
+          }
+        }
+      }
+    }
+    (target, patch) => *target = patch,
+  }
+}
+
+/// The conditions currently in the status of `resource`, if any.
+fn conditions<R: Serialize>(resource: &R) -> Vec<Condition> {
+  let value = match serde_json::to_value(resource) {
+    Ok(value) => value,
+    Err(_) => return Vec::new(),
+  };
+
+  value
+    .get("status")
+    .and_then(|status| status.get("conditions"))
+    .and_then(|conditions| serde_json::from_value(conditions.clone()).ok())
+    .unwrap_or_default()
+}
+
+/// Marks `resource` as not ready, because its namespace opted out of reconciliation. Nothing is
+/// written if the resource is already marked as such.
+pub(crate) async fn record_ignored<R>(writer: &StatusWriter<R>, resource: &R, namespace: &str)
+where
+  R: Resource + Serialize + DeserializeOwned + Clone + fmt::Debug,
+  R::DynamicType: Eq + hash::Hash + Clone + Default,
+{
+  let reason = Reason::Suspended.to_string();
+  let message = format!("namespace {namespace} is annotated with {IGNORE_ANNOTATION}");
+  let mut conditions = conditions(resource);
+  if find_condition(&conditions, MetaCondition::Ready)
+    .is_some_and(|c| c.status == "False" && c.reason == reason && c.message == message)
+  {
+    return;
+  }
+
+  let generation = resource.meta().generation;
+  set_condition(
+    &mut conditions,
+    new_condition(MetaCondition::Ready, false, reason, message, generation),
+  );
+
+  let status = json!({
+    "status": {
+      "conditions": conditions,
+    }
+  });
+  writer.patch(resource, status).await;
+}
+
+/// Reports the writes a reconcile of `resource` skipped in read-only mode in its `ReadOnly`
+/// condition, or removes the condition once nothing was skipped. The conditions are read back
+/// first, as the reconcile itself may just have updated them.
+pub(crate) async fn record_read_only<R>(writer: &StatusWriter<R>, resource: &R, skipped: &[String])
+where
+  R: Resource + Serialize + DeserializeOwned + Clone + fmt::Debug,
+  R::DynamicType: Eq + hash::Hash + Clone + Default,
+{
+  let latest = match writer.latest(resource).await {
+    Some(latest) => latest,
+    None => return,
+  };
+
+  let mut conditions = conditions(&latest);
+  let existing = find_condition(&conditions, MetaCondition::ReadOnly);
+  if skipped.is_empty() {
+    if existing.is_none() {
+      return;
+    }
+
+    remove_condition(&mut conditions, MetaCondition::ReadOnly);
+  } else {
+    let message = format!("skipped: {}", skipped.join(", "));
+    if existing.is_some_and(|c| c.message == message) {
+      return;
+    }
+
+    let generation = resource.meta().generation;
+    set_condition(
+      &mut conditions,
+      new_condition(
+        MetaCondition::ReadOnly,
+        true,
+        Reason::WritesSkipped,
+        message,
+        generation,
+      ),
+    );
+  }
+
+  let status = json!({
+    "status": {
+      "conditions": conditions,
+    }
+  });
+  writer.patch(resource, status).await;
+}
+
+/// The `lastFailure` block currently in the status of `resource`, if any.
+fn last_failure<R: Serialize>(resource: &R) -> Option<LastFailure> {
+  let value = serde_json::to_value(resource).ok()?;
+  let failure = value.get("status")?.get("lastFailure")?;
+  serde_json::from_value(failure.clone()).ok()
+}
+
+/// Records a failed reconcile of `resource` in its `lastFailure` status block.
+pub(crate) async fn record_failure<R>(
+  writer: &StatusWriter<R>,
+  resource: &R,
+  reason: impl Into<String>,
+  message: String,
+) where
+  R: Resource + Serialize + DeserializeOwned + Clone + fmt::Debug,
+  R::DynamicType: Eq + hash::Hash + Clone + Default,
+{
+  let mut failure = last_failure(resource).unwrap_or_default();
+  failure.record(Time(Utc::now()), reason, message);
+  writer.patch(resource, last_failure_patch(failure)).await;
+}
+
+/// The `lastHandledReconcileAt` currently in the status of `resource`, if any.
+fn last_handled_reconcile_request<R: Serialize>(resource: &R) -> Option<String> {
+  let value = serde_json::to_value(resource).ok()?;
+  let token = value.get("status")?.get("lastHandledReconcileAt")?;
+  token.as_str().map(Into::into)
+}
+
+/// The token of the reconcile request made on `resource` which has not been handled yet, if any:
+/// the value of the annotation, unless it is the same as the `lastHandledReconcileAt` of the status.
+pub(crate) fn pending_reconcile_request<R: Resource + Serialize>(resource: &R) -> Option<&str> {
+  let token = get_reconcile_annotation_value(resource.annotations())?;
+  let handled = last_handled_reconcile_request(resource);
+  match handled {
+    Some(handled) if is_same_reconcile_request(&handled, token) => None,
+    _ => Some(token),
+  }
+}
+
+/// Records a successful reconcile of `resource`, which is next due at `next_reconcile_at`. The
+/// `lastFailure` status block is marked as resolved, if it was not already, and the pending
+/// reconcile request (if any) is marked as handled.
+pub(crate) async fn record_success<R>(
+  writer: &StatusWriter<R>,
+  resource: &R,
+  next_reconcile_at: Option<Time>,
+) where
+  R: Resource + Serialize + DeserializeOwned + Clone + fmt::Debug,
+  R::DynamicType: Eq + hash::Hash + Clone + Default,
+{
+  let mut status = json!({
+    "status": {
+      "nextReconcileAt": next_reconcile_at,
+    }
+  });
+
+  if let Some(mut failure) = last_failure(resource).filter(|f| !f.is_resolved()) {
+    failure.resolve();
+    merge(&mut status, last_failure_patch(failure));
+  }
+
+  if let Some(token) = pending_reconcile_request(resource) {
+    // the age is only known for requests whose token is a timestamp
+    let age = reconcile_request_age(token, SystemTime::now());
+    debug!(%token, ?age, "handled reconcile request");
+    merge(
+      &mut status,
+      json!({
+        "status": {
+          "lastHandledReconcileAt": token,
+        }
+      }),
+    );
+  }
+
+  writer.patch(resource, status).await;
+}
+
+fn last_failure_patch(failure: LastFailure) -> Value {
+  json!({
+    "status": {
+      "lastFailure": failure,
+    }
+  })
+}
+
+async fn patch<R>(client: &Client, namespace: Option<String>, name: &str, status: &Value)
+where
+  R: Resource + DeserializeOwned + Clone + fmt::Debug,
+  R::DynamicType: Default,
+{
+  let api = match namespace {
+    Some(namespace) => Api::<R>::namespaced(client.clone(), &namespace),
+    None => Api::<R>::all(client.clone()),
+  };
+
+  // a failure to update the status should not affect the outcome of the reconcile itself
+  if let Err(error) = api
+    .patch_status(name, &PatchParams::default(), &Patch::Merge(status))
+    .await
+  {
+    warn!(%error, "failed to update status");
+  }
+}
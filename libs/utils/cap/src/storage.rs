@@ -0,0 +1,255 @@
+use crate::{
+  gc::GcSettings,
+  problem::{self, Problem},
+};
+use fluxcd_utils_cops::artifact::ArtifactStorage;
+use hyper::{
+  header::{CONTENT_LENGTH, CONTENT_TYPE, HOST},
+  service::{make_service_fn, service_fn},
+  Body, HeaderMap, Method, Request, Response, Server, StatusCode,
+};
+use serde::Serialize;
+use std::{convert::Infallible, future::Future, io, net::SocketAddr, path::Path, sync::Arc};
+use tokio::fs;
+use tracing::{info, warn};
+
+/// The address artifacts are advertised at, for clients in the cluster to download them from:
+/// `advertised` if given, and otherwise the host name of the pod with the port of `addr`.
+pub(crate) fn advertised_addr(addr: SocketAddr, advertised: Option<&str>) -> String {
+  if let Some(advertised) = advertised {
+    return advertised.to_string();
+  }
+
+  let host = match std::env::var("HOSTNAME") {
+    Ok(host) if !host.is_empty() => host,
+    _ if addr.ip().is_unspecified() => "localhost".to_string(),
+    _ => addr.ip().to_string(),
+  };
+
+  format!("{host}:{}", addr.port())
+}
+
+/// The base URL of the artifacts, as requested in `headers`: the host the request was sent to,
+/// or forwarded from by a proxy like an Ingress, so that the URLs are reachable the same way as
+/// the request was. `None` if the request does not name a valid host.
+fn request_base_url(headers: &HeaderMap) -> Option<String> {
+  let header = |name: &str| headers.get(name)?.to_str().ok();
+  let host = match header("x-forwarded-host") {
+    // the host the first proxy was requested at, if the request went through several
+    Some(forwarded) => forwarded.split(',').next()?.trim(),
+    None => header(HOST.as_str())?.trim(),
+  };
+  let valid = !host.is_empty()
+    && host
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | ':' | '[' | ']'));
+  if !valid {
+    return None;
+  }
+
+  let scheme = match header("x-forwarded-proto") {
+    Some(proto) if proto.trim().eq_ignore_ascii_case("https") => "https",
+    _ => "http",
+  };
+
+  Some(format!("{scheme}://{host}"))
+}
+
+/// The artifacts of an object, with the URLs they can be downloaded from.
+#[derive(Serialize)]
+struct ArtifactIndex {
+  artifacts: Vec<IndexedArtifact>,
+}
+
+#[derive(Serialize)]
+struct IndexedArtifact {
+  path: String,
+  url: String,
+  size: u64,
+}
+
+/// Artifact storage, the address it is served at, and how it is collected. Besides the artifacts,
+/// the server lists those of every object at `<kind>/<namespace>/<name>/`, with URLs at the host
+/// the listing was requested at rather than the advertised address, for consumers which reach the
+/// server through a Service or Ingress of their own.
+pub(crate) struct StorageServer {
+  pub(crate) storage: Arc<ArtifactStorage>,
+  pub(crate) addr: SocketAddr,
+  pub(crate) gc: GcSettings,
+}
+
+impl StorageServer {
+  /// Serves the artifacts over HTTP, until `signal` completes.
+  pub(crate) async fn serve(self, signal: impl Future<Output = ()>) -> eyre::Result<()> {
+    let storage = self.storage;
+    let make_service = make_service_fn(move |_| {
+      let storage = storage.clone();
+      let service = service_fn(move |request| {
+        let storage = storage.clone();
+        problem::handle("artifacts", request, |request| respond(storage, request))
+      });
+
+      async move { Ok::<_, Infallible>(service) }
+    });
+
+    let server = Server::try_bind(&self.addr)?.serve(make_service);
+    info!(addr = %self.addr, "serving artifacts");
+    server.with_graceful_shutdown(signal).await?;
+
+    Ok(())
+  }
+}
+
+async fn respond(
+  storage: Arc<ArtifactStorage>,
+  request: Request<Body>,
+) -> Result<Response<Body>, Problem> {
+  let head = match *request.method() {
+    Method::GET => false,
+    Method::HEAD => true,
+    _ => {
+      return Err(
+        Problem::new(StatusCode::METHOD_NOT_ALLOWED).detail("artifacts only support GET and HEAD"),
+      )
+    }
+  };
+
+  if let Some(dir) = request.uri().path().strip_suffix('/') {
+    let base_url = request_base_url(request.headers());
+    return index(&storage, dir, base_url.as_deref(), head).await;
+  }
+
+  let not_found =
+    || Problem::new(StatusCode::NOT_FOUND).detail("there is no artifact at this path");
+  let file = storage
+    .resolve(request.uri().path())
+    .ok_or_else(not_found)?;
+  let content = match fs::metadata(&file).await {
+    Ok(metadata) if metadata.is_file() => fs::read(&file).await,
+    Ok(_) => return Err(not_found()),
+    Err(error) => Err(error),
+  };
+
+  let content = match content {
+    Ok(content) => content,
+    Err(error) if error.kind() == io::ErrorKind::NotFound => return Err(not_found()),
+    Err(error) => return Err(unreadable(&file, error)),
+  };
+
+  let length = content.len();
+  let body = if head { Body::empty() } else { content.into() };
+  let response = Response::builder()
+    .header(CONTENT_TYPE, "application/octet-stream")
+    .header(CONTENT_LENGTH, length)
+    .body(body)
+    .expect("response is valid");
+
+  Ok(response)
+}
+
+/// Lists the artifacts in the directory `dir` of `storage`, with their URLs at `base_url` if
+/// given, and otherwise at the advertised address.
+async fn index(
+  storage: &ArtifactStorage,
+  dir: &str,
+  base_url: Option<&str>,
+  head: bool,
+) -> Result<Response<Body>, Problem> {
+  let not_found =
+    || Problem::new(StatusCode::NOT_FOUND).detail("there are no artifacts at this path");
+  let resolved = storage.resolve(dir).ok_or_else(not_found)?;
+  match fs::metadata(&resolved).await {
+    Ok(metadata) if metadata.is_dir() => {}
+    Ok(_) => return Err(not_found()),
+    Err(error) if error.kind() == io::ErrorKind::NotFound => return Err(not_found()),
+    Err(error) => return Err(unreadable(&resolved, error)),
+  }
+
+  let dir = dir.trim_start_matches('/');
+  let mut artifacts = Vec::new();
+  let mut entries = fs::read_dir(&resolved)
+    .await
+    .map_err(|error| unreadable(&resolved, error))?;
+  while let Some(entry) = entries
+    .next_entry()
+    .await
+    .map_err(|error| unreadable(&resolved, error))?
+  {
+    let name = entry.file_name().to_string_lossy().into_owned();
+    // artifacts being written
+    if name.starts_with('.') {
+      continue;
+    }
+
+    let metadata = match entry.metadata().await {
+      Ok(metadata) if metadata.is_file() => metadata,
+      _ => continue,
+    };
+
+    let path = format!("{dir}/{name}");
+    let url = match base_url {
+      Some(base_url) => format!("{base_url}/{path}"),
+      None => storage.url(&path),
+    };
+    artifacts.push(IndexedArtifact {
+      path,
+      url,
+      size: metadata.len(),
+    });
+  }
+  artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+
+  let content = serde_json::to_vec(&ArtifactIndex { artifacts }).expect("index is valid JSON");
+  let length = content.len();
+  let body = if head { Body::empty() } else { content.into() };
+  let response = Response::builder()
+    .header(CONTENT_TYPE, "application/json")
+    .header(CONTENT_LENGTH, length)
+    .body(body)
+    .expect("response is valid");
+
+  Ok(response)
+}
+
+fn unreadable(file: &Path, error: io::Error) -> Problem {
+  warn!(%error, file = %file.display(), "failed to read artifact");
+  Problem::new(StatusCode::INTERNAL_SERVER_ERROR).detail("the artifact could not be read")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn headers(entries: &[(&'static str, &str)]) -> HeaderMap {
+    entries
+      .iter()
+      .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+      .collect()
+  }
+
+  #[test]
+  fn artifact_urls_follow_the_requested_host() {
+    let direct = headers(&[("host", "source-controller.flux-system:9090")]);
+    assert_eq!(
+      request_base_url(&direct).as_deref(),
+      Some("http://source-controller.flux-system:9090")
+    );
+
+    let forwarded = headers(&[
+      ("host", "source-controller.flux-system:9090"),
+      (
+        "x-forwarded-host",
+        "artifacts.example.com, ingress.internal",
+      ),
+      ("x-forwarded-proto", "https"),
+    ]);
+    assert_eq!(
+      request_base_url(&forwarded).as_deref(),
+      Some("https://artifacts.example.com")
+    );
+
+    assert_eq!(request_base_url(&HeaderMap::new()), None);
+    let invalid = headers(&[("host", "example.com/other")]);
+    assert_eq!(request_base_url(&invalid), None);
+  }
+}
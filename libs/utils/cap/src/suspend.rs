@@ -0,0 +1,100 @@
+use crate::status;
+use fluxcd_meta::Reason;
+use fluxcd_utils_cops::context::ReconcileCtx;
+use kube::{
+  runtime::{
+    events::{Event, EventType},
+    reflector::ObjectRef,
+  },
+  Resource,
+};
+use serde::Serialize;
+use std::{
+  collections::{HashMap, HashSet},
+  hash,
+  sync::Mutex,
+};
+use tracing::warn;
+
+/// Keeps track of the reconcile requests which were ignored because the resource they were made
+/// on is suspended, so that each of them is only reported once.
+pub(crate) struct IgnoredRequests<R>
+where
+  R: Resource,
+  R::DynamicType: Eq + hash::Hash,
+{
+  reported: Mutex<HashMap<ObjectRef<R>, String>>,
+}
+
+impl<R> IgnoredRequests<R>
+where
+  R: Resource,
+  R::DynamicType: Eq + hash::Hash + Clone,
+{
+  pub(crate) fn new() -> Self {
+    Self {
+      reported: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// Records that the request `token` made on `obj` is ignored. Returns whether it had not been
+  /// recorded before, and thus still needs to be reported.
+  pub(crate) fn record(&self, obj: ObjectRef<R>, token: &str) -> bool {
+    let mut reported = self.reported.lock().unwrap();
+    if reported.get(&obj).is_some_and(|reported| reported == token) {
+      return false;
+    }
+
+    reported.insert(obj, token.into());
+    true
+  }
+
+  /// Forgets `obj`, used once it is no longer suspended.
+  pub(crate) fn forget(&self, obj: &ObjectRef<R>) {
+    self.reported.lock().unwrap().remove(obj);
+  }
+
+  pub(crate) fn retain(&self, live: &HashSet<ObjectRef<R>>) {
+    self
+      .reported
+      .lock()
+      .unwrap()
+      .retain(|obj, _| live.contains(obj));
+  }
+}
+
+/// Reports the reconcile request made on the suspended `resource` as ignored, if there is one that
+/// has not been handled yet. `lastHandledReconcileAt` is left untouched, so the request is only
+/// marked as handled once the resource is resumed and reconciled.
+pub(crate) async fn report_ignored_request<R>(
+  ignored: &IgnoredRequests<R>,
+  obj: ObjectRef<R>,
+  resource: &R,
+  ctx: &ReconcileCtx,
+) where
+  R: Resource + Serialize,
+  R::DynamicType: Eq + hash::Hash + Clone,
+{
+  let token = match status::pending_reconcile_request(resource) {
+    Some(token) => token,
+    None => return,
+  };
+
+  if !ignored.record(obj, token) {
+    return;
+  }
+
+  let event = Event {
+    type_: EventType::Normal,
+    reason: Reason::Suspended.to_string(),
+    note: Some(format!(
+      "reconcile request {token} is ignored, as the resource is suspended"
+    )),
+    action: "Reconcile".into(),
+    secondary: None,
+  };
+
+  if let Err(error) = ctx.recorder().publish(event).await {
+    warn!(%error, "failed to publish event");
+  }
+}
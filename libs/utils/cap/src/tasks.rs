@@ -0,0 +1,165 @@
+use futures::{future::LocalBoxFuture, stream::FuturesUnordered, Future, FutureExt, StreamExt};
+use std::fmt;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, warn};
+
+/// The tasks of an app, like its controllers and the servers next to them, each under a label so
+/// that failures can be attributed to the task they happened in.
+///
+/// Tasks run on the task awaiting [join](Self::join), rather than being spawned, as they borrow
+/// from the app. The first task to fail cancels the [failure token](Self::failure), which shuts
+/// the app down, so that it never keeps running with one of its tasks gone.
+pub(crate) struct TaskGroup<'a> {
+  tasks: FuturesUnordered<LocalBoxFuture<'a, (usize, eyre::Result<()>)>>,
+  labels: Vec<String>,
+  failure: CancellationToken,
+}
+
+/// How the tasks of a [TaskGroup] ended, in the order they were added.
+pub(crate) struct TaskSummary {
+  exits: Vec<(String, eyre::Result<()>)>,
+
+  /// The index of the task whose failure shut the app down, if any.
+  cause: Option<usize>,
+}
+
+impl<'a> TaskGroup<'a> {
+  pub(crate) fn new() -> Self {
+    Self {
+      tasks: FuturesUnordered::new(),
+      labels: Vec::new(),
+      failure: CancellationToken::new(),
+    }
+  }
+
+  /// Cancelled once the first task fails.
+  pub(crate) fn failure(&self) -> CancellationToken {
+    self.failure.clone()
+  }
+
+  /// Adds `task`, labelled `label`, like `controller source.fluxcd.yolodev.io/Bucket`.
+  pub(crate) fn spawn(
+    &mut self,
+    label: impl Into<String>,
+    task: impl Future<Output = eyre::Result<()>> + 'a,
+  ) {
+    let index = self.labels.len();
+    self.labels.push(label.into());
+    self
+      .tasks
+      .push(task.map(move |result| (index, result)).boxed_local());
+  }
+
+  /// Runs the tasks until all of them have ended.
+  pub(crate) async fn join(mut self) -> TaskSummary {
+    let mut results = std::iter::repeat_with(|| None)
+      .take(self.labels.len())
+      .collect::<Vec<_>>();
+    let mut cause = None;
+    while let Some((index, result)) = self.tasks.next().await {
+      let label = &self.labels[index];
+      match &result {
+        Ok(()) => debug!(task = %label, "task stopped"),
+        Err(error) if cause.is_none() => {
+          error!(task = %label, error = %format!("{error:#}"), "task failed, shutting down");
+          cause = Some(index);
+          self.failure.cancel();
+        }
+        Err(error) => warn!(task = %label, error = %format!("{error:#}"), "task failed"),
+      }
+
+      results[index] = Some(result);
+    }
+
+    let exits = self
+      .labels
+      .into_iter()
+      .zip(results)
+      .map(|(label, result)| (label, result.unwrap_or(Ok(()))))
+      .collect();
+
+    TaskSummary { exits, cause }
+  }
+}
+
+impl TaskSummary {
+  /// The failure which shut the app down, attributed to its task.
+  pub(crate) fn into_result(self) -> eyre::Result<()> {
+    let cause = match self.cause {
+      Some(cause) => cause,
+      None => return Ok(()),
+    };
+
+    let (label, result) = self.exits.into_iter().nth(cause).expect("cause is a task");
+    result.map_err(|error| error.wrap_err(format!("{label} failed")))
+  }
+}
+
+impl fmt::Display for TaskSummary {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.cause {
+      Some(cause) => write!(f, "shut down after {} failed", self.exits[cause].0)?,
+      None => write!(f, "shut down on request")?,
+    }
+
+    for (label, result) in &self.exits {
+      match result {
+        Ok(()) => write!(f, "; {label}: stopped")?,
+        Err(error) => write!(f, "; {label}: failed: {error:#}")?,
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use futures::{executor::block_on, future};
+
+  #[test]
+  fn first_failure_cancels_the_others() {
+    let mut tasks = TaskGroup::new();
+    let failure = tasks.failure();
+    tasks.spawn("controller source.fluxcd.yolodev.io/Bucket", async move {
+      failure.cancelled().await;
+      Ok(())
+    });
+    tasks.spawn(
+      "metrics-server",
+      future::ready(Err(eyre::eyre!("address in use"))),
+    );
+    let failure = tasks.failure();
+    tasks.spawn("probe-server", async move {
+      failure.cancelled().await;
+      Err(eyre::eyre!("stopped after the metrics"))
+    });
+
+    let summary = block_on(tasks.join());
+    assert_eq!(
+      summary.to_string(),
+      "shut down after metrics-server failed; \
+       controller source.fluxcd.yolodev.io/Bucket: stopped; \
+       metrics-server: failed: address in use; \
+       probe-server: failed: stopped after the metrics"
+    );
+    assert_eq!(
+      format!("{:#}", summary.into_result().unwrap_err()),
+      "metrics-server failed: address in use"
+    );
+  }
+
+  #[test]
+  fn stopping_is_not_a_failure() {
+    let mut tasks = TaskGroup::new();
+    tasks.spawn("status-report", future::ready(Ok(())));
+
+    let summary = block_on(tasks.join());
+    assert_eq!(
+      summary.to_string(),
+      "shut down on request; status-report: stopped"
+    );
+    assert!(summary.into_result().is_ok());
+  }
+}
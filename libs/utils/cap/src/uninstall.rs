@@ -0,0 +1,210 @@
+use crate::{health::ControllerStatus, ControllerResourceInfo, DynController};
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use kube::{
+  api::{DeleteParams, ListParams, Patch, PatchParams, PropagationPolicy},
+  core::DynamicObject,
+  Api, Client, CustomResourceExt, ResourceExt,
+};
+use serde_json::json;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+/// Domain of the finalizers of this project, like `source.fluxcd.yolodev.io/cleanup`.
+const FINALIZER_DOMAIN: &str = "fluxcd.yolodev.io";
+
+/// How long to wait for the CRDs to be deleted, once all of their objects were deleted.
+const CRD_DELETE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How uninstalling treats the objects of the project.
+pub(crate) struct UninstallOptions {
+  /// Keep the objects generated from the objects of the project, rather than having them deleted
+  /// with their owner.
+  pub(crate) keep_children: bool,
+
+  /// Only report what would be removed, through server-side dry runs.
+  pub(crate) dry_run: bool,
+}
+
+/// Removes the project from the cluster, for `uninstall`: the finalizers of the project are
+/// removed from all objects of the kinds of `controllers`, which are then deleted along with the
+/// objects they own, and finally the CRDs are deleted. Without the finalizers, objects cannot get
+/// stuck deleting once the controllers are gone, which would keep their namespaces and the CRDs
+/// from being deleted as well.
+pub(crate) async fn uninstall(
+  controllers: Vec<DynController<'_>>,
+  options: UninstallOptions,
+) -> eyre::Result<()> {
+  let client = Client::try_default().await?;
+  let mut crds = Vec::new();
+  for ctrl in controllers {
+    delete_objects(&client, &ctrl.info, &options).await?;
+    crds.push(format!("{}.{}", ctrl.info.plural, ctrl.info.group));
+  }
+
+  // the status reports of the controllers are deleted along with their CRD
+  crds.push(ControllerStatus::crd_name().to_string());
+
+  let api = Api::<CustomResourceDefinition>::all(client);
+  let params = DeleteParams {
+    dry_run: options.dry_run,
+    ..DeleteParams::default()
+  };
+  for name in &crds {
+    match api.delete(name, &params).await {
+      Ok(_) => println!("{name}: deleted{}", dry_run_suffix(&options)),
+      Err(kube::Error::Api(e)) if e.code == 404 => println!("{name}: not installed"),
+      Err(e) => eyre::bail!("{name}: {e}"),
+    }
+  }
+
+  if options.dry_run {
+    return Ok(());
+  }
+
+  // the CRDs are only gone once the API server removed what is left of their objects
+  let deadline = Instant::now() + CRD_DELETE_TIMEOUT;
+  for name in &crds {
+    loop {
+      match api.get(name).await {
+        Err(kube::Error::Api(e)) if e.code == 404 => break,
+        Err(e) => eyre::bail!("{name}: {e}"),
+        Ok(_) if Instant::now() >= deadline => {
+          eyre::bail!("{name} was not deleted in time, check the finalizers of its objects")
+        }
+        Ok(_) => time::sleep(Duration::from_secs(1)).await,
+      }
+    }
+  }
+
+  println!("uninstalled");
+  Ok(())
+}
+
+/// Removes the finalizers of the project from every object of `info`, and deletes the objects.
+async fn delete_objects(
+  client: &Client,
+  info: &ControllerResourceInfo,
+  options: &UninstallOptions,
+) -> eyre::Result<()> {
+  let resource = info.api_resource();
+  let api = |namespace: Option<&str>| match namespace {
+    Some(namespace) => Api::<DynamicObject>::namespaced_with(client.clone(), namespace, &resource),
+    None => Api::<DynamicObject>::all_with(client.clone(), &resource),
+  };
+
+  let objects = match api(None).list(&ListParams::default()).await {
+    Ok(objects) => objects,
+    // the CRD is not installed, so there is nothing to delete
+    Err(kube::Error::Api(e)) if e.code == 404 => return Ok(()),
+    Err(e) => return Err(e.into()),
+  };
+
+  let patch_params = PatchParams {
+    dry_run: options.dry_run,
+    ..PatchParams::default()
+  };
+  let delete_params = DeleteParams {
+    dry_run: options.dry_run,
+    propagation_policy: Some(if options.keep_children {
+      PropagationPolicy::Orphan
+    } else {
+      PropagationPolicy::Background
+    }),
+    ..DeleteParams::default()
+  };
+
+  let mut failed = 0;
+  for obj in objects {
+    let name = obj.name();
+    let api = api(obj.namespace().as_deref());
+    let (managed, others) = obj
+      .finalizers()
+      .iter()
+      .cloned()
+      .partition::<Vec<_>, _>(|finalizer| is_managed_finalizer(finalizer));
+
+    if !managed.is_empty() {
+      let patch = json!({
+        "metadata": {
+          "finalizers": others,
+          "resourceVersion": obj.resource_version(),
+        }
+      });
+      match api.patch(&name, &patch_params, &Patch::Merge(&patch)).await {
+        Ok(_) => println!(
+          "{}/{name}: removed finalizers {}{}",
+          resource.kind,
+          managed.join(", "),
+          dry_run_suffix(options)
+        ),
+        // deleted since it was listed
+        Err(kube::Error::Api(e)) if e.code == 404 => continue,
+        Err(e) => {
+          failed += 1;
+          eprintln!("{}/{name}: {e}", resource.kind);
+          continue;
+        }
+      }
+    }
+
+    match api.delete(&name, &delete_params).await {
+      Ok(_) => println!(
+        "{}/{name}: deleted{}",
+        resource.kind,
+        dry_run_suffix(options)
+      ),
+      Err(kube::Error::Api(e)) if e.code == 404 => (),
+      Err(e) => {
+        failed += 1;
+        eprintln!("{}/{name}: {e}", resource.kind);
+      }
+    }
+
+    if !others.is_empty() {
+      println!(
+        "{}/{name}: waiting for the finalizers {} of other controllers",
+        resource.kind,
+        others.join(", ")
+      );
+    }
+  }
+
+  if failed > 0 {
+    eyre::bail!(
+      "{failed} {} object(s) could not be deleted, the CRDs are kept",
+      resource.kind
+    );
+  }
+
+  Ok(())
+}
+
+fn dry_run_suffix(options: &UninstallOptions) -> &'static str {
+  if options.dry_run {
+    " (dry run)"
+  } else {
+    ""
+  }
+}
+
+/// Whether `finalizer` belongs to this project, like `source.fluxcd.yolodev.io/cleanup`.
+fn is_managed_finalizer(finalizer: &str) -> bool {
+  let domain = finalizer
+    .split_once('/')
+    .map_or(finalizer, |(domain, _)| domain);
+  domain == FINALIZER_DOMAIN || domain.ends_with(&format!(".{FINALIZER_DOMAIN}"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn only_finalizers_of_the_project_are_managed() {
+    assert!(is_managed_finalizer("fluxcd.yolodev.io/cleanup"));
+    assert!(is_managed_finalizer("source.fluxcd.yolodev.io/artifacts"));
+    assert!(!is_managed_finalizer("finalizers.fluxcd.io"));
+    assert!(!is_managed_finalizer("kubernetes.io/pv-protection"));
+    assert!(!is_managed_finalizer("notfluxcd.yolodev.io/cleanup"));
+  }
+}
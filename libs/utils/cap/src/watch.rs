@@ -0,0 +1,80 @@
+use kube::api::ListParams;
+use std::time::Duration;
+
+/// Longest server-side timeout of a watch request, below the 5 minutes the API server closes
+/// watches after anyway.
+pub(crate) const MAX_WATCH_TIMEOUT: Duration = Duration::from_secs(294);
+
+/// How the controllers watch their resources, for `run --watch-timeout` and
+/// `--no-watch-bookmarks`.
+///
+/// Bookmarks keep the resource version a watch is resumed from recent while no object changes, so
+/// that resuming it does not fail with 410 Gone, which is only recovered from by listing all of the
+/// objects again. They should only be disabled for API servers mishandling them.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct WatchSettings {
+  /// Server-side timeout of the watch requests, after which they are resumed. That of the
+  /// Kubernetes client by default.
+  pub(crate) timeout: Option<Duration>,
+
+  /// Do not ask the API server for bookmarks.
+  pub(crate) no_bookmarks: bool,
+}
+
+impl WatchSettings {
+  pub(crate) fn validate(&self) -> eyre::Result<()> {
+    match self.timeout {
+      Some(timeout) if timeout.is_zero() || timeout > MAX_WATCH_TIMEOUT => eyre::bail!(
+        "the watch timeout must be between 1 and {} seconds",
+        MAX_WATCH_TIMEOUT.as_secs()
+      ),
+      _ => Ok(()),
+    }
+  }
+
+  /// The parameters of the watch of a controller, on the objects matching `labels` if given.
+  pub(crate) fn params(&self, labels: Option<&str>) -> ListParams {
+    let mut params = ListParams::default();
+    if let Some(labels) = labels {
+      params = params.labels(labels);
+    }
+    if let Some(timeout) = self.timeout {
+      params = params.timeout(timeout.as_secs() as u32);
+    }
+    if self.no_bookmarks {
+      params = params.disable_bookmarks();
+    }
+
+    params
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn watches_are_tuned() {
+    let params = WatchSettings::default().params(None);
+    assert_eq!((params.timeout, params.bookmarks), (None, true));
+
+    let settings = WatchSettings {
+      timeout: Some(Duration::from_secs(60)),
+      no_bookmarks: true,
+    };
+    let params = settings.params(Some("shard=a"));
+    assert_eq!(params.label_selector.as_deref(), Some("shard=a"));
+    assert_eq!((params.timeout, params.bookmarks), (Some(60), false));
+  }
+
+  #[test]
+  fn timeouts_are_bounded() {
+    let timeout = |secs| WatchSettings {
+      timeout: Some(Duration::from_secs(secs)),
+      no_bookmarks: false,
+    };
+    assert!(timeout(294).validate().is_ok());
+    assert!(timeout(0).validate().is_err());
+    assert!(timeout(300).validate().is_err());
+  }
+}
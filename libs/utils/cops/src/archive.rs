@@ -0,0 +1,135 @@
+use flate2::{write::GzEncoder, Compression};
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+/// Mode of the files in archives, whatever their mode on disk.
+const FILE_MODE: u32 = 0o644;
+
+/// Packs `files`, by path within the archive, into a gzipped tarball. Files are sorted by path and
+/// have fixed metadata (mode, owner and modification time), so that the same files always result
+/// in the same archive, and so in the same checksum.
+pub fn pack<P, C>(files: impl IntoIterator<Item = (P, C)>) -> io::Result<Vec<u8>>
+where
+  P: AsRef<str>,
+  C: AsRef<[u8]>,
+{
+  let mut files = files.into_iter().collect::<Vec<_>>();
+  files.sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+
+  let mut archive = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+  for (path, content) in &files {
+    let content = content.as_ref();
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(FILE_MODE);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mtime(0);
+    archive.append_data(&mut header, path.as_ref(), content)?;
+  }
+
+  archive.into_inner()?.finish()
+}
+
+/// Packs the regular files under `dir` like [pack], by their path relative to `dir`, skipping
+/// those for which `exclude(path, is_dir)` holds, like the files matching the `.sourceignore` of
+/// a source. Excluded directories are not descended into, and symlinks are skipped, so that an
+/// archive never holds files from outside of `dir`.
+pub fn pack_dir(dir: &Path, exclude: impl Fn(&str, bool) -> bool) -> io::Result<Vec<u8>> {
+  let mut files = Vec::new();
+  collect(dir, "", &exclude, &mut files)?;
+
+  let files = files
+    .into_iter()
+    .map(|(path, file)| Ok((path, fs::read(file)?)))
+    .collect::<io::Result<Vec<_>>>()?;
+  pack(files)
+}
+
+fn collect(
+  dir: &Path,
+  prefix: &str,
+  exclude: &impl Fn(&str, bool) -> bool,
+  files: &mut Vec<(String, PathBuf)>,
+) -> io::Result<()> {
+  for entry in fs::read_dir(dir)? {
+    let entry = entry?;
+    let name = entry.file_name();
+    let name = name.to_str().ok_or_else(|| {
+      io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("file name {name:?} in '{}' is not UTF-8", dir.display()),
+      )
+    })?;
+    let path = format!("{prefix}{name}");
+
+    let file_type = entry.file_type()?;
+    if file_type.is_dir() {
+      if !exclude(&path, true) {
+        collect(&entry.path(), &format!("{path}/"), exclude, files)?;
+      }
+    } else if file_type.is_file() && !exclude(&path, false) {
+      files.push((path, entry.path()));
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use flate2::read::GzDecoder;
+  use std::io::Read;
+
+  fn entries(archive: &[u8]) -> Vec<(String, String)> {
+    let mut archive = tar::Archive::new(GzDecoder::new(archive));
+    let mut entries = Vec::new();
+    for entry in archive.entries().unwrap() {
+      let mut entry = entry.unwrap();
+      assert_eq!(entry.header().mtime().unwrap(), 0);
+      assert_eq!(entry.header().uid().unwrap(), 0);
+      let path = entry.path().unwrap().to_string_lossy().into_owned();
+      let mut content = String::new();
+      entry.read_to_string(&mut content).unwrap();
+      entries.push((path, content));
+    }
+
+    entries
+  }
+
+  #[test]
+  fn archives_do_not_depend_on_the_order_of_files() {
+    let a = pack([("b.yaml", "b"), ("a/c.yaml", "c")]).unwrap();
+    let b = pack([("a/c.yaml", "c"), ("b.yaml", "b")]).unwrap();
+    assert_eq!(a, b);
+    assert_eq!(
+      entries(&a),
+      [
+        ("a/c.yaml".to_string(), "c".to_string()),
+        ("b.yaml".to_string(), "b".to_string())
+      ]
+    );
+  }
+
+  #[test]
+  fn packs_directories_without_excluded_files() {
+    let dir = std::env::temp_dir().join(format!("fluxcd-archive-{}", std::process::id()));
+    fs::create_dir_all(dir.join("apps/.git")).unwrap();
+    fs::write(dir.join("apps/deploy.yaml"), "deploy").unwrap();
+    fs::write(dir.join("apps/.git/HEAD"), "ref").unwrap();
+    fs::write(dir.join("logo.png"), "png").unwrap();
+
+    let archive = pack_dir(&dir, |path, is_dir| {
+      (is_dir && path.ends_with(".git")) || path.ends_with(".png")
+    });
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+      entries(&archive.unwrap()),
+      [("apps/deploy.yaml".to_string(), "deploy".to_string())]
+    );
+  }
+}
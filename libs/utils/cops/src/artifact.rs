@@ -0,0 +1,202 @@
+use crate::archive;
+use fluxcd_meta::{Algorithm, Artifact, Checksum};
+use k8s_openapi::{apimachinery::pkg::apis::meta::v1::Time, chrono::Utc};
+use ring::digest::{digest, SHA256};
+use std::{
+  io,
+  path::{Path, PathBuf},
+};
+use tokio::fs;
+
+/// Storage for the artifacts produced by controllers, which the controller app serves over HTTP.
+/// Every object gets a directory of its own, `<kind>/<namespace>/<name>` (or `<kind>/<name>` for
+/// cluster-scoped objects), holding its artifacts by file name.
+///
+/// Artifacts are left in place once no object references them, until the app garbage collects
+/// them.
+#[derive(Clone, Debug)]
+pub struct ArtifactStorage {
+  root: PathBuf,
+  base_url: String,
+}
+
+impl ArtifactStorage {
+  /// Stores artifacts under `root`, advertising them at `http://<advertised_addr>/`.
+  pub fn new(root: impl Into<PathBuf>, advertised_addr: &str) -> Self {
+    Self {
+      root: root.into(),
+      base_url: format!("http://{}", advertised_addr.trim_end_matches('/')),
+    }
+  }
+
+  pub fn root(&self) -> &Path {
+    &self.root
+  }
+
+  /// The directory of the artifacts of the objects of `kind`, relative to the root.
+  pub fn kind_dir(kind: &str) -> String {
+    kind.to_ascii_lowercase()
+  }
+
+  /// The path of the artifact `file` of the object `name` of `kind`, relative to the root.
+  pub fn artifact_path(kind: &str, namespace: Option<&str>, name: &str, file: &str) -> String {
+    let kind = Self::kind_dir(kind);
+    match namespace {
+      Some(namespace) => format!("{kind}/{namespace}/{name}/{file}"),
+      None => format!("{kind}/{name}/{file}"),
+    }
+  }
+
+  /// The URL the artifact at `path` is served at.
+  pub fn url(&self, path: &str) -> String {
+    format!("{}/{path}", self.base_url)
+  }
+
+  /// Writes `content` as the artifact at `path`, replacing its previous content at once, so that
+  /// nobody ever downloads a partially written artifact.
+  pub async fn store(&self, path: &str, revision: &str, content: &[u8]) -> io::Result<Artifact> {
+    let file = self.resolve(path).ok_or_else(|| invalid_path(path))?;
+
+    // hidden, so the partially written file is never served
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    let temp = file.with_file_name(format!(".{name}.tmp"));
+    if let Some(dir) = file.parent() {
+      fs::create_dir_all(dir).await?;
+    }
+    fs::write(&temp, content).await?;
+    fs::rename(&temp, &file).await?;
+
+    Ok(Artifact::new(
+      path,
+      self.url(path),
+      revision,
+      checksum(content),
+      Time(Utc::now()),
+    ))
+  }
+
+  /// Like [store](Self::store), but keeps `previous` if it already holds `content` at `path` and
+  /// its file is still in place, so that the artifact only changes along with its content.
+  pub async fn update(
+    &self,
+    previous: Option<&Artifact>,
+    path: &str,
+    revision: &str,
+    content: &[u8],
+  ) -> io::Result<Artifact> {
+    let unchanged = previous.filter(|previous| {
+      previous.path() == Some(path)
+        && previous.url() == Some(&*self.url(path))
+        && previous.checksum() == Some(&*checksum(content))
+    });
+
+    if let Some((previous, file)) = unchanged.zip(self.resolve(path)) {
+      if fs::metadata(&file).await.is_ok() {
+        return Ok(previous.clone());
+      }
+    }
+
+    self.store(path, revision, content).await
+  }
+
+  /// Packs the files under `dir` with [archive::pack_dir], skipping those `exclude` matches, and
+  /// stores the archive as the artifact at `path` like [update](Self::update).
+  pub async fn update_dir(
+    &self,
+    previous: Option<&Artifact>,
+    path: &str,
+    revision: &str,
+    dir: impl Into<PathBuf>,
+    exclude: impl Fn(&str, bool) -> bool + Send + 'static,
+  ) -> io::Result<Artifact> {
+    let dir = dir.into();
+    let content = tokio::task::spawn_blocking(move || archive::pack_dir(&dir, exclude))
+      .await
+      .map_err(|error| io::Error::new(io::ErrorKind::Other, error))??;
+
+    self.update(previous, path, revision, &content).await
+  }
+
+  /// Whether the file of `artifact` still has the checksum of the artifact, so it was not altered
+  /// or truncated since it was stored.
+  pub async fn verify(&self, artifact: &Artifact) -> io::Result<bool> {
+    let path = artifact.path().unwrap_or_default();
+    let file = self.resolve(path).ok_or_else(|| invalid_path(path))?;
+    let content = fs::read(&file).await?;
+
+    Ok(artifact.checksum() == Some(&*checksum(&content)))
+  }
+
+  /// The file of the artifact at `path`, if it is a valid path. Paths which would escape the root,
+  /// or point at hidden files, are rejected.
+  pub fn resolve(&self, path: &str) -> Option<PathBuf> {
+    let mut file = self.root.clone();
+    for segment in path.trim_start_matches('/').split('/') {
+      if segment.is_empty() || segment.starts_with('.') || segment.contains('\\') {
+        return None;
+      }
+
+      file.push(segment);
+    }
+
+    Some(file)
+  }
+}
+
+fn invalid_path(path: &str) -> io::Error {
+  io::Error::new(
+    io::ErrorKind::InvalidInput,
+    format!("invalid artifact path '{path}'"),
+  )
+}
+
+/// The SHA256 checksum of `content`, in the form `sha256:<hex>`.
+pub fn checksum(content: &[u8]) -> String {
+  Checksum::new(Algorithm::Sha256, digest(&SHA256, content).as_ref())
+    .expect("SHA256 digests are 32 bytes")
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn artifact_paths() {
+    assert_eq!(
+      ArtifactStorage::artifact_path("GitHubUserSshKeys", Some("default"), "alice", "keys"),
+      "githubusersshkeys/default/alice/keys"
+    );
+    assert_eq!(
+      ArtifactStorage::artifact_path("ClusterGitHubUserSshKeys", None, "alice", "keys"),
+      "clustergithubusersshkeys/alice/keys"
+    );
+
+    let storage = ArtifactStorage::new("/data", "source-controller.flux-system:9090/");
+    assert_eq!(
+      storage.url("kind/name/keys"),
+      "http://source-controller.flux-system:9090/kind/name/keys"
+    );
+  }
+
+  #[test]
+  fn resolve_stays_within_the_root() {
+    let storage = ArtifactStorage::new("/data", "localhost");
+    assert_eq!(
+      storage.resolve("/kind/default/name/keys"),
+      Some(PathBuf::from("/data/kind/default/name/keys"))
+    );
+    assert_eq!(storage.resolve("kind/../../etc/passwd"), None);
+    assert_eq!(storage.resolve("kind/default/.keys.tmp"), None);
+    assert_eq!(storage.resolve("kind//keys"), None);
+    assert_eq!(storage.resolve(""), None);
+  }
+
+  #[test]
+  fn checksums() {
+    assert_eq!(
+      checksum(b""),
+      "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+  }
+}
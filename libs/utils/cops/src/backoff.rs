@@ -0,0 +1,196 @@
+use crate::requeue;
+use std::{
+  collections::{HashMap, HashSet},
+  hash,
+  sync::Mutex,
+  time::Duration,
+};
+
+/// Configures how long to wait before retrying a failed reconcile. The delay starts at `initial`
+/// and grows by `factor` with every consecutive failure of the same object, up to `max`. Each
+/// delay is spread by up to `jitter` (a fraction of the delay) either way, so objects failing
+/// together do not all retry at the same time.
+#[derive(Clone, Copy, Debug)]
+pub struct Backoff {
+  /// Delay after the first failure.
+  pub initial: Duration,
+
+  /// Longest delay, however many times the reconcile failed.
+  pub max: Duration,
+
+  /// How much the delay grows with every consecutive failure.
+  pub factor: f64,
+
+  /// Fraction of the delay it is randomly spread by, between 0 and 1.
+  pub jitter: f64,
+}
+
+impl Default for Backoff {
+  fn default() -> Self {
+    Self {
+      initial: Duration::from_secs(5),
+      max: Duration::from_secs(5 * 60),
+      factor: 2.0,
+      jitter: 0.1,
+    }
+  }
+}
+
+impl Backoff {
+  /// Always waits `delay`, without growth nor jitter.
+  pub fn fixed(delay: Duration) -> Self {
+    Self {
+      initial: delay,
+      max: delay,
+      factor: 1.0,
+      jitter: 0.0,
+    }
+  }
+
+  /// The delay after `failures` consecutive failures, before jitter.
+  fn delay(&self, failures: u32) -> Duration {
+    let exponent = failures.saturating_sub(1).min(i32::MAX as u32) as i32;
+    let delay = self.initial.as_secs_f64() * self.factor.max(1.0).powi(exponent);
+    if delay.is_finite() && delay < self.max.as_secs_f64() {
+      Duration::from_secs_f64(delay)
+    } else {
+      self.max
+    }
+  }
+
+  /// Spreads `delay` by up to the jitter either way, without going over `max`.
+  fn spread(&self, delay: Duration) -> Duration {
+    requeue::spread(delay, self.jitter).min(self.max)
+  }
+}
+
+struct State {
+  failures: u32,
+  delay: Duration,
+}
+
+/// Keeps track of the consecutive failures of each object, to retry them after an exponential
+/// [Backoff]. Objects are keyed by `K`, usually an `ObjectRef`.
+pub struct BackoffTracker<K> {
+  backoff: Backoff,
+  states: Mutex<HashMap<K, State>>,
+}
+
+impl<K> BackoffTracker<K>
+where
+  K: Eq + hash::Hash,
+{
+  pub fn new(backoff: Backoff) -> Self {
+    Self {
+      backoff,
+      states: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// Records a failure of `key`, returning how long to wait before retrying it.
+  pub fn next(&self, key: K) -> Duration {
+    let mut states = self.states.lock().unwrap();
+    let state = states.entry(key).or_insert(State {
+      failures: 0,
+      delay: Duration::ZERO,
+    });
+
+    state.failures = state.failures.saturating_add(1);
+    state.delay = self.backoff.spread(self.backoff.delay(state.failures));
+    state.delay
+  }
+
+  /// Records a success of `key`, so its next failure starts over from the initial delay.
+  pub fn reset(&self, key: &K) {
+    self.states.lock().unwrap().remove(key);
+  }
+
+  /// The number of consecutive failures of `key`.
+  pub fn failures(&self, key: &K) -> u32 {
+    let states = self.states.lock().unwrap();
+    states.get(key).map_or(0, |state| state.failures)
+  }
+
+  /// Forgets the objects which are not `live`, as they were deleted.
+  pub fn retain(&self, live: &HashSet<K>) {
+    let mut states = self.states.lock().unwrap();
+    states.retain(|key, _| live.contains(key));
+  }
+
+  /// The number of objects backing off, and the longest delay any of them is waiting for.
+  pub fn levels(&self) -> (usize, Duration) {
+    let states = self.states.lock().unwrap();
+    let longest = states.values().map(|state| state.delay).max();
+    (states.len(), longest.unwrap_or_default())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn backoff() -> Backoff {
+    Backoff {
+      initial: Duration::from_secs(1),
+      max: Duration::from_secs(10),
+      factor: 2.0,
+      jitter: 0.0,
+    }
+  }
+
+  #[test]
+  fn grows_up_to_the_cap() {
+    let tracker = BackoffTracker::new(backoff());
+    let delays: Vec<_> = (0..6).map(|_| tracker.next("a").as_secs()).collect();
+    assert_eq!(delays, [1, 2, 4, 8, 10, 10]);
+    assert_eq!(tracker.failures(&"a"), 6);
+    assert_eq!(tracker.levels(), (1, Duration::from_secs(10)));
+
+    // a huge number of failures does not overflow
+    let huge = Backoff {
+      factor: 1e300,
+      ..backoff()
+    };
+    assert_eq!(huge.delay(u32::MAX), Duration::from_secs(10));
+  }
+
+  #[test]
+  fn resets_on_success() {
+    let tracker = BackoffTracker::new(backoff());
+    tracker.next("a");
+    tracker.next("a");
+    tracker.next("b");
+    tracker.reset(&"a");
+
+    assert_eq!(tracker.failures(&"a"), 0);
+    assert_eq!(tracker.next("a"), Duration::from_secs(1));
+    assert_eq!(tracker.next("b"), Duration::from_secs(2));
+  }
+
+  #[test]
+  fn forgets_deleted_objects() {
+    let tracker = BackoffTracker::new(backoff());
+    tracker.next("a");
+    tracker.next("b");
+    tracker.retain(&["b"].into_iter().collect());
+
+    assert_eq!(tracker.failures(&"a"), 0);
+    assert_eq!(tracker.levels(), (1, Duration::from_secs(1)));
+  }
+
+  #[test]
+  fn jitter_stays_in_bounds() {
+    let tracker = BackoffTracker::new(Backoff {
+      jitter: 0.5,
+      ..backoff()
+    });
+    for key in 0..100 {
+      let delay = tracker.next(key);
+      assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_millis(1500));
+    }
+
+    let fixed = BackoffTracker::new(Backoff::fixed(Duration::from_secs(30)));
+    assert_eq!(fixed.next("a"), Duration::from_secs(30));
+    assert_eq!(fixed.next("a"), Duration::from_secs(30));
+  }
+}
@@ -0,0 +1,23 @@
+use std::time::Duration;
+
+/// Configures micro-batching of the status patches made on behalf of a controller. Patches are
+/// queued per object, merged with any patch still pending for the same object, and sent in bulk
+/// every `flush_interval`. This keeps bursts of reconciles (e.g. right after startup) from
+/// flooding the API server.
+#[derive(Clone, Copy, Debug)]
+pub struct StatusBatching {
+  /// How often queued patches are sent.
+  pub flush_interval: Duration,
+
+  /// Maximum number of patches in flight at the same time during a flush.
+  pub max_in_flight: usize,
+}
+
+impl Default for StatusBatching {
+  fn default() -> Self {
+    Self {
+      flush_interval: Duration::from_secs(1),
+      max_in_flight: 10,
+    }
+  }
+}
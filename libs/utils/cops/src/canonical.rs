@@ -0,0 +1,151 @@
+use crate::artifact::checksum;
+use serde::Serialize;
+use serde_json::{Number, Value};
+
+/// The canonical hash of `value`, in the form `sha256:<hex>`, for detecting changes to specs, the
+/// checksums annotated on children, and skipping the apply of unchanged output.
+///
+/// The hash only depends on the data of `value`, not on how it is represented:
+///
+/// - The entries of maps are hashed in the order of their keys, so a `HashMap` hashes the same as
+///   a `BTreeMap` with the same entries, whatever their insertion order.
+/// - Numbers of equal value hash the same, so `1`, `1u64` and `1.0` do, as do `0.0` and `-0.0`.
+///   Other floats are hashed in their shortest form which parses back to the same float.
+/// - NaN and infinities, which JSON cannot represent, hash as `null`.
+///
+/// Fields which are skipped when they are empty hash differently from fields set to `null`, and
+/// the order of sequences matters. The hash is stable across releases, as changing it would make
+/// every controller apply its output again.
+pub fn object_hash<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<String> {
+  let mut canonical = String::new();
+  write_canonical(&mut canonical, &serde_json::to_value(value)?)?;
+  Ok(checksum(canonical.as_bytes()))
+}
+
+/// Writes `value` to `out` as compact JSON, with the entries of objects sorted by key, and
+/// numbers in their canonical form.
+fn write_canonical(out: &mut String, value: &Value) -> serde_json::Result<()> {
+  match value {
+    Value::Null => out.push_str("null"),
+    Value::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+    Value::Number(number) => write_number(out, number),
+    Value::String(string) => out.push_str(&serde_json::to_string(string)?),
+    Value::Array(items) => {
+      out.push('[');
+      for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+          out.push(',');
+        }
+        write_canonical(out, item)?;
+      }
+      out.push(']');
+    }
+    Value::Object(entries) => {
+      // sorted explicitly, as maps keep their insertion order with serde_json's `preserve_order`
+      let mut entries = entries.iter().collect::<Vec<_>>();
+      entries.sort_unstable_by_key(|(key, _)| *key);
+
+      out.push('{');
+      for (i, (key, value)) in entries.into_iter().enumerate() {
+        if i > 0 {
+          out.push(',');
+        }
+        out.push_str(&serde_json::to_string(key)?);
+        out.push(':');
+        write_canonical(out, value)?;
+      }
+      out.push('}');
+    }
+  }
+
+  Ok(())
+}
+
+/// Writes `number` as an integer if it has an integral value, and otherwise as the shortest
+/// float which parses back to it.
+fn write_number(out: &mut String, number: &Number) {
+  if number.is_i64() || number.is_u64() {
+    out.push_str(&number.to_string());
+    return;
+  }
+
+  let float = number.as_f64().unwrap_or_default();
+  let integral = float.fract() == 0.0 && float.abs() < 2f64.powi(63);
+  if integral {
+    // `-0.0` is written as `0`
+    out.push_str(&(float as i64).to_string());
+  } else {
+    out.push_str(&number.to_string());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+  use std::collections::{BTreeMap, HashMap};
+
+  #[test]
+  fn hash_is_stable() {
+    // changing this hash makes every controller apply its output again
+    assert_eq!(
+      object_hash(&json!({ "b": [1, "two", null], "a": { "d": true, "c": 0.5 } })).unwrap(),
+      checksum(br#"{"a":{"c":0.5,"d":true},"b":[1,"two",null]}"#)
+    );
+  }
+
+  #[test]
+  fn maps_hash_in_key_order() {
+    let keys = (0..64).map(|i| format!("key-{i}")).collect::<Vec<_>>();
+    let forward = keys
+      .iter()
+      .map(|key| (key.clone(), 1))
+      .collect::<HashMap<_, _>>();
+    let backward = keys
+      .iter()
+      .rev()
+      .map(|key| (key.clone(), 1))
+      .collect::<HashMap<_, _>>();
+    let sorted = keys
+      .iter()
+      .map(|key| (key.clone(), 1))
+      .collect::<BTreeMap<_, _>>();
+
+    let hash = object_hash(&sorted).unwrap();
+    assert_eq!(object_hash(&forward).unwrap(), hash);
+    assert_eq!(object_hash(&backward).unwrap(), hash);
+    assert_eq!(
+      object_hash(&json!({ "outer": { "b": 1, "a": 2 } })).unwrap(),
+      object_hash(&json!({ "outer": { "a": 2, "b": 1 } })).unwrap()
+    );
+  }
+
+  #[test]
+  fn equal_numbers_hash_the_same() {
+    let hash = |value: Value| object_hash(&value).unwrap();
+    assert_eq!(hash(json!(1)), hash(json!(1.0)));
+    assert_eq!(hash(json!(1u64)), hash(json!(1i8)));
+    assert_eq!(hash(json!(0.0)), hash(json!(-0.0)));
+    assert_eq!(hash(json!(-3)), hash(json!(-3.0)));
+    assert_eq!(hash(json!(f64::NAN)), hash(json!(null)));
+    assert_eq!(hash(json!(f64::INFINITY)), hash(json!(null)));
+    assert_eq!(hash(json!(0.1 + 0.2)), hash(json!(0.30000000000000004)));
+
+    assert_ne!(hash(json!(0.1 + 0.2)), hash(json!(0.3)));
+    assert_ne!(hash(json!(1.5)), hash(json!(1)));
+    assert_ne!(hash(json!(1e300)), hash(json!(1e299)));
+    assert_ne!(hash(json!(1)), hash(json!("1")));
+  }
+
+  #[test]
+  fn strings_are_escaped() {
+    assert_ne!(
+      object_hash(&json!({ "a\":1,\"b": 2 })).unwrap(),
+      object_hash(&json!({ "a": 1, "b": 2 })).unwrap()
+    );
+    assert_ne!(
+      object_hash(&json!(["a", "b"])).unwrap(),
+      object_hash(&json!(["b", "a"])).unwrap()
+    );
+  }
+}
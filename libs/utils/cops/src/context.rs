@@ -0,0 +1,323 @@
+use crate::{
+  artifact::ArtifactStorage,
+  events::EventRecorder,
+  openapi::OpenApiSchemas,
+  policy::PolicySet,
+  references::{self, ReferenceError, ResolvedRef},
+};
+use fluxcd_meta::{is_force_apply, NamespacedObjectReference, FORCE_APPLY_ANNOTATION};
+use fluxcd_utils_kube::{ConfigMapResolver, SecretResolver};
+use kube::{
+  api::{DeleteParams, Patch, PatchParams},
+  runtime::events::{Event, EventType},
+  Api, Client, Resource,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+  fmt::Debug,
+  future::Future,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// How long a reconcile may take, unless the controller configures otherwise.
+pub const DEFAULT_RECONCILE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Context of a single reconcile, giving access to the cluster, and telling the reconciler how
+/// long it has left to run.
+#[derive(Clone)]
+pub struct ReconcileCtx {
+  client: Client,
+  recorder: EventRecorder,
+  policies: Arc<PolicySet>,
+  schemas: Arc<OpenApiSchemas>,
+  artifacts: Option<Arc<ArtifactStorage>>,
+  secrets: SecretResolver,
+  config_maps: ConfigMapResolver,
+  deadline: Instant,
+  cancellation: CancellationToken,
+  read_only: bool,
+  cross_namespace_refs: bool,
+  skipped: Arc<Mutex<Vec<String>>>,
+}
+
+/// Whether `error` rejected a change to an object because it cannot be changed in place.
+fn is_immutable(error: &kube::core::ErrorResponse) -> bool {
+  error.code == 422 && error.message.contains("immutable")
+}
+
+/// Why a reconcile was aborted before it completed.
+#[derive(Debug, Error)]
+pub enum ReconcileAborted {
+  #[error("reconcile did not complete before its deadline")]
+  DeadlineExceeded,
+
+  #[error("reconcile was cancelled because the controller is shutting down")]
+  Cancelled,
+}
+
+impl ReconcileCtx {
+  pub fn new(
+    client: Client,
+    recorder: EventRecorder,
+    policies: Arc<PolicySet>,
+    schemas: Arc<OpenApiSchemas>,
+    artifacts: Option<Arc<ArtifactStorage>>,
+    timeout: Duration,
+    cancellation: CancellationToken,
+  ) -> Self {
+    Self {
+      secrets: SecretResolver::uncached(client.clone()),
+      config_maps: ConfigMapResolver::uncached(client.clone()),
+      client,
+      recorder,
+      policies,
+      schemas,
+      artifacts,
+      deadline: Instant::now() + timeout,
+      cancellation,
+      read_only: false,
+      cross_namespace_refs: true,
+      skipped: Arc::default(),
+    }
+  }
+
+  /// The same context, which skips writes of objects other than the status of the resource if
+  /// `read_only` is set. Skipped writes are recorded as events, and listed by [Self::skipped].
+  pub fn with_read_only(self, read_only: bool) -> Self {
+    Self { read_only, ..self }
+  }
+
+  /// The same context, which resolves Secrets and ConfigMaps through `secrets` and `config_maps`,
+  /// usually shared by the controllers of the app. They are read from the API server otherwise.
+  pub fn with_resolvers(self, secrets: SecretResolver, config_maps: ConfigMapResolver) -> Self {
+    Self {
+      secrets,
+      config_maps,
+      ..self
+    }
+  }
+
+  /// Whether writes of objects other than the status of the resource are skipped.
+  pub fn is_read_only(&self) -> bool {
+    self.read_only
+  }
+
+  /// The same context, which rejects the references of the resource to objects in other
+  /// namespaces unless `allowed` is set. See [Self::resolve_reference].
+  pub fn with_cross_namespace_refs(self, allowed: bool) -> Self {
+    Self {
+      cross_namespace_refs: allowed,
+      ..self
+    }
+  }
+
+  /// Resolves `reference`, the `field` of the spec of the resource, which is in `namespace` or
+  /// cluster-scoped if `None`. Fails with a [ReferenceError] if the reference is incomplete, or
+  /// points to another namespace while cross-namespace references are disabled.
+  pub fn resolve_reference<'a>(
+    &self,
+    field: &str,
+    reference: &'a NamespacedObjectReference,
+    namespace: Option<&'a str>,
+  ) -> Result<ResolvedRef<'a>, ReferenceError> {
+    references::resolve_reference(field, reference, namespace, self.cross_namespace_refs)
+  }
+
+  /// The writes skipped so far because the context is read-only, like `apply ConfigMap web`.
+  pub fn skipped(&self) -> Vec<String> {
+    self.skipped.lock().unwrap().clone()
+  }
+
+  /// The same context, with a deadline `timeout` from now, for reconciles which had to wait
+  /// before they could start.
+  pub fn restarted(&self, timeout: Duration) -> Self {
+    Self {
+      deadline: Instant::now() + timeout,
+      ..self.clone()
+    }
+  }
+
+  pub fn client(&self) -> &Client {
+    &self.client
+  }
+
+  /// Records events for the resource being reconciled.
+  pub fn recorder(&self) -> &EventRecorder {
+    &self.recorder
+  }
+
+  /// Resolves the Secrets referenced by the spec of the resource.
+  pub fn secrets(&self) -> &SecretResolver {
+    &self.secrets
+  }
+
+  /// Resolves the ConfigMaps referenced by the spec of the resource.
+  pub fn config_maps(&self) -> &ConfigMapResolver {
+    &self.config_maps
+  }
+
+  /// Storage for the artifacts of the resource, if the app serves artifacts.
+  pub fn artifacts(&self) -> Option<&ArtifactStorage> {
+    self.artifacts.as_deref()
+  }
+
+  /// Checks writing `object` on behalf of `owner` (the resource being reconciled) against the
+  /// policies of the controller, and validates it against the cluster's OpenAPI schema.
+  /// Reconcilers call this before every write of generated objects, and bail with the
+  /// [PolicyViolation](crate::policy::PolicyViolation) or
+  /// [SchemaViolation](crate::openapi::SchemaViolation) if the write is rejected.
+  pub async fn admit<O, T>(&self, owner: &O, object: &T) -> eyre::Result<()>
+  where
+    O: Serialize,
+    T: Serialize,
+  {
+    self.policies.check(owner, object)?;
+    self
+      .schemas
+      .validate(&serde_json::to_value(object)?)
+      .await?;
+    Ok(())
+  }
+
+  /// Applies `object` on behalf of `owner` (the resource being reconciled) as `field_manager`,
+  /// taking over any fields managed by others.
+  ///
+  /// Changes which cannot be applied in place, like changes to the template of a Job or to an
+  /// immutable Secret, fail, unless `owner` or `object` is annotated with
+  /// [FORCE_APPLY_ANNOTATION](fluxcd_meta::FORCE_APPLY_ANNOTATION): the object is then deleted and
+  /// created again, which is recorded as an event of `owner`.
+  ///
+  /// Nothing is written if the context is read-only, and `object` is returned as is.
+  pub async fn apply<O, K>(
+    &self,
+    owner: &O,
+    api: &Api<K>,
+    field_manager: &str,
+    object: &K,
+  ) -> eyre::Result<K>
+  where
+    O: Resource,
+    K: Resource + Serialize + DeserializeOwned + Clone + Debug,
+    K::DynamicType: Default,
+  {
+    let name = object.meta().name.as_deref().unwrap_or_default();
+    if self.read_only {
+      self.skip::<K>("Apply", name).await;
+      return Ok(object.clone());
+    }
+
+    let params = PatchParams::apply(field_manager).force();
+    let message = match api.patch(name, &params, &Patch::Apply(object)).await {
+      Err(kube::Error::Api(error)) if is_immutable(&error) => error.message,
+      result => return Ok(result?),
+    };
+
+    let force = [owner.meta(), object.meta()]
+      .into_iter()
+      .filter_map(|meta| meta.annotations.as_ref())
+      .any(is_force_apply);
+    if !force {
+      eyre::bail!("{message} (annotate with {FORCE_APPLY_ANNOTATION}: \"true\" to recreate it)");
+    }
+
+    let kind = K::kind(&Default::default()).into_owned();
+    info!(%kind, %name, %message, "recreating object which cannot be changed in place");
+    api.delete(name, &DeleteParams::background()).await?;
+    let applied = api.patch(name, &params, &Patch::Apply(object)).await?;
+
+    let event = Event {
+      type_: EventType::Normal,
+      reason: "Recreated".into(),
+      note: Some(format!(
+        "{kind} {name} was recreated, as the change could not be applied in place: {message}"
+      )),
+      action: "Apply".into(),
+      secondary: Some(applied.object_ref(&Default::default())),
+    };
+    if let Err(error) = self.recorder.publish(event).await {
+      warn!(%error, "failed to publish event");
+    }
+
+    Ok(applied)
+  }
+
+  /// Deletes the object named `name`, if it exists. Nothing is deleted if the context is
+  /// read-only.
+  pub async fn delete<K>(&self, api: &Api<K>, name: &str) -> eyre::Result<()>
+  where
+    K: Resource + DeserializeOwned + Clone + Debug,
+    K::DynamicType: Default,
+  {
+    if self.read_only {
+      self.skip::<K>("Delete", name).await;
+      return Ok(());
+    }
+
+    match api.delete(name, &DeleteParams::default()).await {
+      Err(kube::Error::Api(error)) if error.code == 404 => Ok(()),
+      result => {
+        result?;
+        Ok(())
+      }
+    }
+  }
+
+  /// Records that `action` of the object of kind `K` named `name` was skipped, as the context is
+  /// read-only.
+  async fn skip<K>(&self, action: &str, name: &str)
+  where
+    K: Resource,
+    K::DynamicType: Default,
+  {
+    let kind = K::kind(&Default::default()).into_owned();
+    let write = format!("{} {kind} {name}", action.to_lowercase());
+    info!(%write, "skipping write in read-only mode");
+
+    let event = Event {
+      type_: EventType::Normal,
+      reason: "WriteSkipped".into(),
+      note: Some(format!("would {write}, but the controller is read-only")),
+      action: action.into(),
+      secondary: None,
+    };
+    if let Err(error) = self.recorder.publish(event).await {
+      warn!(%error, "failed to publish event");
+    }
+
+    self.skipped.lock().unwrap().push(write);
+  }
+
+  /// The point in time by which the reconcile must have completed.
+  pub fn deadline(&self) -> Instant {
+    self.deadline
+  }
+
+  /// Time left until the deadline, useful for capping the timeout of outgoing requests.
+  pub fn remaining(&self) -> Duration {
+    self.deadline.saturating_duration_since(Instant::now())
+  }
+
+  /// Token which is cancelled once the controller starts shutting down.
+  pub fn cancellation(&self) -> &CancellationToken {
+    &self.cancellation
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.cancellation.is_cancelled()
+  }
+
+  /// Runs `future` to completion, unless the deadline passes or the reconcile is cancelled first.
+  pub async fn run<F: Future>(&self, future: F) -> Result<F::Output, ReconcileAborted> {
+    let deadline = tokio::time::Instant::from_std(self.deadline);
+
+    tokio::select! {
+      output = future => Ok(output),
+      _ = tokio::time::sleep_until(deadline) => Err(ReconcileAborted::DeadlineExceeded),
+      _ = self.cancellation.cancelled() => Err(ReconcileAborted::Cancelled),
+    }
+  }
+}
@@ -0,0 +1,182 @@
+use k8s_openapi::{
+  api::core::v1::ObjectReference,
+  apimachinery::pkg::apis::meta::v1::Time,
+  chrono::{DateTime, Utc},
+};
+use kube::{
+  runtime::events::{Event, EventType, Recorder, Reporter},
+  Client,
+};
+use reqwest::{header::CONTENT_TYPE, Url};
+use serde::Serialize;
+use std::time::Duration;
+use tracing::warn;
+
+/// How long forwarding an event to the events receiver may take.
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Forwards events to an external events receiver, like the notification-controller of Flux,
+/// which dispatches them to alerts.
+#[derive(Clone, Debug)]
+pub struct EventForwarder {
+  http: reqwest::Client,
+  addr: Url,
+}
+
+impl EventForwarder {
+  /// Forwards events to the receiver at `addr`, e.g.
+  /// `http://notification-controller.flux-system.svc.cluster.local./`.
+  pub fn new(addr: &str) -> eyre::Result<Self> {
+    let addr = Url::parse(addr)?;
+    let http = reqwest::Client::builder()
+      .timeout(FORWARD_TIMEOUT)
+      .build()?;
+
+    Ok(Self { http, addr })
+  }
+
+  /// The address events are forwarded to.
+  pub fn addr(&self) -> &Url {
+    &self.addr
+  }
+
+  async fn forward(&self, event: &ForwardedEvent<'_>) -> eyre::Result<()> {
+    self
+      .http
+      .post(self.addr.clone())
+      .header(CONTENT_TYPE, "application/json")
+      .body(serde_json::to_vec(event)?)
+      .send()
+      .await?
+      .error_for_status()?;
+
+    Ok(())
+  }
+}
+
+/// An event as posted to the events receiver, in the format of fluxcd/pkg/apis/event.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ForwardedEvent<'a> {
+  involved_object: &'a ObjectReference,
+  severity: &'static str,
+  timestamp: Time,
+  message: &'a str,
+  reason: &'a str,
+  reporting_controller: &'a str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  reporting_instance: Option<&'a str>,
+}
+
+impl<'a> ForwardedEvent<'a> {
+  fn new(
+    reporter: &'a Reporter,
+    object: &'a ObjectReference,
+    event: &'a Event,
+    now: DateTime<Utc>,
+  ) -> Self {
+    let severity = match event.type_ {
+      EventType::Normal => "info",
+      EventType::Warning => "error",
+    };
+
+    Self {
+      involved_object: object,
+      severity,
+      timestamp: Time(now),
+      message: event.note.as_deref().unwrap_or_default(),
+      reason: &event.reason,
+      reporting_controller: &reporter.controller,
+      reporting_instance: reporter.instance.as_deref(),
+    }
+  }
+}
+
+/// Records events of a single object: as `v1.Event`s with the controller as their source, and, if
+/// there is a [EventForwarder], also at the events receiver.
+#[derive(Clone)]
+pub struct EventRecorder {
+  recorder: Recorder,
+  reporter: Reporter,
+  object: ObjectReference,
+  forwarder: Option<EventForwarder>,
+}
+
+impl EventRecorder {
+  /// Records events of the object referenced by `object`, reported by `reporter`.
+  pub fn new(client: Client, reporter: Reporter, object: ObjectReference) -> Self {
+    Self {
+      recorder: Recorder::new(client, reporter.clone(), object.clone()),
+      reporter,
+      object,
+      forwarder: None,
+    }
+  }
+
+  /// The same recorder, which also forwards events through `forwarder`, if any.
+  pub fn with_forwarder(self, forwarder: Option<EventForwarder>) -> Self {
+    Self { forwarder, ..self }
+  }
+
+  /// Publishes `event` as a `v1.Event`, and forwards it to the events receiver. Failing to forward
+  /// the event is logged rather than returned, as the receiver is not required for the controller
+  /// to work.
+  pub async fn publish(&self, event: Event) -> Result<(), kube::Error> {
+    if let Some(forwarder) = &self.forwarder {
+      let forwarded = ForwardedEvent::new(&self.reporter, &self.object, &event, Utc::now());
+      if let Err(error) = forwarder.forward(&forwarded).await {
+        warn!(error = %format!("{error:#}"), addr = %forwarder.addr, "failed to forward event");
+      }
+    }
+
+    self.recorder.publish(event).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use k8s_openapi::chrono::TimeZone;
+  use serde_json::json;
+
+  #[test]
+  fn forwarded_events_use_the_flux_format() {
+    let reporter = Reporter {
+      controller: "source-controller".into(),
+      instance: Some("source-controller-0".into()),
+    };
+    let object = ObjectReference {
+      api_version: Some("source.fluxcd.yolodev.io/v1alpha1".into()),
+      kind: Some("HttpEndpoint".into()),
+      name: Some("web".into()),
+      namespace: Some("default".into()),
+      ..Default::default()
+    };
+    let event = Event {
+      type_: EventType::Warning,
+      reason: "PolicyViolation".into(),
+      note: Some("writing Secret web is not allowed".into()),
+      action: "Reconcile".into(),
+      secondary: None,
+    };
+
+    let forwarded = ForwardedEvent::new(&reporter, &object, &event, Utc.timestamp(0, 0));
+    assert_eq!(
+      serde_json::to_value(&forwarded).unwrap(),
+      json!({
+        "involvedObject": {
+          "apiVersion": "source.fluxcd.yolodev.io/v1alpha1",
+          "kind": "HttpEndpoint",
+          "name": "web",
+          "namespace": "default",
+        },
+        "severity": "error",
+        "timestamp": "1970-01-01T00:00:00Z",
+        "message": "writing Secret web is not allowed",
+        "reason": "PolicyViolation",
+        "reportingController": "source-controller",
+        "reportingInstance": "source-controller-0",
+      })
+    );
+  }
+}
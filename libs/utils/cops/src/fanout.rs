@@ -0,0 +1,194 @@
+use fluxcd_meta::ItemFailure;
+use futures::{stream, Future, StreamExt};
+use std::fmt;
+
+/// The outcome of running a fallible future for every item of a fan-out, in the order of the
+/// items. Every result is attributed to the item it belongs to, so that a reconcile can report
+/// which items failed while still using the others.
+#[derive(Debug)]
+pub struct FanOut<K, T, E> {
+  pub succeeded: Vec<(K, T)>,
+  pub failed: Vec<(K, E)>,
+}
+
+/// Runs the future of every `(item, future)` pair, at most `limit` at a time, and waits for all of
+/// them to complete. Unlike `try_join_all`, a failing future does not cancel the others.
+pub async fn try_join_limited<I, K, F, T, E>(limit: usize, items: I) -> FanOut<K, T, E>
+where
+  I: IntoIterator<Item = (K, F)>,
+  F: Future<Output = Result<T, E>>,
+{
+  let results = stream::iter(items)
+    .map(|(item, future)| async move { (item, future.await) })
+    .buffered(limit.max(1))
+    .collect::<Vec<_>>()
+    .await;
+
+  let mut fan_out = FanOut {
+    succeeded: Vec::new(),
+    failed: Vec::new(),
+  };
+  for (item, result) in results {
+    match result {
+      Ok(value) => fan_out.succeeded.push((item, value)),
+      Err(error) => fan_out.failed.push((item, error)),
+    }
+  }
+
+  fan_out
+}
+
+impl<K, T, E> FanOut<K, T, E>
+where
+  K: fmt::Display,
+  E: fmt::Display,
+{
+  /// Whether every item succeeded.
+  pub fn is_complete(&self) -> bool {
+    self.failed.is_empty()
+  }
+
+  /// The failed items, for the status of the resource.
+  pub fn failures(&self) -> Vec<ItemFailure> {
+    self
+      .failed
+      .iter()
+      .map(|(item, error)| ItemFailure::new(item.to_string(), error.to_string()))
+      .collect()
+  }
+
+  /// The values of the items which succeeded, or an error naming every failed item if none did.
+  /// A fan-out without any items succeeds.
+  pub fn into_partial(self) -> eyre::Result<Vec<(K, T)>> {
+    if self.succeeded.is_empty() && !self.failed.is_empty() {
+      eyre::bail!("all items failed: {}", self.describe_failures());
+    }
+
+    Ok(self.succeeded)
+  }
+
+  /// The values of all items, or an error naming every failed item if any did.
+  pub fn into_complete(self) -> eyre::Result<Vec<(K, T)>> {
+    if !self.failed.is_empty() {
+      let total = self.failed.len() + self.succeeded.len();
+      eyre::bail!(
+        "{} of {total} items failed: {}",
+        self.failed.len(),
+        self.describe_failures()
+      );
+    }
+
+    Ok(self.succeeded)
+  }
+
+  fn describe_failures(&self) -> String {
+    self
+      .failed
+      .iter()
+      .map(|(item, error)| format!("{item}: {error}"))
+      .collect::<Vec<_>>()
+      .join("; ")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use futures::executor::block_on;
+  use std::{
+    cell::Cell,
+    future::{self, Ready},
+    pin::Pin,
+    task::{Context, Poll},
+  };
+
+  /// Yields once, so that the other items get to start.
+  struct YieldOnce(bool);
+
+  impl Future for YieldOnce {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+      if self.0 {
+        return Poll::Ready(());
+      }
+
+      self.0 = true;
+      cx.waker().wake_by_ref();
+      Poll::Pending
+    }
+  }
+
+  fn fetch(user: &'static str) -> (&'static str, Ready<Result<usize, String>>) {
+    let result = match user {
+      "nobody" => Err("user not found".to_string()),
+      user => Ok(user.len()),
+    };
+
+    (user, future::ready(result))
+  }
+
+  #[test]
+  fn failures_are_attributed_to_their_items() {
+    let fan_out = block_on(try_join_limited(
+      2,
+      ["alice", "nobody", "bob"].into_iter().map(fetch),
+    ));
+    assert!(!fan_out.is_complete());
+    assert_eq!(
+      fan_out.failures(),
+      [ItemFailure::new("nobody", "user not found")]
+    );
+
+    let error = block_on(try_join_limited(
+      2,
+      ["alice", "nobody"].into_iter().map(fetch),
+    ))
+    .into_complete()
+    .unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      "1 of 2 items failed: nobody: user not found"
+    );
+
+    let partial = fan_out.into_partial().unwrap();
+    assert_eq!(partial, [("alice", 5), ("bob", 3)]);
+  }
+
+  #[test]
+  fn partial_fails_once_every_item_failed() {
+    let fan_out = block_on(try_join_limited(2, [fetch("nobody")]));
+    let error = fan_out.into_partial().unwrap_err();
+    assert_eq!(
+      error.to_string(),
+      "all items failed: nobody: user not found"
+    );
+
+    let empty = block_on(try_join_limited(
+      2,
+      Vec::<(&str, Ready<Result<(), String>>)>::new(),
+    ));
+    assert!(empty.into_partial().unwrap().is_empty());
+  }
+
+  #[test]
+  fn runs_at_most_limit_items_at_once() {
+    let running = Cell::new(0);
+    let peak = Cell::new(0);
+    let items = (0..8).map(|item| {
+      let future = async {
+        running.set(running.get() + 1);
+        peak.set(peak.get().max(running.get()));
+        YieldOnce(false).await;
+        running.set(running.get() - 1);
+        Ok::<_, String>(())
+      };
+
+      (item, future)
+    });
+
+    let fan_out = block_on(try_join_limited(3, items));
+    assert_eq!(fan_out.succeeded.len(), 8);
+    assert_eq!(peak.get(), 3);
+  }
+}
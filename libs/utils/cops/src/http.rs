@@ -0,0 +1,408 @@
+use crate::artifact::checksum;
+use k8s_openapi::ByteString;
+use prometheus::{exponential_buckets, HistogramOpts, HistogramVec, IntGaugeVec, Opts};
+use reqwest::{Certificate, Client, ClientBuilder, Identity, IntoUrl, RequestBuilder};
+use std::{
+  collections::{BTreeMap, HashMap},
+  env,
+  fmt::{self, Display},
+  str::FromStr,
+  sync::{Arc, Mutex, OnceLock},
+  time::{Duration, Instant},
+};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use tracing::{debug, Span};
+
+/// Secret key holding the PEM encoded client certificate, along with its intermediates.
+pub const TLS_CERT_KEY: &str = "tls.crt";
+
+/// Secret key holding the PEM encoded private key of the client certificate.
+pub const TLS_KEY_KEY: &str = "tls.key";
+
+/// Secret key holding the PEM encoded certificate authorities to trust, on top of the system ones.
+pub const TLS_CA_KEY: &str = "ca.crt";
+
+/// Tuning knobs of the connection pool of an [HttpClient]. Controllers are created before the
+/// command line is parsed, so these are read from the environment by [HttpConfig::from_env].
+#[derive(Clone, Debug)]
+pub struct HttpConfig {
+  /// Maximum number of requests in flight at once, across all hosts (`HTTP_MAX_CONNECTIONS`).
+  /// Further requests wait for one of them to complete.
+  pub max_connections: usize,
+
+  /// Maximum number of idle connections kept open per host (`HTTP_POOL_MAX_IDLE_PER_HOST`).
+  pub pool_max_idle_per_host: usize,
+
+  /// How long an idle connection is kept open (`HTTP_POOL_IDLE_TIMEOUT`, in seconds, `0` keeps
+  /// them open until the server closes them).
+  pub pool_idle_timeout: Option<Duration>,
+
+  /// Interval of the TCP keep-alive probes of open connections (`HTTP_TCP_KEEPALIVE`, in seconds,
+  /// `0` disables them).
+  pub tcp_keepalive: Option<Duration>,
+
+  /// Resolve host names with an async resolver which caches the answers for their TTL
+  /// (`HTTP_DNS_CACHE`), rather than with a blocking `getaddrinfo` call for every new connection.
+  pub dns_cache: bool,
+}
+
+impl Default for HttpConfig {
+  fn default() -> Self {
+    Self {
+      max_connections: 256,
+      pool_max_idle_per_host: usize::MAX,
+      pool_idle_timeout: Some(Duration::from_secs(90)),
+      tcp_keepalive: None,
+      dns_cache: false,
+    }
+  }
+}
+
+impl HttpConfig {
+  /// Reads the config from the environment, using the defaults for unset variables.
+  pub fn from_env() -> eyre::Result<Self> {
+    let defaults = Self::default();
+    Ok(Self {
+      max_connections: var("HTTP_MAX_CONNECTIONS")?.unwrap_or(defaults.max_connections),
+      pool_max_idle_per_host: var("HTTP_POOL_MAX_IDLE_PER_HOST")?
+        .unwrap_or(defaults.pool_max_idle_per_host),
+      pool_idle_timeout: seconds("HTTP_POOL_IDLE_TIMEOUT")?.unwrap_or(defaults.pool_idle_timeout),
+      tcp_keepalive: seconds("HTTP_TCP_KEEPALIVE")?.unwrap_or(defaults.tcp_keepalive),
+      dns_cache: var("HTTP_DNS_CACHE")?.unwrap_or(defaults.dns_cache),
+    })
+  }
+
+  /// Applies the pool, keep-alive and DNS settings to `builder`.
+  pub fn apply(&self, builder: ClientBuilder) -> ClientBuilder {
+    builder
+      .pool_max_idle_per_host(self.pool_max_idle_per_host)
+      .pool_idle_timeout(self.pool_idle_timeout)
+      .tcp_keepalive(self.tcp_keepalive)
+      .trust_dns(self.dns_cache)
+  }
+}
+
+fn var<T>(name: &str) -> eyre::Result<Option<T>>
+where
+  T: FromStr,
+  T::Err: Display,
+{
+  match env::var(name) {
+    Ok(value) => match value.trim().parse() {
+      Ok(value) => Ok(Some(value)),
+      Err(e) => eyre::bail!("invalid {name} '{value}': {e}"),
+    },
+    Err(env::VarError::NotPresent) => Ok(None),
+    Err(e) => eyre::bail!("invalid {name}: {e}"),
+  }
+}
+
+/// Reads a number of seconds from the variable `name`, where `0` means none.
+fn seconds(name: &str) -> eyre::Result<Option<Option<Duration>>> {
+  let value = var::<u64>(name)?;
+  Ok(value.map(|secs| Some(Duration::from_secs(secs)).filter(|d| !d.is_zero())))
+}
+
+/// Metrics of the connection pools of all [HttpClient]s, by client name.
+struct PoolMetrics {
+  in_use: IntGaugeVec,
+  max: IntGaugeVec,
+  wait: HistogramVec,
+}
+
+/// The pool metrics, which are registered in the default registry on first use.
+fn pool_metrics() -> &'static PoolMetrics {
+  static METRICS: OnceLock<PoolMetrics> = OnceLock::new();
+  METRICS.get_or_init(|| {
+    let gauge = |name: &str, help: &str| {
+      let opts = Opts::new(name, help)
+        .subsystem("http_client")
+        .namespace("gotk");
+      IntGaugeVec::new(opts, &["client"]).unwrap()
+    };
+
+    let in_use = gauge(
+      "connections_in_use",
+      "The number of requests in flight of an HTTP client.",
+    );
+    let max = gauge(
+      "connections_max",
+      "The maximum number of requests in flight of an HTTP client.",
+    );
+    let wait = HistogramVec::new(
+      HistogramOpts::new(
+        "pool_wait_seconds",
+        "The time in seconds requests of an HTTP client waited for a free connection.",
+      )
+      .subsystem("http_client")
+      .namespace("gotk")
+      .buckets(exponential_buckets(0.001, 2f64, 15).unwrap()),
+      &["client"],
+    )
+    .unwrap();
+
+    // registering only fails if the names are taken, in which case the metrics go unreported
+    let registry = prometheus::default_registry();
+    let _ = registry.register(Box::new(in_use.clone()));
+    let _ = registry.register(Box::new(max.clone()));
+    let _ = registry.register(Box::new(wait.clone()));
+
+    PoolMetrics { in_use, max, wait }
+  })
+}
+
+/// Marks a request as in flight for as long as it is alive, so requests aborted by a timeout are
+/// accounted for as well.
+struct InUse<'a>(&'a str);
+
+impl<'a> InUse<'a> {
+  fn new(name: &'a str) -> Self {
+    pool_metrics().in_use.with_label_values(&[name]).inc();
+    Self(name)
+  }
+}
+
+impl Drop for InUse<'_> {
+  fn drop(&mut self) {
+    pool_metrics().in_use.with_label_values(&[self.0]).dec();
+  }
+}
+
+#[derive(Debug, Error)]
+pub enum ClientTlsError {
+  #[error("secret is missing '{0}'")]
+  MissingKey(&'static str),
+
+  #[error("invalid client certificate, '{TLS_CERT_KEY}' and '{TLS_KEY_KEY}' must be PEM encoded, with a PKCS#8 or RSA private key")]
+  InvalidIdentity(#[source] reqwest::Error),
+
+  #[error("invalid '{TLS_CA_KEY}', it must hold PEM encoded certificates")]
+  InvalidCa(#[source] reqwest::Error),
+}
+
+/// A client certificate, for servers which authenticate clients through mutual TLS, read from a
+/// Secret of type `kubernetes.io/tls`.
+#[derive(Clone)]
+pub struct ClientTls {
+  /// The PEM encoded certificate, followed by its private key.
+  identity: Vec<u8>,
+
+  /// The PEM encoded certificate authorities to trust, on top of the system ones.
+  ca: Option<Vec<u8>>,
+}
+
+impl ClientTls {
+  /// Reads the client certificate from the data of a Secret, which is `None` if it holds neither
+  /// a `tls.crt` nor a `tls.key`.
+  pub fn from_secret_data(
+    data: &BTreeMap<String, ByteString>,
+  ) -> Result<Option<Self>, ClientTlsError> {
+    let (cert, key) = match (data.get(TLS_CERT_KEY), data.get(TLS_KEY_KEY)) {
+      (None, None) => return Ok(None),
+      (None, Some(_)) => return Err(ClientTlsError::MissingKey(TLS_CERT_KEY)),
+      (Some(_), None) => return Err(ClientTlsError::MissingKey(TLS_KEY_KEY)),
+      (Some(cert), Some(key)) => (cert, key),
+    };
+
+    let mut identity = cert.0.clone();
+    identity.push(b'\n');
+    identity.extend_from_slice(&key.0);
+    Identity::from_pem(&identity).map_err(ClientTlsError::InvalidIdentity)?;
+
+    Ok(Some(Self {
+      identity,
+      ca: data.get(TLS_CA_KEY).map(|ca| ca.0.clone()),
+    }))
+  }
+
+  /// Checksum of the certificate, key and certificate authorities, which changes when any of them
+  /// is rotated.
+  fn fingerprint(&self) -> String {
+    let mut content = self.identity.clone();
+    if let Some(ca) = &self.ca {
+      content.extend_from_slice(ca);
+    }
+
+    checksum(&content)
+  }
+
+  fn apply(&self, builder: ClientBuilder) -> Result<ClientBuilder, ClientTlsError> {
+    let identity = Identity::from_pem(&self.identity).map_err(ClientTlsError::InvalidIdentity)?;
+    let mut builder = builder.identity(identity);
+    if let Some(ca) = &self.ca {
+      let ca = Certificate::from_pem(ca).map_err(ClientTlsError::InvalidCa)?;
+      builder = builder.add_root_certificate(ca);
+    }
+
+    Ok(builder)
+  }
+}
+
+impl fmt::Debug for ClientTls {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ClientTls")
+      .field("fingerprint", &self.fingerprint())
+      .finish()
+  }
+}
+
+/// Builds the [ClientBuilder] of an [HttpClient], once for the client itself and once more for
+/// every client certificate it presents.
+type BuilderFn = dyn Fn() -> ClientBuilder + Send + Sync;
+
+/// HTTP client shared by all objects of a controller, for fetching from external services. The
+/// number of requests in flight is capped by [HttpConfig::max_connections], and the utilization of
+/// the pool is reported in the `gotk_http_client_*` metrics.
+#[derive(Clone)]
+pub struct HttpClient {
+  name: Arc<str>,
+  http: Client,
+  permits: Arc<Semaphore>,
+  builder: Arc<BuilderFn>,
+  config: Arc<HttpConfig>,
+
+  /// The clients presenting a client certificate, by the object they are used for, along with the
+  /// fingerprint of the certificate. They share the cap on requests in flight with this client.
+  tls_clients: Arc<Mutex<HashMap<String, (String, Client)>>>,
+}
+
+impl HttpClient {
+  /// Builds the client named `name` (used to label its metrics) from `builder`, with the settings
+  /// of `config` applied.
+  pub fn new(
+    name: &str,
+    builder: impl Fn() -> ClientBuilder + Send + Sync + 'static,
+    config: &HttpConfig,
+  ) -> eyre::Result<Self> {
+    let http = config.apply(builder()).build()?;
+    let max_connections = config.max_connections.max(1);
+    pool_metrics()
+      .max
+      .with_label_values(&[name])
+      .set(max_connections as i64);
+
+    Ok(Self {
+      name: name.into(),
+      http,
+      permits: Arc::new(Semaphore::new(max_connections)),
+      builder: Arc::new(builder),
+      config: Arc::new(config.clone()),
+      tls_clients: Default::default(),
+    })
+  }
+
+  /// The client to use for `object` (like `namespace/name`), presenting the client certificate
+  /// `tls`. The client is kept for as long as the certificate does not change, so its connections
+  /// are reused, and is rebuilt once the certificate is rotated.
+  pub fn with_tls(&self, object: &str, tls: &ClientTls) -> eyre::Result<Self> {
+    let fingerprint = tls.fingerprint();
+    let mut clients = self.tls_clients.lock().unwrap();
+    let http = match clients.get(object) {
+      Some((current, http)) if *current == fingerprint => http.clone(),
+      current => {
+        if current.is_some() {
+          debug!(client = %self.name, %object, "client certificate changed, rebuilding client");
+        }
+
+        let builder = tls.apply(self.config.apply((self.builder)()))?;
+        let http = builder.build()?;
+        clients.insert(object.into(), (fingerprint, http.clone()));
+        http
+      }
+    };
+
+    Ok(Self {
+      http,
+      ..self.clone()
+    })
+  }
+
+  /// Drops the client kept for `object` by [with_tls](Self::with_tls), if any, once it is no
+  /// longer needed.
+  pub fn forget_tls(&self, object: &str) {
+    self.tls_clients.lock().unwrap().remove(object);
+  }
+
+  pub fn get(&self, url: impl IntoUrl) -> RequestBuilder {
+    self.http.get(url)
+  }
+
+  /// Sends `request` and reads the body of the response, failing if it has an error status. The
+  /// request counts against [HttpConfig::max_connections] until its body has been read, and
+  /// carries the trace context of the current span, so that the server can continue its trace.
+  pub async fn fetch(&self, request: RequestBuilder) -> reqwest::Result<Vec<u8>> {
+    let request = fluxcd_utils_telemetry::trace_context(&Span::current())
+      .into_iter()
+      .fold(request, |request, (name, value)| {
+        request.header(name, value)
+      });
+
+    let start = Instant::now();
+    let _permit = self
+      .permits
+      .acquire()
+      .await
+      .expect("semaphore is never closed");
+    pool_metrics()
+      .wait
+      .with_label_values(&[&*self.name])
+      .observe(start.elapsed().as_secs_f64());
+
+    let _in_use = InUse::new(&self.name);
+    let response = request.send().await?.error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn client_certificate_needs_both_keys() {
+    let data = |keys: &[&str]| {
+      keys
+        .iter()
+        .map(|key| (key.to_string(), ByteString(b"-----BEGIN".to_vec())))
+        .collect::<BTreeMap<_, _>>()
+    };
+
+    assert!(ClientTls::from_secret_data(&data(&["token"]))
+      .unwrap()
+      .is_none());
+    assert!(matches!(
+      ClientTls::from_secret_data(&data(&[TLS_CERT_KEY, TLS_CA_KEY])),
+      Err(ClientTlsError::MissingKey(TLS_KEY_KEY))
+    ));
+    assert!(matches!(
+      ClientTls::from_secret_data(&data(&[TLS_CERT_KEY, TLS_KEY_KEY])),
+      Err(ClientTlsError::InvalidIdentity(_))
+    ));
+  }
+
+  #[test]
+  fn zero_seconds_means_none() {
+    env::set_var("HTTP_TEST_TIMEOUT", "0");
+    assert_eq!(seconds("HTTP_TEST_TIMEOUT").unwrap(), Some(None));
+
+    env::set_var("HTTP_TEST_TIMEOUT", "30");
+    assert_eq!(
+      seconds("HTTP_TEST_TIMEOUT").unwrap(),
+      Some(Some(Duration::from_secs(30)))
+    );
+
+    env::remove_var("HTTP_TEST_TIMEOUT");
+    assert_eq!(seconds("HTTP_TEST_TIMEOUT").unwrap(), None);
+  }
+
+  #[test]
+  fn invalid_values_are_rejected() {
+    env::set_var("HTTP_TEST_INVALID", "many");
+    let error = var::<usize>("HTTP_TEST_INVALID").unwrap_err();
+    assert!(error
+      .to_string()
+      .starts_with("invalid HTTP_TEST_INVALID 'many'"));
+    env::remove_var("HTTP_TEST_INVALID");
+  }
+}
@@ -1,7 +1,34 @@
+pub mod archive;
+pub mod artifact;
+pub mod backoff;
+pub mod batching;
+pub mod canonical;
+pub mod context;
+pub mod events;
+pub mod fanout;
+pub mod http;
 pub mod metrics;
+pub mod openapi;
+pub mod output;
+pub mod owned;
+pub mod policy;
+pub mod predicate;
+pub mod ratelimit;
+pub mod rbac;
+pub mod references;
+pub mod requeue;
+pub mod requirements;
+pub mod schema;
+pub mod secrets;
 
 use async_trait::async_trait;
-use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use backoff::Backoff;
+use batching::StatusBatching;
+use context::{ReconcileCtx, DEFAULT_RECONCILE_TIMEOUT};
+use k8s_openapi::{
+  api::core::v1::ObjectReference,
+  apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition,
+};
 use kube::{
   api::ListParams,
   core::Resource as KubeResource,
@@ -9,8 +36,16 @@ use kube::{
   Api, Client, CustomResourceExt,
 };
 use metrics::Recorder;
+use predicate::Predicates;
+use ratelimit::RateLimit;
+use rbac::Permissions;
+use requirements::Requirements;
 use serde::Deserialize;
-use std::{fmt, hash, sync::Arc};
+use std::{
+  fmt, hash,
+  sync::Arc,
+  time::{Duration, SystemTime},
+};
 
 #[async_trait]
 pub trait Controller<Resource>
@@ -27,19 +62,133 @@ where
 {
   fn metrics(&self) -> &Recorder;
 
-  async fn reconcile(self: Arc<Self>, resource: Arc<Resource>) -> eyre::Result<ReconcilerAction>;
-  fn error_policy(self: Arc<Self>, error: &eyre::Report) -> ReconcilerAction;
+  async fn reconcile(
+    self: Arc<Self>,
+    resource: Arc<Resource>,
+    ctx: ReconcileCtx,
+  ) -> eyre::Result<ReconcilerAction>;
+
+  /// How to retry a failed reconcile. `retry` is the delay the [Backoff] of the controller gives
+  /// for the object after its consecutive failures, which the object is retried after by default.
+  /// The failures are counted per object, and reset once it reconciles successfully.
+  fn error_policy(self: Arc<Self>, _error: &eyre::Report, retry: Duration) -> ReconcilerAction {
+    ReconcilerAction {
+      requeue_after: Some(retry),
+    }
+  }
 
   fn crd() -> CustomResourceDefinition {
-    Resource::crd()
+    let mut crd = Resource::crd();
+    schema::retire_versions(&mut crd, Self::retired_versions());
+    schema::make_structural(&mut crd);
+    crd
+  }
+
+  /// Earlier definitions of the resource (usually `CustomResourceExt::crd()` of the types of
+  /// previous versions). Their versions are kept in the CRD so objects still stored in them remain
+  /// readable, but are no longer served. They can be dropped once `migrate storage-version` has
+  /// rewritten every object in the current version.
+  fn retired_versions() -> Vec<CustomResourceDefinition> {
+    Vec::new()
+  }
+
+  /// What the controller needs from the cluster. These are embedded in the generated CRD and
+  /// validated by the `check` command.
+  fn requirements() -> Requirements {
+    Requirements::default()
+  }
+
+  /// What the controller reads and writes in the cluster, other than the resource it reconciles
+  /// and its status, which the runtime takes care of. These are granted by the RBAC roles printed
+  /// by the `rbac` command.
+  fn permissions() -> Permissions {
+    Permissions::default()
+  }
+
+  /// Which changes to the resource trigger a reconcile. Scheduled requeues and retries always
+  /// run.
+  fn predicates() -> Predicates {
+    Predicates::always()
+  }
+
+  /// How long to wait before retrying failed reconciles, growing with the consecutive failures of
+  /// each object.
+  fn backoff() -> Backoff {
+    Backoff::default()
+  }
+
+  /// How often a single object may be reconciled, so that an object which keeps triggering its
+  /// own reconciles cannot starve the other objects of the controller. Reconciles going over the
+  /// limit are delayed until the object may be reconciled again. `None` disables the limit.
+  fn rate_limit() -> Option<RateLimit> {
+    Some(RateLimit::default())
   }
 
+  /// Whether status patches made by the runtime are batched, and how. Patches are sent right
+  /// away by default.
+  fn status_batching() -> Option<StatusBatching> {
+    None
+  }
+
+  /// Whether the resource is suspended. Suspended resources are not reconciled, and reconcile
+  /// requests made on them are reported as ignored.
+  fn suspended(_resource: &Resource) -> bool {
+    false
+  }
+
+  /// How long a single reconcile of the resource may take before it is aborted.
+  fn reconcile_timeout(_resource: &Resource) -> Duration {
+    DEFAULT_RECONCILE_TIMEOUT
+  }
+
+  /// The share of the reconcile budget of the app the controller gets while other controllers
+  /// are waiting for it too, relative to the weights of those controllers. Only used when the app
+  /// runs with a reconcile budget.
+  fn reconcile_weight() -> u32 {
+    1
+  }
+
+  /// The interval at which the resource is expected to be reconciled, or `None` if the resource
+  /// is not reconciled on a schedule (for instance because it is suspended).
+  fn reconcile_interval(_resource: &Resource) -> Option<Duration> {
+    None
+  }
+
+  /// The time at which the resource was last reconciled, as recorded in its status.
+  fn last_reconciled(_resource: &Resource) -> Option<SystemTime> {
+    None
+  }
+
+  /// Called once the object `resource` refers to has been deleted, as found by the periodic sweep
+  /// of the watch cache, so that the controller can drop what it keeps for it.
+  fn deleted(&self, _resource: &ObjectReference) {}
+
   fn configure(self: Arc<Self>, controller: KubeController<Resource>) -> KubeController<Resource> {
     controller
   }
 
-  fn create(client: Client) -> KubeController<Resource> {
-    let api = Api::<Resource>::all(client);
-    KubeController::new(api, ListParams::default())
+  /// Label selector the watched objects must match, like `shard=a`, so that several instances of
+  /// the app can split the objects between them. It is combined with the selector the app runs
+  /// with, if any. All objects are watched by default.
+  fn label_selector() -> Option<String> {
+    None
+  }
+
+  /// Creates the controller, watching the resource in `namespace` only if the app is scoped to
+  /// one, with `params` holding the label selector and the watch settings of the app.
+  /// Cluster-scoped resources are always watched across the cluster.
+  fn create(
+    client: Client,
+    namespace: Option<&str>,
+    params: ListParams,
+  ) -> KubeController<Resource> {
+    let api = match namespace {
+      Some(namespace) if Resource::crd().spec.scope == "Namespaced" => {
+        Api::<Resource>::namespaced(client, namespace)
+      }
+      _ => Api::<Resource>::all(client),
+    };
+
+    KubeController::new(api, params)
   }
 }
@@ -1,12 +1,101 @@
 use k8s_openapi::{api::core::v1::ObjectReference, apimachinery::pkg::apis::meta::v1::Condition};
 use prometheus::{
-  core::Collector, exponential_buckets, GaugeVec, HistogramOpts, HistogramTimer, HistogramVec, Opts,
+  core::Collector, exponential_buckets, Gauge, GaugeVec, Histogram, HistogramOpts, HistogramTimer,
+  HistogramVec, IntCounterVec, Opts,
 };
+use std::{
+  collections::{HashMap, HashSet},
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+/// How long the label sets of an object are kept after it was last seen, by default.
+pub const DEFAULT_LABEL_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Identifies an object in the labels of the per-object metrics, as `(kind, name, namespace)`.
+type ObjectKey = (String, String, String);
+
+fn object_key(obj: &ObjectReference) -> ObjectKey {
+  (
+    obj.kind.clone().unwrap_or_default(),
+    obj.name.clone().unwrap_or_default(),
+    obj.namespace.clone().unwrap_or_default(),
+  )
+}
+
+/// How a reconcile ended, as counted by `gotk_reconcile_total`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReconcileResult {
+  /// The object was reconciled, or had nothing to reconcile.
+  Success,
+
+  /// The reconcile failed, or was aborted.
+  Error,
+
+  /// The object was put back to be reconciled later without being reconciled, like when it was
+  /// reconciled too often.
+  Requeue,
+}
+
+impl ReconcileResult {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      ReconcileResult::Success => "success",
+      ReconcileResult::Error => "error",
+      ReconcileResult::Requeue => "requeue",
+    }
+  }
+}
+
+/// A running reconcile, counted by `gotk_reconcile_active` until it is dropped.
+pub struct ActiveReconcile(Gauge);
+
+impl Drop for ActiveReconcile {
+  fn drop(&mut self) {
+    self.0.dec();
+  }
+}
+
+/// A reconcile waiting to start, counted by `gotk_workqueue_depth` until it is dropped, when how
+/// long it waited is observed by `gotk_workqueue_queue_duration_seconds`.
+pub struct QueuedReconcile {
+  depth: Gauge,
+  latency: Histogram,
+  queued: Instant,
+}
+
+impl Drop for QueuedReconcile {
+  fn drop(&mut self) {
+    self.depth.dec();
+    self.latency.observe(self.queued.elapsed().as_secs_f64());
+  }
+}
+
+/// The label sets recorded for an object, so they can be removed once it is gone.
+struct LabelSets {
+  last_seen: Instant,
+  condition_types: HashSet<String>,
+}
 
 pub struct Recorder {
   condition: GaugeVec,
   suspend: GaugeVec,
   duration: HistogramVec,
+  overdue: GaugeVec,
+  overdue_objects: GaugeVec,
+  backoff_objects: GaugeVec,
+  backoff: GaugeVec,
+  status_queue: GaugeVec,
+  status_flush: HistogramVec,
+  noop: IntCounterVec,
+  throttled: IntCounterVec,
+  total: IntCounterVec,
+  active: GaugeVec,
+  queue_depth: GaugeVec,
+  queue_latency: HistogramVec,
+  relists: IntCounterVec,
+  objects: Mutex<HashMap<ObjectKey, LabelSets>>,
+  label_ttl: Duration,
 }
 
 macro_rules! reconcile_metric {
@@ -18,6 +107,14 @@ macro_rules! reconcile_metric {
     <GaugeVec>::new(opts, &[$($label,)*])
   }};
 
+  (counter, $name:literal, $help:literal, [$($label:literal),*$(,)?]$(,)?) => {{
+    let opts = Opts::new($name, $help)
+      .subsystem("reconcile")
+      .namespace("gotk");
+
+    <IntCounterVec>::new(opts, &[$($label,)*])
+  }};
+
   (histogram, $name:literal, $help:literal, $buckets:expr, [$($label:literal),*$(,)?]$(,)?) => {{
     let opts = HistogramOpts::new($name, $help)
       .subsystem("reconcile")
@@ -52,8 +149,120 @@ impl Recorder {
         exponential_buckets(10e-9, 10f64, 10)?,
         ["kind", "name", "namespace"],
       )?,
+
+      overdue: reconcile_metric!(
+        gauge,
+        "overdue_seconds",
+        "The longest time a GitOps Toolkit resource has gone past its reconcile interval.",
+        ["kind"],
+      )?,
+
+      overdue_objects: reconcile_metric!(
+        gauge,
+        "overdue_objects",
+        "The number of GitOps Toolkit resources not reconciled within twice their interval.",
+        ["kind"],
+      )?,
+
+      backoff_objects: reconcile_metric!(
+        gauge,
+        "backoff_objects",
+        "The number of GitOps Toolkit resources waiting to retry a failed reconciliation.",
+        ["kind"],
+      )?,
+
+      backoff: reconcile_metric!(
+        gauge,
+        "backoff_seconds",
+        "The longest time a GitOps Toolkit resource waits to retry a failed reconciliation.",
+        ["kind"],
+      )?,
+
+      status_queue: reconcile_metric!(
+        gauge,
+        "status_queue_depth",
+        "The number of status patches of GitOps Toolkit resources waiting to be sent.",
+        ["kind"],
+      )?,
+
+      status_flush: reconcile_metric!(
+        histogram,
+        "status_flush_seconds",
+        "The duration in seconds of sending a batch of queued status patches.",
+        exponential_buckets(0.001, 2f64, 15)?,
+        ["kind"],
+      )?,
+
+      noop: reconcile_metric!(
+        counter,
+        "noop_total",
+        "The number of reconciles of a GitOps Toolkit resource which generated unchanged output.",
+        ["kind", "name", "namespace"],
+      )?,
+
+      throttled: reconcile_metric!(
+        counter,
+        "throttled_total",
+        "The number of reconciles of GitOps Toolkit resources delayed by their rate limit.",
+        ["kind"],
+      )?,
+
+      total: reconcile_metric!(
+        counter,
+        "total",
+        "The number of reconciles of GitOps Toolkit resources, by result.",
+        ["kind", "result"],
+      )?,
+
+      active: reconcile_metric!(
+        gauge,
+        "active",
+        "The number of reconciles of GitOps Toolkit resources currently running.",
+        ["kind"],
+      )?,
+
+      queue_depth: GaugeVec::new(
+        Opts::new(
+          "depth",
+          "The number of reconciles of GitOps Toolkit resources waiting to start.",
+        )
+        .subsystem("workqueue")
+        .namespace("gotk"),
+        &["kind"],
+      )?,
+
+      queue_latency: HistogramVec::new(
+        HistogramOpts::new(
+          "queue_duration_seconds",
+          "How long reconciles of GitOps Toolkit resources waited to start, in seconds.",
+        )
+        .subsystem("workqueue")
+        .namespace("gotk")
+        .buckets(exponential_buckets(0.001, 2f64, 15)?),
+        &["kind"],
+      )?,
+
+      relists: IntCounterVec::new(
+        Opts::new(
+          "relists_total",
+          "The number of full relists of GitOps Toolkit resources after their watch expired.",
+        )
+        .subsystem("watch")
+        .namespace("gotk"),
+        &["kind"],
+      )?,
+
+      objects: Mutex::new(HashMap::new()),
+      label_ttl: DEFAULT_LABEL_TTL,
     })
   }
+
+  /// Keep the label sets of an object for `ttl` after it was last seen, rather than for
+  /// [DEFAULT_LABEL_TTL].
+  pub fn with_label_ttl(mut self, ttl: Duration) -> Self {
+    self.label_ttl = ttl;
+    self
+  }
 }
 
 impl Collector for Recorder {
@@ -62,6 +271,19 @@ impl Collector for Recorder {
     result.extend(self.condition.desc());
     result.extend(self.suspend.desc());
     result.extend(self.duration.desc());
+    result.extend(self.overdue.desc());
+    result.extend(self.overdue_objects.desc());
+    result.extend(self.backoff_objects.desc());
+    result.extend(self.backoff.desc());
+    result.extend(self.status_queue.desc());
+    result.extend(self.status_flush.desc());
+    result.extend(self.noop.desc());
+    result.extend(self.throttled.desc());
+    result.extend(self.total.desc());
+    result.extend(self.active.desc());
+    result.extend(self.queue_depth.desc());
+    result.extend(self.queue_latency.desc());
+    result.extend(self.relists.desc());
 
     result
   }
@@ -71,6 +293,19 @@ impl Collector for Recorder {
     result.extend(self.condition.collect());
     result.extend(self.suspend.collect());
     result.extend(self.duration.collect());
+    result.extend(self.overdue.collect());
+    result.extend(self.overdue_objects.collect());
+    result.extend(self.backoff_objects.collect());
+    result.extend(self.backoff.collect());
+    result.extend(self.status_queue.collect());
+    result.extend(self.status_flush.collect());
+    result.extend(self.noop.collect());
+    result.extend(self.throttled.collect());
+    result.extend(self.total.collect());
+    result.extend(self.active.collect());
+    result.extend(self.queue_depth.collect());
+    result.extend(self.queue_latency.collect());
+    result.extend(self.relists.collect());
 
     result
   }
@@ -82,6 +317,7 @@ impl Recorder {
     let name = obj.name.as_deref().unwrap_or_default();
     let namespace = obj.namespace.as_deref().unwrap_or_default();
     let ty = &*condition.type_;
+    self.seen(obj, Some(ty));
 
     let record = |status: &str, value: bool| {
       self
@@ -101,6 +337,7 @@ impl Recorder {
     let name = obj.name.as_deref().unwrap_or_default();
     let namespace = obj.namespace.as_deref().unwrap_or_default();
     let value = if suspend { 1f64 } else { 0f64 };
+    self.seen(obj, None);
 
     self
       .suspend
@@ -112,10 +349,294 @@ impl Recorder {
     let kind = obj.kind.as_deref().unwrap_or_default();
     let name = obj.name.as_deref().unwrap_or_default();
     let namespace = obj.namespace.as_deref().unwrap_or_default();
+    self.seen(obj, None);
 
     self
       .duration
       .with_label_values(&[kind, name, namespace])
       .start_timer()
   }
+
+  pub fn record_overdue(&self, kind: &str, overdue: Duration, count: usize) {
+    self
+      .overdue
+      .with_label_values(&[kind])
+      .set(overdue.as_secs_f64());
+
+    self
+      .overdue_objects
+      .with_label_values(&[kind])
+      .set(count as f64);
+  }
+
+  /// Records the `count` objects of `kind` backing off after failed reconciles, the longest of
+  /// which waits for `longest`.
+  pub fn record_backoff(&self, kind: &str, count: usize, longest: Duration) {
+    self
+      .backoff_objects
+      .with_label_values(&[kind])
+      .set(count as f64);
+
+    self
+      .backoff
+      .with_label_values(&[kind])
+      .set(longest.as_secs_f64());
+  }
+
+  pub fn record_status_queue(&self, kind: &str, depth: usize) {
+    self
+      .status_queue
+      .with_label_values(&[kind])
+      .set(depth as f64);
+  }
+
+  pub fn record_status_flush(&self, kind: &str, duration: Duration) {
+    self
+      .status_flush
+      .with_label_values(&[kind])
+      .observe(duration.as_secs_f64());
+  }
+
+  /// Records a reconcile of an object of `kind` which ended with `result`.
+  pub fn record_reconcile(&self, kind: &str, result: ReconcileResult) {
+    self.total.with_label_values(&[kind, result.as_str()]).inc();
+  }
+
+  /// Counts a reconcile of an object of `kind` as running, until the returned guard is dropped.
+  pub fn start_reconcile(&self, kind: &str) -> ActiveReconcile {
+    let active = self.active.with_label_values(&[kind]);
+    active.inc();
+    ActiveReconcile(active)
+  }
+
+  /// Counts a reconcile of an object of `kind` as waiting to start, until the returned guard is
+  /// dropped.
+  pub fn queue_reconcile(&self, kind: &str) -> QueuedReconcile {
+    let depth = self.queue_depth.with_label_values(&[kind]);
+    depth.inc();
+    QueuedReconcile {
+      depth,
+      latency: self.queue_latency.with_label_values(&[kind]),
+      queued: Instant::now(),
+    }
+  }
+
+  /// Records a reconcile of an object of `kind` delayed by its rate limit.
+  pub fn record_throttled(&self, kind: &str) {
+    self.throttled.with_label_values(&[kind]).inc();
+  }
+
+  /// Records a full relist of the objects of `kind`, as their watch expired (410 Gone) and could
+  /// not be resumed.
+  pub fn record_relist(&self, kind: &str) {
+    self.relists.with_label_values(&[kind]).inc();
+  }
+
+  /// Records a reconcile which skipped applying its output, as it was unchanged.
+  pub fn record_noop(&self, obj: &ObjectReference) {
+    let kind = obj.kind.as_deref().unwrap_or_default();
+    let name = obj.name.as_deref().unwrap_or_default();
+    let namespace = obj.namespace.as_deref().unwrap_or_default();
+    self.seen(obj, None);
+
+    self.noop.with_label_values(&[kind, name, namespace]).inc();
+  }
+
+  /// Marks `obj` as seen, keeping its label sets around for another TTL.
+  pub fn touch(&self, obj: &ObjectReference) {
+    self.seen(obj, None);
+  }
+
+  fn seen(&self, obj: &ObjectReference, condition_type: Option<&str>) {
+    let mut objects = self.objects.lock().unwrap();
+    let sets = objects.entry(object_key(obj)).or_insert_with(|| LabelSets {
+      last_seen: Instant::now(),
+      condition_types: HashSet::new(),
+    });
+
+    sets.last_seen = Instant::now();
+    if let Some(ty) = condition_type {
+      if !sets.condition_types.contains(ty) {
+        sets.condition_types.insert(ty.into());
+      }
+    }
+  }
+
+  /// Records that `obj` was deleted. Its conditions are reported as `Deleted` until the label TTL
+  /// expires, so the deletion can be observed, while its other label sets are removed right away.
+  pub fn record_deleted(&self, obj: &ObjectReference) {
+    let types = {
+      let mut objects = self.objects.lock().unwrap();
+      let sets = objects.entry(object_key(obj)).or_insert_with(|| LabelSets {
+        last_seen: Instant::now(),
+        condition_types: HashSet::new(),
+      });
+
+      sets.last_seen = Instant::now();
+      sets.condition_types.insert("Ready".into());
+      sets.condition_types.iter().cloned().collect::<Vec<_>>()
+    };
+
+    for type_ in types {
+      let condition = Condition {
+        type_,
+        ..Default::default()
+      };
+      self.record_condition(obj, &condition, true);
+    }
+
+    self.remove_object_labels(&object_key(obj));
+  }
+
+  /// Removes all label sets recorded for `obj`, including its `Deleted` conditions.
+  pub fn forget(&self, obj: &ObjectReference) {
+    let key = object_key(obj);
+    if let Some(sets) = self.objects.lock().unwrap().remove(&key) {
+      self.remove(&key, &sets);
+    }
+  }
+
+  /// Removes the label sets of the objects which were not seen for longer than the label TTL,
+  /// preventing the registry from growing with every object ever reconciled.
+  pub fn expire(&self) {
+    let mut objects = self.objects.lock().unwrap();
+    let expired = objects
+      .iter()
+      .filter(|(_, sets)| sets.last_seen.elapsed() > self.label_ttl)
+      .map(|(key, _)| key.clone())
+      .collect::<Vec<_>>();
+
+    for key in expired {
+      if let Some(sets) = objects.remove(&key) {
+        self.remove(&key, &sets);
+      }
+    }
+  }
+
+  fn remove(&self, key: &ObjectKey, sets: &LabelSets) {
+    let (kind, name, namespace) = key;
+    let labels = [kind.as_str(), name.as_str(), namespace.as_str()];
+
+    // removing a label set which was never recorded fails, which is fine
+    for ty in &sets.condition_types {
+      for status in ["True", "False", "Unknown", "Deleted"] {
+        let _ = self.condition.remove_label_values(&[
+          labels[0],
+          labels[1],
+          labels[2],
+          ty.as_str(),
+          status,
+        ]);
+      }
+    }
+
+    self.remove_object_labels(key);
+  }
+
+  /// Removes the label sets of `key` which are not about a condition.
+  fn remove_object_labels(&self, (kind, name, namespace): &ObjectKey) {
+    let labels = [kind.as_str(), name.as_str(), namespace.as_str()];
+    let _ = self.suspend.remove_label_values(&labels);
+    let _ = self.duration.remove_label_values(&labels);
+    let _ = self.noop.remove_label_values(&labels);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn obj(name: &str) -> ObjectReference {
+    ObjectReference {
+      kind: Some("HttpEndpoint".into()),
+      name: Some(name.into()),
+      namespace: Some("default".into()),
+      ..Default::default()
+    }
+  }
+
+  fn suspend_series(recorder: &Recorder) -> usize {
+    recorder
+      .suspend
+      .collect()
+      .iter()
+      .map(|family| family.get_metric().len())
+      .sum()
+  }
+
+  #[test]
+  fn reconciles_are_counted_while_running() {
+    let recorder = Recorder::new().unwrap();
+    let active = recorder.start_reconcile("HttpEndpoint");
+    let queued = recorder.queue_reconcile("HttpEndpoint");
+    assert_eq!(
+      recorder.active.with_label_values(&["HttpEndpoint"]).get(),
+      1.0
+    );
+    assert_eq!(
+      recorder
+        .queue_depth
+        .with_label_values(&["HttpEndpoint"])
+        .get(),
+      1.0
+    );
+
+    drop((active, queued));
+    recorder.record_reconcile("HttpEndpoint", ReconcileResult::Requeue);
+    assert_eq!(
+      recorder.active.with_label_values(&["HttpEndpoint"]).get(),
+      0.0
+    );
+    assert_eq!(
+      recorder
+        .queue_depth
+        .with_label_values(&["HttpEndpoint"])
+        .get(),
+      0.0
+    );
+    let latency = recorder.queue_latency.with_label_values(&["HttpEndpoint"]);
+    assert_eq!(latency.get_sample_count(), 1);
+    let total = recorder
+      .total
+      .with_label_values(&["HttpEndpoint", "requeue"]);
+    assert_eq!(total.get(), 1);
+  }
+
+  #[test]
+  fn forget_removes_label_sets() {
+    let recorder = Recorder::new().unwrap();
+    recorder.record_suspend(&obj("a"), true);
+    recorder.record_suspend(&obj("b"), false);
+    assert_eq!(suspend_series(&recorder), 2);
+
+    recorder.forget(&obj("a"));
+    assert_eq!(suspend_series(&recorder), 1);
+  }
+
+  #[test]
+  fn deleted_objects_only_keep_their_conditions() {
+    let recorder = Recorder::new().unwrap();
+    recorder.record_suspend(&obj("a"), true);
+    recorder.record_deleted(&obj("a"));
+    assert_eq!(suspend_series(&recorder), 0);
+
+    let deleted = recorder
+      .condition
+      .with_label_values(&["HttpEndpoint", "a", "default", "Ready", "Deleted"])
+      .get();
+    assert_eq!(deleted, 1f64);
+  }
+
+  #[test]
+  fn expire_removes_objects_not_seen_within_ttl() {
+    let recorder = Recorder::new().unwrap().with_label_ttl(Duration::ZERO);
+    recorder.record_suspend(&obj("a"), true);
+    recorder.expire();
+    assert_eq!(suspend_series(&recorder), 0);
+
+    let recorder = Recorder::new().unwrap();
+    recorder.record_suspend(&obj("a"), true);
+    recorder.expire();
+    assert_eq!(suspend_series(&recorder), 1);
+  }
 }
@@ -0,0 +1,400 @@
+use kube::Client;
+use serde_json::{Map, Value};
+use std::{collections::HashMap, fmt};
+use thiserror::Error;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+/// A field of an object which does not match the schema of its kind.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldError {
+  /// Path of the field, like `spec.containers[0].name`.
+  pub path: String,
+  pub message: String,
+}
+
+impl fmt::Display for FieldError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.path.is_empty() {
+      f.write_str(&self.message)
+    } else {
+      write!(f, "{}: {}", self.path, self.message)
+    }
+  }
+}
+
+/// An object rejected by client-side validation, before it was sent to the cluster.
+#[derive(Debug, Error)]
+#[error("invalid {kind} '{name}': {}", .errors.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+pub struct SchemaViolation {
+  pub kind: String,
+  pub name: String,
+  pub errors: Vec<FieldError>,
+}
+
+/// The definitions of the cluster's OpenAPI (v2) schema, indexed by the kinds they describe.
+#[derive(Debug, Default)]
+struct Definitions {
+  definitions: Map<String, Value>,
+  kinds: HashMap<(String, String, String), String>,
+}
+
+impl Definitions {
+  fn parse(document: Value) -> Self {
+    let definitions = match document {
+      Value::Object(mut document) => match document.remove("definitions") {
+        Some(Value::Object(definitions)) => definitions,
+        _ => Map::new(),
+      },
+      _ => Map::new(),
+    };
+
+    let mut kinds = HashMap::new();
+    for (name, definition) in &definitions {
+      let gvks = definition
+        .get("x-kubernetes-group-version-kind")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten();
+      for gvk in gvks {
+        let field = |name| gvk.get(name).and_then(Value::as_str).unwrap_or_default();
+        let key = (
+          field("group").to_string(),
+          field("version").to_string(),
+          field("kind").to_string(),
+        );
+        kinds.insert(key, name.clone());
+      }
+    }
+
+    Self { definitions, kinds }
+  }
+
+  fn schema(&self, api_version: &str, kind: &str) -> Option<&Value> {
+    let (group, version) = api_version.rsplit_once('/').unwrap_or(("", api_version));
+    let key = (group.to_string(), version.to_string(), kind.to_string());
+    self
+      .kinds
+      .get(&key)
+      .and_then(|name| self.definitions.get(name))
+  }
+
+  /// Checks `value` against `schema`, collecting every mismatch into `errors`. Only what the
+  /// schema states is checked, so schemas without a type (like `RawExtension`) accept anything,
+  /// and `null` is accepted everywhere, as the API server treats it as unset.
+  fn validate(
+    &self,
+    schema: &Value,
+    value: &Value,
+    path: &mut String,
+    errors: &mut Vec<FieldError>,
+  ) {
+    let schema = match self.resolve(schema) {
+      Some(schema) => schema,
+      None => return,
+    };
+    if value.is_null()
+      || schema
+        .get("x-kubernetes-preserve-unknown-fields")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+    {
+      return;
+    }
+
+    if schema
+      .get("x-kubernetes-int-or-string")
+      .and_then(Value::as_bool)
+      .unwrap_or(false)
+    {
+      if !value.is_i64() && !value.is_u64() && !value.is_string() {
+        errors.push(error(
+          path,
+          format!("expected integer or string, got {}", type_of(value)),
+        ));
+      }
+      return;
+    }
+
+    let expected = schema.get("type").and_then(Value::as_str);
+    let matches = match expected {
+      Some("object") => value.is_object(),
+      Some("array") => value.is_array(),
+      Some("string") => value.is_string(),
+      Some("integer") => value.is_i64() || value.is_u64(),
+      Some("number") => value.is_number(),
+      Some("boolean") => value.is_boolean(),
+      _ => true,
+    };
+    if !matches {
+      let expected = expected.unwrap_or_default();
+      errors.push(error(
+        path,
+        format!("expected {expected}, got {}", type_of(value)),
+      ));
+      return;
+    }
+
+    match value {
+      Value::Object(fields) => self.validate_object(schema, fields, path, errors),
+      Value::Array(items) => {
+        if let Some(schema) = schema.get("items") {
+          for (index, item) in items.iter().enumerate() {
+            let len = path.len();
+            path.push_str(&format!("[{index}]"));
+            self.validate(schema, item, path, errors);
+            path.truncate(len);
+          }
+        }
+      }
+      _ => (),
+    }
+  }
+
+  fn validate_object(
+    &self,
+    schema: &Value,
+    fields: &Map<String, Value>,
+    path: &mut String,
+    errors: &mut Vec<FieldError>,
+  ) {
+    let required = schema
+      .get("required")
+      .and_then(Value::as_array)
+      .into_iter()
+      .flatten()
+      .filter_map(Value::as_str);
+    for name in required {
+      if fields.get(name).is_none_or(Value::is_null) {
+        errors.push(error(
+          &field_path(path, name),
+          "required field is missing".into(),
+        ));
+      }
+    }
+
+    let properties = schema.get("properties").and_then(Value::as_object);
+    let additional = schema.get("additionalProperties");
+    for (name, value) in fields {
+      let len = path.len();
+      *path = field_path(path, name);
+      match (properties.and_then(|p| p.get(name)), additional) {
+        (Some(schema), _) => self.validate(schema, value, path, errors),
+        (None, Some(Value::Bool(true))) => (),
+        (None, Some(Value::Bool(false))) => errors.push(error(path, "unknown field".into())),
+        (None, Some(schema)) => self.validate(schema, value, path, errors),
+        // `apiVersion` and `kind` are not always part of the schema of embedded objects
+        (None, None) if properties.is_some() && name != "apiVersion" && name != "kind" => {
+          errors.push(error(path, "unknown field".into()))
+        }
+        (None, None) => (),
+      }
+      path.truncate(len);
+    }
+  }
+
+  /// Follows `$ref`s to the schema they point to.
+  fn resolve<'a>(&'a self, mut schema: &'a Value) -> Option<&'a Value> {
+    // bounded, in case of references pointing at each other
+    for _ in 0..16 {
+      match schema.get("$ref").and_then(Value::as_str) {
+        Some(reference) => {
+          let name = reference.strip_prefix("#/definitions/")?;
+          schema = self.definitions.get(name)?;
+        }
+        None => return Some(schema),
+      }
+    }
+
+    None
+  }
+}
+
+fn field_path(path: &str, name: &str) -> String {
+  if path.is_empty() {
+    name.to_string()
+  } else {
+    format!("{path}.{name}")
+  }
+}
+
+fn error(path: &str, message: String) -> FieldError {
+  FieldError {
+    path: path.to_string(),
+    message,
+  }
+}
+
+fn type_of(value: &Value) -> &'static str {
+  match value {
+    Value::Null => "null",
+    Value::Bool(_) => "boolean",
+    Value::Number(n) if n.is_f64() => "number",
+    Value::Number(_) => "integer",
+    Value::String(_) => "string",
+    Value::Array(_) => "array",
+    Value::Object(_) => "object",
+  }
+}
+
+/// Validates the objects written by controllers against the OpenAPI schema published by the
+/// cluster, so that invalid objects are reported with the fields at fault, rather than with
+/// whatever the API server makes of them.
+///
+/// The schema is fetched on first use and kept for the lifetime of the process. Kinds it does not
+/// describe (like CRDs installed later on) are not validated, and neither is anything if the
+/// schema cannot be fetched, leaving it to the API server.
+#[derive(Default)]
+pub struct OpenApiSchemas {
+  client: Option<Client>,
+  definitions: OnceCell<Definitions>,
+}
+
+impl OpenApiSchemas {
+  pub fn new(client: Client) -> Self {
+    Self {
+      client: Some(client),
+      definitions: OnceCell::new(),
+    }
+  }
+
+  /// Validates `object` against the schema of its `apiVersion` and `kind`.
+  pub async fn validate(&self, object: &Value) -> Result<(), SchemaViolation> {
+    let definitions = match self.definitions().await {
+      Some(definitions) => definitions,
+      None => return Ok(()),
+    };
+
+    let field = |name| object.get(name).and_then(Value::as_str).unwrap_or_default();
+    let schema = match definitions.schema(field("apiVersion"), field("kind")) {
+      Some(schema) => schema,
+      None => return Ok(()),
+    };
+
+    let mut errors = Vec::new();
+    definitions.validate(schema, object, &mut String::new(), &mut errors);
+    if errors.is_empty() {
+      return Ok(());
+    }
+
+    Err(SchemaViolation {
+      kind: field("kind").to_string(),
+      name: object
+        .pointer("/metadata/name")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string(),
+      errors,
+    })
+  }
+
+  async fn definitions(&self) -> Option<&Definitions> {
+    let client = self.client.as_ref()?;
+    let result = self
+      .definitions
+      .get_or_try_init(|| async {
+        let request = http::Request::get("/openapi/v2").body(Vec::new())?;
+        let document = client.request::<Value>(request).await?;
+        Ok::<_, eyre::Report>(Definitions::parse(document))
+      })
+      .await;
+
+    match result {
+      Ok(definitions) => Some(definitions),
+      // tried again on the next write
+      Err(error) => {
+        warn!(%error, "failed to fetch the OpenAPI schema, skipping validation");
+        None
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn definitions() -> Definitions {
+    Definitions::parse(json!({
+      "definitions": {
+        "io.k8s.api.core.v1.ConfigMap": {
+          "type": "object",
+          "properties": {
+            "apiVersion": { "type": "string" },
+            "kind": { "type": "string" },
+            "metadata": { "$ref": "#/definitions/io.k8s.apimachinery.pkg.apis.meta.v1.ObjectMeta" },
+            "data": { "type": "object", "additionalProperties": { "type": "string" } },
+            "immutable": { "type": "boolean" },
+          },
+          "x-kubernetes-group-version-kind": [{ "group": "", "version": "v1", "kind": "ConfigMap" }],
+        },
+        "io.k8s.apimachinery.pkg.apis.meta.v1.ObjectMeta": {
+          "type": "object",
+          "properties": {
+            "name": { "type": "string" },
+            "labels": { "type": "object", "additionalProperties": { "type": "string" } },
+            "ownerReferences": {
+              "type": "array",
+              "items": {
+                "type": "object",
+                "required": ["name", "uid"],
+                "properties": { "name": { "type": "string" }, "uid": { "type": "string" } },
+              },
+            },
+          },
+        },
+      }
+    }))
+  }
+
+  fn validate(object: Value) -> Vec<String> {
+    let definitions = definitions();
+    let api_version = object["apiVersion"].as_str().unwrap();
+    let kind = object["kind"].as_str().unwrap();
+    let schema = definitions.schema(api_version, kind).unwrap();
+    let mut errors = Vec::new();
+    definitions.validate(schema, &object, &mut String::new(), &mut errors);
+    errors.iter().map(ToString::to_string).collect()
+  }
+
+  #[test]
+  fn accepts_valid_objects() {
+    let errors = validate(json!({
+      "apiVersion": "v1",
+      "kind": "ConfigMap",
+      "metadata": { "name": "out", "labels": { "app": "a" }, "ownerReferences": [{ "name": "o", "uid": "1" }] },
+      "data": { "key": "value" },
+      "immutable": null,
+    }));
+    assert!(errors.is_empty(), "{errors:?}");
+  }
+
+  #[test]
+  fn reports_every_invalid_field() {
+    let errors = validate(json!({
+      "apiVersion": "v1",
+      "kind": "ConfigMap",
+      "metadata": { "name": 1, "ownerReferences": [{ "name": "o" }] },
+      "data": { "key": 3 },
+      "immutable": "yes",
+      "spec": {},
+    }));
+    assert_eq!(
+      errors,
+      [
+        "data.key: expected string, got integer",
+        "immutable: expected boolean, got string",
+        "metadata.name: expected string, got integer",
+        "metadata.ownerReferences[0].uid: required field is missing",
+        "spec: unknown field",
+      ]
+    );
+  }
+
+  #[test]
+  fn unknown_kinds_have_no_schema() {
+    assert!(definitions().schema("v1", "Secret").is_none());
+    assert!(definitions().schema("apps/v1", "ConfigMap").is_none());
+  }
+}
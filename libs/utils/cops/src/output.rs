@@ -0,0 +1,74 @@
+use crate::canonical::object_hash;
+use serde::Serialize;
+use std::{collections::HashMap, sync::Mutex};
+
+/// The hash of the output generated by a reconcile (e.g. the children it applies), in the form
+/// `sha256:<hex>`. It is the canonical [object_hash], so output built from maps in a different
+/// order is still unchanged.
+pub fn output_hash<T: Serialize>(output: &T) -> serde_json::Result<String> {
+  object_hash(output)
+}
+
+/// Remembers the hash of the output last applied for every object, by UID, so reconciles which
+/// generate identical output can skip applying it.
+///
+/// The cache is only kept in memory, so controllers also persist the hash (usually in the status
+/// of the object), which is used when the cache has no entry, e.g. right after a restart.
+#[derive(Default)]
+pub struct OutputCache {
+  hashes: Mutex<HashMap<String, String>>,
+}
+
+impl OutputCache {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Whether `hash` is the hash of the output last applied for the object with `uid`. The
+  /// `persisted` hash is used if the cache has no entry for the object.
+  pub fn is_unchanged(&self, uid: &str, hash: &str, persisted: Option<&str>) -> bool {
+    match self.hashes.lock().unwrap().get(uid) {
+      Some(cached) => cached == hash,
+      None => persisted == Some(hash),
+    }
+  }
+
+  /// Records that the output with `hash` was applied for the object with `uid`.
+  pub fn record(&self, uid: impl Into<String>, hash: impl Into<String>) {
+    self.hashes.lock().unwrap().insert(uid.into(), hash.into());
+  }
+
+  /// Forgets the output of the object with `uid`, so the next output is always applied.
+  pub fn forget(&self, uid: &str) {
+    self.hashes.lock().unwrap().remove(uid);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn hash_is_stable() {
+    let a = output_hash(&("key", "value")).unwrap();
+    let b = output_hash(&("key", "value")).unwrap();
+    let c = output_hash(&("key", "other")).unwrap();
+    assert!(a.starts_with("sha256:"));
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+  }
+
+  #[test]
+  fn cache_prefers_recorded_hash() {
+    let cache = OutputCache::new();
+    assert!(!cache.is_unchanged("uid", "a", None));
+    assert!(cache.is_unchanged("uid", "a", Some("a")));
+
+    cache.record("uid", "b");
+    assert!(cache.is_unchanged("uid", "b", Some("a")));
+    assert!(!cache.is_unchanged("uid", "a", Some("a")));
+
+    cache.forget("uid");
+    assert!(cache.is_unchanged("uid", "a", Some("a")));
+  }
+}
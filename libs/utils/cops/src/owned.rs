@@ -0,0 +1,209 @@
+use crate::context::ReconcileCtx;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ObjectMeta, OwnerReference};
+use kube::{api::ListParams, Api, Resource, ResourceExt};
+use serde::de::DeserializeOwned;
+use std::{collections::HashSet, fmt::Debug};
+
+/// Label of the objects generated from a resource, holding the inventory id of the resource, so
+/// that the objects it no longer generates can be found and pruned by [gc_owned].
+pub const INVENTORY_LABEL: &str = "fluxcd.yolodev.io/inventory";
+
+/// The reference making `owner` the controller of an object generated from it, so that the object
+/// is garbage collected along with it. `None` if `owner` was not created yet, as it has no uid.
+pub fn controller_owner_ref<K>(owner: &K) -> Option<OwnerReference>
+where
+  K: Resource,
+  K::DynamicType: Default,
+{
+  let dt = K::DynamicType::default();
+  let meta = owner.meta();
+
+  Some(OwnerReference {
+    api_version: K::api_version(&dt).into_owned(),
+    kind: K::kind(&dt).into_owned(),
+    name: meta.name.clone()?,
+    uid: meta.uid.clone()?,
+    controller: Some(true),
+    block_owner_deletion: Some(true),
+  })
+}
+
+/// The id the objects generated from `owner` are labelled with, which is its uid.
+pub fn inventory_id<K: Resource>(owner: &K) -> Option<String> {
+  owner.meta().uid.clone()
+}
+
+/// Marks an object generated from `owner`, through its `meta`, as controlled by `owner` and
+/// labelled with its inventory id.
+pub fn own<K>(owner: &K, meta: &mut ObjectMeta)
+where
+  K: Resource,
+  K::DynamicType: Default,
+{
+  meta.owner_references = controller_owner_ref(owner).map(|r| vec![r]);
+  if let Some(id) = inventory_id(owner) {
+    meta
+      .labels
+      .get_or_insert_with(Default::default)
+      .insert(INVENTORY_LABEL.into(), id);
+  }
+}
+
+/// The objects of `owned` which are not in `desired`, by namespace and name.
+fn orphans<'a, K: Resource>(owned: &'a [K], desired: &[K]) -> Vec<&'a K> {
+  let desired = desired
+    .iter()
+    .map(|object| (object.namespace(), object.name()))
+    .collect::<HashSet<_>>();
+
+  owned
+    .iter()
+    .filter(|object| object.meta().deletion_timestamp.is_none())
+    .filter(|object| !desired.contains(&(object.namespace(), object.name())))
+    .collect()
+}
+
+/// Deletes the objects of kind `K` in the cluster labelled with the inventory id `inventory`
+/// which are not in `desired`, like the Secrets generated for namespaces a resource no longer
+/// selects. Returns the pruned objects, as `namespace/name`. Nothing is deleted if the context is
+/// read-only.
+pub async fn gc_owned<K>(
+  ctx: &ReconcileCtx,
+  inventory: &str,
+  desired: &[K],
+) -> eyre::Result<Vec<String>>
+where
+  K: Resource + Clone + DeserializeOwned + Debug,
+  K::DynamicType: Default,
+{
+  let params = ListParams::default().labels(&format!("{INVENTORY_LABEL}={inventory}"));
+  let owned = Api::<K>::all(ctx.client().clone()).list(&params).await?;
+
+  let mut pruned = Vec::new();
+  for orphan in orphans(&owned.items, desired) {
+    let name = orphan.name();
+    let api = match orphan.namespace() {
+      Some(namespace) => {
+        pruned.push(format!("{namespace}/{name}"));
+        Api::<K>::namespaced(ctx.client().clone(), &namespace)
+      }
+      None => {
+        pruned.push(name.clone());
+        Api::<K>::all(ctx.client().clone())
+      }
+    };
+    ctx.delete(&api, &name).await?;
+  }
+
+  Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::events::EventRecorder;
+  use http::{Method, Request, Response};
+  use k8s_openapi::{
+    api::core::v1::{ConfigMap, ObjectReference, Secret},
+    apimachinery::pkg::apis::meta::v1::Time,
+    chrono::Utc,
+  };
+  use kube::Client;
+  use std::{
+    convert::Infallible,
+    sync::{Arc, Mutex},
+    time::Duration,
+  };
+  use tokio_util::sync::CancellationToken;
+
+  fn secret(namespace: &str, name: &str) -> Secret {
+    Secret {
+      metadata: ObjectMeta {
+        name: Some(name.into()),
+        namespace: Some(namespace.into()),
+        ..Default::default()
+      },
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn owned_objects_reference_and_are_labelled_with_the_owner() {
+    let mut owner = ConfigMap::default();
+    owner.metadata.name = Some("keys".into());
+    let mut meta = ObjectMeta::default();
+    own(&owner, &mut meta);
+    assert_eq!(meta.owner_references, None);
+    assert_eq!(meta.labels, None);
+
+    owner.metadata.uid = Some("0a1b".into());
+    own(&owner, &mut meta);
+    let reference = &meta.owner_references.unwrap()[0];
+    assert_eq!(
+      (&*reference.api_version, &*reference.kind, &*reference.uid),
+      ("v1", "ConfigMap", "0a1b")
+    );
+    assert_eq!(reference.controller, Some(true));
+    assert_eq!(meta.labels.unwrap()[INVENTORY_LABEL], "0a1b");
+  }
+
+  #[test]
+  fn orphans_are_the_owned_objects_not_desired() {
+    let mut deleting = secret("c", "keys");
+    deleting.metadata.deletion_timestamp = Some(Time(Utc::now()));
+    let owned = [secret("a", "keys"), secret("b", "keys"), deleting];
+    let desired = [secret("a", "keys"), secret("b", "other")];
+
+    let orphans = orphans(&owned, &desired)
+      .into_iter()
+      .map(|o| (o.namespace().unwrap(), o.name()))
+      .collect::<Vec<_>>();
+    assert_eq!(orphans, [("b".to_string(), "keys".to_string())]);
+  }
+
+  #[tokio::test]
+  async fn prunes_the_secrets_of_deselected_namespaces() {
+    // the Secrets generated for namespaces a and b, of which only a is still selected
+    let owned = serde_json::json!({
+      "apiVersion": "v1",
+      "kind": "SecretList",
+      "metadata": {},
+      "items": [secret("a", "keys"), secret("b", "keys")],
+    });
+    let deleted = Arc::new(Mutex::new(Vec::new()));
+    let deletes = deleted.clone();
+    let service = tower::service_fn(move |request: Request<hyper::Body>| {
+      let body = if request.method() == Method::DELETE {
+        deletes
+          .lock()
+          .unwrap()
+          .push(request.uri().path().to_string());
+        serde_json::json!({ "kind": "Status", "status": "Success" })
+      } else {
+        owned.clone()
+      };
+      async move { Ok::<_, Infallible>(Response::new(hyper::Body::from(body.to_string()))) }
+    });
+    let client = Client::new(service, "default");
+    let recorder = EventRecorder::new(client.clone(), "test".into(), ObjectReference::default());
+    let ctx = ReconcileCtx::new(
+      client,
+      recorder,
+      Arc::default(),
+      Arc::default(),
+      None,
+      Duration::from_secs(10),
+      CancellationToken::new(),
+    );
+
+    let pruned = gc_owned(&ctx, "0a1b", &[secret("a", "keys")])
+      .await
+      .unwrap();
+    assert_eq!(pruned, ["b/keys"]);
+
+    assert_eq!(
+      *deleted.lock().unwrap(),
+      ["/api/v1/namespaces/b/secrets/keys"]
+    );
+  }
+}
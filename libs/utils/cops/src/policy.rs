@@ -0,0 +1,203 @@
+mod expr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{env, fs, io, path::Path};
+use thiserror::Error;
+
+pub use expr::{EvalError, Expr, ParseError};
+
+/// Environment variable naming the file the policies are loaded from.
+pub const POLICY_FILE_ENV: &str = "POLICY_FILE";
+
+#[derive(Debug, Error)]
+pub enum PolicyError {
+  #[error("failed to read policies from '{0}'")]
+  Io(String, #[source] io::Error),
+
+  #[error("invalid policy file")]
+  Yaml(#[from] serde_yaml::Error),
+
+  #[error("invalid expression in policy '{0}'")]
+  Parse(String, #[source] ParseError),
+}
+
+/// A write vetoed by a policy.
+#[derive(Debug, Error)]
+#[error("denied by policy '{policy}': {message}")]
+pub struct PolicyViolation {
+  pub policy: String,
+  pub message: String,
+}
+
+#[derive(Deserialize)]
+struct PolicySpec {
+  name: String,
+  deny: String,
+  #[serde(default)]
+  message: Option<String>,
+}
+
+/// A guardrail on the objects written by controllers. The `deny` expression (a subset of CEL, see
+/// [Expr]) is evaluated with `object` bound to the object about to be written and `owner` to the
+/// resource being reconciled, and the write is vetoed if it evaluates to `true`.
+#[derive(Debug)]
+struct Policy {
+  name: String,
+  source: String,
+  deny: Expr,
+  message: Option<String>,
+}
+
+/// The policies every write of the controllers is checked against. This is experimental, and
+/// only covers the objects controllers generate from their resources (not status updates).
+#[derive(Debug, Default)]
+pub struct PolicySet {
+  policies: Vec<Policy>,
+}
+
+impl PolicySet {
+  /// Loads the policies from the file named by [POLICY_FILE_ENV], if it is set.
+  pub fn from_env() -> Result<Self, PolicyError> {
+    match env::var_os(POLICY_FILE_ENV) {
+      Some(path) => Self::from_file(Path::new(&path)),
+      None => Ok(Self::default()),
+    }
+  }
+
+  pub fn from_file(path: &Path) -> Result<Self, PolicyError> {
+    let yaml =
+      fs::read_to_string(path).map_err(|e| PolicyError::Io(path.display().to_string(), e))?;
+    Self::from_yaml(&yaml)
+  }
+
+  /// Parses a list of policies, like:
+  ///
+  /// ```yaml
+  /// - name: no-kube-system
+  ///   deny: object.metadata.namespace == "kube-system"
+  ///   message: controllers may not write to kube-system
+  /// ```
+  pub fn from_yaml(yaml: &str) -> Result<Self, PolicyError> {
+    let specs = serde_yaml::from_str::<Option<Vec<PolicySpec>>>(yaml)?.unwrap_or_default();
+    let policies = specs
+      .into_iter()
+      .map(|spec| {
+        let deny = Expr::parse(&spec.deny).map_err(|e| PolicyError::Parse(spec.name.clone(), e))?;
+        Ok(Policy {
+          name: spec.name,
+          source: spec.deny,
+          deny,
+          message: spec.message,
+        })
+      })
+      .collect::<Result<_, PolicyError>>()?;
+
+    Ok(Self { policies })
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.policies.is_empty()
+  }
+
+  /// Checks writing `object` on behalf of `owner` against every policy. Policies which fail to
+  /// evaluate (e.g. because they refer to a missing field) veto the write as well, so that a
+  /// broken policy does not silently let everything through.
+  pub fn check<O, T>(&self, owner: &O, object: &T) -> Result<(), PolicyViolation>
+  where
+    O: Serialize,
+    T: Serialize,
+  {
+    if self.policies.is_empty() {
+      return Ok(());
+    }
+
+    // objects which fail to serialize are null, which no policy can evaluate
+    let mut vars = Map::new();
+    vars.insert(
+      "owner".into(),
+      serde_json::to_value(owner).unwrap_or_default(),
+    );
+    vars.insert(
+      "object".into(),
+      serde_json::to_value(object).unwrap_or_default(),
+    );
+
+    for policy in &self.policies {
+      let message = match policy.deny.eval(&vars) {
+        Ok(Value::Bool(false)) => continue,
+        Ok(Value::Bool(true)) => policy
+          .message
+          .clone()
+          .unwrap_or_else(|| format!("matched '{}'", policy.source)),
+        Ok(value) => format!("'{}' evaluated to {value}, not a bool", policy.source),
+        Err(e) => format!("failed to evaluate '{}': {e}", policy.source),
+      };
+
+      return Err(PolicyViolation {
+        policy: policy.name.clone(),
+        message,
+      });
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  const POLICIES: &str = r#"
+- name: no-kube-system
+  deny: object.metadata.namespace == "kube-system"
+  message: controllers may not write to kube-system
+- name: labelled-owner
+  deny: '!has(owner.metadata.labels)'
+"#;
+
+  fn object(namespace: &str) -> Value {
+    json!({ "metadata": { "name": "out", "namespace": namespace } })
+  }
+
+  #[test]
+  fn allows_writes_no_policy_denies() {
+    let policies = PolicySet::from_yaml(POLICIES).unwrap();
+    let owner = json!({ "metadata": { "labels": { "team": "a" } } });
+    assert!(policies.check(&owner, &object("default")).is_ok());
+  }
+
+  #[test]
+  fn denies_with_the_policy_message() {
+    let policies = PolicySet::from_yaml(POLICIES).unwrap();
+    let owner = json!({ "metadata": { "labels": { "team": "a" } } });
+    let violation = policies.check(&owner, &object("kube-system")).unwrap_err();
+    assert_eq!(violation.policy, "no-kube-system");
+    assert_eq!(
+      violation.message,
+      "controllers may not write to kube-system"
+    );
+
+    let violation = policies
+      .check(&json!({ "metadata": {} }), &object("default"))
+      .unwrap_err();
+    assert_eq!(violation.policy, "labelled-owner");
+    assert_eq!(violation.message, "matched '!has(owner.metadata.labels)'");
+  }
+
+  #[test]
+  fn denies_when_evaluation_fails() {
+    let policies =
+      PolicySet::from_yaml("- name: broken\n  deny: object.spec.replicas > 3\n").unwrap();
+    let violation = policies.check(&json!({}), &object("default")).unwrap_err();
+    assert_eq!(violation.policy, "broken");
+    assert!(violation.message.starts_with("failed to evaluate"));
+  }
+
+  #[test]
+  fn rejects_invalid_expressions() {
+    let error = PolicySet::from_yaml("- name: broken\n  deny: object ==\n").unwrap_err();
+    assert!(matches!(error, PolicyError::Parse(name, _) if name == "broken"));
+  }
+}
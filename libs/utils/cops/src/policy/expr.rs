@@ -0,0 +1,588 @@
+//! A small subset of [CEL](https://github.com/google/cel-spec), enough to express conditions on
+//! the objects written by controllers.
+//!
+//! Supported are `null`, bool, number, string and list literals, field access (`a.b`, `a["b"]`),
+//! list indexing, `!`, `-`, `&&`, `||`, the comparison operators, `in`, and the functions
+//! `has(a.b)`, `size(x)`, `x.size()`, `x.startsWith(s)`, `x.endsWith(s)` and `x.contains(s)`.
+
+use serde_json::{Map, Value};
+use std::{cmp::Ordering, fmt};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ParseError {
+  #[error("unexpected character '{0}' at {1}")]
+  UnexpectedChar(char, usize),
+
+  #[error("unterminated string starting at {0}")]
+  UnterminatedString(usize),
+
+  #[error("invalid number '{0}'")]
+  InvalidNumber(String),
+
+  #[error("expected {expected} but found {found}")]
+  Expected { expected: String, found: String },
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum EvalError {
+  #[error("no such key '{0}'")]
+  NoSuchKey(String),
+
+  #[error("undeclared reference to '{0}'")]
+  UndeclaredReference(String),
+
+  #[error("no such overload for '{0}'")]
+  NoSuchOverload(String),
+
+  #[error("index {0} out of range")]
+  IndexOutOfRange(i64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+  Ident(String),
+  Str(String),
+  Num(f64),
+  Op(&'static str),
+  End,
+}
+
+impl fmt::Display for Token {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Token::Ident(name) => write!(f, "'{name}'"),
+      Token::Str(value) => write!(f, "{value:?}"),
+      Token::Num(value) => write!(f, "{value}"),
+      Token::Op(op) => write!(f, "'{op}'"),
+      Token::End => f.write_str("end of expression"),
+    }
+  }
+}
+
+/// Operators, longest first so that `<=` is not lexed as `<` followed by `=`.
+const OPERATORS: &[&str] = &[
+  "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "-", ".", ",", "(", ")", "[", "]",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+  let chars = source.char_indices().collect::<Vec<_>>();
+  let mut tokens = Vec::new();
+  let mut i = 0;
+
+  while i < chars.len() {
+    let (pos, c) = chars[i];
+    if c.is_whitespace() {
+      i += 1;
+    } else if c == '"' || c == '\'' {
+      let mut value = String::new();
+      i += 1;
+      loop {
+        match chars.get(i).map(|(_, c)| *c) {
+          None => return Err(ParseError::UnterminatedString(pos)),
+          Some(end) if end == c => break,
+          Some('\\') => {
+            i += 1;
+            match chars.get(i).map(|(_, c)| *c) {
+              Some('n') => value.push('\n'),
+              Some('t') => value.push('\t'),
+              Some(escaped) => value.push(escaped),
+              None => return Err(ParseError::UnterminatedString(pos)),
+            }
+          }
+          Some(c) => value.push(c),
+        }
+        i += 1;
+      }
+
+      i += 1;
+      tokens.push(Token::Str(value));
+    } else if c.is_ascii_digit() {
+      let start = i;
+      while chars
+        .get(i)
+        .is_some_and(|(_, c)| c.is_ascii_digit() || *c == '.')
+      {
+        i += 1;
+      }
+
+      let text = chars[start..i].iter().map(|(_, c)| c).collect::<String>();
+      let value = text.parse().map_err(|_| ParseError::InvalidNumber(text))?;
+      tokens.push(Token::Num(value));
+    } else if c.is_alphabetic() || c == '_' {
+      let start = i;
+      while chars
+        .get(i)
+        .is_some_and(|(_, c)| c.is_alphanumeric() || *c == '_')
+      {
+        i += 1;
+      }
+
+      tokens.push(Token::Ident(
+        chars[start..i].iter().map(|(_, c)| c).collect(),
+      ));
+    } else {
+      let rest = &source[pos..];
+      let op = OPERATORS
+        .iter()
+        .find(|op| rest.starts_with(**op))
+        .ok_or(ParseError::UnexpectedChar(c, pos))?;
+      i += op.len();
+      tokens.push(Token::Op(op));
+    }
+  }
+
+  tokens.push(Token::End);
+  Ok(tokens)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+  Eq,
+  Ne,
+  Lt,
+  Le,
+  Gt,
+  Ge,
+  In,
+}
+
+/// A parsed expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+  Literal(Value),
+  List(Vec<Expr>),
+  Ident(String),
+  Field(Box<Expr>, String),
+  Index(Box<Expr>, Box<Expr>),
+  Not(Box<Expr>),
+  Neg(Box<Expr>),
+  And(Box<Expr>, Box<Expr>),
+  Or(Box<Expr>, Box<Expr>),
+  Binary(BinaryOp, Box<Expr>, Box<Expr>),
+  Call(Option<Box<Expr>>, String, Vec<Expr>),
+}
+
+struct Parser {
+  tokens: Vec<Token>,
+  pos: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> &Token {
+    &self.tokens[self.pos]
+  }
+
+  fn next(&mut self) -> Token {
+    let token = self.tokens[self.pos].clone();
+    if token != Token::End {
+      self.pos += 1;
+    }
+
+    token
+  }
+
+  fn eat(&mut self, op: &str) -> bool {
+    if matches!(self.peek(), Token::Op(o) if *o == op) {
+      self.pos += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn expect(&mut self, op: &str) -> Result<(), ParseError> {
+    if self.eat(op) {
+      Ok(())
+    } else {
+      Err(ParseError::Expected {
+        expected: format!("'{op}'"),
+        found: self.peek().to_string(),
+      })
+    }
+  }
+
+  fn or(&mut self) -> Result<Expr, ParseError> {
+    let mut expr = self.and()?;
+    while self.eat("||") {
+      expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+    }
+
+    Ok(expr)
+  }
+
+  fn and(&mut self) -> Result<Expr, ParseError> {
+    let mut expr = self.comparison()?;
+    while self.eat("&&") {
+      expr = Expr::And(Box::new(expr), Box::new(self.comparison()?));
+    }
+
+    Ok(expr)
+  }
+
+  fn comparison(&mut self) -> Result<Expr, ParseError> {
+    let left = self.unary()?;
+    let op = match self.peek() {
+      Token::Op("==") => BinaryOp::Eq,
+      Token::Op("!=") => BinaryOp::Ne,
+      Token::Op("<") => BinaryOp::Lt,
+      Token::Op("<=") => BinaryOp::Le,
+      Token::Op(">") => BinaryOp::Gt,
+      Token::Op(">=") => BinaryOp::Ge,
+      Token::Ident(name) if name == "in" => BinaryOp::In,
+      _ => return Ok(left),
+    };
+
+    self.next();
+    let right = self.unary()?;
+    Ok(Expr::Binary(op, Box::new(left), Box::new(right)))
+  }
+
+  fn unary(&mut self) -> Result<Expr, ParseError> {
+    if self.eat("!") {
+      Ok(Expr::Not(Box::new(self.unary()?)))
+    } else if self.eat("-") {
+      Ok(Expr::Neg(Box::new(self.unary()?)))
+    } else {
+      self.postfix()
+    }
+  }
+
+  fn postfix(&mut self) -> Result<Expr, ParseError> {
+    let mut expr = self.primary()?;
+    loop {
+      if self.eat(".") {
+        let name = self.ident()?;
+        if self.eat("(") {
+          let args = self.args(")")?;
+          expr = Expr::Call(Some(Box::new(expr)), name, args);
+        } else {
+          expr = Expr::Field(Box::new(expr), name);
+        }
+      } else if self.eat("[") {
+        let index = self.or()?;
+        self.expect("]")?;
+        expr = Expr::Index(Box::new(expr), Box::new(index));
+      } else {
+        return Ok(expr);
+      }
+    }
+  }
+
+  fn primary(&mut self) -> Result<Expr, ParseError> {
+    match self.next() {
+      Token::Str(value) => Ok(Expr::Literal(Value::String(value))),
+      Token::Num(value) => Ok(Expr::Literal(Value::from(value))),
+      Token::Ident(name) => match name.as_str() {
+        "true" => Ok(Expr::Literal(Value::Bool(true))),
+        "false" => Ok(Expr::Literal(Value::Bool(false))),
+        "null" => Ok(Expr::Literal(Value::Null)),
+        _ if self.eat("(") => Ok(Expr::Call(None, name, self.args(")")?)),
+        _ => Ok(Expr::Ident(name)),
+      },
+      Token::Op("(") => {
+        let expr = self.or()?;
+        self.expect(")")?;
+        Ok(expr)
+      }
+      Token::Op("[") => Ok(Expr::List(self.args("]")?)),
+      found => Err(ParseError::Expected {
+        expected: "an expression".into(),
+        found: found.to_string(),
+      }),
+    }
+  }
+
+  /// Parses comma separated expressions up to the closing `end`, the opening token has already
+  /// been consumed.
+  fn args(&mut self, end: &str) -> Result<Vec<Expr>, ParseError> {
+    let mut args = Vec::new();
+    if self.eat(end) {
+      return Ok(args);
+    }
+
+    loop {
+      args.push(self.or()?);
+      if self.eat(end) {
+        return Ok(args);
+      }
+
+      self.expect(",")?;
+    }
+  }
+
+  fn ident(&mut self) -> Result<String, ParseError> {
+    match self.next() {
+      Token::Ident(name) => Ok(name),
+      found => Err(ParseError::Expected {
+        expected: "a field name".into(),
+        found: found.to_string(),
+      }),
+    }
+  }
+}
+
+impl Expr {
+  pub fn parse(source: &str) -> Result<Self, ParseError> {
+    let mut parser = Parser {
+      tokens: tokenize(source)?,
+      pos: 0,
+    };
+
+    let expr = parser.or()?;
+    match parser.peek() {
+      Token::End => Ok(expr),
+      found => Err(ParseError::Expected {
+        expected: "end of expression".into(),
+        found: found.to_string(),
+      }),
+    }
+  }
+
+  /// Evaluates the expression, resolving identifiers in `vars`.
+  pub fn eval(&self, vars: &Map<String, Value>) -> Result<Value, EvalError> {
+    match self {
+      Expr::Literal(value) => Ok(value.clone()),
+      Expr::List(items) => items
+        .iter()
+        .map(|item| item.eval(vars))
+        .collect::<Result<_, _>>()
+        .map(Value::Array),
+      Expr::Ident(name) => vars
+        .get(name)
+        .cloned()
+        .ok_or_else(|| EvalError::UndeclaredReference(name.clone())),
+      Expr::Field(target, name) => field(target.eval(vars)?, name),
+      Expr::Index(target, index) => match (target.eval(vars)?, index.eval(vars)?) {
+        (target @ Value::Object(_), Value::String(key)) => field(target, &key),
+        (Value::Array(items), Value::Number(n)) => {
+          let index = n.as_f64().unwrap_or(-1.0) as i64;
+          usize::try_from(index)
+            .ok()
+            .and_then(|i| items.get(i).cloned())
+            .ok_or(EvalError::IndexOutOfRange(index))
+        }
+        _ => Err(EvalError::NoSuchOverload("_[_]".into())),
+      },
+      Expr::Not(inner) => match inner.eval(vars)? {
+        Value::Bool(value) => Ok(Value::Bool(!value)),
+        _ => Err(EvalError::NoSuchOverload("!_".into())),
+      },
+      Expr::Neg(inner) => match inner.eval(vars)?.as_f64() {
+        Some(value) => Ok(Value::from(-value)),
+        None => Err(EvalError::NoSuchOverload("-_".into())),
+      },
+      // like in CEL, an error on one side is absorbed if the other side decides the result
+      Expr::And(left, right) => logical(left.eval(vars), || right.eval(vars), false, "_&&_"),
+      Expr::Or(left, right) => logical(left.eval(vars), || right.eval(vars), true, "_||_"),
+      Expr::Binary(op, left, right) => binary(*op, left.eval(vars)?, right.eval(vars)?),
+      Expr::Call(None, name, args) if name == "has" => match args.as_slice() {
+        [field @ Expr::Field(..)] => match field.eval(vars) {
+          Ok(_) => Ok(Value::Bool(true)),
+          Err(EvalError::NoSuchKey(_)) => Ok(Value::Bool(false)),
+          Err(e) => Err(e),
+        },
+        _ => Err(EvalError::NoSuchOverload("has".into())),
+      },
+      Expr::Call(target, name, args) => {
+        let mut values = Vec::with_capacity(args.len() + 1);
+        if let Some(target) = target {
+          values.push(target.eval(vars)?);
+        }
+
+        for arg in args {
+          values.push(arg.eval(vars)?);
+        }
+
+        call(name, &values)
+      }
+    }
+  }
+}
+
+fn field(target: Value, name: &str) -> Result<Value, EvalError> {
+  match target {
+    Value::Object(mut fields) => fields
+      .remove(name)
+      .ok_or_else(|| EvalError::NoSuchKey(name.into())),
+    _ => Err(EvalError::NoSuchKey(name.into())),
+  }
+}
+
+fn logical(
+  left: Result<Value, EvalError>,
+  right: impl FnOnce() -> Result<Value, EvalError>,
+  decisive: bool,
+  name: &str,
+) -> Result<Value, EvalError> {
+  let overload = || EvalError::NoSuchOverload(name.into());
+  let left = match left {
+    Ok(Value::Bool(value)) if value == decisive => return Ok(Value::Bool(value)),
+    Ok(Value::Bool(_)) => Ok(()),
+    Ok(_) => Err(overload()),
+    Err(e) => Err(e),
+  };
+
+  match (left, right()) {
+    (_, Ok(Value::Bool(value))) if value == decisive => Ok(Value::Bool(value)),
+    (Err(e), _) => Err(e),
+    (Ok(()), Ok(Value::Bool(value))) => Ok(Value::Bool(value)),
+    (Ok(()), Ok(_)) => Err(overload()),
+    (Ok(()), Err(e)) => Err(e),
+  }
+}
+
+fn equals(left: &Value, right: &Value) -> bool {
+  match (left, right) {
+    (Value::Number(l), Value::Number(r)) => l.as_f64() == r.as_f64(),
+    (Value::Array(l), Value::Array(r)) => {
+      l.len() == r.len() && l.iter().zip(r).all(|(l, r)| equals(l, r))
+    }
+    _ => left == right,
+  }
+}
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+  match (left, right) {
+    (Value::Number(l), Value::Number(r)) => l.as_f64()?.partial_cmp(&r.as_f64()?),
+    (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+    (Value::Bool(l), Value::Bool(r)) => Some(l.cmp(r)),
+    _ => None,
+  }
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Result<Value, EvalError> {
+  let ordering =
+    |name: &str| compare(&left, &right).ok_or_else(|| EvalError::NoSuchOverload(name.into()));
+
+  let result = match op {
+    BinaryOp::Eq => equals(&left, &right),
+    BinaryOp::Ne => !equals(&left, &right),
+    BinaryOp::Lt => ordering("_<_")?.is_lt(),
+    BinaryOp::Le => ordering("_<=_")?.is_le(),
+    BinaryOp::Gt => ordering("_>_")?.is_gt(),
+    BinaryOp::Ge => ordering("_>=_")?.is_ge(),
+    BinaryOp::In => match &right {
+      Value::Array(items) => items.iter().any(|item| equals(item, &left)),
+      Value::Object(fields) => match &left {
+        Value::String(key) => fields.contains_key(key),
+        _ => return Err(EvalError::NoSuchOverload("@in".into())),
+      },
+      _ => return Err(EvalError::NoSuchOverload("@in".into())),
+    },
+  };
+
+  Ok(Value::Bool(result))
+}
+
+fn call(name: &str, args: &[Value]) -> Result<Value, EvalError> {
+  match (name, args) {
+    ("size", [Value::String(s)]) => Ok(Value::from(s.chars().count())),
+    ("size", [Value::Array(items)]) => Ok(Value::from(items.len())),
+    ("size", [Value::Object(fields)]) => Ok(Value::from(fields.len())),
+    ("startsWith", [Value::String(s), Value::String(prefix)]) => {
+      Ok(Value::Bool(s.starts_with(prefix.as_str())))
+    }
+    ("endsWith", [Value::String(s), Value::String(suffix)]) => {
+      Ok(Value::Bool(s.ends_with(suffix.as_str())))
+    }
+    ("contains", [Value::String(s), Value::String(part)]) => {
+      Ok(Value::Bool(s.contains(part.as_str())))
+    }
+    _ => Err(EvalError::NoSuchOverload(name.into())),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn eval(source: &str) -> Result<Value, EvalError> {
+    let vars = json!({
+      "object": {
+        "kind": "Secret",
+        "metadata": {
+          "name": "token",
+          "namespace": "kube-system",
+          "labels": { "team": "platform" },
+        },
+        "data": { "a": "", "b": "" },
+      },
+      "replicas": 3,
+    });
+
+    Expr::parse(source).unwrap().eval(vars.as_object().unwrap())
+  }
+
+  #[test]
+  fn fields_and_comparisons() {
+    assert_eq!(eval(r#"object.kind == "Secret""#), Ok(json!(true)));
+    assert_eq!(
+      eval(r#"object.metadata.labels["team"] != 'platform'"#),
+      Ok(json!(false))
+    );
+    assert_eq!(eval("replicas >= 3 && replicas < 4.5"), Ok(json!(true)));
+    assert_eq!(eval("-replicas < 0"), Ok(json!(true)));
+  }
+
+  #[test]
+  fn functions() {
+    assert_eq!(eval("size(object.data) == 2"), Ok(json!(true)));
+    assert_eq!(eval("object.metadata.name.size() == 5"), Ok(json!(true)));
+    assert_eq!(
+      eval(r#"object.metadata.namespace.startsWith("kube-")"#),
+      Ok(json!(true))
+    );
+    assert_eq!(
+      eval(r#"object.metadata.name.endsWith("ken")"#),
+      Ok(json!(true))
+    );
+    assert_eq!(
+      eval(r#"object.metadata.name.contains("x")"#),
+      Ok(json!(false))
+    );
+  }
+
+  #[test]
+  fn membership() {
+    assert_eq!(
+      eval(r#"object.kind in ["ConfigMap", "Secret"]"#),
+      Ok(json!(true))
+    );
+    assert_eq!(eval(r#""team" in object.metadata.labels"#), Ok(json!(true)));
+    assert_eq!(
+      eval(r#""owner" in object.metadata.labels"#),
+      Ok(json!(false))
+    );
+  }
+
+  #[test]
+  fn missing_fields() {
+    assert_eq!(
+      eval("object.metadata.annotations.x == 1"),
+      Err(EvalError::NoSuchKey("annotations".into()))
+    );
+    assert_eq!(eval("has(object.metadata.annotations)"), Ok(json!(false)));
+    assert_eq!(eval("has(object.metadata.labels)"), Ok(json!(true)));
+  }
+
+  #[test]
+  fn errors_are_absorbed_by_logical_operators() {
+    assert_eq!(eval("object.missing == 1 || true"), Ok(json!(true)));
+    assert_eq!(eval("false && object.missing == 1"), Ok(json!(false)));
+    assert_eq!(
+      eval("object.missing == 1 || false"),
+      Err(EvalError::NoSuchKey("missing".into()))
+    );
+  }
+
+  #[test]
+  fn parse_errors() {
+    assert!(Expr::parse("object.kind ==").is_err());
+    assert!(Expr::parse("(true").is_err());
+    assert!(Expr::parse("'open").is_err());
+    assert!(Expr::parse("a # b").is_err());
+    assert!(Expr::parse("true true").is_err());
+  }
+}
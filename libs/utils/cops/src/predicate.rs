@@ -0,0 +1,117 @@
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Decides which changes to a resource warrant a reconcile. Every watch event triggers a
+/// reconcile by default, including the ones caused by the controller patching the status of the
+/// resource; predicates let a controller ignore those.
+#[derive(Clone, Debug, Default)]
+pub struct Predicates {
+  generation: bool,
+  annotations: BTreeSet<String>,
+}
+
+/// The parts of a resource the [Predicates] look at. A reconcile is only needed when this
+/// changes between two events.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fingerprint {
+  generation: Option<i64>,
+  deleting: bool,
+  annotations: BTreeMap<String, String>,
+}
+
+impl Predicates {
+  /// Reconcile on every event.
+  pub fn always() -> Self {
+    Self::default()
+  }
+
+  /// Reconcile when `metadata.generation` changes, which the API server only increments for
+  /// changes to the spec (and deletion) of resources with a status subresource.
+  pub fn generation() -> Self {
+    Self {
+      generation: true,
+      ..Self::default()
+    }
+  }
+
+  /// Also reconcile when the value of the annotation `name` changes.
+  pub fn annotation(mut self, name: impl Into<String>) -> Self {
+    self.annotations.insert(name.into());
+    self
+  }
+
+  /// Whether every event should trigger a reconcile.
+  pub fn is_always(&self) -> bool {
+    !self.generation && self.annotations.is_empty()
+  }
+
+  /// The fingerprint of a resource with `meta`, or `None` if every event should trigger a
+  /// reconcile.
+  pub fn fingerprint(&self, meta: &ObjectMeta) -> Option<Fingerprint> {
+    if self.is_always() {
+      return None;
+    }
+
+    let annotations = meta
+      .annotations
+      .iter()
+      .flatten()
+      .filter(|(name, _)| self.annotations.contains(*name))
+      .map(|(name, value)| (name.clone(), value.clone()))
+      .collect();
+
+    Some(Fingerprint {
+      generation: meta.generation.filter(|_| self.generation),
+      deleting: meta.deletion_timestamp.is_some(),
+      annotations,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use k8s_openapi::{apimachinery::pkg::apis::meta::v1::Time, chrono::Utc};
+
+  const REQUEST: &str = "reconcile.fluxcd.io/requestedAt";
+
+  fn meta(generation: i64, annotations: &[(&str, &str)]) -> ObjectMeta {
+    ObjectMeta {
+      generation: Some(generation),
+      annotations: Some(
+        annotations
+          .iter()
+          .map(|(name, value)| (name.to_string(), value.to_string()))
+          .collect(),
+      ),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn always_has_no_fingerprint() {
+    assert!(Predicates::always().fingerprint(&meta(1, &[])).is_none());
+  }
+
+  #[test]
+  fn ignores_unrelated_changes() {
+    let predicates = Predicates::generation().annotation(REQUEST);
+    let before = predicates.fingerprint(&meta(1, &[("other", "a")]));
+    let after = predicates.fingerprint(&meta(1, &[("other", "b")]));
+    assert_eq!(before, after);
+  }
+
+  #[test]
+  fn detects_relevant_changes() {
+    let predicates = Predicates::generation().annotation(REQUEST);
+    let base = predicates.fingerprint(&meta(1, &[(REQUEST, "a")]));
+    let generation = predicates.fingerprint(&meta(2, &[(REQUEST, "a")]));
+    let requested = predicates.fingerprint(&meta(1, &[(REQUEST, "b")]));
+    assert_ne!(base, generation);
+    assert_ne!(base, requested);
+
+    let mut deleting = meta(1, &[(REQUEST, "a")]);
+    deleting.deletion_timestamp = Some(Time(Utc::now()));
+    assert_ne!(base, predicates.fingerprint(&deleting));
+  }
+}
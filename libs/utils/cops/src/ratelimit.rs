@@ -0,0 +1,131 @@
+use std::{
+  collections::{HashMap, HashSet},
+  hash,
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+/// Configures how often a single object may be reconciled, as a token bucket: an object may be
+/// reconciled `burst` times back to back, and then once per `interval`, so that an object which
+/// keeps triggering its own reconciles, like through a storm of status updates, cannot starve the
+/// other objects of the controller.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+  /// Reconciles an object may run back to back, once it was quiet for long enough.
+  pub burst: u32,
+
+  /// How often an object may be reconciled once its burst is used up.
+  pub interval: Duration,
+}
+
+impl Default for RateLimit {
+  fn default() -> Self {
+    Self {
+      burst: 10,
+      interval: Duration::from_secs(1),
+    }
+  }
+}
+
+struct Bucket {
+  tokens: f64,
+  updated: Instant,
+}
+
+/// Keeps a token bucket per object, to delay the reconciles of objects going over their
+/// [RateLimit]. Objects are keyed by `K`, usually an `ObjectRef`.
+pub struct RateLimiter<K> {
+  limit: Option<RateLimit>,
+  buckets: Mutex<HashMap<K, Bucket>>,
+}
+
+impl<K> RateLimiter<K>
+where
+  K: Eq + hash::Hash,
+{
+  /// Creates the limiter of objects to `limit`, or which never delays them if `None`.
+  pub fn new(limit: Option<RateLimit>) -> Self {
+    Self {
+      limit,
+      buckets: Mutex::new(HashMap::new()),
+    }
+  }
+
+  /// Takes a token for a reconcile of `key`. Returns `None` if it may be reconciled right away,
+  /// or else how long to wait until it may.
+  pub fn acquire(&self, key: K) -> Option<Duration> {
+    self.acquire_at(key, Instant::now())
+  }
+
+  fn acquire_at(&self, key: K, now: Instant) -> Option<Duration> {
+    let limit = self.limit?;
+    let burst = limit.burst.max(1) as f64;
+    let interval = limit.interval.as_secs_f64();
+
+    let mut buckets = self.buckets.lock().unwrap();
+    let bucket = buckets.entry(key).or_insert(Bucket {
+      tokens: burst,
+      updated: now,
+    });
+    let refilled = if interval > 0.0 {
+      now.saturating_duration_since(bucket.updated).as_secs_f64() / interval
+    } else {
+      burst
+    };
+    bucket.tokens = (bucket.tokens + refilled).min(burst);
+    bucket.updated = now;
+
+    if bucket.tokens >= 1.0 {
+      bucket.tokens -= 1.0;
+      None
+    } else {
+      Some(Duration::from_secs_f64((1.0 - bucket.tokens) * interval))
+    }
+  }
+
+  /// Forgets the objects which are not `live`, as they were deleted.
+  pub fn retain(&self, live: &HashSet<K>) {
+    let mut buckets = self.buckets.lock().unwrap();
+    buckets.retain(|key, _| live.contains(key));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn throttles_objects_past_their_burst() {
+    let limiter = RateLimiter::new(Some(RateLimit {
+      burst: 2,
+      interval: Duration::from_secs(10),
+    }));
+    let start = Instant::now();
+
+    assert_eq!(limiter.acquire_at("a", start), None);
+    assert_eq!(limiter.acquire_at("a", start), None);
+    assert_eq!(
+      limiter.acquire_at("a", start),
+      Some(Duration::from_secs(10))
+    );
+
+    // other objects are not held up by the hot one
+    assert_eq!(limiter.acquire_at("b", start), None);
+
+    // the bucket refills over time, up to the burst
+    let later = start + Duration::from_secs(15);
+    assert_eq!(limiter.acquire_at("a", later), None);
+    assert_eq!(limiter.acquire_at("a", later), Some(Duration::from_secs(5)));
+    let much_later = later + Duration::from_secs(1000);
+    assert_eq!(limiter.acquire_at("a", much_later), None);
+    assert_eq!(limiter.acquire_at("a", much_later), None);
+    assert!(limiter.acquire_at("a", much_later).is_some());
+  }
+
+  #[test]
+  fn unlimited_never_throttles() {
+    let limiter = RateLimiter::new(None);
+    let now = Instant::now();
+    assert!((0..100).all(|_| limiter.acquire_at("a", now).is_none()));
+  }
+}
@@ -0,0 +1,181 @@
+use k8s_openapi::api::rbac::v1::PolicyRule;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Verbs reading a resource.
+pub const READ: [&str; 3] = ["get", "list", "watch"];
+
+/// Verbs writing a resource, through [ReconcileCtx::apply](crate::context::ReconcileCtx::apply)
+/// and [ReconcileCtx::delete](crate::context::ReconcileCtx::delete).
+pub const WRITE: [&str; 4] = ["create", "update", "patch", "delete"];
+
+/// Whether a permission is granted in the namespaces the app runs in, or across the cluster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Scope {
+  Namespaced,
+  Cluster,
+}
+
+/// The permissions a controller needs on resources of the cluster, on top of those the runtime
+/// needs for the resource it reconciles. The RBAC roles of an app are generated from those of all
+/// of its controllers by the `rbac` command.
+///
+/// ```ignore
+/// Permissions::default()
+///   .namespaced("", "secrets", READ)
+///   .namespaced("", "secrets", WRITE)
+///   .cluster("", "namespaces", READ)
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Permissions {
+  /// The verbs granted by scope, API group (`""` for the core group) and resource.
+  verbs: BTreeMap<(Scope, String, String), BTreeSet<String>>,
+}
+
+impl Permissions {
+  /// Grants `verbs` on `resource` of the API `group`, like `secrets` of the core group `""`, in
+  /// the namespaces the app runs in. Subresources are named after their resource, like
+  /// `deployments/scale`.
+  pub fn namespaced<'a>(
+    self,
+    group: &str,
+    resource: &str,
+    verbs: impl IntoIterator<Item = &'a str>,
+  ) -> Self {
+    self.allow(Scope::Namespaced, group, resource, verbs)
+  }
+
+  /// Grants `verbs` on `resource` of the API `group` across the cluster, for cluster-scoped
+  /// resources like `namespaces`.
+  pub fn cluster<'a>(
+    self,
+    group: &str,
+    resource: &str,
+    verbs: impl IntoIterator<Item = &'a str>,
+  ) -> Self {
+    self.allow(Scope::Cluster, group, resource, verbs)
+  }
+
+  /// Grants `verbs` on `resource` of the API `group` in `scope`.
+  pub fn allow<'a>(
+    mut self,
+    scope: Scope,
+    group: &str,
+    resource: &str,
+    verbs: impl IntoIterator<Item = &'a str>,
+  ) -> Self {
+    self
+      .verbs
+      .entry((scope, group.into(), resource.into()))
+      .or_default()
+      .extend(verbs.into_iter().map(Into::into));
+    self
+  }
+
+  /// Whether all of `verbs` are granted on `resource` of the API `group`, in either scope.
+  pub fn grants<'a>(
+    &self,
+    group: &str,
+    resource: &str,
+    verbs: impl IntoIterator<Item = &'a str>,
+  ) -> bool {
+    let verbs = verbs.into_iter().collect::<Vec<_>>();
+    [Scope::Namespaced, Scope::Cluster]
+      .into_iter()
+      .any(|scope| {
+        let granted = self.verbs.get(&(scope, group.into(), resource.into()));
+        granted.is_some_and(|granted| verbs.iter().all(|verb| granted.contains(*verb)))
+      })
+  }
+
+  /// Adds the permissions of `other` to these.
+  pub fn merge(&mut self, other: &Permissions) {
+    for (key, verbs) in &other.verbs {
+      self
+        .verbs
+        .entry(key.clone())
+        .or_default()
+        .extend(verbs.iter().cloned());
+    }
+  }
+
+  /// The rules granting the permissions of `scope`, with the resources of an API group needing the
+  /// same verbs sharing a rule, in a stable order so that the generated manifests only change
+  /// along with the permissions.
+  pub fn rules(&self, scope: Scope) -> Vec<PolicyRule> {
+    let mut resources = BTreeMap::<(&str, Vec<&str>), Vec<String>>::new();
+    for ((_, group, resource), verbs) in self.verbs.iter().filter(|((s, _, _), _)| *s == scope) {
+      let verbs = verbs.iter().map(String::as_str).collect();
+      resources
+        .entry((group, verbs))
+        .or_default()
+        .push(resource.clone());
+    }
+
+    resources
+      .into_iter()
+      .map(|((group, verbs), resources)| PolicyRule {
+        api_groups: Some(vec![group.into()]),
+        resources: Some(resources),
+        verbs: verbs.into_iter().map(Into::into).collect(),
+        ..Default::default()
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rule(group: &str, resources: &[&str], verbs: &[&str]) -> PolicyRule {
+    PolicyRule {
+      api_groups: Some(vec![group.into()]),
+      resources: Some(resources.iter().map(|r| r.to_string()).collect()),
+      verbs: verbs.iter().map(|v| v.to_string()).collect(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn resources_needing_the_same_verbs_share_rules() {
+    let mut permissions = Permissions::default()
+      .namespaced("", "secrets", READ)
+      .namespaced("", "configmaps", READ)
+      .cluster("", "namespaces", READ);
+    permissions.merge(&Permissions::default().namespaced("", "secrets", ["patch"]));
+
+    assert_eq!(
+      permissions.rules(Scope::Namespaced),
+      [
+        rule("", &["secrets"], &["get", "list", "patch", "watch"]),
+        rule("", &["configmaps"], &["get", "list", "watch"]),
+      ]
+    );
+    assert_eq!(
+      permissions.rules(Scope::Cluster),
+      [rule("", &["namespaces"], &["get", "list", "watch"])]
+    );
+
+    permissions.merge(&Permissions::default().namespaced("", "configmaps", ["patch"]));
+    assert_eq!(
+      permissions.rules(Scope::Namespaced),
+      [rule(
+        "",
+        &["configmaps", "secrets"],
+        &["get", "list", "patch", "watch"]
+      )]
+    );
+  }
+
+  #[test]
+  fn grants_verbs_in_either_scope() {
+    let permissions = Permissions::default()
+      .namespaced("", "secrets", READ)
+      .cluster("", "namespaces", ["get"]);
+
+    assert!(permissions.grants("", "secrets", READ));
+    assert!(permissions.grants("", "namespaces", ["get"]));
+    assert!(!permissions.grants("", "namespaces", READ));
+    assert!(!permissions.grants("", "configmaps", ["get"]));
+  }
+}
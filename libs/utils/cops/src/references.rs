@@ -0,0 +1,127 @@
+use fluxcd_meta::{NamespacedObjectReference, Reason};
+use thiserror::Error;
+
+/// A reference in the spec of a resource which cannot be followed.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum ReferenceError {
+  #[error("{field} requires a name")]
+  NameMissing { field: String },
+
+  #[error("{field} of a cluster-scoped resource requires a namespace")]
+  NamespaceMissing { field: String },
+
+  #[error(
+    "{field} refers to namespace '{namespace}', but cross-namespace references are disabled"
+  )]
+  CrossNamespace { field: String, namespace: String },
+}
+
+/// The reason of the conditions recording `error`, if it is caused by a reference to another
+/// namespace while cross-namespace references are disabled.
+pub fn reference_reason(error: &eyre::Report) -> Option<Reason> {
+  error
+    .chain()
+    .find_map(|cause| cause.downcast_ref::<ReferenceError>())
+    .and_then(|error| match error {
+      ReferenceError::CrossNamespace { .. } => Some(Reason::AccessDenied),
+      _ => None,
+    })
+}
+
+/// The object a reference resolves to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResolvedRef<'a> {
+  pub namespace: &'a str,
+  pub name: &'a str,
+}
+
+/// Resolves `reference`, the `field` of the spec of a resource in `namespace`, or of a
+/// cluster-scoped resource if `None`. References without a namespace are to the namespace of the
+/// resource. References to other namespaces are rejected unless `cross_namespace` is set, so that
+/// tenants cannot use the objects of each other; those of cluster-scoped resources always are
+/// allowed, as only cluster administrators can create them.
+pub fn resolve_reference<'a>(
+  field: &str,
+  reference: &'a NamespacedObjectReference,
+  namespace: Option<&'a str>,
+  cross_namespace: bool,
+) -> Result<ResolvedRef<'a>, ReferenceError> {
+  let name = reference
+    .name()
+    .ok_or_else(|| ReferenceError::NameMissing {
+      field: field.into(),
+    })?;
+
+  let namespace = match (namespace, reference.namespace()) {
+    (Some(own), None) => own,
+    (Some(own), Some(namespace)) if namespace == own || cross_namespace => namespace,
+    (Some(_), Some(namespace)) => {
+      return Err(ReferenceError::CrossNamespace {
+        field: field.into(),
+        namespace: namespace.into(),
+      })
+    }
+    (None, Some(namespace)) => namespace,
+    (None, None) => {
+      return Err(ReferenceError::NamespaceMissing {
+        field: field.into(),
+      })
+    }
+  };
+
+  Ok(ResolvedRef { namespace, name })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn reference(namespace: Option<&str>) -> NamespacedObjectReference {
+    NamespacedObjectReference::new("creds", namespace)
+  }
+
+  #[test]
+  fn references_default_to_the_namespace_of_the_resource() {
+    let resolved = resolve_reference("secretRef", &reference(None), Some("team-a"), false);
+    assert_eq!(
+      resolved,
+      Ok(ResolvedRef {
+        namespace: "team-a",
+        name: "creds"
+      })
+    );
+
+    let cluster = resolve_reference("secretRef", &reference(None), None, true);
+    assert_eq!(
+      cluster.unwrap_err().to_string(),
+      "secretRef of a cluster-scoped resource requires a namespace"
+    );
+  }
+
+  #[test]
+  fn cross_namespace_references_can_be_disabled() {
+    let other = reference(Some("team-b"));
+    let allowed = resolve_reference("secretRef", &other, Some("team-a"), true);
+    assert_eq!(allowed.unwrap().namespace, "team-b");
+
+    let denied = resolve_reference("secretRef", &other, Some("team-a"), false).unwrap_err();
+    assert_eq!(
+      denied.to_string(),
+      "secretRef refers to namespace 'team-b', but cross-namespace references are disabled"
+    );
+    assert_eq!(
+      reference_reason(&eyre::Report::new(denied)),
+      Some(Reason::AccessDenied)
+    );
+
+    let own = resolve_reference(
+      "secretRef",
+      &reference(Some("team-a")),
+      Some("team-a"),
+      false,
+    );
+    assert_eq!(own.unwrap().namespace, "team-a");
+    let cluster = resolve_reference("secretRef", &other, None, false);
+    assert_eq!(cluster.unwrap().namespace, "team-b");
+  }
+}
@@ -0,0 +1,63 @@
+use kube::runtime::controller::ReconcilerAction;
+use std::{
+  collections::hash_map::RandomState,
+  hash::{BuildHasher, Hasher},
+  time::Duration,
+};
+
+/// Requeues the resource after `interval`, usually its `spec.interval`, or not at all if the
+/// interval is negative.
+pub fn requeue_after(interval: fluxcd_meta::Duration) -> ReconcilerAction {
+  ReconcilerAction {
+    requeue_after: interval.to_std(),
+  }
+}
+
+/// Requeues the resource after `interval`, spread by up to `jitter` (a fraction of the interval)
+/// either way, so resources with the same interval which were created together do not all
+/// reconcile at the same time.
+pub fn requeue_after_jittered(interval: fluxcd_meta::Duration, jitter: f64) -> ReconcilerAction {
+  ReconcilerAction {
+    requeue_after: interval.to_std().map(|interval| spread(interval, jitter)),
+  }
+}
+
+/// Spreads `delay` by up to `jitter` (a fraction of the delay, between 0 and 1) either way.
+pub fn spread(delay: Duration, jitter: f64) -> Duration {
+  let jitter = jitter.clamp(0.0, 1.0);
+  if jitter == 0.0 {
+    return delay;
+  }
+
+  // uniform in [-1, 1), from the randomly seeded hasher of the standard library
+  let random = RandomState::new().build_hasher().finish();
+  let unit = (random >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0;
+  delay.mul_f64(1.0 + jitter * unit)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn requeues_after_intervals() {
+    let minute = fluxcd_meta::Duration::MINUTE;
+    assert_eq!(
+      requeue_after(minute).requeue_after,
+      Some(Duration::from_secs(60))
+    );
+    assert_eq!(
+      requeue_after(fluxcd_meta::Duration::MIN).requeue_after,
+      None
+    );
+    assert_eq!(
+      requeue_after_jittered(minute, 0.0).requeue_after,
+      Some(Duration::from_secs(60))
+    );
+
+    for _ in 0..100 {
+      let after = requeue_after_jittered(minute, 0.1).requeue_after.unwrap();
+      assert!(after >= Duration::from_secs(54) && after <= Duration::from_secs(66));
+    }
+  }
+}
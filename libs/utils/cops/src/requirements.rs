@@ -0,0 +1,214 @@
+use k8s_openapi::{
+  apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition,
+  apimachinery::pkg::version::Info,
+};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+/// Annotation recording the minimum Kubernetes version a CRD's controller supports.
+pub const MIN_KUBE_VERSION_ANNOTATION: &str = "fluxcd.yolodev.io/min-kube-version";
+
+/// Annotation recording the API versions a CRD's controller requires the cluster to serve.
+pub const REQUIRED_APIS_ANNOTATION: &str = "fluxcd.yolodev.io/required-apis";
+
+/// A Kubernetes version, as reported by the API server. Only the major and minor components are
+/// tracked, as patch releases do not change which APIs are served.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KubeVersion {
+  pub major: u32,
+  pub minor: u32,
+}
+
+impl KubeVersion {
+  pub const fn new(major: u32, minor: u32) -> Self {
+    Self { major, minor }
+  }
+}
+
+impl fmt::Display for KubeVersion {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}.{}", self.major, self.minor)
+  }
+}
+
+#[derive(Debug, Error)]
+#[error("invalid kubernetes version '{input}'")]
+pub struct KubeVersionParseError {
+  input: String,
+}
+
+impl KubeVersionParseError {
+  pub fn input(&self) -> &str {
+    &self.input
+  }
+}
+
+/// Parses a version component, ignoring any trailing non-digit characters. Managed offerings
+/// commonly report minor versions like "21+".
+fn parse_component(value: &str) -> Option<u32> {
+  let digits = value
+    .find(|c: char| !c.is_ascii_digit())
+    .map_or(value, |end| &value[..end]);
+
+  digits.parse().ok()
+}
+
+impl FromStr for KubeVersion {
+  type Err = KubeVersionParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let err = || KubeVersionParseError { input: s.into() };
+    let value = s.strip_prefix('v').unwrap_or(s);
+    let (major, minor) = value.split_once('.').ok_or_else(err)?;
+    let minor = minor.split_once('.').map_or(minor, |(minor, _)| minor);
+
+    Ok(Self {
+      major: parse_component(major).ok_or_else(err)?,
+      minor: parse_component(minor).ok_or_else(err)?,
+    })
+  }
+}
+
+impl TryFrom<&Info> for KubeVersion {
+  type Error = KubeVersionParseError;
+
+  fn try_from(info: &Info) -> Result<Self, Self::Error> {
+    match (parse_component(&info.major), parse_component(&info.minor)) {
+      (Some(major), Some(minor)) => Ok(Self { major, minor }),
+      _ => info.git_version.parse(),
+    }
+  }
+}
+
+/// Describes what a controller needs from the cluster it is installed in.
+#[derive(Clone, Debug, Default)]
+pub struct Requirements {
+  /// The minimum Kubernetes version the controller supports.
+  pub min_kube_version: Option<KubeVersion>,
+
+  /// API versions the cluster must serve, in `group/version` form (or just `version` for the
+  /// core group), e.g. `coordination.k8s.io/v1`.
+  pub api_versions: Vec<String>,
+}
+
+/// A requirement which is not satisfied by the cluster.
+#[derive(Debug, Error)]
+pub enum UnmetRequirement {
+  #[error("requires kubernetes {required} or newer, but the cluster is running {actual}")]
+  KubeVersion {
+    required: KubeVersion,
+    actual: KubeVersion,
+  },
+
+  #[error("requires the cluster to serve {api_version}")]
+  ApiVersion { api_version: String },
+}
+
+impl Requirements {
+  pub fn min_kube_version(mut self, version: KubeVersion) -> Self {
+    self.min_kube_version = Some(version);
+    self
+  }
+
+  pub fn api_version(mut self, api_version: impl Into<String>) -> Self {
+    self.api_versions.push(api_version.into());
+    self
+  }
+
+  /// Records the requirements as annotations on `crd`, so that they travel with the manifests.
+  /// Without any requirement, `crd` is left as is.
+  pub fn annotate(&self, crd: &mut CustomResourceDefinition) {
+    if self.min_kube_version.is_none() && self.api_versions.is_empty() {
+      return;
+    }
+
+    let annotations = crd
+      .metadata
+      .annotations
+      .get_or_insert_with(Default::default);
+
+    if let Some(version) = &self.min_kube_version {
+      annotations.insert(MIN_KUBE_VERSION_ANNOTATION.into(), version.to_string());
+    }
+
+    if !self.api_versions.is_empty() {
+      annotations.insert(REQUIRED_APIS_ANNOTATION.into(), self.api_versions.join(","));
+    }
+  }
+
+  /// Checks the requirements against a cluster running `version` and serving `api_versions`.
+  pub fn check<'a>(
+    &'a self,
+    version: KubeVersion,
+    api_versions: &'a [String],
+  ) -> impl Iterator<Item = UnmetRequirement> + 'a {
+    let kube_version = self
+      .min_kube_version
+      .filter(|required| *required > version)
+      .map(|required| UnmetRequirement::KubeVersion {
+        required,
+        actual: version,
+      });
+
+    let apis = self
+      .api_versions
+      .iter()
+      .filter(move |required| !api_versions.contains(required))
+      .map(|api_version| UnmetRequirement::ApiVersion {
+        api_version: api_version.clone(),
+      });
+
+    kube_version.into_iter().chain(apis)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_kube_version() {
+    assert_eq!(KubeVersion::new(1, 21), "1.21".parse().unwrap());
+    assert_eq!(KubeVersion::new(1, 21), "v1.21.3".parse().unwrap());
+    assert_eq!(KubeVersion::new(1, 21), "v1.21+".parse().unwrap());
+    assert!("1".parse::<KubeVersion>().is_err());
+    assert!("one.two".parse::<KubeVersion>().is_err());
+  }
+
+  #[test]
+  fn check_requirements() {
+    let requirements = Requirements::default()
+      .min_kube_version(KubeVersion::new(1, 21))
+      .api_version("coordination.k8s.io/v1");
+
+    let served = vec!["v1".to_string(), "coordination.k8s.io/v1".to_string()];
+    assert_eq!(
+      0,
+      requirements.check(KubeVersion::new(1, 22), &served).count()
+    );
+    assert_eq!(
+      1,
+      requirements.check(KubeVersion::new(1, 20), &served).count()
+    );
+    assert_eq!(
+      2,
+      requirements
+        .check(KubeVersion::new(1, 20), &served[..1])
+        .count()
+    );
+  }
+
+  #[test]
+  fn annotate_only_with_requirements() {
+    let mut crd = CustomResourceDefinition::default();
+    Requirements::default().annotate(&mut crd);
+    assert_eq!(crd.metadata.annotations, None);
+
+    Requirements::default()
+      .min_kube_version(KubeVersion::new(1, 21))
+      .annotate(&mut crd);
+    let annotations = crd.metadata.annotations.unwrap();
+    assert_eq!(annotations[MIN_KUBE_VERSION_ANNOTATION], "1.21");
+    assert!(!annotations.contains_key(REQUIRED_APIS_ANNOTATION));
+  }
+}
@@ -0,0 +1,148 @@
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+  CustomResourceDefinition, JSONSchemaProps, JSONSchemaPropsOrArray, JSONSchemaPropsOrBool,
+};
+
+/// Rewrites the schemas of `crd` so they are accepted as structural schemas.
+///
+/// Strict types (`#[serde(deny_unknown_fields)]`, or `#![strict]` api objects) generate schemas
+/// with `additionalProperties: false`, which Kubernetes does not allow next to `properties`. The
+/// flag is dropped from those, as a structural schema already prunes and reports unknown fields.
+pub fn make_structural(crd: &mut CustomResourceDefinition) {
+  for version in &mut crd.spec.versions {
+    if let Some(schema) = version
+      .schema
+      .as_mut()
+      .and_then(|s| s.open_api_v3_schema.as_mut())
+    {
+      visit(schema);
+    }
+  }
+}
+
+/// Adds the versions of the `retired` definitions of `crd` which it no longer has, as neither
+/// served nor used for storage. Objects stored in them can still be read (and rewritten in the
+/// storage version), but clients can no longer use them.
+pub fn retire_versions(
+  crd: &mut CustomResourceDefinition,
+  retired: impl IntoIterator<Item = CustomResourceDefinition>,
+) {
+  for retired in retired {
+    debug_assert_eq!(retired.spec.group, crd.spec.group);
+    for mut version in retired.spec.versions {
+      if crd.spec.versions.iter().any(|v| v.name == version.name) {
+        continue;
+      }
+
+      version.served = false;
+      version.storage = false;
+      crd.spec.versions.push(version);
+    }
+  }
+}
+
+fn visit(schema: &mut JSONSchemaProps) {
+  if schema.properties.is_some()
+    && matches!(
+      schema.additional_properties,
+      Some(JSONSchemaPropsOrBool::Bool(false))
+    )
+  {
+    schema.additional_properties = None;
+  }
+
+  for property in schema.properties.iter_mut().flat_map(|p| p.values_mut()) {
+    visit(property);
+  }
+
+  if let Some(JSONSchemaPropsOrBool::Schema(additional)) = &mut schema.additional_properties {
+    visit(additional);
+  }
+
+  match &mut schema.items {
+    Some(JSONSchemaPropsOrArray::Schema(items)) => visit(items),
+    Some(JSONSchemaPropsOrArray::Schemas(items)) => items.iter_mut().for_each(visit),
+    None => (),
+  }
+
+  let combined = [&mut schema.all_of, &mut schema.any_of, &mut schema.one_of];
+  for schemas in combined.into_iter().flatten() {
+    schemas.iter_mut().for_each(visit);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinitionVersion;
+  use std::collections::BTreeMap;
+
+  #[test]
+  fn removes_additional_properties_next_to_properties() {
+    let strict = JSONSchemaProps {
+      properties: Some(BTreeMap::from([(
+        "name".into(),
+        JSONSchemaProps::default(),
+      )])),
+      additional_properties: Some(JSONSchemaPropsOrBool::Bool(false)),
+      ..Default::default()
+    };
+
+    let map = JSONSchemaProps {
+      additional_properties: Some(JSONSchemaPropsOrBool::Schema(Box::new(strict.clone()))),
+      ..Default::default()
+    };
+
+    let mut schema = JSONSchemaProps {
+      properties: Some(BTreeMap::from([
+        ("spec".into(), strict),
+        ("labels".into(), map),
+      ])),
+      ..Default::default()
+    };
+
+    visit(&mut schema);
+
+    let properties = schema.properties.unwrap();
+    assert_eq!(properties["spec"].additional_properties, None);
+    match &properties["labels"].additional_properties {
+      Some(JSONSchemaPropsOrBool::Schema(value)) => assert_eq!(value.additional_properties, None),
+      other => panic!("unexpected additional properties {other:?}"),
+    }
+  }
+
+  fn crd(versions: &[&str]) -> CustomResourceDefinition {
+    let mut crd = CustomResourceDefinition::default();
+    crd.spec.group = "example.com".into();
+    crd.spec.versions = versions
+      .iter()
+      .map(|name| CustomResourceDefinitionVersion {
+        name: name.to_string(),
+        served: true,
+        storage: true,
+        ..Default::default()
+      })
+      .collect();
+    crd
+  }
+
+  #[test]
+  fn retired_versions_are_neither_served_nor_stored() {
+    let mut current = crd(&["v1"]);
+    retire_versions(&mut current, [crd(&["v1beta1"]), crd(&["v1alpha1", "v1"])]);
+
+    let versions = current
+      .spec
+      .versions
+      .iter()
+      .map(|v| (v.name.as_str(), v.served, v.storage))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      versions,
+      [
+        ("v1", true, true),
+        ("v1beta1", false, false),
+        ("v1alpha1", false, false),
+      ]
+    );
+  }
+}
@@ -0,0 +1,131 @@
+use eyre::WrapErr;
+use k8s_openapi::{api::core::v1::Secret, ByteString};
+use kube::{Api, Client};
+use std::collections::BTreeMap;
+
+pub use fluxcd_utils_kube::secrets::{secret_reason, SecretError};
+
+/// Validates a Secret referenced by the spec of a resource before it is used, so that a missing
+/// Secret or key is reported as such rather than failing deep inside a fetch.
+///
+/// ```ignore
+/// let data = SecretCheck::new(&namespace, name)
+///   .require(ACCESS_KEY_KEY)
+///   .require(SECRET_KEY_KEY)
+///   .fetch(ctx.client())
+///   .await?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct SecretCheck {
+  namespace: String,
+  name: String,
+  required: Vec<Vec<String>>,
+}
+
+impl SecretCheck {
+  /// Checks the Secret `name` in `namespace`, which only has to exist unless keys are required.
+  pub fn new(namespace: &str, name: &str) -> Self {
+    Self {
+      namespace: namespace.into(),
+      name: name.into(),
+      required: Vec::new(),
+    }
+  }
+
+  /// Requires the Secret to hold `key`.
+  pub fn require(self, key: &str) -> Self {
+    self.require_any([key])
+  }
+
+  /// Requires the Secret to hold at least one of `keys`.
+  pub fn require_any<'a>(mut self, keys: impl IntoIterator<Item = &'a str>) -> Self {
+    self
+      .required
+      .push(keys.into_iter().map(Into::into).collect());
+    self
+  }
+
+  /// Checks that `data`, the content of the Secret, holds the required keys.
+  pub fn validate(&self, data: &BTreeMap<String, ByteString>) -> Result<(), SecretError> {
+    let missing = self
+      .required
+      .iter()
+      .find(|keys| !keys.iter().any(|key| data.contains_key(key)));
+
+    match missing {
+      Some(keys) => Err(SecretError::KeyMissing {
+        namespace: self.namespace.clone(),
+        name: self.name.clone(),
+        keys: keys.clone(),
+      }),
+      None => Ok(()),
+    }
+  }
+
+  /// Gets the Secret, and returns its content once it holds the required keys. Fails with a
+  /// [SecretError] if it does not exist or misses a key.
+  pub async fn fetch(&self, client: &Client) -> eyre::Result<BTreeMap<String, ByteString>> {
+    let (namespace, name) = (&self.namespace, &self.name);
+    let api = Api::<Secret>::namespaced(client.clone(), namespace);
+    let secret = match api.get(name).await {
+      Err(kube::Error::Api(e)) if e.code == 404 => {
+        let (namespace, name) = (namespace.clone(), name.clone());
+        return Err(SecretError::SecretMissing { namespace, name }.into());
+      }
+      result => result.wrap_err_with(|| format!("failed to get secret '{namespace}/{name}'"))?,
+    };
+
+    let data = secret.data.unwrap_or_default();
+    self.validate(&data)?;
+    Ok(data)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use fluxcd_meta::Reason;
+
+  fn data(keys: &[&str]) -> BTreeMap<String, ByteString> {
+    keys
+      .iter()
+      .map(|key| (key.to_string(), ByteString(b"value".to_vec())))
+      .collect()
+  }
+
+  #[test]
+  fn requires_keys() {
+    let check = SecretCheck::new("flux-system", "creds")
+      .require("accesskey")
+      .require("secretkey");
+    assert_eq!(check.validate(&data(&["accesskey", "secretkey"])), Ok(()));
+
+    let error = check.validate(&data(&["accesskey"])).unwrap_err();
+    assert_eq!(error.reason(), Reason::KeyMissing);
+    assert_eq!(
+      error.to_string(),
+      "secret 'flux-system/creds' is missing 'secretkey'"
+    );
+  }
+
+  #[test]
+  fn requires_any_of_alternative_keys() {
+    let check = SecretCheck::new("flux-system", "azure").require_any(["accountKey", "sasKey"]);
+    assert_eq!(check.validate(&data(&["sasKey"])), Ok(()));
+    assert_eq!(
+      check.validate(&data(&[])).unwrap_err().to_string(),
+      "secret 'flux-system/azure' is missing either 'accountKey' or 'sasKey'"
+    );
+  }
+
+  #[test]
+  fn finds_the_reason_in_the_chain() {
+    let error = eyre::Report::new(SecretError::SecretMissing {
+      namespace: "default".into(),
+      name: "token".into(),
+    })
+    .wrap_err("failed to fetch");
+    assert_eq!(secret_reason(&error), Some(Reason::SecretMissing));
+    assert_eq!(secret_reason(&eyre::eyre!("timed out")), None);
+  }
+}
@@ -0,0 +1,74 @@
+use futures::{Future, StreamExt};
+use kube::{
+  api::ListParams,
+  runtime::{
+    reflector,
+    reflector::{store::Writer, ObjectRef, Store},
+    watcher,
+  },
+  Api, Client, Resource,
+};
+use serde::de::DeserializeOwned;
+use std::{fmt::Debug, hash::Hash};
+use tracing::warn;
+
+/// Cache of the objects of kind `K`, kept up to date by a watch. Objects which are not in it, as
+/// the watch has not listed them yet or they are outside of the watched namespace, are read from
+/// the API server instead.
+#[derive(Clone)]
+pub(crate) struct Cache<K>
+where
+  K: Resource + Clone + 'static,
+  K::DynamicType: Eq + Hash + Clone,
+{
+  store: Store<K>,
+  client: Client,
+}
+
+impl<K> Cache<K>
+where
+  K: Resource + Clone + DeserializeOwned + Debug + Send + Sync + 'static,
+  K::DynamicType: Default + Eq + Hash + Clone,
+{
+  /// Creates the cache of the objects in `namespace`, or in all namespaces, and the future which
+  /// keeps it up to date for as long as it is polled.
+  pub(crate) fn new(client: Client, namespace: Option<&str>) -> (Self, impl Future<Output = ()>) {
+    let writer = Writer::default();
+    let store = writer.as_reader();
+    let api = match namespace {
+      Some(namespace) => Api::<K>::namespaced(client.clone(), namespace),
+      None => Api::<K>::all(client.clone()),
+    };
+    let kind = K::kind(&K::DynamicType::default()).into_owned();
+    let watch = reflector(writer, watcher(api, ListParams::default())).for_each(move |event| {
+      if let Err(error) = event {
+        warn!(%error, %kind, "watch failed");
+      }
+      futures::future::ready(())
+    });
+
+    (Self { store, client }, watch)
+  }
+
+  /// Creates the cache which is never filled, so that every object is read from the API server.
+  pub(crate) fn uncached(client: Client) -> Self {
+    let store = Writer::default().as_reader();
+    Self { store, client }
+  }
+
+  /// The object `name` in `namespace`, `None` if it does not exist.
+  pub(crate) async fn get(&self, namespace: &str, name: &str) -> Result<Option<K>, kube::Error> {
+    if let Some(object) = self.store.get(&ObjectRef::new(name).within(namespace)) {
+      return Ok(Some(object));
+    }
+
+    match Api::<K>::namespaced(self.client.clone(), namespace)
+      .get(name)
+      .await
+    {
+      Ok(object) => Ok(Some(object)),
+      Err(kube::Error::Api(e)) if e.code == 404 => Ok(None),
+      Err(e) => Err(e),
+    }
+  }
+}
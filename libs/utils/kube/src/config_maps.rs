@@ -0,0 +1,86 @@
+use crate::cache::Cache;
+use eyre::WrapErr;
+use fluxcd_meta::LocalObjectReference;
+use futures::Future;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::Client;
+use std::collections::BTreeMap;
+
+/// Resolves the ConfigMaps referenced by the specs of resources from a cache kept up to date by a
+/// watch, like [SecretResolver](crate::SecretResolver) does for Secrets.
+#[derive(Clone)]
+pub struct ConfigMapResolver {
+  cache: Cache<ConfigMap>,
+}
+
+impl ConfigMapResolver {
+  /// Creates the resolver of the ConfigMaps of all namespaces, and the future which keeps its
+  /// cache up to date for as long as it is polled.
+  pub fn new(client: Client) -> (Self, impl Future<Output = ()>) {
+    let (cache, watch) = Cache::new(client, None);
+    (Self { cache }, watch)
+  }
+
+  /// Creates the resolver which only caches the ConfigMaps of `namespace`. The ConfigMaps of other
+  /// namespaces are read from the API server.
+  pub fn namespaced(client: Client, namespace: &str) -> (Self, impl Future<Output = ()>) {
+    let (cache, watch) = Cache::new(client, Some(namespace));
+    (Self { cache }, watch)
+  }
+
+  /// Creates the resolver which reads every ConfigMap from the API server, for apps which do not
+  /// watch ConfigMaps.
+  pub fn uncached(client: Client) -> Self {
+    Self {
+      cache: Cache::uncached(client),
+    }
+  }
+
+  /// Resolves `reference`, from the spec of a resource in `namespace`.
+  pub async fn resolve(
+    &self,
+    namespace: &str,
+    reference: &LocalObjectReference,
+  ) -> eyre::Result<ResolvedConfigMap> {
+    let name = reference
+      .name()
+      .ok_or_else(|| eyre::eyre!("config map reference without a name"))?;
+    let config_map = self
+      .cache
+      .get(namespace, name)
+      .await
+      .wrap_err_with(|| format!("failed to get config map '{namespace}/{name}'"))?
+      .ok_or_else(|| eyre::eyre!("config map '{namespace}/{name}' does not exist"))?;
+
+    Ok(ResolvedConfigMap {
+      namespace: namespace.into(),
+      name: name.into(),
+      data: config_map.data.unwrap_or_default(),
+    })
+  }
+}
+
+/// The content of a resolved ConfigMap.
+#[derive(Clone, Debug)]
+pub struct ResolvedConfigMap {
+  namespace: String,
+  name: String,
+  data: BTreeMap<String, String>,
+}
+
+impl ResolvedConfigMap {
+  /// The content of the ConfigMap, by key.
+  pub fn data(&self) -> &BTreeMap<String, String> {
+    &self.data
+  }
+
+  /// The value of `key`.
+  pub fn value(&self, key: &str) -> eyre::Result<&str> {
+    let (namespace, name) = (&self.namespace, &self.name);
+    self
+      .data
+      .get(key)
+      .map(String::as_str)
+      .ok_or_else(|| eyre::eyre!("config map '{namespace}/{name}' is missing '{key}'"))
+  }
+}
@@ -0,0 +1,6 @@
+mod cache;
+pub mod config_maps;
+pub mod secrets;
+
+pub use config_maps::{ConfigMapResolver, ResolvedConfigMap};
+pub use secrets::{secret_reason, BasicAuth, ResolvedSecret, SecretError, SecretResolver, SshKey};
@@ -0,0 +1,302 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use proc_macro_crate::{crate_name, FoundCrate};
+use quote::{format_ident, quote, ToTokens};
+use std::collections::HashSet;
+use syn::{
+  parse::{Parse, ParseStream},
+  parse_macro_input, parse_quote,
+  punctuated::Punctuated,
+  spanned::Spanned,
+  Expr, GenericArgument, Ident, ImplItem, ItemImpl, PathArguments, Token, Type,
+};
+
+/// Fills in the parts of an `impl Controller<R> for C` block which are the same for most
+/// controllers, unless the block defines them itself:
+///
+/// - `metrics`, returning the `metrics` field of the controller (or the field named by the
+///   `metrics` argument).
+/// - `backoff`, if `backoff` is an expression evaluating to the `Backoff` of the controller,
+///   rather than the default one.
+/// - `suspended`, if `suspend` names the flag of the resource, like `spec.suspend`.
+/// - `reconcile_interval`, if `interval` names the `fluxcd_meta::Duration` of the resource, like
+///   `spec.interval`. Suspended resources are not scheduled.
+///
+/// The attribute goes above `#[async_trait]`:
+///
+/// ```ignore
+/// #[flux_controller(suspend = spec.suspend, interval = spec.interval, backoff = BACKOFF)]
+/// #[async_trait]
+/// impl Controller<DnsRecords> for DnsRecordsController {
+///   async fn reconcile(/* ... */) -> Result<ReconcilerAction> {
+///     // ...
+///   }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn flux_controller(args: TokenStream, item: TokenStream) -> TokenStream {
+  let args = parse_macro_input!(args as Args);
+  let item = parse_macro_input!(item as ItemImpl);
+  expand(args, item)
+    .unwrap_or_else(syn::Error::into_compile_error)
+    .into()
+}
+
+#[derive(Default)]
+struct Args {
+  metrics: Option<Ident>,
+  backoff: Option<Expr>,
+  suspend: Option<Expr>,
+  interval: Option<Expr>,
+}
+
+struct Arg {
+  name: Ident,
+  value: Expr,
+}
+
+impl Parse for Arg {
+  fn parse(input: ParseStream) -> syn::Result<Self> {
+    let name = input.parse()?;
+    input.parse::<Token![=]>()?;
+    let value = input.parse()?;
+    Ok(Self { name, value })
+  }
+}
+
+impl Parse for Args {
+  fn parse(input: ParseStream) -> syn::Result<Self> {
+    let mut args = Args::default();
+    for arg in Punctuated::<Arg, Token![,]>::parse_terminated(input)? {
+      match &*arg.name.to_string() {
+        "metrics" => match &arg.value {
+          Expr::Path(path) if path.path.get_ident().is_some() => {
+            args.metrics = path.path.get_ident().cloned();
+          }
+          value => return Err(syn::Error::new(value.span(), "expected a field name")),
+        },
+        "backoff" => args.backoff = Some(arg.value),
+        "suspend" => args.suspend = Some(field_path(arg.value)?),
+        "interval" => args.interval = Some(field_path(arg.value)?),
+        _ => {
+          return Err(syn::Error::new(
+            arg.name.span(),
+            "expected one of `metrics`, `backoff`, `suspend`, or `interval`",
+          ))
+        }
+      }
+    }
+
+    Ok(args)
+  }
+}
+
+/// Checks that `expr` is a path to a field of the resource, like `spec.suspend`.
+fn field_path(expr: Expr) -> syn::Result<Expr> {
+  fn is_field_path(expr: &Expr) -> bool {
+    match expr {
+      Expr::Field(field) => is_field_path(&field.base),
+      Expr::Path(path) => path.path.get_ident().is_some(),
+      _ => false,
+    }
+  }
+
+  if !is_field_path(&expr) {
+    return Err(syn::Error::new(
+      expr.span(),
+      "expected a field of the resource, like `spec.suspend`",
+    ));
+  }
+
+  Ok(expr)
+}
+
+/// The resource `R` of `impl Controller<R> for C`.
+fn resource_type(item: &ItemImpl) -> syn::Result<Type> {
+  let resource = item
+    .trait_
+    .as_ref()
+    .and_then(|(_, path, _)| path.segments.last())
+    .and_then(|segment| match &segment.arguments {
+      PathArguments::AngleBracketed(args) => args.args.first(),
+      _ => None,
+    });
+
+  match resource {
+    Some(GenericArgument::Type(resource)) => Ok(resource.clone()),
+    _ => Err(syn::Error::new(
+      item.span(),
+      "expected an implementation of `Controller<Resource>`",
+    )),
+  }
+}
+
+/// The path of `fluxcd-utils-cap` in the crate of the controller, which depends on it either
+/// directly or through `fluxcd-prelude`.
+fn cap_path() -> TokenStream2 {
+  let path = |name: &str| {
+    let name = format_ident!("{}", name);
+    quote!(::#name)
+  };
+
+  match crate_name("fluxcd-utils-cap") {
+    Ok(FoundCrate::Itself) => quote!(crate),
+    Ok(FoundCrate::Name(name)) => path(&name),
+    Err(_) => match crate_name("fluxcd-prelude") {
+      Ok(FoundCrate::Name(name)) => {
+        let prelude = path(&name);
+        quote!(#prelude::cap)
+      }
+      _ => path("fluxcd_utils_cap"),
+    },
+  }
+}
+
+fn expand(args: Args, mut item: ItemImpl) -> syn::Result<TokenStream2> {
+  let resource = resource_type(&item)?;
+  let cap = cap_path();
+  let defined = item
+    .items
+    .iter()
+    .filter_map(|item| match item {
+      ImplItem::Method(method) => Some(method.sig.ident.to_string()),
+      _ => None,
+    })
+    .collect::<HashSet<_>>();
+
+  let mut generated = Vec::<ImplItem>::new();
+  if !defined.contains("metrics") {
+    let field = args.metrics.unwrap_or_else(|| format_ident!("metrics"));
+    generated.push(parse_quote! {
+      fn metrics(&self) -> &#cap::metrics::Recorder {
+        &self.#field
+      }
+    });
+  }
+
+  if let Some(backoff) = args.backoff.filter(|_| !defined.contains("backoff")) {
+    generated.push(parse_quote! {
+      fn backoff() -> #cap::backoff::Backoff {
+        #backoff
+      }
+    });
+  }
+
+  if let Some(suspend) = args
+    .suspend
+    .as_ref()
+    .filter(|_| !defined.contains("suspended"))
+  {
+    generated.push(parse_quote! {
+      fn suspended(resource: &#resource) -> bool {
+        resource.#suspend
+      }
+    });
+  }
+
+  if let Some(interval) = args
+    .interval
+    .as_ref()
+    .filter(|_| !defined.contains("reconcile_interval"))
+  {
+    let suspended = args.suspend.as_ref().map(|suspend| {
+      quote! {
+        if resource.#suspend {
+          return ::std::option::Option::None;
+        }
+      }
+    });
+    generated.push(parse_quote! {
+      fn reconcile_interval(resource: &#resource) -> ::std::option::Option<::std::time::Duration> {
+        #suspended
+        resource.#interval.to_std()
+      }
+    });
+  }
+
+  item.items.extend(generated);
+  Ok(item.into_token_stream())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn methods(args: TokenStream2, item: TokenStream2) -> Vec<(String, String)> {
+    let args = syn::parse2::<Args>(args).unwrap();
+    let item = syn::parse2::<ItemImpl>(item).unwrap();
+    let expanded = syn::parse2::<ItemImpl>(expand(args, item).unwrap()).unwrap();
+    expanded
+      .items
+      .iter()
+      .filter_map(|item| match item {
+        ImplItem::Method(method) => Some((
+          method.sig.ident.to_string(),
+          method.block.to_token_stream().to_string(),
+        )),
+        _ => None,
+      })
+      .collect()
+  }
+
+  #[test]
+  fn generates_the_missing_methods() {
+    let methods = methods(
+      quote!(
+        suspend = spec.suspend,
+        interval = spec.interval,
+        backoff = Backoff::fixed(RETRY_INTERVAL)
+      ),
+      quote! {
+        impl Controller<DnsRecords> for DnsRecordsController {
+          async fn reconcile(self: Arc<Self>, resource: Arc<DnsRecords>, ctx: ReconcileCtx) {}
+        }
+      },
+    );
+
+    let names = methods.iter().map(|(name, _)| &**name).collect::<Vec<_>>();
+    assert_eq!(
+      names,
+      [
+        "reconcile",
+        "metrics",
+        "backoff",
+        "suspended",
+        "reconcile_interval"
+      ]
+    );
+    assert!(methods[2].1.contains("Backoff :: fixed (RETRY_INTERVAL)"));
+    assert!(methods[3].1.contains("resource . spec . suspend"));
+    assert!(methods[4]
+      .1
+      .contains("resource . spec . interval . to_std ()"));
+  }
+
+  #[test]
+  fn keeps_methods_defined_by_the_controller() {
+    let methods = methods(
+      quote!(metrics = recorder, suspend = spec.suspend),
+      quote! {
+        impl Controller<HttpEndpoint> for HttpEndpointController {
+          fn suspended(resource: &HttpEndpoint) -> bool { false }
+          fn error_policy(self: Arc<Self>, error: &Report, retry: Duration) -> ReconcilerAction { todo!() }
+        }
+      },
+    );
+
+    let names = methods.iter().map(|(name, _)| &**name).collect::<Vec<_>>();
+    assert_eq!(names, ["suspended", "error_policy", "metrics"]);
+    assert!(methods[2].1.contains("self . recorder"));
+  }
+
+  #[test]
+  fn rejects_expressions_which_are_not_fields() {
+    let error = syn::parse2::<Args>(quote!(suspend = spec.suspend()))
+      .err()
+      .unwrap();
+    assert_eq!(
+      error.to_string(),
+      "expected a field of the resource, like `spec.suspend`"
+    );
+  }
+}
@@ -38,6 +38,12 @@ macro_rules! str_enum {
       )+
     }
 
+    impl $name {
+      /// Every value, in the order they are declared in.
+      #[allow(dead_code)]
+      $vis const VALUES: &'static [&'static str] = &[$($var_val,)+];
+    }
+
     impl ::core::fmt::Display for $name {
       fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
         match self {
@@ -106,9 +112,21 @@ macro_rules! str_enum {
   };
 }
 
+/// Defines an API object where every field is optional, and serialized under its API name.
+///
+/// Unknown fields are ignored when deserializing, unless the object opts into strict mode by
+/// starting with `#![strict]`. Strict objects reject unknown fields, and must derive `JsonSchema`
+/// so their schema can be generated without additional properties.
 #[macro_export]
 macro_rules! api_object {
   (
+    #![strict]
+    $($rest:tt)*
+  ) => {
+    $crate::api_object!(@impl [all()] $($rest)*);
+  };
+  (
+    @impl [$strict:meta]
     $(#[$m:meta])*
     $vis:vis struct $name:ident {
       $(
@@ -118,9 +136,11 @@ macro_rules! api_object {
     }
   ) => {
     $(#[$m])*
+    #[cfg_attr($strict, schemars(deny_unknown_fields))]
     $vis struct $name {
       $(
         $(#[$fld_m])*
+        #[cfg_attr($strict, schemars(rename = $fld_api_name))]
         $fld_name: Option<$fld_ty>,
       )+
     }
@@ -157,6 +177,9 @@ macro_rules! api_object {
                 {
                   Ok(match v {
                     $($fld_api_name => Field::[<Key_ $fld_name>],)*
+                    _ if cfg!($strict) => {
+                      return Err(E::unknown_field(v, &[$($fld_api_name,)*]));
+                    }
                     _ => Field::Other,
                   })
                 }
@@ -233,4 +256,53 @@ macro_rules! api_object {
       }
     }
   };
+  (
+    $($rest:tt)*
+  ) => {
+    $crate::api_object!(@impl [any()] $($rest)*);
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use schemars::{schema_for, JsonSchema};
+
+  api_object! {
+    #[derive(Debug, PartialEq, JsonSchema)]
+    struct Lenient {
+      last_seen: String = "lastSeen",
+    }
+  }
+
+  api_object! {
+    #![strict]
+    #[derive(Debug, PartialEq, JsonSchema)]
+    struct Strict {
+      last_seen: String = "lastSeen",
+    }
+  }
+
+  #[test]
+  fn lenient_ignores_unknown_fields() {
+    let value: Lenient = serde_json::from_str(r#"{"lastSeen":"now","lastSen":"typo"}"#).unwrap();
+    assert_eq!(
+      value,
+      Lenient {
+        last_seen: Some("now".into())
+      }
+    );
+  }
+
+  #[test]
+  fn strict_rejects_unknown_fields() {
+    let err = serde_json::from_str::<Strict>(r#"{"lastSeen":"now","lastSen":"typo"}"#).unwrap_err();
+    assert!(err.to_string().contains("unknown field `lastSen`"), "{err}");
+  }
+
+  #[test]
+  fn strict_schema_denies_additional_properties() {
+    let schema = serde_json::to_value(schema_for!(Strict)).unwrap();
+    assert_eq!(schema["additionalProperties"], false);
+    assert!(schema["properties"].get("lastSeen").is_some());
+  }
 }
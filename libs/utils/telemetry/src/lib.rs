@@ -1,23 +1,244 @@
+use opentelemetry::{
+  sdk::{
+    propagation::TraceContextPropagator,
+    trace::{self, Sampler},
+    Resource,
+  },
+  trace::{SpanContext, TraceContextExt},
+  KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use std::{
+  collections::HashMap,
+  str::FromStr,
+  sync::atomic::{AtomicU64, Ordering},
+};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Registry};
 use tracing_tree::HierarchicalLayer;
 
-pub fn setup() -> eyre::Result<()> {
-  let tracer = opentelemetry_jaeger::new_pipeline().install_batch(opentelemetry::runtime::Tokio)?;
+/// Where the spans are exported to, and which of them.
+#[derive(Clone, Debug)]
+pub struct TracingConfig {
+  /// OTLP/HTTP endpoint the spans are exported to, like `http://otel-collector:4318/v1/traces`.
+  /// They are sent to the local Jaeger agent otherwise.
+  pub otlp_endpoint: Option<String>,
+
+  /// Headers the spans are exported with, like the credentials of the collector.
+  pub otlp_headers: HashMap<String, String>,
+
+  /// Fraction of the traces started by the app which are sampled, from 0 to 1. Traces continued
+  /// from a sampled parent are always sampled.
+  pub sample_ratio: f64,
+}
+
+impl Default for TracingConfig {
+  fn default() -> Self {
+    Self {
+      otlp_endpoint: None,
+      otlp_headers: HashMap::new(),
+      sample_ratio: 1.0,
+    }
+  }
+}
+
+/// How the logs are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+  /// Human readable, with the spans the events happened in as a tree.
+  #[default]
+  Console,
+
+  /// A JSON object per line, with the fields of the spans the event happened in, like the kind,
+  /// namespace and name of the object being reconciled, for log aggregators.
+  Json,
+}
+
+impl FromStr for LogFormat {
+  type Err = eyre::Report;
+
+  fn from_str(format: &str) -> eyre::Result<Self> {
+    match format {
+      "console" => Ok(Self::Console),
+      "json" => Ok(Self::Json),
+      _ => eyre::bail!("unknown log format '{format}', expected 'console' or 'json'"),
+    }
+  }
+}
+
+/// How the logs are written, and which of them.
+#[derive(Clone, Debug, Default)]
+pub struct LogConfig {
+  pub format: LogFormat,
+
+  /// Directives filtering the logs, like `info` or `info,kube=warn`. Taken from `RUST_LOG` if not
+  /// given.
+  pub level: Option<String>,
+}
+
+/// Parses the `key=value` pairs of `headers`, separated by commas, like in
+/// `OTEL_EXPORTER_OTLP_HEADERS`.
+pub fn parse_headers(headers: &str) -> eyre::Result<HashMap<String, String>> {
+  headers
+    .split(',')
+    .map(str::trim)
+    .filter(|header| !header.is_empty())
+    .map(|header| match header.split_once('=') {
+      Some((key, value)) if !key.trim().is_empty() => {
+        Ok((key.trim().to_string(), value.trim().to_string()))
+      }
+      _ => eyre::bail!("invalid header '{header}', expected 'key=value'"),
+    })
+    .collect()
+}
+
+/// Sets up logging as configured by `logs`, and the export of the spans of the app named
+/// `service` as configured by `config`. The W3C trace context is used to continue traces across
+/// the requests of the app.
+pub fn setup(service: &str, config: &TracingConfig, logs: &LogConfig) -> eyre::Result<()> {
+  if !(0.0..=1.0).contains(&config.sample_ratio) {
+    eyre::bail!("the trace sample ratio must be between 0 and 1");
+  }
+
+  let trace_config = trace::config()
+    .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+      config.sample_ratio,
+    ))))
+    .with_resource(Resource::new([KeyValue::new(
+      "service.name",
+      service.to_string(),
+    )]));
+  let tracer = match &config.otlp_endpoint {
+    Some(endpoint) => {
+      let exporter = opentelemetry_otlp::new_exporter()
+        .http()
+        .with_endpoint(endpoint)
+        .with_headers(config.otlp_headers.clone());
+      opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(exporter)
+        .with_trace_config(trace_config)
+        .install_batch(opentelemetry::runtime::Tokio)?
+    }
+    None => opentelemetry_jaeger::new_pipeline()
+      .with_service_name(service)
+      .with_trace_config(trace_config)
+      .install_batch(opentelemetry::runtime::Tokio)?,
+  };
+  opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
   let telemetry = tracing_opentelemetry::layer().with_tracer(tracer);
 
-  Registry::default()
-    .with(EnvFilter::from_default_env())
-    .with(
-      HierarchicalLayer::new(2)
+  let filter = match &logs.level {
+    Some(level) => EnvFilter::try_new(level)?,
+    None => EnvFilter::from_default_env(),
+  };
+  let (console, json) = match logs.format {
+    LogFormat::Console => {
+      let console = HierarchicalLayer::new(2)
         .with_targets(true)
-        .with_bracketed_fields(true),
-    )
+        .with_bracketed_fields(true);
+      (Some(console), None)
+    }
+    LogFormat::Json => {
+      let json = tracing_subscriber::fmt::layer()
+        .json()
+        .flatten_event(true)
+        .with_span_list(true);
+      (None, Some(json))
+    }
+  };
+
+  Registry::default()
+    .with(filter)
+    .with(console)
+    .with(json)
     .with(telemetry)
     .init();
 
   Ok(())
 }
 
+/// Exports any spans which have not been exported yet.
+pub fn flush() {
+  opentelemetry::global::force_flush_tracer_provider()
+}
+
 pub fn teardown() {
   opentelemetry::global::shutdown_tracer_provider()
 }
+
+/// A reference to an exported span, which later spans can link to (e.g. to follow consecutive
+/// reconciles of the same object across traces).
+#[derive(Clone, Debug)]
+pub struct SpanLink(SpanContext);
+
+impl SpanLink {
+  /// A link to `span`, if it is exported.
+  pub fn to(span: &Span) -> Option<Self> {
+    let context = span.context().span().span_context().clone();
+    context.is_valid().then_some(Self(context))
+  }
+
+  /// Adds a link to the linked span to `span`.
+  pub fn add_to(&self, span: &Span) {
+    span.add_link(self.0.clone());
+  }
+
+  pub fn span_id(&self) -> String {
+    self.0.span_id().to_string()
+  }
+}
+
+/// The headers carrying the trace context of `span` (`traceparent`, and `tracestate` if any), for
+/// the services called by the app to continue its trace. Empty if the span is not exported.
+pub fn trace_context(span: &Span) -> HashMap<String, String> {
+  let context = span.context();
+  let mut headers = HashMap::new();
+  opentelemetry::global::get_text_map_propagator(|propagator| {
+    propagator.inject_context(&context, &mut headers)
+  });
+
+  headers
+}
+
+/// An ID correlating what a client sees with the logs and traces of `span`: the ID of its trace
+/// when it is exported, and otherwise an ID unique to this process.
+pub fn correlation_id(span: &Span) -> String {
+  static NEXT: AtomicU64 = AtomicU64::new(1);
+
+  let context = span.context().span().span_context().clone();
+  if context.is_valid() {
+    return context.trace_id().to_string();
+  }
+
+  format!(
+    "{:x}-{:x}",
+    std::process::id(),
+    NEXT.fetch_add(1, Ordering::Relaxed)
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_log_formats() {
+    assert_eq!("json".parse::<LogFormat>().unwrap(), LogFormat::Json);
+    assert_eq!("console".parse::<LogFormat>().unwrap(), LogFormat::Console);
+    assert!("logfmt".parse::<LogFormat>().is_err());
+  }
+
+  #[test]
+  fn parses_headers_like_the_otlp_exporters() {
+    let headers = parse_headers("api-key=secret, x-tenant = a=b,").unwrap();
+    assert_eq!(headers.len(), 2);
+    assert_eq!(headers["api-key"], "secret");
+    assert_eq!(headers["x-tenant"], "a=b");
+
+    assert!(parse_headers("").unwrap().is_empty());
+    assert!(parse_headers("api-key").is_err());
+    assert!(parse_headers("=secret").is_err());
+  }
+}
@@ -0,0 +1,249 @@
+use eyre::{Result, WrapErr};
+use fluxcd_api_image_reflector::{ImagePolicy, ImageRepository};
+use fluxcd_api_notification_alert::{Alert, Provider};
+use fluxcd_api_notification_receiver::Receiver;
+use fluxcd_api_source_bucket::Bucket;
+use fluxcd_api_source_dns_records::DnsRecords;
+use fluxcd_api_source_github_keys::{
+  ClusterGitHubUserSshKeys, GitHubTeamSshKeys, GitHubUserSshKeys,
+};
+use fluxcd_api_source_http_endpoint::HttpEndpoint;
+use fluxcd_utils_cap::ControllerStatus;
+use fluxcd_utils_cops::schema::make_structural;
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use kube::CustomResourceExt;
+use std::{
+  collections::BTreeSet,
+  fmt, fs,
+  path::{Path, PathBuf},
+};
+
+/// Directory the golden files are kept in.
+pub const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/golden");
+
+/// Command updating the golden files, for messages about drift.
+pub const BLESS_COMMAND: &str = "cargo run -p fluxcd-crd-golden -- --bless";
+
+/// Every CRD of the API crates, with the schemas the controllers install. New kinds have to be
+/// registered here to get a golden file.
+pub fn crds() -> Vec<CustomResourceDefinition> {
+  let mut crds = vec![
+    Alert::crd(),
+    Bucket::crd(),
+    ClusterGitHubUserSshKeys::crd(),
+    ControllerStatus::crd(),
+    DnsRecords::crd(),
+    GitHubTeamSshKeys::crd(),
+    GitHubUserSshKeys::crd(),
+    HttpEndpoint::crd(),
+    ImagePolicy::crd(),
+    ImageRepository::crd(),
+    Provider::crd(),
+    Receiver::crd(),
+  ];
+
+  for crd in &mut crds {
+    make_structural(crd);
+  }
+
+  crds
+}
+
+/// The name of the golden file of `crd`, like `buckets.source.fluxcd.yolodev.io.yaml`.
+fn file_name(crd: &CustomResourceDefinition) -> String {
+  let name = crd.metadata.name.as_deref().unwrap_or_default();
+  format!("{name}.yaml")
+}
+
+fn render(crd: &CustomResourceDefinition) -> Result<String> {
+  Ok(serde_yaml::to_string(crd)?)
+}
+
+/// The golden files in `dir`.
+fn goldens(dir: &Path) -> Result<BTreeSet<String>> {
+  if !dir.exists() {
+    return Ok(BTreeSet::new());
+  }
+
+  let mut names = BTreeSet::new();
+  for entry in fs::read_dir(dir).wrap_err_with(|| format!("reading {}", dir.display()))? {
+    let name = entry?.file_name().to_string_lossy().into_owned();
+    if name.ends_with(".yaml") {
+      names.insert(name);
+    }
+  }
+
+  Ok(names)
+}
+
+/// How a CRD differs from its golden file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Drift {
+  /// The CRD has no golden file, as its kind is new.
+  Missing { file: String },
+
+  /// The schema of the CRD changed, first at `line` of the golden file.
+  Changed {
+    file: String,
+    line: usize,
+    expected: String,
+    actual: String,
+  },
+
+  /// The golden file has no CRD anymore, as its kind was removed.
+  Stale { file: String },
+}
+
+impl fmt::Display for Drift {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Drift::Missing { file } => write!(f, "{file}: no golden file"),
+      Drift::Changed {
+        file,
+        line,
+        expected,
+        actual,
+      } => write!(f, "{file}:{line}: changed\n  - {expected}\n  + {actual}"),
+      Drift::Stale { file } => write!(f, "{file}: no CRD matches the golden file"),
+    }
+  }
+}
+
+/// The first line at which `actual` differs from `expected`, with both lines, or `None` if they
+/// are the same.
+fn first_difference(expected: &str, actual: &str) -> Option<(usize, String, String)> {
+  let mut expected = expected.lines();
+  let mut actual = actual.lines();
+  for line in 1.. {
+    match (expected.next(), actual.next()) {
+      (None, None) => return None,
+      (e, a) if e == a => continue,
+      (e, a) => {
+        let text = |l: Option<&str>| l.unwrap_or("<end of file>").to_string();
+        return Some((line, text(e), text(a)));
+      }
+    }
+  }
+
+  unreachable!()
+}
+
+/// Compares `crds` to their golden files in `dir`.
+pub fn check(dir: &Path, crds: &[CustomResourceDefinition]) -> Result<Vec<Drift>> {
+  let mut stale = goldens(dir)?;
+  let mut drifts = Vec::new();
+  for crd in crds {
+    let file = file_name(crd);
+    stale.remove(&file);
+
+    let path = dir.join(&file);
+    if !path.exists() {
+      drifts.push(Drift::Missing { file });
+      continue;
+    }
+
+    let expected =
+      fs::read_to_string(&path).wrap_err_with(|| format!("reading {}", path.display()))?;
+    if let Some((line, expected, actual)) = first_difference(&expected, &render(crd)?) {
+      drifts.push(Drift::Changed {
+        file,
+        line,
+        expected,
+        actual,
+      });
+    }
+  }
+
+  drifts.extend(stale.into_iter().map(|file| Drift::Stale { file }));
+  Ok(drifts)
+}
+
+/// Writes the golden files of `crds` to `dir`, and removes those of CRDs which are gone. Returns
+/// the files written or removed.
+pub fn bless(dir: &Path, crds: &[CustomResourceDefinition]) -> Result<Vec<PathBuf>> {
+  fs::create_dir_all(dir).wrap_err_with(|| format!("creating {}", dir.display()))?;
+
+  let mut updated = Vec::new();
+  for drift in check(dir, crds)? {
+    match drift {
+      Drift::Missing { file } | Drift::Changed { file, .. } => {
+        let crd = crds.iter().find(|crd| file_name(crd) == file);
+        let path = dir.join(file);
+        let yaml = render(crd.expect("drifts are of the given CRDs"))?;
+        fs::write(&path, yaml).wrap_err_with(|| format!("writing {}", path.display()))?;
+        updated.push(path);
+      }
+      Drift::Stale { file } => {
+        let path = dir.join(file);
+        fs::remove_file(&path).wrap_err_with(|| format!("removing {}", path.display()))?;
+        updated.push(path);
+      }
+    }
+  }
+
+  Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A directory of its own for the test `name`.
+  fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("fluxcd-crd-golden-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+  }
+
+  #[test]
+  fn blessed_goldens_have_no_drift() {
+    let dir = scratch_dir("blessed");
+    let crds = [Bucket::crd(), DnsRecords::crd()];
+
+    assert_eq!(bless(&dir, &crds).unwrap().len(), 2);
+    assert_eq!(check(&dir, &crds).unwrap(), []);
+    assert_eq!(bless(&dir, &crds).unwrap(), Vec::<PathBuf>::new());
+
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn reports_drift() {
+    let dir = scratch_dir("drift");
+    let crds = [Bucket::crd(), DnsRecords::crd()];
+    bless(&dir, &crds).unwrap();
+
+    let bucket = dir.join(file_name(&crds[0]));
+    let golden = fs::read_to_string(&bucket).unwrap();
+    fs::write(&bucket, golden.replacen("Bucket", "Pail", 1)).unwrap();
+
+    let drifts = check(&dir, &[Bucket::crd(), HttpEndpoint::crd()]).unwrap();
+    assert!(matches!(&drifts[0], Drift::Changed { expected, .. } if expected.contains("Pail")));
+    assert_eq!(
+      drifts[1..],
+      [
+        Drift::Missing {
+          file: file_name(&HttpEndpoint::crd())
+        },
+        Drift::Stale {
+          file: file_name(&DnsRecords::crd())
+        },
+      ]
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn first_differences() {
+    assert_eq!(first_difference("a\nb\n", "a\nb\n"), None);
+    assert_eq!(
+      first_difference("a\nb\n", "a\nc\n"),
+      Some((2, "b".into(), "c".into()))
+    );
+    assert_eq!(
+      first_difference("a\n", "a\nb\n"),
+      Some((2, "<end of file>".into(), "b".into()))
+    );
+  }
+}
@@ -0,0 +1,44 @@
+use clap::Parser;
+use eyre::{bail, Result};
+use fluxcd_crd_golden::{bless, check, crds, BLESS_COMMAND, GOLDEN_DIR};
+use std::path::PathBuf;
+
+/// Compare the CRDs of the API crates to their golden files, to catch schema changes.
+#[derive(Parser)]
+#[clap(version)]
+struct Args {
+  /// Update the golden files to the current CRDs, after reviewing their changes.
+  #[clap(long)]
+  bless: bool,
+
+  /// Directory of the golden files.
+  #[clap(long, default_value = GOLDEN_DIR)]
+  dir: PathBuf,
+}
+
+fn main() -> Result<()> {
+  let args = Args::parse();
+  let crds = crds();
+
+  if args.bless {
+    for path in bless(&args.dir, &crds)? {
+      println!("updated {}", path.display());
+    }
+
+    return Ok(());
+  }
+
+  let drifts = check(&args.dir, &crds)?;
+  for drift in &drifts {
+    eprintln!("{drift}");
+  }
+  if !drifts.is_empty() {
+    bail!(
+      "{} CRD(s) differ from their golden files, run `{BLESS_COMMAND}` if the changes are intended",
+      drifts.len()
+    );
+  }
+
+  println!("{} CRD(s) match their golden files", crds.len());
+  Ok(())
+}
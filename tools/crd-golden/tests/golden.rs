@@ -0,0 +1,17 @@
+use fluxcd_crd_golden::{check, crds, BLESS_COMMAND, GOLDEN_DIR};
+use std::path::Path;
+
+#[test]
+fn crds_match_goldens() {
+  let drifts = check(Path::new(GOLDEN_DIR), &crds()).unwrap();
+  let report = drifts
+    .iter()
+    .map(ToString::to_string)
+    .collect::<Vec<_>>()
+    .join("\n");
+
+  assert!(
+    drifts.is_empty(),
+    "CRDs differ from their golden files, run `{BLESS_COMMAND}` if the changes are intended:\n{report}"
+  );
+}
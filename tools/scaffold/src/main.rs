@@ -0,0 +1,249 @@
+use clap::Parser;
+use eyre::{bail, Context, Result};
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
+
+const API_CARGO: &str = include_str!("../templates/api/Cargo.toml.tmpl");
+const API_LIB: &str = include_str!("../templates/api/lib.rs.tmpl");
+const CONTROLLER_CARGO: &str = include_str!("../templates/controller/Cargo.toml.tmpl");
+const CONTROLLER_MAIN: &str = include_str!("../templates/controller/main.rs.tmpl");
+
+/// Generate the API and controller crates for a new kind, and add them to the workspace.
+#[derive(Parser)]
+#[clap(version)]
+struct Args {
+  /// API group of the kind, e.g. `source`. Resources are served from `<group>.fluxcd.yolodev.io`.
+  group: String,
+
+  /// Directory name of the crates, e.g. `github-keys`.
+  name: String,
+
+  /// Kind of the resource, e.g. `GitHubUserSshKeys`.
+  #[clap(long)]
+  kind: String,
+
+  /// Root of the workspace.
+  #[clap(long, default_value = ".")]
+  root: PathBuf,
+}
+
+struct Scaffold {
+  group: String,
+  name: String,
+  kind: String,
+}
+
+impl Scaffold {
+  fn new(args: &Args) -> Result<Self> {
+    let is_kebab = |s: &str| {
+      !s.is_empty()
+        && !s.starts_with('-')
+        && !s.ends_with('-')
+        && s
+          .chars()
+          .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    };
+
+    if !is_kebab(&args.group) {
+      bail!("group '{}' must be lowercase kebab-case", args.group);
+    }
+
+    if !is_kebab(&args.name) {
+      bail!("name '{}' must be lowercase kebab-case", args.name);
+    }
+
+    if !args.kind.starts_with(|c: char| c.is_ascii_uppercase())
+      || !args.kind.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+      bail!("kind '{}' must be PascalCase", args.kind);
+    }
+
+    Ok(Self {
+      group: args.group.clone(),
+      name: args.name.clone(),
+      kind: args.kind.clone(),
+    })
+  }
+
+  fn api_crate(&self) -> String {
+    format!("fluxcd-api-{}-{}", self.group, self.name)
+  }
+
+  fn controller_crate(&self) -> String {
+    format!("fluxcd-{}-controller-{}", self.group, self.name)
+  }
+
+  fn api_dir(&self) -> String {
+    format!("api/{}/{}", self.group, self.name)
+  }
+
+  fn controller_dir(&self) -> String {
+    format!("controllers/{}/{}", self.group, self.name)
+  }
+
+  fn render(&self, template: &str) -> String {
+    template
+      .replace("{{api_crate_ident}}", &self.api_crate().replace('-', "_"))
+      .replace("{{api_crate}}", &self.api_crate())
+      .replace("{{controller_crate}}", &self.controller_crate())
+      .replace("{{group}}", &self.group)
+      .replace("{{name}}", &self.name)
+      .replace("{{kind}}", &self.kind)
+  }
+
+  fn write(&self, root: &Path, path: String, template: &str) -> Result<()> {
+    let path = root.join(path);
+    if path.exists() {
+      bail!("{} already exists", path.display());
+    }
+
+    if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir).wrap_err_with(|| format!("creating {}", dir.display()))?;
+    }
+
+    fs::write(&path, self.render(template))
+      .wrap_err_with(|| format!("writing {}", path.display()))?;
+    println!("created {}", path.display());
+    Ok(())
+  }
+}
+
+/// Adds `member` to the workspace members listed under the `# <section>` comment, before the first
+/// member sorting after it. The manifest is returned as is if `member` is already listed.
+fn add_member(manifest: &str, section: &str, member: &str) -> Result<String> {
+  let mut lines = manifest.lines().collect::<Vec<_>>();
+  let header = format!("# {section}");
+  let start = match lines.iter().position(|l| l.trim() == header) {
+    Some(start) => start,
+    None => bail!("workspace manifest has no '{header}' section"),
+  };
+
+  let end = lines[start + 1..]
+    .iter()
+    .position(|l| !l.trim_start().starts_with('"'))
+    .map_or(lines.len(), |i| start + 1 + i);
+
+  let entry = format!("  \"{member}\",");
+  let members = &lines[start + 1..end];
+  if members.iter().any(|l| l.trim() == entry.trim()) {
+    return Ok(manifest.to_string());
+  }
+
+  let at = members
+    .iter()
+    .position(|l| l.trim() > entry.trim())
+    .map_or(end, |i| start + 1 + i);
+  lines.insert(at, &entry);
+
+  let mut result = lines.join("\n");
+  result.push('\n');
+  Ok(result)
+}
+
+fn main() -> Result<()> {
+  let args = Args::parse();
+  let scaffold = Scaffold::new(&args)?;
+  let root = &args.root;
+
+  let manifest_path = root.join("Cargo.toml");
+  let manifest = fs::read_to_string(&manifest_path)
+    .wrap_err_with(|| format!("reading {}", manifest_path.display()))?;
+
+  let manifest = add_member(&manifest, "CRD types", &scaffold.api_dir())?;
+  let manifest = add_member(&manifest, "Controllers", &scaffold.controller_dir())?;
+
+  let api_dir = scaffold.api_dir();
+  let controller_dir = scaffold.controller_dir();
+  scaffold.write(root, format!("{api_dir}/Cargo.toml"), API_CARGO)?;
+  scaffold.write(root, format!("{api_dir}/src/lib.rs"), API_LIB)?;
+  scaffold.write(
+    root,
+    format!("{controller_dir}/Cargo.toml"),
+    CONTROLLER_CARGO,
+  )?;
+  scaffold.write(
+    root,
+    format!("{controller_dir}/src/main.rs"),
+    CONTROLLER_MAIN,
+  )?;
+
+  fs::write(&manifest_path, manifest)
+    .wrap_err_with(|| format!("writing {}", manifest_path.display()))?;
+  println!("added {api_dir} and {controller_dir} to the workspace");
+  println!(
+    "register the kind in tools/crd-golden, then run `cargo run -p fluxcd-crd-golden -- --bless`"
+  );
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const MANIFEST: &str = r#"[workspace]
+members = [
+  # CRD types
+  "api/source/dns-records",
+  "api/source/http-endpoint",
+
+  # Controllers
+  "controllers/source/dns-records",
+]
+"#;
+
+  fn args(group: &str, name: &str, kind: &str) -> Args {
+    Args {
+      group: group.into(),
+      name: name.into(),
+      kind: kind.into(),
+      root: PathBuf::from("."),
+    }
+  }
+
+  #[test]
+  fn rejects_invalid_names() {
+    assert!(Scaffold::new(&args("source", "github-keys", "GitHubUserSshKeys")).is_ok());
+
+    for (group, name, kind) in [
+      ("Source", "github-keys", "GitHubUserSshKeys"),
+      ("source", "", "GitHubUserSshKeys"),
+      ("source", "-keys", "GitHubUserSshKeys"),
+      ("source", "github_keys", "GitHubUserSshKeys"),
+      ("source", "github-keys", "gitHubUserSshKeys"),
+      ("source", "github-keys", "GitHub-Keys"),
+    ] {
+      assert!(
+        Scaffold::new(&args(group, name, kind)).is_err(),
+        "{group} {name} {kind}"
+      );
+    }
+  }
+
+  #[test]
+  fn adds_members_sorted() {
+    let manifest = add_member(MANIFEST, "CRD types", "api/source/github-keys").unwrap();
+    let manifest = add_member(&manifest, "Controllers", "controllers/source/github-keys").unwrap();
+    assert_eq!(
+      manifest,
+      r#"[workspace]
+members = [
+  # CRD types
+  "api/source/dns-records",
+  "api/source/github-keys",
+  "api/source/http-endpoint",
+
+  # Controllers
+  "controllers/source/dns-records",
+  "controllers/source/github-keys",
+]
+"#
+    );
+
+    let again = add_member(&manifest, "CRD types", "api/source/github-keys").unwrap();
+    assert_eq!(again, manifest);
+    assert!(add_member(MANIFEST, "Tools", "tools/scaffold").is_err());
+  }
+}
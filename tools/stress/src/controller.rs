@@ -0,0 +1,184 @@
+use async_trait::async_trait;
+use eyre::Result;
+use fluxcd_meta::{set_condition, Condition as MetaCondition, Reason};
+use fluxcd_utils_cap::{backoff::Backoff, context::ReconcileCtx, metrics, Controller};
+use k8s_openapi::{
+  apimachinery::pkg::apis::meta::v1::{Condition, Time},
+  chrono::Utc,
+};
+use kube::{
+  api::{Patch, PatchParams},
+  runtime::controller::ReconcilerAction,
+  Api, CustomResource, ResourceExt,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+  collections::HashMap,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+  },
+  time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+
+/// A synthetic resource, which does nothing but take some time to reconcile.
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[kube(
+  group = "stress.fluxcd.yolodev.io",
+  version = "v1alpha1",
+  kind = "StressObject",
+  status = "StressObjectStatus",
+  namespaced
+)]
+#[serde(rename_all = "camelCase")]
+pub struct StressObjectSpec {
+  /// Time each reconcile spends working, in milliseconds.
+  #[serde(default)]
+  pub work_ms: u64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StressObjectStatus {
+  #[serde(skip_serializing_if = "Vec::is_empty", default)]
+  pub conditions: Vec<Condition>,
+}
+
+/// Tracks when the resources of a run were created, and how long it took until they were first
+/// reconciled.
+pub struct Tracker {
+  expected: usize,
+  created: Mutex<HashMap<String, Instant>>,
+  latencies: Mutex<Vec<Duration>>,
+  reconciles: AtomicU64,
+  done: Notify,
+}
+
+impl Tracker {
+  pub fn new(expected: usize) -> Self {
+    Self {
+      expected,
+      created: Mutex::new(HashMap::new()),
+      latencies: Mutex::new(Vec::with_capacity(expected)),
+      reconciles: AtomicU64::new(0),
+      done: Notify::new(),
+    }
+  }
+
+  /// Marks `name` as created now. Called right before the create request is sent, so that a
+  /// reconcile can never observe the resource before it is tracked.
+  pub fn created(&self, name: &str) {
+    self
+      .created
+      .lock()
+      .unwrap()
+      .insert(name.to_string(), Instant::now());
+  }
+
+  fn reconciled(&self, name: &str) {
+    self.reconciles.fetch_add(1, Ordering::Relaxed);
+    let created = match self.created.lock().unwrap().remove(name) {
+      Some(created) => created,
+      // reconciled before, or not part of this run
+      None => return,
+    };
+
+    let mut latencies = self.latencies.lock().unwrap();
+    latencies.push(created.elapsed());
+    if latencies.len() == self.expected {
+      self.done.notify_one();
+    }
+  }
+
+  /// Waits until every expected resource has been reconciled at least once.
+  pub async fn wait(&self) {
+    while self.latencies.lock().unwrap().len() < self.expected {
+      self.done.notified().await;
+    }
+  }
+
+  /// The time from creation to the first reconcile, of every resource reconciled so far.
+  pub fn latencies(&self) -> Vec<Duration> {
+    self.latencies.lock().unwrap().clone()
+  }
+
+  /// The number of reconciles, including repeated reconciles of the same resource.
+  pub fn reconciles(&self) -> u64 {
+    self.reconciles.load(Ordering::Relaxed)
+  }
+}
+
+pub struct StressController {
+  metrics: metrics::Recorder,
+  tracker: Arc<Tracker>,
+}
+
+impl StressController {
+  pub fn new(tracker: Arc<Tracker>) -> Result<Self> {
+    Ok(Self {
+      metrics: metrics::Recorder::new()?,
+      tracker,
+    })
+  }
+}
+
+#[async_trait]
+impl Controller<StressObject> for StressController {
+  async fn reconcile(
+    self: Arc<Self>,
+    resource: Arc<StressObject>,
+    ctx: ReconcileCtx,
+  ) -> Result<ReconcilerAction> {
+    if resource.spec.work_ms > 0 {
+      tokio::time::sleep(Duration::from_millis(resource.spec.work_ms)).await;
+    }
+
+    let mut conditions = resource
+      .status
+      .as_ref()
+      .map(|s| s.conditions.clone())
+      .unwrap_or_default();
+    set_condition(
+      &mut conditions,
+      Condition {
+        type_: MetaCondition::Ready.to_string(),
+        status: "True".into(),
+        reason: Reason::Succeeded.to_string(),
+        message: String::new(),
+        observed_generation: resource.metadata.generation,
+        last_transition_time: Time(Utc::now()),
+      },
+    );
+
+    let status = json!({
+      "status": {
+        "conditions": conditions,
+      }
+    });
+    let namespace = resource.namespace().unwrap_or_default();
+    Api::<StressObject>::namespaced(ctx.client().clone(), &namespace)
+      .patch_status(
+        &resource.name(),
+        &PatchParams::default(),
+        &Patch::Merge(&status),
+      )
+      .await?;
+
+    self.tracker.reconciled(&resource.name());
+    Ok(ReconcilerAction {
+      requeue_after: None,
+    })
+  }
+
+  fn backoff() -> Backoff {
+    // a failed reconcile is retried quickly, so it does not stall the measurement
+    Backoff::fixed(Duration::from_secs(1))
+  }
+
+  fn metrics(&self) -> &metrics::Recorder {
+    &self.metrics
+  }
+}
@@ -0,0 +1,194 @@
+mod controller;
+mod report;
+
+use clap::Parser;
+use controller::{StressController, StressObject, StressObjectSpec, Tracker};
+use eyre::{bail, Result};
+use fluxcd_utils_cap::{Controller, ControllerApp};
+use futures::{stream, StreamExt};
+use k8s_openapi::{
+  api::core::v1::Namespace,
+  apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition, chrono::Utc,
+};
+use kube::{
+  api::{DeleteParams, ListParams, Patch, PatchParams, PostParams},
+  runtime::wait::{await_condition, conditions},
+  Api, Client, ResourceExt,
+};
+use report::Report;
+use serde_json::json;
+use std::{
+  sync::Arc,
+  time::{Duration, Instant},
+};
+use tokio::{runtime::Runtime, sync::oneshot, time};
+use tracing::warn;
+
+const CRATE_NAME: &str = env!("CARGO_PKG_NAME");
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Create many synthetic resources in a cluster (e.g. a kind cluster), reconcile them with an
+/// in-process controller running on the controller runtime, and report the reconcile throughput
+/// and latency, and the API requests it took.
+#[derive(Parser)]
+#[clap(version)]
+struct Args {
+  /// Number of resources to create.
+  #[clap(short = 'n', long, default_value = "1000")]
+  objects: usize,
+
+  /// Namespace the resources are created in, which is created if it does not exist.
+  #[clap(long, default_value = "fluxcd-stress")]
+  namespace: String,
+
+  /// Time each reconcile spends working, in milliseconds.
+  #[clap(long, default_value = "0")]
+  work_ms: u64,
+
+  /// Number of resources created concurrently.
+  #[clap(long, default_value = "32")]
+  concurrency: usize,
+
+  /// How long to wait for all resources to be reconciled, in seconds.
+  #[clap(long, default_value = "600")]
+  timeout: u64,
+
+  /// Keep the resources once done, rather than deleting them.
+  #[clap(long)]
+  keep: bool,
+}
+
+async fn install_crd(client: &Client) -> Result<()> {
+  let crd = <StressController as Controller<StressObject>>::crd();
+  let name = crd.name();
+  let api = Api::<CustomResourceDefinition>::all(client.clone());
+  api
+    .patch(
+      &name,
+      &PatchParams::apply(CRATE_NAME).force(),
+      &Patch::Apply(&crd),
+    )
+    .await?;
+
+  let established = await_condition(api, &name, conditions::is_crd_established());
+  match time::timeout(Duration::from_secs(30), established).await {
+    Ok(result) => result?,
+    Err(_) => bail!("CRD {name} was not established within 30s"),
+  };
+
+  Ok(())
+}
+
+async fn create_namespace(client: &Client, name: &str) -> Result<()> {
+  let namespace = json!({
+    "apiVersion": "v1",
+    "kind": "Namespace",
+    "metadata": {
+      "name": name,
+    }
+  });
+  Api::<Namespace>::all(client.clone())
+    .patch(
+      name,
+      &PatchParams::apply(CRATE_NAME),
+      &Patch::Apply(&namespace),
+    )
+    .await?;
+
+  Ok(())
+}
+
+/// Creates the resources and waits until they have all been reconciled, or the timeout passes.
+async fn drive(api: &Api<StressObject>, args: &Args, tracker: &Tracker) -> Result<Report> {
+  // names are unique per run, so objects left over by an earlier run are not mistaken for ours
+  let run = Utc::now().timestamp();
+  let start = Instant::now();
+  let failed = stream::iter(0..args.objects)
+    .map(|index| {
+      let name = format!("stress-{run}-{index}");
+      let object = StressObject::new(
+        &name,
+        StressObjectSpec {
+          work_ms: args.work_ms,
+        },
+      );
+
+      async move {
+        tracker.created(&name);
+        api.create(&PostParams::default(), &object).await
+      }
+    })
+    .buffer_unordered(args.concurrency.max(1))
+    .filter_map(|result| async move { result.err() })
+    .inspect(|error| warn!(%error, "failed to create resource"))
+    .count()
+    .await;
+  let created_in = start.elapsed();
+  if failed > 0 {
+    bail!("failed to create {failed} resource(s)");
+  }
+
+  let timeout = Duration::from_secs(args.timeout).saturating_sub(created_in);
+  if time::timeout(timeout, tracker.wait()).await.is_err() {
+    warn!("timed out waiting for the resources to be reconciled");
+  }
+
+  Ok(Report::new(
+    args.objects,
+    tracker.reconciles(),
+    created_in,
+    start.elapsed(),
+    tracker.latencies(),
+  ))
+}
+
+async fn run(args: Args) -> Result<()> {
+  // the requests of the test itself go through a plain client, so only those made by the
+  // controller are counted in the report
+  let client = Client::try_default().await?;
+  install_crd(&client).await?;
+  create_namespace(&client, &args.namespace).await?;
+
+  let api = Api::<StressObject>::namespaced(client, &args.namespace);
+  let tracker = Arc::new(Tracker::new(args.objects));
+  let controller = StressController::new(tracker.clone())?;
+  let (stop, stopped) = oneshot::channel::<()>();
+
+  let controllers = ControllerApp::run_until(
+    CRATE_NAME,
+    CRATE_VERSION,
+    move |app| Ok(app.controller(controller)),
+    async move {
+      let _ = stopped.await;
+    },
+  );
+  let driver = async {
+    let report = drive(&api, &args, &tracker).await;
+    let _ = stop.send(());
+    report
+  };
+
+  let (ran, report) = futures::join!(controllers, driver);
+  ran?;
+  report?.print();
+
+  if !args.keep {
+    api
+      .delete_collection(&DeleteParams::default(), &ListParams::default())
+      .await?;
+  }
+
+  Ok(())
+}
+
+fn main() -> Result<()> {
+  let args = Args::parse();
+  let rt = Runtime::new()?;
+  rt.block_on(async {
+    fluxcd_utils_telemetry::setup("stress", &Default::default(), &Default::default())?;
+    let result = run(args).await;
+    fluxcd_utils_telemetry::teardown();
+
+    result
+  })
+}
@@ -0,0 +1,131 @@
+use std::{collections::BTreeMap, time::Duration};
+
+/// Name of the counter of the requests the controllers made to the Kubernetes API.
+const API_REQUESTS_METRIC: &str = "gotk_kube_api_requests_total";
+
+/// The results of a stress run.
+pub struct Report {
+  pub objects: usize,
+  pub reconciled: usize,
+  pub reconciles: u64,
+  /// Time it took to create all objects.
+  pub created_in: Duration,
+  /// Time from creating the first object until the last one was reconciled.
+  pub elapsed: Duration,
+  /// The time from creation to the first reconcile of every reconciled object, sorted.
+  pub latencies: Vec<Duration>,
+  /// The number of API requests made by the controllers, by verb.
+  pub api_requests: BTreeMap<String, u64>,
+}
+
+impl Report {
+  pub fn new(
+    objects: usize,
+    reconciles: u64,
+    created_in: Duration,
+    elapsed: Duration,
+    mut latencies: Vec<Duration>,
+  ) -> Self {
+    latencies.sort();
+    Self {
+      objects,
+      reconciled: latencies.len(),
+      reconciles,
+      created_in,
+      elapsed,
+      latencies,
+      api_requests: api_requests(),
+    }
+  }
+
+  /// The latency below which `percentile` percent of the reconciled objects were reconciled.
+  pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+    percentile_of(&self.latencies, percentile)
+  }
+
+  pub fn print(&self) {
+    let seconds = self.elapsed.as_secs_f64();
+    println!(
+      "objects:      {} ({} reconciled)",
+      self.objects, self.reconciled
+    );
+    println!("created in:   {:.2}s", self.created_in.as_secs_f64());
+    println!("elapsed:      {seconds:.2}s");
+    println!(
+      "throughput:   {:.1} objects/s",
+      self.reconciled as f64 / seconds.max(f64::EPSILON)
+    );
+    println!("reconciles:   {}", self.reconciles);
+
+    println!("latency:");
+    for (label, percentile) in [("p50", 50.0), ("p90", 90.0), ("p99", 99.0), ("max", 100.0)] {
+      match self.percentile(percentile) {
+        Some(latency) => println!("  {label}:        {:.1}ms", latency.as_secs_f64() * 1000.0),
+        None => println!("  {label}:        -"),
+      }
+    }
+
+    let total = self.api_requests.values().sum::<u64>();
+    println!("api requests: {total}");
+    for (verb, count) in &self.api_requests {
+      println!("  {verb:<11} {count}");
+    }
+  }
+}
+
+/// Nearest-rank percentile of `sorted`.
+fn percentile_of(sorted: &[Duration], percentile: f64) -> Option<Duration> {
+  if sorted.is_empty() {
+    return None;
+  }
+
+  let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+  Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+/// Sums the API request counter of the controllers in the default registry by verb.
+fn api_requests() -> BTreeMap<String, u64> {
+  let mut requests = BTreeMap::new();
+  let families = prometheus::gather();
+  let family = families
+    .iter()
+    .find(|f| f.get_name() == API_REQUESTS_METRIC);
+  for metric in family.iter().flat_map(|f| f.get_metric()) {
+    let verb = metric
+      .get_label()
+      .iter()
+      .find(|label| label.get_name() == "verb")
+      .map(|label| label.get_value().to_string())
+      .unwrap_or_default();
+    *requests.entry(verb).or_default() += metric.get_counter().get_value() as u64;
+  }
+
+  requests
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn nearest_rank_percentiles() {
+    let latencies = (1..=10).map(Duration::from_millis).collect::<Vec<_>>();
+    assert_eq!(
+      percentile_of(&latencies, 50.0),
+      Some(Duration::from_millis(5))
+    );
+    assert_eq!(
+      percentile_of(&latencies, 90.0),
+      Some(Duration::from_millis(9))
+    );
+    assert_eq!(
+      percentile_of(&latencies, 99.0),
+      Some(Duration::from_millis(10))
+    );
+    assert_eq!(
+      percentile_of(&latencies, 0.0),
+      Some(Duration::from_millis(1))
+    );
+    assert_eq!(percentile_of(&[], 50.0), None);
+  }
+}